@@ -24,10 +24,9 @@ pub fn shared_config(mode: Mode) -> SharedConfig {
     SharedConfig {
         client_send_interval: Duration::default(),
         server_send_interval: Duration::from_secs_f64(1.0 / 32.0),
+        send_timing: SendTiming::Frame,
         // server_send_interval: Duration::from_millis(500),
-        tick: TickConfig {
-            tick_duration: Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ),
-        },
+        tick: TickConfig::new(Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ)),
         mode,
     }
 }
@@ -160,6 +160,7 @@ fn client_app(settings: Settings, net_config: client::NetConfig) -> App {
         replication: ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -201,6 +202,7 @@ fn server_app(settings: Settings, extra_transport_configs: Vec<TransportConfig>)
         replication: lightyear::server::replication::ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -241,6 +243,7 @@ fn combined_app(
         replication: lightyear::server::replication::ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -260,6 +263,7 @@ fn combined_app(
         replication: ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
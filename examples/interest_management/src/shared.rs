@@ -17,11 +17,12 @@ pub fn shared_config(mode: Mode) -> SharedConfig {
         client_send_interval: Duration::default(),
         // server_send_interval: Duration::default(),
         server_send_interval: Duration::from_millis(40),
-        tick: TickConfig {
+        send_timing: SendTiming::Frame,
+        tick: TickConfig::new(
             // right now, we NEED the tick_duration to be smaller than the send_interval
             // (otherwise we can send multiple packets for the same tick at different frames)
-            tick_duration: Duration::from_secs_f64(1.0 / 64.0),
-        },
+            Duration::from_secs_f64(1.0 / 64.0),
+        ),
         mode,
     }
 }
@@ -166,6 +166,7 @@ fn client_app(settings: Settings, net_config: client::NetConfig) -> App {
             // enable send because we pre-spawn entities on the client
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -207,6 +208,7 @@ fn server_app(settings: Settings, extra_transport_configs: Vec<TransportConfig>)
         replication: lightyear::server::replication::ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -249,6 +251,7 @@ fn combined_app(
         replication: lightyear::server::replication::ReplicationConfig {
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -276,6 +279,7 @@ fn combined_app(
             // enable send because we pre-spawn entities on the client
             enable_send: true,
             enable_receive: true,
+            ..default()
         },
         ..default()
     };
@@ -126,7 +126,7 @@ pub struct ColorComponent(pub(crate) Color);
 #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BallMarker;
 
-#[component_protocol(protocol = "MyProtocol")]
+#[component_protocol(protocol = "MyProtocol", xpbd_2d)]
 pub enum Components {
     #[protocol(sync(mode = "once"))]
     PlayerId(PlayerId),
@@ -134,32 +134,8 @@ pub enum Components {
     ColorComponent(ColorComponent),
     #[protocol(sync(mode = "once"))]
     BallMarker(BallMarker),
-    // You need to specify how to do interpolation for the component
-    // Normally LinearInterpolation is fine, but it's not possible for xpbd's components
-    // as they do not implement Mul<f32> and Add<Self>
-    // Instead, lightyear already implemented the interpolation for xpbd's components (although you could also implement it yourself)
-    //
-    // Then you can also specify how to correct the component when there is a mispredictions
-    // The default is `InstantCorrector` which just snaps to the corrected value
-    // You can also use `InterpolatedCorrector` which will re-use your interpolation function to
-    // interpolate smoothly from the previously predicted value to the newly corrected value
-    #[protocol(sync(
-        mode = "full",
-        lerp = "PositionLinearInterpolation",
-        corrector = "InterpolatedCorrector"
-    ))]
-    Position(Position),
-    #[protocol(sync(
-        mode = "full",
-        lerp = "RotationLinearInterpolation",
-        corrector = "InterpolatedCorrector"
-    ))]
-    Rotation(Rotation),
-    // NOTE: correction is only needed for components that are visually displayed!
-    #[protocol(sync(mode = "full", lerp = "LinearVelocityLinearInterpolation"))]
-    LinearVelocity(LinearVelocity),
-    #[protocol(sync(mode = "full", lerp = "AngularVelocityLinearInterpolation"))]
-    AngularVelocity(AngularVelocity),
+    // The xpbd components (Position, Rotation, LinearVelocity, AngularVelocity, RigidBody) are added
+    // to the protocol by the `xpbd_2d` attribute, with prediction and interpolation enabled
 }
 
 // Channels
@@ -13,6 +13,7 @@ use lightyear::prelude::client::*;
 use lightyear::prelude::TickManager;
 use lightyear::prelude::*;
 use lightyear::transport::io::IoDiagnosticsPlugin;
+use lightyear::utils::bevy_xpbd_2d::PhysicsRollbackPlugin;
 
 use crate::protocol::*;
 
@@ -26,9 +27,8 @@ pub fn shared_config(mode: Mode) -> SharedConfig {
         client_send_interval: Duration::default(),
         // server_send_interval: Duration::from_secs_f64(1.0 / 32.0),
         server_send_interval: Duration::from_millis(100),
-        tick: TickConfig {
-            tick_duration: Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ),
-        },
+        send_timing: SendTiming::Frame,
+        tick: TickConfig::new(Duration::from_secs_f64(1.0 / FIXED_TIMESTEP_HZ)),
         mode,
     }
 }
@@ -69,9 +69,10 @@ impl Plugin for SharedPlugin {
         app.add_systems(Startup, init);
 
         // physics
-        app.add_plugins(PhysicsPlugins::new(FixedUpdate))
-            .insert_resource(Time::new_with(Physics::fixed_once_hz(FIXED_TIMESTEP_HZ)))
-            .insert_resource(Gravity(Vec2::ZERO));
+        app.add_plugins(PhysicsRollbackPlugin::new(Duration::from_secs_f64(
+            1.0 / FIXED_TIMESTEP_HZ,
+        )))
+        .insert_resource(Gravity(Vec2::ZERO));
         app.configure_sets(
             FixedUpdate,
             (
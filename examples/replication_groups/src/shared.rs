@@ -15,9 +15,8 @@ pub fn shared_config(mode: Mode) -> SharedConfig {
         client_send_interval: Duration::default(),
         // server_send_interval: Duration::from_millis(40),
         server_send_interval: Duration::from_millis(100),
-        tick: TickConfig {
-            tick_duration: Duration::from_secs_f64(1.0 / 64.0),
-        },
+        send_timing: SendTiming::Frame,
+        tick: TickConfig::new(Duration::from_secs_f64(1.0 / 64.0)),
         mode,
     }
 }
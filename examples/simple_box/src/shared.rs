@@ -18,10 +18,9 @@ pub fn shared_config(mode: Mode) -> SharedConfig {
     SharedConfig {
         client_send_interval: Duration::default(),
         server_send_interval: Duration::from_millis(40),
+        send_timing: SendTiming::Frame,
         // server_send_interval: Duration::from_millis(100),
-        tick: TickConfig {
-            tick_duration: Duration::from_secs_f64(1.0 / 64.0),
-        },
+        tick: TickConfig::new(Duration::from_secs_f64(1.0 / 64.0)),
         mode,
     }
 }
@@ -0,0 +1,11 @@
+//! Fuzz the parsing of netcode connect tokens.
+//! Parsing arbitrary bytes should return an error, never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use lightyear::connection::netcode::ConnectToken;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ConnectToken::try_from_bytes(data);
+});
@@ -0,0 +1,14 @@
+//! Fuzz the decoding of the packets received from the remote peer.
+//! Decoding arbitrary bytes should return an error, never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use lightyear::packet::packet::Packet;
+use lightyear::serialize::reader::ReadBuffer;
+use lightyear::serialize::wordbuffer::reader::ReadWordBuffer;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = ReadWordBuffer::start_read(data);
+    let _ = Packet::decode(&mut reader);
+});
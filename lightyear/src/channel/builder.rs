@@ -5,6 +5,7 @@ use bevy::utils::Duration;
 
 use lightyear_macros::ChannelInternal;
 
+use crate::channel::diagnostics::ChannelStats;
 use crate::channel::receivers::ordered_reliable::OrderedReliableReceiver;
 use crate::channel::receivers::sequenced_reliable::SequencedReliableReceiver;
 use crate::channel::receivers::sequenced_unreliable::SequencedUnreliableReceiver;
@@ -25,6 +26,8 @@ pub struct ChannelContainer {
     pub setting: ChannelSettings,
     pub(crate) receiver: ChannelReceiver,
     pub(crate) sender: ChannelSender,
+    /// Bandwidth used by the channel, used for diagnostics
+    pub(crate) stats: ChannelStats,
 }
 
 /// A Channel is an abstraction for a way to send messages over the network
@@ -42,6 +45,7 @@ pub struct ChannelContainer {
 ///     mode: ChannelMode::UnorderedUnreliable,
 ///     direction: ChannelDirection::Bidirectional,
 ///     priority: 1.0,
+///     drop_policy: DropPolicy::Discard,
 /// });
 /// ```
 pub trait Channel: 'static {
@@ -110,6 +114,7 @@ impl ChannelContainer {
             setting: settings_clone,
             receiver,
             sender,
+            stats: ChannelStats::default(),
         }
     }
 }
@@ -121,6 +126,8 @@ pub struct ChannelSettings {
     pub direction: ChannelDirection,
     /// Sets the priority of the channel. The final priority of a message will be `MessagePriority * ChannelPriority`
     pub priority: f32,
+    /// What to do with the messages of this channel that could not be sent because the bandwidth quota was reached
+    pub drop_policy: DropPolicy,
 }
 
 impl Default for ChannelSettings {
@@ -129,10 +136,34 @@ impl Default for ChannelSettings {
             mode: ChannelMode::UnorderedUnreliable,
             direction: ChannelDirection::Bidirectional,
             priority: 1.0,
+            drop_policy: DropPolicy::default(),
         }
     }
 }
 
+/// [`DropPolicy`] specifies what happens to the messages of a channel that don't fit in the bandwidth quota
+/// (see [`PacketConfig`](crate::client::config::PacketConfig)).
+///
+/// By default, messages that could not be sent are discarded. With the other policies, they are kept in a buffer;
+/// the policy decides what to do when new messages are sent on the channel while some older messages are still
+/// waiting in that buffer.
+///
+/// This only applies to unreliable channels: reliable channels will always resend the messages that were
+/// not sent once the resend delay has elapsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DropPolicy {
+    /// Discard the messages that could not be sent
+    #[default]
+    Discard,
+    /// Keep all the messages; they will be sent (in order of priority) as soon as there is enough bandwidth
+    Queue,
+    /// Discard the buffered messages when newer messages are sent on the channel.
+    /// Useful for data where only the latest value matters (cursor positions, etc.)
+    DropOldest,
+    /// Discard the new messages sent on the channel while older messages are still waiting to be sent
+    DropNewest,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 /// ChannelMode specifies how messages are sent and received
 /// See more information [here](http://www.jenkinssoftware.com/raknet/manual/reliabilitytypes.html)
@@ -234,8 +265,19 @@ pub struct PingChannel;
 
 #[derive(ChannelInternal)]
 /// Default channel to send inputs from client to server. This is a Sequenced Unreliable channel.
+/// The messages of this channel are always packed first, even if the bandwidth quota is reached.
 pub struct InputChannel;
 
+#[derive(ChannelInternal)]
+/// Channel used by the server to relay the inputs of a client to the other clients
+/// (see [`InputBroadcastConfig`](crate::server::input::InputBroadcastConfig)). This is an Unordered Unreliable channel.
+pub struct InputBroadcastChannel;
+
+/// Channel used by the server to send control messages to the clients that must be applied in order
+/// (for example the tick duration changes or the time nudges). This is an Ordered Reliable channel.
+#[derive(ChannelInternal)]
+pub struct ServerControlChannel;
+
 /// Default Unordedered Unreliable channel, to send messages as fast as possible without any ordering.
 #[derive(ChannelInternal)]
 pub struct DefaultUnorderedUnreliableChannel;
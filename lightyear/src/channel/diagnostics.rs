@@ -0,0 +1,107 @@
+//! Per-channel bandwidth diagnostics
+use bevy::app::{App, Plugin};
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::{Real, Res, Resource, Time};
+use bevy::utils::HashMap;
+
+use crate::protocol::channel::ChannelRegistry;
+
+/// Number of message bytes sent/received on a channel since the last diagnostics update
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct ChannelStats {
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+}
+
+/// Diagnostic paths of every channel, built once so that they are not formatted every frame
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ChannelDiagnosticPaths {
+    /// The 'KB received per second' and 'KB sent per second' paths of each channel
+    paths: HashMap<String, (DiagnosticPath, DiagnosticPath)>,
+}
+
+/// Registers a 'KB sent per second' and a 'KB received per second' diagnostic for each channel
+/// of the protocol.
+///
+/// The diagnostics are named `channel/<ChannelName>/KB sent per second` and
+/// `channel/<ChannelName>/KB received per second`, so they can be displayed by any existing
+/// diagnostic overlay.
+pub struct ChannelDiagnosticsPlugin {
+    channel_registry: ChannelRegistry,
+}
+
+impl ChannelDiagnosticsPlugin {
+    pub fn new(channel_registry: ChannelRegistry) -> Self {
+        Self { channel_registry }
+    }
+
+    /// Max diagnostic history length.
+    pub const DIAGNOSTIC_HISTORY_LEN: usize = 60;
+
+    /// How many bytes do we receive per second on the channel
+    pub fn bytes_in(channel_name: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!("channel/{channel_name}/KB received per second"))
+    }
+
+    /// How many bytes do we send per second on the channel
+    pub fn bytes_out(channel_name: &str) -> DiagnosticPath {
+        DiagnosticPath::new(format!("channel/{channel_name}/KB sent per second"))
+    }
+
+    /// Add a measurement for every channel, using the stats accumulated since the last update.
+    ///
+    /// `stats` can contain multiple entries for the same channel (for example one per connected
+    /// client on the server); they are summed together. The stats are reset afterwards.
+    /// Channels in `channel_names` that don't have any stats get a measurement of 0.
+    pub(crate) fn update_diagnostics<'a>(
+        channel_names: impl Iterator<Item = &'a str>,
+        stats: impl Iterator<Item = (&'a str, &'a mut ChannelStats)>,
+        paths: &ChannelDiagnosticPaths,
+        time: &Res<Time<Real>>,
+        diagnostics: &mut Diagnostics,
+    ) {
+        let delta_seconds = time.delta_seconds_f64();
+        if delta_seconds == 0.0 {
+            return;
+        }
+        let mut total: HashMap<&str, ChannelStats> = channel_names
+            .map(|name| (name, ChannelStats::default()))
+            .collect();
+        for (name, channel_stats) in stats {
+            let total = total.entry(name).or_default();
+            total.bytes_sent += channel_stats.bytes_sent;
+            total.bytes_received += channel_stats.bytes_received;
+            *channel_stats = ChannelStats::default();
+        }
+        for (name, channel_stats) in total {
+            let Some((bytes_in, bytes_out)) = paths.paths.get(name) else {
+                continue;
+            };
+            diagnostics.add_measurement(bytes_in, || {
+                (channel_stats.bytes_received as f64 / 1000.0) / delta_seconds
+            });
+            diagnostics.add_measurement(bytes_out, || {
+                (channel_stats.bytes_sent as f64 / 1000.0) / delta_seconds
+            });
+        }
+    }
+}
+
+impl Plugin for ChannelDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut paths = ChannelDiagnosticPaths::default();
+        for name in self.channel_registry.names() {
+            let (bytes_in, bytes_out) = (Self::bytes_in(name), Self::bytes_out(name));
+            app.register_diagnostic(
+                Diagnostic::new(bytes_in.clone())
+                    .with_max_history_length(Self::DIAGNOSTIC_HISTORY_LEN),
+            );
+            app.register_diagnostic(
+                Diagnostic::new(bytes_out.clone())
+                    .with_max_history_length(Self::DIAGNOSTIC_HISTORY_LEN),
+            );
+            paths.paths.insert(name.to_string(), (bytes_in, bytes_out));
+        }
+        app.insert_resource(paths);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bytes::Bytes;
 use tracing::trace;
 
@@ -32,11 +32,32 @@ impl FragmentReceiver {
         })
     }
 
+    /// Check that a fragment received from the remote peer is valid, without updating any state
+    pub fn validate(&self, fragment: &FragmentData) -> Result<()> {
+        let num_fragments = fragment.num_fragments as usize;
+        if let Some(constructor) = self.fragment_messages.get(&fragment.message_id) {
+            if constructor.num_fragments != num_fragments {
+                bail!(
+                    "fragment of message {:?} has {} fragments instead of {}",
+                    fragment.message_id,
+                    num_fragments,
+                    constructor.num_fragments
+                );
+            }
+        }
+        FragmentConstructor::validate(
+            num_fragments,
+            fragment.fragment_id as usize,
+            fragment.bytes.len(),
+        )
+    }
+
     pub fn receive_fragment(
         &mut self,
         fragment: FragmentData,
         current_time: Option<WrappedTime>,
     ) -> Result<Option<SingleData>> {
+        self.validate(&fragment)?;
         let fragment_message = self
             .fragment_messages
             .entry(fragment.message_id)
@@ -84,16 +105,35 @@ impl FragmentConstructor {
         }
     }
 
+    /// The fragment was sent by the remote peer, check that it is valid
+    fn validate(num_fragments: usize, fragment_index: usize, num_bytes: usize) -> Result<()> {
+        if fragment_index >= num_fragments {
+            bail!(
+                "invalid fragment index {} for a message with {} fragments",
+                fragment_index,
+                num_fragments
+            );
+        }
+        let is_last_fragment = fragment_index == num_fragments - 1;
+        if num_bytes > FRAGMENT_SIZE || (!is_last_fragment && num_bytes != FRAGMENT_SIZE) {
+            bail!(
+                "invalid number of bytes ({}) for fragment {}",
+                num_bytes,
+                fragment_index
+            );
+        }
+        Ok(())
+    }
+
     pub fn receive_fragment(
         &mut self,
         fragment_index: usize,
         bytes: &[u8],
         received_time: Option<WrappedTime>,
     ) -> Result<Option<Bytes>> {
+        Self::validate(self.num_fragments, fragment_index, bytes.len())?;
         self.last_received = received_time;
-
         let is_last_fragment = fragment_index == self.num_fragments - 1;
-        // TODO: check sizes?
 
         if !self.received[fragment_index] {
             self.received[fragment_index] = true;
@@ -145,4 +185,38 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_receiver_invalid_fragment() {
+        let mut receiver = FragmentReceiver::new();
+        let num_bytes = (FRAGMENT_SIZE as f32 * 1.5) as usize;
+        let message_bytes = Bytes::from(vec![1u8; num_bytes]);
+        let mut fragments =
+            FragmentSender::new().build_fragments(MessageId(0), None, message_bytes, 0.0);
+        // the remote peer sent a fragment index that is out of bounds
+        fragments[1].fragment_id = 2;
+
+        assert!(receiver
+            .receive_fragment(fragments[0].clone(), None)
+            .is_ok());
+        assert!(receiver
+            .receive_fragment(fragments[1].clone(), None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_receiver_num_fragments_mismatch() {
+        let mut receiver = FragmentReceiver::new();
+        let num_bytes = (FRAGMENT_SIZE as f32 * 1.5) as usize;
+        let message_bytes = Bytes::from(vec![1u8; num_bytes]);
+        let mut fragments =
+            FragmentSender::new().build_fragments(MessageId(0), None, message_bytes, 0.0);
+        // the remote peer sent fragments of the same message with a different number of fragments
+        fragments[1].num_fragments = 3;
+
+        assert!(receiver
+            .receive_fragment(fragments[0].clone(), None)
+            .is_ok());
+        assert!(receiver.validate(&fragments[1]).is_err());
+    }
 }
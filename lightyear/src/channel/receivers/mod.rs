@@ -30,6 +30,9 @@ pub trait ChannelReceive {
     /// Bookkeeping on the channel
     fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager);
 
+    /// Checks that a message received from the remote peer can be buffered, without updating any state
+    fn validate(&self, message: &MessageContainer) -> anyhow::Result<()>;
+
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()>;
 
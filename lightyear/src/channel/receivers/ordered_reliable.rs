@@ -33,6 +33,16 @@ impl OrderedReliableReceiver {
 impl ChannelReceive for OrderedReliableReceiver {
     fn update(&mut self, _: &TimeManager, _: &TickManager) {}
 
+    fn validate(&self, message: &MessageContainer) -> anyhow::Result<()> {
+        message
+            .message_id()
+            .ok_or_else(|| anyhow!("message id not found"))?;
+        if let MessageContainer::Fragment(fragment) = message {
+            self.fragment_receiver.validate(fragment)?;
+        }
+        Ok(())
+    }
+
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
         let message_id = message
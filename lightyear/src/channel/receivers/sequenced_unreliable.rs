@@ -40,6 +40,16 @@ impl ChannelReceive for SequencedUnreliableReceiver {
             .cleanup(self.current_time - DISCARD_AFTER);
     }
 
+    fn validate(&self, message: &MessageContainer) -> anyhow::Result<()> {
+        message
+            .message_id()
+            .ok_or_else(|| anyhow!("message id not found"))?;
+        if let MessageContainer::Fragment(fragment) = message {
+            self.fragment_receiver.validate(fragment)?;
+        }
+        Ok(())
+    }
+
     /// Queues a received message in an internal buffer
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
         let message_id = message
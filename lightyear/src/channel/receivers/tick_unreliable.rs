@@ -61,6 +61,13 @@ impl ChannelReceive for TickUnreliableReceiver {
             .cleanup(self.current_time - DISCARD_AFTER);
     }
 
+    fn validate(&self, message: &MessageContainer) -> anyhow::Result<()> {
+        if let MessageContainer::Fragment(fragment) = message {
+            self.fragment_receiver.validate(fragment)?;
+        }
+        Ok(())
+    }
+
     /// Queues a received message in an internal buffer
     /// The messages are associated with the corresponding tick
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
@@ -100,9 +107,7 @@ mod tests {
     #[test]
     fn test_tick_unreliable_receiver_internals() -> anyhow::Result<()> {
         let mut receiver = TickUnreliableReceiver::new();
-        let mut tick_manager = TickManager::from_config(TickConfig {
-            tick_duration: Duration::from_millis(10),
-        });
+        let mut tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
         let time_manager = TimeManager::default();
 
         let single1 = SingleData::new(None, Bytes::from("hello"), 1.0);
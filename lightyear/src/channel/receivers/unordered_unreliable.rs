@@ -32,6 +32,13 @@ impl ChannelReceive for UnorderedUnreliableReceiver {
             .cleanup(self.current_time - DISCARD_AFTER);
     }
 
+    fn validate(&self, message: &MessageContainer) -> anyhow::Result<()> {
+        if let MessageContainer::Fragment(fragment) = message {
+            self.fragment_receiver.validate(fragment)?;
+        }
+        Ok(())
+    }
+
     fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
         match message {
             MessageContainer::Single(data) => self.recv_message_buffer.push_back(data),
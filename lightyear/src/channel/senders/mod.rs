@@ -51,6 +51,12 @@ pub trait ChannelSend {
     /// Returns true if there are messages in the buffer that are ready to be sent
     fn has_messages_to_send(&self) -> bool;
 
+    /// Returns true if some messages were not delivered yet.
+    /// For channels without reliability, a message is considered delivered as soon as it is sent
+    fn has_unacked_messages(&self) -> bool {
+        self.has_messages_to_send()
+    }
+
     /// Create a new receiver that will receive a message id when a sent message is acked
     fn subscribe_acks(&mut self) -> Receiver<MessageId>;
 }
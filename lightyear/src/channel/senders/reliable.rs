@@ -265,6 +265,10 @@ impl ChannelSend for ReliableSender {
         !self.single_messages_to_send.is_empty() || !self.fragmented_messages_to_send.is_empty()
     }
 
+    fn has_unacked_messages(&self) -> bool {
+        !self.unacked_messages.is_empty()
+    }
+
     fn subscribe_acks(&mut self) -> Receiver<MessageId> {
         todo!()
     }
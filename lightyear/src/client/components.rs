@@ -5,6 +5,7 @@ use std::fmt::Debug;
 
 use bevy::prelude::{Component, Entity};
 use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::{Message, Tick};
 
@@ -36,6 +37,30 @@ pub trait LerpFn<C> {
     fn lerp(start: &C, other: &C, t: f32) -> C;
 }
 
+/// Implement this trait on your own component types to define how they should be interpolated.
+///
+/// Then select the [`LerpInterpolator`](crate::client::interpolation::LerpInterpolator) in the protocol to use it:
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq)]
+/// pub struct GridPosition(IVec2);
+///
+/// impl Lerp for GridPosition {
+///     fn lerp(&self, other: &Self, t: f32) -> Self {
+///         if t < 0.5 { self.clone() } else { other.clone() }
+///     }
+/// }
+///
+/// #[component_protocol(protocol = "MyProtocol")]
+/// pub enum MyComponentsProtocol {
+///     #[protocol(sync(mode = "full", lerp = "LerpInterpolator"))]
+///     GridPosition(GridPosition),
+/// }
+/// ```
+pub trait Lerp {
+    /// Interpolate between `self` (t = 0.0) and `other` (t = 1.0)
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
 /// Defines how to do interpolation/correction for the component
 pub trait SyncMetadata<C> {
     type Interpolator: LerpFn<C> + 'static;
@@ -44,7 +69,7 @@ pub trait SyncMetadata<C> {
     fn mode() -> ComponentSyncMode;
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
 /// Defines how a predicted or interpolated component will be replicated from confirmed to predicted/interpolated
 ///
 /// We use a single enum instead of 2 separate enums because we want to be able to use the same enum for both predicted and interpolated components
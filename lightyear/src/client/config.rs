@@ -56,6 +56,9 @@ pub struct PacketConfig {
     pub send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// If true, the client disconnects from the server when it receives a packet that contains invalid data.
+    /// Otherwise the invalid packet is simply dropped.
+    pub disconnect_on_invalid_packet: bool,
 }
 
 impl Default for PacketConfig {
@@ -64,6 +67,7 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            disconnect_on_invalid_packet: false,
         }
     }
 }
@@ -84,6 +88,11 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn enable_disconnect_on_invalid_packet(mut self) -> Self {
+        self.disconnect_on_invalid_packet = true;
+        self
+    }
 }
 
 /// The configuration object that lets you create a `ClientPlugin` with the desired settings.
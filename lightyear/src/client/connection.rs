@@ -1,18 +1,23 @@
 //! Specify how a Client sends/receives messages with a Server
+use std::collections::VecDeque;
+
 use anyhow::Result;
 use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::{EntityHashMap, MapEntities};
 use bevy::prelude::{Entity, Local, Resource, World};
 use bevy::reflect::Reflect;
-use bevy::utils::Duration;
+use bevy::utils::{Duration, HashMap};
 use serde::Serialize;
-use tracing::{debug, info, trace, trace_span, warn};
+use tracing::{debug, error, info, trace, trace_span, warn};
 
-use crate::_reexport::{ClientMarker, EntityUpdatesChannel, PingChannel, ReplicationSend};
+use crate::_reexport::{
+    ClientMarker, EntityActionsChannel, EntityUpdatesChannel, PingChannel, ReplicationSend,
+};
 use crate::channel::senders::ChannelSend;
 use crate::client::config::PacketConfig;
 use crate::client::message::ClientMessage;
 use crate::client::sync::SyncConfig;
+use crate::connection::netcode::ConnectToken;
 use crate::inputs::native::input_buffer::InputBuffer;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet::Packet;
@@ -26,10 +31,11 @@ use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
 use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::entity_map::RemoteEntityMap;
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
-use crate::shared::replication::ReplicationMessage;
 use crate::shared::replication::ReplicationMessageData;
+use crate::shared::replication::{decompress_baseline, ReplicationMessage};
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
@@ -63,6 +69,16 @@ pub struct ConnectionManager<P: Protocol> {
 
     pub(crate) ping_manager: PingManager,
     pub(crate) sync_manager: SyncManager,
+    /// Inputs of the other clients, relayed by the server
+    /// (see [`InputBroadcastConfig`](crate::server::input::InputBroadcastConfig))
+    pub(crate) remote_inputs: HashMap<ClientId, InputBuffer<P::Input>>,
+    /// Input messages of the additional input types of the other clients, relayed by the server
+    /// during the last receive (see [`RemoteInputs`](crate::client::input::RemoteInputs))
+    pub(crate) remote_additional_inputs: Vec<(ClientId, P::Message)>,
+    /// Tick duration changes received from the server, with the tick from which they apply
+    pub(crate) pending_tick_durations: VecDeque<(Tick, Duration)>,
+    /// Pause (`true`) or resume (`false`) received from the server, with the corresponding server tick
+    pub(crate) pending_pause: Option<(bool, Tick)>,
     // TODO: maybe don't do any replication until connection is synced?
 }
 
@@ -75,6 +91,7 @@ impl<P: Protocol> ConnectionManager<P> {
         input_delay_ticks: u16,
     ) -> Self {
         // create the message manager and the channels
+        let bandwidth_cap_enabled = packet_config.bandwidth_cap_enabled;
         let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
         // get the acks-tracker for entity updates
         let update_acks_tracker = message_manager
@@ -86,8 +103,11 @@ impl<P: Protocol> ConnectionManager<P> {
         // get a channel to get notified when a replication update message gets actually send (to update priority)
         let replication_update_send_receiver =
             message_manager.get_replication_update_send_receiver();
-        let replication_sender =
-            ReplicationSender::new(update_acks_tracker, replication_update_send_receiver);
+        let replication_sender = ReplicationSender::new(
+            update_acks_tracker,
+            replication_update_send_receiver,
+            bandwidth_cap_enabled,
+        );
         let replication_receiver = ReplicationReceiver::new();
         Self {
             message_manager,
@@ -95,6 +115,10 @@ impl<P: Protocol> ConnectionManager<P> {
             replication_receiver,
             ping_manager: PingManager::new(ping_config),
             sync_manager: SyncManager::new(sync_config, input_delay_ticks),
+            remote_inputs: HashMap::default(),
+            remote_additional_inputs: vec![],
+            pending_tick_durations: VecDeque::new(),
+            pending_pause: None,
             events: ConnectionEvents::default(),
         }
     }
@@ -118,14 +142,60 @@ impl<P: Protocol> ConnectionManager<P> {
             .unwrap_or(Tick(0))
     }
 
+    /// Most recent 64-bit tick received from the server.
+    ///
+    /// Only available if [`TickConfig::extended`](crate::prelude::TickConfig::extended) is enabled
+    pub fn latest_received_server_extended_tick(&self) -> Option<u64> {
+        self.message_manager.remote_extended_tick
+    }
+
+    /// Estimate of the jitter of the arrival times of the server packets.
+    ///
+    /// This is used to compute the interpolation delay if [`AdaptiveInterpolationDelay`](crate::client::interpolation::plugin::AdaptiveInterpolationDelay) is enabled.
+    pub fn server_packet_jitter(&self) -> Duration {
+        self.sync_manager.server_packet_jitter
+    }
+
     pub(crate) fn clear(&mut self) {
         self.events.clear();
     }
 
+    /// Map between the server's entities and the client's entities
+    pub fn remote_entity_map(&self) -> &RemoteEntityMap {
+        &self.replication_receiver.remote_entity_map
+    }
+
+    /// Map an entity of the server to an already existing local entity.
+    ///
+    /// When the server replicates `remote_entity`, its components will be added to `local_entity`
+    /// instead of spawning a new entity. This is useful for entities that exist on both sides
+    /// before being replicated (for example if they were loaded from the same scene file).
+    pub fn register_entity_mapping(&mut self, remote_entity: Entity, local_entity: Entity) {
+        self.replication_receiver
+            .register_entity_mapping(remote_entity, local_entity);
+    }
+
+    /// Input of another client for the given tick, if it was relayed by the server
+    /// (see [`InputBroadcastConfig`](crate::server::input::InputBroadcastConfig))
+    pub fn remote_input(&self, client_id: ClientId, tick: Tick) -> Option<&P::Input> {
+        self.remote_inputs.get(&client_id)?.get(tick)
+    }
+
+    /// Entities that were replicated from the server are sent back using the server's entity id.
+    /// (for example when the server transferred the [`Authority`](crate::prelude::Authority) over the entity to this client)
+    fn to_remote_entity(&self, entity: Entity) -> Entity {
+        self.replication_receiver
+            .remote_entity_map
+            .get_remote(entity)
+            .copied()
+            .unwrap_or(entity)
+    }
+
     pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
         self.message_manager
             .update(time_manager, &self.ping_manager, tick_manager);
         self.ping_manager.update(time_manager);
+        self.replication_sender.update(time_manager);
 
         // (we update the sync manager in POST_UPDATE)
     }
@@ -154,6 +224,27 @@ impl<P: Protocol> ConnectionManager<P> {
         self.buffer_message(message.into(), channel, target)
     }
 
+    /// Present a new [`ConnectToken`] to the server, to extend the session without reconnecting.
+    ///
+    /// The token must have been issued for the same client id (for example by the `TokenBackend` of the
+    /// `token_backend` feature). The server emits a `TokenRefreshEvent` once the token is validated.
+    pub fn refresh_token(&mut self, token: ConnectToken) -> Result<()> {
+        let message = ClientMessage::<P>::RefreshToken(token.try_into_refresh_bytes()?);
+        message.emit_send_logs("EntityActionsChannel");
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<EntityActionsChannel>())?;
+        Ok(())
+    }
+
+    /// Notify the server that we restarted the sync handshake, so that it resets the time nudge
+    pub(crate) fn send_resync(&mut self) -> Result<()> {
+        let message = ClientMessage::<P>::Resync;
+        message.emit_send_logs("EntityActionsChannel");
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<EntityActionsChannel>())?;
+        Ok(())
+    }
+
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
@@ -257,6 +348,7 @@ impl<P: Protocol> ConnectionManager<P> {
                     Ok::<(), anyhow::Error>(())
                 })?;
         }
+        self.message_manager.tick_generation = tick_manager.tick_generation();
         let payloads = self.message_manager.send_packets(tick_manager.tick());
 
         // update the replication sender about which messages were actually sent, and accumulate priority
@@ -271,6 +363,7 @@ impl<P: Protocol> ConnectionManager<P> {
         tick_manager: &TickManager,
     ) -> ConnectionEvents<P> {
         let _span = trace_span!("receive").entered();
+        self.remote_additional_inputs.clear();
         for (channel_kind, messages) in self.message_manager.read_messages::<ServerMessage<P>>() {
             let channel_name = self
                 .message_manager
@@ -294,6 +387,53 @@ impl<P: Protocol> ConnectionManager<P> {
                             // buffer the replication message
                             self.replication_receiver.recv_message(replication, tick);
                         }
+                        ServerMessage::ReplicationBaseline(bytes) => {
+                            match decompress_baseline(&bytes) {
+                                Ok(baseline) => {
+                                    // buffer all the messages at once, so that they are applied in the same frame
+                                    for replication in baseline {
+                                        self.replication_receiver.recv_message(replication, tick);
+                                    }
+                                }
+                                Err(e) => {
+                                    error!("Could not read the replication baseline: {:?}", e);
+                                }
+                            }
+                        }
+                        ServerMessage::TickDuration(tick, tick_duration) => {
+                            debug!(
+                                ?tick,
+                                ?tick_duration,
+                                "Received new tick duration from the server"
+                            );
+                            self.pending_tick_durations.push_back((tick, tick_duration));
+                        }
+                        ServerMessage::Pause(server_tick) => {
+                            debug!(?server_tick, "The server paused the simulation");
+                            self.pending_pause = Some((true, server_tick));
+                        }
+                        ServerMessage::Resume(server_tick) => {
+                            debug!(?server_tick, "The server resumed the simulation");
+                            self.pending_pause = Some((false, server_tick));
+                        }
+                        ServerMessage::TimeNudge(offset) => {
+                            debug!(
+                                ?offset,
+                                "The server asked to adjust the prediction time objective"
+                            );
+                            self.sync_manager.server_nudge = offset;
+                        }
+                        ServerMessage::Inputs(client_id, input_message) => {
+                            trace!(?client_id, end_tick = ?input_message.end_tick, "Received inputs of another client");
+                            self.remote_inputs
+                                .entry(client_id)
+                                .or_default()
+                                .update_from_message(input_message, |_, input| Some(input));
+                        }
+                        ServerMessage::AdditionalInputs(client_id, message) => {
+                            trace!(?client_id, "Received additional inputs of another client");
+                            self.remote_additional_inputs.push((client_id, message));
+                        }
                         ServerMessage::Sync(ref sync) => {
                             match sync {
                                 SyncMessage::Ping(ping) => {
@@ -345,6 +485,7 @@ impl<P: Protocol> ConnectionManager<P> {
                             replication,
                             group,
                             &mut self.events,
+                            &|_, component| Some(component),
                         );
                     });
             }
@@ -366,6 +507,19 @@ impl<P: Protocol> ConnectionManager<P> {
             .map_or(true, |server_tick| tick >= server_tick)
         {
             trace!("new last recv server tick: {:?}", tick);
+            // NOTE: the jitter is only sampled on the first packet of each server tick: the other packets
+            //  of the same tick were sent together with the first one and would skew the estimate
+            if let Some(previous_tick) = self
+                .sync_manager
+                .latest_received_server_tick
+                .filter(|previous_tick| tick > *previous_tick)
+            {
+                let expected_interval =
+                    tick_manager.config.tick_duration * (tick - previous_tick) as u32;
+                let actual_interval = self.sync_manager.duration_since_latest_received_server_tick;
+                self.sync_manager
+                    .update_server_packet_jitter(expected_interval, actual_interval);
+            }
             self.sync_manager.latest_received_server_tick = Some(tick);
             // TODO: add 'received_new_server_tick' ?
             // we probably actually physically received the packet some time between our last `receive` and now.
@@ -410,6 +564,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     ) -> Result<()> {
         trace!(?entity, "Prepare entity spawn to server");
         let group_id = replicate.replication_group.group_id(Some(entity));
+        let remote_entity = self.to_remote_entity(entity);
         let replication_sender = &mut self.replication_sender;
         // update the collect changes tick
         // (we can collect changes only since the last actions because all updates will wait for that action to be spawned)
@@ -419,7 +574,12 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     .entry(group)
         //     .or_default()
         //     .update_collect_changes_since_this_tick(system_current_tick);
-        replication_sender.prepare_entity_spawn(entity, group_id);
+        replication_sender.prepare_entity_spawn(remote_entity, group_id);
+        replication_sender
+            .group_channels
+            .entry(group_id)
+            .or_default()
+            .dependency = replicate.replication_group.dependency();
 
         // also set the priority for the group when we spawn it
         self.update_priority(
@@ -441,6 +601,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     ) -> Result<()> {
         // trace!(?entity, "Send entity despawn for tick {:?}", self.tick());
         let group_id = replicate.replication_group.group_id(Some(entity));
+        let remote_entity = self.to_remote_entity(entity);
         let replication_sender = &mut self.replication_sender;
         // update the collect changes tick
         // replication_sender
@@ -448,7 +609,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     .entry(group)
         //     .or_default()
         //     .update_collect_changes_since_this_tick(system_current_tick);
-        replication_sender.prepare_entity_despawn(entity, group_id);
+        replication_sender.prepare_entity_despawn(remote_entity, group_id);
         // Prediction/interpolation
         Ok(())
     }
@@ -475,6 +636,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     .entry(group)
         //     .or_default()
         //     .update_collect_changes_since_this_tick(system_current_tick);
+        let entity = self.to_remote_entity(entity);
         self.replication_sender
             .prepare_component_insert(entity, group_id, component.clone());
         Ok(())
@@ -495,6 +657,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     .entry(group)
         //     .or_default()
         //     .update_collect_changes_since_this_tick(system_current_tick);
+        let entity = self.to_remote_entity(entity);
         self.replication_sender
             .prepare_component_remove(entity, group_id, component_kind);
         Ok(())
@@ -512,17 +675,20 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         let kind: P::ComponentKinds = (&component).into();
         let group_id = replicate.group_id(Some(entity));
         // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
-        let collect_changes_since_this_tick = self
+        let group_channel = self
             .replication_sender
             .group_channels
             .entry(group_id)
-            .or_default()
-            .collect_changes_since_this_tick;
+            .or_default();
+        group_channel.send_interval = replicate.send_interval;
+        let collect_changes_since_this_tick = group_channel.collect_changes_since_this_tick;
         // send the update for all changes newer than the last ack bevy tick for the group
-
-        if collect_changes_since_this_tick.map_or(true, |c| {
-            component_change_tick.is_newer_than(c, system_current_tick)
-        }) {
+        // (groups in snapshot mode always send the full state)
+        if replicate.replication_group.is_snapshot()
+            || collect_changes_since_this_tick.map_or(true, |c| {
+                component_change_tick.is_newer_than(c, system_current_tick)
+            })
+        {
             trace!(
                 change_tick = ?component_change_tick,
                 ?collect_changes_since_this_tick,
@@ -535,6 +701,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
             //     tick = ?self.tick_manager.tick(),
             //     "Updating single component"
             // );
+            let entity = self.to_remote_entity(entity);
             self.replication_sender
                 .prepare_entity_update(entity, group_id, component.clone());
         }
@@ -581,3 +748,66 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::packet::priority_manager::PriorityConfig;
+    use crate::prelude::*;
+    use crate::serialize::wordbuffer::reader::ReadWordBuffer;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_server_packet_jitter() {
+        let protocol = protocol();
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol.channel_registry(),
+            PacketConfig::default(),
+            SyncConfig::default(),
+            PingConfig::default(),
+            0,
+        );
+        let mut receive = |manager: &mut ConnectionManager<MyProtocol>, tick: Tick| {
+            let message = MyMessageProtocol::Message1(Message1("1".to_string()));
+            server_message_manager
+                .buffer_send(message, ChannelKind::of::<Channel1>())
+                .unwrap();
+            for packet_bytes in server_message_manager.send_packets(tick).unwrap() {
+                let packet = Packet::decode(
+                    &mut ReadWordBuffer::start_read(packet_bytes.as_slice()),
+                    false,
+                )
+                .unwrap();
+                manager.recv_packet(packet, &tick_manager).unwrap();
+            }
+        };
+
+        // the server packets arrive exactly at the tick interval
+        for tick in 0..10 {
+            manager
+                .sync_manager
+                .duration_since_latest_received_server_tick = Duration::from_millis(10);
+            receive(&mut manager, Tick(tick));
+            // a second packet for the same tick arrives a bit after the first one
+            manager
+                .sync_manager
+                .duration_since_latest_received_server_tick = Duration::from_millis(2);
+            receive(&mut manager, Tick(tick));
+        }
+        assert_eq!(manager.server_packet_jitter(), Duration::ZERO);
+
+        // the server packets arrive irregularly
+        for tick in 10..20 {
+            manager
+                .sync_manager
+                .duration_since_latest_received_server_tick =
+                Duration::from_millis(if tick % 2 == 0 { 5 } else { 15 });
+            receive(&mut manager, Tick(tick));
+        }
+        assert!(manager.server_packet_jitter() > Duration::ZERO);
+    }
+}
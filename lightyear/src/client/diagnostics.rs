@@ -2,6 +2,8 @@ use bevy::app::{App, Plugin, PostUpdate};
 use bevy::diagnostic::Diagnostics;
 use bevy::prelude::{Real, Res, ResMut, Time};
 
+use crate::channel::diagnostics::{ChannelDiagnosticPaths, ChannelDiagnosticsPlugin};
+use crate::client::connection::ConnectionManager;
 use crate::connection::client::{ClientConnection, NetClient};
 use crate::prelude::Protocol;
 use crate::transport::io::IoDiagnosticsPlugin;
@@ -27,9 +29,31 @@ fn io_diagnostics_system(
         IoDiagnosticsPlugin::update_diagnostics(&mut io.stats, &time, &mut diagnostics);
     }
 }
+
+fn channel_diagnostics_system<P: Protocol>(
+    mut connection: ResMut<ConnectionManager<P>>,
+    paths: Res<ChannelDiagnosticPaths>,
+    time: Res<Time<Real>>,
+    mut diagnostics: Diagnostics,
+) {
+    // the client has one instance of every channel, so all of them are present in the stats
+    ChannelDiagnosticsPlugin::update_diagnostics(
+        std::iter::empty(),
+        connection.message_manager.channel_stats(),
+        &paths,
+        &time,
+        &mut diagnostics,
+    );
+}
+
 impl<P: Protocol> Plugin for ClientDiagnosticsPlugin<P> {
     fn build(&self, app: &mut App) {
+        let channel_registry = app.world.resource::<P>().channel_registry().clone();
         app.add_plugins(IoDiagnosticsPlugin);
-        app.add_systems(PostUpdate, io_diagnostics_system);
+        app.add_plugins(ChannelDiagnosticsPlugin::new(channel_registry));
+        app.add_systems(
+            PostUpdate,
+            (io_diagnostics_system, channel_diagnostics_system::<P>),
+        );
     }
 }
@@ -13,11 +13,17 @@
 //! }
 //! ```
 
-use crate::prelude::{ClientId, Protocol};
+use crate::_reexport::ClientMarker;
+use crate::client::connection::ConnectionManager;
+use crate::packet::message::Message;
+use crate::prelude::{Channel, ClientId, DisconnectReason, Protocol};
 use crate::shared::events::connection::ConnectionEvents;
+use crate::shared::events::network::{emit_network_events, ReceivedNetworkEvents};
 use crate::shared::events::plugin::EventsPlugin;
-use bevy::app::{App, Plugin, PostUpdate};
-use bevy::prelude::{Event, Events};
+use crate::shared::sets::InternalMainSet;
+use bevy::app::{App, Plugin, PostUpdate, PreUpdate};
+use bevy::prelude::{Event, EventReader, Events, IntoSystemConfigs, ResMut};
+use tracing::error;
 
 /// Plugin that handles generating bevy [`Events`] related to networking and replication
 pub struct ClientEventsPlugin<P: Protocol> {
@@ -37,6 +43,7 @@ impl<P: Protocol> Plugin for ClientEventsPlugin<P> {
         app
             // EVENTS
             .add_event::<ConnectEvent>()
+            .add_event::<DisconnectEvent>()
             // PLUGIN
             // TODO: it's annoying to have to keep that () around...
             //  revisit this.. maybe the into_iter_messages returns directly an object that
@@ -47,6 +54,59 @@ impl<P: Protocol> Plugin for ClientEventsPlugin<P> {
     }
 }
 
+/// Plugin that replicates the bevy [`Event`] `E` between the client and the server.
+///
+/// `E` must be a message of the protocol. Any event written on the client with an `EventWriter<E>` is sent
+/// to the server on the channel `C`, and any `E` message received from the server is emitted as an event `E`.
+/// Events received from the server are not sent back.
+pub struct NetworkEventPlugin<P: Protocol, C: Channel, E: Message + Event + Clone> {
+    _marker: std::marker::PhantomData<(P, C, E)>,
+}
+
+impl<P: Protocol, C: Channel, E: Message + Event + Clone> Default for NetworkEventPlugin<P, C, E> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, C: Channel, E: Message + Event + Clone> Plugin for NetworkEventPlugin<P, C, E>
+where
+    P::Message: From<E>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<E>()
+            .init_resource::<ReceivedNetworkEvents<E>>()
+            .add_systems(
+                PreUpdate,
+                emit_network_events::<E, ()>.after(InternalMainSet::<ClientMarker>::Receive),
+            )
+            .add_systems(
+                PostUpdate,
+                send_network_events::<P, C, E>.before(InternalMainSet::<ClientMarker>::Send),
+            );
+    }
+}
+
+/// Send the events written on the client to the server
+fn send_network_events<P: Protocol, C: Channel, E: Message + Event + Clone>(
+    mut events: EventReader<E>,
+    mut received: ResMut<ReceivedNetworkEvents<E>>,
+    mut connection: ResMut<ConnectionManager<P>>,
+) where
+    P::Message: From<E>,
+{
+    for (event, id) in events.read_with_id() {
+        if received.take(id.id) {
+            continue;
+        }
+        let _ = connection
+            .send_message::<C, E>(event.clone())
+            .map_err(|e| error!("could not send network event: {:?}", e));
+    }
+}
+
 /// Bevy [`Event`] emitted on the client on the frame where the connection is established
 ///
 /// We keep this separate from the server's ConnectEvent so that we have different events emitted on the client
@@ -64,7 +124,22 @@ impl ConnectEvent {
 }
 
 /// Bevy [`Event`] emitted on the client on the frame where the connection is disconnected
-pub type DisconnectEvent = crate::shared::events::components::DisconnectEvent<()>;
+///
+/// Contains the reason of the disconnection, if the server provided one
+#[derive(Event)]
+pub struct DisconnectEvent(Option<DisconnectReason>);
+
+impl DisconnectEvent {
+    pub fn new(reason: Option<DisconnectReason>) -> Self {
+        Self(reason)
+    }
+    pub fn reason(&self) -> Option<DisconnectReason> {
+        self.0
+    }
+}
+
+/// Bevy [`Event`] emitted on the client when a packet sent to the server is considered lost
+pub type PacketLostEvent = crate::shared::events::components::PacketLostEvent<()>;
 /// Bevy [`Event`] emitted on the client to indicate the user input for the tick
 pub type InputEvent<I> = crate::shared::events::components::InputEvent<I, ()>;
 /// Bevy [`Event`] emitted on the client when a EntitySpawn replication message is received
@@ -25,10 +25,13 @@
 //! This module is kept for simplicity but might get removed in the future.
 use crate::_reexport::ClientMarker;
 use bevy::prelude::{
-    not, App, Condition, EventReader, EventWriter, Events, FixedPostUpdate, FixedPreUpdate, In,
-    IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, Res, ResMut, Resource, SystemSet,
+    not, App, Condition, EventReader, EventWriter, Events, Fixed, FixedPostUpdate, FixedPreUpdate,
+    In, IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, PreUpdate, Res, ResMut,
+    Resource, SystemSet, Time,
 };
 use bevy::reflect::Reflect;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, trace};
 
 use crate::channel::builder::InputChannel;
@@ -39,14 +42,14 @@ use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::prediction::rollback::{Rollback, RollbackState};
 use crate::client::sync::{client_is_synced, SyncSet};
 use crate::connection::client::NetClient;
-use crate::inputs::native::input_buffer::InputBuffer;
-use crate::inputs::native::UserAction;
+use crate::inputs::native::input_buffer::{InputBuffer, InputMessage};
+use crate::inputs::native::{InputMode, UserAction};
 use crate::prelude::client::ClientConnection;
-use crate::prelude::{server, SharedConfig, Tick, TickManager};
+use crate::prelude::{server, ClientId, SharedConfig, Tick, TickManager};
 use crate::protocol::Protocol;
 use crate::shared::config::Mode;
 use crate::shared::sets::InternalMainSet;
-use crate::shared::tick_manager::TickEvent;
+use crate::shared::tick_manager::{is_first_substep, is_last_substep, TickEvent};
 
 #[derive(Debug, Clone, Reflect)]
 pub struct InputConfig {
@@ -54,7 +57,14 @@ pub struct InputConfig {
     /// This is used to compute the redundancy of the input messages.
     /// For instance, a value of 3 means that each input packet will contain the inputs for all the ticks
     ///  for the 3 last packets.
+    /// The server only applies the inputs of the ticks that it did not receive yet.
     pub packet_redundancy: u16,
+    /// If true, record the fraction of the tick at which each input was sampled, and send it to the server
+    /// along with the input (see [`InputEvent::sub_tick`]).
+    ///
+    /// This enables sub-tick hit registration (for example to resolve who shot first) at low tick rates,
+    /// at the cost of a slightly bigger input message.
+    pub sub_tick_timestamps: bool,
 }
 
 /// Resource that handles buffering and sending inputs to the server
@@ -64,16 +74,112 @@ pub struct InputConfig {
 #[derive(Debug, Resource)]
 pub struct InputManager<A: UserAction> {
     pub(crate) input_buffer: InputBuffer<A>,
+    /// History of the inputs that is being recorded, if recording is enabled
+    recording: Option<InputHistory<A>>,
+    /// Recorded inputs that are being played back
+    replay: Option<InputReplay<A>>,
 }
 
 impl<A: UserAction> Default for InputManager<A> {
     fn default() -> Self {
         Self {
             input_buffer: InputBuffer::default(),
+            recording: None,
+            replay: None,
         }
     }
 }
 
+/// Inputs of an additional input type `A` of the other clients, relayed by the server
+/// (see [`InputBroadcastConfig`](crate::server::input::InputBroadcastConfig)).
+///
+/// The relayed inputs of the [`Protocol::Input`] type are available via [`ConnectionManager::remote_input`].
+#[derive(Debug, Resource)]
+pub struct RemoteInputs<A: UserAction> {
+    buffers: HashMap<ClientId, InputBuffer<A>>,
+}
+
+impl<A: UserAction> Default for RemoteInputs<A> {
+    fn default() -> Self {
+        Self {
+            buffers: HashMap::default(),
+        }
+    }
+}
+
+impl<A: UserAction> RemoteInputs<A> {
+    /// Input of another client for the given tick, if it was relayed by the server
+    pub fn get(&self, client_id: ClientId, tick: Tick) -> Option<&A> {
+        self.buffers.get(&client_id)?.get(tick)
+    }
+}
+
+/// Serializable history of the inputs of the client, with one entry per tick.
+///
+/// It can be recorded with [`InputManager::start_recording`], saved to disk, and fed back into the input
+/// system with [`InputManager::start_replay`] to build deterministic replays.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputHistory<A> {
+    /// Tick of the first input of the history
+    pub start_tick: Tick,
+    /// Input for each tick, starting from `start_tick`
+    pub inputs: Vec<Option<A>>,
+}
+
+impl<A: UserAction> InputHistory<A> {
+    pub fn new(start_tick: Tick) -> Self {
+        Self {
+            start_tick,
+            inputs: vec![],
+        }
+    }
+
+    /// Tick of the last input of the history
+    pub fn end_tick(&self) -> Tick {
+        self.start_tick + (self.inputs.len() as i16 - 1)
+    }
+
+    /// Input recorded for the given tick
+    pub fn get(&self, tick: Tick) -> Option<&A> {
+        let index = tick - self.start_tick;
+        if index < 0 {
+            return None;
+        }
+        self.inputs.get(index as usize)?.as_ref()
+    }
+
+    /// Iterate through the inputs of the history, along with their tick
+    pub fn iter(&self) -> impl Iterator<Item = (Tick, Option<&A>)> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (self.start_tick + i as i16, input.as_ref()))
+    }
+
+    /// Record the input of the next tick
+    fn push(&mut self, tick: Tick, input: Option<A>) {
+        if self.inputs.is_empty() {
+            self.start_tick = tick;
+        }
+        // fill any gap (for example if the tick was snapped forward after a resync)
+        while !self.inputs.is_empty() && self.end_tick() + 1 < tick {
+            self.inputs.push(None);
+        }
+        // do not record a tick twice (for example if the tick was snapped backward after a resync)
+        if self.inputs.is_empty() || self.end_tick() < tick {
+            self.inputs.push(input);
+        }
+    }
+}
+
+/// Recorded inputs that are being played back
+#[derive(Debug)]
+struct InputReplay<A> {
+    history: InputHistory<A>,
+    /// Tick at which the replay started
+    replay_start_tick: Option<Tick>,
+}
+
 impl<A: UserAction> InputManager<A> {
     /// Get a cloned version of the input (we might not want to pop from the buffer because we want
     /// to keep it for rollback)
@@ -85,16 +191,64 @@ impl<A: UserAction> InputManager<A> {
     pub fn add_input(&mut self, input: A, tick: Tick) {
         self.input_buffer.set(tick, Some(input));
     }
+
+    /// Start recording the inputs of the client (this discards any previous recording)
+    pub fn start_recording(&mut self) {
+        self.recording = Some(InputHistory::new(Tick(0)));
+    }
+
+    /// Stop recording the inputs, and return the recorded history
+    pub fn stop_recording(&mut self) -> Option<InputHistory<A>> {
+        self.recording.take()
+    }
+
+    /// The inputs recorded so far, if recording is enabled
+    pub fn recording(&self) -> Option<&InputHistory<A>> {
+        self.recording.as_ref()
+    }
+
+    /// Play back a recorded history of inputs, starting from the next tick.
+    ///
+    /// While the replay is running, the recorded inputs override the inputs added with [`InputManager::add_input`].
+    pub fn start_replay(&mut self, history: InputHistory<A>) {
+        self.replay = Some(InputReplay {
+            history,
+            replay_start_tick: None,
+        });
+    }
+
+    /// Stop the replay that is currently running
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    /// Returns true if a recorded history of inputs is being played back
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
 }
 
 impl Default for InputConfig {
     fn default() -> Self {
         InputConfig {
             packet_redundancy: 10,
+            sub_tick_timestamps: false,
         }
     }
 }
 
+impl InputConfig {
+    pub fn with_packet_redundancy(mut self, packet_redundancy: u16) -> Self {
+        self.packet_redundancy = packet_redundancy;
+        self
+    }
+
+    pub fn with_sub_tick_timestamps(mut self, sub_tick_timestamps: bool) -> Self {
+        self.sub_tick_timestamps = sub_tick_timestamps;
+        self
+    }
+}
+
 pub struct InputPlugin<P: Protocol> {
     config: InputConfig,
     _marker: std::marker::PhantomData<P>,
@@ -141,9 +295,15 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
                 InputSystemSet::BufferInputs.run_if(not(is_in_rollback)),
                 InputSystemSet::WriteInputEvent,
             )
-                .chain(),
+                .chain()
+                // inputs are written once per tick, on the first physics step
+                .run_if(is_first_substep),
+        );
+        // the input events are kept for every physics step of the tick
+        app.configure_sets(
+            FixedPostUpdate,
+            InputSystemSet::ClearInputEvent.run_if(is_last_substep),
         );
-        app.configure_sets(FixedPostUpdate, InputSystemSet::ClearInputEvent);
         // SYSTEMS
         app.add_systems(
             FixedPostUpdate,
@@ -182,14 +342,29 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
         // SYSTEMS
         app.add_systems(
             FixedPreUpdate,
-            write_input_event::<P::Input>.in_set(InputSystemSet::WriteInputEvent),
+            (
+                (
+                    replay_inputs::<P::Input>,
+                    record_sub_tick::<P::Input>,
+                    record_inputs::<P::Input>,
+                )
+                    .chain()
+                    .run_if(not(is_in_rollback)),
+                write_input_event::<P::Input>,
+            )
+                .chain()
+                .in_set(InputSystemSet::WriteInputEvent),
         );
 
         app.add_systems(
             PostUpdate,
             (
                 receive_tick_events::<P::Input>.in_set(InputSystemSet::ReceiveTickEvents),
-                prepare_input_message::<P>.in_set(InputSystemSet::SendInputMessage),
+                (
+                    prepare_input_message::<P, P::Input>,
+                    prune_remote_inputs::<P>,
+                )
+                    .in_set(InputSystemSet::SendInputMessage),
             ),
         );
 
@@ -199,6 +374,92 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
     }
 }
 
+/// Plugin to handle an additional input type `A`, on top of the [`Protocol::Input`] type.
+///
+/// For example movement inputs that are sampled every tick, and UI commands that are only sent occasionally.
+/// Each input type has its own [`InputManager<A>`] to buffer the inputs, and its own [`InputEvent<A>`].
+///
+/// The input messages of `A` need to be part of the message protocol:
+/// ```rust,ignore
+/// #[message_protocol(protocol = "MyProtocol")]
+/// pub enum Messages {
+///     UiCommand(InputMessage<UiCommand>),
+/// }
+///
+/// app.add_plugins(AdditionalInputPlugin::<MyProtocol, UiCommand>::new(InputMode::Sparse));
+/// ```
+/// The server must add the corresponding
+/// [`AdditionalInputPlugin`](crate::server::input::AdditionalInputPlugin) to receive the inputs.
+pub struct AdditionalInputPlugin<P, A> {
+    mode: InputMode,
+    _marker: std::marker::PhantomData<(P, A)>,
+}
+
+impl<P, A> AdditionalInputPlugin<P, A> {
+    pub fn new(mode: InputMode) -> Self {
+        Self {
+            mode,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, A: UserAction> Plugin for AdditionalInputPlugin<P, A>
+where
+    P::Message: From<InputMessage<A>> + TryInto<InputMessage<A>, Error = ()>,
+{
+    fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.init_resource::<InputManager<A>>();
+        app.init_resource::<RemoteInputs<A>>();
+        // EVENT
+        app.add_event::<InputEvent<A>>();
+        // SYSTEMS
+        app.add_systems(
+            FixedPostUpdate,
+            clear_input_events::<A>.in_set(InputSystemSet::ClearInputEvent),
+        );
+        if app.world.resource::<ClientConfig>().shared.mode == Mode::HostServer {
+            app.add_systems(
+                FixedPreUpdate,
+                send_input_directly_to_client_events::<A>.in_set(InputSystemSet::WriteInputEvent),
+            );
+            return;
+        }
+        let write_input_event = match self.mode {
+            InputMode::Continuous => write_input_event::<A>.into_configs(),
+            // sparse commands are only applied once, they are not replayed during rollback
+            InputMode::Sparse => write_input_event::<A>.run_if(not(is_in_rollback)),
+        };
+        app.add_systems(
+            FixedPreUpdate,
+            (
+                (replay_inputs::<A>, record_sub_tick::<A>, record_inputs::<A>)
+                    .chain()
+                    .run_if(not(is_in_rollback)),
+                write_input_event,
+            )
+                .chain()
+                .in_set(InputSystemSet::WriteInputEvent),
+        );
+        app.add_systems(
+            PreUpdate,
+            receive_remote_inputs::<P, A>.after(InternalMainSet::<ClientMarker>::Receive),
+        );
+        app.add_systems(
+            PostUpdate,
+            (
+                receive_tick_events::<A>.in_set(InputSystemSet::ReceiveTickEvents),
+                (
+                    prepare_input_message::<P, A>,
+                    prune_additional_remote_inputs::<P, A>,
+                )
+                    .in_set(InputSystemSet::SendInputMessage),
+            ),
+        );
+    }
+}
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum InputSystemSet {
     // FIXED UPDATE
@@ -234,6 +495,61 @@ fn clear_input_events<A: UserAction>(mut input_events: EventReader<InputEvent<A>
     input_events.clear();
 }
 
+/// Override the input of the current tick with the recorded input, if a replay is running
+fn replay_inputs<A: UserAction>(
+    tick_manager: Res<TickManager>,
+    mut input_manager: ResMut<InputManager<A>>,
+) {
+    let tick = tick_manager.tick();
+    let input_manager = input_manager.as_mut();
+    let Some(replay) = &mut input_manager.replay else {
+        return;
+    };
+    let replay_start_tick = *replay.replay_start_tick.get_or_insert(tick);
+    let recorded_tick = replay.history.start_tick + (tick - replay_start_tick);
+    if recorded_tick > replay.history.end_tick() {
+        debug!("input replay finished");
+        input_manager.replay = None;
+        return;
+    }
+    input_manager
+        .input_buffer
+        .set(tick, replay.history.get(recorded_tick).cloned());
+}
+
+/// Add the input of the current tick to the recorded history, if recording is enabled
+fn record_inputs<A: UserAction>(
+    tick_manager: Res<TickManager>,
+    mut input_manager: ResMut<InputManager<A>>,
+) {
+    let tick = tick_manager.tick();
+    let input = input_manager.get_input(tick);
+    if let Some(recording) = &mut input_manager.recording {
+        recording.push(tick, input);
+    }
+}
+
+/// Record the fraction of the tick at which the input of the current tick was sampled.
+///
+/// The inputs are sampled once per frame, so the input of the last tick that runs during the frame is
+/// sampled `overstep` after the start of that tick.
+fn record_sub_tick<A: UserAction>(
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    fixed_time: Res<Time<Fixed>>,
+    mut input_manager: ResMut<InputManager<A>>,
+) {
+    if !config.input.sub_tick_timestamps {
+        return;
+    }
+    let tick = tick_manager.tick();
+    if input_manager.input_buffer.get(tick).is_some() {
+        input_manager
+            .input_buffer
+            .set_sub_tick(tick, fixed_time.overstep_fraction());
+    }
+}
+
 // Create a system that reads from the input buffer and returns the inputs of all clients for the current tick.
 // The only tricky part is that events are cleared every frame, but we want to clear every tick instead
 // Do it in this system because we want an input for every tick
@@ -250,7 +566,8 @@ fn write_input_event<A: UserAction>(
         } => rollback_tick,
     });
     let input = input_manager.get_input(tick);
-    client_input_events.send(InputEvent::new(input_manager.get_input(tick), ()));
+    let sub_tick = input_manager.input_buffer.get_sub_tick(tick);
+    client_input_events.send(InputEvent::new(input, ()).with_sub_tick(sub_tick));
 }
 
 /// Receive an [`TickEvent`] signifying that the local tick has been updated,
@@ -271,18 +588,25 @@ fn receive_tick_events<A: UserAction>(
                     input_manager.input_buffer.start_tick =
                         Some(start_tick + (*new_tick - *old_tick));
                 };
+                input_manager
+                    .input_buffer
+                    .sub_ticks
+                    .iter_mut()
+                    .for_each(|(tick, _)| *tick = *tick + (*new_tick - *old_tick));
             }
         }
     }
 }
 
 /// Take the input buffer, and prepare the input message to send to the server
-fn prepare_input_message<P: Protocol>(
+fn prepare_input_message<P: Protocol, A: UserAction>(
     connection: Option<ResMut<ConnectionManager<P>>>,
-    mut input_manager: ResMut<InputManager<P::Input>>,
+    mut input_manager: ResMut<InputManager<A>>,
     config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
-) {
+) where
+    P::Message: From<InputMessage<A>>,
+{
     let Some(mut connection) = connection else {
         return;
     };
@@ -336,6 +660,62 @@ fn prepare_input_message<P: Protocol>(
     // .pop(current_tick - (message_len + 1));
 }
 
+/// Delete the old inputs of the other clients that were relayed by the server
+fn prune_remote_inputs<P: Protocol>(
+    connection: Option<ResMut<ConnectionManager<P>>>,
+    tick_manager: Res<TickManager>,
+) {
+    let Some(mut connection) = connection else {
+        return;
+    };
+    let interpolation_tick = connection.sync_manager.interpolation_tick(&tick_manager);
+    connection
+        .remote_inputs
+        .values_mut()
+        .for_each(|input_buffer| {
+            input_buffer.pop(interpolation_tick);
+        });
+}
+
+/// Buffer the inputs of type `A` of the other clients that were relayed by the server
+fn receive_remote_inputs<P: Protocol, A: UserAction>(
+    connection: Option<Res<ConnectionManager<P>>>,
+    mut remote_inputs: ResMut<RemoteInputs<A>>,
+) where
+    P::Message: TryInto<InputMessage<A>, Error = ()>,
+{
+    let Some(connection) = connection else {
+        return;
+    };
+    for (client_id, message) in connection.remote_additional_inputs.iter() {
+        // the relayed messages of the other additional input types are skipped
+        let Ok(input_message) = message.clone().try_into() else {
+            continue;
+        };
+        trace!(?client_id, "Received additional inputs of another client");
+        remote_inputs
+            .buffers
+            .entry(*client_id)
+            .or_default()
+            .update_from_message(input_message, |_, input| Some(input));
+    }
+}
+
+/// Delete the old inputs of type `A` of the other clients that were relayed by the server
+fn prune_additional_remote_inputs<P: Protocol, A: UserAction>(
+    connection: Option<Res<ConnectionManager<P>>>,
+    tick_manager: Res<TickManager>,
+    mut remote_inputs: ResMut<RemoteInputs<A>>,
+) {
+    let Some(connection) = connection else {
+        return;
+    };
+    let interpolation_tick = connection.sync_manager.interpolation_tick(&tick_manager);
+    remote_inputs.buffers.values_mut().for_each(|input_buffer| {
+        input_buffer.pop(interpolation_tick);
+    });
+}
+
 /// In host server mode, we don't buffer inputs (because there is no rollback) and we don't send
 /// inputs through the network, we just send directly to the server's InputEvents
 fn send_input_directly_to_client_events<A: UserAction>(
@@ -347,3 +727,58 @@ fn send_input_directly_to_client_events<A: UserAction>(
     let input = input_manager.input_buffer.pop(tick);
     client_input_events.send(InputEvent::new(input, ()));
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay_inputs() {
+        let mut world = World::new();
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        world.init_resource::<InputManager<usize>>();
+        world
+            .resource_mut::<InputManager<usize>>()
+            .start_recording();
+
+        // record the inputs for ticks 0, 1 and 2
+        for input in [Some(1), None, Some(2)] {
+            let tick = world.resource::<TickManager>().tick();
+            if let Some(input) = input {
+                world
+                    .resource_mut::<InputManager<usize>>()
+                    .add_input(input, tick);
+            }
+            world.run_system_once(record_inputs::<usize>);
+            world.resource_mut::<TickManager>().increment_tick();
+        }
+        let history = world
+            .resource_mut::<InputManager<usize>>()
+            .stop_recording()
+            .unwrap();
+        assert_eq!(history.start_tick, Tick(0));
+        assert_eq!(history.inputs, vec![Some(1), None, Some(2)]);
+
+        // the history is played back starting from the current tick (tick 3)
+        world
+            .resource_mut::<InputManager<usize>>()
+            .start_replay(history);
+        for _ in 0..4 {
+            world.run_system_once(replay_inputs::<usize>);
+            world.resource_mut::<TickManager>().increment_tick();
+        }
+        let input_manager = world.resource::<InputManager<usize>>();
+        assert_eq!(input_manager.get_input(Tick(3)), Some(1));
+        assert_eq!(input_manager.get_input(Tick(4)), None);
+        assert_eq!(input_manager.get_input(Tick(5)), Some(2));
+        assert!(!input_manager.is_replaying());
+    }
+}
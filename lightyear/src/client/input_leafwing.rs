@@ -58,7 +58,7 @@ use crate::prelude::{Mode, TickManager};
 use crate::protocol::Protocol;
 use crate::shared::replication::components::PrePredicted;
 use crate::shared::sets::{FixedUpdateSet, InternalMainSet};
-use crate::shared::tick_manager::TickEvent;
+use crate::shared::tick_manager::{is_first_substep, TickEvent};
 
 /// Run condition to control most of the systems in the LeafwingInputPlugin
 fn run_if_enabled<A: LeafwingUserAction>(config: Res<ToggleActions<A>>) -> bool {
@@ -97,7 +97,7 @@ pub struct LeafwingInputConfig<A> {
     /// This is used to compute the redundancy of the input messages.
     /// For instance, a value of 3 means that each input packet will contain the inputs for all the ticks
     ///  for the 3 last packets.
-    // TODO: this seems unused now
+    /// The server only applies the inputs of the ticks that it did not receive yet.
     pub packet_redundancy: u16,
 
     /// If true, we only send diffs on the tick they were generated. (i.e. we will send a key-press only once)
@@ -121,6 +121,11 @@ impl<A> Default for LeafwingInputConfig<A> {
 }
 
 impl<A> LeafwingInputConfig<A> {
+    pub fn with_packet_redundancy(mut self, packet_redundancy: u16) -> Self {
+        self.packet_redundancy = packet_redundancy;
+        self
+    }
+
     // pub fn with_input_delay_ticks(mut self, tick: u16) -> Self {
     //     self.input_delay_ticks = tick;
     //     self
@@ -196,7 +201,11 @@ where
         app.init_resource::<Events<ActionDiffEvent<A>>>();
         // SETS
         // app.configure_sets(PreUpdate, InputManagerSystem::Tick.run_if(should_tick::<A>));
-        app.configure_sets(FixedPreUpdate, InputSystemSet::BufferClientInputs);
+        // inputs are applied once per tick, on the first physics step
+        app.configure_sets(
+            FixedPreUpdate,
+            InputSystemSet::BufferClientInputs.run_if(is_first_substep),
+        );
         app.configure_sets(
             PostUpdate,
             // we send inputs only every send_interval
@@ -594,6 +603,7 @@ fn clean_buffers<P: Protocol, A: LeafwingUserAction>(
 fn prepare_input_message<P: Protocol, A: LeafwingUserAction>(
     mut connection: ResMut<ConnectionManager<P>>,
     config: Res<ClientConfig>,
+    input_config: Res<LeafwingInputConfig<A>>,
     tick_manager: Res<TickManager>,
     global_action_diff_buffer: Option<Res<ActionDiffBuffer<A>>>,
     action_diff_buffer_query: Query<
@@ -621,7 +631,7 @@ fn prepare_input_message<P: Protocol, A: LeafwingUserAction>(
         + 1)
     .try_into()
     .unwrap();
-    let redundancy = config.input.packet_redundancy;
+    let redundancy = input_config.packet_redundancy;
     let message_len = redundancy * num_tick;
     let mut message = InputMessage::<A>::new(tick);
     for (entity, action_diff_buffer, predicted, pre_predicted) in action_diff_buffer_query.iter() {
@@ -953,7 +963,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
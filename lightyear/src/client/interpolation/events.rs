@@ -0,0 +1,37 @@
+//! Events emitted when the interpolation cannot smoothly interpolate between two server snapshots.
+//!
+//! These can be used to react to the missing data (for example to fade out an entity that stopped receiving
+//! updates), or to track the health of the network.
+//! ```rust,no_run,ignore
+//! fn track_starvation(mut events: EventReader<InterpolationStarvedEvent<MyProtocol>>) {
+//!     for event in events.read() {
+//!         warn!(entity = ?event.entity, component = ?event.component, "no server snapshot to interpolate towards");
+//!     }
+//! }
+//! ```
+use bevy::prelude::{Entity, Event};
+
+use crate::protocol::Protocol;
+use crate::shared::tick_manager::Tick;
+
+/// Event emitted when an interpolated component snaps to a new server snapshot instead of being interpolated
+/// (see [`InterpolationSnapPlugin`](super::snap::InterpolationSnapPlugin))
+#[derive(Event, Debug, Clone)]
+pub struct InterpolationSnapEvent<P: Protocol> {
+    pub entity: Entity,
+    pub component: P::ComponentKinds,
+    /// Tick of the server snapshot that the component snapped to
+    pub tick: Tick,
+}
+
+/// Event emitted when the interpolation tick reaches the latest server snapshot of a component
+/// and there is no newer snapshot to interpolate towards.
+///
+/// The component is then either frozen or extrapolated until new data arrives.
+#[derive(Event, Debug, Clone)]
+pub struct InterpolationStarvedEvent<P: Protocol> {
+    pub entity: Entity,
+    pub component: P::ComponentKinds,
+    /// Tick of the latest server snapshot that was received for the component
+    pub tick: Tick,
+}
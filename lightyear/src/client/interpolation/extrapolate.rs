@@ -0,0 +1,358 @@
+//! Bounded extrapolation when the interpolation buffer runs dry.
+//!
+//! If the server updates arrive late, the interpolation tick can reach the last received snapshot before the next
+//! one is available: without extrapolation the entity freezes until the next update arrives.
+//! When [`InterpolationConfig::extrapolation`](super::plugin::InterpolationConfig::extrapolation) is set, the entity
+//! keeps moving along the delta between the last two snapshots for up to
+//! [`ExtrapolationConfig::max_duration`]. When the real data arrives (or if the extrapolation window expires),
+//! the entity is blended back to its interpolated value over [`ExtrapolationConfig::blend_duration`].
+//!
+//! The extrapolation of a component can be bounded further with an [`ExtrapolationLimit`], so that a momentary
+//! gap in the server data can't fling the entity across the map:
+//! ```rust,no_run,ignore
+//! // positions can't be extrapolated faster than 10 units/s
+//! app.insert_resource(ExtrapolationLimit::<Position>::new(10.0, |a, b| a.0.distance(b.0)));
+//! // rotations can't be extrapolated faster than PI radians/s
+//! app.insert_resource(ExtrapolationLimit::<Rotation>::new(PI, |a, b| a.0.angle_between(b.0)));
+//! ```
+use bevy::prelude::{
+    Commands, Component, Entity, Query, Reflect, Res, Resource, Time, With, Without,
+};
+use bevy::utils::Duration;
+use tracing::trace;
+
+use crate::client::components::{LerpFn, SyncMetadata};
+use crate::client::config::ClientConfig;
+use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::prelude::TickManager;
+use crate::protocol::Protocol;
+use crate::shared::tick_manager::Tick;
+
+#[derive(Clone, Debug, Reflect)]
+pub struct ExtrapolationConfig {
+    /// Maximum duration for which we extrapolate past the last received snapshot
+    pub max_duration: Duration,
+    /// Duration of the blend from the extrapolated value back to the interpolated value
+    pub blend_duration: Duration,
+}
+
+impl Default for ExtrapolationConfig {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_millis(100),
+            blend_duration: Duration::from_millis(100),
+        }
+    }
+}
+
+impl ExtrapolationConfig {
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    pub fn with_blend_duration(mut self, blend_duration: Duration) -> Self {
+        self.blend_duration = blend_duration;
+        self
+    }
+}
+
+/// Maximum rate of change of the component `C` while it is extrapolated
+#[derive(Resource)]
+pub struct ExtrapolationLimit<C> {
+    /// Maximum change per second (for example a maximum speed for a position, or a maximum angular velocity
+    /// in radians per second for a rotation)
+    pub max_rate: f32,
+    /// Computes the difference between two values of the component (for example a distance, or an angle)
+    pub distance: fn(&C, &C) -> f32,
+}
+
+impl<C> ExtrapolationLimit<C> {
+    pub fn new(max_rate: f32, distance: fn(&C, &C) -> f32) -> Self {
+        Self { max_rate, distance }
+    }
+}
+
+impl<C> Clone for ExtrapolationLimit<C> {
+    fn clone(&self) -> Self {
+        Self::new(self.max_rate, self.distance)
+    }
+}
+
+/// Component that tracks the last two snapshots of the component `C`, to extrapolate when there is
+/// no snapshot to interpolate towards
+#[derive(Component, Debug)]
+pub struct ExtrapolateStatus<C> {
+    /// The snapshot that was received before `start`
+    previous: Option<(Tick, C)>,
+    /// The latest snapshot that we started interpolating from
+    start: Option<(Tick, C)>,
+    /// The last extrapolated value, if we are currently extrapolating
+    extrapolated: Option<C>,
+    /// Value we are blending from, along with the blend progress in [0.0, 1.0[
+    blend: Option<(C, f32)>,
+}
+
+impl<C> Default for ExtrapolateStatus<C> {
+    fn default() -> Self {
+        Self {
+            previous: None,
+            start: None,
+            extrapolated: None,
+            blend: None,
+        }
+    }
+}
+
+impl<C> ExtrapolateStatus<C> {
+    /// Returns true if the component value is currently extrapolated past the last received snapshot
+    pub fn is_extrapolating(&self) -> bool {
+        self.extrapolated.is_some()
+    }
+}
+
+/// Add the [`ExtrapolateStatus`] to the interpolated entities if extrapolation is enabled
+pub(crate) fn add_extrapolate_status<C: Component>(
+    config: Res<ClientConfig>,
+    mut commands: Commands,
+    query: Query<Entity, (With<InterpolateStatus<C>>, Without<ExtrapolateStatus<C>>)>,
+) {
+    if config.interpolation.extrapolation.is_none() {
+        return;
+    }
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(ExtrapolateStatus::<C>::default());
+    }
+}
+
+/// Extrapolate the component along the last delta if there is no snapshot to interpolate towards,
+/// and blend back to the interpolated value once the extrapolation stops
+pub(crate) fn extrapolate<C: Component + Clone, P: Protocol>(
+    config: Res<ClientConfig>,
+    connection: Res<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+    time: Res<Time>,
+    limit: Option<Res<ExtrapolationLimit<C>>>,
+    mut query: Query<(&mut C, &InterpolateStatus<C>, &mut ExtrapolateStatus<C>)>,
+) where
+    P::Components: SyncMetadata<C>,
+{
+    let Some(extrapolation) = &config.interpolation.extrapolation else {
+        return;
+    };
+    let tick_duration = tick_manager.config.tick_duration.as_secs_f32();
+    let max_ticks = extrapolation.max_duration.as_secs_f32() / tick_duration;
+    let blend_delta = if extrapolation.blend_duration.is_zero() {
+        1.0
+    } else {
+        time.delta_seconds() / extrapolation.blend_duration.as_secs_f32()
+    };
+    for (mut component, status, extrapolate) in query.iter_mut() {
+        let extrapolate = extrapolate.into_inner();
+        // keep track of the last two snapshots
+        if let Some((start_tick, start_value)) = &status.start {
+            if extrapolate
+                .start
+                .as_ref()
+                .map_or(true, |(tick, _)| tick != start_tick)
+            {
+                extrapolate.previous = extrapolate
+                    .start
+                    .replace((*start_tick, start_value.clone()));
+            }
+        }
+
+        let target = if status.end.is_some() {
+            // we have a snapshot to interpolate towards: the value was already interpolated
+            component.clone()
+        } else {
+            let (Some((previous_tick, previous_value)), Some((start_tick, start_value))) =
+                (&extrapolate.previous, &extrapolate.start)
+            else {
+                continue;
+            };
+            // if we received server packets more recent than the last snapshot, the component simply
+            // did not change since then: there is nothing to extrapolate
+            let starving = connection.latest_received_server_tick() <= *start_tick;
+            let elapsed = (status.current_tick - *start_tick) as f32 + status.current_overstep;
+            if starving && elapsed <= max_ticks {
+                if elapsed > 0.0 && start_tick != previous_tick {
+                    // continue along the delta between the last two snapshots
+                    let mut t = 1.0 + elapsed / (*start_tick - *previous_tick) as f32;
+                    let mut value = P::Components::lerp(previous_value, start_value, t);
+                    if let Some(limit) = &limit {
+                        // do not move further than the maximum rate allows since the last snapshot
+                        let max_delta = limit.max_rate * elapsed * tick_duration;
+                        let delta = (limit.distance)(start_value, &value);
+                        if delta > max_delta {
+                            trace!(?delta, ?max_delta, "clamping the extrapolation");
+                            t = 1.0 + (t - 1.0) * max_delta / delta;
+                            value = P::Components::lerp(previous_value, start_value, t);
+                        }
+                    }
+                    trace!(?start_tick, ?elapsed, "extrapolating");
+                    extrapolate.extrapolated = Some(value.clone());
+                    *component = value;
+                }
+                continue;
+            }
+            // the component didn't change or the extrapolation window expired: go back to the last received snapshot
+            start_value.clone()
+        };
+
+        if let Some(from) = extrapolate.extrapolated.take() {
+            trace!("stop extrapolating, blending back to the interpolated value");
+            extrapolate.blend = Some((from, 0.0));
+        }
+        if let Some((from, progress)) = extrapolate.blend.take() {
+            let progress = progress + blend_delta;
+            if progress < 1.0 {
+                *component = P::Components::lerp(&from, &target, progress);
+                extrapolate.blend = Some((from, progress));
+            } else {
+                *component = target;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::client::config::PacketConfig;
+    use crate::client::interpolation::plugin::InterpolationConfig;
+    use crate::client::sync::SyncConfig;
+    use crate::prelude::{PingConfig, TickConfig};
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    fn connection() -> ConnectionManager<MyProtocol> {
+        ConnectionManager::new(
+            protocol().channel_registry(),
+            PacketConfig::default(),
+            SyncConfig::default(),
+            PingConfig::default(),
+            0,
+        )
+    }
+
+    fn status(
+        start: Option<(Tick, Component1)>,
+        end: Option<(Tick, Component1)>,
+        current_tick: Tick,
+    ) -> InterpolateStatus<Component1> {
+        InterpolateStatus {
+            start,
+            end,
+            current_tick,
+            current_overstep: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_extrapolation() {
+        let mut world = World::new();
+        world.insert_resource(ClientConfig {
+            interpolation: InterpolationConfig::default().with_extrapolation(
+                ExtrapolationConfig::default()
+                    .with_max_duration(Duration::from_millis(30))
+                    .with_blend_duration(Duration::ZERO),
+            ),
+            ..Default::default()
+        });
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        world.init_resource::<Time>();
+        world.insert_resource(connection());
+        let entity = world
+            .spawn((
+                Component1(0.0),
+                status(Some((Tick(0), Component1(0.0))), None, Tick(0)),
+                ExtrapolateStatus::<Component1>::default(),
+            ))
+            .id();
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+
+        // the interpolation tick reaches the latest snapshot at tick 2, there is no snapshot to interpolate towards
+        world.entity_mut(entity).insert((
+            Component1(2.0),
+            status(Some((Tick(2), Component1(2.0))), None, Tick(4)),
+        ));
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+        assert_eq!(world.get::<Component1>(entity), Some(&Component1(4.0)));
+        assert!(world
+            .get::<ExtrapolateStatus<Component1>>(entity)
+            .unwrap()
+            .is_extrapolating());
+
+        // the extrapolation is bounded
+        world
+            .get_mut::<InterpolateStatus<Component1>>(entity)
+            .unwrap()
+            .current_tick = Tick(10);
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+        assert_eq!(world.get::<Component1>(entity), Some(&Component1(2.0)));
+        assert!(!world
+            .get::<ExtrapolateStatus<Component1>>(entity)
+            .unwrap()
+            .is_extrapolating());
+
+        // we received server packets after the last snapshot: the component did not change, so we don't extrapolate
+        world
+            .resource_mut::<ConnectionManager<MyProtocol>>()
+            .sync_manager
+            .latest_received_server_tick = Some(Tick(3));
+        world
+            .get_mut::<InterpolateStatus<Component1>>(entity)
+            .unwrap()
+            .current_tick = Tick(4);
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+        assert_eq!(world.get::<Component1>(entity), Some(&Component1(2.0)));
+        assert!(!world
+            .get::<ExtrapolateStatus<Component1>>(entity)
+            .unwrap()
+            .is_extrapolating());
+    }
+
+    #[test]
+    fn test_extrapolation_limit() {
+        let mut world = World::new();
+        world.insert_resource(ClientConfig {
+            interpolation: InterpolationConfig::default()
+                .with_extrapolation(ExtrapolationConfig::default()),
+            ..Default::default()
+        });
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        world.init_resource::<Time>();
+        world.insert_resource(connection());
+        // the snapshots move at 100 units/s, but the extrapolation is limited to 50 units/s
+        world.insert_resource(ExtrapolationLimit::<Component1>::new(50.0, |a, b| {
+            (a.0 - b.0).abs()
+        }));
+        let entity = world
+            .spawn((
+                Component1(0.0),
+                status(Some((Tick(0), Component1(0.0))), None, Tick(0)),
+                ExtrapolateStatus::<Component1>::default(),
+            ))
+            .id();
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+
+        world.entity_mut(entity).insert((
+            Component1(2.0),
+            status(Some((Tick(2), Component1(2.0))), None, Tick(4)),
+        ));
+        world.run_system_once(extrapolate::<Component1, MyProtocol>);
+        let value = world.get::<Component1>(entity).unwrap().0;
+        assert!((value - 3.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,217 @@
+//! Cubic Hermite interpolation of a component using a replicated velocity component.
+//!
+//! Linear interpolation between server snapshots produces visible corners at every snapshot when the
+//! server send rate is low. If the velocity is also replicated, we can use it as the tangent at both
+//! snapshots to get a smooth (C1) trajectory.
+//!
+//! Both components must be interpolated (`#[sync(full)]` in the protocol). The
+//! [`HermiteInterpolationPlugin`] is added for each (component, velocity) pair: it runs after the
+//! default interpolation and overrides the value of the component `C` whenever the velocity `V` is known
+//! at both snapshots. Otherwise the linearly interpolated value is kept.
+//! ```rust,no_run,ignore
+//! use lightyear::prelude::client::HermiteInterpolationPlugin;
+//! let mut app = bevy::app::App::new();
+//! app.add_plugins(HermiteInterpolationPlugin::<Position, LinearVelocity>::default());
+//! ```
+use std::marker::PhantomData;
+use std::ops::{Add, Mul};
+
+use bevy::prelude::{App, Component, IntoSystemConfigs, Plugin, Query, Res};
+use tracing::trace;
+
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::plugin::{interpolation_schedule, InterpolationSet};
+use crate::prelude::TickManager;
+use crate::shared::tick_manager::Tick;
+
+/// Interpolation function that uses the velocity `V` of a component `C` at the start and end snapshots
+pub trait HermiteFn<C, V> {
+    /// Interpolate between `start` and `end` at the fraction `t`.
+    ///
+    /// `duration` is the time in seconds between the two snapshots; the velocities are expressed per second.
+    fn hermite(start: (&C, &V), end: (&C, &V), t: f32, duration: f32) -> C;
+}
+
+/// Cubic Hermite spline, for components that can be linearly combined (for example `Vec2` or `Vec3` wrappers)
+pub struct CubicHermiteInterpolator;
+
+impl<C, V> HermiteFn<C, V> for CubicHermiteInterpolator
+where
+    for<'a> &'a C: Mul<f32, Output = C>,
+    for<'a> &'a V: Mul<f32, Output = C>,
+    C: Add<C, Output = C>,
+{
+    fn hermite(start: (&C, &V), end: (&C, &V), t: f32, duration: f32) -> C {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+        start.0 * h00 + start.1 * (h10 * duration) + end.0 * h01 + end.1 * (h11 * duration)
+    }
+}
+
+/// Interpolate the component `C` with the interpolation function `F`, using the velocity component `V`
+pub struct HermiteInterpolationPlugin<C, V, F = CubicHermiteInterpolator> {
+    _marker: PhantomData<(C, V, F)>,
+}
+
+impl<C, V, F> Default for HermiteInterpolationPlugin<C, V, F> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component, V: Component, F: HermiteFn<C, V> + Send + Sync + 'static> Plugin
+    for HermiteInterpolationPlugin<C, V, F>
+{
+    fn build(&self, app: &mut App) {
+        let schedule = interpolation_schedule(app);
+        app.add_systems(
+            schedule,
+            hermite_interpolate::<C, V, F>
+                .after(InterpolationSet::Interpolate)
+                .in_set(InterpolationSet::All),
+        );
+    }
+}
+
+/// Get the value of the velocity at the given snapshot tick.
+///
+/// The history only contains the ticks where the component changed, so the start value is valid until the end tick.
+fn velocity_at<V: Component>(status: &InterpolateStatus<V>, tick: Tick) -> Option<&V> {
+    if let Some((end_tick, end_value)) = &status.end {
+        if *end_tick == tick {
+            return Some(end_value);
+        }
+        if *end_tick < tick {
+            return None;
+        }
+    }
+    status
+        .start
+        .as_ref()
+        .filter(|(start_tick, _)| *start_tick <= tick)
+        .map(|(_, value)| value)
+}
+
+/// Override the interpolated value of `C` using the velocities at the start and end snapshots
+pub(crate) fn hermite_interpolate<C: Component, V: Component, F: HermiteFn<C, V>>(
+    tick_manager: Res<TickManager>,
+    mut query: Query<(&mut C, &InterpolateStatus<C>, &InterpolateStatus<V>)>,
+) {
+    let tick_duration = tick_manager.config.tick_duration.as_secs_f32();
+    for (mut component, status, velocity_status) in query.iter_mut() {
+        let (Some((start_tick, start_value)), Some((end_tick, end_value))) =
+            (&status.start, &status.end)
+        else {
+            continue;
+        };
+        if start_tick == end_tick {
+            continue;
+        }
+        let (Some(start_velocity), Some(end_velocity)) = (
+            velocity_at(velocity_status, *start_tick),
+            velocity_at(velocity_status, *end_tick),
+        ) else {
+            trace!(
+                ?start_tick,
+                ?end_tick,
+                "velocity unknown at the snapshots, keeping the linear interpolation"
+            );
+            continue;
+        };
+        let t = status.interpolation_fraction().unwrap();
+        let duration = (*end_tick - *start_tick) as f32 * tick_duration;
+        *component = F::hermite(
+            (start_value, start_velocity),
+            (end_value, end_velocity),
+            t,
+            duration,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Velocity(f32);
+
+    impl Mul<f32> for &Position {
+        type Output = Position;
+        fn mul(self, rhs: f32) -> Position {
+            Position(self.0 * rhs)
+        }
+    }
+
+    impl Mul<f32> for &Velocity {
+        type Output = Position;
+        fn mul(self, rhs: f32) -> Position {
+            Position(self.0 * rhs)
+        }
+    }
+
+    impl Add for Position {
+        type Output = Position;
+        fn add(self, rhs: Position) -> Position {
+            Position(self.0 + rhs.0)
+        }
+    }
+
+    #[test]
+    fn test_hermite_interpolation() {
+        let mut world = World::new();
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(100),
+        )));
+        // the entity moves from 0.0 to 1.0 in 1 second, starting and ending at rest
+        let entity = world
+            .spawn((
+                Position(0.0),
+                InterpolateStatus::<Position> {
+                    start: Some((Tick(0), Position(0.0))),
+                    end: Some((Tick(10), Position(1.0))),
+                    current_tick: Tick(2),
+                    current_overstep: 0.5,
+                },
+                InterpolateStatus::<Velocity> {
+                    start: Some((Tick(0), Velocity(0.0))),
+                    end: Some((Tick(10), Velocity(0.0))),
+                    current_tick: Tick(2),
+                    current_overstep: 0.5,
+                },
+            ))
+            .id();
+        world.run_system_once(hermite_interpolate::<Position, Velocity, CubicHermiteInterpolator>);
+        // smoothstep at t = 0.25
+        let value = world.get::<Position>(entity).unwrap().0;
+        assert!((value - 0.15625).abs() < 1e-5);
+
+        // constant velocity: the trajectory is linear
+        world
+            .get_mut::<InterpolateStatus<Velocity>>(entity)
+            .unwrap()
+            .start = Some((Tick(0), Velocity(1.0)));
+        world
+            .get_mut::<InterpolateStatus<Velocity>>(entity)
+            .unwrap()
+            .end = None;
+        world.run_system_once(hermite_interpolate::<Position, Velocity, CubicHermiteInterpolator>);
+        let value = world.get::<Position>(entity).unwrap().0;
+        assert!((value - 0.25).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,95 @@
+//! Replicate the hierarchy of the confirmed entities to the interpolated entities.
+//!
+//! The `Transform` of a child entity is relative to its parent. If the interpolated child entity was not parented
+//! to the interpolated parent entity, both entities would be interpolated independently and the child would
+//! swim relative to its parent. Instead, the interpolated child is parented to the interpolated parent, so that
+//! its local transform is interpolated relative to the parent.
+use bevy::prelude::{BuildChildren, Commands, Parent, Query, With};
+use tracing::trace;
+
+use crate::client::components::Confirmed;
+use crate::client::interpolation::Interpolated;
+use crate::shared::replication::hierarchy::ParentSync;
+
+/// Parent the interpolated entities to the interpolated entity of their confirmed parent
+pub(crate) fn sync_interpolated_hierarchy(
+    mut commands: Commands,
+    confirmed_query: Query<(&Confirmed, Option<&Parent>), With<ParentSync>>,
+    parent_query: Query<&Confirmed>,
+    interpolated_query: Query<Option<&Parent>, With<Interpolated>>,
+) {
+    for (confirmed, confirmed_parent) in confirmed_query.iter() {
+        let Some(interpolated) = confirmed.interpolated else {
+            continue;
+        };
+        let Ok(interpolated_parent) = interpolated_query.get(interpolated) else {
+            continue;
+        };
+        match confirmed_parent {
+            Some(confirmed_parent) => {
+                // the interpolated parent might not be spawned yet
+                let Some(new_parent) = parent_query
+                    .get(confirmed_parent.get())
+                    .ok()
+                    .and_then(|parent| parent.interpolated)
+                else {
+                    continue;
+                };
+                if interpolated_parent.map_or(true, |parent| parent.get() != new_parent) {
+                    trace!(?interpolated, ?new_parent, "update interpolated parent");
+                    commands.entity(interpolated).set_parent(new_parent);
+                }
+            }
+            None => {
+                if interpolated_parent.is_some() {
+                    commands.entity(interpolated).remove_parent();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{BuildWorldChildren, Entity, World};
+
+    use crate::shared::tick_manager::Tick;
+
+    use super::*;
+
+    fn spawn_pair(world: &mut World) -> (Entity, Entity) {
+        let confirmed = world.spawn(ParentSync::default()).id();
+        let interpolated = world
+            .spawn(Interpolated {
+                confirmed_entity: confirmed,
+            })
+            .id();
+        world.entity_mut(confirmed).insert(Confirmed {
+            predicted: None,
+            interpolated: Some(interpolated),
+            tick: Tick(0),
+        });
+        (confirmed, interpolated)
+    }
+
+    #[test]
+    fn test_sync_interpolated_hierarchy() {
+        let mut world = World::new();
+        let (confirmed_parent, interpolated_parent) = spawn_pair(&mut world);
+        let (confirmed_child, interpolated_child) = spawn_pair(&mut world);
+        world
+            .entity_mut(confirmed_child)
+            .set_parent(confirmed_parent);
+
+        world.run_system_once(sync_interpolated_hierarchy);
+        assert_eq!(
+            world.get::<Parent>(interpolated_child).map(|p| p.get()),
+            Some(interpolated_parent)
+        );
+
+        world.entity_mut(confirmed_child).remove_parent();
+        world.run_system_once(sync_interpolated_hierarchy);
+        assert!(world.get::<Parent>(interpolated_child).is_none());
+    }
+}
@@ -1,11 +1,15 @@
-use bevy::prelude::{Commands, Component, DetectChanges, Entity, Query, Ref, Res, With, Without};
+use bevy::prelude::{
+    Commands, Component, DetectChanges, Entity, EventWriter, Query, Ref, Res, With, Without,
+};
 use tracing::{debug, info, trace};
 
 use crate::_reexport::{ComponentProtocol, FromType};
 use crate::client::components::{SyncComponent, SyncMetadata};
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::events::InterpolationStarvedEvent;
 use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::client::interpolation::plugin::{ComponentInterpolationDelay, InterpolationUpdateMode};
 use crate::client::interpolation::Interpolated;
 use crate::prelude::TickManager;
 use crate::protocol::Protocol;
@@ -51,12 +55,47 @@ impl<C: Component> InterpolateStatus<C> {
     }
 }
 
+/// Current interpolation tick and overstep for the component `C`, taking into account
+/// its [`ComponentInterpolationDelay`] if there is one
+pub(crate) fn component_interpolation_tick<C, P: Protocol>(
+    config: &ClientConfig,
+    connection: &ConnectionManager<P>,
+    tick_manager: &TickManager,
+    delay_override: Option<&ComponentInterpolationDelay<C>>,
+) -> (Tick, f32) {
+    let (tick, overstep) = match delay_override {
+        None => (
+            connection.sync_manager.interpolation_tick(tick_manager),
+            connection.sync_manager.interpolation_overstep(tick_manager),
+        ),
+        Some(delay_override) => {
+            let interpolation_time = connection.sync_manager.interpolation_time_with_delay(
+                &config.interpolation.delay,
+                &delay_override.delay,
+                config.shared.server_send_interval,
+            );
+            let tick_duration = tick_manager.config.tick_duration;
+            (
+                interpolation_time.to_tick(tick_duration),
+                interpolation_time.tick_overstep(tick_duration),
+            )
+        }
+    };
+    match config.interpolation.update_mode {
+        InterpolationUpdateMode::Frame => (tick, overstep),
+        // the interpolated values only change on tick boundaries
+        InterpolationUpdateMode::FixedTick => (tick, 0.0),
+    }
+}
+
 /// At the end of each frame, interpolate the components between the last 2 confirmed server states
 /// Invariant: start_tick <= current_interpolate_tick + overstep < end_tick
 pub(crate) fn update_interpolate_status<C: SyncComponent, P: Protocol>(
     config: Res<ClientConfig>,
     connection: Res<ConnectionManager<P>>,
     tick_manager: Res<TickManager>,
+    delay_override: Option<Res<ComponentInterpolationDelay<C>>>,
+    mut starved_events: EventWriter<InterpolationStarvedEvent<P>>,
     mut query: Query<(
         Entity,
         Option<&mut C>,
@@ -75,13 +114,21 @@ pub(crate) fn update_interpolate_status<C: SyncComponent, P: Protocol>(
         / config.shared.tick.tick_duration.as_secs_f32()) as i16
         + 1;
 
-    let current_interpolate_tick = connection
-        .sync_manager
-        .interpolation_tick(tick_manager.as_ref());
-    let current_interpolate_overstep = connection
-        .sync_manager
-        .interpolation_overstep(tick_manager.as_ref());
+    let (current_interpolate_tick, current_interpolate_overstep) = component_interpolation_tick(
+        config.as_ref(),
+        connection.as_ref(),
+        tick_manager.as_ref(),
+        delay_override.as_deref(),
+    );
+    // if we received server packets more recent than the last snapshot, the component simply did not change
+    let is_starving = |status: &InterpolateStatus<C>| {
+        status.end.is_none()
+            && status.start.as_ref().is_some_and(|(start_tick, _)| {
+                connection.latest_received_server_tick() <= *start_tick
+            })
+    };
     for (entity, component, mut status, mut history) in query.iter_mut() {
+        let was_starving = is_starving(&status);
         let mut start = status.start.take();
         let mut end = status.end.take();
 
@@ -201,8 +248,19 @@ pub(crate) fn update_interpolate_status<C: SyncComponent, P: Protocol>(
         if status.start.is_none() {
             trace!("no lerp start tick");
         }
-        if status.end.is_none() {
+        if is_starving(&status) {
             // warn!("no lerp end tick: might want to increase the interpolation delay");
+            // only notify when we start starving, not on every frame
+            if let Some((start_tick, _)) = &status.start {
+                if !was_starving {
+                    trace!(?entity, component = ?kind, "interpolation is starving");
+                    starved_events.send(InterpolationStarvedEvent {
+                        entity,
+                        component: kind,
+                        tick: *start_tick,
+                    });
+                }
+            }
         }
     }
 }
@@ -325,7 +383,7 @@ pub(crate) fn interpolate<C: Component + Clone, P: Protocol>(
 //             min_delay: interpolation_delay,
 //             send_interval_ratio: 0.0,
 //         });
-//         let mut stepper = BevyStepper::new(
+//         let mut stepper = BevyStepper::from_configs(
 //             shared_config,
 //             sync_config,
 //             prediction_config,
@@ -547,3 +605,59 @@ pub(crate) fn interpolate<C: Component + Clone, P: Protocol>(
 //         Ok(())
 //     }
 // }
+
+#[cfg(test)]
+mod update_mode_tests {
+    use bevy::utils::Duration;
+
+    use crate::client::config::PacketConfig;
+    use crate::client::interpolation::plugin::InterpolationConfig;
+    use crate::client::sync::SyncConfig;
+    use crate::prelude::{PingConfig, TickConfig};
+    use crate::shared::time_manager::WrappedTime;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_fixed_tick_update_mode() {
+        let tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        let mut connection = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry(),
+            PacketConfig::default(),
+            SyncConfig::default(),
+            PingConfig::default(),
+            0,
+        );
+        connection.sync_manager.interpolation_time =
+            WrappedTime::from_duration(Duration::from_millis(15));
+
+        // every frame: we use the overstep between ticks
+        let config = ClientConfig::default();
+        assert_eq!(
+            component_interpolation_tick::<Component1, _>(
+                &config,
+                &connection,
+                &tick_manager,
+                None
+            ),
+            (Tick(1), 0.5)
+        );
+
+        // every tick: the interpolation status (and therefore the interpolated values) only change on tick boundaries
+        let config = ClientConfig {
+            interpolation: InterpolationConfig::default()
+                .with_update_mode(InterpolationUpdateMode::FixedTick),
+            ..Default::default()
+        };
+        assert_eq!(
+            component_interpolation_tick::<Component1, _>(
+                &config,
+                &connection,
+                &tick_manager,
+                None
+            ),
+            (Tick(1), 0.0)
+        );
+    }
+}
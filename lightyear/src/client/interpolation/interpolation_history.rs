@@ -2,17 +2,20 @@ use std::ops::Deref;
 
 use crate::_reexport::FromType;
 use bevy::prelude::{
-    Commands, Component, DetectChanges, Entity, Query, Ref, Res, ResMut, With, Without,
+    Changed, Commands, Component, DetectChanges, Entity, Query, Ref, Res, ResMut, With, Without,
 };
 use tracing::{debug, trace};
 
 use crate::client::components::{ComponentSyncMode, SyncComponent};
 use crate::client::components::{Confirmed, SyncMetadata};
+use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
-use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::interpolate::{component_interpolation_tick, InterpolateStatus};
+use crate::client::interpolation::opt_out::DisableInterpolation;
+use crate::client::interpolation::plugin::ComponentInterpolationDelay;
 use crate::client::interpolation::resource::InterpolationManager;
 use crate::client::interpolation::Interpolated;
-use crate::prelude::{ExternalMapper, TickManager};
+use crate::prelude::{ExternalMapper, SyncModeOverride, TickManager};
 use crate::protocol::Protocol;
 use crate::shared::tick_manager::Tick;
 use crate::utils::ready_buffer::ReadyBuffer;
@@ -51,6 +54,27 @@ impl<T: SyncComponent> ConfirmedHistory<T> {
         }
     }
 
+    /// Iterate through the server updates that are waiting to be interpolated, ordered by tick
+    pub fn iter(&self) -> impl Iterator<Item = (Tick, &T)> {
+        let mut items: Vec<_> = self
+            .buffer
+            .heap
+            .iter()
+            .map(|item| (item.key, &item.item))
+            .collect();
+        items.sort_by_key(|(tick, _)| *tick);
+        items.into_iter()
+    }
+
+    /// Number of server updates that are waiting to be interpolated
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
     /// Reset the history for this component
     pub(crate) fn clear(&mut self) {
         self.buffer = ReadyBuffer::new();
@@ -80,25 +104,44 @@ impl<T: SyncComponent> ConfirmedHistory<T> {
 pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
     // TODO: unfortunately we need this to be mutable because of the MapEntities trait even though it's not actually needed...
     mut manager: ResMut<InterpolationManager>,
+    config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
     mut commands: Commands,
     connection: Res<ConnectionManager<P>>,
-    interpolated_entities: Query<Entity, (Without<ConfirmedHistory<C>>, With<Interpolated>)>,
-    confirmed_entities: Query<(&Confirmed, Ref<C>)>,
+    delay_override: Option<Res<ComponentInterpolationDelay<C>>>,
+    interpolated_entities: Query<
+        (Entity, Ref<Interpolated>),
+        (
+            Without<ConfirmedHistory<C>>,
+            Without<DisableInterpolation>,
+            With<Interpolated>,
+        ),
+    >,
+    confirmed_entities: Query<(
+        &Confirmed,
+        Ref<C>,
+        Option<Ref<SyncModeOverride<P::ComponentKinds>>>,
+    )>,
 ) where
     P::Components: SyncMetadata<C>,
     P::Components: ExternalMapper<C>,
+    P::ComponentKinds: FromType<C>,
 {
-    let current_tick = connection
-        .sync_manager
-        .interpolation_tick(tick_manager.as_ref());
-    let current_overstep = connection
-        .sync_manager
-        .interpolation_overstep(tick_manager.as_ref());
-    for (confirmed_entity, confirmed_component) in confirmed_entities.iter() {
+    let (current_tick, current_overstep) = component_interpolation_tick(
+        config.as_ref(),
+        connection.as_ref(),
+        tick_manager.as_ref(),
+        delay_override.as_deref(),
+    );
+    for (confirmed_entity, confirmed_component, sync_mode_override) in confirmed_entities.iter() {
         if let Some(p) = confirmed_entity.interpolated {
-            if let Ok(interpolated_entity) = interpolated_entities.get(p) {
-                if confirmed_component.is_added() {
+            if let Ok((interpolated_entity, interpolated)) = interpolated_entities.get(p) {
+                // the component got added on the confirmed side, the entity just switched to interpolation,
+                // or its sync mode was overridden
+                if confirmed_component.is_added()
+                    || interpolated.is_added()
+                    || sync_mode_override.as_ref().is_some_and(|o| o.is_changed())
+                {
                     // safety: we know the entity exists
                     let mut interpolated_entity_mut =
                         commands.get_entity(interpolated_entity).unwrap();
@@ -110,7 +153,10 @@ pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
                         &mut new_component,
                         &mut manager.interpolated_entity_map,
                     );
-                    match P::Components::mode() {
+                    match SyncModeOverride::mode::<C>(
+                        sync_mode_override.as_deref(),
+                        P::Components::mode(),
+                    ) {
                         ComponentSyncMode::Full => {
                             trace!(?interpolated_entity, tick=?tick_manager.tick(),  "spawn interpolation history");
                             interpolated_entity_mut.insert((
@@ -187,18 +233,62 @@ pub(crate) fn apply_confirmed_update_mode_full<C: SyncComponent, P: Protocol>(
     }
 }
 
+/// Remove the interpolation history of a component when the entity's [`SyncModeOverride`] stops using `Full` sync for it.
+///
+/// The history is added back by [`add_component_history`] if the entity switches back to `Full` sync.
+#[allow(clippy::type_complexity)]
+pub(crate) fn remove_history_on_sync_mode_change<C: SyncComponent, P: Protocol>(
+    mut commands: Commands,
+    interpolated_entities: Query<(), (With<ConfirmedHistory<C>>, With<Interpolated>)>,
+    confirmed_entities: Query<
+        (&Confirmed, &SyncModeOverride<P::ComponentKinds>),
+        Changed<SyncModeOverride<P::ComponentKinds>>,
+    >,
+) where
+    P::Components: SyncMetadata<C>,
+    P::ComponentKinds: FromType<C>,
+{
+    for (confirmed, sync_mode_override) in confirmed_entities.iter() {
+        let Some(p) = confirmed.interpolated else {
+            continue;
+        };
+        if interpolated_entities.contains(p)
+            && SyncModeOverride::mode::<C>(Some(sync_mode_override), P::Components::mode())
+                != ComponentSyncMode::Full
+        {
+            commands
+                .entity(p)
+                .remove::<(ConfirmedHistory<C>, InterpolateStatus<C>)>();
+        }
+    }
+}
+
 /// When we receive a server update for a simple component, we just update the entity directly
 pub(crate) fn apply_confirmed_update_mode_simple<C: SyncComponent, P: Protocol>(
     // TODO: unfortunately we need this to be mutable because of the MapEntities trait even though it's not actually needed...
     mut manager: ResMut<InterpolationManager>,
     mut interpolated_entities: Query<&mut C, (With<Interpolated>, Without<Confirmed>)>,
-    confirmed_entities: Query<(Entity, &Confirmed, Ref<C>)>,
+    confirmed_entities: Query<(
+        Entity,
+        &Confirmed,
+        Ref<C>,
+        Option<&SyncModeOverride<P::ComponentKinds>>,
+    )>,
 ) where
     P::Components: SyncMetadata<C>,
     P::Components: ExternalMapper<C>,
+    P::ComponentKinds: FromType<C>,
 {
-    for (confirmed_entity, confirmed, confirmed_component) in confirmed_entities.iter() {
+    for (confirmed_entity, confirmed, confirmed_component, sync_mode_override) in
+        confirmed_entities.iter()
+    {
         if let Some(p) = confirmed.interpolated {
+            // entities can override the sync mode of fully synced components to Simple/Once
+            if SyncModeOverride::mode::<C>(sync_mode_override, P::Components::mode())
+                != ComponentSyncMode::Simple
+            {
+                continue;
+            }
             if confirmed_component.is_changed() && !confirmed_component.is_added() {
                 if let Ok(mut interpolated_component) = interpolated_entities.get_mut(p) {
                     // for sync-components, we just match the confirmed component
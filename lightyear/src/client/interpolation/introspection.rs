@@ -0,0 +1,134 @@
+//! Inspect the interpolation buffers of the interpolated entities.
+//!
+//! This can be used to build a debug overlay showing the health of the interpolation buffers (how many server
+//! snapshots are available ahead of the interpolation tick), to diagnose stutter.
+//! ```rust,no_run,ignore
+//! fn debug_overlay(buffers: InterpolationBuffers<Position>) {
+//!     for (entity, info) in buffers.iter() {
+//!         info!(?entity, buffered_ticks = info.buffered_ticks(), starving = info.is_starving());
+//!     }
+//! }
+//! ```
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Entity, Query};
+
+use crate::client::components::SyncComponent;
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::shared::tick_manager::Tick;
+
+/// The interpolation state of the component `C` of an entity
+#[derive(Debug, PartialEq)]
+pub struct InterpolationBufferInfo<'a, C> {
+    /// The current interpolation tick
+    pub current_tick: Tick,
+    /// The fraction between [current_tick, current_tick + 1[
+    pub current_overstep: f32,
+    /// The snapshot we are interpolating from
+    pub start: Option<(Tick, &'a C)>,
+    /// The snapshot we are interpolating towards
+    pub end: Option<(Tick, &'a C)>,
+    /// The snapshots received from the server that come after `end`, ordered by tick
+    pub pending: Vec<(Tick, &'a C)>,
+}
+
+impl<'a, C> InterpolationBufferInfo<'a, C> {
+    /// Number of ticks of server data that are available ahead of the interpolation tick
+    pub fn buffered_ticks(&self) -> u16 {
+        self.pending
+            .last()
+            .map(|(tick, _)| *tick)
+            .or(self.end.as_ref().map(|(tick, _)| *tick))
+            .map_or(0, |tick| (tick - self.current_tick).max(0) as u16)
+    }
+
+    /// Returns true if there is no snapshot to interpolate towards, so the entity is not moving
+    pub fn is_starving(&self) -> bool {
+        self.end.is_none()
+    }
+}
+
+/// [`SystemParam`] to inspect the interpolation buffers of the component `C`
+#[derive(SystemParam)]
+pub struct InterpolationBuffers<'w, 's, C: SyncComponent> {
+    query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static InterpolateStatus<C>,
+            &'static ConfirmedHistory<C>,
+        ),
+    >,
+}
+
+impl<'w, 's, C: SyncComponent> InterpolationBuffers<'w, 's, C> {
+    /// Get the interpolation state of the component `C` for an entity
+    pub fn get(&self, entity: Entity) -> Option<InterpolationBufferInfo<'_, C>> {
+        self.query
+            .get(entity)
+            .ok()
+            .map(|(_, status, history)| Self::info(status, history))
+    }
+
+    /// Iterate through the interpolation state of all the entities that interpolate the component `C`
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, InterpolationBufferInfo<'_, C>)> {
+        self.query
+            .iter()
+            .map(|(entity, status, history)| (entity, Self::info(status, history)))
+    }
+
+    fn info<'a>(
+        status: &'a InterpolateStatus<C>,
+        history: &'a ConfirmedHistory<C>,
+    ) -> InterpolationBufferInfo<'a, C> {
+        InterpolationBufferInfo {
+            current_tick: status.current_tick,
+            current_overstep: status.current_overstep,
+            start: status.start.as_ref().map(|(tick, value)| (*tick, value)),
+            end: status.end.as_ref().map(|(tick, value)| (*tick, value)),
+            pending: history.iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_interpolation_buffers() {
+        let mut world = World::new();
+        let mut history = ConfirmedHistory::<Component1>::new();
+        history.buffer.add_item(Tick(8), Component1(8.0));
+        history.buffer.add_item(Tick(6), Component1(6.0));
+        let entity = world
+            .spawn((
+                InterpolateStatus::<Component1> {
+                    start: Some((Tick(2), Component1(2.0))),
+                    end: Some((Tick(4), Component1(4.0))),
+                    current_tick: Tick(3),
+                    current_overstep: 0.5,
+                },
+                history,
+            ))
+            .id();
+
+        world.run_system_once(move |buffers: InterpolationBuffers<Component1>| {
+            let info = buffers.get(entity).unwrap();
+            assert_eq!(info.end, Some((Tick(4), &Component1(4.0))));
+            assert_eq!(
+                info.pending,
+                vec![(Tick(6), &Component1(6.0)), (Tick(8), &Component1(8.0))]
+            );
+            assert_eq!(info.buffered_ticks(), 5);
+            assert!(!info.is_starving());
+            assert_eq!(buffers.iter().count(), 1);
+        });
+    }
+}
@@ -1,15 +1,17 @@
 //! Handles interpolation of entities between server updates
-use std::ops::{Add, Mul};
+use std::ops::{Add, Deref, Mul};
 
-use bevy::prelude::{Added, Commands, Component, Entity, Query, Reflect, Res, ResMut};
+use bevy::prelude::{Added, Commands, Component, Entity, Quat, Query, Reflect, Res, ResMut};
 use tracing::trace;
 
 pub use interpolate::InterpolateStatus;
 pub use interpolation_history::ConfirmedHistory;
 pub use plugin::{add_interpolation_systems, add_prepare_interpolation_systems};
-pub use visual_interpolation::{VisualInterpolateStatus, VisualInterpolationPlugin};
+pub use visual_interpolation::{
+    TransformVisualInterpolationPlugin, VisualInterpolateStatus, VisualInterpolationPlugin,
+};
 
-use crate::client::components::{Confirmed, LerpFn, SyncComponent};
+use crate::client::components::{Confirmed, Lerp, LerpFn, SyncComponent};
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::interpolation::resource::InterpolationManager;
@@ -17,10 +19,17 @@ use crate::protocol::Protocol;
 use crate::shared::replication::components::ShouldBeInterpolated;
 
 mod despawn;
+pub mod events;
+pub mod extrapolate;
+pub mod hermite;
+mod hierarchy;
 mod interpolate;
 pub mod interpolation_history;
+pub mod introspection;
+pub mod opt_out;
 pub mod plugin;
-mod resource;
+pub(crate) mod resource;
+pub mod snap;
 mod spawn;
 mod visual_interpolation;
 
@@ -36,6 +45,42 @@ where
     }
 }
 
+/// Interpolator that uses the [`Lerp`] implementation of the component
+pub struct LerpInterpolator;
+impl<C: Lerp> LerpFn<C> for LerpInterpolator {
+    fn lerp(start: &C, other: &C, t: f32) -> C {
+        start.lerp(other, t)
+    }
+}
+
+/// Interpolator that performs spherical linear interpolation, for rotation components that wrap a [`Quat`].
+///
+/// Linearly interpolating the components of a quaternion does not produce a constant angular velocity,
+/// and can go through non-normalized rotations.
+/// The interpolation always takes the shortest path between the two rotations.
+/// ```rust,ignore
+/// #[component_protocol(protocol = "MyProtocol")]
+/// pub enum MyComponentsProtocol {
+///     #[protocol(sync(mode = "full", lerp = "SlerpInterpolator"))]
+///     Rotation(Rotation),
+/// }
+/// ```
+pub struct SlerpInterpolator;
+impl<C> LerpFn<C> for SlerpInterpolator
+where
+    C: Deref<Target = Quat> + From<Quat>,
+{
+    fn lerp(start: &C, other: &C, t: f32) -> C {
+        let start = **start;
+        let mut end = **other;
+        // q and -q represent the same rotation, pick the one closest to the start
+        if start.dot(end) < 0.0 {
+            end = -end;
+        }
+        C::from(start.slerp(end, t))
+    }
+}
+
 /// Use this if you don't want to use an interpolation function for this component.
 /// (For example if you are running your own interpolation logic)
 pub struct NullInterpolator;
@@ -55,3 +100,33 @@ pub struct Interpolated {
     //  - leave the entity alive until the confirmed entity catches up to it and then it gets removed.
     //    - or do this only for certain components (audio, animation, particles..) -> mode on PredictedComponent
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Rotation(Quat);
+
+    impl Deref for Rotation {
+        type Target = Quat;
+        fn deref(&self) -> &Quat {
+            &self.0
+        }
+    }
+
+    impl From<Quat> for Rotation {
+        fn from(value: Quat) -> Self {
+            Self(value)
+        }
+    }
+
+    #[test]
+    fn test_slerp_shortest_path() {
+        let start = Rotation(Quat::from_rotation_z(0.1));
+        // same rotation as `from_rotation_z(0.3)`, but in the opposite hemisphere
+        let end = Rotation(-Quat::from_rotation_z(0.3));
+        let value = SlerpInterpolator::lerp(&start, &end, 0.5);
+        assert!(value.0.angle_between(Quat::from_rotation_z(0.2)) < 1e-4);
+    }
+}
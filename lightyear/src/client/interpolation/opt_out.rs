@@ -0,0 +1,109 @@
+//! Disable interpolation for an entity at runtime.
+//!
+//! Insert the [`DisableInterpolation`] component on an [`Interpolated`] entity to stop interpolating it (for example
+//! when the entity becomes locally controlled). The interpolation buffers are cleared and the components keep their
+//! current value, so that there is no visual pop; the server updates are not applied to the entity anymore.
+//!
+//! When the component is removed, the interpolation starts again from the current value of the components towards
+//! the next server updates.
+use bevy::prelude::{Commands, Component, Entity, Query, Reflect, RemovedComponents, Res, With};
+use tracing::debug;
+
+use crate::client::components::{SyncComponent, SyncMetadata};
+use crate::client::config::ClientConfig;
+use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::extrapolate::ExtrapolateStatus;
+use crate::client::interpolation::interpolate::{component_interpolation_tick, InterpolateStatus};
+use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+use crate::client::interpolation::plugin::ComponentInterpolationDelay;
+use crate::client::interpolation::Interpolated;
+use crate::prelude::TickManager;
+use crate::protocol::Protocol;
+
+/// Insert this component on an [`Interpolated`] entity to stop interpolating it
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+pub struct DisableInterpolation;
+
+/// Remove the interpolation buffers of the entities where interpolation was disabled
+pub(crate) fn disable_interpolation<C: SyncComponent>(
+    mut commands: Commands,
+    query: Query<Entity, (With<DisableInterpolation>, With<ConfirmedHistory<C>>)>,
+) {
+    for entity in query.iter() {
+        debug!(?entity, "disabling interpolation");
+        commands.entity(entity).remove::<(
+            ConfirmedHistory<C>,
+            InterpolateStatus<C>,
+            ExtrapolateStatus<C>,
+        )>();
+    }
+}
+
+/// Restart the interpolation from the current value of the component when interpolation is enabled again
+pub(crate) fn enable_interpolation<C: SyncComponent, P: Protocol>(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    connection: Res<ConnectionManager<P>>,
+    delay_override: Option<Res<ComponentInterpolationDelay<C>>>,
+    mut removed: RemovedComponents<DisableInterpolation>,
+    query: Query<&C, With<Interpolated>>,
+) where
+    P::Components: SyncMetadata<C>,
+{
+    let (current_tick, current_overstep) = component_interpolation_tick(
+        config.as_ref(),
+        connection.as_ref(),
+        tick_manager.as_ref(),
+        delay_override.as_deref(),
+    );
+    for entity in removed.read() {
+        let Ok(component) = query.get(entity) else {
+            continue;
+        };
+        debug!(?entity, "enabling interpolation");
+        commands.entity(entity).insert((
+            ConfirmedHistory::<C>::new(),
+            InterpolateStatus::<C> {
+                start: Some((current_tick, component.clone())),
+                end: None,
+                current_tick,
+                current_overstep,
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::shared::tick_manager::Tick;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_disable_interpolation() {
+        let mut world = World::new();
+        let entity = world
+            .spawn((
+                Component1(1.5),
+                ConfirmedHistory::<Component1>::new(),
+                InterpolateStatus::<Component1> {
+                    start: Some((Tick(0), Component1(0.0))),
+                    end: Some((Tick(2), Component1(2.0))),
+                    current_tick: Tick(1),
+                    current_overstep: 0.5,
+                },
+                DisableInterpolation,
+            ))
+            .id();
+        world.run_system_once(disable_interpolation::<Component1>);
+        assert!(world.get::<ConfirmedHistory<Component1>>(entity).is_none());
+        assert!(world.get::<InterpolateStatus<Component1>>(entity).is_none());
+        // the component keeps its current value
+        assert_eq!(world.get::<Component1>(entity), Some(&Component1(1.5)));
+    }
+}
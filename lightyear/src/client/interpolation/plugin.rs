@@ -1,25 +1,37 @@
 use std::marker::PhantomData;
 
 use crate::_reexport::FromType;
+use bevy::ecs::schedule::{InternedScheduleLabel, ScheduleLabel};
 use bevy::prelude::*;
 use bevy::utils::Duration;
 
 use crate::client::components::{ComponentSyncMode, SyncComponent, SyncMetadata};
 use crate::client::config::ClientConfig;
 use crate::client::interpolation::despawn::{despawn_interpolated, removed_components};
+use crate::client::interpolation::events::{InterpolationSnapEvent, InterpolationStarvedEvent};
+use crate::client::interpolation::extrapolate::{
+    add_extrapolate_status, extrapolate, ExtrapolationConfig,
+};
+use crate::client::interpolation::hierarchy::sync_interpolated_hierarchy;
 use crate::client::interpolation::interpolate::{
     insert_interpolated_component, interpolate, update_interpolate_status,
 };
+use crate::client::interpolation::opt_out::{
+    disable_interpolation, enable_interpolation, DisableInterpolation,
+};
 use crate::client::interpolation::resource::InterpolationManager;
 use crate::client::interpolation::spawn::spawn_interpolated_entity;
 use crate::client::interpolation::Interpolated;
+use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::sync::client_is_synced;
 use crate::prelude::{ExternalMapper, Mode};
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::Protocol;
+use crate::shared::tick_manager::is_first_substep;
 
 use super::interpolation_history::{
     add_component_history, apply_confirmed_update_mode_full, apply_confirmed_update_mode_simple,
+    remove_history_on_sync_mode_change,
 };
 
 // TODO: maybe this is not an enum and user can specify multiple values, and we use the max delay between all of them?
@@ -34,6 +46,9 @@ pub struct InterpolationDelay {
     /// The higher the server update_rate (i.e. smaller send_interval), the smaller the interpolation delay
     /// Set to 0.0 if you want to only use the Delay
     pub send_interval_ratio: f32,
+    /// If set, the delay is adjusted at runtime from the measured jitter of the server packets,
+    /// instead of being computed from `min_delay` and `send_interval_ratio`
+    pub adaptive: Option<AdaptiveInterpolationDelay>,
 }
 
 impl Default for InterpolationDelay {
@@ -41,6 +56,7 @@ impl Default for InterpolationDelay {
         Self {
             min_delay: Duration::from_millis(0),
             send_interval_ratio: 2.0,
+            adaptive: None,
         }
     }
 }
@@ -56,14 +72,113 @@ impl InterpolationDelay {
         self
     }
 
+    pub fn with_adaptive(mut self, adaptive: AdaptiveInterpolationDelay) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
     /// How much behind the latest server update we want the interpolation time to be
-    pub(crate) fn to_duration(&self, server_send_interval: Duration) -> Duration {
+    ///
+    /// `jitter` is the measured jitter of the arrival times of the server packets.
+    pub(crate) fn to_duration(&self, server_send_interval: Duration, jitter: Duration) -> Duration {
+        if let Some(adaptive) = &self.adaptive {
+            // we need to wait for the next server update, with some margin for the late packets
+            let delay = server_send_interval + jitter.mul_f32(adaptive.jitter_multiple);
+            return delay.min(adaptive.max_delay).max(adaptive.min_delay);
+        }
         // TODO: deal with server_send_interval = 0 (set to frame rate)
         let ratio_value = server_send_interval.mul_f32(self.send_interval_ratio);
         std::cmp::max(ratio_value, self.min_delay)
     }
 }
 
+/// Override the global [`InterpolationDelay`] for the component `C`
+///
+/// This is useful when components are updated at different rates: for example a health bar that rarely changes
+/// can use a bigger delay than the position of the entity, which needs to stay as close as possible to the server.
+/// ```rust,no_run,ignore
+/// app.insert_resource(ComponentInterpolationDelay::<Health>::new(
+///     InterpolationDelay::default().with_send_interval_ratio(4.0),
+/// ));
+/// ```
+#[derive(Resource)]
+pub struct ComponentInterpolationDelay<C> {
+    pub delay: InterpolationDelay,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ComponentInterpolationDelay<C> {
+    pub fn new(delay: InterpolationDelay) -> Self {
+        Self {
+            delay,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Clone for ComponentInterpolationDelay<C> {
+    fn clone(&self) -> Self {
+        Self::new(self.delay.clone())
+    }
+}
+
+/// Adjust the interpolation delay to the jitter of the server packets, to keep the interpolation
+/// buffer healthy with minimal added latency
+#[derive(Clone, Debug, Reflect)]
+pub struct AdaptiveInterpolationDelay {
+    /// How many multiples of the measured jitter we add to the server send interval
+    pub jitter_multiple: f32,
+    /// The delay will never be smaller than this
+    pub min_delay: Duration,
+    /// The delay will never be bigger than this
+    pub max_delay: Duration,
+}
+
+impl Default for AdaptiveInterpolationDelay {
+    fn default() -> Self {
+        Self {
+            jitter_multiple: 3.0,
+            min_delay: Duration::from_millis(0),
+            max_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl AdaptiveInterpolationDelay {
+    pub fn with_jitter_multiple(mut self, jitter_multiple: f32) -> Self {
+        self.jitter_multiple = jitter_multiple;
+        self
+    }
+
+    /// Set the bounds of the delay. Panics if `min_delay` is bigger than `max_delay`.
+    pub fn with_delay_bounds(mut self, min_delay: Duration, max_delay: Duration) -> Self {
+        assert!(
+            min_delay <= max_delay,
+            "the min delay ({min_delay:?}) must not be bigger than the max delay ({max_delay:?})"
+        );
+        self.min_delay = min_delay;
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// Defines how often the interpolated component values change
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum InterpolationUpdateMode {
+    /// The interpolated values change every frame, using the overstep between ticks.
+    /// Use this for smooth visuals (for example physics-driven entities)
+    #[default]
+    Frame,
+    /// The interpolated values only change when the interpolation tick changes (for example for grid-based games).
+    ///
+    /// The [`InterpolationSet::PrepareInterpolation`] and [`InterpolationSet::Interpolate`] sets run in the
+    /// [`FixedPreUpdate`] schedule (once per tick, and not during rollbacks) instead of [`Update`], and the overstep
+    /// between ticks is ignored when computing the [`InterpolateStatus`](crate::client::interpolation::InterpolateStatus),
+    /// so every system that writes the interpolated values (interpolation, extrapolation, hermite, snapping)
+    /// stays consistent.
+    FixedTick,
+}
+
 /// Config to specify how the snapshot interpolation should behave
 #[derive(Clone, Reflect)]
 pub struct InterpolationConfig {
@@ -71,6 +186,11 @@ pub struct InterpolationConfig {
     /// If true, disable the interpolation logic (but still keep the internal component history buffers)
     /// The user will have to manually implement
     pub custom_interpolation_logic: bool,
+    /// If set, keep moving the entities along their last delta when there is no server snapshot
+    /// to interpolate towards, instead of freezing them
+    pub extrapolation: Option<ExtrapolationConfig>,
+    /// Whether the interpolated values are written every frame or every fixed tick
+    pub update_mode: InterpolationUpdateMode,
     // How long are we keeping the history of the confirmed entities so we can interpolate between them?
     // pub(crate) interpolation_buffer_size: Duration,
 }
@@ -81,6 +201,8 @@ impl Default for InterpolationConfig {
         Self {
             delay: InterpolationDelay::default(),
             custom_interpolation_logic: false,
+            extrapolation: None,
+            update_mode: InterpolationUpdateMode::default(),
             // interpolation_buffer_size: Duration::from_millis(100),
         }
     }
@@ -91,6 +213,16 @@ impl InterpolationConfig {
         self.delay = delay;
         self
     }
+
+    pub fn with_update_mode(mut self, update_mode: InterpolationUpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    pub fn with_extrapolation(mut self, extrapolation: ExtrapolationConfig) -> Self {
+        self.extrapolation = Some(extrapolation);
+        self
+    }
 }
 
 pub struct InterpolationPlugin<P: Protocol> {
@@ -146,6 +278,19 @@ pub enum InterpolationSet {
     All,
 }
 
+/// Schedule of the [`InterpolationSet::PrepareInterpolation`] and [`InterpolationSet::Interpolate`] sets,
+/// which depends on the [`InterpolationUpdateMode`]
+pub(crate) fn interpolation_schedule(app: &App) -> InternedScheduleLabel {
+    match app
+        .world
+        .get_resource::<ClientConfig>()
+        .map(|config| config.interpolation.update_mode)
+    {
+        Some(InterpolationUpdateMode::FixedTick) => FixedPreUpdate.intern(),
+        _ => Update.intern(),
+    }
+}
+
 /// Add per-component systems related to interpolation
 pub fn add_prepare_interpolation_systems<C: SyncComponent, P: Protocol>(app: &mut App)
 where
@@ -162,10 +307,11 @@ where
             removed_components::<C>.in_set(InterpolationSet::Despawn),
         ),
     );
+    let schedule = interpolation_schedule(app);
     match P::Components::mode() {
         ComponentSyncMode::Full => {
             app.add_systems(
-                Update,
+                schedule,
                 (
                     apply_confirmed_update_mode_full::<C, P>,
                     update_interpolate_status::<C, P>.run_if(client_is_synced::<P>),
@@ -176,10 +322,25 @@ where
                     .chain()
                     .in_set(InterpolationSet::PrepareInterpolation),
             );
+            app.add_systems(
+                Update,
+                (
+                    disable_interpolation::<C>,
+                    enable_interpolation::<C, P>.run_if(client_is_synced::<P>),
+                    // entities can override the sync mode to Simple/Once (see `Replicate::set_sync_mode`)
+                    remove_history_on_sync_mode_change::<C, P>,
+                )
+                    .in_set(InterpolationSet::SpawnHistory),
+            );
+            app.add_systems(
+                schedule,
+                apply_confirmed_update_mode_simple::<C, P>
+                    .in_set(InterpolationSet::PrepareInterpolation),
+            );
         }
         ComponentSyncMode::Simple => {
             app.add_systems(
-                Update,
+                schedule,
                 apply_confirmed_update_mode_simple::<C, P>
                     .in_set(InterpolationSet::PrepareInterpolation),
             );
@@ -194,9 +355,16 @@ pub fn add_interpolation_systems<C: Component + Clone, P: Protocol>(app: &mut Ap
 where
     P::Components: SyncMetadata<C>,
 {
+    let schedule = interpolation_schedule(app);
     app.add_systems(
-        Update,
-        interpolate::<C, P>.in_set(InterpolationSet::Interpolate),
+        schedule,
+        (
+            add_extrapolate_status::<C>,
+            interpolate::<C, P>,
+            extrapolate::<C, P>,
+        )
+            .chain()
+            .in_set(InterpolationSet::Interpolate),
     );
 }
 
@@ -205,13 +373,20 @@ impl<P: Protocol> Plugin for InterpolationPlugin<P> {
         // REFLECT
         app.register_type::<InterpolationConfig>()
             .register_type::<InterpolationDelay>()
-            .register_type::<Interpolated>();
+            .register_type::<AdaptiveInterpolationDelay>()
+            .register_type::<ExtrapolationConfig>()
+            .register_type::<InterpolationUpdateMode>()
+            .register_type::<Interpolated>()
+            .register_type::<DisableInterpolation>();
 
         P::Components::add_prepare_interpolation_systems(app);
         if !self.config.custom_interpolation_logic {
             P::Components::add_interpolation_systems(app);
         }
 
+        // EVENTS
+        app.add_event::<InterpolationSnapEvent<P>>()
+            .add_event::<InterpolationStarvedEvent<P>>();
         // RESOURCES
         app.init_resource::<InterpolationManager>();
         // SETS
@@ -228,11 +403,32 @@ impl<P: Protocol> Plugin for InterpolationPlugin<P> {
                 .chain(),
         );
         app.configure_sets(Update, InterpolationSet::All.run_if(client_is_synced::<P>));
+        if self.config.update_mode == InterpolationUpdateMode::FixedTick {
+            // the interpolated values are only updated once per tick, and are not affected by rollbacks
+            app.configure_sets(
+                FixedPreUpdate,
+                (
+                    InterpolationSet::PrepareInterpolation,
+                    InterpolationSet::Interpolate,
+                )
+                    .in_set(InterpolationSet::All)
+                    .chain(),
+            );
+            app.configure_sets(
+                FixedPreUpdate,
+                InterpolationSet::All.run_if(
+                    client_is_synced::<P>
+                        .and_then(is_first_substep)
+                        .and_then(not(is_in_rollback)),
+                ),
+            );
+        }
         // SYSTEMS
         app.add_systems(
             Update,
             (
                 spawn_interpolated_entity::<P>.in_set(InterpolationSet::SpawnInterpolation),
+                sync_interpolated_hierarchy.in_set(InterpolationSet::SpawnHistory),
                 despawn_interpolated.in_set(InterpolationSet::Despawn),
             ),
         );
@@ -0,0 +1,165 @@
+//! Snap the interpolated entities instead of interpolating when they teleport.
+//!
+//! If two consecutive server snapshots are very far apart (the entity teleported or respawned), interpolating
+//! between them would sweep the entity across the map during the interpolation window.
+//! With [`InterpolationSnapPlugin`], the component `C` is set directly to the new snapshot value if the distance
+//! between the two snapshots is bigger than a threshold, and an
+//! [`InterpolationSnapEvent`](super::events::InterpolationSnapEvent) is emitted.
+//! ```rust,no_run,ignore
+//! use lightyear::prelude::client::InterpolationSnapPlugin;
+//! let mut app = bevy::app::App::new();
+//! app.add_plugins(InterpolationSnapPlugin::<Position, MyProtocol>::new(10.0, |a, b| a.0.distance(b.0)));
+//! ```
+use std::marker::PhantomData;
+
+use bevy::prelude::{
+    App, Component, Entity, EventWriter, IntoSystemConfigs, Plugin, Query, Res, Resource, With,
+};
+use tracing::debug;
+
+use crate::_reexport::FromType;
+use crate::client::interpolation::events::InterpolationSnapEvent;
+use crate::client::interpolation::interpolate::InterpolateStatus;
+use crate::client::interpolation::plugin::{interpolation_schedule, InterpolationSet};
+use crate::protocol::Protocol;
+
+/// Snap the component `C` to the new snapshot if it differs from the previous one by more than `max_delta`
+pub struct InterpolationSnapPlugin<C, P> {
+    threshold: SnapThreshold<C>,
+    _marker: PhantomData<P>,
+}
+
+impl<C, P> InterpolationSnapPlugin<C, P> {
+    /// `distance` computes the difference between two values of the component
+    pub fn new(max_delta: f32, distance: fn(&C, &C) -> f32) -> Self {
+        Self {
+            threshold: SnapThreshold {
+                max_delta,
+                distance,
+            },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component + Clone, P: Protocol> Plugin for InterpolationSnapPlugin<C, P>
+where
+    P::ComponentKinds: FromType<C>,
+{
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.threshold.clone());
+        app.add_event::<InterpolationSnapEvent<P>>();
+        let schedule = interpolation_schedule(app);
+        app.add_systems(
+            schedule,
+            snap_interpolation::<C, P>
+                .after(InterpolationSet::PrepareInterpolation)
+                .before(InterpolationSet::Interpolate)
+                .in_set(InterpolationSet::All),
+        );
+    }
+}
+
+/// Maximum difference between two consecutive snapshots of the component `C` that is interpolated
+#[derive(Resource)]
+pub struct SnapThreshold<C> {
+    pub max_delta: f32,
+    pub distance: fn(&C, &C) -> f32,
+}
+
+impl<C> Clone for SnapThreshold<C> {
+    fn clone(&self) -> Self {
+        Self {
+            max_delta: self.max_delta,
+            distance: self.distance,
+        }
+    }
+}
+
+/// If the start and end snapshots are too far apart, start from the end snapshot directly
+pub(crate) fn snap_interpolation<C: Component + Clone, P: Protocol>(
+    threshold: Res<SnapThreshold<C>>,
+    mut events: EventWriter<InterpolationSnapEvent<P>>,
+    // only consider entities where the component was already inserted, to avoid interfering with the insertion
+    mut query: Query<(Entity, &mut InterpolateStatus<C>), With<C>>,
+) where
+    P::ComponentKinds: FromType<C>,
+{
+    for (entity, mut status) in query.iter_mut() {
+        let (Some((start_tick, start_value)), Some((end_tick, end_value))) =
+            (&status.start, &status.end)
+        else {
+            continue;
+        };
+        if start_tick == end_tick {
+            continue;
+        }
+        let delta = (threshold.distance)(start_value, end_value);
+        if delta > threshold.max_delta {
+            debug!(
+                ?entity,
+                ?start_tick,
+                ?end_tick,
+                ?delta,
+                "snapshots are too far apart, snapping instead of interpolating"
+            );
+            events.send(InterpolationSnapEvent {
+                entity,
+                component: P::ComponentKinds::from_type(),
+                tick: *end_tick,
+            });
+            status.start = status.end.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Events, World};
+
+    use crate::shared::tick_manager::Tick;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    fn status(end: f32) -> InterpolateStatus<Component1> {
+        InterpolateStatus {
+            start: Some((Tick(0), Component1(0.0))),
+            end: Some((Tick(10), Component1(end))),
+            current_tick: Tick(2),
+            current_overstep: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_snap_interpolation() {
+        let mut world = World::new();
+        world.init_resource::<Events<InterpolationSnapEvent<MyProtocol>>>();
+        world.insert_resource(SnapThreshold::<Component1> {
+            max_delta: 5.0,
+            distance: |a, b| (a.0 - b.0).abs(),
+        });
+        let walk = world.spawn((Component1(0.0), status(1.0))).id();
+        let teleport = world.spawn((Component1(0.0), status(100.0))).id();
+        world.run_system_once(snap_interpolation::<Component1, MyProtocol>);
+
+        assert_eq!(
+            world.get::<InterpolateStatus<Component1>>(walk),
+            Some(&status(1.0))
+        );
+        let teleport_status = world
+            .get::<InterpolateStatus<Component1>>(teleport)
+            .unwrap();
+        assert_eq!(teleport_status.start, Some((Tick(10), Component1(100.0))));
+        assert_eq!(teleport_status.interpolation_fraction(), Some(0.0));
+
+        let events: Vec<_> = world
+            .resource_mut::<Events<InterpolationSnapEvent<MyProtocol>>>()
+            .drain()
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].entity, teleport);
+        assert_eq!(events[0].tick, Tick(10));
+    }
+}
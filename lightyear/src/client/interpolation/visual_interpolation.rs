@@ -27,6 +27,11 @@
 //!     commands.spawn().insert(VisualInterpolateState::<Component1>::default());
 //! }
 //! ```
+//!
+//! For the `Transform` component (which is usually not part of the protocol), use the [`TransformVisualInterpolationPlugin`]
+//! instead: it automatically visually interpolates the `Transform` of all predicted entities. Add the
+//! `VisualInterpolateStatus::<Transform>` component manually to other entities (for example local entities that are
+//! simulated in FixedUpdate) to also interpolate them.
 
 // TODO: in post-update, interpolate the visual state of the game between with 1 tick of delay.
 // - we need to store the component values of the previous tick
@@ -34,11 +39,15 @@
 // - in PreUpdate, we restore the component value to the previous tick values
 
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
 
-use crate::_reexport::{ComponentProtocol, FromType};
-use crate::client::components::{ComponentSyncMode, SyncComponent, SyncMetadata};
+use crate::client::components::{ComponentSyncMode, LerpFn, SyncComponent, SyncMetadata};
+use crate::client::prediction::plugin::PredictionSet;
+use crate::client::prediction::Predicted;
 use crate::prelude::client::InterpolationSet;
 use crate::prelude::{Protocol, TickManager, TimeManager};
+use crate::shared::tick_manager::is_last_substep;
+use crate::utils::bevy::TransformLinearInterpolation;
 
 pub struct VisualInterpolationPlugin<C: SyncComponent, P: Protocol>
 where
@@ -61,39 +70,91 @@ where
 impl<C: SyncComponent, P: Protocol> Plugin for VisualInterpolationPlugin<C, P>
 where
     P::Components: SyncMetadata<C>,
-    P::ComponentKinds: FromType<C>,
 {
     fn build(&self, app: &mut App) {
-        // REFLECTION
-        app.register_type::<VisualInterpolateMarker>();
-        // SETS
-        app.configure_sets(PreUpdate, InterpolationSet::RestoreVisualInterpolation);
-        app.configure_sets(
-            FixedPostUpdate,
-            InterpolationSet::UpdateVisualInterpolationState,
-        );
-        app.configure_sets(PostUpdate, InterpolationSet::VisualInterpolation);
-
-        // SYSTEMS
         if P::Components::mode() == ComponentSyncMode::Full {
-            app.add_systems(
-                PreUpdate,
-                restore_from_visual_interpolation::<C, P>
-                    .in_set(InterpolationSet::RestoreVisualInterpolation),
-            );
-            app.add_systems(
-                FixedPostUpdate,
-                update_visual_interpolation_status::<C>
-                    .in_set(InterpolationSet::UpdateVisualInterpolationState),
-            );
-            app.add_systems(
-                PostUpdate,
-                visual_interpolation::<C, P>.in_set(InterpolationSet::VisualInterpolation),
+            add_visual_interpolation_systems::<C, <P::Components as SyncMetadata<C>>::Interpolator>(
+                app,
             );
         }
     }
 }
 
+/// Visually interpolate the `Transform` of predicted entities between the previous and the current FixedUpdate tick
+#[derive(Default)]
+pub struct TransformVisualInterpolationPlugin;
+
+impl Plugin for TransformVisualInterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        add_visual_interpolation_systems::<Transform, TransformLinearInterpolation>(app);
+        app.add_systems(
+            PreUpdate,
+            add_predicted_transform_visual_interpolation
+                .in_set(InterpolationSet::RestoreVisualInterpolation),
+        );
+    }
+}
+
+fn add_visual_interpolation_systems<C: Component + Clone, L: LerpFn<C> + 'static>(app: &mut App) {
+    // REFLECTION
+    app.register_type::<VisualInterpolateMarker>();
+    // SETS
+    app.configure_sets(
+        PreUpdate,
+        InterpolationSet::RestoreVisualInterpolation
+            // the visual correction offset must be removed first, and we need to restore the real value
+            // before checking for rollbacks
+            .after(PredictionSet::RestoreVisualCorrection)
+            .before(PredictionSet::CheckRollback),
+    );
+    app.configure_sets(
+        FixedPostUpdate,
+        // the overstep is a fraction of the network tick, so we record the values once per tick
+        InterpolationSet::UpdateVisualInterpolationState.run_if(is_last_substep),
+    );
+    app.configure_sets(
+        PostUpdate,
+        InterpolationSet::VisualInterpolation
+            .before(PredictionSet::VisualCorrection)
+            .before(TransformSystem::TransformPropagate),
+    );
+
+    // SYSTEMS
+    app.add_systems(
+        PreUpdate,
+        restore_from_visual_interpolation::<C>.in_set(InterpolationSet::RestoreVisualInterpolation),
+    );
+    app.add_systems(
+        FixedPostUpdate,
+        update_visual_interpolation_status::<C>
+            .in_set(InterpolationSet::UpdateVisualInterpolationState),
+    );
+    app.add_systems(
+        PostUpdate,
+        visual_interpolation::<C, L>.in_set(InterpolationSet::VisualInterpolation),
+    );
+}
+
+/// Enable visual interpolation of the `Transform` on the predicted entities
+#[allow(clippy::type_complexity)]
+pub(crate) fn add_predicted_transform_visual_interpolation(
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        (
+            With<Predicted>,
+            With<Transform>,
+            Without<VisualInterpolateStatus<Transform>>,
+        ),
+    >,
+) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(VisualInterpolateStatus::<Transform>::default());
+    }
+}
+
 // TODO: we might want to add this automatically to some entities that are predicted?
 /// Component that stores the previous value of a component for visual interpolation
 /// For now we will only use this to interpolate components that are updated during the FixedUpdate schedule.
@@ -125,18 +186,13 @@ impl<C: Component> Default for VisualInterpolateStatus<C> {
 #[derive(Component, Debug, Reflect)]
 pub struct VisualInterpolateMarker;
 
-// TODO: explore how we could allow this for non-marker components, user would need to specify the interpolation function?
-//  (to avoid orphan rule)
-/// Currently we will only support components that are present in the protocol and have a SyncMetadata implementation
-pub(crate) fn visual_interpolation<C: SyncComponent, P: Protocol>(
+/// Interpolate the component between the previous and the current tick values, using the interpolation function `L`
+pub(crate) fn visual_interpolation<C: Component + Clone, L: LerpFn<C>>(
     tick_manager: Res<TickManager>,
     time_manager: Res<TimeManager>,
     mut query: Query<(&mut C, &VisualInterpolateStatus<C>)>,
-) where
-    P::Components: SyncMetadata<C>,
-    P::ComponentKinds: FromType<C>,
-{
-    let kind = P::ComponentKinds::from_type();
+) {
+    let kind = std::any::type_name::<C>();
     let tick = tick_manager.tick();
     let overstep = time_manager.overstep();
     for (mut component, interpolate_status) in query.iter_mut() {
@@ -154,14 +210,13 @@ pub(crate) fn visual_interpolation<C: SyncComponent, P: Protocol>(
             ?overstep,
             "Visual interpolation of fixed-update component!"
         );
-        *component.bypass_change_detection() =
-            P::Components::lerp(previous_value, current_value, overstep);
+        *component.bypass_change_detection() = L::lerp(previous_value, current_value, overstep);
     }
 }
 
 /// Update the previous and current tick values.
 /// Runs in FixedUpdate after FixedUpdate::Main (where the component values are updated)
-pub(crate) fn update_visual_interpolation_status<C: SyncComponent>(
+pub(crate) fn update_visual_interpolation_status<C: Component + Clone>(
     mut query: Query<(Ref<C>, &mut VisualInterpolateStatus<C>)>,
 ) {
     for (component, mut interpolate_status) in query.iter_mut() {
@@ -180,12 +235,10 @@ pub(crate) fn update_visual_interpolation_status<C: SyncComponent>(
 }
 
 /// Restore the component value to the non-interpolated value
-pub(crate) fn restore_from_visual_interpolation<C: SyncComponent, P: Protocol>(
+pub(crate) fn restore_from_visual_interpolation<C: Component + Clone>(
     mut query: Query<(&mut C, &mut VisualInterpolateStatus<C>)>,
-) where
-    P::ComponentKinds: FromType<C>,
-{
-    let kind = P::ComponentKinds::from_type();
+) {
+    let kind = std::any::type_name::<C>();
     for (mut component, interpolate_status) in query.iter_mut() {
         if let Some(current_value) = &interpolate_status.current_value {
             trace!(?kind, "Restoring visual interpolation");
@@ -197,6 +250,7 @@ pub(crate) fn restore_from_visual_interpolation<C: SyncComponent, P: Protocol>(
 #[cfg(test)]
 mod tests {
     use approx::assert_relative_eq;
+    use bevy::ecs::system::RunSystemOnce;
     use bevy::prelude::*;
     use bevy::utils::Duration;
 
@@ -224,7 +278,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
@@ -958,4 +1012,31 @@ mod tests {
             max_relative = 0.1
         );
     }
+
+    #[test]
+    fn test_predicted_transform_visual_interpolation() {
+        let mut world = World::new();
+        let entity = world
+            .spawn((
+                Predicted {
+                    confirmed_entity: None,
+                },
+                Transform::from_xyz(1.0, 0.0, 0.0),
+            ))
+            .id();
+        world.run_system_once(add_predicted_transform_visual_interpolation);
+        assert!(world
+            .get::<VisualInterpolateStatus<Transform>>(entity)
+            .is_some());
+
+        // the transform is moved during the fixed-update tick
+        world.get_mut::<Transform>(entity).unwrap().translation.x = 2.0;
+        world.run_system_once(update_visual_interpolation_status::<Transform>);
+        world.get_mut::<Transform>(entity).unwrap().translation.x = 3.0;
+        world.run_system_once(update_visual_interpolation_status::<Transform>);
+        // the visual interpolation modified the transform, we restore the value of the current tick
+        world.get_mut::<Transform>(entity).unwrap().translation.x = 2.5;
+        world.run_system_once(restore_from_visual_interpolation::<Transform>);
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation.x, 3.0);
+    }
 }
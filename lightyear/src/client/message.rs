@@ -29,6 +29,12 @@ pub enum ClientMessage<P: Protocol> {
     // the reason why we include sync here instead of doing another MessageManager is so that
     // the sync messages can be added to packets that have other messages
     Sync(SyncMessage),
+    /// A new connect token, sent to extend the session of the client without reconnecting
+    #[bitcode_hint(frequency = 1)]
+    RefreshToken(Vec<u8>),
+    /// The client restarted the sync handshake, so the server should reset its time nudge
+    #[bitcode_hint(frequency = 1)]
+    Resync,
 }
 
 impl<P: Protocol> BitSerializable for ClientMessage<P> {
@@ -140,6 +146,12 @@ impl<P: Protocol> ClientMessage<P> {
                     metrics::counter!("send_pong", "channel" => channel_name).increment(1);
                 }
             },
+            ClientMessage::RefreshToken(_) => {
+                trace!(channel = ?channel_name, "Sending token refresh");
+            }
+            ClientMessage::Resync => {
+                trace!(channel = ?channel_name, "Sending resync");
+            }
         }
     }
 }
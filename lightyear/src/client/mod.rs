@@ -19,6 +19,8 @@ pub mod prediction;
 
 pub mod sync;
 
+pub mod tick_snap;
+
 mod diagnostics;
 mod easings;
 #[cfg_attr(docsrs, doc(cfg(feature = "leafwing")))]
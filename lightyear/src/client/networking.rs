@@ -6,18 +6,25 @@ use std::ops::DerefMut;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick, SystemParam, SystemState};
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
-use tracing::{error, trace};
+use bevy::utils::Duration;
+use tracing::{error, info, trace, warn};
 
 use crate::_reexport::{ClientMarker, ReplicationSend};
 use crate::client::components::Confirmed;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
-use crate::client::events::{ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent};
+use crate::client::events::{
+    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, PacketLostEvent,
+};
 use crate::client::interpolation::Interpolated;
+use crate::client::prediction::plugin::is_in_rollback;
 use crate::client::prediction::Predicted;
-use crate::client::sync::SyncSet;
+use crate::client::sync::{
+    record_sync_diagnostics, update_timelines, InterpolationTime, PredictionTime,
+    ServerTimeEstimate, SyncDiagnostics, SyncEvent, SyncSet,
+};
 use crate::connection::client::{ClientConnection, NetClient, NetConfig};
-use crate::prelude::{SharedConfig, TickManager, TimeManager};
+use crate::prelude::{FixedUpdateSet, SharedConfig, TickManager, TimeManager};
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
@@ -25,7 +32,7 @@ use crate::shared::config::Mode;
 use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
 use crate::shared::sets::InternalMainSet;
 use crate::shared::tick_manager::TickEvent;
-use crate::shared::time_manager::is_client_ready_to_send;
+use crate::shared::time_manager::{is_client_ready_to_send, network_delta};
 use crate::transport::io::IoState;
 
 pub(crate) struct ClientNetworkingPlugin<P: Protocol> {
@@ -45,6 +52,13 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
         app
             // STATE
             .init_state::<NetworkingState>()
+            // RESOURCES
+            .init_resource::<PredictionTime>()
+            .init_resource::<InterpolationTime>()
+            .init_resource::<ServerTimeEstimate>()
+            .init_resource::<SyncDiagnostics>()
+            // EVENTS
+            .add_event::<SyncEvent>()
             // SYSTEM SETS
             .configure_sets(
                 PreUpdate,
@@ -67,14 +81,30 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
             // SYSTEMS
             .add_systems(
                 PreUpdate,
-                receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                (
+                    receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                    update_pause::<P>.after(InternalMainSet::<ClientMarker>::Receive),
+                ),
+            )
+            // the tick duration changes are applied at the tick chosen by the server
+            .add_systems(
+                FixedFirst,
+                update_tick_duration::<P>
+                    .after(FixedUpdateSet::TickUpdate)
+                    .run_if(not(is_in_rollback)),
             )
             .add_systems(
                 PostUpdate,
                 (
                     send::<P>.in_set(InternalMainSet::<ClientMarker>::SendPackets),
                     // TODO: update virtual time with Time<Real> so we have more accurate time at Send time.
-                    sync_update::<P>.in_set(SyncSet),
+                    (
+                        sync_update::<P>,
+                        update_timelines::<P>,
+                        record_sync_diagnostics::<P>,
+                    )
+                        .chain()
+                        .in_set(SyncSet),
                 ),
             );
 
@@ -104,7 +134,75 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
         app.add_systems(OnEnter(NetworkingState::Connected), on_connect);
 
         // DISCONNECTED
-        app.add_systems(OnEnter(NetworkingState::Disconnected), on_disconnect);
+        app.add_systems(
+            OnEnter(NetworkingState::Disconnected),
+            (on_disconnect, lose_sync::<P>),
+        );
+    }
+}
+
+/// Apply the tick duration received from the server, once the client reaches the tick of the change.
+///
+/// This runs right after the tick is incremented, so that the new tick is the first one that lasts the new
+/// tick duration. If the change arrives after the client already simulated that tick, it is applied right away.
+pub(crate) fn update_tick_duration<P: Protocol>(
+    mut connection: ResMut<ConnectionManager<P>>,
+    mut config: ResMut<ClientConfig>,
+    mut tick_manager: ResMut<TickManager>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    let current_tick = tick_manager.tick();
+    let mut new_tick_duration = None;
+    while let Some((_, tick_duration)) = connection
+        .pending_tick_durations
+        .front()
+        .filter(|(tick, _)| current_tick >= *tick)
+        .copied()
+    {
+        connection.pending_tick_durations.pop_front();
+        new_tick_duration = Some(tick_duration);
+    }
+    let Some(tick_duration) = new_tick_duration else {
+        return;
+    };
+    let previous = tick_manager.config.tick_duration;
+    if tick_duration == previous {
+        return;
+    }
+    info!(
+        ?previous,
+        ?tick_duration,
+        "The server changed the tick duration"
+    );
+    tick_manager.set_tick_duration(tick_duration, &mut fixed_time);
+    config.shared.tick.tick_duration = tick_duration;
+    // NOTE: the tick generation computed from the server pongs assumes a constant tick duration,
+    //  so it might be inaccurate after a change
+    connection
+        .sync_manager
+        .rescale_tick_duration(previous, tick_duration);
+}
+
+/// Pause or resume the simulation when the server asks for it
+pub(crate) fn update_pause<P: Protocol>(
+    mut connection: ResMut<ConnectionManager<P>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Some((pause, server_tick)) = connection.pending_pause.take() else {
+        return;
+    };
+    if pause {
+        info!(?server_tick, "The server paused the simulation");
+        virtual_time.pause();
+    } else {
+        info!(?server_tick, "The server resumed the simulation");
+        virtual_time.unpause();
+        // the server's tick did not advance while the simulation was paused: restart the estimate of the
+        // server time from the tick at which the simulation resumed
+        connection.sync_manager.latest_received_server_tick = Some(server_tick);
+        connection
+            .sync_manager
+            .duration_since_latest_received_server_tick = Duration::default();
     }
 }
 
@@ -131,11 +229,16 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                 world.resource_scope(
                                                     |world: &mut World, mut next_state: Mut<NextState<NetworkingState>>| {
                                                         let delta = world.resource::<Time<Virtual>>().delta();
+                                                        let real_delta = world.resource::<Time<Real>>().delta();
+                                                        // keep the connection alive even if the simulation is paused
+                                                        let network_delta = network_delta(world);
+                                                        let disconnect_on_invalid_packet = world.resource::<ClientConfig>().packet.disconnect_on_invalid_packet;
                                                         // UPDATE: update client state, send keep-alives, receive packets from io, update connection sync state
                                                         time_manager.update(delta);
+                                                        time_manager.update_real(real_delta);
                                                         trace!(time = ?time_manager.current_time(), tick = ?tick_manager.tick(), "receive");
                                                         let _ = netclient
-                                                            .try_update(delta.as_secs_f64())
+                                                            .try_update(network_delta.as_secs_f64())
                                                             .map_err(|e| {
                                                                 error!("Error updating netcode: {}", e);
                                                             });
@@ -155,9 +258,26 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
 
                                                         // RECV PACKETS: buffer packets into message managers
                                                         while let Some(packet) = netclient.recv() {
-                                                            connection
-                                                                .recv_packet(packet, tick_manager.as_ref())
+                                                            // the packet was sent by the server, it could contain invalid data
+                                                            if let Err(e) = connection.recv_packet(packet, tick_manager.as_ref()) {
+                                                                error!("Error receiving packet from the server, dropping it: {:?}", e);
+                                                                if disconnect_on_invalid_packet {
+                                                                    let _ = netclient.disconnect();
+                                                                    next_state.set(NetworkingState::Disconnected);
+                                                                    break;
+                                                                }
+                                                            }
+                                                        }
+                                                        // LOST PACKETS: notify about the packets we sent that got lost
+                                                        let lost_packets = connection.message_manager.drain_lost_packets();
+                                                        if !lost_packets.is_empty() {
+                                                            let mut packet_lost_event_writer = world
+                                                                .get_resource_mut::<Events<PacketLostEvent>>()
                                                                 .unwrap();
+                                                            for (packet_id, lost_messages) in lost_packets {
+                                                                packet_lost_event_writer
+                                                                    .send(PacketLostEvent::new(packet_id, lost_messages, ()));
+                                                            }
                                                         }
                                                         // RECEIVE: receive packets from message managers
                                                         let mut events = connection.receive(
@@ -229,11 +349,13 @@ pub(crate) fn send<P: Protocol>(
     let packet_bytes = connection
         .send_packets(time_manager.as_ref(), tick_manager.as_ref())
         .unwrap();
-    for packet_byte in packet_bytes {
+    for packet_byte in packet_bytes.iter() {
         let _ = netcode.send(packet_byte.as_slice()).map_err(|e| {
             error!("Error sending packet: {}", e);
         });
     }
+    // return the payloads to the pool so they can be re-used for the next packets
+    connection.message_manager.release_payloads(packet_bytes);
 
     // no need to clear the connection, because we already std::mem::take it
     // client.connection.clear();
@@ -253,8 +375,33 @@ pub(crate) fn sync_update<P: Protocol>(
     mut tick_manager: ResMut<TickManager>,
     mut virtual_time: ResMut<Time<Virtual>>,
     mut tick_events: EventWriter<TickEvent>,
+    mut sync_events: EventWriter<SyncEvent>,
 ) {
     let connection = connection.into_inner();
+    // the host clock jumped (sleep/suspend, debugger pause): the time estimates are meaningless,
+    // so we redo the handshake instead of trying to catch up with huge speed adjustments
+    if connection.sync_manager.is_synced() {
+        if let Some(jump) = config
+            .sync
+            .clock_jump_threshold
+            .and_then(|threshold| time_manager.clock_jump(threshold))
+        {
+            warn!(?jump, "Host clock jumped, resyncing with the server");
+            connection.sync_manager.resync(time_manager.deref_mut());
+            connection.ping_manager.reset_sync_stats();
+            // the server nudge was reset, the server must not assume that it still applies
+            let _ = connection.send_resync().map_err(|e| {
+                error!("could not notify the server of the resync: {:?}", e);
+            });
+            virtual_time.set_relative_speed(time_manager.get_relative_speed());
+            sync_events.send(SyncEvent::SyncLost);
+        }
+    }
+    let was_synced = connection.sync_manager.is_synced();
+    // keep the generation of our tick consistent with the server's
+    if let Some(server_extended_tick) = connection.latest_received_server_extended_tick() {
+        tick_manager.sync_generation(server_extended_tick);
+    }
     // NOTE: this triggers change detection
     // Handle pongs, update RTT estimates, update client prediction time
     if let Some(tick_event) = connection.sync_manager.update(
@@ -266,6 +413,11 @@ pub(crate) fn sync_update<P: Protocol>(
     ) {
         tick_events.send(tick_event);
     }
+    if !was_synced && connection.sync_manager.is_synced() {
+        sync_events.send(SyncEvent::Synced {
+            tick: tick_manager.tick(),
+        });
+    }
 
     if connection.sync_manager.is_synced() {
         if let Some(tick_event) = connection.sync_manager.update_prediction_time(
@@ -273,6 +425,9 @@ pub(crate) fn sync_update<P: Protocol>(
             tick_manager.deref_mut(),
             &connection.ping_manager,
         ) {
+            if let Some(sync_event) = connection.sync_manager.snap_event(&tick_event) {
+                sync_events.send(sync_event);
+            }
             tick_events.send(tick_event);
         }
         let relative_speed = time_manager.get_relative_speed();
@@ -280,6 +435,16 @@ pub(crate) fn sync_update<P: Protocol>(
     }
 }
 
+/// Notify that the client is not synced anymore after a disconnection
+fn lose_sync<P: Protocol>(
+    connection: Res<ConnectionManager<P>>,
+    mut sync_events: EventWriter<SyncEvent>,
+) {
+    if connection.sync_manager.is_synced() {
+        sync_events.send(SyncEvent::SyncLost);
+    }
+}
+
 #[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NetworkingState {
     #[default]
@@ -371,7 +536,7 @@ fn on_disconnect(
 
     // no need to update the io state, because we will recreate a new `ClientConnection`
     // for the next connection attempt
-    disconnect_event_writer.send(DisconnectEvent::new(()));
+    disconnect_event_writer.send(DisconnectEvent::new(netcode.disconnect_reason()));
 
     // in host-server mode, we also want to send a connect event to the server
     if config.shared.mode == Mode::HostServer {
@@ -434,7 +599,8 @@ fn rebuild_net_config<P: Protocol>(world: &mut World) {
     // drop the previous client connection to make sure we release any resources before creating the new one
     world.remove_resource::<ClientConnection>();
     // insert the new client connection
-    let netclient = client_config.net.clone().build_client();
+    let mut netclient = client_config.net.clone().build_client();
+    netclient.set_extended_ticks(client_config.shared.tick.extended);
     world.insert_resource(netclient);
 }
 
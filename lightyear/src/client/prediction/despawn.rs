@@ -1,14 +1,21 @@
 use std::marker::PhantomData;
 
 use bevy::ecs::system::{Command, EntityCommands};
+#[cfg(feature = "render")]
+use bevy::prelude::Visibility;
 use bevy::prelude::{
-    Commands, Component, Entity, Query, Reflect, RemovedComponents, ResMut, With, Without, World,
+    Commands, Component, Entity, Query, Reflect, RemovedComponents, Res, ResMut, With, Without,
+    World,
 };
 use tracing::{debug, error, trace};
 
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent, SyncMetadata};
 use crate::client::config::ClientConfig;
+use crate::client::prediction::predicted_history::PredictionHistory;
 use crate::client::prediction::resource::PredictionManager;
+use crate::client::prediction::rollback::{
+    ExcludedFromRollback, Rollback, RollbackGroup, RollbackState,
+};
 use crate::client::prediction::Predicted;
 use crate::prelude::{Mode, ShouldBePredicted, TickManager};
 use crate::protocol::Protocol;
@@ -32,10 +39,32 @@ pub(crate) struct PredictionDespawnMarker {
     pub(crate) death_tick: Tick,
 }
 
+/// Component added to a Predicted entity whose despawn was predicted, while we wait for the server
+/// to confirm it (see [`PredictionConfig::despawn_grace_ticks`](crate::client::prediction::plugin::PredictionConfig::despawn_grace_ticks)).
+///
+/// The entity is still alive, so systems that should ignore dead entities can filter on `Without<PredictionDespawnPending>`.
+/// If the `render` feature is enabled, the entity is also hidden.
+#[derive(Component, PartialEq, Debug, Clone)]
+pub struct PredictionDespawnPending {
+    /// Tick at which the despawn was predicted
+    pub death_tick: Tick,
+    /// Visibility of the entity before it was hidden
+    #[cfg(feature = "render")]
+    previous_visibility: Option<Visibility>,
+}
+
 impl<P: Protocol> Command for PredictionDespawnCommand<P> {
     fn apply(self, world: &mut World) {
         let tick_manager = world.get_resource::<TickManager>().unwrap();
-        let current_tick = tick_manager.tick();
+        // during rollback, the despawn happens at the tick that is being resimulated
+        let current_tick = match world.get_resource::<Rollback>().map(|r| r.state) {
+            Some(RollbackState::ShouldRollback { current_tick }) => current_tick,
+            _ => tick_manager.tick(),
+        };
+        let despawn_grace_ticks = world
+            .resource::<ClientConfig>()
+            .prediction
+            .despawn_grace_ticks;
 
         // if we are in host server mode, there is no rollback so we can despawn the entity immediately
         if world.resource::<ClientConfig>().shared.mode == Mode::HostServer {
@@ -45,7 +74,24 @@ impl<P: Protocol> Command for PredictionDespawnCommand<P> {
         let mut predicted_entity_to_despawn: Option<Entity> = None;
 
         if let Some(mut entity) = world.get_entity_mut(self.entity) {
-            if entity.get::<Predicted>().is_some() || entity.get::<ShouldBePredicted>().is_some() {
+            if despawn_grace_ticks.is_some() && entity.get::<Predicted>().is_some() {
+                // keep the entity alive (but hidden) until the server confirms the despawn
+                if entity.get::<PredictionDespawnPending>().is_none() {
+                    trace!("inserting prediction despawn pending");
+                    let pending = PredictionDespawnPending {
+                        death_tick: current_tick,
+                        #[cfg(feature = "render")]
+                        previous_visibility: entity.get::<Visibility>().copied(),
+                    };
+                    #[cfg(feature = "render")]
+                    if let Some(mut visibility) = entity.get_mut::<Visibility>() {
+                        *visibility = Visibility::Hidden;
+                    }
+                    entity.insert(pending);
+                }
+            } else if entity.get::<Predicted>().is_some()
+                || entity.get::<ShouldBePredicted>().is_some()
+            {
                 // if this is a predicted or pre-predicted entity, do not despawn the entity immediately but instead
                 // add a PredictionDespawn component to it to mark that it should be despawned as soon
                 // as the confirmed entity catches up to it
@@ -109,6 +155,55 @@ pub(crate) fn despawn_confirmed(
     }
 }
 
+/// Resurrect the entity whose despawn was predicted, by removing the [`PredictionDespawnPending`] component
+fn resurrect(commands: &mut Commands, entity: Entity, pending: &PredictionDespawnPending) {
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.remove::<PredictionDespawnPending>();
+    #[cfg(feature = "render")]
+    if let Some(visibility) = pending.previous_visibility {
+        entity_commands.insert(visibility);
+    }
+    #[cfg(not(feature = "render"))]
+    let _ = pending;
+}
+
+/// If the server didn't confirm the predicted despawn within the grace period, the prediction was wrong:
+/// resurrect the entity.
+///
+/// (If the server did confirm it, the entity was already despawned in [`despawn_confirmed`])
+pub(crate) fn resurrect_expired_pending_despawn(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    query: Query<(Entity, &PredictionDespawnPending)>,
+) {
+    let Some(grace_ticks) = config.prediction.despawn_grace_ticks else {
+        return;
+    };
+    let current_tick = tick_manager.tick();
+    for (entity, pending) in query.iter() {
+        if current_tick - pending.death_tick > grace_ticks as i16 {
+            debug!(?entity, death_tick = ?pending.death_tick, "predicted despawn was not confirmed, resurrecting entity");
+            resurrect(&mut commands, entity, pending);
+        }
+    }
+}
+
+/// Before a rollback, resurrect the entities whose despawn was predicted.
+///
+/// If the despawn is still predicted during the resimulation, the entity will be marked as pending again.
+pub(crate) fn clear_pending_despawn_for_rollback(
+    mut commands: Commands,
+    rollback: Res<Rollback>,
+    query: Query<(Entity, &PredictionDespawnPending, Option<&RollbackGroup>)>,
+) {
+    for (entity, pending, group) in query.iter() {
+        if rollback.is_rolled_back(group) {
+            resurrect(&mut commands, entity, pending);
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct RemovedCache<C: Component>(pub Option<C>);
 
@@ -116,32 +211,43 @@ pub struct RemovedCache<C: Component>(pub Option<C>);
 /// Instead of despawning the entity, we remove all components except the history and the predicted marker
 pub(crate) fn remove_component_for_despawn_predicted<C: SyncComponent, P: Protocol>(
     mut commands: Commands,
-    full_query: Query<Entity, (With<C>, With<PredictionDespawnMarker>)>,
-    simple_query: Query<(Entity, &C), With<PredictionDespawnMarker>>,
+    full_query: Query<
+        Entity,
+        (
+            With<C>,
+            With<PredictionDespawnMarker>,
+            With<PredictionHistory<C>>,
+            Without<ExcludedFromRollback>,
+        ),
+    >,
+    simple_query: Query<
+        (Entity, &C),
+        (
+            With<PredictionDespawnMarker>,
+            Without<PredictionHistory<C>>,
+            Without<ExcludedFromRollback>,
+        ),
+    >,
 ) where
     P::Components: SyncMetadata<C>,
 {
-    match P::Components::mode() {
-        // for full components, we can delete the component
-        // it will get re-instated during rollback if the confirmed entity doesn't get despawned
-        ComponentSyncMode::Full => {
-            for entity in full_query.iter() {
-                trace!("removing full component for prediction_despawn");
-                commands.entity(entity).remove::<C>();
-            }
-        }
-        // for simple/once components, there is no rollback, we can just cache them temporarily
-        // and restore them in case of rollback
-        ComponentSyncMode::Simple | ComponentSyncMode::Once => {
-            for (entity, component) in simple_query.iter() {
-                trace!("removing simple/once component for prediction_despawn");
-                commands
-                    .entity(entity)
-                    .remove::<C>()
-                    .insert(RemovedCache(Some(component.clone())));
-            }
-        }
-        ComponentSyncMode::None => {}
+    if P::Components::mode() == ComponentSyncMode::None {
+        return;
+    }
+    // for full components, we can delete the component
+    // it will get re-instated during rollback if the confirmed entity doesn't get despawned
+    for entity in full_query.iter() {
+        trace!("removing full component for prediction_despawn");
+        commands.entity(entity).remove::<C>();
+    }
+    // for simple/once components (or full components whose sync mode was overridden), there is no rollback,
+    // we can just cache them temporarily and restore them in case of rollback
+    for (entity, component) in simple_query.iter() {
+        trace!("removing simple/once component for prediction_despawn");
+        commands
+            .entity(entity)
+            .remove::<C>()
+            .insert(RemovedCache(Some(component.clone())));
     }
 }
 
@@ -202,6 +308,54 @@ pub(crate) fn remove_despawn_marker(
     }
 }
 
+#[cfg(test)]
+mod grace_tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::prelude::TickConfig;
+    use crate::tests::protocol::MyProtocol;
+
+    use super::*;
+
+    #[test]
+    fn test_despawn_grace_period() {
+        let mut world = World::new();
+        let mut config = ClientConfig::default();
+        config.prediction = config.prediction.with_despawn_grace_ticks(2);
+        world.insert_resource(config);
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            bevy::utils::Duration::from_millis(10),
+        )));
+        world.init_resource::<Rollback>();
+        let predicted = world
+            .spawn(Predicted {
+                confirmed_entity: None,
+            })
+            .id();
+
+        // the despawn is predicted: the entity stays alive until the server confirms it
+        PredictionDespawnCommand::<MyProtocol> {
+            entity: predicted,
+            _marker: PhantomData,
+        }
+        .apply(&mut world);
+        assert!(world.get::<PredictionDespawnPending>(predicted).is_some());
+        assert!(world.get::<PredictionDespawnMarker>(predicted).is_none());
+
+        // still within the grace period
+        world.resource_mut::<TickManager>().increment_tick();
+        world.resource_mut::<TickManager>().increment_tick();
+        world.run_system_once(resurrect_expired_pending_despawn);
+        assert!(world.get::<PredictionDespawnPending>(predicted).is_some());
+
+        // the server never confirmed the despawn: the entity is resurrected
+        world.resource_mut::<TickManager>().increment_tick();
+        world.run_system_once(resurrect_expired_pending_despawn);
+        assert!(world.get_entity(predicted).is_some());
+        assert!(world.get::<PredictionDespawnPending>(predicted).is_none());
+    }
+}
+
 // TODO: revisit this; rollbacks happen when we receive a replication message now
 // #[cfg(test)]
 // mod tests {
@@ -248,7 +402,7 @@ pub(crate) fn remove_despawn_marker(
 //             min_delay: interpolation_delay,
 //             send_interval_ratio: 0.0,
 //         });
-//         let mut stepper = BevyStepper::new(
+//         let mut stepper = BevyStepper::from_configs(
 //             shared_config,
 //             sync_config,
 //             prediction_config,
@@ -438,7 +592,7 @@ pub(crate) fn remove_despawn_marker(
 //             min_delay: interpolation_delay,
 //             send_interval_ratio: 0.0,
 //         });
-//         let mut stepper = BevyStepper::new(
+//         let mut stepper = BevyStepper::from_configs(
 //             shared_config,
 //             sync_config,
 //             prediction_config,
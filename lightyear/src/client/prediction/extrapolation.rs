@@ -0,0 +1,170 @@
+//! Extrapolate remote entities forward in time using their last known velocity.
+//!
+//! Remote entities (for example the other players) are usually interpolated, which means that they are displayed
+//! behind in time. Fast-paced games can prefer to display them at the current predicted tick instead, guessing
+//! their current state from their last known state and velocity.
+//!
+//! To do this, predict the entity as usual (with `Replicate::prediction_target`) and add the [`Extrapolated`]
+//! component to the Predicted entity. Every time a new server state is received, the predicted component is
+//! set to the confirmed value, extrapolated to the current tick; and every `FixedUpdate` tick it is moved forward
+//! using the velocity. Extrapolated entities never trigger a rollback.
+use std::marker::PhantomData;
+
+use bevy::prelude::{
+    App, Component, DetectChanges, FixedUpdate, IntoSystemConfigs, Plugin, PreUpdate, Query, Ref,
+    Res, With, Without,
+};
+use bevy::utils::Duration;
+
+use crate::client::components::Confirmed;
+use crate::client::config::ClientConfig;
+use crate::client::prediction::plugin::PredictionSet;
+use crate::client::prediction::rollback::ExcludedFromRollback;
+use crate::client::prediction::Predicted;
+use crate::prelude::TickManager;
+
+/// Marker component for Predicted entities that should be extrapolated instead of predicted
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Extrapolated;
+
+/// A component that can be moved forward in time using a velocity component `V`
+pub trait Extrapolate<V: Component>: Component + Clone {
+    /// Returns the value of the component after moving at `velocity` for `delta`
+    fn extrapolate(&self, velocity: &V, delta: Duration) -> Self;
+}
+
+/// Extrapolate the component `C` of [`Extrapolated`] entities using the velocity component `V`.
+///
+/// Both components must be replicated and predicted.
+pub struct ExtrapolationPlugin<C, V> {
+    _marker: PhantomData<(C, V)>,
+}
+
+impl<C, V> Default for ExtrapolationPlugin<C, V> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Extrapolate<V>, V: Component + Clone> Plugin for ExtrapolationPlugin<C, V> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            extrapolate_from_confirmed::<C, V>.in_set(PredictionSet::CheckRollback),
+        );
+        app.add_systems(FixedUpdate, extrapolate_tick::<C, V>);
+    }
+}
+
+/// When a new server state is received, extrapolate it to the current tick
+pub(crate) fn extrapolate_from_confirmed<C: Extrapolate<V>, V: Component + Clone>(
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    confirmed_query: Query<(&C, &V, Ref<Confirmed>)>,
+    mut predicted_query: Query<
+        (&mut C, &mut V),
+        (With<Predicted>, With<Extrapolated>, Without<Confirmed>),
+    >,
+) {
+    let current_tick = tick_manager.tick();
+    for (confirmed_component, confirmed_velocity, confirmed) in confirmed_query.iter() {
+        if !confirmed.is_changed() {
+            continue;
+        }
+        let Some(Ok((mut component, mut velocity))) =
+            confirmed.predicted.map(|p| predicted_query.get_mut(p))
+        else {
+            continue;
+        };
+        let num_ticks = (current_tick - confirmed.tick).max(0) as u32;
+        *component = confirmed_component.extrapolate(
+            confirmed_velocity,
+            config.shared.tick.tick_duration * num_ticks,
+        );
+        *velocity = confirmed_velocity.clone();
+    }
+}
+
+/// Move the extrapolated entities forward by one tick
+pub(crate) fn extrapolate_tick<C: Extrapolate<V>, V: Component + Clone>(
+    config: Res<ClientConfig>,
+    // the entities that are not part of the current rollback were already moved forward for these ticks
+    mut query: Query<
+        (&mut C, &V),
+        (
+            With<Predicted>,
+            With<Extrapolated>,
+            Without<ExcludedFromRollback>,
+        ),
+    >,
+) {
+    for (mut component, velocity) in query.iter_mut() {
+        *component = component.extrapolate(velocity, config.shared.tick.tick_duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::prelude::{Tick, TickConfig};
+
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Velocity(f32);
+
+    impl Extrapolate<Velocity> for Position {
+        fn extrapolate(&self, velocity: &Velocity, delta: Duration) -> Self {
+            Position(self.0 + velocity.0 * delta.as_secs_f32())
+        }
+    }
+
+    #[test]
+    fn test_extrapolation() {
+        let tick_duration = Duration::from_millis(250);
+        let mut world = World::new();
+        let mut config = ClientConfig::default();
+        config.shared.tick = TickConfig::new(tick_duration);
+        world.insert_resource(config);
+        let mut tick_manager = TickManager::from_config(TickConfig::new(tick_duration));
+        for _ in 0..5 {
+            tick_manager.increment_tick();
+        }
+        world.insert_resource(tick_manager);
+
+        let predicted = world
+            .spawn((
+                Predicted {
+                    confirmed_entity: None,
+                },
+                Extrapolated,
+                Position(0.0),
+                Velocity(0.0),
+            ))
+            .id();
+        // the server state is 3 ticks behind the client
+        world.spawn((
+            Confirmed {
+                predicted: Some(predicted),
+                interpolated: None,
+                tick: Tick(2),
+            },
+            Position(1.0),
+            Velocity(10.0),
+        ));
+
+        world.run_system_once(extrapolate_from_confirmed::<Position, Velocity>);
+        assert_eq!(world.get::<Position>(predicted), Some(&Position(8.5)));
+        assert_eq!(world.get::<Velocity>(predicted), Some(&Velocity(10.0)));
+
+        world.run_system_once(extrapolate_tick::<Position, Velocity>);
+        assert_eq!(world.get::<Position>(predicted), Some(&Position(11.0)));
+    }
+}
@@ -0,0 +1,211 @@
+//! Roll back the descendants of predicted entities along with their parent.
+//!
+//! The children of a Predicted entity (weapon attachments, turrets, etc.) are usually spawned locally on the client
+//! and are not replicated, so they don't have a Confirmed counterpart. With [`HierarchyRollbackPlugin`], the
+//! history of their component `C` (by default [`Transform`]) is recorded every tick; when the Predicted root
+//! entity is rolled back, the descendants are restored to their value at the rollback tick so that they are
+//! resimulated along with their parent.
+use std::marker::PhantomData;
+
+use bevy::hierarchy::HierarchyQueryExt;
+use bevy::prelude::{
+    App, Children, Commands, Component, DetectChanges, Entity, FixedPostUpdate, IntoSystemConfigs,
+    Plugin, PreUpdate, Query, Ref, Res, Transform, With, Without,
+};
+use tracing::trace;
+
+use crate::client::components::Confirmed;
+use crate::client::prediction::plugin::PredictionSet;
+use crate::client::prediction::rollback::{
+    ExcludedFromRollback, Rollback, RollbackGroup, RollbackState,
+};
+use crate::client::prediction::Predicted;
+use crate::prelude::TickManager;
+use crate::shared::tick_manager::Tick;
+use crate::utils::ready_buffer::ReadyBuffer;
+
+/// Roll back the component `C` of the descendants of Predicted entities
+pub struct HierarchyRollbackPlugin<C = Transform> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for HierarchyRollbackPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component + Clone> Plugin for HierarchyRollbackPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            (
+                add_descendant_history::<C>.in_set(PredictionSet::SpawnHistory),
+                trim_descendant_history::<C>.in_set(PredictionSet::CheckRollback),
+                prepare_descendant_rollback::<C>.in_set(PredictionSet::PrepareRollback),
+            ),
+        );
+        app.add_systems(
+            FixedPostUpdate,
+            update_descendant_history::<C>.in_set(PredictionSet::UpdateHistory),
+        );
+    }
+}
+
+/// History of the component `C` on a descendant of a Predicted entity
+#[derive(Component, Debug)]
+pub struct DescendantHistory<C> {
+    /// The Predicted entity at the root of the hierarchy
+    root: Entity,
+    buffer: ReadyBuffer<Tick, C>,
+}
+
+impl<C: Clone> DescendantHistory<C> {
+    /// Get the value of the component at the specified tick, and clear all the older history.
+    ///
+    /// The returned value is kept in the history, since the component keeps that value until the next change.
+    fn pop_until_tick(&mut self, tick: Tick) -> Option<C> {
+        self.buffer.pop_until(&tick).map(|(tick, value)| {
+            self.buffer.add_item(tick, value.clone());
+            value
+        })
+    }
+}
+
+/// Add a history to the descendants of Predicted entities
+pub(crate) fn add_descendant_history<C: Component + Clone>(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    roots: Query<Entity, (With<Predicted>, With<Children>)>,
+    children: Query<&Children>,
+    descendants: Query<&C, Without<DescendantHistory<C>>>,
+) {
+    let tick = tick_manager.tick();
+    for root in roots.iter() {
+        for descendant in children.iter_descendants(root) {
+            let Ok(component) = descendants.get(descendant) else {
+                continue;
+            };
+            trace!(?root, ?descendant, "adding descendant history");
+            let mut buffer = ReadyBuffer::new();
+            buffer.add_item(tick, component.clone());
+            commands
+                .entity(descendant)
+                .insert(DescendantHistory { root, buffer });
+        }
+    }
+}
+
+/// After each fixed-update tick, record the value of the component on the descendants
+pub(crate) fn update_descendant_history<C: Component + Clone>(
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    excluded: Query<(), With<ExcludedFromRollback>>,
+    mut query: Query<(Ref<C>, &mut DescendantHistory<C>)>,
+) {
+    let tick = match rollback.state {
+        RollbackState::Default => tick_manager.tick(),
+        RollbackState::ShouldRollback { current_tick } => current_tick,
+    };
+    for (component, mut history) in query.iter_mut() {
+        // descendants of entities that are not part of the current rollback keep their existing history
+        if excluded.contains(history.root) {
+            continue;
+        }
+        if component.is_changed() {
+            history.buffer.add_item(tick, component.clone());
+        }
+    }
+}
+
+/// Clear the history that is older than the latest confirmed tick of the root entity
+pub(crate) fn trim_descendant_history<C: Component + Clone>(
+    roots: Query<&Predicted>,
+    confirmed: Query<&Confirmed>,
+    mut query: Query<&mut DescendantHistory<C>>,
+) {
+    for mut history in query.iter_mut() {
+        let Some(tick) = roots
+            .get(history.root)
+            .ok()
+            .and_then(|predicted| predicted.confirmed_entity)
+            .and_then(|entity| confirmed.get(entity).ok())
+            .map(|confirmed| confirmed.tick)
+        else {
+            continue;
+        };
+        history.pop_until_tick(tick);
+    }
+}
+
+/// Restore the descendants of the rolled back entities to their value at the rollback tick
+pub(crate) fn prepare_descendant_rollback<C: Component + Clone>(
+    rollback: Res<Rollback>,
+    roots: Query<Option<&RollbackGroup>, With<Predicted>>,
+    mut query: Query<(&mut C, &mut DescendantHistory<C>)>,
+) {
+    let RollbackState::ShouldRollback { current_tick } = rollback.state else {
+        return;
+    };
+    // the rollback starts from the end of the confirmed tick
+    let tick = current_tick - 1;
+    for (mut component, mut history) in query.iter_mut() {
+        let Ok(group) = roots.get(history.root) else {
+            continue;
+        };
+        if !rollback.is_rolled_back(group) {
+            continue;
+        }
+        let Some(value) = history.pop_until_tick(tick) else {
+            continue;
+        };
+        // the rest of the history will be recorded again during the rollback
+        history.buffer = ReadyBuffer::new();
+        history.buffer.add_item(tick, value.clone());
+        *component = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{BuildWorldChildren, World};
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_descendant_rollback() {
+        let mut world = World::new();
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        world.init_resource::<Rollback>();
+        let mut turret = Entity::PLACEHOLDER;
+        world
+            .spawn(Predicted {
+                confirmed_entity: None,
+            })
+            .with_children(|parent| {
+                turret = parent.spawn(Transform::from_xyz(1.0, 0.0, 0.0)).id();
+            });
+        world.run_system_once(add_descendant_history::<Transform>);
+        assert!(world.get::<DescendantHistory<Transform>>(turret).is_some());
+
+        // the turret moves during ticks 1 and 2
+        for x in [2.0, 3.0] {
+            world.resource_mut::<TickManager>().increment_tick();
+            world.get_mut::<Transform>(turret).unwrap().translation.x = x;
+            world.run_system_once(update_descendant_history::<Transform>);
+        }
+
+        // rollback from the end of tick 1: the turret is restored to its value at tick 1
+        world.resource_mut::<Rollback>().add_group(None, Tick(1));
+        world.run_system_once(prepare_descendant_rollback::<Transform>);
+        assert_eq!(world.get::<Transform>(turret).unwrap().translation.x, 2.0);
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt::Debug;
 use bevy::prelude::*;
 use tracing::error;
 
-pub use despawn::PredictionDespawnCommandsExt;
+pub use despawn::{PredictionDespawnCommandsExt, PredictionDespawnPending};
 pub use plugin::add_prediction_systems;
 pub use predicted_history::{ComponentState, PredictionHistory};
 
@@ -18,13 +18,20 @@ use crate::shared::tick_manager::Tick;
 
 pub(crate) mod correction;
 mod despawn;
+pub mod extrapolation;
+pub mod hierarchy;
 pub mod plugin;
 mod pre_prediction;
+pub mod predicted_event;
 pub mod predicted_history;
+pub mod predicted_resource;
 pub mod prespawn;
 pub(crate) mod resource;
 pub(crate) mod rollback;
+pub mod simulation;
+pub mod smoothing;
 pub mod spawn;
+pub mod switch;
 
 /// Marks an entity that is being predicted by the client
 #[derive(Component, Debug, Reflect)]
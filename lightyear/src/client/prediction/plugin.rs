@@ -1,11 +1,12 @@
 use std::marker::PhantomData;
 
 use bevy::prelude::{
-    apply_deferred, App, FixedPostUpdate, IntoSystemConfigs, IntoSystemSetConfigs, Plugin,
-    PostUpdate, PreUpdate, Res, SystemSet,
+    apply_deferred, App, FixedPostUpdate, FixedPreUpdate, IntoSystemConfigs, IntoSystemSetConfigs,
+    Plugin, PostUpdate, PreUpdate, Res, SystemSet,
 };
 use bevy::reflect::Reflect;
 use bevy::transform::TransformSystem;
+use bevy::utils::Duration;
 
 use crate::_reexport::{ClientMarker, FromType};
 use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent, SyncMetadata};
@@ -14,8 +15,9 @@ use crate::client::prediction::correction::{
     get_visually_corrected_state, restore_corrected_state,
 };
 use crate::client::prediction::despawn::{
-    despawn_confirmed, remove_component_for_despawn_predicted, remove_despawn_marker,
-    restore_components_if_despawn_rolled_back, PredictionDespawnMarker,
+    clear_pending_despawn_for_rollback, despawn_confirmed, remove_component_for_despawn_predicted,
+    remove_despawn_marker, restore_components_if_despawn_rolled_back,
+    resurrect_expired_pending_despawn, PredictionDespawnMarker,
 };
 use crate::client::prediction::predicted_history::{
     add_prespawned_component_history, update_prediction_history,
@@ -24,6 +26,11 @@ use crate::client::prediction::prespawn::{
     PreSpawnedPlayerObjectPlugin, PreSpawnedPlayerObjectSet,
 };
 use crate::client::prediction::resource::PredictionManager;
+use crate::client::prediction::simulation::{run_predicted_simulation, PredictedSimulation};
+use crate::client::prediction::smoothing::{
+    apply_visual_offset, compute_visual_error, record_pre_rollback_transform, remove_visual_offset,
+};
+use crate::client::prediction::switch::{remove_history_on_switch, switch_prediction_mode};
 use crate::client::prediction::Predicted;
 use crate::client::sync::client_is_synced;
 use crate::connection::client::{ClientConnection, NetClient};
@@ -31,12 +38,16 @@ use crate::prelude::{ExternalMapper, PreSpawnedPlayerObject};
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::Protocol;
 use crate::shared::sets::InternalMainSet;
+use crate::shared::tick_manager::is_last_substep;
 
 use super::pre_prediction::{PrePredictionPlugin, PrePredictionSet};
-use super::predicted_history::{add_component_history, apply_confirmed_update};
+use super::predicted_history::{
+    add_component_history, apply_confirmed_update, remove_history_on_sync_mode_change,
+};
 use super::rollback::{
-    check_rollback, increment_rollback_tick, prepare_rollback, prepare_rollback_prespawn,
-    run_rollback, Rollback, RollbackState,
+    check_rollback, end_rollback, exclude_from_rollback, increment_rollback_tick, prepare_rollback,
+    prepare_rollback_prespawn, remove_rollback_exclusion, run_rollback, Rollback, RollbackEndEvent,
+    RollbackGroup, RollbackMetrics, RollbackSnapEvent, RollbackStartEvent, RollbackState,
 };
 use super::spawn::spawn_predicted_entity;
 
@@ -58,6 +69,28 @@ pub struct PredictionConfig {
     /// (i.e. if the client is 10 ticks head and correction_ticks is 1.0, then the correction will be done over 10 ticks)
     // Number of ticks it will take to visually update the Predicted state to the new Corrected state
     pub correction_ticks_factor: f32,
+    /// Maximum number of ticks that can be resimulated in a single rollback.
+    ///
+    /// If a rollback would need to resimulate more ticks than this (for example after a long frame hitch),
+    /// the predicted entities are snapped to the server state instead, and a [`RollbackSnapEvent`](super::rollback::RollbackSnapEvent) is emitted.
+    /// By default there is no limit.
+    pub max_rollback_ticks: Option<u16>,
+    /// Number of ticks that a predicted despawn waits for the server confirmation.
+    ///
+    /// If set, [`prediction_despawn`](super::PredictionDespawnCommandsExt::prediction_despawn) keeps the
+    /// Predicted entity alive (with the [`PredictionDespawnPending`](super::PredictionDespawnPending) component)
+    /// instead of removing its components. The entity is despawned when the server confirms the despawn, or
+    /// resurrected if the confirmation didn't arrive after this many ticks.
+    pub despawn_grace_ticks: Option<u16>,
+    /// Maximum amount of time that can be spent resimulating ticks in a frame.
+    ///
+    /// The cost of resimulating a tick is measured during rollbacks; if a rollback is expected to exceed
+    /// the budget, the predicted entities are snapped to the server state instead of resimulating the ticks,
+    /// and a [`RollbackSnapEvent`](super::rollback::RollbackSnapEvent) is emitted (like with `max_rollback_ticks`).
+    /// This prevents low-end clients from spiraling when they fall behind.
+    /// See [`RollbackMetrics`](super::rollback::RollbackMetrics).
+    /// By default there is no budget.
+    pub rollback_budget: Option<Duration>,
 }
 
 impl PredictionConfig {
@@ -77,6 +110,24 @@ impl PredictionConfig {
         self
     }
 
+    /// Set the maximum number of ticks that can be resimulated in a single rollback
+    pub fn with_max_rollback_ticks(mut self, max_rollback_ticks: u16) -> Self {
+        self.max_rollback_ticks = Some(max_rollback_ticks);
+        self
+    }
+
+    /// Set the number of ticks that a predicted despawn waits for the server confirmation
+    pub fn with_despawn_grace_ticks(mut self, despawn_grace_ticks: u16) -> Self {
+        self.despawn_grace_ticks = Some(despawn_grace_ticks);
+        self
+    }
+
+    /// Set the maximum amount of time that can be spent resimulating ticks in a frame
+    pub fn with_rollback_budget(mut self, rollback_budget: Duration) -> Self {
+        self.rollback_budget = Some(rollback_budget);
+        self
+    }
+
     /// Update the amount of input delay (number of ticks)
     pub fn with_correction_ticks_factor(mut self, factor: f32) -> Self {
         self.correction_ticks_factor = factor;
@@ -125,8 +176,14 @@ pub enum PredictionSet {
     /// For pre-spawned entities, we just roll them back to their historical state.
     /// If they didn't exist in the rollback tick, despawn them
     PrepareRollback,
+    /// Runs right before the rollback, only if there is a rollback.
+    ///
+    /// Add your own systems to this set to save or restore custom non-replicated state before resimulating
+    PreRollback,
     /// Perform rollback
     Rollback,
+    /// Runs right after the rollback, only if there was a rollback.
+    PostRollback,
     // NOTE: no need to add RollbackFlush because running a schedule (which we do for rollback) will flush all commands at the end of each run
 
     // FixedPostUpdate Sets
@@ -162,6 +219,8 @@ where
         (
             // handle components being added
             add_component_history::<C, P>.in_set(PredictionSet::SpawnHistory),
+            // handle entities switching between prediction and interpolation
+            remove_history_on_switch::<C>.in_set(PredictionSet::SpawnHistory),
         ),
     );
     match P::Components::mode() {
@@ -179,6 +238,11 @@ where
                     check_rollback::<C, P>.in_set(PredictionSet::CheckRollback),
                     (prepare_rollback::<C, P>, prepare_rollback_prespawn::<C, P>)
                         .in_set(PredictionSet::PrepareRollback),
+                    // entities can override the sync mode to Simple/Once (see `Replicate::set_sync_mode`)
+                    remove_history_on_sync_mode_change::<C, P>.in_set(PredictionSet::SpawnHistory),
+                    apply_confirmed_update::<C, P>.in_set(PredictionSet::CheckRollback),
+                    restore_components_if_despawn_rolled_back::<C>
+                        .in_set(PredictionSet::PrepareRollback),
                 ),
             );
             app.add_systems(
@@ -236,6 +300,7 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
             .register_type::<PreSpawnedPlayerObject>()
             .register_type::<Rollback>()
             .register_type::<RollbackState>()
+            .register_type::<RollbackGroup>()
             .register_type::<PredictionDespawnMarker>()
             .register_type::<PredictionConfig>();
 
@@ -243,9 +308,14 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
 
         // RESOURCES
         app.init_resource::<PredictionManager>();
-        app.insert_resource(Rollback {
-            state: RollbackState::Default,
-        });
+        app.init_resource::<Rollback>();
+        app.init_resource::<RollbackMetrics>();
+        // custom simulation systems, that are also run during rollbacks
+        app.init_schedule(PredictedSimulation);
+        app.add_systems(FixedPreUpdate, run_predicted_simulation);
+        app.add_event::<RollbackStartEvent>()
+            .add_event::<RollbackEndEvent>()
+            .add_event::<RollbackSnapEvent>();
 
         // PreUpdate systems:
         // 1. Receive confirmed entities, add Confirmed and Predicted components
@@ -262,7 +332,9 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
                     PredictionSet::RestoreVisualCorrection,
                     PredictionSet::CheckRollback,
                     PredictionSet::PrepareRollback.run_if(is_in_rollback),
+                    PredictionSet::PreRollback.run_if(is_in_rollback),
                     PredictionSet::Rollback.run_if(is_in_rollback),
+                    PredictionSet::PostRollback.run_if(is_in_rollback),
                 )
                     .chain()
                     .in_set(PredictionSet::All),
@@ -282,10 +354,25 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
                         .after(PrePredictionSet::Spawn),
                     // NOTE: we put `despawn_confirmed` here because we only need to run it once per frame,
                     //  not at every fixed-update tick, since it only depends on server messages
-                    despawn_confirmed,
+                    (despawn_confirmed, resurrect_expired_pending_despawn).chain(),
+                    switch_prediction_mode,
                 )
                     .in_set(PredictionSet::SpawnPrediction),
+                clear_pending_despawn_for_rollback.in_set(PredictionSet::PrepareRollback),
+                remove_visual_offset.in_set(PredictionSet::RestoreVisualCorrection),
+                // record the transform before `PrepareRollback` resets the predicted state to the server state
+                record_pre_rollback_transform
+                    .run_if(is_in_rollback)
+                    .after(PredictionSet::CheckRollback)
+                    .before(PredictionSet::PrepareRollback)
+                    .in_set(PredictionSet::All),
+                exclude_from_rollback.in_set(PredictionSet::PreRollback),
                 run_rollback.in_set(PredictionSet::Rollback),
+                (compute_visual_error, remove_rollback_exclusion)
+                    .in_set(PredictionSet::PostRollback),
+                end_rollback
+                    .after(PredictionSet::PostRollback)
+                    .in_set(PredictionSet::All),
             ),
         );
 
@@ -300,8 +387,9 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
                 PredictionSet::EntityDespawn,
                 // for prespawned entities that could be spawned during FixedUpdate, we want to add the history
                 // right away to avoid rollbacks
-                PredictionSet::SpawnHistory,
-                PredictionSet::UpdateHistory,
+                // the history is recorded once per tick, at the end of the last physics step
+                PredictionSet::SpawnHistory.run_if(is_last_substep),
+                PredictionSet::UpdateHistory.run_if(is_last_substep),
                 PredictionSet::IncrementRollbackTick.run_if(is_in_rollback),
             )
                 .in_set(PredictionSet::All)
@@ -321,6 +409,7 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
 
         // PostUpdate systems
         // 1. Visually interpolate the prediction to the corrected state
+        // 2. Add the visual error offset to the transforms of the entities that have `VisualSmoothing`
         app.configure_sets(
             PostUpdate,
             PredictionSet::VisualCorrection
@@ -328,6 +417,10 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
                 .before(TransformSystem::TransformPropagate),
         )
         .configure_sets(PostUpdate, PredictionSet::All.run_if(client_is_synced::<P>));
+        app.add_systems(
+            PostUpdate,
+            apply_visual_offset.in_set(PredictionSet::VisualCorrection),
+        );
 
         // PLUGINS
         app.add_plugins((
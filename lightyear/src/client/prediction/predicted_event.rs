@@ -0,0 +1,216 @@
+//! Rollback-aware events
+//!
+//! Events sent from `FixedUpdate` systems in the predicted timeline (damage numbers, sound triggers, etc.)
+//! would be sent again every time the tick is resimulated during a rollback.
+//! With [`PredictedEventWriter`], the events are tracked per tick:
+//! - an event that is sent again while resimulating a tick for which it was already sent is not re-emitted
+//! - an event that was sent for a tick but is not sent again when that tick is resimulated came from a mispredicted
+//!   branch: a [`PredictedEventCancelled`] event is emitted so that you can cancel its effects
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{
+    App, Event, EventWriter, IntoSystemConfigs, Plugin, PreUpdate, Res, ResMut, Resource,
+};
+use tracing::trace;
+
+use crate::client::config::ClientConfig;
+use crate::client::prediction::plugin::PredictionSet;
+use crate::client::prediction::rollback::{Rollback, RollbackState};
+use crate::prelude::TickManager;
+use crate::shared::tick_manager::Tick;
+
+/// Number of ticks for which the sent events are remembered, if
+/// [`PredictionConfig::max_rollback_ticks`](crate::client::prediction::plugin::PredictionConfig::max_rollback_ticks) is not set
+const DEFAULT_EVENT_HISTORY_TICKS: u16 = 128;
+
+/// Track the events `E` sent in the predicted timeline, so that they are not duplicated during rollbacks
+pub struct PredictedEventPlugin<E> {
+    _marker: std::marker::PhantomData<E>,
+}
+
+impl<E> Default for PredictedEventPlugin<E> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E: Event + Clone + PartialEq> Plugin for PredictedEventPlugin<E> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<E>()
+            .add_event::<PredictedEventCancelled<E>>()
+            .init_resource::<PredictedEvents<E>>();
+        app.add_systems(
+            PreUpdate,
+            (
+                start_event_rollback::<E>.in_set(PredictionSet::PreRollback),
+                cancel_mispredicted_events::<E>.in_set(PredictionSet::PostRollback),
+            ),
+        );
+    }
+}
+
+/// Emitted when an event `E` was sent in a mispredicted branch of the simulation
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PredictedEventCancelled<E> {
+    /// The tick for which the event was sent
+    pub tick: Tick,
+    pub event: E,
+}
+
+#[derive(Debug)]
+struct SentEvent<E> {
+    tick: Tick,
+    event: E,
+    /// True if the tick is being resimulated and the event has not been sent again yet
+    awaiting_resimulation: bool,
+}
+
+/// The events `E` that were sent in the predicted timeline
+#[derive(Resource, Debug)]
+pub struct PredictedEvents<E> {
+    sent: Vec<SentEvent<E>>,
+}
+
+impl<E> Default for PredictedEvents<E> {
+    fn default() -> Self {
+        Self { sent: Vec::new() }
+    }
+}
+
+/// [`SystemParam`] to send rollback-aware events from `FixedUpdate` systems
+#[derive(SystemParam)]
+pub struct PredictedEventWriter<'w, 's, E: Event + Clone + PartialEq> {
+    events: EventWriter<'w, E>,
+    predicted_events: ResMut<'w, PredictedEvents<E>>,
+    tick_manager: Res<'w, TickManager>,
+    rollback: Res<'w, Rollback>,
+    config: Res<'w, ClientConfig>,
+    _marker: std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w, 's, E: Event + Clone + PartialEq> PredictedEventWriter<'w, 's, E> {
+    /// Send an event for the current tick of the predicted timeline.
+    ///
+    /// If the event was already sent for this tick before a rollback, it is not sent again.
+    pub fn send(&mut self, event: E) {
+        let tick = match self.rollback.state {
+            RollbackState::Default => {
+                let tick = self.tick_manager.tick();
+                let history_ticks = self
+                    .config
+                    .prediction
+                    .max_rollback_ticks
+                    .unwrap_or(DEFAULT_EVENT_HISTORY_TICKS);
+                let oldest_tick = tick - history_ticks;
+                self.predicted_events
+                    .sent
+                    .retain(|sent| sent.tick >= oldest_tick);
+                tick
+            }
+            RollbackState::ShouldRollback { current_tick } => {
+                if let Some(sent) = self.predicted_events.sent.iter_mut().find(|sent| {
+                    sent.awaiting_resimulation && sent.tick == current_tick && sent.event == event
+                }) {
+                    trace!(tick = ?current_tick, "event was already sent before the rollback");
+                    sent.awaiting_resimulation = false;
+                    return;
+                }
+                current_tick
+            }
+        };
+        self.predicted_events.sent.push(SentEvent {
+            tick,
+            event: event.clone(),
+            awaiting_resimulation: false,
+        });
+        self.events.send(event);
+    }
+}
+
+/// At the start of the rollback, mark the events of the ticks that will be resimulated
+pub(crate) fn start_event_rollback<E: Event>(
+    rollback: Res<Rollback>,
+    mut predicted_events: ResMut<PredictedEvents<E>>,
+) {
+    let RollbackState::ShouldRollback { current_tick } = rollback.state else {
+        return;
+    };
+    for sent in predicted_events.sent.iter_mut() {
+        if sent.tick >= current_tick {
+            sent.awaiting_resimulation = true;
+        }
+    }
+}
+
+/// At the end of the rollback, cancel the events that were not sent again during the resimulation
+pub(crate) fn cancel_mispredicted_events<E: Event + Clone>(
+    mut predicted_events: ResMut<PredictedEvents<E>>,
+    mut cancelled: EventWriter<PredictedEventCancelled<E>>,
+) {
+    predicted_events.sent.retain(|sent| {
+        if sent.awaiting_resimulation {
+            trace!(tick = ?sent.tick, "cancelling mispredicted event");
+            cancelled.send(PredictedEventCancelled {
+                tick: sent.tick,
+                event: sent.event.clone(),
+            });
+        }
+        !sent.awaiting_resimulation
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Events, World};
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[derive(Event, Clone, Debug, PartialEq)]
+    struct Hit(u32);
+
+    fn drain<E: Event>(world: &mut World) -> Vec<E> {
+        world.resource_mut::<Events<E>>().drain().collect()
+    }
+
+    #[test]
+    fn test_predicted_events() {
+        let mut world = World::new();
+        world.init_resource::<ClientConfig>();
+        world.init_resource::<Rollback>();
+        world.init_resource::<PredictedEvents<Hit>>();
+        world.init_resource::<Events<Hit>>();
+        world.init_resource::<Events<PredictedEventCancelled<Hit>>>();
+        let mut tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        tick_manager.increment_tick();
+        world.insert_resource(tick_manager);
+
+        // two events are sent at tick 1
+        world.run_system_once(|mut writer: PredictedEventWriter<Hit>| {
+            writer.send(Hit(1));
+            writer.send(Hit(2));
+        });
+        assert_eq!(drain::<Hit>(&mut world), vec![Hit(1), Hit(2)]);
+
+        // during the rollback, only one of them is sent again, along with a new event
+        world.resource_mut::<Rollback>().add_group(None, Tick(0));
+        world.run_system_once(start_event_rollback::<Hit>);
+        world.run_system_once(|mut writer: PredictedEventWriter<Hit>| {
+            writer.send(Hit(1));
+            writer.send(Hit(3));
+        });
+        world.run_system_once(cancel_mispredicted_events::<Hit>);
+        assert_eq!(drain::<Hit>(&mut world), vec![Hit(3)]);
+        assert_eq!(
+            drain::<PredictedEventCancelled<Hit>>(&mut world),
+            vec![PredictedEventCancelled {
+                tick: Tick(1),
+                event: Hit(2),
+            }]
+        );
+    }
+}
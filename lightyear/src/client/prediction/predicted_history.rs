@@ -2,15 +2,18 @@ use std::ops::Deref;
 
 use crate::_reexport::FromType;
 use bevy::prelude::{
-    Commands, Component, DetectChanges, Entity, Or, Query, Ref, RemovedComponents, Res, ResMut,
-    With, Without,
+    Changed, Commands, Component, DetectChanges, Entity, Or, Query, Ref, RemovedComponents, Res,
+    ResMut, With, Without,
 };
-use tracing::{debug, error, info, trace};
+use tracing::{debug, info, trace};
 
 use crate::client::components::{ComponentSyncMode, SyncComponent, SyncMetadata};
+use crate::client::prediction::correction::Correction;
 use crate::client::prediction::resource::PredictionManager;
-use crate::client::prediction::rollback::{Rollback, RollbackState};
-use crate::prelude::{ExternalMapper, PreSpawnedPlayerObject, ShouldBePredicted, TickManager};
+use crate::client::prediction::rollback::{ExcludedFromRollback, Rollback, RollbackState};
+use crate::prelude::{
+    ExternalMapper, PreSpawnedPlayerObject, ShouldBePredicted, SyncModeOverride, TickManager,
+};
 use crate::protocol::Protocol;
 use crate::shared::tick_manager::Tick;
 use crate::utils::ready_buffer::ReadyBuffer;
@@ -57,6 +60,41 @@ impl<T: SyncComponent> PartialEq for PredictionHistory<T> {
 }
 
 impl<T: SyncComponent> PredictionHistory<T> {
+    /// Get the predicted state of the component at the specified tick.
+    ///
+    /// The history only stores the ticks where the component changed, so this returns the latest state
+    /// recorded at or before `tick`. Returns `None` if `tick` is older than the oldest tick in the history.
+    pub fn get(&self, tick: Tick) -> Option<&ComponentState<T>> {
+        self.buffer
+            .heap
+            .iter()
+            .filter(|item| item.key <= tick)
+            .max_by_key(|item| item.key)
+            .map(|item| &item.item)
+    }
+
+    /// Iterate through the recorded states of the component, ordered by tick
+    pub fn iter(&self) -> impl Iterator<Item = (Tick, &ComponentState<T>)> {
+        let mut items: Vec<_> = self
+            .buffer
+            .heap
+            .iter()
+            .map(|item| (item.key, &item.item))
+            .collect();
+        items.sort_by_key(|(tick, _)| *tick);
+        items.into_iter()
+    }
+
+    /// The oldest tick stored in the history
+    pub fn oldest_tick(&self) -> Option<Tick> {
+        self.buffer.heap.peek().map(|item| item.key)
+    }
+
+    /// The most recent tick stored in the history
+    pub fn newest_tick(&self) -> Option<Tick> {
+        self.buffer.heap.iter().map(|item| item.key).max()
+    }
+
     /// Reset the history for this component
     pub(crate) fn clear(&mut self) {
         self.buffer = ReadyBuffer::new();
@@ -124,14 +162,19 @@ pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
     mut commands: Commands,
     tick_manager: Res<TickManager>,
     predicted_entities: Query<
-        (Entity, Option<Ref<C>>),
+        (Entity, Option<Ref<C>>, Ref<Predicted>),
         (
             Without<PredictionHistory<C>>,
             // for all types of predicted entities, we want to add the component history to enable them to be rolled-back
             With<Predicted>,
         ),
     >,
-    confirmed_entities: Query<(Entity, &Confirmed, Option<Ref<C>>)>,
+    confirmed_entities: Query<(
+        Entity,
+        &Confirmed,
+        Option<Ref<C>>,
+        Option<Ref<SyncModeOverride<P::ComponentKinds>>>,
+    )>,
 ) where
     P::Components: SyncMetadata<C>,
     P::Components: ExternalMapper<C>,
@@ -139,17 +182,35 @@ pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
 {
     let kind = P::ComponentKinds::from_type();
     let tick = tick_manager.tick();
-    for (confirmed_entity, confirmed, confirmed_component) in confirmed_entities.iter() {
+    for (confirmed_entity, confirmed, confirmed_component, sync_mode_override) in
+        confirmed_entities.iter()
+    {
         if let Some(p) = confirmed.predicted {
-            if let Ok((predicted_entity, predicted_component)) = predicted_entities.get(p) {
+            if let Ok((predicted_entity, predicted_component, predicted)) =
+                predicted_entities.get(p)
+            {
+                let mode = SyncModeOverride::mode::<C>(
+                    sync_mode_override.as_deref(),
+                    P::Components::mode(),
+                );
                 // if component got added on predicted side, add history
-                add_history::<C, P>(tick, predicted_entity, &predicted_component, &mut commands);
+                add_history::<C, P>(
+                    tick,
+                    predicted_entity,
+                    &predicted_component,
+                    mode,
+                    &mut commands,
+                );
 
-                // if component got added on confirmed side
+                // if component got added on confirmed side (or the entity just switched to prediction,
+                // or its sync mode was overridden)
                 // - full: sync component and add history
                 // - simple/once: sync component
                 if let Some(confirmed_component) = confirmed_component {
-                    if confirmed_component.is_added() {
+                    if confirmed_component.is_added()
+                        || predicted.is_added()
+                        || sync_mode_override.is_some_and(|o| o.is_changed())
+                    {
                         trace!(?kind, "Component added on confirmed side");
                         // safety: we know the entity exists
                         let mut predicted_entity_mut =
@@ -160,7 +221,7 @@ pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
                             &mut new_component,
                             &mut manager.predicted_entity_map,
                         );
-                        match P::Components::mode() {
+                        match mode {
                             ComponentSyncMode::Full => {
                                 // insert history, it will be quickly filled by a rollback (since it starts empty before the current client tick)
                                 // TODO: then there's no need to add the component here, since it's going to get added during rollback anyway
@@ -220,6 +281,7 @@ pub fn add_prespawned_component_history<C: SyncComponent, P: Protocol>(
             tick_manager.tick(),
             predicted_entity,
             &predicted_component,
+            P::Components::mode(),
             &mut commands,
         );
     }
@@ -230,13 +292,14 @@ fn add_history<C: SyncComponent, P: Protocol>(
     tick: Tick,
     predicted_entity: Entity,
     predicted_component: &Option<Ref<C>>,
+    mode: ComponentSyncMode,
     commands: &mut Commands,
 ) where
     P::Components: SyncMetadata<C>,
     P::ComponentKinds: FromType<C>,
 {
     let kind = P::ComponentKinds::from_type();
-    if P::Components::mode() == ComponentSyncMode::Full {
+    if mode == ComponentSyncMode::Full {
         if let Some(predicted_component) = predicted_component {
             // component got added on predicted side, add history
             if predicted_component.is_added() {
@@ -292,9 +355,13 @@ fn add_history<C: SyncComponent, P: Protocol>(
 
 /// After one fixed-update tick, we record the predicted component history for the current tick
 pub fn update_prediction_history<T: SyncComponent>(
-    mut query: Query<(Ref<T>, &mut PredictionHistory<T>)>,
+    // entities that are not part of the current rollback keep their existing history
+    mut query: Query<(Ref<T>, &mut PredictionHistory<T>), Without<ExcludedFromRollback>>,
     mut removed_component: RemovedComponents<T>,
-    mut removed_entities: Query<&mut PredictionHistory<T>, Without<T>>,
+    mut removed_entities: Query<
+        &mut PredictionHistory<T>,
+        (Without<T>, Without<ExcludedFromRollback>),
+    >,
     tick_manager: Res<TickManager>,
     rollback: Res<Rollback>,
 ) {
@@ -323,6 +390,36 @@ pub fn update_prediction_history<T: SyncComponent>(
     }
 }
 
+/// Remove the prediction history of a component when the entity's [`SyncModeOverride`] stops using `Full` sync for it.
+///
+/// The history is added back by [`add_component_history`] if the entity switches back to `Full` sync.
+#[allow(clippy::type_complexity)]
+pub(crate) fn remove_history_on_sync_mode_change<C: SyncComponent, P: Protocol>(
+    mut commands: Commands,
+    predicted_entities: Query<(), (With<PredictionHistory<C>>, With<Predicted>)>,
+    confirmed_entities: Query<
+        (&Confirmed, &SyncModeOverride<P::ComponentKinds>),
+        Changed<SyncModeOverride<P::ComponentKinds>>,
+    >,
+) where
+    P::Components: SyncMetadata<C>,
+    P::ComponentKinds: FromType<C>,
+{
+    for (confirmed, sync_mode_override) in confirmed_entities.iter() {
+        let Some(p) = confirmed.predicted else {
+            continue;
+        };
+        if predicted_entities.contains(p)
+            && SyncModeOverride::mode::<C>(Some(sync_mode_override), P::Components::mode())
+                != ComponentSyncMode::Full
+        {
+            commands
+                .entity(p)
+                .remove::<(PredictionHistory<C>, Correction<C>)>();
+        }
+    }
+}
+
 /// When we receive a server update, we might want to apply it to the predicted entity
 #[allow(clippy::type_complexity)]
 pub(crate) fn apply_confirmed_update<C: SyncComponent, P: Protocol>(
@@ -336,25 +433,23 @@ pub(crate) fn apply_confirmed_update<C: SyncComponent, P: Protocol>(
             With<Predicted>,
         ),
     >,
-    confirmed_entities: Query<(&Confirmed, Ref<C>)>,
+    confirmed_entities: Query<(
+        &Confirmed,
+        Ref<C>,
+        Option<&SyncModeOverride<P::ComponentKinds>>,
+    )>,
 ) where
     P::Components: SyncMetadata<C>,
     P::Components: ExternalMapper<C>,
+    P::ComponentKinds: FromType<C>,
 {
-    for (confirmed_entity, confirmed_component) in confirmed_entities.iter() {
+    for (confirmed_entity, confirmed_component, sync_mode_override) in confirmed_entities.iter() {
         if let Some(p) = confirmed_entity.predicted {
             if confirmed_component.is_changed() && !confirmed_component.is_added() {
                 if let Ok(mut predicted_component) = predicted_entities.get_mut(p) {
-                    match P::Components::mode() {
-                        ComponentSyncMode::Full => {
-                            error!(
-                                "The predicted entity {:?} should have a ComponentHistory",
-                                p
-                            );
-                            unreachable!(
-                                "This system should only run for ComponentSyncMode::Simple"
-                            );
-                        }
+                    match SyncModeOverride::mode::<C>(sync_mode_override, P::Components::mode()) {
+                        // fully synced components are updated via rollback
+                        ComponentSyncMode::Full => {}
                         // for sync-components, we just match the confirmed component
                         ComponentSyncMode::Simple => {
                             // map any entities from confirmed to predicted
@@ -375,6 +470,11 @@ pub(crate) fn apply_confirmed_update<C: SyncComponent, P: Protocol>(
 
 #[cfg(test)]
 mod tests {
+    use bevy::prelude::Component;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
     // use super::*;
     //
     // #[derive(Component, Clone, PartialEq, Eq, Debug)]
@@ -407,4 +507,34 @@ mod tests {
     //     assert_eq!(component_history.get_history_at_tick(Tick(0)), None);
     //     assert_eq!(component_history.buffer.len(), 1);
     // }
+
+    #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+    struct A(u32);
+
+    #[test]
+    fn test_prediction_history_read_access() {
+        let mut history = PredictionHistory::<A>::default();
+        assert_eq!(history.get(Tick(1)), None);
+
+        history
+            .buffer
+            .add_item(Tick(3), ComponentState::Updated(A(3)));
+        history
+            .buffer
+            .add_item(Tick(1), ComponentState::Updated(A(1)));
+        history.buffer.add_item(Tick(5), ComponentState::Removed);
+
+        // the history only stores the ticks where the component changed
+        assert_eq!(history.get(Tick(0)), None);
+        assert_eq!(history.get(Tick(1)), Some(&ComponentState::Updated(A(1))));
+        assert_eq!(history.get(Tick(4)), Some(&ComponentState::Updated(A(3))));
+        assert_eq!(history.get(Tick(6)), Some(&ComponentState::Removed));
+
+        assert_eq!(
+            history.iter().map(|(tick, _)| tick).collect::<Vec<_>>(),
+            vec![Tick(1), Tick(3), Tick(5)]
+        );
+        assert_eq!(history.oldest_tick(), Some(Tick(1)));
+        assert_eq!(history.newest_tick(), Some(Tick(5)));
+    }
 }
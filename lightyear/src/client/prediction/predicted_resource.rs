@@ -0,0 +1,167 @@
+//! Prediction of replicated resources
+//!
+//! A resource replicated by the server (see [`ReplicateResourceExt`](crate::prelude::ReplicateResourceExt))
+//! is usually overwritten on the client every time an update is received.
+//! With [`ResourcePredictionPlugin`], the client simulates the resource in `FixedUpdate` like a predicted component:
+//! its history is stored every tick, compared to the server value when an update is received, and the resource is
+//! rolled back to the server value (along with the predicted entities) if there is a mismatch.
+use std::marker::PhantomData;
+
+use bevy::prelude::{
+    App, Commands, DetectChanges, FixedPostUpdate, IntoSystemConfigs, Local, Plugin, PreUpdate,
+    Query, Ref, Res, ResMut, Resource,
+};
+use tracing::debug;
+
+use crate::client::components::Confirmed;
+use crate::client::prediction::plugin::PredictionSet;
+use crate::client::prediction::rollback::{Rollback, RollbackState};
+use crate::prelude::TickManager;
+use crate::shared::replication::resources::{ReplicateResource, ResourceHistory};
+
+/// Predict the replicated resource `R` on the client
+pub struct ResourcePredictionPlugin<R> {
+    _marker: PhantomData<R>,
+}
+
+impl<R> Default for ResourcePredictionPlugin<R> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Resource + Clone + PartialEq> Plugin for ResourcePredictionPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ResourceHistory<R>>();
+        app.add_systems(
+            PreUpdate,
+            (
+                check_resource_rollback::<R>.in_set(PredictionSet::CheckRollback),
+                prepare_resource_rollback::<R>.in_set(PredictionSet::PrepareRollback),
+            ),
+        );
+        app.add_systems(
+            FixedPostUpdate,
+            update_resource_history::<R>.in_set(PredictionSet::UpdateHistory),
+        );
+    }
+}
+
+/// Record the value of the resource after each tick
+pub(crate) fn update_resource_history<R: Resource + Clone>(
+    resource: Option<Res<R>>,
+    mut history: ResMut<ResourceHistory<R>>,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+    mut existed: Local<bool>,
+) {
+    let tick = match rollback.state {
+        RollbackState::Default => tick_manager.tick(),
+        RollbackState::ShouldRollback { current_tick } => current_tick,
+    };
+    match resource {
+        Some(resource) => {
+            if resource.is_changed() {
+                history.buffer.add_item(tick, Some(resource.clone()));
+            }
+            *existed = true;
+        }
+        None => {
+            // only record the removal once
+            if *existed {
+                history.buffer.add_item(tick, None);
+            }
+            *existed = false;
+        }
+    }
+}
+
+/// Compare the server value of the resource with the predicted history, and trigger a rollback on mismatch
+pub(crate) fn check_resource_rollback<R: Resource + Clone + PartialEq>(
+    replicating_entity: Query<(Ref<ReplicateResource<R>>, &Confirmed)>,
+    mut history: ResMut<ResourceHistory<R>>,
+    mut rollback: ResMut<Rollback>,
+) {
+    let Ok((replicate_resource, confirmed)) = replicating_entity.get_single() else {
+        return;
+    };
+    if !replicate_resource.is_changed() {
+        return;
+    }
+    let tick = confirmed.tick;
+    let history_value = history.pop_until_tick(tick);
+    let should_rollback = match history_value {
+        None => replicate_resource.resource.is_some(),
+        Some(history_value) => history_value != replicate_resource.resource,
+    };
+    if should_rollback && !rollback.is_rolled_back(None) {
+        debug!(
+            ?tick,
+            "Rollback check: mismatch for resource {:?}",
+            std::any::type_name::<R>()
+        );
+        // resources are shared by all the entities, so we roll back everything
+        rollback.add_group(None, tick);
+    }
+}
+
+/// Reset the resource to the server value before resimulating
+pub(crate) fn prepare_resource_rollback<R: Resource + Clone + PartialEq>(
+    mut commands: Commands,
+    replicating_entity: Query<(&ReplicateResource<R>, &Confirmed)>,
+    mut history: ResMut<ResourceHistory<R>>,
+) {
+    let Ok((replicate_resource, confirmed)) = replicating_entity.get_single() else {
+        return;
+    };
+    history.clear();
+    history
+        .buffer
+        .add_item(confirmed.tick, replicate_resource.resource.clone());
+    match &replicate_resource.resource {
+        None => commands.remove_resource::<R>(),
+        Some(value) => commands.insert_resource(value.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::shared::tick_manager::Tick;
+
+    use super::*;
+
+    #[derive(Resource, Clone, Debug, PartialEq)]
+    struct MatchPhase(u32);
+
+    #[test]
+    fn test_resource_rollback() {
+        let mut world = World::new();
+        world.init_resource::<Rollback>();
+        let mut history = ResourceHistory::<MatchPhase>::default();
+        history.buffer.add_item(Tick(2), Some(MatchPhase(1)));
+        world.insert_resource(history);
+        world.insert_resource(MatchPhase(1));
+
+        // the server value at tick 2 is different from the predicted one
+        world.spawn((
+            ReplicateResource {
+                resource: Some(MatchPhase(2)),
+            },
+            Confirmed {
+                predicted: None,
+                interpolated: None,
+                tick: Tick(2),
+            },
+        ));
+        world.run_system_once(check_resource_rollback::<MatchPhase>);
+        assert!(world.resource::<Rollback>().is_rolled_back(None));
+
+        world.run_system_once(prepare_resource_rollback::<MatchPhase>);
+        assert_eq!(world.resource::<MatchPhase>(), &MatchPhase(2));
+    }
+}
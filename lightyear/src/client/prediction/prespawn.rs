@@ -1,5 +1,6 @@
 //! Handles spawning entities that are predicted
 
+use bevy::ecs::system::Command;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::any::{Any, TypeId};
@@ -16,8 +17,10 @@ use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::rollback::{Rollback, RollbackState};
 use crate::client::prediction::Predicted;
 use crate::client::sync::client_is_synced;
+use crate::connection::client::{ClientConnection, NetClient};
+use crate::connection::id::ClientId;
 use crate::prelude::client::PredictionSet;
-use crate::prelude::{ShouldBePredicted, TickManager};
+use crate::prelude::{ShouldBePredicted, Tick, TickManager};
 use crate::protocol::Protocol;
 use crate::shared::replication::components::{DespawnTracker, Replicate};
 use crate::shared::sets::InternalReplicationSet;
@@ -350,6 +353,11 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
     }
 }
 
+/// Add this component to an entity that is spawned on both the client (in the predicted timeline)
+/// and the server, for example a projectile shot by the player.
+///
+/// When the server replicates its entity, the client will look for the pre-spawned entity with the same hash
+/// and use it as the Predicted entity, instead of spawning a duplicate.
 #[derive(
     Component, Serialize, Deserialize, Default, Debug, Copy, Clone, PartialEq, Eq, Reflect,
 )]
@@ -362,6 +370,69 @@ pub struct PreSpawnedPlayerObject {
     // pub conflict_resolution: ConflictResolution,
 }
 
+impl PreSpawnedPlayerObject {
+    /// Use a hash provided by the user to match the client and server entities.
+    ///
+    /// The hash must be the same on the client and the server (for example derived from the
+    /// shooter entity and the shot index), and unique among the entities pre-spawned around the same tick.
+    pub fn new(hash: u64) -> Self {
+        Self { hash: Some(hash) }
+    }
+
+    /// Hash for an entity spawned as a consequence of the input of a client (for example a projectile).
+    ///
+    /// The client spawns the entity when it predicts the input at `tick`, and the server spawns it when
+    /// it processes the input of `client_id` for the same tick; `index` distinguishes the entities spawned
+    /// by the same input.
+    pub fn from_input(client_id: ClientId, tick: Tick, index: u32) -> Self {
+        let mut hasher = seahash::SeaHasher::new();
+        client_id.hash(&mut hasher);
+        tick.hash(&mut hasher);
+        index.hash(&mut hasher);
+        Self::new(hasher.finish())
+    }
+}
+
+/// Command to spawn a "fire-and-match" projectile on the client, in the predicted timeline.
+///
+/// The projectile is spawned immediately with a [`PreSpawnedPlayerObject`] hash computed with
+/// [`PreSpawnedPlayerObject::from_input`] from the client id and the current tick (or the rollback tick during a rollback).
+/// The server should spawn the projectile with the same hash when it processes the client's input for that tick.
+/// When the server entity is replicated, it is matched with the client projectile, which becomes its Predicted entity.
+/// If the server rejects the shot (and never spawns the projectile), the client projectile is despawned once
+/// the server state for that tick has been received.
+pub struct SpawnPredictedProjectile<B: Bundle> {
+    bundle: B,
+    index: u32,
+}
+
+impl<B: Bundle> Command for SpawnPredictedProjectile<B> {
+    fn apply(self, world: &mut World) {
+        let tick = match world.resource::<Rollback>().state {
+            RollbackState::Default => world.resource::<TickManager>().tick(),
+            RollbackState::ShouldRollback { current_tick } => current_tick,
+        };
+        let client_id = world.resource::<ClientConnection>().id();
+        world.spawn((
+            self.bundle,
+            PreSpawnedPlayerObject::from_input(client_id, tick, self.index),
+        ));
+    }
+}
+
+pub trait PredictedProjectileCommandsExt {
+    /// Spawn a projectile in the predicted timeline, that will be matched with the projectile spawned by the server.
+    ///
+    /// `index` must be unique among the projectiles spawned by the same input (for example for a shotgun).
+    fn spawn_predicted_projectile<B: Bundle>(&mut self, bundle: B, index: u32);
+}
+
+impl PredictedProjectileCommandsExt for Commands<'_, '_> {
+    fn spawn_predicted_projectile<B: Bundle>(&mut self, bundle: B, index: u32) {
+        self.add(SpawnPredictedProjectile { bundle, index });
+    }
+}
+
 // pub enum ClientNoMatchHandling {
 //     /// If we don't get any server-entity that matches this prespawned player object, then we despawn it on the client
 //     /// Once we are sure that we won't get any more server updates for that entity
@@ -449,6 +520,7 @@ pub struct PreSpawnedPlayerObject {
 
 #[cfg(test)]
 mod tests {
+    use bevy::ecs::system::Command;
     use bevy::prelude::Entity;
     use bevy::utils::Duration;
     use hashbrown::HashMap;
@@ -460,6 +532,8 @@ mod tests {
     use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
 
+    use super::SpawnPredictedProjectile;
+
     #[test]
     fn test_compute_hash() {
         let mut stepper = BevyStepper::default();
@@ -511,4 +585,99 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_predicted_projectile() {
+        let mut stepper = BevyStepper::default();
+        let client_id = stepper.client_app.world.resource::<ClientConnection>().id();
+        let tick = stepper.client_app.world.resource::<TickManager>().tick();
+
+        // the client spawns two projectiles for the same input
+        for index in 0..2 {
+            SpawnPredictedProjectile {
+                bundle: Component1(1.0),
+                index,
+            }
+            .apply(&mut stepper.client_app.world);
+        }
+        stepper.frame_step();
+        let prediction_manager = stepper.client_app.world.resource::<PredictionManager>();
+        for index in 0..2 {
+            let hash = PreSpawnedPlayerObject::from_input(client_id, tick, index)
+                .hash
+                .unwrap();
+            assert_eq!(
+                prediction_manager
+                    .prespawn_hash_to_entities
+                    .get(&hash)
+                    .map(|entities| entities.len()),
+                Some(1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_prespawn_user_hash() {
+        let mut stepper = BevyStepper::default();
+
+        // the client pre-spawns an entity with a user-provided hash
+        let client_entity = stepper
+            .client_app
+            .world
+            .spawn((Component1(1.0), PreSpawnedPlayerObject::new(1)))
+            .id();
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<PredictionManager>()
+                .prespawn_hash_to_entities
+                .get(&1),
+            Some(&vec![client_entity])
+        );
+
+        // the server spawns the same entity with the same hash
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                PreSpawnedPlayerObject::new(1),
+                Replicate {
+                    prediction_target: NetworkTarget::All,
+                    ..Default::default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // the pre-spawned entity is used as the predicted entity of the replicated entity
+        let confirmed_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .remote_entity_map()
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .get::<Predicted>(client_entity)
+                .unwrap()
+                .confirmed_entity,
+            Some(confirmed_entity)
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .get::<Confirmed>(confirmed_entity)
+                .unwrap()
+                .predicted,
+            Some(client_entity)
+        );
+    }
 }
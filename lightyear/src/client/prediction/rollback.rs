@@ -4,10 +4,11 @@ use bevy::app::FixedMain;
 use bevy::ecs::entity::EntityHashSet;
 use bevy::ecs::reflect::ReflectResource;
 use bevy::prelude::{
-    Commands, DespawnRecursiveExt, DetectChanges, Entity, Query, Ref, Res, ResMut, Resource, With,
-    Without, World,
+    Commands, Component, DespawnRecursiveExt, DetectChanges, Entity, Event, EventWriter, Query,
+    Ref, Res, ResMut, Resource, With, Without, World,
 };
 use bevy::reflect::Reflect;
+use bevy::utils::{Duration, HashSet, Instant};
 use tracing::{debug, error, trace, trace_span};
 
 use crate::_reexport::{ComponentProtocol, FromType};
@@ -15,6 +16,7 @@ use crate::client::components::{ComponentSyncMode, Confirmed, SyncComponent};
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::prediction::correction::Correction;
+use crate::client::prediction::extrapolation::Extrapolated;
 use crate::client::prediction::predicted_history::ComponentState;
 use crate::client::prediction::resource::PredictionManager;
 use crate::prelude::client::SyncMetadata;
@@ -29,7 +31,105 @@ use super::Predicted;
 #[reflect(Resource)]
 pub struct Rollback {
     pub state: RollbackState,
-    // pub rollback_groups: EntityHashMap<ReplicationGroupId, RollbackState>,
+    /// The [`RollbackGroup`]s that are being rolled back.
+    /// If `None`, all the predicted entities are rolled back.
+    #[reflect(ignore)]
+    pub groups: Option<HashSet<RollbackGroup>>,
+    /// Index of the physics step within the rollback tick (see [`TickConfig::physics_substeps`](crate::prelude::TickConfig::physics_substeps))
+    pub(crate) substep: u16,
+}
+
+impl Rollback {
+    /// Returns true if the predicted entity (with the optional [`RollbackGroup`]) is part of the current rollback
+    pub fn is_rolled_back(&self, group: Option<&RollbackGroup>) -> bool {
+        match self.state {
+            RollbackState::Default => false,
+            RollbackState::ShouldRollback { .. } => match &self.groups {
+                None => true,
+                Some(groups) => group.is_some_and(|group| groups.contains(group)),
+            },
+        }
+    }
+
+    /// Returns true if we are currently in a rollback
+    pub fn is_rollback(&self) -> bool {
+        matches!(self.state, RollbackState::ShouldRollback { .. })
+    }
+
+    /// Start a rollback from the confirmed `tick` for the entities of the `group`.
+    ///
+    /// If the entity is not part of a group, all predicted entities will be rolled back.
+    pub(crate) fn add_group(&mut self, group: Option<&RollbackGroup>, tick: Tick) {
+        match self.state {
+            RollbackState::Default => {
+                self.state = RollbackState::ShouldRollback {
+                    // we already rolled-back the state for the entity's latest_tick
+                    // after this we will start right away with a physics update, so we need to start taking the inputs from the next tick
+                    current_tick: tick + 1,
+                };
+                self.groups = group.map(|group| HashSet::from_iter([*group]));
+                self.substep = 0;
+            }
+            RollbackState::ShouldRollback { .. } => match (&mut self.groups, group) {
+                (Some(groups), Some(group)) => {
+                    groups.insert(*group);
+                }
+                _ => self.groups = None,
+            },
+        }
+    }
+}
+
+/// Add this component to a Predicted entity to only roll it back when there is a misprediction for an entity
+/// of the same group (instead of rolling back all the predicted entities).
+///
+/// Entities that are not part of the rollback get the [`ExcludedFromRollback`] component during the rollback.
+/// Your `FixedUpdate` systems should ignore them (with `Without<ExcludedFromRollback>`) so that they are not
+/// simulated multiple times.
+/// A misprediction on a Predicted entity without a group rolls back all the predicted entities.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct RollbackGroup(pub u32);
+
+/// Marker component added during a rollback to the Predicted entities that are not being rolled back
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ExcludedFromRollback;
+
+/// Statistics about the rollbacks, used to enforce the
+/// [`PredictionConfig::rollback_budget`](crate::client::prediction::plugin::PredictionConfig::rollback_budget)
+#[derive(Resource, Debug, Default, Clone)]
+pub struct RollbackMetrics {
+    /// Number of rollbacks that resimulated ticks
+    pub num_rollbacks: u32,
+    /// Number of rollbacks that were replaced by a snap to the server state because resimulating
+    /// the ticks would have exceeded the budget
+    pub num_budget_exceeded: u32,
+    /// Moving average of the time it takes to resimulate one tick
+    pub tick_duration: Option<Duration>,
+}
+
+impl RollbackMetrics {
+    /// Weight of the latest measurement in the moving average of the tick duration
+    const SMOOTHING_FACTOR: f32 = 0.2;
+
+    fn record_tick_duration(&mut self, duration: Duration) {
+        self.tick_duration = Some(match self.tick_duration {
+            None => duration,
+            Some(average) => {
+                average.mul_f32(1.0 - Self::SMOOTHING_FACTOR)
+                    + duration.mul_f32(Self::SMOOTHING_FACTOR)
+            }
+        });
+    }
+
+    /// Number of ticks that can be resimulated in a frame without exceeding `budget` (at least 1, so that
+    /// the cost of a tick keeps being measured).
+    ///
+    /// Returns None if the cost of resimulating a tick has not been measured yet.
+    fn ticks_within_budget(&self, budget: Duration) -> Option<u32> {
+        self.tick_duration.map(|tick_duration| {
+            ((budget.as_secs_f64() / tick_duration.as_secs_f64()) as u32).max(1)
+        })
+    }
 }
 
 /// Resource that will track whether we should do rollback or not
@@ -46,6 +146,65 @@ pub enum RollbackState {
     },
 }
 
+/// Event emitted right before the client starts resimulating the ticks `from_tick..=to_tick`
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct RollbackStartEvent {
+    pub from_tick: Tick,
+    pub to_tick: Tick,
+}
+
+/// Event emitted right after the client finished resimulating the ticks `from_tick..=to_tick`
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct RollbackEndEvent {
+    pub from_tick: Tick,
+    pub to_tick: Tick,
+}
+
+/// Custom comparison used to decide if the predicted value of the component `C` mismatches the confirmed value
+/// received from the server, which triggers a rollback.
+///
+/// By default, any difference (according to `PartialEq`) triggers a rollback, so that harmless float noise can cause
+/// rollbacks. Insert this resource to use a more lenient comparison, for example:
+/// ```rust,ignore
+/// app.insert_resource(ShouldRollbackFn::<Position>::new(|predicted, confirmed| {
+///     predicted.0.distance(confirmed.0) > 0.01
+/// }));
+/// ```
+#[derive(Resource)]
+pub struct ShouldRollbackFn<C> {
+    should_rollback: fn(&C, &C) -> bool,
+}
+
+impl<C> ShouldRollbackFn<C> {
+    /// `should_rollback(predicted, confirmed)` should return true if we need to rollback
+    pub fn new(should_rollback: fn(&C, &C) -> bool) -> Self {
+        Self { should_rollback }
+    }
+}
+
+/// Returns true if the predicted value mismatches the confirmed value
+fn is_mismatch<C: PartialEq>(
+    should_rollback_fn: Option<&ShouldRollbackFn<C>>,
+    predicted: &C,
+    confirmed: &C,
+) -> bool {
+    should_rollback_fn.map_or_else(
+        || predicted != confirmed,
+        |f| (f.should_rollback)(predicted, confirmed),
+    )
+}
+
+/// Event emitted when a rollback would have needed to resimulate more than
+/// [`PredictionConfig::max_rollback_ticks`](crate::client::prediction::plugin::PredictionConfig::max_rollback_ticks)
+/// ticks, or would have exceeded the
+/// [`PredictionConfig::rollback_budget`](crate::client::prediction::plugin::PredictionConfig::rollback_budget);
+/// the predicted entities were snapped to the server state of `confirmed_tick` instead
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct RollbackSnapEvent {
+    pub confirmed_tick: Tick,
+    pub current_tick: Tick,
+}
+
 #[allow(clippy::type_complexity)]
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
@@ -55,8 +214,13 @@ pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
 
     // We also snap the value of the component to the server state if we are in rollback
     // We use Option<> because the predicted component could have been removed while it still exists in Confirmed
-    mut predicted_query: Query<&mut PredictionHistory<C>, (With<Predicted>, Without<Confirmed>)>,
+    // extrapolated entities never trigger a rollback
+    mut predicted_query: Query<
+        (&mut PredictionHistory<C>, Option<&RollbackGroup>),
+        (With<Predicted>, Without<Confirmed>, Without<Extrapolated>),
+    >,
     confirmed_query: Query<(Entity, Option<&C>, Ref<Confirmed>)>,
+    should_rollback_fn: Option<Res<ShouldRollbackFn<C>>>,
     mut rollback: ResMut<Rollback>,
 ) where
     <P as Protocol>::ComponentKinds: FromType<C>,
@@ -91,7 +255,7 @@ pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
         let Some(p) = confirmed.predicted else {
             continue;
         };
-        let Ok(mut predicted_history) = predicted_query.get_mut(p) else {
+        let Ok((mut predicted_history, group)) = predicted_query.get_mut(p) else {
             debug!("Predicted entity {:?} was not found", confirmed.predicted);
             continue;
         };
@@ -115,47 +279,41 @@ pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
         // that we should rollback (RollbackState::Default)
         // That is not the case, because if we do rollback we will need to snap the client entity to the server state
         // So either way we will need to do an operation.
-        match rollback.state {
-            // 3.a We are still not sure if we should do rollback. Compare history against confirmed
-            // We rollback if there's no history (newly added predicted entity, or if there is a mismatch)
-            RollbackState::Default => {
-                let history_value = predicted_history.pop_until_tick(tick);
-                let predicted_exist = history_value.is_some();
-                let confirmed_exist = confirmed_component.is_some();
-                let should_rollback = match confirmed_component {
-                    // TODO: history-value should not be empty here; should we panic if it is?
-                    // confirm does not exist. rollback if history value is not Removed
-                    None => history_value.map_or(false, |history_value| {
-                        history_value != ComponentState::Removed
-                    }),
-                    // confirm exist. rollback if history value is different
-                    Some(c) => history_value.map_or(true, |history_value| match history_value {
-                        ComponentState::Updated(history_value) => history_value != *c,
-                        ComponentState::Removed => true,
-                    }),
-                };
-                if should_rollback {
-                    debug!(
-                   ?predicted_exist, ?confirmed_exist,
-                   "Rollback check: mismatch for component between predicted and confirmed {:?} on tick {:?} for component {:?}. Current tick: {:?}",
-                   confirmed_entity, tick, kind, current_tick
-                   );
-                    // TODO: try atomic enum update
-                    rollback.state = RollbackState::ShouldRollback {
-                        // we already rolled-back the state for the entity's latest_tick
-                        // after this we will start right away with a physics update, so we need to start taking the inputs from the next tick
-                        current_tick: tick + 1,
-                    };
+        if rollback.is_rolled_back(group) {
+            // 3.b We already know we should do rollback for this entity (because of another entity/component)
+            trace!(
+               "Rollback check: should roll back for component between predicted and confirmed on tick {:?} for component {:?}. Current tick: {:?}",
+               tick, kind, current_tick
+               );
+            continue;
+        }
+        // 3.a We are still not sure if we should do rollback. Compare history against confirmed
+        // We rollback if there's no history (newly added predicted entity, or if there is a mismatch)
+        let history_value = predicted_history.pop_until_tick(tick);
+        let predicted_exist = history_value.is_some();
+        let confirmed_exist = confirmed_component.is_some();
+        let should_rollback = match confirmed_component {
+            // TODO: history-value should not be empty here; should we panic if it is?
+            // confirm does not exist. rollback if history value is not Removed
+            None => history_value.map_or(false, |history_value| {
+                history_value != ComponentState::Removed
+            }),
+            // confirm exist. rollback if history value is different
+            Some(c) => history_value.map_or(true, |history_value| match history_value {
+                ComponentState::Updated(history_value) => {
+                    is_mismatch(should_rollback_fn.as_deref(), &history_value, c)
                 }
-            }
-            // 3.b We already know we should do rollback (because of another entity/component), start the rollback
-            RollbackState::ShouldRollback { .. } => {
-                trace!(
-                   "Rollback check: should roll back for component between predicted and confirmed on tick {:?} for component {:?}. Current tick: {:?}",
-                   tick, kind, current_tick
-                   );
-            }
+                ComponentState::Removed => true,
+            }),
         };
+        if should_rollback {
+            debug!(
+               ?predicted_exist, ?confirmed_exist, ?group,
+               "Rollback check: mismatch for component between predicted and confirmed {:?} on tick {:?} for component {:?}. Current tick: {:?}",
+               confirmed_entity, tick, kind, current_tick
+               );
+            rollback.add_group(group, tick);
+        }
     }
 }
 
@@ -174,6 +332,7 @@ pub(crate) fn prepare_rollback<C: SyncComponent, P: Protocol>(
             Option<&mut C>,
             &mut PredictionHistory<C>,
             Option<&mut Correction<C>>,
+            Option<&RollbackGroup>,
         ),
         (
             With<Predicted>,
@@ -214,12 +373,21 @@ pub(crate) fn prepare_rollback<C: SyncComponent, P: Protocol>(
         };
 
         // 1. Get the predicted entity, and it's history
-        let Ok((predicted_entity, predicted_component, mut predicted_history, mut correction)) =
-            predicted_query.get_mut(p)
+        let Ok((
+            predicted_entity,
+            predicted_component,
+            mut predicted_history,
+            mut correction,
+            group,
+        )) = predicted_query.get_mut(p)
         else {
             debug!("Predicted entity {:?} was not found", confirmed.predicted);
             continue;
         };
+        // the entity is not part of the rollback
+        if !rollback.is_rolled_back(group) {
+            continue;
+        }
 
         // 2. we need to clear the history so we can write a new one
         predicted_history.clear();
@@ -459,27 +627,145 @@ pub(crate) fn run_rollback(world: &mut World) {
         // (we set `current_rollback_tick` to `confirmed + 1` so that on the FixedUpdate rollback run, we fetch the input for
         // `confirmed + 1`
         let num_rollback_ticks = current_tick + 1 - current_rollback_tick;
+        let substeps = tick_manager.config.substeps() as i16;
+        let tick_manager_substep = tick_manager.substep() as i16;
+        let (max_rollback_ticks, rollback_budget) =
+            world
+                .get_resource::<ClientConfig>()
+                .map_or((None, None), |config| {
+                    (
+                        config.prediction.max_rollback_ticks,
+                        config.prediction.rollback_budget,
+                    )
+                });
+        let too_deep = max_rollback_ticks.is_some_and(|max| num_rollback_ticks as i32 > max as i32);
+        if too_deep {
+            // the entities have already been snapped to the server state in `prepare_rollback`,
+            // we just don't resimulate the ticks
+            debug!(
+                ?num_rollback_ticks,
+                "Rollback is too deep, snapping to the server state instead"
+            );
+            world.send_event(RollbackSnapEvent {
+                confirmed_tick: current_rollback_tick - 1,
+                current_tick,
+            });
+            return;
+        }
+        // resimulating the ticks would stall the frame for longer than the budget: snap to the server state
+        // instead, so that low-end clients don't spiral when they fall behind
+        let budget_ticks = rollback_budget.and_then(|budget| {
+            world
+                .get_resource::<RollbackMetrics>()
+                .and_then(|metrics| metrics.ticks_within_budget(budget))
+        });
+        if let Some(budget_ticks) =
+            budget_ticks.filter(|budget_ticks| num_rollback_ticks as i64 > *budget_ticks as i64)
+        {
+            debug!(
+                ?num_rollback_ticks,
+                ?budget_ticks,
+                "Rollback exceeds the budget, snapping to the server state instead"
+            );
+            #[cfg(feature = "metrics")]
+            {
+                metrics::counter!("rollback_budget_exceeded").increment(1);
+            }
+            if let Some(mut metrics) = world.get_resource_mut::<RollbackMetrics>() {
+                metrics.num_budget_exceeded += 1;
+            }
+            world.send_event(RollbackSnapEvent {
+                confirmed_tick: current_rollback_tick - 1,
+                current_tick,
+            });
+            return;
+        }
         debug!(
             "Rollback between {:?} and {:?}",
             current_rollback_tick, current_tick
         );
+        world.send_event(RollbackStartEvent {
+            from_tick: current_rollback_tick,
+            to_tick: current_tick,
+        });
 
         // run the physics fixed update schedule (which should contain ALL predicted/rollback components)
-        for i in 0..num_rollback_ticks {
+        // NOTE: the current tick might not have run all its substeps yet (the rollback runs in PreUpdate,
+        //  possibly in the middle of a tick), so we only resimulate the substeps of the current tick that already ran
+        let num_rollback_steps = if num_rollback_ticks > 0 {
+            (num_rollback_ticks - 1) * substeps + tick_manager_substep + 1
+        } else {
+            0
+        };
+        let start = Instant::now();
+        for _ in 0..num_rollback_steps {
             // TODO: if we are in rollback, there are some FixedUpdate systems that we don't want to re-run ??
             //  for example we only want to run the physics on non-confirmed entities
             world.run_schedule(FixedMain)
         }
+        if num_rollback_ticks > 0 {
+            let elapsed = start.elapsed();
+            if let Some(mut metrics) = world.get_resource_mut::<RollbackMetrics>() {
+                metrics.num_rollbacks += 1;
+                metrics.record_tick_duration(elapsed / num_rollback_ticks as u32);
+            }
+        }
         debug!("Finished rollback. Current tick: {:?}", current_tick);
+        world.send_event(RollbackEndEvent {
+            from_tick: current_rollback_tick,
+            to_tick: current_tick,
+        });
+    }
+}
+
+/// Mark the predicted entities that are not part of the rollback
+pub(crate) fn exclude_from_rollback(
+    mut commands: Commands,
+    rollback: Res<Rollback>,
+    query: Query<(Entity, Option<&RollbackGroup>), With<Predicted>>,
+) {
+    if rollback.groups.is_none() {
+        return;
+    }
+    for (entity, group) in query.iter() {
+        if !rollback.is_rolled_back(group) {
+            commands.entity(entity).insert(ExcludedFromRollback);
+        }
     }
+}
 
-    // revert the state of Rollback for the next frame
-    let mut rollback = world.get_resource_mut::<Rollback>().unwrap();
+/// Remove the [`ExcludedFromRollback`] markers once the rollback is over
+pub(crate) fn remove_rollback_exclusion(
+    mut commands: Commands,
+    query: Query<Entity, With<ExcludedFromRollback>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).remove::<ExcludedFromRollback>();
+    }
+}
+
+/// Revert the state of Rollback for the next frame.
+///
+/// This runs after the [`PredictionSet::PostRollback`](super::plugin::PredictionSet::PostRollback) set,
+/// so that the user systems in that set can still detect that a rollback happened.
+pub(crate) fn end_rollback(mut rollback: ResMut<Rollback>) {
     rollback.state = RollbackState::Default;
+    rollback.groups = None;
+    // the rollback might have stopped in the middle of the current tick
+    rollback.substep = 0;
 }
 
-pub(crate) fn increment_rollback_tick(mut rollback: ResMut<Rollback>) {
+pub(crate) fn increment_rollback_tick(
+    mut rollback: ResMut<Rollback>,
+    tick_manager: Res<TickManager>,
+) {
     trace!("increment rollback tick");
+    // only move to the next tick after the last physics step of the tick
+    rollback.substep += 1;
+    if rollback.substep < tick_manager.config.substeps() {
+        return;
+    }
+    rollback.substep = 0;
     // update the rollback tick
     // (we already set the history for client.last_received_server_tick() in the rollback check,
     // we will start at the next tick. This is valid because this system runs after the physics systems)
@@ -491,6 +777,236 @@ pub(crate) fn increment_rollback_tick(mut rollback: ResMut<Rollback>) {
     }
 }
 
+#[cfg(test)]
+mod rollback_events_tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Events, Schedule};
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_rollback_events() {
+        let mut world = World::new();
+        let mut tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        for _ in 0..5 {
+            tick_manager.increment_tick();
+        }
+        world.insert_resource(tick_manager);
+        world.insert_resource(Rollback {
+            state: RollbackState::ShouldRollback {
+                current_tick: Tick(3),
+            },
+            ..Default::default()
+        });
+        world.init_resource::<Events<RollbackStartEvent>>();
+        world.init_resource::<Events<RollbackEndEvent>>();
+        world.init_resource::<Events<RollbackSnapEvent>>();
+        world.add_schedule(Schedule::new(FixedMain));
+
+        run_rollback(&mut world);
+        let expected = (Tick(3), Tick(5));
+        let start = world
+            .resource_mut::<Events<RollbackStartEvent>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!((start.from_tick, start.to_tick), expected);
+        let end = world
+            .resource_mut::<Events<RollbackEndEvent>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!((end.from_tick, end.to_tick), expected);
+        // the rollback state is only reset at the end of the rollback sets
+        assert!(matches!(
+            world.resource::<Rollback>().state,
+            RollbackState::ShouldRollback { .. }
+        ));
+        world.run_system_once(end_rollback);
+        assert!(matches!(
+            world.resource::<Rollback>().state,
+            RollbackState::Default
+        ));
+    }
+
+    #[derive(Resource, Default)]
+    struct NumFixedSteps(usize);
+
+    fn count_fixed_steps(mut steps: ResMut<NumFixedSteps>) {
+        steps.0 += 1;
+    }
+
+    #[test]
+    fn test_rollback_mid_tick_substeps() {
+        let mut world = World::new();
+        let mut tick_manager = TickManager::from_config(
+            TickConfig::new(Duration::from_millis(40)).with_physics_substeps(4),
+        );
+        // we are in the middle of tick 5: only its first 2 substeps ran
+        for _ in 0..(5 * 4 + 1) {
+            tick_manager.increment_tick();
+        }
+        assert_eq!(tick_manager.tick(), Tick(5));
+        assert_eq!(tick_manager.substep(), 1);
+        world.insert_resource(tick_manager);
+        world.insert_resource(Rollback {
+            state: RollbackState::ShouldRollback {
+                current_tick: Tick(3),
+            },
+            ..Default::default()
+        });
+        world.init_resource::<Events<RollbackStartEvent>>();
+        world.init_resource::<Events<RollbackEndEvent>>();
+        world.init_resource::<Events<RollbackSnapEvent>>();
+        world.init_resource::<NumFixedSteps>();
+        let mut schedule = Schedule::new(FixedMain);
+        schedule.add_systems(count_fixed_steps);
+        world.add_schedule(schedule);
+
+        // ticks 3 and 4 are resimulated fully, tick 5 only up to the substep that already ran
+        run_rollback(&mut world);
+        assert_eq!(world.resource::<NumFixedSteps>().0, 2 * 4 + 2);
+    }
+
+    #[test]
+    fn test_rollback_groups() {
+        let group_a = RollbackGroup(0);
+        let group_b = RollbackGroup(1);
+        let mut rollback = Rollback::default();
+        assert!(!rollback.is_rolled_back(Some(&group_a)));
+
+        // a misprediction in group A only rolls back group A
+        rollback.add_group(Some(&group_a), Tick(3));
+        assert!(rollback.is_rolled_back(Some(&group_a)));
+        assert!(!rollback.is_rolled_back(Some(&group_b)));
+        assert!(!rollback.is_rolled_back(None));
+
+        rollback.add_group(Some(&group_b), Tick(3));
+        assert!(rollback.is_rolled_back(Some(&group_b)));
+
+        // a misprediction on an entity without a group rolls back everything
+        rollback.add_group(None, Tick(3));
+        assert!(rollback.is_rolled_back(None));
+    }
+
+    #[test]
+    fn test_should_rollback_fn() {
+        let epsilon = ShouldRollbackFn::<f32>::new(|predicted, confirmed| {
+            (predicted - confirmed).abs() > 0.1
+        });
+        assert!(is_mismatch(None, &1.0, &1.01));
+        assert!(!is_mismatch(Some(&epsilon), &1.0, &1.01));
+        assert!(is_mismatch(Some(&epsilon), &1.0, &2.0));
+    }
+
+    #[test]
+    fn test_max_rollback_ticks() {
+        let mut world = World::new();
+        let mut config = ClientConfig::default();
+        config.prediction = config.prediction.with_max_rollback_ticks(2);
+        world.insert_resource(config);
+        let mut tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        for _ in 0..5 {
+            tick_manager.increment_tick();
+        }
+        world.insert_resource(tick_manager);
+        world.insert_resource(Rollback {
+            state: RollbackState::ShouldRollback {
+                current_tick: Tick(3),
+            },
+            ..Default::default()
+        });
+        world.init_resource::<Events<RollbackStartEvent>>();
+        world.init_resource::<Events<RollbackEndEvent>>();
+        world.init_resource::<Events<RollbackSnapEvent>>();
+        world.add_schedule(Schedule::new(FixedMain));
+
+        // we would need to resimulate 3 ticks: snap instead
+        run_rollback(&mut world);
+        assert!(world
+            .resource_mut::<Events<RollbackStartEvent>>()
+            .drain()
+            .next()
+            .is_none());
+        let snap = world
+            .resource_mut::<Events<RollbackSnapEvent>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!(snap.confirmed_tick, Tick(2));
+        assert_eq!(snap.current_tick, Tick(5));
+    }
+
+    #[test]
+    fn test_rollback_budget() {
+        let mut world = World::new();
+        let mut config = ClientConfig::default();
+        config.prediction = config
+            .prediction
+            .with_rollback_budget(Duration::from_millis(5));
+        world.insert_resource(config);
+        let mut tick_manager = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        for _ in 0..5 {
+            tick_manager.increment_tick();
+        }
+        world.insert_resource(tick_manager);
+        world.insert_resource(Rollback {
+            state: RollbackState::ShouldRollback {
+                current_tick: Tick(3),
+            },
+            ..Default::default()
+        });
+        // resimulating a tick was measured to take 2ms
+        world.insert_resource(RollbackMetrics {
+            tick_duration: Some(Duration::from_millis(2)),
+            ..Default::default()
+        });
+        world.init_resource::<Events<RollbackStartEvent>>();
+        world.init_resource::<Events<RollbackEndEvent>>();
+        world.init_resource::<Events<RollbackSnapEvent>>();
+        world.add_schedule(Schedule::new(FixedMain));
+
+        // resimulating 3 ticks would take 6ms: snap to the server state instead
+        run_rollback(&mut world);
+        assert!(world
+            .resource_mut::<Events<RollbackStartEvent>>()
+            .drain()
+            .next()
+            .is_none());
+        let snap = world
+            .resource_mut::<Events<RollbackSnapEvent>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!(snap.confirmed_tick, Tick(2));
+        assert_eq!(snap.current_tick, Tick(5));
+        let metrics = world.resource::<RollbackMetrics>();
+        assert_eq!(metrics.num_budget_exceeded, 1);
+        assert_eq!(metrics.num_rollbacks, 0);
+
+        // a shorter rollback fits in the budget: the ticks are resimulated
+        world.insert_resource(Rollback {
+            state: RollbackState::ShouldRollback {
+                current_tick: Tick(4),
+            },
+            ..Default::default()
+        });
+        run_rollback(&mut world);
+        let start = world
+            .resource_mut::<Events<RollbackStartEvent>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!(start.from_tick, Tick(4));
+        let metrics = world.resource::<RollbackMetrics>();
+        assert_eq!(metrics.num_budget_exceeded, 1);
+        assert_eq!(metrics.num_rollbacks, 1);
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use bevy::utils::Duration;
@@ -540,7 +1056,7 @@ pub(crate) fn increment_rollback_tick(mut rollback: ResMut<Rollback>) {
 //             min_delay: interpolation_delay,
 //             send_interval_ratio: 0.0,
 //         });
-//         let mut stepper = BevyStepper::new(
+//         let mut stepper = BevyStepper::from_configs(
 //             shared_config,
 //             sync_config,
 //             prediction_config,
@@ -0,0 +1,80 @@
+//! Custom simulation systems that are run every tick of the predicted timeline.
+//!
+//! Rollbacks only resimulate the `FixedMain` schedule. State that is updated outside of it (for example
+//! cooldown timers or buffs ticked in `Update`) would not be resimulated, and would drift from the server.
+//! Systems added with [`PredictedSimulationExt::add_predicted_simulation_systems`] are run once per tick,
+//! both during normal prediction and during rollback resimulation; if the state they update is predicted
+//! (a predicted component, or a resource with [`ResourcePredictionPlugin`](super::predicted_resource::ResourcePredictionPlugin)),
+//! it will be rolled back correctly.
+//!
+//! During these systems, the [`Time`] resource always contains the fixed timestep, even when the tick is being resimulated.
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::{App, Fixed, IntoSystemConfigs, Time, World};
+
+/// Schedule that runs once per tick in the predicted timeline, during `FixedPreUpdate`
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PredictedSimulation;
+
+pub trait PredictedSimulationExt {
+    /// Add systems that will be run every tick of the predicted timeline, including during rollbacks
+    fn add_predicted_simulation_systems<M>(
+        &mut self,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self;
+}
+
+impl PredictedSimulationExt for App {
+    fn add_predicted_simulation_systems<M>(
+        &mut self,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        self.add_systems(PredictedSimulation, systems)
+    }
+}
+
+/// Run the [`PredictedSimulation`] schedule with the fixed timestep
+pub(crate) fn run_predicted_simulation(world: &mut World) {
+    // during rollbacks, the FixedMain schedule is run directly so `Time` still contains the virtual time
+    let fixed_time = world.resource::<Time<Fixed>>().as_generic();
+    let previous_time = std::mem::replace(world.resource_mut::<Time>().as_mut(), fixed_time);
+    let _ = world.try_run_schedule(PredictedSimulation);
+    *world.resource_mut::<Time>() = previous_time;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Res, ResMut, Resource, Schedule};
+    use bevy::utils::Duration;
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Cooldown(Duration);
+
+    fn tick_cooldown(time: Res<Time>, mut cooldown: ResMut<Cooldown>) {
+        cooldown.0 += time.delta();
+    }
+
+    #[test]
+    fn test_predicted_simulation() {
+        let tick_duration = Duration::from_millis(10);
+        let mut world = World::new();
+        world.init_resource::<Cooldown>();
+        let mut fixed_time = Time::<Fixed>::from_duration(tick_duration);
+        fixed_time.advance_by(tick_duration);
+        world.insert_resource(fixed_time);
+        // the virtual time has a different delta
+        let mut time = Time::default();
+        time.advance_by(Duration::from_millis(25));
+        world.insert_resource(time);
+        let mut schedule = Schedule::new(PredictedSimulation);
+        schedule.add_systems(tick_cooldown);
+        world.add_schedule(schedule);
+
+        run_predicted_simulation(&mut world);
+        run_predicted_simulation(&mut world);
+        assert_eq!(world.resource::<Cooldown>().0, tick_duration * 2);
+        // the time is restored afterwards
+        assert_eq!(world.resource::<Time>().delta(), Duration::from_millis(25));
+    }
+}
@@ -0,0 +1,217 @@
+//! Smooth out the visual effect of prediction corrections on the rendered [`Transform`].
+//!
+//! When a rollback changes the position of a predicted entity, the entity would visually snap to the
+//! corrected position. Instead, we record the visual error introduced by the rollback (the difference
+//! between the transform before and after the rollback) and add it as an offset to the rendered transform,
+//! decaying it to zero over a configurable duration.
+//!
+//! The offset is only applied during the rendering part of the frame (it is removed again at the start of
+//! the next frame), so the simulation always uses the corrected value.
+use bevy::prelude::{Component, DetectChangesMut, Quat, Query, Res, Time, Transform, Vec3};
+use bevy::utils::Duration;
+
+use crate::client::easings::ease_out_quad;
+
+/// Add this component to a predicted entity to smooth out the corrections of its [`Transform`].
+#[derive(Component, Debug, Clone)]
+pub struct VisualSmoothing {
+    /// Duration over which the visual error is decayed to zero
+    pub duration: Duration,
+    /// Transform of the entity right before the rollback
+    pre_rollback: Option<Transform>,
+    /// Visual error at the time of the latest correction
+    error_translation: Vec3,
+    error_rotation: Quat,
+    /// Time elapsed since the latest correction
+    elapsed: Duration,
+    /// Offset currently applied to the transform (so that we can remove it at the start of the next frame)
+    applied_translation: Vec3,
+    applied_rotation: Quat,
+}
+
+impl VisualSmoothing {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            pre_rollback: None,
+            error_translation: Vec3::ZERO,
+            error_rotation: Quat::IDENTITY,
+            elapsed: Duration::ZERO,
+            applied_translation: Vec3::ZERO,
+            applied_rotation: Quat::IDENTITY,
+        }
+    }
+
+    fn is_smoothing(&self) -> bool {
+        self.error_translation != Vec3::ZERO || self.error_rotation != Quat::IDENTITY
+    }
+
+    /// The part of the error that still needs to be applied to the transform
+    fn remaining_offset(&self) -> (Vec3, Quat) {
+        if !self.is_smoothing() || self.elapsed >= self.duration {
+            return (Vec3::ZERO, Quat::IDENTITY);
+        }
+        let t = ease_out_quad(self.elapsed.as_secs_f32() / self.duration.as_secs_f32());
+        (
+            self.error_translation * (1.0 - t),
+            Quat::IDENTITY.slerp(self.error_rotation, 1.0 - t),
+        )
+    }
+}
+
+impl Default for VisualSmoothing {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100))
+    }
+}
+
+/// At the start of the frame, remove the visual offset so that the simulation uses the real value
+pub(crate) fn remove_visual_offset(mut query: Query<(&mut Transform, &mut VisualSmoothing)>) {
+    for (mut transform, mut smoothing) in query.iter_mut() {
+        if smoothing.applied_translation == Vec3::ZERO
+            && smoothing.applied_rotation == Quat::IDENTITY
+        {
+            continue;
+        }
+        let transform = transform.bypass_change_detection();
+        transform.translation -= smoothing.applied_translation;
+        transform.rotation = smoothing.applied_rotation.inverse() * transform.rotation;
+        smoothing.applied_translation = Vec3::ZERO;
+        smoothing.applied_rotation = Quat::IDENTITY;
+    }
+}
+
+/// Record the transform right before the rollback
+pub(crate) fn record_pre_rollback_transform(mut query: Query<(&Transform, &mut VisualSmoothing)>) {
+    for (transform, mut smoothing) in query.iter_mut() {
+        smoothing.pre_rollback = Some(*transform);
+    }
+}
+
+/// Compute the visual error introduced by the rollback.
+///
+/// If we were already in the middle of smoothing a previous correction, the remaining offset is
+/// added to the new error so that the entity doesn't jump visually.
+pub(crate) fn compute_visual_error(mut query: Query<(&Transform, &mut VisualSmoothing)>) {
+    for (transform, mut smoothing) in query.iter_mut() {
+        let Some(pre_rollback) = smoothing.pre_rollback.take() else {
+            continue;
+        };
+        let (remaining_translation, remaining_rotation) = smoothing.remaining_offset();
+        smoothing.error_translation =
+            remaining_translation + pre_rollback.translation - transform.translation;
+        smoothing.error_rotation =
+            (remaining_rotation * pre_rollback.rotation * transform.rotation.inverse()).normalize();
+        smoothing.elapsed = Duration::ZERO;
+    }
+}
+
+/// Add the decaying visual offset to the transform, right before it is propagated for rendering
+pub(crate) fn apply_visual_offset(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut VisualSmoothing)>,
+) {
+    for (mut transform, mut smoothing) in query.iter_mut() {
+        if !smoothing.is_smoothing() {
+            continue;
+        }
+        smoothing.elapsed += time.delta();
+        let (translation, rotation) = smoothing.remaining_offset();
+        if smoothing.elapsed >= smoothing.duration {
+            // the smoothing is over
+            smoothing.error_translation = Vec3::ZERO;
+            smoothing.error_rotation = Quat::IDENTITY;
+        }
+        let transform = transform.bypass_change_detection();
+        transform.translation += translation;
+        transform.rotation = rotation * transform.rotation;
+        smoothing.applied_translation = translation;
+        smoothing.applied_rotation = rotation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{IntoSystemConfigs, Local, PreUpdate, ResMut, With};
+
+    use crate::client::prediction::plugin::PredictionSet;
+    use crate::client::prediction::rollback::{Rollback, RollbackState};
+    use crate::prelude::TickManager;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    /// Force a single rollback
+    fn force_rollback(
+        mut done: Local<bool>,
+        mut rollback: ResMut<Rollback>,
+        tick_manager: Res<TickManager>,
+    ) {
+        if !*done {
+            *done = true;
+            rollback.state = RollbackState::ShouldRollback {
+                current_tick: tick_manager.tick(),
+            };
+        }
+    }
+
+    /// Simulate the reset of the predicted state to the server state at the start of the rollback
+    fn reset_to_server_state(mut query: Query<&mut Transform, With<VisualSmoothing>>) {
+        for mut transform in query.iter_mut() {
+            transform.translation.x = 0.0;
+        }
+    }
+
+    #[test]
+    fn test_visual_smoothing() {
+        let mut stepper = BevyStepper::default();
+        stepper.client_app.add_systems(
+            PreUpdate,
+            (
+                force_rollback.in_set(PredictionSet::CheckRollback),
+                reset_to_server_state.in_set(PredictionSet::PrepareRollback),
+            ),
+        );
+        let entity = stepper
+            .client_app
+            .world
+            .spawn((
+                Transform::from_xyz(10.0, 0.0, 0.0),
+                VisualSmoothing::new(Duration::from_millis(100)),
+            ))
+            .id();
+
+        // the rollback moves the entity from 10.0 to 0.0: the error is measured from the transform
+        // before the reset, and the entity is still rendered close to the pre-rollback position
+        stepper.frame_step();
+        let smoothing = stepper
+            .client_app
+            .world
+            .get::<VisualSmoothing>(entity)
+            .unwrap();
+        assert_eq!(smoothing.error_translation.x, 10.0);
+        let rendered = stepper
+            .client_app
+            .world
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .x;
+        assert!(rendered > 5.0 && rendered < 10.0);
+
+        // after the smoothing duration, the entity is rendered at the corrected position
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .get::<Transform>(entity)
+                .unwrap()
+                .translation
+                .x,
+            0.0
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Switch an entity between prediction and interpolation at runtime.
+//!
+//! Insert the [`ForcePredicted`] component on a [`Confirmed`] entity to start predicting it, and remove it to go
+//! back to interpolating it (for example when a player enters or exits a vehicle).
+//!
+//! The existing Predicted/Interpolated entity is re-used, so that the components added by the user (meshes, children, etc.)
+//! are kept: only the [`Predicted`]/[`Interpolated`] markers and the prediction/interpolation histories are swapped.
+//! When switching to prediction, a rollback is triggered to bring the entity from the confirmed tick to the
+//! current predicted tick; add [`VisualSmoothing`](super::smoothing::VisualSmoothing) to the entity to smooth out
+//! the visual jump.
+use bevy::prelude::{
+    Added, Commands, Component, Entity, Query, RemovedComponents, ResMut, With, Without,
+};
+use tracing::debug;
+
+use crate::client::components::{Confirmed, SyncComponent};
+use crate::client::interpolation::extrapolate::ExtrapolateStatus;
+use crate::client::interpolation::resource::InterpolationManager;
+use crate::client::interpolation::{ConfirmedHistory, InterpolateStatus, Interpolated};
+use crate::client::prediction::correction::Correction;
+use crate::client::prediction::predicted_history::PredictionHistory;
+use crate::client::prediction::resource::PredictionManager;
+use crate::client::prediction::rollback::Rollback;
+use crate::client::prediction::Predicted;
+
+/// Insert this component on a [`Confirmed`] entity to predict it instead of interpolating it.
+///
+/// When the component is removed, the entity is interpolated again.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ForcePredicted;
+
+/// Swap the Predicted/Interpolated markers when [`ForcePredicted`] is added or removed
+#[allow(clippy::type_complexity)]
+pub(crate) fn switch_prediction_mode(
+    mut commands: Commands,
+    mut prediction_manager: ResMut<PredictionManager>,
+    mut interpolation_manager: ResMut<InterpolationManager>,
+    mut rollback: ResMut<Rollback>,
+    mut added: Query<(Entity, &mut Confirmed), (With<ForcePredicted>, Added<ForcePredicted>)>,
+    mut removed: RemovedComponents<ForcePredicted>,
+    mut confirmed_query: Query<&mut Confirmed, Without<ForcePredicted>>,
+) {
+    for (confirmed_entity, mut confirmed) in added.iter_mut() {
+        if confirmed.predicted.is_some() {
+            continue;
+        }
+        let predicted = match confirmed.interpolated.take() {
+            Some(interpolated) => {
+                debug!(
+                    ?confirmed_entity,
+                    ?interpolated,
+                    "switching entity to prediction"
+                );
+                interpolation_manager
+                    .interpolated_entity_map
+                    .confirmed_to_interpolated
+                    .remove(&confirmed_entity);
+                commands
+                    .entity(interpolated)
+                    .remove::<Interpolated>()
+                    .insert(Predicted {
+                        confirmed_entity: Some(confirmed_entity),
+                    });
+                interpolated
+            }
+            None => commands
+                .spawn(Predicted {
+                    confirmed_entity: Some(confirmed_entity),
+                })
+                .id(),
+        };
+        prediction_manager
+            .predicted_entity_map
+            .confirmed_to_predicted
+            .insert(confirmed_entity, predicted);
+        confirmed.predicted = Some(predicted);
+        // resimulate from the confirmed state to the current tick
+        rollback.add_group(None, confirmed.tick);
+    }
+
+    for confirmed_entity in removed.read() {
+        let Ok(mut confirmed) = confirmed_query.get_mut(confirmed_entity) else {
+            continue;
+        };
+        if confirmed.interpolated.is_some() {
+            continue;
+        }
+        let Some(predicted) = confirmed.predicted.take() else {
+            continue;
+        };
+        debug!(
+            ?confirmed_entity,
+            ?predicted,
+            "switching entity to interpolation"
+        );
+        prediction_manager
+            .predicted_entity_map
+            .confirmed_to_predicted
+            .remove(&confirmed_entity);
+        interpolation_manager
+            .interpolated_entity_map
+            .confirmed_to_interpolated
+            .insert(confirmed_entity, predicted);
+        commands
+            .entity(predicted)
+            .remove::<Predicted>()
+            .insert(Interpolated { confirmed_entity });
+        confirmed.interpolated = Some(predicted);
+    }
+}
+
+/// Remove the history of the previous mode of the entity after it was switched.
+///
+/// The history of the new mode is added by the `add_component_history` systems.
+#[allow(clippy::type_complexity)]
+pub(crate) fn remove_history_on_switch<C: SyncComponent>(
+    mut commands: Commands,
+    to_predicted: Query<Entity, (Added<Predicted>, With<ConfirmedHistory<C>>)>,
+    to_interpolated: Query<Entity, (Added<Interpolated>, With<PredictionHistory<C>>)>,
+) {
+    for entity in to_predicted.iter() {
+        commands.entity(entity).remove::<(
+            ConfirmedHistory<C>,
+            InterpolateStatus<C>,
+            ExtrapolateStatus<C>,
+        )>();
+    }
+    for entity in to_interpolated.iter() {
+        commands
+            .entity(entity)
+            .remove::<(PredictionHistory<C>, Correction<C>)>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::prelude::Tick;
+
+    use super::*;
+
+    #[test]
+    fn test_switch_prediction_mode() {
+        let mut world = World::new();
+        world.init_resource::<PredictionManager>();
+        world.init_resource::<InterpolationManager>();
+        world.init_resource::<Rollback>();
+        let confirmed = world.spawn_empty().id();
+        let interpolated = world
+            .spawn(Interpolated {
+                confirmed_entity: confirmed,
+            })
+            .id();
+        world.entity_mut(confirmed).insert(Confirmed {
+            predicted: None,
+            interpolated: Some(interpolated),
+            tick: Tick(2),
+        });
+
+        // the interpolated entity becomes predicted, and we roll back from the confirmed tick
+        world.entity_mut(confirmed).insert(ForcePredicted);
+        world.run_system_once(switch_prediction_mode);
+        assert!(world.get::<Interpolated>(interpolated).is_none());
+        assert_eq!(
+            world
+                .get::<Predicted>(interpolated)
+                .unwrap()
+                .confirmed_entity,
+            Some(confirmed)
+        );
+        let confirmed_component = world.get::<Confirmed>(confirmed).unwrap();
+        assert_eq!(confirmed_component.predicted, Some(interpolated));
+        assert_eq!(confirmed_component.interpolated, None);
+        assert!(world.resource::<Rollback>().is_rolled_back(None));
+
+        // and back to interpolation
+        world.entity_mut(confirmed).remove::<ForcePredicted>();
+        world.run_system_once(switch_prediction_mode);
+        assert!(world.get::<Predicted>(interpolated).is_none());
+        assert!(world.get::<Interpolated>(interpolated).is_some());
+        let confirmed_component = world.get::<Confirmed>(confirmed).unwrap();
+        assert_eq!(confirmed_component.predicted, None);
+        assert_eq!(confirmed_component.interpolated, Some(interpolated));
+    }
+}
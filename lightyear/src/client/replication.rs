@@ -3,12 +3,20 @@ use crate::client::config::ClientConfig;
 use bevy::prelude::*;
 use bevy::utils::Duration;
 
+use crate::client::components::Confirmed;
 use crate::client::connection::ConnectionManager;
+use crate::client::interpolation::resource::InterpolationManager;
+use crate::client::prediction::resource::PredictionManager;
 use crate::client::sync::client_is_synced;
-use crate::prelude::client::InterpolationDelay;
-use crate::prelude::Protocol;
+use crate::connection::client::NetClient;
+use crate::prelude::client::{ClientConnection, InterpolationDelay};
+use crate::prelude::{
+    Authority, PrePredicted, PreSpawnedPlayerObject, Protocol, ShouldBeInterpolated,
+    ShouldBePredicted,
+};
+use crate::shared::replication::components::Replicate;
 use crate::shared::replication::plugin::ReplicationPlugin;
-use crate::shared::sets::InternalReplicationSet;
+use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 
 #[derive(Clone, Debug, Reflect)]
 pub struct ReplicationConfig {
@@ -16,6 +24,13 @@ pub struct ReplicationConfig {
     pub enable_send: bool,
     /// Set to true to enable receiving replication updates from the server
     pub enable_receive: bool,
+    /// How often the replication updates are gathered and sent to the server.
+    ///
+    /// This lets the client replicate at a lower rate while messages and inputs are still
+    /// sent every [`SharedConfig::client_send_interval`](crate::prelude::SharedConfig::client_send_interval).
+    /// The updates are only gathered on frames where packets are sent.
+    /// A duration of 0 means that the updates are gathered every time we send packets.
+    pub send_interval: Duration,
 }
 
 impl Default for ReplicationConfig {
@@ -23,6 +38,7 @@ impl Default for ReplicationConfig {
         Self {
             enable_send: false,
             enable_receive: true,
+            send_interval: Duration::default(),
         }
     }
 }
@@ -48,6 +64,7 @@ impl<P: Protocol> Plugin for ClientReplicationPlugin<P> {
                 config.shared.tick.tick_duration,
                 config.replication.enable_send,
                 config.replication.enable_receive,
+                config.replication.send_interval,
             ))
             // TODO: currently we only support pre-spawned entities spawned during the FixedUpdate schedule
             // // SYSTEM SETS
@@ -64,6 +81,70 @@ impl<P: Protocol> Plugin for ClientReplicationPlugin<P> {
                 //  But then pre-predicted entities that are spawned right away will not be replicated?
                 // NOTE: we always need to add this condition if we don't enable replication, because
                 InternalReplicationSet::<ClientMarker>::All.run_if(client_is_synced::<P>),
+            )
+            // SYSTEMS
+            .add_systems(
+                PreUpdate,
+                handle_authority_change::<P>.after(InternalMainSet::<ClientMarker>::Receive),
             );
     }
 }
+
+/// When the server gives us the [`Authority`] over an entity, we start simulating the `Confirmed` entity directly
+/// and replicating it to the server. When we lose the authority, we stop replicating it.
+fn handle_authority_change<P: Protocol>(
+    mut commands: Commands,
+    connection: Res<ClientConnection>,
+    mut prediction_manager: Option<ResMut<PredictionManager>>,
+    mut interpolation_manager: Option<ResMut<InterpolationManager>>,
+    mut query: Query<
+        (
+            Entity,
+            &Authority,
+            Option<&mut Confirmed>,
+            Has<Replicate<P>>,
+        ),
+        Changed<Authority>,
+    >,
+) {
+    let local_client = connection.id();
+    for (entity, authority, confirmed, has_replicate) in query.iter_mut() {
+        if authority.is_client(local_client) {
+            debug!(?entity, "received authority over entity");
+            if !has_replicate {
+                let mut replicate = Replicate::<P>::default();
+                // these components are controlled by the server
+                replicate.disable_component::<Authority>();
+                replicate.disable_component::<ShouldBePredicted>();
+                replicate.disable_component::<ShouldBeInterpolated>();
+                replicate.disable_component::<PrePredicted>();
+                replicate.disable_component::<PreSpawnedPlayerObject>();
+                commands.entity(entity).insert(replicate);
+            }
+            // the confirmed entity is now the one being simulated, we don't need the predicted/interpolated copies
+            if let Some(mut confirmed) = confirmed {
+                if let Some(predicted) = confirmed.predicted.take() {
+                    if let Some(manager) = prediction_manager.as_mut() {
+                        manager
+                            .predicted_entity_map
+                            .confirmed_to_predicted
+                            .remove(&entity);
+                    }
+                    commands.entity(predicted).despawn_recursive();
+                }
+                if let Some(interpolated) = confirmed.interpolated.take() {
+                    if let Some(manager) = interpolation_manager.as_mut() {
+                        manager
+                            .interpolated_entity_map
+                            .confirmed_to_interpolated
+                            .remove(&entity);
+                    }
+                    commands.entity(interpolated).despawn_recursive();
+                }
+            }
+        } else if has_replicate {
+            debug!(?entity, "lost authority over entity");
+            commands.entity(entity).remove::<Replicate<P>>();
+        }
+    }
+}
@@ -1,9 +1,11 @@
 /*! Handles syncing the time between the client and the server
 */
-use bevy::prelude::{Reflect, Res, SystemSet};
+use anyhow::{bail, Result};
+use bevy::prelude::{Event, Reflect, Res, ResMut, Resource, SystemSet};
 use bevy::utils::Duration;
 use chrono::Duration as ChronoDuration;
-use tracing::{debug, info, trace};
+use std::collections::VecDeque;
+use tracing::{debug, error, info, trace};
 
 use crate::client::connection::ConnectionManager;
 use crate::client::interpolation::plugin::InterpolationDelay;
@@ -25,6 +27,213 @@ pub fn client_is_synced<P: Protocol>(connection: Option<Res<ConnectionManager<P>
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub struct SyncSet;
 
+/// The prediction timeline of the client, which runs ahead of the server so that the inputs for tick T
+/// arrive on the server before the server simulates tick T.
+///
+/// This read-only resource is updated every frame in the [`SyncSet`] once the client is synced.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PredictionTime {
+    tick: Tick,
+    overstep: f32,
+    time: WrappedTime,
+}
+
+impl PredictionTime {
+    /// The current client tick
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Fraction of a tick that has elapsed since the start of the current tick
+    pub fn overstep(&self) -> f32 {
+        self.overstep
+    }
+
+    pub fn time(&self) -> WrappedTime {
+        self.time
+    }
+}
+
+/// The interpolation timeline of the client, which runs behind the server so that interpolated
+/// entities always have an update to interpolate towards.
+///
+/// This read-only resource is updated every frame in the [`SyncSet`] once the client is synced.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct InterpolationTime {
+    tick: Tick,
+    overstep: f32,
+    time: WrappedTime,
+}
+
+impl InterpolationTime {
+    /// The current interpolation tick
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Fraction of a tick that has elapsed since the start of the interpolation tick
+    pub fn overstep(&self) -> f32 {
+        self.overstep
+    }
+
+    pub fn time(&self) -> WrappedTime {
+        self.time
+    }
+}
+
+/// The client's estimate of the current server time.
+///
+/// This read-only resource is updated every frame in the [`SyncSet`] once the client is synced.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ServerTimeEstimate {
+    tick: Tick,
+    time: WrappedTime,
+}
+
+impl ServerTimeEstimate {
+    /// Estimate of the current server tick
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    pub fn time(&self) -> WrappedTime {
+        self.time
+    }
+}
+
+/// Update the public timeline resources from the [`SyncManager`]
+pub(crate) fn update_timelines<P: Protocol>(
+    connection: Res<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    mut prediction_time: ResMut<PredictionTime>,
+    mut interpolation_time: ResMut<InterpolationTime>,
+    mut server_time: ResMut<ServerTimeEstimate>,
+) {
+    let sync_manager = &connection.sync_manager;
+    if !sync_manager.is_synced() {
+        return;
+    }
+    let tick_duration = tick_manager.config.tick_duration;
+    *prediction_time = PredictionTime {
+        tick: tick_manager.tick(),
+        overstep: time_manager.overstep(),
+        time: sync_manager.current_prediction_time(&tick_manager, &time_manager),
+    };
+    *interpolation_time = InterpolationTime {
+        tick: sync_manager.interpolation_tick(&tick_manager),
+        overstep: sync_manager.interpolation_overstep(&tick_manager),
+        time: sync_manager.interpolation_time,
+    };
+    *server_time = ServerTimeEstimate {
+        tick: sync_manager.server_time_estimate().to_tick(tick_duration),
+        time: sync_manager.server_time_estimate(),
+    };
+}
+
+/// A sample of the sync state of the client, recorded every frame in the [`SyncDiagnostics`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncSample {
+    /// The client tick when the sample was recorded
+    pub tick: Tick,
+    /// Estimated offset (in milliseconds) between the client's prediction time and the server time.
+    /// Positive if the client is ahead of the server
+    pub clock_offset_ms: f32,
+    /// Estimated round-trip time
+    pub rtt: Duration,
+    /// Estimated jitter of the round-trip time
+    pub jitter: Duration,
+    /// Relative speed applied to the client's virtual time to keep it in sync
+    pub relative_speed: f32,
+}
+
+/// Recent history of the sync state of the client (clock offset, jitter and applied relative speed).
+///
+/// Debug UIs can plot these values to investigate rubber-banding. The history is recorded every frame
+/// in the [`SyncSet`] once the client is synced; insert the resource with [`SyncDiagnostics::with_capacity`]
+/// before adding the client plugins to keep a longer history.
+#[derive(Resource, Debug, Clone)]
+pub struct SyncDiagnostics {
+    samples: VecDeque<SyncSample>,
+    capacity: usize,
+}
+
+impl Default for SyncDiagnostics {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+impl SyncDiagnostics {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The recorded samples, from the oldest to the most recent
+    pub fn samples(&self) -> impl Iterator<Item = &SyncSample> {
+        self.samples.iter()
+    }
+
+    /// The most recent sample
+    pub fn latest(&self) -> Option<&SyncSample> {
+        self.samples.back()
+    }
+
+    fn push(&mut self, sample: SyncSample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// Record the current sync state in the [`SyncDiagnostics`]
+pub(crate) fn record_sync_diagnostics<P: Protocol>(
+    connection: Res<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+    time_manager: Res<TimeManager>,
+    prediction_time: Res<PredictionTime>,
+    server_time: Res<ServerTimeEstimate>,
+    mut diagnostics: ResMut<SyncDiagnostics>,
+) {
+    if !connection.sync_manager.is_synced() {
+        return;
+    }
+    diagnostics.push(SyncSample {
+        tick: tick_manager.tick(),
+        clock_offset_ms: (prediction_time.time - server_time.time)
+            .num_microseconds()
+            .unwrap_or_default() as f32
+            / 1000.0,
+        rtt: connection.ping_manager.rtt(),
+        jitter: connection.ping_manager.jitter(),
+        relative_speed: time_manager.get_relative_speed(),
+    });
+}
+
+/// Events emitted when the sync state of the client changes
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum SyncEvent {
+    /// The client finished the handshake and is now synced with the server
+    Synced { tick: Tick },
+    /// The client is not synced with the server anymore (for example because it was disconnected)
+    SyncLost,
+    /// The client's prediction time drifted too far from the server and the tick was snapped to the objective.
+    /// Only emitted if the tick changed by at least [`SyncConfig::snap_event_threshold`] ticks
+    TickSnap {
+        old_tick: Tick,
+        new_tick: Tick,
+        /// Estimated offset (in milliseconds) between the prediction time and its objective before the snap
+        offset_ms: i64,
+    },
+}
+
 /// Configuration for the sync manager, which is in charge of syncing the client's tick/time with the server's tick/time
 ///
 /// The sync manager runs only on the client and maintains two different times:
@@ -47,14 +256,33 @@ pub struct SyncConfig {
     pub handshake_pings: u8,
     /// Error margin for upstream throttle (in multiple of ticks)
     pub error_margin: f32,
-    /// If the error margin is too big, we snap the prediction/interpolation time to the objective value
+    /// If the error is bigger than this (in multiple of ticks), we snap the prediction/interpolation time
+    /// to the objective value instead of speeding up or slowing down
     pub max_error_margin: f32,
+    /// Error margin for the interpolation time, within which the interpolation timeline runs at normal speed
+    pub interpolation_error_margin: Duration,
     // TODO: instead of constant speedup_factor, the speedup should be linear w.r.t the offset
     /// By how much should we speed up the simulation to make ticks stay in sync with server?
+    /// Must be in `[1.0, 1.0 + max_speed_adjustment]` (see [`SyncConfig::validate`])
     pub speedup_factor: f32,
-
-    // Integration
-    server_time_estimate_smoothing: f32,
+    /// By how much should we slow down the simulation when we are too far ahead of the server?
+    /// The relative speed becomes `1.0 / slowdown_factor`. If `None`, the `speedup_factor` is used.
+    /// Must be in `[1.0, 1.0 / (1.0 - max_speed_adjustment)]`
+    pub slowdown_factor: Option<f32>,
+    /// Maximum deviation of the relative speed of the client timelines from 1.0.
+    /// For example 0.1 means that the relative speed always stays within [0.9, 1.1]. Must be in `[0.0, 1.0)`
+    pub max_speed_adjustment: f32,
+    /// Algorithm used to smooth the estimate of the server time
+    pub algorithm: SyncAlgorithm,
+    /// Minimum number of ticks by which the tick must be snapped to emit a [`SyncEvent::TickSnap`]
+    pub snap_event_threshold: u16,
+    /// If a single frame takes longer than this (in wall-clock time), we consider that the host clock jumped
+    /// (the OS suspended the process, the process was paused by a debugger, etc.) and we redo the sync handshake
+    /// with the server. If `None` (the default), clock jumps are not detected.
+    ///
+    /// This should be much larger than the longest frame hitch that you expect (loading screens, etc.),
+    /// since every detected jump interrupts the simulation until the client is synced again.
+    pub clock_jump_threshold: Option<Duration>,
 }
 
 impl Default for SyncConfig {
@@ -65,9 +293,13 @@ impl Default for SyncConfig {
             handshake_pings: 3,
             error_margin: 0.5,
             max_error_margin: 5.0,
+            interpolation_error_margin: Duration::from_millis(10),
             speedup_factor: 1.05,
-            // server_time_estimate_smoothing: 0.0,
-            server_time_estimate_smoothing: 0.2,
+            slowdown_factor: None,
+            max_speed_adjustment: 0.25,
+            algorithm: SyncAlgorithm::default(),
+            snap_event_threshold: 0,
+            clock_jump_threshold: None,
         }
     }
 }
@@ -77,6 +309,164 @@ impl SyncConfig {
         self.speedup_factor = speedup_factor;
         self
     }
+
+    pub fn with_slowdown_factor(mut self, slowdown_factor: f32) -> Self {
+        self.slowdown_factor = Some(slowdown_factor);
+        self
+    }
+
+    pub fn with_max_speed_adjustment(mut self, max_speed_adjustment: f32) -> Self {
+        self.max_speed_adjustment = max_speed_adjustment;
+        self
+    }
+
+    pub fn with_error_margin(mut self, error_margin: f32) -> Self {
+        self.error_margin = error_margin;
+        self
+    }
+
+    pub fn with_max_error_margin(mut self, max_error_margin: f32) -> Self {
+        self.max_error_margin = max_error_margin;
+        self
+    }
+
+    pub fn with_interpolation_error_margin(mut self, interpolation_error_margin: Duration) -> Self {
+        self.interpolation_error_margin = interpolation_error_margin;
+        self
+    }
+
+    /// Check that the speed adjustment factors are consistent with `max_speed_adjustment`
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..1.0).contains(&self.max_speed_adjustment) {
+            bail!(
+                "max_speed_adjustment must be in [0.0, 1.0), got {}",
+                self.max_speed_adjustment
+            );
+        }
+        let max_speedup = 1.0 + self.max_speed_adjustment;
+        if !(1.0..=max_speedup).contains(&self.speedup_factor) {
+            bail!(
+                "speedup_factor must be in [1.0, {}], got {}",
+                max_speedup,
+                self.speedup_factor
+            );
+        }
+        if let Some(slowdown_factor) = self.slowdown_factor {
+            let max_slowdown = 1.0 / (1.0 - self.max_speed_adjustment);
+            if !(1.0..=max_slowdown).contains(&slowdown_factor) {
+                bail!(
+                    "slowdown_factor must be in [1.0, {}], got {}",
+                    max_slowdown,
+                    slowdown_factor
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Relative speed to apply when a timeline is behind its objective
+    fn speedup(&self) -> f32 {
+        self.speedup_factor
+    }
+
+    /// Relative speed to apply when a timeline is ahead of its objective
+    fn slowdown(&self) -> f32 {
+        1.0 / self.slowdown_factor.unwrap_or(self.speedup_factor)
+    }
+
+    pub fn with_algorithm(mut self, algorithm: SyncAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_snap_event_threshold(mut self, snap_event_threshold: u16) -> Self {
+        self.snap_event_threshold = snap_event_threshold;
+        self
+    }
+
+    pub fn with_clock_jump_threshold(mut self, clock_jump_threshold: Option<Duration>) -> Self {
+        self.clock_jump_threshold = clock_jump_threshold;
+        self
+    }
+}
+
+/// Algorithm used to smooth the estimate of the server time.
+///
+/// Every time a packet is received from the server, we compute a new measurement of the server time
+/// (from the server tick of the packet). The error between that measurement and the current estimate
+/// is then used to correct the estimate.
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub enum SyncAlgorithm {
+    /// Exponentially weighted moving average: the estimate is corrected by `alpha * error`.
+    ///
+    /// Higher values of `alpha` (in [0.0, 1.0]) converge faster but are more sensitive to jitter.
+    Ewma { alpha: f32 },
+    /// The estimate is corrected by the median of the errors of the last `samples` measurements.
+    ///
+    /// Outliers (for example packets delayed by a lag spike) are ignored, at the cost of a slower convergence.
+    Median { samples: usize },
+    /// Kalman-style filter: the correction adapts to the expected noise of the measurements.
+    ///
+    /// - `process_noise`: variance (in ms²) of the drift of the server time between two measurements.
+    /// - `measurement_noise`: variance (in ms²) of the measurements (i.e. the jitter squared).
+    ///
+    /// A low `measurement_noise` compared to `process_noise` converges faster but smooths less.
+    Kalman {
+        process_noise: f32,
+        measurement_noise: f32,
+    },
+}
+
+impl Default for SyncAlgorithm {
+    fn default() -> Self {
+        SyncAlgorithm::Ewma { alpha: 0.8 }
+    }
+}
+
+/// State of the [`SyncAlgorithm`] used to smooth the server time estimate
+#[derive(Default, Debug)]
+struct ServerTimeFilter {
+    /// Errors (in ms) of the latest measurements, relative to the current estimate
+    errors: VecDeque<f32>,
+    /// Variance (in ms²) of the estimate
+    variance: f32,
+}
+
+impl ServerTimeFilter {
+    /// Returns the correction (in ms) to apply to the estimate, given the error (in ms) of a new measurement
+    fn correction(&mut self, algorithm: &SyncAlgorithm, error: f32) -> f32 {
+        match *algorithm {
+            SyncAlgorithm::Ewma { alpha } => alpha.clamp(0.0, 1.0) * error,
+            SyncAlgorithm::Median { samples } => {
+                self.errors.push_back(error);
+                while self.errors.len() > samples.max(1) {
+                    self.errors.pop_front();
+                }
+                let mut sorted: Vec<f32> = self.errors.iter().copied().collect();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let median = sorted[sorted.len() / 2];
+                // the errors are now relative to the corrected estimate
+                self.errors.iter_mut().for_each(|e| *e -= median);
+                median
+            }
+            SyncAlgorithm::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                // predict
+                self.variance += process_noise;
+                // update
+                let gain = self.variance / (self.variance + measurement_noise).max(f32::EPSILON);
+                self.variance *= 1.0 - gain;
+                gain * error
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.errors.clear();
+        self.variance = 0.0;
+    }
 }
 
 #[derive(Default)]
@@ -102,6 +492,7 @@ pub struct SyncManager {
 
     // time
     server_time_estimate: WrappedTime,
+    server_time_filter: ServerTimeFilter,
     pub(crate) interpolation_time: WrappedTime,
     interpolation_speed_ratio: f32,
 
@@ -117,17 +508,32 @@ pub struct SyncManager {
     /// The Tick associated with the 'server_tick_generation' (it might not be the same as latest_received_server_tick
     /// because we update the generation only from pong messages)
     pub(crate) server_pong_tick: Tick,
+    /// Estimate of the jitter of the arrival times of the server packets
+    pub(crate) server_packet_jitter: Duration,
+    /// Offset between the prediction time and its objective that caused the latest tick snap
+    pub(crate) snap_offset: ChronoDuration,
+    /// Number of ticks added to the prediction time objective, requested by the server depending on the
+    /// health of our input buffer on the server (see [`InputNudgeConfig`](crate::server::input::InputNudgeConfig))
+    pub(crate) server_nudge: i16,
 }
 
 // TODO: split into PredictionTime Manager, InterpolationTime Manager
 impl SyncManager {
-    pub fn new(config: SyncConfig, input_delay_ticks: u16) -> Self {
+    pub fn new(mut config: SyncConfig, input_delay_ticks: u16) -> Self {
+        if let Err(e) = config.validate() {
+            error!("Invalid sync config, falling back to the default speed adjustment: {e:?}");
+            let default = SyncConfig::default();
+            config.speedup_factor = default.speedup_factor;
+            config.slowdown_factor = default.slowdown_factor;
+            config.max_speed_adjustment = default.max_speed_adjustment;
+        }
         Self {
             config,
             input_delay_ticks,
             synced: false,
             // time
             server_time_estimate: WrappedTime::default(),
+            server_time_filter: ServerTimeFilter::default(),
             interpolation_time: WrappedTime::default(),
             interpolation_speed_ratio: 1.0,
             // server tick
@@ -136,6 +542,9 @@ impl SyncManager {
             new_latest_received_server_tick: false,
             server_pong_generation: 0,
             server_pong_tick: Tick(0),
+            server_packet_jitter: Duration::default(),
+            snap_offset: ChronoDuration::zero(),
+            server_nudge: 0,
         }
     }
 
@@ -218,10 +627,29 @@ impl SyncManager {
         }
     }
 
+    /// Rescale the time estimates when the tick duration changes, so that they still point to the same ticks
+    pub(crate) fn rescale_tick_duration(&mut self, old: Duration, new: Duration) {
+        let ratio = new.as_secs_f32() / old.as_secs_f32();
+        self.server_time_estimate = self.server_time_estimate * ratio;
+        self.interpolation_time = self.interpolation_time * ratio;
+        self.duration_since_latest_received_server_tick = self
+            .duration_since_latest_received_server_tick
+            .mul_f32(ratio);
+    }
+
     pub(crate) fn is_synced(&self) -> bool {
         self.synced
     }
 
+    /// Restart the sync handshake from scratch (for example after the host clock jumped).
+    /// The timelines run at normal speed until the handshake is finalized again.
+    pub(crate) fn resync(&mut self, time_manager: &mut TimeManager) {
+        self.synced = false;
+        self.server_time_filter.reset();
+        self.server_nudge = 0;
+        time_manager.sync_relative_speed = 1.0;
+    }
+
     /// Compute the current client time; we will make sure that the client tick is ahead of the server tick
     /// Even if it is wrapped around.
     /// (i.e. if client tick is 1, and server tick is 65535, we act as if the client tick was 65537)
@@ -299,10 +727,16 @@ impl SyncManager {
         //  might be earlier than what we compute using the server tick
         if self.server_time_estimate == WrappedTime::default() || !self.is_synced() {
             self.server_time_estimate = new_server_time_estimate;
+            self.server_time_filter.reset();
         } else {
-            self.server_time_estimate = self.server_time_estimate
-                * self.config.server_time_estimate_smoothing
-                + new_server_time_estimate * (1.0 - self.config.server_time_estimate_smoothing);
+            let error = (new_server_time_estimate - self.server_time_estimate)
+                .num_microseconds()
+                .unwrap_or_default() as f32
+                / 1000.0;
+            let correction = self
+                .server_time_filter
+                .correction(&self.config.algorithm, error);
+            self.server_time_estimate += ChronoDuration::microseconds((correction * 1000.0) as i64);
         }
         debug!(
             ?new_server_time_estimate,
@@ -332,6 +766,8 @@ impl SyncManager {
         ChronoDuration::nanoseconds(
             jitter.as_nanos() as i64 * self.config.jitter_multiple_margin as i64
                 + tick_duration.as_nanos() as i64 * self.config.tick_margin as i64
+                // offset requested by the server depending on the health of our input buffer
+                + tick_duration.as_nanos() as i64 * self.server_nudge as i64
                 - input_delay.as_nanos() as i64,
         )
     }
@@ -360,6 +796,23 @@ impl SyncManager {
         )
     }
 
+    /// Update the estimate of the jitter of the server packets' arrival times, when we receive a packet for a new
+    /// server tick. (similar to the interarrival jitter of RFC 3550)
+    pub(crate) fn update_server_packet_jitter(
+        &mut self,
+        expected_interval: Duration,
+        actual_interval: Duration,
+    ) {
+        let deviation = if actual_interval > expected_interval {
+            actual_interval - expected_interval
+        } else {
+            expected_interval - actual_interval
+        };
+        let jitter = self.server_packet_jitter.as_secs_f32();
+        self.server_packet_jitter =
+            Duration::from_secs_f32(jitter + (deviation.as_secs_f32() - jitter) / 16.0);
+    }
+
     pub(crate) fn interpolation_objective(
         &self,
         // TODO: make interpolation delay part of SyncConfig?
@@ -376,9 +829,10 @@ impl SyncManager {
         // let objective_time = self.server_time_estimate();
         // how much we want interpolation time to be behind the latest received server tick?
         // TODO: use a specified config margin + add std of time_between_server_updates?
-        let objective_delta =
-            chrono::Duration::from_std(interpolation_delay.to_duration(server_send_interval))
-                .unwrap();
+        let objective_delta = chrono::Duration::from_std(
+            interpolation_delay.to_duration(server_send_interval, self.server_packet_jitter),
+        )
+        .unwrap();
         // info!("objective_delta: {:?}", objective_delta);
         self.server_time_estimate() - objective_delta
     }
@@ -393,6 +847,23 @@ impl SyncManager {
             .tick_overstep(tick_manager.config.tick_duration)
     }
 
+    /// Interpolation time for a component that uses `delay` instead of the global `interpolation_delay`
+    pub(crate) fn interpolation_time_with_delay(
+        &self,
+        interpolation_delay: &InterpolationDelay,
+        delay: &InterpolationDelay,
+        server_send_interval: Duration,
+    ) -> WrappedTime {
+        let global_delay =
+            interpolation_delay.to_duration(server_send_interval, self.server_packet_jitter);
+        let delay = delay.to_duration(server_send_interval, self.server_packet_jitter);
+        if delay >= global_delay {
+            self.interpolation_time - (delay - global_delay)
+        } else {
+            self.interpolation_time + (global_delay - delay)
+        }
+    }
+
     // TODO: only run when there's a change? (new server tick received or new ping received)
     // TODO: change name to make it clear that we might modify speed
     pub(crate) fn update_interpolation_time(
@@ -431,20 +902,33 @@ impl SyncManager {
             return;
         }
 
-        // TODO: make this configurable
-        let error_margin = chrono::Duration::milliseconds(10);
+        let error_margin =
+            chrono::Duration::from_std(self.config.interpolation_error_margin).unwrap();
         if delta > error_margin {
             // interpolation time is too far behind, speed-up!
-            self.interpolation_speed_ratio = 1.0 * self.config.speedup_factor;
+            self.interpolation_speed_ratio = self.config.speedup();
             trace!("interpolation is too far behind, speed up!");
         } else if delta < -error_margin {
             trace!("interpolation is too far ahead, slow down!");
-            self.interpolation_speed_ratio = 1.0 / self.config.speedup_factor;
+            self.interpolation_speed_ratio = self.config.slowdown();
         } else {
             self.interpolation_speed_ratio = 1.0;
         }
     }
 
+    /// Returns the [`SyncEvent`] corresponding to a tick snap performed by the sync manager, if it is
+    /// larger than the configured threshold
+    pub(crate) fn snap_event(&self, tick_event: &TickEvent) -> Option<SyncEvent> {
+        let TickEvent::TickSnap { old_tick, new_tick } = *tick_event;
+        ((new_tick - old_tick).unsigned_abs() >= self.config.snap_event_threshold).then(|| {
+            SyncEvent::TickSnap {
+                old_tick,
+                new_tick,
+                offset_ms: self.snap_offset.num_milliseconds(),
+            }
+        })
+    }
+
     /// Update the client time ("upstream-throttle"): speed-up or down depending on the
     /// The objective of update-client-time is to make sure the client packets for tick T arrive on server before server reaches tick T
     /// but not too far ahead
@@ -496,7 +980,7 @@ impl SyncManager {
                 error_margin_time_ms = ?error_margin_time.num_milliseconds(),
                 "Error too big, snapping prediction time/tick to objective",
             );
-
+            self.snap_offset = error;
             return self.finalize(time_manager, tick_manager, ping_manager);
         }
 
@@ -513,7 +997,7 @@ impl SyncManager {
                 "Too far ahead of server! Slow down!",
             );
             // we are too far ahead of the server, slow down
-            1.0 / self.config.speedup_factor
+            self.config.slowdown()
         } else if error < -error_margin_time {
             debug!(
                 ?rtt,
@@ -527,7 +1011,7 @@ impl SyncManager {
                 "Too far behind of server! Speed up!",
             );
             // we are too far behind the server, speed up
-            1.0 * self.config.speedup_factor
+            self.config.speedup()
         } else {
             // we are within margins
             trace!("good speed");
@@ -593,6 +1077,9 @@ mod tests {
     use bevy::utils::Duration;
 
     use crate::client::input::{InputManager, InputSystemSet};
+    use crate::client::interpolation::plugin::AdaptiveInterpolationDelay;
+    use crate::client::networking::NetworkingState;
+    use crate::connection::client::{ClientConnection, NetClient};
     use crate::prelude::*;
     use crate::server::events::InputEvent;
     use crate::tests::protocol::*;
@@ -627,7 +1114,7 @@ mod tests {
             incoming_jitter: Duration::from_millis(0),
             incoming_loss: 0.0,
         };
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             SyncConfig::default(),
             client::PredictionConfig::default(),
@@ -704,4 +1191,431 @@ mod tests {
             &Component1(1.0)
         );
     }
+
+    #[test]
+    fn test_tick_duration_change() {
+        let mut stepper = BevyStepper::default();
+        let tick_duration = Duration::from_millis(20);
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .set_tick_duration(tick_duration);
+        stepper.frame_step();
+        // the change is scheduled in the future, so that the client can apply it at the same tick
+        let (change_tick, _) = stepper
+            .server_app
+            .world
+            .resource::<ServerConnectionManager>()
+            .scheduled_tick_duration
+            .unwrap();
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .resource::<TickManager>()
+                .config
+                .tick_duration,
+            Duration::from_millis(10)
+        );
+        for _ in 0..60 {
+            stepper.frame_step();
+        }
+        assert!(stepper.server_app.world.resource::<TickManager>().tick() > change_tick);
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .pending_tick_durations
+            .is_empty());
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .resource::<TickManager>()
+                .config
+                .tick_duration,
+            tick_duration
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<TickManager>()
+                .config
+                .tick_duration,
+            tick_duration
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<Time<Fixed>>()
+                .timestep(),
+            tick_duration
+        );
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<client::ClientConfig>()
+                .shared
+                .tick
+                .tick_duration,
+            tick_duration
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .is_synced());
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .pause();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert!(stepper
+            .server_app
+            .world
+            .resource::<Time<Virtual>>()
+            .is_paused());
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<Time<Virtual>>()
+            .is_paused());
+
+        // the ticks don't advance while the simulation is paused
+        let server_tick = stepper.server_tick();
+        let client_tick = stepper.client_tick();
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+        assert_eq!(stepper.server_tick(), server_tick);
+        assert_eq!(stepper.client_tick(), client_tick);
+        // the connection is kept alive
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<ClientConnection>()
+                .state(),
+            NetworkingState::Connected
+        );
+
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .resume();
+        for _ in 0..10 {
+            stepper.frame_step();
+        }
+        assert!(!stepper
+            .server_app
+            .world
+            .resource::<Time<Virtual>>()
+            .is_paused());
+        assert!(!stepper
+            .client_app
+            .world
+            .resource::<Time<Virtual>>()
+            .is_paused());
+        assert!(stepper.server_tick() > server_tick);
+        assert!(stepper.client_tick() > client_tick);
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<ClientConnectionManager>()
+                .sync_manager
+                .latest_received_server_tick
+                .map(|tick| tick >= server_tick),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_clock_jump_resync() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .client_app
+            .world
+            .resource_mut::<crate::client::config::ClientConfig>()
+            .sync
+            .clock_jump_threshold = Some(Duration::from_secs(1));
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .is_synced());
+
+        // the host was suspended for a few seconds
+        stepper.advance_time(Duration::from_secs(3));
+        stepper.client_app.update();
+        stepper.server_app.update();
+        assert!(!stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .is_synced());
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<TimeManager>()
+                .get_relative_speed(),
+            1.0
+        );
+
+        // the client redoes the handshake instead of speeding up massively
+        for _ in 0..100 {
+            stepper.frame_step();
+        }
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .is_synced());
+        let relative_speed = stepper
+            .client_app
+            .world
+            .resource::<TimeManager>()
+            .get_relative_speed();
+        assert!((0.75..=1.25).contains(&relative_speed));
+    }
+
+    #[test]
+    fn test_timeline_resources() {
+        let mut stepper = BevyStepper::default();
+        stepper.frame_step();
+        let world = &stepper.client_app.world;
+        assert_eq!(
+            world.resource::<PredictionTime>().tick(),
+            world.resource::<TickManager>().tick()
+        );
+        let manager = world.resource::<ClientConnectionManager>();
+        assert_eq!(
+            world.resource::<InterpolationTime>().tick(),
+            manager
+                .sync_manager
+                .interpolation_tick(world.resource::<TickManager>())
+        );
+        // the client runs ahead of the server, and the interpolation timeline behind
+        let server_tick = world.resource::<ServerTimeEstimate>().tick();
+        assert!(world.resource::<PredictionTime>().tick() > server_tick);
+        assert!(world.resource::<InterpolationTime>().tick() < server_tick);
+    }
+
+    #[test]
+    fn test_sync_diagnostics() {
+        let mut stepper = BevyStepper::default();
+        stepper.frame_step_n(10);
+        let diagnostics = stepper.client_resource::<SyncDiagnostics>();
+        assert!(diagnostics.samples().count() >= 10);
+        let latest = diagnostics.latest().unwrap();
+        assert_eq!(latest.tick, stepper.client_resource::<TickManager>().tick());
+        // the client runs ahead of the server so that its inputs arrive in time
+        assert!(latest.clock_offset_ms >= 0.0);
+        assert!(latest.relative_speed > 0.0);
+
+        // the oldest samples are dropped
+        let mut diagnostics = SyncDiagnostics::with_capacity(2);
+        for i in 0..3 {
+            diagnostics.push(SyncSample {
+                tick: Tick(i),
+                clock_offset_ms: 0.0,
+                rtt: Duration::default(),
+                jitter: Duration::default(),
+                relative_speed: 1.0,
+            });
+        }
+        assert_eq!(
+            diagnostics.samples().map(|s| s.tick).collect::<Vec<_>>(),
+            vec![Tick(1), Tick(2)]
+        );
+    }
+
+    #[test]
+    fn test_speed_adjustment() {
+        let config = SyncConfig::default();
+        assert_eq!(config.speedup(), 1.05);
+        assert_eq!(config.slowdown(), 1.0 / 1.05);
+
+        let config = SyncConfig::default()
+            .speedup_factor(1.08)
+            .with_slowdown_factor(1.02)
+            .with_max_speed_adjustment(0.1);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.speedup(), 1.08);
+        assert_eq!(config.slowdown(), 1.0 / 1.02);
+
+        // out-of-range factors are reported instead of being clamped
+        let config = SyncConfig::default()
+            .speedup_factor(1.5)
+            .with_max_speed_adjustment(0.1);
+        assert!(config.validate().is_err());
+        assert!(SyncConfig::default()
+            .speedup_factor(0.9)
+            .validate()
+            .is_err());
+        assert!(SyncConfig::default()
+            .with_slowdown_factor(2.0)
+            .validate()
+            .is_err());
+        assert!(SyncConfig::default()
+            .with_max_speed_adjustment(1.0)
+            .validate()
+            .is_err());
+
+        // an invalid config falls back to the default speed adjustment
+        let sync_manager = SyncManager::new(config, 0);
+        assert_eq!(sync_manager.config.speedup(), 1.05);
+
+        // a speedup factor of 1.0 disables the speed adjustments
+        let config = SyncConfig::default().speedup_factor(1.0);
+        assert_eq!(config.speedup(), 1.0);
+        assert_eq!(config.slowdown(), 1.0);
+    }
+
+    #[test]
+    fn test_snap_event() {
+        let mut sync_manager =
+            SyncManager::new(SyncConfig::default().with_snap_event_threshold(5), 0);
+        sync_manager.snap_offset = ChronoDuration::milliseconds(-80);
+        let small_snap = TickEvent::TickSnap {
+            old_tick: Tick(10),
+            new_tick: Tick(7),
+        };
+        assert_eq!(sync_manager.snap_event(&small_snap), None);
+        let large_snap = TickEvent::TickSnap {
+            old_tick: Tick(10),
+            new_tick: Tick(2),
+        };
+        assert_eq!(
+            sync_manager.snap_event(&large_snap),
+            Some(SyncEvent::TickSnap {
+                old_tick: Tick(10),
+                new_tick: Tick(2),
+                offset_ms: -80,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sync_lost_on_disconnect() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .client_app
+            .world
+            .resource_mut::<NextState<NetworkingState>>()
+            .set(NetworkingState::Disconnected);
+        stepper.frame_step();
+        let events = stepper.client_app.world.resource::<Events<SyncEvent>>();
+        assert_eq!(
+            events.get_reader().read(events).collect::<Vec<_>>(),
+            vec![&SyncEvent::SyncLost]
+        );
+    }
+
+    #[test]
+    fn test_sync_algorithms() {
+        // ewma
+        let mut filter = ServerTimeFilter::default();
+        let ewma = SyncAlgorithm::Ewma { alpha: 0.5 };
+        assert_eq!(filter.correction(&ewma, 10.0), 5.0);
+
+        // the median ignores the outlier
+        let mut filter = ServerTimeFilter::default();
+        let median = SyncAlgorithm::Median { samples: 3 };
+        assert_eq!(filter.correction(&median, 0.0), 0.0);
+        assert_eq!(filter.correction(&median, 1.0), 1.0);
+        assert_eq!(filter.correction(&median, 100.0), 0.0);
+
+        // the kalman filter smooths the corrections but converges towards a constant offset
+        let mut filter = ServerTimeFilter::default();
+        let kalman = SyncAlgorithm::Kalman {
+            process_noise: 1.0,
+            measurement_noise: 4.0,
+        };
+        let mut error: f32 = 10.0;
+        for _ in 0..20 {
+            let correction = filter.correction(&kalman, error);
+            assert!(correction > 0.0 && correction < error);
+            error -= correction;
+        }
+        assert!(error.abs() < 0.1);
+    }
+
+    #[test]
+    fn test_adaptive_interpolation_delay() {
+        let send_interval = Duration::from_millis(50);
+        let delay = InterpolationDelay::default().with_adaptive(
+            AdaptiveInterpolationDelay::default()
+                .with_jitter_multiple(2.0)
+                .with_delay_bounds(Duration::ZERO, Duration::from_millis(100)),
+        );
+        let mut sync_manager = SyncManager::new(SyncConfig::default(), 0);
+        // packets arrive exactly at the send interval: no jitter
+        for _ in 0..10 {
+            sync_manager.update_server_packet_jitter(send_interval, send_interval);
+        }
+        assert_eq!(
+            delay.to_duration(send_interval, sync_manager.server_packet_jitter),
+            send_interval
+        );
+
+        // packets arrive irregularly: the delay increases
+        for i in 0..100 {
+            let actual = if i % 2 == 0 {
+                Duration::from_millis(30)
+            } else {
+                Duration::from_millis(70)
+            };
+            sync_manager.update_server_packet_jitter(send_interval, actual);
+        }
+        let jitter = sync_manager.server_packet_jitter;
+        assert!(jitter > Duration::from_millis(15));
+        assert_eq!(
+            delay.to_duration(send_interval, jitter),
+            send_interval + jitter.mul_f32(2.0)
+        );
+
+        // the delay is bounded
+        sync_manager.server_packet_jitter = Duration::from_millis(200);
+        assert_eq!(
+            delay.to_duration(send_interval, sync_manager.server_packet_jitter),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_interpolation_time_with_delay() {
+        let send_interval = Duration::from_millis(50);
+        // global delay of 100ms
+        let global_delay = InterpolationDelay::default();
+        let mut sync_manager = SyncManager::new(SyncConfig::default(), 0);
+        sync_manager.interpolation_time = WrappedTime::from_duration(Duration::from_millis(1000));
+
+        // a component with a bigger delay is further behind
+        let slow_delay = InterpolationDelay::default().with_send_interval_ratio(4.0);
+        assert_eq!(
+            sync_manager.interpolation_time_with_delay(&global_delay, &slow_delay, send_interval),
+            WrappedTime::from_duration(Duration::from_millis(900))
+        );
+        // a component with a smaller delay is closer to the server
+        let fast_delay = InterpolationDelay::default().with_send_interval_ratio(1.0);
+        assert_eq!(
+            sync_manager.interpolation_time_with_delay(&global_delay, &fast_delay, send_interval),
+            WrappedTime::from_duration(Duration::from_millis(1050))
+        );
+    }
 }
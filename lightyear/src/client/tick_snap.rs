@@ -0,0 +1,159 @@
+//! Keep user data that stores ticks consistent when the client tick is snapped.
+//!
+//! When the client drifts too far from the server, the sync manager snaps the client tick to a new value and emits
+//! a [`TickEvent::TickSnap`]. Any tick stored by the user (the end tick of a cooldown, the tick of a scheduled action, etc.)
+//! then points to the wrong moment of the simulation.
+//!
+//! Components and resources registered with [`TickSnapExt`] are automatically offset by the tick difference
+//! when a snap happens:
+//! ```rust,no_run,ignore
+//! #[derive(Component)]
+//! struct Cooldown {
+//!     ready_at: Tick,
+//! }
+//!
+//! impl TickSnapAdjust for Cooldown {
+//!     fn adjust_ticks(&mut self, delta: i16) {
+//!         self.ready_at.adjust_ticks(delta);
+//!     }
+//! }
+//!
+//! app.adjust_component_on_tick_snap::<Cooldown>();
+//! ```
+use bevy::prelude::{
+    App, Component, EventReader, IntoSystemConfigs, PostUpdate, Query, ResMut, Resource,
+};
+
+use crate::client::sync::SyncSet;
+use crate::shared::tick_manager::{Tick, TickEvent};
+
+/// Data that contains ticks of the client timeline, and must be offset when the client tick is snapped
+pub trait TickSnapAdjust {
+    /// Offset all the ticks by `delta` (the new tick minus the old tick)
+    fn adjust_ticks(&mut self, delta: i16);
+}
+
+impl TickSnapAdjust for Tick {
+    fn adjust_ticks(&mut self, delta: i16) {
+        *self = *self + delta;
+    }
+}
+
+impl<T: TickSnapAdjust> TickSnapAdjust for Option<T> {
+    fn adjust_ticks(&mut self, delta: i16) {
+        if let Some(inner) = self {
+            inner.adjust_ticks(delta);
+        }
+    }
+}
+
+pub trait TickSnapExt {
+    /// Offset the ticks of every component `C` when the client tick is snapped
+    fn adjust_component_on_tick_snap<C: Component + TickSnapAdjust>(&mut self) -> &mut Self;
+
+    /// Offset the ticks of the resource `R` when the client tick is snapped
+    fn adjust_resource_on_tick_snap<R: Resource + TickSnapAdjust>(&mut self) -> &mut Self;
+}
+
+impl TickSnapExt for App {
+    fn adjust_component_on_tick_snap<C: Component + TickSnapAdjust>(&mut self) -> &mut Self {
+        self.add_systems(PostUpdate, adjust_components::<C>.after(SyncSet))
+    }
+
+    fn adjust_resource_on_tick_snap<R: Resource + TickSnapAdjust>(&mut self) -> &mut Self {
+        self.add_systems(PostUpdate, adjust_resource::<R>.after(SyncSet))
+    }
+}
+
+/// Total tick offset of the snaps that happened since the last time the events were read
+fn snap_delta(tick_events: &mut EventReader<TickEvent>) -> i16 {
+    tick_events
+        .read()
+        .map(|event| match event {
+            TickEvent::TickSnap { old_tick, new_tick } => *new_tick - *old_tick,
+        })
+        .fold(0, i16::wrapping_add)
+}
+
+fn adjust_components<C: Component + TickSnapAdjust>(
+    mut tick_events: EventReader<TickEvent>,
+    mut query: Query<&mut C>,
+) {
+    let delta = snap_delta(&mut tick_events);
+    if delta != 0 {
+        query
+            .iter_mut()
+            .for_each(|mut component| component.adjust_ticks(delta));
+    }
+}
+
+fn adjust_resource<R: Resource + TickSnapAdjust>(
+    mut tick_events: EventReader<TickEvent>,
+    resource: Option<ResMut<R>>,
+) {
+    let delta = snap_delta(&mut tick_events);
+    if delta != 0 {
+        if let Some(mut resource) = resource {
+            resource.adjust_ticks(delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Events, MinimalPlugins};
+
+    use super::*;
+
+    #[derive(Component, Resource, Debug, PartialEq)]
+    struct Cooldown {
+        ready_at: Option<Tick>,
+    }
+
+    impl TickSnapAdjust for Cooldown {
+        fn adjust_ticks(&mut self, delta: i16) {
+            self.ready_at.adjust_ticks(delta);
+        }
+    }
+
+    #[test]
+    fn test_adjust_on_tick_snap() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<TickEvent>()
+            .insert_resource(Cooldown {
+                ready_at: Some(Tick(10)),
+            })
+            .adjust_component_on_tick_snap::<Cooldown>()
+            .adjust_resource_on_tick_snap::<Cooldown>();
+        let entity = app
+            .world
+            .spawn(Cooldown {
+                ready_at: Some(Tick(65530)),
+            })
+            .id();
+        let none_entity = app.world.spawn(Cooldown { ready_at: None }).id();
+
+        app.world
+            .resource_mut::<Events<TickEvent>>()
+            .send(TickEvent::TickSnap {
+                old_tick: Tick(100),
+                new_tick: Tick(108),
+            });
+        app.update();
+
+        assert_eq!(app.world.resource::<Cooldown>().ready_at, Some(Tick(18)));
+        assert_eq!(
+            app.world.get::<Cooldown>(entity).unwrap().ready_at,
+            Some(Tick(2))
+        );
+        assert_eq!(
+            app.world.get::<Cooldown>(none_entity).unwrap().ready_at,
+            None
+        );
+
+        // no snap: nothing changes
+        app.update();
+        assert_eq!(app.world.resource::<Cooldown>().ready_at, Some(Tick(18)));
+    }
+}
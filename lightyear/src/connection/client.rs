@@ -6,12 +6,14 @@ use anyhow::Result;
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::{NextState, Reflect, ResMut, Resource};
 use enum_dispatch::enum_dispatch;
+use tracing::error;
 
 use crate::_reexport::ReadWordBuffer;
 use crate::client::config::NetcodeConfig;
 use crate::client::networking::NetworkingState;
 use crate::connection::id::ClientId;
-use crate::connection::netcode::ConnectToken;
+use crate::connection::netcode::{ConnectToken, DisconnectReason};
+use crate::connection::replay::{PacketLog, PacketRecorder};
 
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::client::SteamConfig;
@@ -53,6 +55,17 @@ pub trait NetClient: Send + Sync {
 
     /// Get mutable access to the inner io
     fn io_mut(&mut self) -> Option<&mut Io>;
+
+    /// Reason why the connection with the server was closed, if it is known
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        None
+    }
+
+    /// Decode the tick generation that is written after the header of the packets if extended ticks are enabled
+    /// (see [`TickConfig::extended`](crate::prelude::TickConfig::extended))
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        let _ = extended_ticks;
+    }
 }
 
 #[enum_dispatch(NetClient)]
@@ -61,12 +74,41 @@ pub(crate) enum NetClientDispatch {
     #[cfg(all(feature = "steam", not(target_family = "wasm")))]
     Steam(super::steam::client::Client),
     Local(super::local::client::Client),
+    Replay(super::replay::client::Client),
 }
 
 /// Resource that holds the client connection
 #[derive(Resource)]
 pub struct ClientConnection {
     pub(crate) client: NetClientDispatch,
+    /// Records the packets received from the server, if a recording is in progress
+    recorder: Option<PacketRecorder>,
+}
+
+impl ClientConnection {
+    fn new(client: NetClientDispatch) -> Self {
+        Self {
+            client,
+            recorder: None,
+        }
+    }
+
+    /// Start recording the packets received from the server.
+    ///
+    /// The recording can be replayed with [`NetConfig::Replay`]
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(PacketRecorder::new(self.id()));
+    }
+
+    /// Stop the recording, and return the packets that were received since the recording started
+    pub fn stop_recording(&mut self) -> Option<PacketLog> {
+        self.recorder.take().map(PacketRecorder::finish)
+    }
+
+    /// Returns true if the packets received from the server are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -90,6 +132,12 @@ pub enum NetConfig {
     Local {
         id: u64,
     },
+    /// Replay the packets of a recorded session instead of connecting to a server
+    /// (see [`ClientConnection::start_recording`])
+    Replay {
+        #[reflect(ignore)]
+        log: PacketLog,
+    },
 }
 
 impl Default for NetConfig {
@@ -122,9 +170,7 @@ impl NetConfig {
                     io_config,
                     io: None,
                 };
-                ClientConnection {
-                    client: NetClientDispatch::Netcode(client),
-                }
+                ClientConnection::new(NetClientDispatch::Netcode(client))
             }
             #[cfg(all(feature = "steam", not(target_family = "wasm")))]
             NetConfig::Steam {
@@ -134,15 +180,15 @@ impl NetConfig {
                 // TODO: handle errors
                 let client = super::steam::client::Client::new(config, conditioner)
                     .expect("could not create steam client");
-                ClientConnection {
-                    client: NetClientDispatch::Steam(client),
-                }
+                ClientConnection::new(NetClientDispatch::Steam(client))
             }
             NetConfig::Local { id } => {
                 let client = super::local::client::Client::new(id);
-                ClientConnection {
-                    client: NetClientDispatch::Local(client),
-                }
+                ClientConnection::new(NetClientDispatch::Local(client))
+            }
+            NetConfig::Replay { log } => {
+                let client = super::replay::client::Client::new(log);
+                ClientConnection::new(NetClientDispatch::Replay(client))
             }
         }
     }
@@ -162,11 +208,20 @@ impl NetClient for ClientConnection {
     }
 
     fn try_update(&mut self, delta_ms: f64) -> Result<()> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.update(delta_ms);
+        }
         self.client.try_update(delta_ms)
     }
 
     fn recv(&mut self) -> Option<Packet> {
-        self.client.recv()
+        let packet = self.client.recv()?;
+        if let Some(recorder) = &mut self.recorder {
+            let _ = recorder.record(&packet).map_err(|e| {
+                error!("could not record packet: {:?}", e);
+            });
+        }
+        Some(packet)
     }
 
     fn send(&mut self, buf: &[u8]) -> Result<()> {
@@ -188,6 +243,14 @@ impl NetClient for ClientConnection {
     fn io_mut(&mut self) -> Option<&mut Io> {
         self.client.io_mut()
     }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.client.disconnect_reason()
+    }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.client.set_extended_ticks(extended_ticks)
+    }
 }
 
 #[derive(Resource, Default, Clone)]
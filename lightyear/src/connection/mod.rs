@@ -7,6 +7,8 @@ pub(crate) mod server;
 
 pub mod id;
 mod local;
+/// Record the packets received from the server and replay them
+pub mod replay;
 #[cfg_attr(docsrs, doc(cfg(all(feature = "steam", not(target_family = "wasm")))))]
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 pub(crate) mod steam;
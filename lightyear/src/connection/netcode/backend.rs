@@ -0,0 +1,176 @@
+//! Glue between an authentication service and the generation of [`ConnectToken`]s.
+//!
+//! In the netcode protocol, clients don't connect to the game server directly: they first authenticate
+//! with a web backend, which returns a [`ConnectToken`] that the client then presents to the game server.
+//!
+//! The [`TokenBackend`] does the second half of that work: it calls a user-provided async handler that
+//! authenticates the credentials sent by a client (a session cookie, an OAuth token, etc.), and generates a
+//! fresh [`ConnectToken`] for the authenticated client. It does not open any socket: plug
+//! [`TokenBackend::issue_bytes`] into the HTTPS endpoint of your web framework, since the token contains the
+//! encryption keys of the connection and must not be sent in clear.
+//!
+//! A connected client can also fetch a new token before its current one expires, and present it with
+//! [`ConnectionManager::refresh_token`](crate::client::connection::ConnectionManager::refresh_token) to extend its
+//! session without reconnecting.
+//! ```rust,no_run,ignore
+//! let backend = TokenBackend::new(protocol_id, private_key, server_addr, |credentials: Vec<u8>| async move {
+//!     let account = accounts.verify_session(&credentials).await?;
+//!     Some(AuthenticatedClient::new(account.id).with_user_data(account.party_data()))
+//! });
+//!
+//! // in the HTTPS handler
+//! let token_bytes = backend.issue_bytes(request_body).await?;
+//! ```
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use thiserror::Error;
+
+use super::{
+    token::TOKEN_EXPIRE_SEC, ClientId, ConnectToken, Key, CONNECTION_TIMEOUT_SEC,
+    CONNECT_TOKEN_BYTES, USER_DATA_BYTES,
+};
+
+/// An error that can occur when issuing a [`ConnectToken`]
+#[derive(Error, Debug)]
+pub enum TokenBackendError {
+    #[error("the client could not be authenticated")]
+    Unauthorized,
+    #[error(transparent)]
+    Netcode(#[from] super::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// A client that was authenticated by the handler of the [`TokenBackend`]
+#[derive(Debug, Clone)]
+pub struct AuthenticatedClient {
+    /// The id of the client, must be unique for each client
+    pub client_id: ClientId,
+    /// Data added to the token (for example an account id), that the server can read when the client connects
+    pub user_data: [u8; USER_DATA_BYTES],
+}
+
+impl AuthenticatedClient {
+    pub fn new(client_id: ClientId) -> Self {
+        Self {
+            client_id,
+            user_data: [0; USER_DATA_BYTES],
+        }
+    }
+
+    pub fn with_user_data(mut self, user_data: [u8; USER_DATA_BYTES]) -> Self {
+        self.user_data = user_data;
+        self
+    }
+}
+
+type AuthenticateFn =
+    Arc<dyn Fn(Vec<u8>) -> BoxFuture<'static, Option<AuthenticatedClient>> + Send + Sync>;
+
+/// Issues [`ConnectToken`]s to the clients that are authenticated by a user-provided async handler
+#[derive(Clone)]
+pub struct TokenBackend {
+    protocol_id: u64,
+    private_key: Key,
+    server_addr: SocketAddr,
+    expire_seconds: i32,
+    timeout_seconds: i32,
+    authenticate: AuthenticateFn,
+}
+
+impl TokenBackend {
+    /// Create a backend that issues tokens for the server at `server_addr`.
+    ///
+    /// `protocol_id` and `private_key` must be the same as the ones used by the server.
+    /// `authenticate` receives the credentials sent by the client, and returns `None` if they are invalid.
+    pub fn new<F, Fut>(
+        protocol_id: u64,
+        private_key: Key,
+        server_addr: SocketAddr,
+        authenticate: F,
+    ) -> Self
+    where
+        F: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<AuthenticatedClient>> + Send + 'static,
+    {
+        Self {
+            protocol_id,
+            private_key,
+            server_addr,
+            expire_seconds: TOKEN_EXPIRE_SEC,
+            timeout_seconds: CONNECTION_TIMEOUT_SEC,
+            authenticate: Arc::new(move |credentials| authenticate(credentials).boxed()),
+        }
+    }
+
+    /// Set the time in seconds that the issued tokens will be valid for.
+    pub fn expire_seconds(mut self, expire_seconds: i32) -> Self {
+        self.expire_seconds = expire_seconds;
+        self
+    }
+
+    /// Set the time in seconds after which a connection is closed if no packets are received.
+    pub fn timeout_seconds(mut self, timeout_seconds: i32) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+
+    /// Authenticate the `credentials` and generate a [`ConnectToken`] for the client
+    pub async fn issue(&self, credentials: Vec<u8>) -> Result<ConnectToken, TokenBackendError> {
+        let client = (self.authenticate)(credentials)
+            .await
+            .ok_or(TokenBackendError::Unauthorized)?;
+        let token = ConnectToken::build(
+            self.server_addr,
+            self.protocol_id,
+            client.client_id,
+            self.private_key,
+        )
+        .expire_seconds(self.expire_seconds)
+        .timeout_seconds(self.timeout_seconds)
+        .user_data(client.user_data)
+        .generate()?;
+        Ok(token)
+    }
+
+    /// Authenticate the `credentials` and generate a serialized [`ConnectToken`], ready to be sent to the client
+    pub async fn issue_bytes(
+        &self,
+        credentials: Vec<u8>,
+    ) -> Result<[u8; CONNECT_TOKEN_BYTES], TokenBackendError> {
+        Ok(self.issue(credentials).await?.try_into_bytes()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use crate::connection::netcode::{generate_key, NetcodeClient};
+
+    use super::*;
+
+    #[test]
+    fn test_issue_token() {
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let backend = TokenBackend::new(
+            0,
+            generate_key(),
+            server_addr,
+            |credentials: Vec<u8>| async move {
+                (credentials == b"secret").then(|| AuthenticatedClient::new(7))
+            },
+        );
+
+        assert!(matches!(
+            block_on(backend.issue(b"wrong".to_vec())),
+            Err(TokenBackendError::Unauthorized)
+        ));
+        let token_bytes = block_on(backend.issue_bytes(b"secret".to_vec())).unwrap();
+        assert!(NetcodeClient::new(&token_bytes).is_ok());
+    }
+}
@@ -19,7 +19,8 @@ use super::{
     bytes::Bytes,
     error::{Error, Result},
     packet::{
-        DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket, RequestPacket, ResponsePacket,
+        DisconnectPacket, DisconnectReason, KeepAlivePacket, Packet, PayloadPacket, RequestPacket,
+        ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken},
@@ -194,8 +195,11 @@ pub struct NetcodeClient<Ctx = ()> {
     replay_protection: ReplayProtection,
     should_disconnect: bool,
     should_disconnect_state: ClientState,
+    disconnect_reason: Option<DisconnectReason>,
     packet_queue: VecDeque<crate::packet::packet::Packet>,
     buffer_pool: BufferPool,
+    /// If true, the packets contain the generation of the tick after their header
+    pub(crate) extended_ticks: bool,
     cfg: ClientConfig<Ctx>,
 }
 
@@ -229,8 +233,10 @@ impl<Ctx> NetcodeClient<Ctx> {
             replay_protection: ReplayProtection::new(),
             should_disconnect: false,
             should_disconnect_state: ClientState::Disconnected,
+            disconnect_reason: None,
             packet_queue: VecDeque::new(),
             buffer_pool: BufferPool::default(),
+            extended_ticks: false,
             cfg,
         })
     }
@@ -406,19 +412,29 @@ impl<Ctx> NetcodeClient<Ctx> {
                 // instead of allocating a new buffer, fetch one from the pool
                 trace!("read from netcode client pre");
                 let mut reader = self.buffer_pool.start_read(pkt.buf);
-                let packet = crate::packet::packet::Packet::decode(&mut reader)
-                    .map_err(|_| super::packet::Error::InvalidPayload)?;
+                let packet =
+                    crate::packet::packet::Packet::decode(&mut reader, self.extended_ticks);
                 trace!(
                     "read from netcode client post; pool len: {}",
                     self.buffer_pool.0.len()
                 );
                 // return the buffer to the pool
                 self.buffer_pool.attach(reader);
+                // drop packets that cannot be decoded instead of stopping to receive packets
+                let Ok(packet) = packet.inspect_err(|e| {
+                    error!("client ignored payload packet that could not be decoded: {e:?}")
+                }) else {
+                    return Ok(());
+                };
                 // TODO: control the size/memory of the packet queue?
                 self.packet_queue.push_back(packet);
             }
-            (Packet::Disconnect(_), ClientState::Connected) => {
-                debug!("client received disconnect packet from server");
+            (Packet::Disconnect(packet), ClientState::Connected) => {
+                debug!(
+                    "client received disconnect packet from server with reason {:?}",
+                    packet.reason
+                );
+                self.disconnect_reason = Some(packet.reason);
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::Disconnected;
             }
@@ -465,6 +481,7 @@ impl<Ctx> NetcodeClient<Ctx> {
             }
             ClientState::Connected if is_connection_timed_out => {
                 info!("client connection timed out");
+                self.disconnect_reason = Some(DisconnectReason::IdleTimeout);
                 ClientState::ConnectionTimedOut
             }
             _ => return,
@@ -517,6 +534,7 @@ impl<Ctx> NetcodeClient<Ctx> {
     /// This function does not perform any IO, it only readies the client to send/receive packets on the next call to [`update`](NetcodeClient::update). <br>
     pub fn connect(&mut self) {
         self.reset_connection();
+        self.disconnect_reason = None;
         self.set_state(ClientState::SendingConnectionRequest);
         info!(
             "client connecting to server {} [{}/{}]",
@@ -613,7 +631,7 @@ impl<Ctx> NetcodeClient<Ctx> {
             self.cfg.num_disconnect_packets
         );
         for _ in 0..self.cfg.num_disconnect_packets {
-            self.send_packet(DisconnectPacket::create(), io)?;
+            self.send_packet(DisconnectPacket::create(DisconnectReason::Unspecified), io)?;
         }
         self.reset(ClientState::Disconnected);
         Ok(())
@@ -640,6 +658,11 @@ impl<Ctx> NetcodeClient<Ctx> {
     pub fn is_disconnected(&self) -> bool {
         self.state == ClientState::Disconnected
     }
+    /// Returns the reason why the connection with the server was closed, if the server provided one
+    /// or if the connection timed out.
+    pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.disconnect_reason
+    }
 }
 
 /// Client that can establish a connection to the Server
@@ -716,4 +739,12 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
     fn io_mut(&mut self) -> Option<&mut Io> {
         self.io.as_mut()
     }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.client.disconnect_reason()
+    }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.client.extended_ticks = extended_ticks;
+    }
 }
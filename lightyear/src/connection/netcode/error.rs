@@ -17,6 +17,12 @@ pub enum Error {
     SystemTime(#[from] std::time::SystemTimeError),
     #[error("invalid connect token: {0}")]
     InvalidToken(super::token::InvalidTokenError),
+    #[error("the connect token was issued for client {actual} instead of client {expected}")]
+    ClientIdMismatch { expected: u64, actual: u64 },
+    #[error("the connect token has already been used")]
+    TokenAlreadyUsed,
+    #[error("the connect token was rejected by the server")]
+    TokenRejected,
     #[error(transparent)]
     Crypto(#[from] super::crypto::Error),
     #[error("invalid packet: {0}")]
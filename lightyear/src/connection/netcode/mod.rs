@@ -29,6 +29,9 @@
  5. The `Server` makes sure the token is valid and allows the `Client` to connect.
  6. The `Client` and `Server` can now exchange encrypted and signed UDP packets.
 
+ Steps 2 and 3 can be implemented with a `TokenBackend` (behind the `token_backend` feature), which generates
+ tokens for the clients that are authenticated by your own handler.
+
  To learn more about the netcode protocol, see the upstream [specification](https://github.com/networkprotocol/netcode/blob/master/STANDARD.md).
 
  ## Server
@@ -123,12 +126,21 @@ loop {
 ```
 */
 
+#[cfg_attr(docsrs, doc(cfg(feature = "token_backend")))]
+#[cfg(feature = "token_backend")]
+pub use backend::{AuthenticatedClient, TokenBackend, TokenBackendError};
 pub use client::{Client, ClientConfig, ClientState, NetcodeClient};
 pub use crypto::{generate_key, try_generate_key, Key};
 pub use error::{Error, Result};
-pub use server::{Callback, ClientId, NetcodeServer, Server, ServerConfig};
+pub use packet::DisconnectReason;
+pub use server::{
+    Callback, ClientId, ConnectionFilter, ConnectionRequest, ConnectionRequestHandler,
+    NetcodeServer, Server, ServerConfig, MAX_CLIENTS,
+};
 pub use token::{ConnectToken, ConnectTokenBuilder, InvalidTokenError};
 
+#[cfg(feature = "token_backend")]
+mod backend;
 mod bytes;
 mod client;
 mod crypto;
@@ -3,6 +3,7 @@ use std::{
     mem::size_of,
 };
 
+use bevy::reflect::Reflect;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chacha20poly1305::XNonce;
 use tracing::debug;
@@ -270,22 +271,72 @@ impl PayloadPacket<'_> {
     }
 }
 
-pub struct DisconnectPacket {}
+/// Reason included in the disconnect packets, so that the remote knows why the connection was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum DisconnectReason {
+    /// No reason was provided
+    #[default]
+    Unspecified,
+    /// The server decided to disconnect the client
+    Kicked,
+    /// The server is shutting down
+    ServerShutdown,
+    /// The client is running a version of the game that is not compatible with the server
+    VersionMismatch,
+    /// No packets were received from the remote for too long
+    IdleTimeout,
+    /// The connect token of the client expired, and the client did not present a new one
+    TokenExpired,
+}
+
+impl DisconnectReason {
+    fn to_u8(self) -> u8 {
+        match self {
+            DisconnectReason::Unspecified => 0,
+            DisconnectReason::Kicked => 1,
+            DisconnectReason::ServerShutdown => 2,
+            DisconnectReason::VersionMismatch => 3,
+            DisconnectReason::IdleTimeout => 4,
+            DisconnectReason::TokenExpired => 5,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => DisconnectReason::Kicked,
+            2 => DisconnectReason::ServerShutdown,
+            3 => DisconnectReason::VersionMismatch,
+            4 => DisconnectReason::IdleTimeout,
+            5 => DisconnectReason::TokenExpired,
+            // remotes that don't send a reason are treated as unspecified
+            _ => DisconnectReason::Unspecified,
+        }
+    }
+}
+
+pub struct DisconnectPacket {
+    pub reason: DisconnectReason,
+}
 
 impl DisconnectPacket {
-    pub fn create() -> Packet<'static> {
-        Packet::Disconnect(Self {})
+    pub fn create(reason: DisconnectReason) -> Packet<'static> {
+        Packet::Disconnect(Self { reason })
     }
 }
 
 impl Bytes for DisconnectPacket {
     type Error = io::Error;
-    fn write_to(&self, _writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+        writer.write_u8(self.reason.to_u8())?;
         Ok(())
     }
 
-    fn read_from(_reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
-        Ok(Self {})
+    fn read_from(reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
+        // packets sent by older peers do not contain a reason
+        let reason = reader
+            .read_u8()
+            .map_or(DisconnectReason::Unspecified, DisconnectReason::from_u8);
+        Ok(Self { reason })
     }
 }
 
@@ -679,7 +730,7 @@ mod tests {
         let sequence = 0u64;
         let mut replay_protection = ReplayProtection::new();
 
-        let packet = Packet::Disconnect(DisconnectPacket {});
+        let packet = DisconnectPacket::create(DisconnectReason::ServerShutdown);
 
         let mut buf = [0u8; MAX_PKT_BUF_SIZE];
         let size = packet
@@ -696,9 +747,14 @@ mod tests {
         )
         .unwrap();
 
-        let Packet::Disconnect(_disconnect_pkt) = packet else {
+        let Packet::Disconnect(disconnect_pkt) = packet else {
             panic!("wrong packet type");
         };
+        assert_eq!(disconnect_pkt.reason, DisconnectReason::ServerShutdown);
+
+        // a disconnect packet without a reason is still a valid disconnect packet
+        let packet = DisconnectPacket::read_from(&mut std::io::Cursor::new(&[][..])).unwrap();
+        assert_eq!(packet.reason, DisconnectReason::Unspecified);
     }
 
     #[test]
@@ -1,5 +1,6 @@
-use std::collections::{HashMap, VecDeque};
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Context};
@@ -22,12 +23,12 @@ use super::{
     error::{Error, Result},
     generate_key,
     packet::{
-        ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket,
-        RequestPacket, ResponsePacket,
+        ChallengePacket, DeniedPacket, DisconnectPacket, DisconnectReason, KeepAlivePacket, Packet,
+        PayloadPacket, RequestPacket, ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
-    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
+    MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC, USER_DATA_BYTES,
 };
 
 pub const MAX_CLIENTS: usize = 256;
@@ -91,6 +92,9 @@ struct Connection {
     send_key: Key,
     receive_key: Key,
     sequence: u64,
+    user_data: [u8; USER_DATA_BYTES],
+    /// Unix timestamp (in seconds) at which the connect token of the client expires
+    expire_timestamp: u64,
 }
 
 impl Connection {
@@ -150,12 +154,16 @@ impl ConnectionCache {
         timeout: i32,
         send_key: Key,
         receive_key: Key,
+        user_data: [u8; USER_DATA_BYTES],
+        expire_timestamp: u64,
     ) {
         if let Some((_, ref mut existing)) = self.find_by_addr(&addr) {
             existing.client_id = client_id;
             existing.timeout = timeout;
             existing.send_key = send_key;
             existing.receive_key = receive_key;
+            existing.user_data = user_data;
+            existing.expire_timestamp = expire_timestamp;
             existing.last_access_time = self.time;
             return;
         }
@@ -171,6 +179,8 @@ impl ConnectionCache {
             send_key,
             receive_key,
             sequence: 0,
+            user_data,
+            expire_timestamp,
         };
         self.clients.insert(client_id, conn);
         self.replay_protection
@@ -218,12 +228,96 @@ impl ConnectionCache {
 
 pub type Callback<Ctx> = Box<dyn FnMut(ClientId, &mut Ctx) + Send + Sync + 'static>;
 
+/// A connection request from a client that presented a valid connect token, before the client is admitted
+#[derive(Debug, Clone)]
+pub struct ConnectionRequest {
+    pub client_id: ClientId,
+    pub addr: SocketAddr,
+    /// The user data contained in the connect token
+    pub user_data: [u8; USER_DATA_BYTES],
+    /// Number of clients that are currently connected to the server
+    pub num_connected_clients: usize,
+    /// Maximum number of clients that can be connected to the server
+    pub max_clients: usize,
+}
+
+/// Decides whether a [`ConnectionRequest`] is accepted (`true`) or denied (`false`)
+pub type ConnectionRequestHandler = Arc<dyn Fn(&ConnectionRequest) -> bool + Send + Sync>;
+
+/// Allow and deny lists of IP addresses and client ids, checked before any state is allocated
+/// for a connection request.
+///
+/// A request is rejected if its address or client id is denied. If an allow list is not empty, only the
+/// addresses (or client ids) that it contains are accepted.
+/// Changing the filter does not affect the clients that are already connected.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionFilter {
+    allowed_ips: HashSet<IpAddr>,
+    denied_ips: HashSet<IpAddr>,
+    allowed_client_ids: HashSet<ClientId>,
+    denied_client_ids: HashSet<ClientId>,
+}
+
+impl ConnectionFilter {
+    /// Only accept the clients connecting from the IP addresses that were allowed
+    pub fn allow_ip(&mut self, ip: IpAddr) -> &mut Self {
+        self.allowed_ips.insert(ip);
+        self
+    }
+    pub fn remove_allowed_ip(&mut self, ip: &IpAddr) -> &mut Self {
+        self.allowed_ips.remove(ip);
+        self
+    }
+    /// Reject the clients connecting from this IP address
+    pub fn deny_ip(&mut self, ip: IpAddr) -> &mut Self {
+        self.denied_ips.insert(ip);
+        self
+    }
+    pub fn remove_denied_ip(&mut self, ip: &IpAddr) -> &mut Self {
+        self.denied_ips.remove(ip);
+        self
+    }
+    /// Only accept the client ids that were allowed
+    pub fn allow_client_id(&mut self, client_id: ClientId) -> &mut Self {
+        self.allowed_client_ids.insert(client_id);
+        self
+    }
+    pub fn remove_allowed_client_id(&mut self, client_id: ClientId) -> &mut Self {
+        self.allowed_client_ids.remove(&client_id);
+        self
+    }
+    /// Reject the clients with this id
+    pub fn deny_client_id(&mut self, client_id: ClientId) -> &mut Self {
+        self.denied_client_ids.insert(client_id);
+        self
+    }
+    pub fn remove_denied_client_id(&mut self, client_id: ClientId) -> &mut Self {
+        self.denied_client_ids.remove(&client_id);
+        self
+    }
+
+    /// Returns true if a client can connect from the IP address `ip`
+    pub fn accepts_ip(&self, ip: &IpAddr) -> bool {
+        !self.denied_ips.contains(ip)
+            && (self.allowed_ips.is_empty() || self.allowed_ips.contains(ip))
+    }
+    /// Returns true if a client with the id `client_id` can connect
+    pub fn accepts_client_id(&self, client_id: ClientId) -> bool {
+        !self.denied_client_ids.contains(&client_id)
+            && (self.allowed_client_ids.is_empty() || self.allowed_client_ids.contains(&client_id))
+    }
+}
+
 /// Configuration for a server.
 ///
 /// * `num_disconnect_packets` - The number of redundant disconnect packets that will be sent to a client when the server is disconnecting it.
 /// * `keep_alive_send_rate` - The rate at which keep-alive packets will be sent to clients.
 /// * `on_connect` - A callback that will be called when a client is connected to the server.
 /// * `on_disconnect` - A callback that will be called when a client is disconnected from the server.
+/// * `on_connection_request` - A handler that decides whether a client is allowed to connect to the server.
+/// * `disconnect_on_token_expiry` - Whether clients are disconnected when their connect token expires.
+/// * `max_clients` - The maximum number of clients that can be connected at the same time.
+/// * `connection_filter` - Allow and deny lists of addresses and client ids.
 ///
 /// # Example
 /// ```
@@ -250,6 +344,10 @@ pub struct ServerConfig<Ctx> {
     context: Ctx,
     on_connect: Option<Callback<Ctx>>,
     on_disconnect: Option<Callback<Ctx>>,
+    on_connection_request: Option<ConnectionRequestHandler>,
+    disconnect_on_token_expiry: bool,
+    max_clients: usize,
+    connection_filter: ConnectionFilter,
 }
 
 impl Default for ServerConfig<()> {
@@ -263,6 +361,10 @@ impl Default for ServerConfig<()> {
             context: (),
             on_connect: None,
             on_disconnect: None,
+            on_connection_request: None,
+            disconnect_on_token_expiry: false,
+            max_clients: MAX_CLIENTS,
+            connection_filter: ConnectionFilter::default(),
         }
     }
 }
@@ -283,6 +385,10 @@ impl<Ctx> ServerConfig<Ctx> {
             context: ctx,
             on_connect: None,
             on_disconnect: None,
+            on_connection_request: None,
+            disconnect_on_token_expiry: false,
+            max_clients: MAX_CLIENTS,
+            connection_filter: ConnectionFilter::default(),
         }
     }
     /// Set the number of redundant disconnect packets that will be sent to a client when the server is disconnecting it. <br>
@@ -337,6 +443,33 @@ impl<Ctx> ServerConfig<Ctx> {
         self.on_disconnect = Some(Box::new(cb));
         self
     }
+    /// Provide a handler that will be called when a client with a valid connect token asks to connect. <br>
+    /// The client is denied if the handler returns `false`; this can be used to ban clients, keep slots
+    /// reserved for some players, etc.
+    pub fn on_connection_request(mut self, handler: ConnectionRequestHandler) -> Self {
+        self.on_connection_request = Some(handler);
+        self
+    }
+    /// Disconnect the clients whose connect token expired, with the reason [`DisconnectReason::TokenExpired`]. <br>
+    /// Clients can extend their session without reconnecting by presenting a new token
+    /// (see [`NetcodeServer::refresh_token`]). The default is `false`: the token is only checked when connecting.
+    pub fn disconnect_on_token_expiry(mut self, disconnect: bool) -> Self {
+        self.disconnect_on_token_expiry = disconnect;
+        self
+    }
+    /// Set the maximum number of clients that can be connected at the same time. <br>
+    /// The default is [`MAX_CLIENTS`]. It can be changed while the server is running with
+    /// [`NetcodeServer::set_max_clients`].
+    pub fn max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+    /// Set the allow and deny lists of addresses and client ids. <br>
+    /// The filter can be modified while the server is running with [`NetcodeServer::connection_filter_mut`].
+    pub fn connection_filter(mut self, filter: ConnectionFilter) -> Self {
+        self.connection_filter = filter;
+        self
+    }
 }
 
 /// The `netcode` server.
@@ -382,6 +515,8 @@ pub struct NetcodeServer<Ctx = ()> {
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
+    /// If true, the packets contain the generation of the tick after their header
+    pub(crate) extended_ticks: bool,
     cfg: ServerConfig<Ctx>,
 }
 
@@ -400,6 +535,7 @@ impl NetcodeServer {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            extended_ticks: false,
             cfg: ServerConfig::default(),
         };
         // info!("server started on {}", server.io.local_addr());
@@ -434,6 +570,7 @@ impl<Ctx> NetcodeServer<Ctx> {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            extended_ticks: false,
             cfg,
         };
         // info!("server started on {}", server.addr());
@@ -494,10 +631,16 @@ impl<Ctx> NetcodeServer<Ctx> {
                 if let Some(idx) = client_id {
                     // use a buffer from the pool to avoid re-allocating
                     let mut reader = self.conn_cache.buffer_pool.start_read(packet.buf);
-                    let packet = crate::packet::packet::Packet::decode(&mut reader)
-                        .map_err(|_| super::packet::Error::InvalidPayload)?;
+                    let packet =
+                        crate::packet::packet::Packet::decode(&mut reader, self.extended_ticks);
                     // return the buffer to the pool
                     self.conn_cache.buffer_pool.attach(reader);
+                    // drop packets that cannot be decoded instead of stopping to receive packets
+                    let Ok(packet) = packet.inspect_err(|e| {
+                        error!("server ignored payload packet from client {idx} that could not be decoded: {e:?}")
+                    }) else {
+                        return Ok(());
+                    };
                     self.conn_cache.packet_queue.push_back((packet, idx));
                 }
                 Ok(())
@@ -557,6 +700,20 @@ impl<Ctx> NetcodeServer<Ctx> {
             debug!("server ignored connection request. failed to read connect token");
             return Ok(());
         };
+        if !self
+            .cfg
+            .connection_filter
+            .accepts_client_id(token.client_id)
+        {
+            debug!("server denied connection request. the client id is not allowed");
+            self.send_to_addr(
+                DeniedPacket::create(),
+                from_addr,
+                token.server_to_client_key,
+                sender,
+            )?;
+            return Ok(());
+        }
         // TODO: this doesn't work with local hosts because the local bind_addr is often 0.0.0.0, even though
         //  the tokens contain 127.0.0.1
         // if !token
@@ -599,7 +756,28 @@ impl<Ctx> NetcodeServer<Ctx> {
             debug!("server ignored connection request. connect token has already been used");
             return Ok(());
         };
-        if self.num_connected_clients() >= MAX_CLIENTS {
+        if let Some(handler) = self.cfg.on_connection_request.as_ref() {
+            let request = ConnectionRequest {
+                client_id: token.client_id,
+                addr: from_addr,
+                user_data: token.user_data,
+                num_connected_clients: self.num_connected_clients(),
+                max_clients: self.cfg.max_clients,
+            };
+            if !handler(&request) {
+                debug!(
+                    "server denied connection request. rejected by the connection request handler"
+                );
+                self.send_to_addr(
+                    DeniedPacket::create(),
+                    from_addr,
+                    token.server_to_client_key,
+                    sender,
+                )?;
+                return Ok(());
+            }
+        }
+        if self.num_connected_clients() >= self.cfg.max_clients {
             debug!("server denied connection request. server is full");
             self.send_to_addr(
                 DeniedPacket::create(),
@@ -615,6 +793,8 @@ impl<Ctx> NetcodeServer<Ctx> {
             token.timeout_seconds,
             token.server_to_client_key,
             token.client_to_server_key,
+            token.user_data,
+            packet.expire_timestamp,
         );
         let Ok(challenge_token_encrypted) = ChallengeToken {
             client_id: token.client_id,
@@ -656,7 +836,7 @@ impl<Ctx> NetcodeServer<Ctx> {
             return Ok(());
         };
 
-        if self.num_connected_clients() >= MAX_CLIENTS {
+        if self.num_connected_clients() >= self.cfg.max_clients {
             debug!("server denied connection response. server is full");
             self.send_to_addr(
                 DeniedPacket::create(),
@@ -686,7 +866,8 @@ impl<Ctx> NetcodeServer<Ctx> {
         self.on_connect(id);
         Ok(())
     }
-    fn check_for_timeouts(&mut self) {
+    fn check_for_timeouts(&mut self, io: &mut Io) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         for id in self.conn_cache.ids() {
             let Some(client) = self.conn_cache.clients.get_mut(&id) else {
                 continue;
@@ -698,10 +879,19 @@ impl<Ctx> NetcodeServer<Ctx> {
                 && client.last_receive_time + (client.timeout as f64) < self.time
             {
                 debug!("server timed out client {id}");
-                self.on_disconnect(id);
-                self.conn_cache.remove(id);
+                // the client might still be able to receive packets, let it know why it got disconnected
+                if let Err(e) = self.disconnect_with_reason(id, DisconnectReason::IdleTimeout, io) {
+                    error!("server could not notify timed out client {id}: {e}");
+                }
+            } else if self.cfg.disconnect_on_token_expiry && client.expire_timestamp <= now {
+                debug!("server disconnected client {id} because its connect token expired");
+                if let Err(e) = self.disconnect_with_reason(id, DisconnectReason::TokenExpired, io)
+                {
+                    error!("server could not notify client {id} with an expired token: {e}");
+                }
             }
         }
+        Ok(())
     }
     fn send_packets(&mut self, io: &mut Io) -> Result<()> {
         for id in self.conn_cache.ids() {
@@ -735,6 +925,11 @@ impl<Ctx> NetcodeServer<Ctx> {
             // Too small to be a packet
             return Ok(());
         }
+        if buf[0] == Packet::REQUEST && !self.cfg.connection_filter.accepts_ip(&addr.ip()) {
+            // drop the request before decrypting it, without answering
+            debug!("server ignored connection request from address {addr} that is not allowed");
+            return Ok(());
+        }
         let (key, replay_protection) = match self.conn_cache.find_by_addr(&addr) {
             // Regardless of whether an entry in the connection cache exists for the client or not,
             // if the packet is a connection request we need to use the server's private key to decrypt it.
@@ -808,8 +1003,8 @@ impl<Ctx> NetcodeServer<Ctx> {
     pub fn try_update(&mut self, delta_ms: f64, io: &mut Io) -> Result<()> {
         self.time += delta_ms;
         self.conn_cache.update(delta_ms);
+        self.check_for_timeouts(io)?;
         let (sender, receiver) = io.split();
-        self.check_for_timeouts();
         self.recv_packets(sender, receiver)?;
         self.send_packets(io)?;
         Ok(())
@@ -916,33 +1111,112 @@ impl<Ctx> NetcodeServer<Ctx> {
         self.token_sequence += 1;
         token_builder
     }
-    /// Disconnects a client.
+    /// Extends the session of a connected client with a new connect token, without reconnecting.
+    ///
+    /// `token` contains the public data of the new token (see `ConnectToken::try_into_refresh_bytes`), and must
+    /// have been issued for the same client id. The expiry, timeout and user data of the connection are replaced
+    /// with the ones of the new token; the encryption keys of the connection are kept.
+    pub fn refresh_token(&mut self, client_id: ClientId, token: &[u8]) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut packet = RequestPacket::read_from(&mut std::io::Cursor::new(token))?;
+        packet.validate(self.protocol_id, now)?;
+        packet.decrypt_token_data(self.private_key)?;
+        let token =
+            ConnectTokenPrivate::read_from(&mut std::io::Cursor::new(&packet.token_data[..]))?;
+        let Some(conn) = self.conn_cache.clients.get(&client_id) else {
+            return Err(Error::ClientNotFound);
+        };
+        if !conn.is_connected() {
+            return Err(Error::ClientNotConnected);
+        }
+        let addr = conn.addr;
+        if token.client_id != client_id {
+            return Err(Error::ClientIdMismatch {
+                expected: client_id,
+                actual: token.client_id,
+            });
+        }
+        // the new token goes through the same checks as a connection request
+        if !self.cfg.connection_filter.accepts_ip(&addr.ip())
+            || !self.cfg.connection_filter.accepts_client_id(client_id)
+        {
+            debug!("server rejected the token refresh of client {client_id}. the client is not allowed");
+            return Err(Error::TokenRejected);
+        }
+        let entry = TokenEntry {
+            time: self.time,
+            addr,
+            mac: packet.token_data
+                [ConnectTokenPrivate::SIZE - MAC_BYTES..ConnectTokenPrivate::SIZE]
+                .try_into()
+                .expect("valid MAC size"),
+        };
+        if !self.token_entries.find_or_insert(entry) {
+            debug!("server rejected the token refresh of client {client_id}. the token has already been used");
+            return Err(Error::TokenAlreadyUsed);
+        }
+        if let Some(handler) = self.cfg.on_connection_request.as_ref() {
+            let request = ConnectionRequest {
+                client_id,
+                addr,
+                user_data: token.user_data,
+                num_connected_clients: self.num_connected_clients(),
+                max_clients: self.cfg.max_clients,
+            };
+            if !handler(&request) {
+                debug!("server rejected the token refresh of client {client_id}. rejected by the connection request handler");
+                return Err(Error::TokenRejected);
+            }
+        }
+        let Some(conn) = self.conn_cache.clients.get_mut(&client_id) else {
+            return Err(Error::ClientNotFound);
+        };
+        conn.expire_timestamp = packet.expire_timestamp;
+        conn.timeout = token.timeout_seconds;
+        conn.user_data = token.user_data;
+        debug!("server refreshed the connect token of client {client_id}");
+        Ok(())
+    }
+    /// Disconnects a client, with the reason [`DisconnectReason::Kicked`].
     ///
     /// The server will send a number of redundant disconnect packets to the client, and then remove its connection info.
     pub fn disconnect(&mut self, client_id: ClientId, io: &mut Io) -> Result<()> {
+        self.disconnect_with_reason(client_id, DisconnectReason::Kicked, io)
+    }
+    /// Disconnects a client.
+    ///
+    /// The `reason` is included in the disconnect packets sent to the client.
+    pub fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+        io: &mut Io,
+    ) -> Result<()> {
         let Some(conn) = self.conn_cache.clients.get_mut(&client_id) else {
             return Ok(());
         };
         if !conn.is_connected() {
             return Ok(());
         }
-        debug!("server disconnecting client {client_id}");
-        for _ in 0..self.cfg.num_disconnect_packets {
-            self.send_to_client(DisconnectPacket::create(), client_id, io)?;
-        }
+        debug!("server disconnecting client {client_id} with reason {reason:?}");
+        // the client is removed even if the disconnect packets could not be sent
+        let sent = (0..self.cfg.num_disconnect_packets)
+            .try_for_each(|_| self.send_to_client(DisconnectPacket::create(reason), client_id, io));
         self.on_disconnect(client_id);
         self.conn_cache.remove(client_id);
-        Ok(())
+        sent
     }
     /// Disconnects all clients.
-    pub fn disconnect_all(&mut self, io: &mut Io) -> Result<()> {
+    pub fn disconnect_all(&mut self, reason: DisconnectReason, io: &mut Io) -> Result<()> {
         debug!("server disconnecting all clients");
         for id in self.conn_cache.ids() {
             let Some(conn) = self.conn_cache.clients.get_mut(&id) else {
                 continue;
             };
             if conn.is_connected() {
-                self.disconnect(id, io)?;
+                if let Err(e) = self.disconnect_with_reason(id, reason, io) {
+                    error!("server could not notify client {id} of the disconnection: {e}");
+                }
             }
         }
         Ok(())
@@ -968,11 +1242,41 @@ impl<Ctx> NetcodeServer<Ctx> {
             .count()
     }
 
+    /// Gets the maximum number of clients that can be connected at the same time.
+    pub fn max_clients(&self) -> usize {
+        self.cfg.max_clients
+    }
+
+    /// Changes the maximum number of clients that can be connected at the same time.
+    ///
+    /// If the limit is lowered below the number of connected clients, the clients that are already connected
+    /// are kept, and new connection requests are denied until enough clients disconnect.
+    pub fn set_max_clients(&mut self, max_clients: usize) {
+        debug!("server max clients set to {max_clients}");
+        self.cfg.max_clients = max_clients;
+    }
+
+    /// Gets the allow and deny lists of addresses and client ids.
+    pub fn connection_filter(&self) -> &ConnectionFilter {
+        &self.cfg.connection_filter
+    }
+
+    /// Gets a mutable reference to the allow and deny lists of addresses and client ids,
+    /// to ban or unban clients while the server is running.
+    pub fn connection_filter_mut(&mut self) -> &mut ConnectionFilter {
+        &mut self.cfg.connection_filter
+    }
+
     /// Gets the address of a client.
     pub fn client_addr(&self, client_id: ClientId) -> Option<SocketAddr> {
         self.conn_cache.clients.get(&client_id).map(|c| c.addr)
     }
 
+    /// Gets the user data contained in the connect token that a client used to connect.
+    pub fn user_data(&self, client_id: ClientId) -> Option<[u8; USER_DATA_BYTES]> {
+        self.conn_cache.clients.get(&client_id).map(|c| c.user_data)
+    }
+
     /// Gets the address of the server
     pub fn local_addr(&self) -> SocketAddr {
         self.cfg.server_addr
@@ -1007,7 +1311,8 @@ impl NetServer for Server {
                 .connected_client_ids()
                 .map(id::ClientId::Netcode)
                 .collect::<Vec<_>>();
-            self.server.disconnect_all(&mut io)?;
+            self.server
+                .disconnect_all(DisconnectReason::ServerShutdown, &mut io)?;
             self.server
                 .cfg
                 .context
@@ -1020,11 +1325,19 @@ impl NetServer for Server {
     }
 
     fn disconnect(&mut self, client_id: id::ClientId) -> anyhow::Result<()> {
+        self.disconnect_with_reason(client_id, DisconnectReason::Kicked)
+    }
+
+    fn disconnect_with_reason(
+        &mut self,
+        client_id: id::ClientId,
+        reason: DisconnectReason,
+    ) -> anyhow::Result<()> {
         match client_id {
             id::ClientId::Netcode(id) => {
                 if let Some(io) = self.io.as_mut() {
                     self.server
-                        .disconnect(id, io)
+                        .disconnect_with_reason(id, reason, io)
                         .context("Could not disconnect client")?;
                     self.server.cfg.context.disconnections.push(client_id);
                 }
@@ -1079,6 +1392,34 @@ impl NetServer for Server {
     fn io(&self) -> Option<&Io> {
         self.io.as_ref()
     }
+
+    fn user_data(&self, client_id: id::ClientId) -> Option<[u8; USER_DATA_BYTES]> {
+        match client_id {
+            id::ClientId::Netcode(id) => self.server.user_data(id),
+            _ => None,
+        }
+    }
+
+    fn set_max_clients(&mut self, max_clients: usize) -> anyhow::Result<()> {
+        self.server.set_max_clients(max_clients);
+        Ok(())
+    }
+
+    fn connection_filter_mut(&mut self) -> Option<&mut ConnectionFilter> {
+        Some(self.server.connection_filter_mut())
+    }
+
+    fn refresh_token(&mut self, client_id: id::ClientId, token: &[u8]) -> anyhow::Result<()> {
+        let id::ClientId::Netcode(client_id) = client_id else {
+            return Err(anyhow!("the client id must be of type Netcode"));
+        };
+        self.server
+            .refresh_token(client_id, token)
+            .context("could not refresh token")
+    }
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.server.extended_ticks = extended_ticks;
+    }
 }
 
 impl Server {
@@ -1096,6 +1437,12 @@ impl Server {
         cfg = cfg.keep_alive_send_rate(config.keep_alive_send_rate);
         cfg = cfg.num_disconnect_packets(config.num_disconnect_packets);
         cfg = cfg.client_timeout_secs(config.client_timeout_secs);
+        cfg = cfg.disconnect_on_token_expiry(config.disconnect_on_token_expiry);
+        cfg = cfg.max_clients(config.max_clients);
+        cfg = cfg.connection_filter(config.connection_filter);
+        if let Some(handler) = config.connection_request_handler {
+            cfg = cfg.on_connection_request(handler);
+        }
         let server = NetcodeServer::with_config(config.protocol_id, private_key, cfg)
             .expect("Could not create server netcode");
 
@@ -1106,3 +1453,274 @@ impl Server {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::connection::netcode::{ClientState, NetcodeClient};
+    use crate::prelude::TransportConfig;
+
+    use super::*;
+
+    /// Create the IO of a server and of one local client per address, connected with crossbeam channels
+    fn channel_io<const N: usize>(client_addrs: [SocketAddr; N]) -> (Io, [Io; N]) {
+        let mut channels = vec![];
+        let client_ios = client_addrs.map(|addr| {
+            let (client_send, to_server) = crossbeam_channel::unbounded();
+            let (from_server_send, client_recv) = crossbeam_channel::unbounded();
+            channels.push((addr, to_server, from_server_send));
+            IoConfig::from_transport(TransportConfig::LocalChannel {
+                recv: client_recv,
+                send: client_send,
+            })
+            .connect()
+            .unwrap()
+        });
+        let server_io = IoConfig::from_transport(TransportConfig::Channels { channels })
+            .connect()
+            .unwrap();
+        (server_io, client_ios)
+    }
+
+    #[test]
+    fn test_connection_request_handler() {
+        let server_addr = SocketAddr::from_str("127.0.0.1:5000").unwrap();
+        let banned_addr = SocketAddr::from_str("127.0.0.1:5001").unwrap();
+        let allowed_addr = SocketAddr::from_str("127.0.0.1:5002").unwrap();
+        let (mut server_io, [mut banned_io, mut allowed_io]) =
+            channel_io([banned_addr, allowed_addr]);
+
+        // deny the clients whose token has the first byte of user data set
+        let cfg = ServerConfig::default().on_connection_request(Arc::new(
+            |request: &ConnectionRequest| request.user_data[0] == 0,
+        ));
+        let mut server = NetcodeServer::with_config(0, crypto::generate_key(), cfg).unwrap();
+        let mut banned_user_data = [0u8; USER_DATA_BYTES];
+        banned_user_data[0] = 1;
+        let banned_token = server
+            .token(1, server_addr)
+            .user_data(banned_user_data)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        // the user data of the token is available on the server once the client is connected
+        let mut allowed_user_data = [7u8; USER_DATA_BYTES];
+        allowed_user_data[0] = 0;
+        let allowed_token = server
+            .token(2, server_addr)
+            .user_data(allowed_user_data)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut banned = NetcodeClient::new(&banned_token).unwrap();
+        let mut allowed = NetcodeClient::new(&allowed_token).unwrap();
+        banned.connect();
+        allowed.connect();
+
+        for _ in 0..10 {
+            banned.update(0.1, &mut banned_io);
+            allowed.update(0.1, &mut allowed_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(banned.state(), ClientState::ConnectionDenied);
+        assert_eq!(allowed.state(), ClientState::Connected);
+        assert_eq!(server.connected_client_ids().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(server.user_data(2), Some(allowed_user_data));
+    }
+
+    #[test]
+    fn test_refresh_token() {
+        let server_addr = SocketAddr::from_str("127.0.0.1:5000").unwrap();
+        let client_addr = SocketAddr::from_str("127.0.0.1:5001").unwrap();
+        let (mut server_io, [mut client_io]) = channel_io([client_addr]);
+
+        let cfg = ServerConfig::default().disconnect_on_token_expiry(true);
+        let mut server = NetcodeServer::with_config(0, crypto::generate_key(), cfg).unwrap();
+        let token = server
+            .token(1, server_addr)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut client = NetcodeClient::new(&token).unwrap();
+        client.connect();
+        for _ in 0..10 {
+            client.update(0.1, &mut client_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(client.state(), ClientState::Connected);
+
+        // a token issued for another client cannot be used to extend the session
+        let other_token = server.token(2, server_addr).generate().unwrap();
+        assert!(matches!(
+            server.refresh_token(1, &other_token.try_into_refresh_bytes().unwrap()),
+            Err(Error::ClientIdMismatch {
+                expected: 1,
+                actual: 2
+            })
+        ));
+
+        let refreshed_user_data = [3u8; USER_DATA_BYTES];
+        let new_token = server
+            .token(1, server_addr)
+            .expire_seconds(3600)
+            .user_data(refreshed_user_data)
+            .generate()
+            .unwrap();
+        server
+            .refresh_token(1, &new_token.try_into_refresh_bytes().unwrap())
+            .unwrap();
+        assert_eq!(server.user_data(1), Some(refreshed_user_data));
+        assert_eq!(
+            server.conn_cache.find_by_id(1).unwrap().expire_timestamp,
+            new_token.expire_timestamp
+        );
+
+        // the new token goes through the same checks as a connection request
+        server.connection_filter_mut().deny_client_id(1);
+        let denied_token = server.token(1, server_addr).generate().unwrap();
+        assert!(matches!(
+            server.refresh_token(1, &denied_token.try_into_refresh_bytes().unwrap()),
+            Err(Error::TokenRejected)
+        ));
+        assert_eq!(server.user_data(1), Some(refreshed_user_data));
+        server.connection_filter_mut().remove_denied_client_id(1);
+
+        // the connection is still up after the refresh
+        for _ in 0..10 {
+            client.update(0.1, &mut client_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(client.state(), ClientState::Connected);
+        assert_eq!(server.connected_client_ids().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_set_max_clients() {
+        let server_addr = SocketAddr::from_str("127.0.0.1:5000").unwrap();
+        let first_addr = SocketAddr::from_str("127.0.0.1:5001").unwrap();
+        let second_addr = SocketAddr::from_str("127.0.0.1:5002").unwrap();
+        let (mut server_io, [mut first_io, mut second_io]) = channel_io([first_addr, second_addr]);
+
+        let cfg = ServerConfig::default().max_clients(1);
+        let mut server = NetcodeServer::with_config(0, crypto::generate_key(), cfg).unwrap();
+        let first_token = server
+            .token(1, server_addr)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let mut first = NetcodeClient::new(&first_token).unwrap();
+        first.connect();
+        for _ in 0..10 {
+            first.update(0.1, &mut first_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(first.state(), ClientState::Connected);
+
+        // the server is full: the second client is denied
+        let mut second = NetcodeClient::new(
+            &server
+                .token(2, server_addr)
+                .generate()
+                .unwrap()
+                .try_into_bytes()
+                .unwrap(),
+        )
+        .unwrap();
+        second.connect();
+        for _ in 0..10 {
+            first.update(0.1, &mut first_io);
+            second.update(0.1, &mut second_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(second.state(), ClientState::ConnectionDenied);
+
+        // once the limit is raised, the second client can connect
+        server.set_max_clients(2);
+        let mut second = NetcodeClient::new(
+            &server
+                .token(2, server_addr)
+                .generate()
+                .unwrap()
+                .try_into_bytes()
+                .unwrap(),
+        )
+        .unwrap();
+        second.connect();
+        for _ in 0..10 {
+            first.update(0.1, &mut first_io);
+            second.update(0.1, &mut second_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(second.state(), ClientState::Connected);
+
+        // lowering the limit keeps the clients that are already connected
+        server.set_max_clients(1);
+        for _ in 0..10 {
+            first.update(0.1, &mut first_io);
+            second.update(0.1, &mut second_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(first.state(), ClientState::Connected);
+        assert_eq!(second.state(), ClientState::Connected);
+        assert_eq!(server.num_connected_clients(), 2);
+    }
+
+    #[test]
+    fn test_connection_filter() {
+        let server_addr = SocketAddr::from_str("127.0.0.1:5000").unwrap();
+        let banned_addr = SocketAddr::from_str("127.0.0.2:5001").unwrap();
+        let client_addr = SocketAddr::from_str("127.0.0.1:5002").unwrap();
+        let (mut server_io, [mut banned_io, mut client_io]) =
+            channel_io([banned_addr, client_addr]);
+
+        let mut filter = ConnectionFilter::default();
+        filter.deny_ip(banned_addr.ip()).deny_client_id(2);
+        let cfg = ServerConfig::default().connection_filter(filter);
+        let mut server = NetcodeServer::with_config(0, crypto::generate_key(), cfg).unwrap();
+        let new_client = |server: &mut NetcodeServer, client_id: ClientId| {
+            let token = server
+                .token(client_id, server_addr)
+                .generate()
+                .unwrap()
+                .try_into_bytes()
+                .unwrap();
+            let mut client = NetcodeClient::new(&token).unwrap();
+            client.connect();
+            client
+        };
+
+        // requests from a denied address are dropped without an answer
+        let mut banned = new_client(&mut server, 1);
+        // requests from a denied client id are denied
+        let mut client = new_client(&mut server, 2);
+        for _ in 0..10 {
+            banned.update(0.1, &mut banned_io);
+            client.update(0.1, &mut client_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(banned.state(), ClientState::SendingConnectionRequest);
+        assert_eq!(client.state(), ClientState::ConnectionDenied);
+        assert_eq!(server.num_connected_clients(), 0);
+
+        // the client id can be unbanned while the server is running
+        server.connection_filter_mut().remove_denied_client_id(2);
+        let mut client = new_client(&mut server, 2);
+        for _ in 0..10 {
+            client.update(0.1, &mut client_io);
+            server.update(0.1, &mut server_io);
+        }
+        assert_eq!(client.state(), ClientState::Connected);
+
+        // with an allow list, only the listed client ids are accepted
+        let mut filter = ConnectionFilter::default();
+        filter.allow_client_id(3);
+        assert!(filter.accepts_client_id(3));
+        assert!(!filter.accepts_client_id(4));
+        assert!(filter.accepts_ip(&banned_addr.ip()));
+    }
+}
@@ -12,6 +12,7 @@ use super::{
     bytes::Bytes,
     crypto::{self, Key},
     error::Error,
+    packet::RequestPacket,
     utils, CONNECTION_TIMEOUT_SEC, CONNECT_TOKEN_BYTES, NETCODE_VERSION, PRIVATE_KEY_BYTES,
     USER_DATA_BYTES,
 };
@@ -428,6 +429,23 @@ impl ConnectToken {
         ConnectTokenBuilder::new(server_addresses, protocol_id, client_id, private_key)
     }
 
+    /// Serializes the part of the token that the server needs to extend the session of a client
+    /// that is already connected (the same data as in a connection request packet).
+    ///
+    /// The keys of the new token are not used: the connection keeps its current encryption keys.
+    pub(crate) fn try_into_refresh_bytes(&self) -> Result<Vec<u8>, io::Error> {
+        let mut buf = Vec::new();
+        RequestPacket {
+            version_info: self.version_info,
+            protocol_id: self.protocol_id,
+            expire_timestamp: self.expire_timestamp,
+            token_nonce: self.nonce,
+            token_data: Box::new(self.private_data),
+        }
+        .write_to(&mut buf)?;
+        Ok(buf)
+    }
+
     /// Tries to convert the token into a 2048-byte array.
     pub fn try_into_bytes(self) -> Result<[u8; CONNECT_TOKEN_BYTES], io::Error> {
         let mut buf = [0u8; CONNECT_TOKEN_BYTES];
@@ -0,0 +1,140 @@
+//! Client that replays a [`PacketLog`] instead of connecting to a server
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use tracing::error;
+
+use crate::_reexport::ReadWordBuffer;
+use crate::client::networking::NetworkingState;
+use crate::connection::client::NetClient;
+use crate::connection::replay::{PacketLog, RecordedPacket};
+use crate::packet::packet::Packet;
+use crate::prelude::{ClientId, Io};
+use crate::serialize::reader::ReadBuffer;
+use crate::transport::LOCAL_SOCKET;
+
+pub struct Client {
+    id: ClientId,
+    packets: VecDeque<RecordedPacket>,
+    /// Time (in seconds) elapsed since the start of the replay
+    elapsed: f64,
+    is_connected: bool,
+    /// If true, the recorded packets contain the generation of the tick after their header
+    extended_ticks: bool,
+}
+
+impl Client {
+    pub fn new(log: PacketLog) -> Self {
+        Self {
+            id: log.client_id,
+            packets: log.packets.into(),
+            elapsed: 0.0,
+            is_connected: false,
+            extended_ticks: false,
+        }
+    }
+}
+
+impl NetClient for Client {
+    fn connect(&mut self) -> Result<()> {
+        self.is_connected = true;
+        self.elapsed = 0.0;
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.is_connected = false;
+        Ok(())
+    }
+
+    fn state(&self) -> NetworkingState {
+        if self.is_connected {
+            NetworkingState::Connected
+        } else {
+            NetworkingState::Disconnected
+        }
+    }
+
+    fn try_update(&mut self, delta_ms: f64) -> Result<()> {
+        if self.is_connected {
+            self.elapsed += delta_ms;
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<Packet> {
+        if !self.is_connected {
+            return None;
+        }
+        while self
+            .packets
+            .front()
+            .is_some_and(|packet| packet.time <= self.elapsed)
+        {
+            let recorded = self.packets.pop_front().unwrap();
+            let mut reader = ReadWordBuffer::start_read(recorded.bytes.as_slice());
+            match Packet::decode(&mut reader, self.extended_ticks) {
+                Ok(packet) => return Some(packet),
+                Err(e) => error!("could not decode replayed packet: {e:?}"),
+            }
+        }
+        None
+    }
+
+    /// The packets sent by the client are discarded
+    fn send(&mut self, _: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn io(&self) -> Option<&Io> {
+        None
+    }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        None
+    }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.extended_ticks = extended_ticks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::connection::replay::PacketRecorder;
+    use crate::packet::message::SingleData;
+    use crate::packet::packet_manager::PacketBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_replay_packets() {
+        let mut packet = PacketBuilder::new().build_new_single_packet();
+        packet.add_message(0, SingleData::new(None, Bytes::from("hello"), 1.0));
+        let mut recorder = PacketRecorder::new(ClientId::Netcode(1));
+        recorder.update(0.5);
+        recorder.record(&packet).unwrap();
+        let log = recorder.finish();
+        assert_eq!(log.packets.len(), 1);
+
+        let mut client = Client::new(log);
+        client.connect().unwrap();
+        assert_eq!(client.id(), ClientId::Netcode(1));
+        client.try_update(0.25).unwrap();
+        assert!(client.recv().is_none());
+        client.try_update(0.25).unwrap();
+        assert_eq!(client.recv().map(|packet| packet.num_messages()), Some(1));
+        assert!(client.recv().is_none());
+    }
+}
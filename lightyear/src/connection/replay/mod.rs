@@ -0,0 +1,95 @@
+//! Record the packets received from the server, and replay them later instead of connecting to a server.
+//!
+//! This can be used to reproduce a bug locally from a recording made by a player: the replayed packets go through
+//! the same receive/prediction pipeline as the packets received from a real server.
+//! ```rust,no_run,ignore
+//! // record the session
+//! fn start_recording(mut connection: ResMut<ClientConnection>) {
+//!     connection.start_recording();
+//! }
+//! fn save_recording(mut connection: ResMut<ClientConnection>) {
+//!     let log = connection.stop_recording().unwrap();
+//!     std::fs::write("session.replay", serde_json::to_vec(&log).unwrap()).unwrap();
+//! }
+//!
+//! // replay the session
+//! let log: PacketLog = serde_json::from_slice(&std::fs::read("session.replay").unwrap()).unwrap();
+//! let net_config = NetConfig::Replay { log };
+//! ```
+//! The inputs of the client can be recorded and replayed as well with the [`InputManager`](crate::client::input::InputManager).
+use serde::{Deserialize, Serialize};
+
+use crate::_reexport::WriteWordBuffer;
+use crate::connection::id::ClientId;
+use crate::packet::packet::{Packet, MTU_PAYLOAD_BYTES};
+use crate::serialize::writer::WriteBuffer;
+
+pub(crate) mod client;
+
+/// A packet received from the server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedPacket {
+    /// Time (in seconds) elapsed since the start of the recording when the packet was received
+    pub time: f64,
+    pub bytes: Vec<u8>,
+}
+
+/// Log of the packets received from the server during a session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PacketLog {
+    /// Id of the client that recorded the session
+    pub client_id: ClientId,
+    /// The packets, ordered by reception time
+    pub packets: Vec<RecordedPacket>,
+}
+
+impl Default for PacketLog {
+    fn default() -> Self {
+        Self::new(ClientId::Local(0))
+    }
+}
+
+impl PacketLog {
+    pub fn new(client_id: ClientId) -> Self {
+        Self {
+            client_id,
+            packets: vec![],
+        }
+    }
+}
+
+/// Records the packets received by a [`ClientConnection`](crate::connection::client::ClientConnection)
+pub(crate) struct PacketRecorder {
+    elapsed: f64,
+    log: PacketLog,
+    writer: WriteWordBuffer,
+}
+
+impl PacketRecorder {
+    pub(crate) fn new(client_id: ClientId) -> Self {
+        Self {
+            elapsed: 0.0,
+            log: PacketLog::new(client_id),
+            writer: WriteWordBuffer::with_capacity(MTU_PAYLOAD_BYTES),
+        }
+    }
+
+    pub(crate) fn update(&mut self, delta: f64) {
+        self.elapsed += delta;
+    }
+
+    pub(crate) fn record(&mut self, packet: &Packet) -> anyhow::Result<()> {
+        self.writer.start_write();
+        packet.encode(&mut self.writer)?;
+        let bytes = self.writer.finish_write().to_vec();
+        self.log.packets.push(RecordedPacket {
+            time: self.elapsed,
+            bytes,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> PacketLog {
+        self.log
+    }
+}
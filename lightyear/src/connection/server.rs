@@ -3,6 +3,7 @@ use bevy::prelude::Resource;
 use bevy::utils::HashMap;
 
 use crate::connection::id::ClientId;
+use crate::connection::netcode::{ConnectionFilter, DisconnectReason, USER_DATA_BYTES};
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::server::SteamConfig;
 use crate::packet::packet::Packet;
@@ -24,6 +25,18 @@ pub trait NetServer: Send + Sync {
     /// Is also responsible for adding the client to the list of new disconnections.
     fn disconnect(&mut self, client_id: ClientId) -> Result<()>;
 
+    /// Disconnect a specific client, and let them know why they were disconnected.
+    ///
+    /// Transports that cannot send a reason to the client just disconnect the client.
+    fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) -> Result<()> {
+        let _ = reason;
+        self.disconnect(client_id)
+    }
+
     /// Return the list of connected clients
     fn connected_client_ids(&self) -> Vec<ClientId>;
 
@@ -41,6 +54,40 @@ pub trait NetServer: Send + Sync {
     fn new_disconnections(&self) -> Vec<ClientId>;
 
     fn io(&self) -> Option<&Io>;
+
+    /// Return the user data that the client provided when connecting (for example in its `ConnectToken`)
+    fn user_data(&self, client_id: ClientId) -> Option<[u8; USER_DATA_BYTES]> {
+        let _ = client_id;
+        None
+    }
+
+    /// Change the maximum number of clients that can be connected to this server.
+    ///
+    /// Clients that are already connected are kept if the limit is lowered; new clients are rejected
+    /// until there is room for them.
+    fn set_max_clients(&mut self, max_clients: usize) -> Result<()> {
+        let _ = max_clients;
+        Err(anyhow!(
+            "this transport does not support changing the maximum number of clients"
+        ))
+    }
+
+    /// The allow and deny lists of addresses and client ids used by this server, if the transport supports them
+    fn connection_filter_mut(&mut self) -> Option<&mut ConnectionFilter> {
+        None
+    }
+
+    /// Extend the session of a connected client with a new token that it sent over the existing connection
+    fn refresh_token(&mut self, client_id: ClientId, token: &[u8]) -> Result<()> {
+        let _ = (client_id, token);
+        Err(anyhow!("this transport does not support refreshing tokens"))
+    }
+
+    /// Decode the tick generation that is written after the header of the packets if extended ticks are enabled
+    /// (see [`TickConfig::extended`](crate::prelude::TickConfig::extended))
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        let _ = extended_ticks;
+    }
 }
 
 /// A wrapper around a `Box<dyn NetServer>`
@@ -112,6 +159,14 @@ impl NetServer for ServerConnection {
         self.server.disconnect(client_id)
     }
 
+    fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) -> Result<()> {
+        self.server.disconnect_with_reason(client_id, reason)
+    }
+
     fn connected_client_ids(&self) -> Vec<ClientId> {
         self.server.connected_client_ids()
     }
@@ -139,6 +194,26 @@ impl NetServer for ServerConnection {
     fn io(&self) -> Option<&Io> {
         self.server.io()
     }
+
+    fn user_data(&self, client_id: ClientId) -> Option<[u8; USER_DATA_BYTES]> {
+        self.server.user_data(client_id)
+    }
+
+    fn set_max_clients(&mut self, max_clients: usize) -> Result<()> {
+        self.server.set_max_clients(max_clients)
+    }
+
+    fn connection_filter_mut(&mut self) -> Option<&mut ConnectionFilter> {
+        self.server.connection_filter_mut()
+    }
+
+    fn refresh_token(&mut self, client_id: ClientId, token: &[u8]) -> Result<()> {
+        self.server.refresh_token(client_id, token)
+    }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.server.set_extended_ticks(extended_ticks)
+    }
 }
 
 type ServerConnectionIdx = usize;
@@ -190,12 +265,21 @@ impl ServerConnections {
 
     /// Disconnect a specific client
     pub fn disconnect(&mut self, client_id: ClientId) -> Result<()> {
+        self.disconnect_with_reason(client_id, DisconnectReason::Kicked)
+    }
+
+    /// Disconnect a specific client, and send them the `reason` of the disconnection
+    pub fn disconnect_with_reason(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) -> Result<()> {
         self.client_server_map.get(&client_id).map_or(
             Err(anyhow!(
                 "Could not find the server instance associated with client: {client_id:?}"
             )),
             |&server_idx| {
-                self.servers[server_idx].disconnect(client_id)?;
+                self.servers[server_idx].disconnect_with_reason(client_id, reason)?;
                 // NOTE: we don't remove the client from the map here because it is done
                 //  in the server's `receive` method
                 // self.client_server_map.remove(&client_id);
@@ -204,6 +288,45 @@ impl ServerConnections {
         )
     }
 
+    /// Change the maximum number of clients at runtime.
+    ///
+    /// The limit is applied separately by each internal server, to the clients that use its transport.
+    /// Every server that supports it is updated, even if the transport of another server doesn't;
+    /// in that case the errors of all the unsupported servers are returned.
+    pub fn set_max_clients(&mut self, max_clients: usize) -> Result<()> {
+        let errors: Vec<_> = self
+            .servers
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, server)| {
+                server
+                    .set_max_clients(max_clients)
+                    .err()
+                    .map(|e| format!("server {idx}: {e}"))
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "could not set the maximum number of clients ({})",
+                errors.join(", ")
+            ))
+        }
+    }
+
+    /// Modify the allow and deny lists of addresses and client ids of all the internal servers that support them
+    /// (for example to ban a client at runtime).
+    ///
+    /// Clients that are already connected are not disconnected: use [`ServerConnections::disconnect`] for that.
+    pub fn modify_connection_filter(&mut self, mut f: impl FnMut(&mut ConnectionFilter)) {
+        for server in &mut self.servers {
+            if let Some(filter) = server.connection_filter_mut() {
+                f(filter);
+            }
+        }
+    }
+
     /// Returns true if the server is currently listening for client packets
     pub(crate) fn is_listening(&self) -> bool {
         self.is_listening
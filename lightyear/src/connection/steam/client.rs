@@ -17,7 +17,7 @@ use steamworks::networking_types::{
     NetworkingConnectionState, SendFlags,
 };
 use steamworks::{ClientManager, SingleClient};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
 use super::{get_networking_options, SingleClientThreadSafe};
 
@@ -52,6 +52,8 @@ pub struct Client {
     packet_queue: VecDeque<Packet>,
     buffer_pool: BufferPool,
     conditioner: Option<LinkConditionerConfig>,
+    /// If true, the packets contain the generation of the tick after their header
+    extended_ticks: bool,
 }
 
 impl Client {
@@ -68,6 +70,7 @@ impl Client {
             packet_queue: VecDeque::new(),
             buffer_pool: BufferPool::default(),
             conditioner,
+            extended_ticks: false,
         })
     }
 
@@ -155,9 +158,15 @@ impl NetClient for Client {
                 {
                     // get a buffer from the pool to avoid new allocations
                     let mut reader = self.buffer_pool.start_read(message.data());
-                    let packet = Packet::decode(&mut reader).context("could not decode packet")?;
+                    let packet = Packet::decode(&mut reader, self.extended_ticks);
                     // return the buffer to the pool
                     self.buffer_pool.attach(reader);
+                    // drop packets that cannot be decoded instead of stopping to receive packets
+                    let Ok(packet) = packet
+                        .inspect_err(|e| error!("Could not decode packet, dropping it: {:?}", e))
+                    else {
+                        continue;
+                    };
                     self.packet_queue.push_back(packet);
                 }
                 Ok(())
@@ -193,4 +202,8 @@ impl NetClient for Client {
     fn io_mut(&mut self) -> Option<&mut Io> {
         None
     }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.extended_ticks = extended_ticks;
+    }
 }
@@ -61,6 +61,8 @@ pub struct Server {
     new_connections: Vec<ClientId>,
     new_disconnections: Vec<ClientId>,
     conditioner: Option<LinkConditionerConfig>,
+    /// If true, the packets contain the generation of the tick after their header
+    extended_ticks: bool,
 }
 
 impl Server {
@@ -88,6 +90,7 @@ impl Server {
             new_connections: Vec::new(),
             new_disconnections: Vec::new(),
             conditioner,
+            extended_ticks: false,
         })
     }
 }
@@ -207,9 +210,15 @@ impl NetServer for Server {
             {
                 // get a buffer from the pool to avoid new allocations
                 let mut reader = self.buffer_pool.start_read(message.data());
-                let packet = Packet::decode(&mut reader).context("could not decode packet")?;
+                let packet = Packet::decode(&mut reader, self.extended_ticks);
                 // return the buffer to the pool
                 self.buffer_pool.attach(reader);
+                // drop packets that cannot be decoded instead of stopping to receive packets
+                let Ok(packet) = packet.inspect_err(|e| {
+                    error!(?client_id, "Could not decode packet, dropping it: {:?}", e)
+                }) else {
+                    continue;
+                };
                 self.packet_queue.push_back((packet, *client_id));
             }
             // TODO: is this necessary since I disabled nagle?
@@ -245,7 +254,16 @@ impl NetServer for Server {
         self.new_disconnections.clone()
     }
 
+    fn set_max_clients(&mut self, max_clients: usize) -> Result<()> {
+        self.config.max_clients = max_clients;
+        Ok(())
+    }
+
     fn io(&self) -> Option<&Io> {
         None
     }
+
+    fn set_extended_ticks(&mut self, extended_ticks: bool) {
+        self.extended_ticks = extended_ticks;
+    }
 }
@@ -363,6 +363,9 @@ impl<T: LeafwingUserAction> InputBuffer<T> {
 pub(crate) struct ActionDiffBuffer<A: LeafwingUserAction> {
     pub(crate) start_tick: Option<Tick>,
     buffer: VecDeque<HashMap<A, ActionDiff<A>>>,
+    /// Most recent tick received from the remote input messages.
+    /// Each message contains redundant diffs for the previous ticks, which we only need to apply once
+    last_received_tick: Option<Tick>,
 }
 
 impl<A: LeafwingUserAction> Default for ActionDiffBuffer<A> {
@@ -370,6 +373,7 @@ impl<A: LeafwingUserAction> Default for ActionDiffBuffer<A> {
         Self {
             start_tick: None,
             buffer: VecDeque::new(),
+            last_received_tick: None,
         }
     }
 }
@@ -462,16 +466,41 @@ impl<A: LeafwingUserAction> ActionDiffBuffer<A> {
             .map(|v| v.values().cloned().collect())
             .unwrap_or(vec![])
     }
-    pub(crate) fn update_from_message(&mut self, end_tick: Tick, diffs: Vec<Vec<ActionDiff<A>>>) {
+
+    /// Update the buffer with the diffs of an input message received from the client.
+    ///
+    /// Every new diff goes through `validate` before being buffered: it can modify the diff,
+    /// or return None to drop it.
+    pub(crate) fn update_from_message(
+        &mut self,
+        end_tick: Tick,
+        diffs: Vec<Vec<ActionDiff<A>>>,
+        mut validate: impl FnMut(Tick, ActionDiff<A>) -> Option<ActionDiff<A>>,
+    ) {
         let message_start_tick = end_tick - diffs.len() as u16 + 1;
         if self.start_tick.is_none() {
             // initialize the buffer
             self.start_tick = Some(message_start_tick);
         };
-        let start_tick = self.start_tick.unwrap();
+        let last_received_tick = self.last_received_tick;
+        if last_received_tick.map_or(true, |tick| end_tick > tick) {
+            self.last_received_tick = Some(end_tick);
+        }
 
         for (delta, diffs_for_tick) in diffs.into_iter().enumerate() {
             let tick = message_start_tick + Tick(delta as u16);
+            // only fill the ticks that were not received yet: the redundant diffs were already applied
+            // from a previous message (the diffs of the last received tick can still be completed by the
+            // diffs of later frames)
+            if last_received_tick.is_some_and(|last_tick| tick < last_tick)
+                && !self.get(tick).is_empty()
+            {
+                continue;
+            }
+            let diffs_for_tick = diffs_for_tick
+                .into_iter()
+                .filter_map(|diff| validate(tick, diff))
+                .collect();
             self.set(tick, diffs_for_tick);
         }
     }
@@ -620,7 +649,7 @@ mod tests {
             vec![],
         ];
 
-        diff_buffer.update_from_message(end_tick, diffs);
+        diff_buffer.update_from_message(end_tick, diffs, |_, diff| Some(diff));
 
         assert_eq!(diff_buffer.get(Tick(20)), vec![]);
         assert_eq!(diff_buffer.get(Tick(19)), vec![]);
@@ -642,4 +671,35 @@ mod tests {
         );
         assert_eq!(diff_buffer.get(Tick(12)), vec![]);
     }
+
+    #[test]
+    fn test_update_from_redundant_messages() {
+        let mut diff_buffer = ActionDiffBuffer::default();
+        let pressed = ActionDiff::Pressed {
+            action: Action::Jump,
+        };
+        let released = ActionDiff::Released {
+            action: Action::Jump,
+        };
+        diff_buffer.update_from_message(
+            Tick(11),
+            vec![vec![pressed.clone()], vec![]],
+            |_, diff| Some(diff),
+        );
+
+        // a message that arrived out of order does not override the more recent diffs
+        diff_buffer.update_from_message(
+            Tick(10),
+            vec![vec![], vec![released.clone()]],
+            |_, diff| Some(diff),
+        );
+        assert_eq!(diff_buffer.get(Tick(10)), vec![pressed.clone()]);
+
+        diff_buffer.update_from_message(
+            Tick(12),
+            vec![vec![], vec![released.clone()]],
+            |_, diff| Some(diff),
+        );
+        assert_eq!(diff_buffer.get(Tick(12)), vec![released]);
+    }
 }
@@ -14,6 +14,12 @@ use super::UserAction;
 pub struct InputBuffer<T: UserAction> {
     pub buffer: VecDeque<Option<T>>,
     pub start_tick: Option<Tick>,
+    /// Most recent tick received from the remote input messages.
+    /// Each message contains redundant inputs for the previous ticks, which we only need to apply once
+    pub(crate) last_received_tick: Option<Tick>,
+    /// Fraction of the tick at which the inputs were sampled (quantized to a u8), ordered by tick.
+    /// Only present for the inputs that were recorded with a sub-tick timestamp
+    pub(crate) sub_ticks: VecDeque<(Tick, u8)>,
 }
 
 // TODO: add encode directive to encode even more efficiently
@@ -22,6 +28,8 @@ pub struct InputBuffer<T: UserAction> {
 pub(crate) enum InputData<T> {
     Absent,
     SameAsPrecedent,
+    /// The preceding value is repeated for the next `n` ticks
+    Repeat(u16),
     Input(T),
 }
 
@@ -33,6 +41,8 @@ pub struct InputMessage<T> {
     pub(crate) end_tick: Tick,
     // first element is tick end_tick-N+1, last element is end_tick
     pub(crate) inputs: Vec<InputData<T>>,
+    /// Sub-tick timestamps of the inputs of the message (see [`InputBuffer::set_sub_tick`])
+    pub(crate) sub_ticks: Vec<(Tick, u8)>,
 }
 
 impl<T: UserAction> InputMessage<T> {
@@ -42,10 +52,21 @@ impl<T: UserAction> InputMessage<T> {
         }
         let mut iter = self.inputs.iter();
         if iter.next().unwrap() == &InputData::Absent {
-            return iter.all(|x| x == &InputData::SameAsPrecedent);
+            return iter.all(|x| matches!(x, InputData::SameAsPrecedent | InputData::Repeat(_)));
         }
         false
     }
+
+    /// Number of ticks covered by the message
+    pub(crate) fn num_ticks(&self) -> u16 {
+        self.inputs
+            .iter()
+            .map(|input| match input {
+                InputData::Repeat(n) => *n,
+                _ => 1,
+            })
+            .sum()
+    }
 }
 
 impl<T: UserAction> Default for InputBuffer<T> {
@@ -54,6 +75,8 @@ impl<T: UserAction> Default for InputBuffer<T> {
             // buffer: SequenceBuffer::new(),
             buffer: VecDeque::new(),
             start_tick: None,
+            last_received_tick: None,
+            sub_ticks: VecDeque::new(),
             // end_tick: Tick(0),
         }
     }
@@ -74,6 +97,7 @@ impl<T: UserAction> InputBuffer<T> {
         if tick < start_tick {
             return None;
         }
+        self.prune_sub_ticks(tick);
         if tick > start_tick + (self.buffer.len() as i16 - 1) {
             // pop everything
             self.buffer = VecDeque::new();
@@ -105,6 +129,37 @@ impl<T: UserAction> InputBuffer<T> {
             .as_ref()
     }
 
+    /// Record the fraction of the tick (in `[0.0, 1.0]`) at which the input for `tick` was sampled.
+    ///
+    /// This allows sub-tick precision for hit registration, even at low tick rates.
+    pub(crate) fn set_sub_tick(&mut self, tick: Tick, fraction: f32) {
+        let quantized = (fraction.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+        self.insert_sub_tick(tick, quantized);
+    }
+
+    /// Fraction of the tick (in `[0.0, 1.0]`) at which the input for `tick` was sampled, if it was recorded
+    pub(crate) fn get_sub_tick(&self, tick: Tick) -> Option<f32> {
+        self.sub_ticks
+            .iter()
+            .find(|(t, _)| *t == tick)
+            .map(|(_, quantized)| *quantized as f32 / u8::MAX as f32)
+    }
+
+    fn insert_sub_tick(&mut self, tick: Tick, quantized: u8) {
+        let index = self.sub_ticks.partition_point(|(t, _)| *t < tick);
+        match self.sub_ticks.get_mut(index) {
+            Some((t, value)) if *t == tick => *value = quantized,
+            _ => self.sub_ticks.insert(index, (tick, quantized)),
+        }
+    }
+
+    /// Remove the sub-tick timestamps of the ticks older than `tick`
+    fn prune_sub_ticks(&mut self, tick: Tick) {
+        while self.sub_ticks.front().is_some_and(|(t, _)| *t < tick) {
+            self.sub_ticks.pop_front();
+        }
+    }
+
     pub(crate) fn set(&mut self, tick: Tick, value: Option<T>) {
         let Some(start_tick) = self.start_tick else {
             // initialize the buffer
@@ -129,61 +184,129 @@ impl<T: UserAction> InputBuffer<T> {
     }
 
     /// We received a new input message from the user, and use it to update the input buffer
-    /// TODO: should we keep track of which inputs in the input buffer are absent and only update those?
+    ///
+    /// Every new input goes through `validate` before being buffered: it can modify the input,
+    /// or return None to reject it. A rejected input is replaced by the last valid input of the previous
+    /// ticks, so that the tick is not validated again when its redundant copies are received.
+    ///
+    /// The messages contain redundant inputs for the previous ticks, so we only write the ticks of the
+    /// buffer that are still empty; each input is validated at most once.
+    ///
+    /// Returns the inputs that arrived too late, for ticks that were already consumed.
     ///  The current tick is the current server tick, no need to update the buffer for ticks that are older than that
-    pub(crate) fn update_from_message(&mut self, message: InputMessage<T>) {
-        let message_start_tick = Tick(message.end_tick.0) - message.inputs.len() as u16 + 1;
+    pub(crate) fn update_from_message(
+        &mut self,
+        message: InputMessage<T>,
+        mut validate: impl FnMut(Tick, T) -> Option<T>,
+    ) -> Vec<(Tick, T)> {
+        let num_ticks = message.num_ticks();
+        let message_start_tick = Tick(message.end_tick.0) - num_ticks + 1;
+        let last_received_tick = self.last_received_tick;
+        if last_received_tick.map_or(true, |tick| message.end_tick > tick) {
+            self.last_received_tick = Some(message.end_tick);
+        }
+        for (tick, quantized) in message.sub_ticks {
+            if self.get_sub_tick(tick).is_none()
+                && self
+                    .start_tick
+                    .map_or(true, |start_tick| tick >= start_tick)
+            {
+                self.insert_sub_tick(tick, quantized);
+            }
+        }
         let mut prev_value = None;
+        let mut tick = message_start_tick;
+        let mut late_inputs = vec![];
+        // last valid input before the current tick, used as a fallback for rejected inputs
+        let mut last_valid = self.get(message_start_tick - 1).cloned();
 
-        for (delta, input) in message.inputs.into_iter().enumerate() {
-            let tick = message_start_tick + Tick(delta as u16);
-            match input {
+        for input in message.inputs.into_iter() {
+            let repeat = match input {
                 InputData::Absent => {
                     prev_value = None;
-                    self.set(tick, None);
-                }
-                InputData::SameAsPrecedent => {
-                    self.set(tick, prev_value.clone());
+                    1
                 }
+                InputData::SameAsPrecedent => 1,
+                InputData::Repeat(n) => n,
                 InputData::Input(input) => {
                     prev_value = Some(input);
-                    if self.get(tick) == prev_value.as_ref() {
-                        continue;
-                    } else {
-                        self.set(tick, prev_value.clone());
+                    1
+                }
+            };
+            for _ in 0..repeat {
+                // we still need to read the redundant inputs to decode the following entries
+                if self.start_tick.is_some_and(|start_tick| tick < start_tick) {
+                    // the tick was already consumed: the input is late, unless it was already
+                    // received in a previous message
+                    let duplicate = last_received_tick.is_some_and(|last_tick| tick <= last_tick);
+                    if let Some(input) = prev_value
+                        .clone()
+                        .filter(|_| !duplicate)
+                        .and_then(|input| validate(tick, input))
+                    {
+                        late_inputs.push((tick, input));
+                    }
+                } else if let Some(input) = self.get(tick) {
+                    // only fill the ticks that were not received yet
+                    last_valid = Some(input.clone());
+                } else if let Some(input) = prev_value.clone() {
+                    let input = validate(tick, input).or_else(|| last_valid.clone());
+                    if input.is_some() {
+                        self.set(tick, input.clone());
                     }
+                    last_valid = input;
+                } else {
+                    last_valid = None;
                 }
+                tick = tick + 1;
             }
         }
+        late_inputs
     }
 
     // Convert the last N ticks up to end_tick included into a compressed message that we can send to the server
     // Return None if the last N inputs are all Absent
     pub(crate) fn create_message(&self, end_tick: Tick, num_ticks: u16) -> InputMessage<T> {
         let mut inputs = Vec::new();
-        // start with the first value
         let start_tick = Tick(end_tick.0) - num_ticks + 1;
-        inputs.push(
-            self.get(start_tick)
-                .map_or(InputData::Absent, |input| InputData::Input(input.clone())),
-        );
         // keep track of the previous value to avoid sending the same value multiple times
-        let mut prev_value_idx = 0;
-        for delta in 1..num_ticks {
+        let mut prev_value = None;
+        let mut repeat = 0;
+        for delta in 0..num_ticks {
             let tick = start_tick + Tick(delta);
-            // safe because we keep pushing elements
             let value = self
                 .get(tick)
                 .map_or(InputData::Absent, |input| InputData::Input(input.clone()));
-            // safe before prev_value_idx is always present
-            if inputs.get(prev_value_idx).unwrap() == &value {
-                inputs.push(InputData::SameAsPrecedent);
-            } else {
-                prev_value_idx = inputs.len();
-                inputs.push(value);
+            if prev_value.as_ref() == Some(&value) {
+                repeat += 1;
+                continue;
             }
+            Self::push_repeat(&mut inputs, repeat);
+            repeat = 0;
+            inputs.push(value.clone());
+            prev_value = Some(value);
+        }
+        Self::push_repeat(&mut inputs, repeat);
+        let sub_ticks = self
+            .sub_ticks
+            .iter()
+            .filter(|(tick, _)| *tick >= start_tick && *tick <= end_tick)
+            .copied()
+            .collect();
+        InputMessage {
+            inputs,
+            end_tick,
+            sub_ticks,
+        }
+    }
+
+    /// Encode a run of values that are identical to the preceding value
+    fn push_repeat(inputs: &mut Vec<InputData<T>>, repeat: u16) {
+        match repeat {
+            0 => {}
+            1 => inputs.push(InputData::SameAsPrecedent),
+            n => inputs.push(InputData::Repeat(n)),
         }
-        InputMessage { inputs, end_tick }
     }
 }
 
@@ -233,11 +356,12 @@ mod tests {
                     InputData::Input(1),
                     InputData::SameAsPrecedent,
                     InputData::Absent,
-                    InputData::SameAsPrecedent,
-                    InputData::SameAsPrecedent,
+                    InputData::Repeat(2),
                 ],
+                sub_ticks: vec![],
             }
         );
+        assert_eq!(message.num_ticks(), 8);
     }
 
     #[test]
@@ -256,8 +380,9 @@ mod tests {
                 InputData::SameAsPrecedent,
                 InputData::SameAsPrecedent,
             ],
+            sub_ticks: vec![],
         };
-        input_buffer.update_from_message(message);
+        input_buffer.update_from_message(message, |_, input| Some(input));
 
         assert_eq!(input_buffer.get(Tick(20)), None);
         assert_eq!(input_buffer.get(Tick(19)), None);
@@ -268,4 +393,203 @@ mod tests {
         assert_eq!(input_buffer.get(Tick(14)), Some(&0));
         assert_eq!(input_buffer.get(Tick(13)), None);
     }
+
+    #[test]
+    fn test_update_from_redundant_messages() {
+        let mut input_buffer = InputBuffer::default();
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(11),
+                inputs: vec![InputData::Input(0), InputData::SameAsPrecedent],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        // the server consumed the input for tick 10
+        assert_eq!(input_buffer.pop(Tick(10)), Some(0));
+
+        // a message that arrived out of order does not override the more recent inputs
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(10),
+                inputs: vec![InputData::Input(2), InputData::SameAsPrecedent],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        assert_eq!(input_buffer.get(Tick(10)), None);
+        assert_eq!(input_buffer.get(Tick(11)), Some(&0));
+
+        // the redundant inputs of the next message are skipped
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(13),
+                inputs: vec![
+                    InputData::Input(0),
+                    InputData::Input(1),
+                    InputData::Input(3),
+                ],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        assert_eq!(input_buffer.get(Tick(11)), Some(&0));
+        assert_eq!(input_buffer.get(Tick(12)), Some(&1));
+        assert_eq!(input_buffer.get(Tick(13)), Some(&3));
+        assert_eq!(input_buffer.last_received_tick, Some(Tick(13)));
+    }
+
+    #[test]
+    fn test_update_from_out_of_order_message() {
+        let mut input_buffer = InputBuffer::default();
+        let mut num_validated = 0;
+        let mut validate = |_: Tick, input: usize| {
+            num_validated += 1;
+            Some(input)
+        };
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(11),
+                inputs: vec![InputData::Input(0), InputData::Input(1)],
+                sub_ticks: vec![],
+            },
+            &mut validate,
+        );
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(15),
+                inputs: vec![InputData::Input(4), InputData::Input(5)],
+                sub_ticks: vec![],
+            },
+            &mut validate,
+        );
+        // an older message fills the ticks that were not received yet, but doesn't override the others
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(13),
+                inputs: vec![
+                    InputData::Input(6),
+                    InputData::Input(2),
+                    InputData::Input(3),
+                ],
+                sub_ticks: vec![],
+            },
+            &mut validate,
+        );
+        for tick in 10..16 {
+            assert_eq!(input_buffer.get(Tick(tick)), Some(&(tick as usize - 10)));
+        }
+        // each tick is validated only once
+        assert_eq!(num_validated, 6);
+        // the last received tick is the most recent tick received
+        assert_eq!(input_buffer.last_received_tick, Some(Tick(15)));
+    }
+
+    #[test]
+    fn test_update_from_message_rejected_input() {
+        let mut input_buffer = InputBuffer::default();
+        let mut num_validated = 0;
+        let mut validate = |_: Tick, input: usize| {
+            num_validated += 1;
+            // reject the inputs that are too big
+            (input < 10).then_some(input)
+        };
+        let message = InputMessage {
+            end_tick: Tick(12),
+            inputs: vec![
+                InputData::Input(1),
+                InputData::Input(100),
+                InputData::Input(2),
+            ],
+            sub_ticks: vec![],
+        };
+        input_buffer.update_from_message(message.clone(), &mut validate);
+        // the rejected input is replaced by the last valid input
+        assert_eq!(input_buffer.get(Tick(10)), Some(&1));
+        assert_eq!(input_buffer.get(Tick(11)), Some(&1));
+        assert_eq!(input_buffer.get(Tick(12)), Some(&2));
+
+        // the rejected input is not validated again when the redundant inputs are received
+        input_buffer.update_from_message(message, &mut validate);
+        assert_eq!(num_validated, 3);
+    }
+
+    #[test]
+    fn test_repeat_round_trip() {
+        let mut input_buffer = InputBuffer::default();
+        for tick in 0..20 {
+            input_buffer.set(Tick(tick), Some(if tick < 15 { 0 } else { 1 }));
+        }
+        let message = input_buffer.create_message(Tick(19), 20);
+        assert_eq!(
+            message.inputs,
+            vec![
+                InputData::Input(0),
+                InputData::Repeat(14),
+                InputData::Input(1),
+                InputData::Repeat(4),
+            ]
+        );
+
+        let mut server_buffer = InputBuffer::default();
+        server_buffer.update_from_message(message, |_, input| Some(input));
+        for tick in 0..20 {
+            assert_eq!(
+                server_buffer.get(Tick(tick)),
+                Some(&if tick < 15 { 0 } else { 1 })
+            );
+        }
+    }
+
+    #[test]
+    fn test_sub_ticks() {
+        let mut input_buffer = InputBuffer::default();
+        input_buffer.set(Tick(4), Some(0));
+        input_buffer.set_sub_tick(Tick(4), 0.25);
+        input_buffer.set(Tick(6), Some(1));
+        input_buffer.set_sub_tick(Tick(6), 1.0);
+        let message = input_buffer.create_message(Tick(6), 2);
+        assert_eq!(message.sub_ticks, vec![(Tick(6), u8::MAX)]);
+
+        let mut server_buffer = InputBuffer::default();
+        server_buffer.update_from_message(input_buffer.create_message(Tick(6), 3), |_, input| {
+            Some(input)
+        });
+        assert!((server_buffer.get_sub_tick(Tick(4)).unwrap() - 0.25).abs() < 0.01);
+        assert_eq!(server_buffer.get_sub_tick(Tick(5)), None);
+        assert_eq!(server_buffer.get_sub_tick(Tick(6)), Some(1.0));
+
+        server_buffer.pop(Tick(5));
+        assert_eq!(server_buffer.get_sub_tick(Tick(4)), None);
+        assert_eq!(server_buffer.get_sub_tick(Tick(6)), Some(1.0));
+    }
+
+    #[test]
+    fn test_late_inputs() {
+        let mut input_buffer = InputBuffer::default();
+        input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(10),
+                inputs: vec![InputData::Input(0)],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        // the server already consumed the ticks 11 and 12
+        assert_eq!(input_buffer.pop(Tick(12)), None);
+        let late_inputs = input_buffer.update_from_message(
+            InputMessage {
+                end_tick: Tick(13),
+                inputs: vec![
+                    InputData::Input(0),
+                    InputData::Input(1),
+                    InputData::Input(2),
+                ],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        assert_eq!(late_inputs, vec![(Tick(11), 0), (Tick(12), 1)]);
+        assert_eq!(input_buffer.get(Tick(13)), Some(&2));
+    }
 }
@@ -2,7 +2,7 @@
 Handles dealing with inputs (keyboard presses, mouse clicks) sent from a player (client) to server.
 */
 
-use bevy::prelude::TypePath;
+use bevy::prelude::{Reflect, TypePath};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::fmt::Debug;
@@ -14,6 +14,9 @@ use crate::protocol::BitSerializable;
 /// Defines an [`InputBuffer`](input_buffer::InputBuffer) buffer to store the inputs of a player for each tick
 pub mod input_buffer;
 
+/// Helpers to quantize analog inputs so that they take less space when serialized
+pub mod quantize;
+
 // TODO: should we request that a user input is a message?
 // TODO: the bound should be `BitSerializable`, not `Serialize + DeserializeOwned`
 //  but it causes the derive macro for InputMessage to fail
@@ -23,3 +26,16 @@ pub trait UserAction:
 }
 
 impl UserAction for () {}
+
+/// How the inputs of a [`UserAction`] are buffered and delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum InputMode {
+    /// An input is expected for every tick (for example movement inputs).
+    /// If the input for a tick is missing, the server reuses the last input it received, and the
+    /// inputs are replayed on the client during rollbacks.
+    #[default]
+    Continuous,
+    /// The inputs are sparse commands (for example UI commands) that only apply to the tick where they were issued.
+    /// Missing inputs are not replaced by the server, and the commands are not replayed on the client during rollbacks.
+    Sparse,
+}
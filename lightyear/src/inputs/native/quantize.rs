@@ -0,0 +1,160 @@
+//! Quantization helpers to reduce the size of analog inputs.
+//!
+//! Inputs are sent every tick (and repeated in several packets for redundancy), so storing an analog stick axis
+//! as a `f32` costs 4 bytes per axis per tick. Most games don't need that much precision: the axis can be
+//! quantized to an `i8` (255 steps in [-1.0, 1.0]) or an `i16` if more precision is needed.
+//! ```rust,no_run,ignore
+//! #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+//! pub struct PlayerInput {
+//!     // 2 bytes instead of 8
+//!     movement: QuantizedAxis2,
+//!     // 4 bytes instead of 8, with more precision than `i8`
+//!     aim: QuantizedAxis2<i16>,
+//! }
+//!
+//! let input = PlayerInput {
+//!     movement: QuantizedAxis2::new(gamepad_stick),
+//!     aim: QuantizedAxis2::new(aim_stick),
+//! };
+//! let movement: Vec2 = input.movement.get();
+//! ```
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use bevy::math::Vec2;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Integer type used to store a quantized value in [-1.0, 1.0].
+/// The precision of the quantization depends on the size of the integer.
+pub trait QuantizedInt:
+    Copy
+    + Default
+    + PartialEq
+    + Eq
+    + Hash
+    + Debug
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    /// Number of steps between 0.0 and 1.0
+    const STEPS: f32;
+
+    fn from_steps(steps: f32) -> Self;
+
+    fn to_steps(self) -> f32;
+}
+
+impl QuantizedInt for i8 {
+    const STEPS: f32 = i8::MAX as f32;
+
+    fn from_steps(steps: f32) -> Self {
+        steps as i8
+    }
+
+    fn to_steps(self) -> f32 {
+        self as f32
+    }
+}
+
+impl QuantizedInt for i16 {
+    const STEPS: f32 = i16::MAX as f32;
+
+    fn from_steps(steps: f32) -> Self {
+        steps as i16
+    }
+
+    fn to_steps(self) -> f32 {
+        self as f32
+    }
+}
+
+/// An axis value in [-1.0, 1.0] quantized to the integer `T`.
+/// Values outside of the range are clamped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct QuantizedAxis<T = i8>(pub T);
+
+impl<T: QuantizedInt> QuantizedAxis<T> {
+    pub fn new(value: f32) -> Self {
+        if value.is_nan() {
+            return Self::default();
+        }
+        Self(T::from_steps((value.clamp(-1.0, 1.0) * T::STEPS).round()))
+    }
+
+    /// Returns the value in [-1.0, 1.0]
+    pub fn get(&self) -> f32 {
+        self.0.to_steps() / T::STEPS
+    }
+}
+
+impl<T: QuantizedInt> From<f32> for QuantizedAxis<T> {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A 2D axis (for example an analog stick) where each coordinate in [-1.0, 1.0] is quantized to the integer `T`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct QuantizedAxis2<T = i8> {
+    pub x: QuantizedAxis<T>,
+    pub y: QuantizedAxis<T>,
+}
+
+impl<T: QuantizedInt> QuantizedAxis2<T> {
+    pub fn new(value: Vec2) -> Self {
+        Self {
+            x: QuantizedAxis::new(value.x),
+            y: QuantizedAxis::new(value.y),
+        }
+    }
+
+    /// Returns the value, where each coordinate is in [-1.0, 1.0]
+    pub fn get(&self) -> Vec2 {
+        Vec2::new(self.x.get(), self.y.get())
+    }
+}
+
+impl<T: QuantizedInt> From<Vec2> for QuantizedAxis2<T> {
+    fn from(value: Vec2) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_reexport::{ReadBuffer, ReadWordBuffer, WriteBuffer, WriteWordBuffer};
+    use crate::protocol::BitSerializable;
+
+    use super::*;
+
+    #[test]
+    fn test_quantized_axis() {
+        assert_eq!(QuantizedAxis::<i8>::new(1.0).0, 127);
+        assert_eq!(QuantizedAxis::<i8>::new(-1.0).0, -127);
+        assert_eq!(QuantizedAxis::<i8>::new(3.0).0, 127);
+        assert_eq!(QuantizedAxis::<i8>::new(f32::NAN).0, 0);
+        assert_eq!(QuantizedAxis::<i8>::new(0.0).get(), 0.0);
+
+        let value = 0.3;
+        assert!((QuantizedAxis::<i8>::new(value).get() - value).abs() <= 0.5 / i8::MAX as f32);
+        assert!((QuantizedAxis::<i16>::new(value).get() - value).abs() <= 0.5 / i16::MAX as f32);
+    }
+
+    #[test]
+    fn test_serde_quantized_axis() {
+        let axis = QuantizedAxis2::<i8>::new(Vec2::new(0.5, -0.25));
+        let mut writer = WriteWordBuffer::with_capacity(10);
+        axis.encode(&mut writer).unwrap();
+        // smaller than two f32
+        assert!(writer.num_bits_written() < 2 * 32);
+        let bytes = writer.finish_write();
+
+        let mut reader = ReadWordBuffer::start_read(bytes);
+        let decoded = QuantizedAxis2::<i8>::decode(&mut reader).unwrap();
+        assert_eq!(decoded, axis);
+    }
+}
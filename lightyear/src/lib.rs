@@ -27,12 +27,15 @@ pub mod _reexport {
 
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
-        EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
+        EntityActionsChannel, EntityUpdatesChannel, InputBroadcastChannel, InputChannel,
+        PingChannel, ServerControlChannel,
     };
     pub use crate::client::interpolation::{
         add_interpolation_systems, add_prepare_interpolation_systems,
     };
-    pub use crate::client::interpolation::{LinearInterpolator, NullInterpolator};
+    pub use crate::client::interpolation::{
+        LerpInterpolator, LinearInterpolator, NullInterpolator, SlerpInterpolator,
+    };
     pub use crate::client::prediction::add_prediction_systems;
     pub use crate::client::prediction::correction::{InstantCorrector, InterpolatedCorrector};
     pub use crate::protocol::component::{
@@ -75,14 +78,17 @@ pub mod prelude {
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
         Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode, ChannelSettings,
-        DefaultUnorderedUnreliableChannel, ReliableSettings,
+        DefaultUnorderedUnreliableChannel, DropPolicy, ReliableSettings,
     };
     pub use crate::client::prediction::prespawn::PreSpawnedPlayerObject;
     pub use crate::connection::id::ClientId;
-    pub use crate::connection::netcode::{generate_key, Key};
+    pub use crate::connection::netcode::{generate_key, DisconnectReason, Key};
+    #[cfg(feature = "leafwing")]
+    pub use crate::inputs::leafwing::input_buffer::ActionDiff;
     #[cfg(feature = "leafwing")]
     pub use crate::inputs::leafwing::LeafwingUserAction;
-    pub use crate::inputs::native::UserAction;
+    pub use crate::inputs::native::quantize::{QuantizedAxis, QuantizedAxis2};
+    pub use crate::inputs::native::{InputMode, UserAction};
     pub use crate::packet::message::Message;
     pub use crate::protocol::channel::{ChannelKind, ChannelRegistry};
     pub use crate::protocol::Protocol;
@@ -90,70 +96,137 @@ pub mod prelude {
     pub use crate::shared::config::{Mode, SharedConfig};
     pub use crate::shared::ping::manager::PingConfig;
     pub use crate::shared::plugin::{NetworkIdentity, SharedPlugin};
+    pub use crate::shared::replication::authority::Authority;
     pub use crate::shared::replication::components::{
-        NetworkTarget, PrePredicted, ReplicationGroup, ReplicationMode, ShouldBePredicted,
+        ControlledBy, DespawnBehavior, Despawned, NetworkTarget, PrePredicted, ReplicationGroup,
+        ReplicationMode, ReplicationThreshold, ShouldBePredicted, SyncModeOverride,
     };
     pub use crate::shared::replication::entity_map::{ExternalMapper, RemoteEntityMap};
     pub use crate::shared::replication::hierarchy::ParentSync;
     pub use crate::shared::replication::resources::{
-        ReplicateResource, ReplicateResourceExt, StopReplicateResourceExt,
+        ReplicateResource, ReplicateResourceExt, ResourceHistory, StopReplicateResourceExt,
     };
     pub use crate::shared::sets::{FixedUpdateSet, MainSet};
     pub use crate::shared::tick_manager::TickManager;
-    pub use crate::shared::tick_manager::{Tick, TickConfig};
-    pub use crate::shared::time_manager::TimeManager;
+    pub use crate::shared::tick_manager::{is_first_substep, is_last_substep, Tick, TickConfig};
+    pub use crate::shared::time_manager::{SendTiming, TimeManager};
     pub use crate::transport::config::{IoConfig, TransportConfig};
     pub use crate::transport::io::Io;
     pub use crate::transport::middleware::conditioner::LinkConditionerConfig;
 
     pub mod client {
         pub use crate::client::components::{
-            ComponentSyncMode, Confirmed, LerpFn, SyncComponent, SyncMetadata,
+            ComponentSyncMode, Confirmed, Lerp, LerpFn, SyncComponent, SyncMetadata,
         };
         pub use crate::client::config::{ClientConfig, NetcodeConfig, PacketConfig};
         pub use crate::client::events::{
             ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
             DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            NetworkEventPlugin, PacketLostEvent,
+        };
+        pub use crate::client::input::{
+            AdditionalInputPlugin, InputConfig, InputHistory, InputManager, InputSystemSet,
+            RemoteInputs,
         };
-        pub use crate::client::input::{InputConfig, InputManager, InputSystemSet};
         #[cfg(feature = "leafwing")]
         pub use crate::client::input_leafwing::{
             LeafwingInputConfig, LeafwingInputPlugin, ToggleActions,
         };
+        pub use crate::client::interpolation::events::{
+            InterpolationSnapEvent, InterpolationStarvedEvent,
+        };
+        pub use crate::client::interpolation::extrapolate::{
+            ExtrapolateStatus, ExtrapolationConfig, ExtrapolationLimit,
+        };
+        pub use crate::client::interpolation::hermite::{
+            CubicHermiteInterpolator, HermiteFn, HermiteInterpolationPlugin,
+        };
         pub use crate::client::interpolation::interpolation_history::ConfirmedHistory;
+        pub use crate::client::interpolation::introspection::{
+            InterpolationBufferInfo, InterpolationBuffers,
+        };
+        pub use crate::client::interpolation::opt_out::DisableInterpolation;
         pub use crate::client::interpolation::plugin::{
-            InterpolationConfig, InterpolationDelay, InterpolationSet,
+            AdaptiveInterpolationDelay, ComponentInterpolationDelay, InterpolationConfig,
+            InterpolationDelay, InterpolationSet, InterpolationUpdateMode,
         };
+        pub use crate::client::interpolation::snap::{InterpolationSnapPlugin, SnapThreshold};
         pub use crate::client::interpolation::{
-            InterpolateStatus, Interpolated, VisualInterpolateStatus, VisualInterpolationPlugin,
+            InterpolateStatus, Interpolated, TransformVisualInterpolationPlugin,
+            VisualInterpolateStatus, VisualInterpolationPlugin,
         };
         pub use crate::client::networking::{ClientConnectionParam, NetworkingState};
         pub use crate::client::plugin::{ClientPlugin, PluginConfig};
         pub use crate::client::prediction::correction::Correction;
+        pub use crate::client::prediction::extrapolation::{
+            Extrapolate, Extrapolated, ExtrapolationPlugin,
+        };
+        pub use crate::client::prediction::hierarchy::HierarchyRollbackPlugin;
         pub use crate::client::prediction::plugin::is_in_rollback;
         pub use crate::client::prediction::plugin::{PredictionConfig, PredictionSet};
+        pub use crate::client::prediction::predicted_event::{
+            PredictedEventCancelled, PredictedEventPlugin, PredictedEventWriter,
+        };
         pub use crate::client::prediction::predicted_history::{ComponentState, PredictionHistory};
-        pub use crate::client::prediction::rollback::{Rollback, RollbackState};
-        pub use crate::client::prediction::{Predicted, PredictionDespawnCommandsExt};
+        pub use crate::client::prediction::predicted_resource::ResourcePredictionPlugin;
+        pub use crate::client::prediction::prespawn::PredictedProjectileCommandsExt;
+        pub use crate::client::prediction::rollback::{
+            ExcludedFromRollback, Rollback, RollbackEndEvent, RollbackGroup, RollbackMetrics,
+            RollbackSnapEvent, RollbackStartEvent, RollbackState, ShouldRollbackFn,
+        };
+        pub use crate::client::prediction::simulation::{
+            PredictedSimulation, PredictedSimulationExt,
+        };
+        pub use crate::client::prediction::smoothing::VisualSmoothing;
+        pub use crate::client::prediction::switch::ForcePredicted;
+        pub use crate::client::prediction::{
+            Predicted, PredictionDespawnCommandsExt, PredictionDespawnPending,
+        };
         pub use crate::client::replication::ReplicationConfig;
-        pub use crate::client::sync::SyncConfig;
+        pub use crate::client::sync::{
+            InterpolationTime, PredictionTime, ServerTimeEstimate, SyncAlgorithm, SyncConfig,
+            SyncDiagnostics, SyncEvent, SyncSample,
+        };
+        pub use crate::client::tick_snap::{TickSnapAdjust, TickSnapExt};
         pub use crate::connection::client::{
             Authentication, ClientConnection, NetClient, NetConfig,
         };
+        pub use crate::connection::replay::{PacketLog, RecordedPacket};
         #[cfg(all(feature = "steam", not(target_family = "wasm")))]
         pub use crate::connection::steam::client::SteamConfig;
     }
     pub mod server {
+        pub use crate::connection::netcode::{ConnectionFilter, ConnectionRequest};
         pub use crate::server::config::{NetcodeConfig, PacketConfig, ServerConfig};
         pub use crate::server::events::{
             ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
             DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            NetworkEventPlugin, PacketLostEvent, TokenRefreshEvent,
         };
+        pub use crate::server::input::{
+            AdditionalInputPlugin, InputBroadcastConfig, InputBufferMetrics, InputFlaggedEvent,
+            InputNudgeConfig, InputRateLimitAction, InputRateLimitConfig, InputRateLimitedEvent,
+            InputValidator, InputVerdict, LateInputConfig, LateInputEvent, MissingInputEvent,
+            MissingInputStrategy,
+        };
+        pub use crate::server::lag_compensation::{
+            rewind_world, LagCompensated, LagCompensation, LagCompensationPlugin,
+        };
+        pub use crate::server::ownership::ControlledEntities;
         pub use crate::server::plugin::{PluginConfig, ServerPlugin};
+        pub use crate::server::relevance::{
+            DistanceRelevanceConfig, DistanceRelevancePlugin, GridRelevanceConfig,
+            GridRelevancePlugin, PointOfInterest,
+        };
         pub use crate::server::replication::{
-            ReplicationConfig, ServerFilter, ServerReplicationSet,
+            ReplicationConfig, ReplicationValidators, ServerFilter, ServerReplicationSet,
+        };
+        pub use crate::server::rewind::{
+            is_rewinding, ExcludedFromRewind, InputRewind, InputRewindPlugin,
         };
-        pub use crate::server::room::{RoomId, RoomManager, RoomMut, RoomRef};
+        pub use crate::server::room::{RoomId, RoomManager, RoomMut, RoomRef, VisibilityManager};
+        pub use crate::server::snapshot::WorldSnapshot;
+        pub use crate::server::target::{DynamicTarget, TargetRule, TeamId, Teams};
 
         pub use crate::connection::server::{
             NetConfig, NetServer, ServerConnection, ServerConnections,
@@ -0,0 +1,20 @@
+use crate::protocol::channel::ChannelKind;
+use crate::protocol::registry::NetId;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors that can happen when processing a packet received from the remote peer.
+///
+/// These are caused by invalid data sent by the remote, so the packet should be dropped.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not decode packet: {0}")]
+    Decode(anyhow::Error),
+    #[error("received a message on an unknown channel (net id: {0})")]
+    UnknownChannel(NetId),
+    #[error("received an invalid message on channel {channel:?}: {error}")]
+    InvalidMessage {
+        channel: ChannelKind,
+        error: anyhow::Error,
+    },
+}
@@ -62,6 +62,8 @@ pub struct PacketHeaderManager {
     // so we can resend them when dropped
     // sent_packets_not_acked: HashSet<PacketId>,
     sent_packets_not_acked: HashMap<PacketId, WrappedTime>,
+    // packets that we sent and that we now consider lost, since the last time they were drained
+    lost_packets: Vec<PacketId>,
     stats_manager: PacketStatsManager,
 
     // channel to notify the sender of the packet_id of the packets that were delivered
@@ -84,6 +86,7 @@ impl PacketHeaderManager {
             stats_manager: PacketStatsManager::default(),
             // sent_packets_not_acked: HashSet::with_capacity(MAX_SEND_PACKET_QUEUE_SIZE as usize),
             sent_packets_not_acked: HashMap::new(),
+            lost_packets: Vec::new(),
             recv_buffer: ReceiveBuffer::new(),
             // ack_notification_sender,
             // ack_notification_receiver,
@@ -97,8 +100,9 @@ impl PacketHeaderManager {
         // clear sent packets that haven't received any ack for a while
         self.sent_packets_not_acked.retain(|packet_id, time_sent| {
             if self.current_time - (*time_sent) > CLEAR_UNACKED_PACKETS_DELAY {
-                trace!("sent packet got lost");
+                trace!(?packet_id, "sent packet got lost");
                 self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
                 return false;
             }
             true
@@ -121,6 +125,14 @@ impl PacketHeaderManager {
         &self.sent_packets_not_acked
     }
 
+    /// Returns the packets that have been considered lost since the last call
+    ///
+    /// A packet is lost if it falls out of the ack window of the remote (it can never be acked anymore),
+    /// or if we haven't received an ack for it after `CLEAR_UNACKED_PACKETS_DELAY`
+    pub(crate) fn drain_lost_packets(&mut self) -> Vec<PacketId> {
+        std::mem::take(&mut self.lost_packets)
+    }
+
     /// Increment the packet id of the next packet to be sent
     pub fn increment_next_packet_id(&mut self) {
         self.next_packet_id = PacketId(self.next_packet_id.wrapping_add(1));
@@ -151,6 +163,19 @@ impl PacketHeaderManager {
                 }
             }
         }
+
+        // the remote only sends acks for the `ACK_BITFIELD_SIZE` packets before `last_ack_packet_id`,
+        // so any older packet that hasn't been acked yet will never be acked: it is lost
+        let oldest_ackable_packet_id = header.last_ack_packet_id - (ACK_BITFIELD_SIZE as u16);
+        self.sent_packets_not_acked.retain(|packet_id, _| {
+            if *packet_id < oldest_ackable_packet_id {
+                trace!(?packet_id, "sent packet got lost");
+                self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
+                return false;
+            }
+            true
+        });
         newly_acked_packets
     }
 
@@ -341,6 +366,35 @@ mod tests {
         assert_eq!(recv_buffer.get_bitfield(), 1 << (32 - 1));
     }
 
+    #[test]
+    fn test_lost_packets() {
+        let mut manager = PacketHeaderManager::new();
+        for _ in 0..40 {
+            manager.prepare_send_packet_header(PacketType::Data);
+        }
+        // the remote received packet 39, and the 32 packets before it except for packet 10
+        let header = PacketHeader {
+            packet_type: PacketType::Data,
+            packet_id: PacketId(0),
+            last_ack_packet_id: PacketId(39),
+            ack_bitfield: u32::MAX & !(1 << (39 - 10 - 1)),
+            tick: Tick(0),
+        };
+        let acked_packets = manager.process_recv_packet_header(&header);
+        assert_eq!(acked_packets.len(), 32);
+
+        // packets 0 to 6 are outside of the ack window, so they are lost
+        // packet 10 could still be acked by a future packet
+        let mut lost_packets = manager.drain_lost_packets();
+        lost_packets.sort();
+        assert_eq!(lost_packets, (0..7).map(PacketId).collect::<Vec<_>>());
+        assert!(manager.drain_lost_packets().is_empty());
+        assert_eq!(
+            manager.sent_packets_not_acked().keys().collect::<Vec<_>>(),
+            vec![&PacketId(10)]
+        );
+    }
+
     #[test]
     fn test_serde_header() -> anyhow::Result<()> {
         let header = PacketHeader {
@@ -206,6 +206,13 @@ impl FragmentData {
         let tick = reader.decode::<Option<Tick>>(Fixed)?;
         let fragment_id = reader.decode::<FragmentIndex>(Gamma)?;
         let num_fragments = reader.decode::<FragmentIndex>(Gamma)?;
+        if fragment_id >= num_fragments {
+            anyhow::bail!(
+                "invalid fragment id {} for a message with {} fragments",
+                fragment_id,
+                num_fragments
+            );
+        }
         let bytes = if fragment_id == num_fragments - 1 {
             // let num_bytes = reader.decode::<usize>(Gamma)?;
             // let num_bytes_non_zero = std::num::NonZeroUsize::new(num_bytes)
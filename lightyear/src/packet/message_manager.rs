@@ -5,13 +5,16 @@ use bevy::ptr::UnsafeCellDeref;
 use bevy::reflect::Reflect;
 use bitcode::buffer::BufferTrait;
 use bitcode::word_buffer::WordBuffer;
+use bytes::Bytes;
 use crossbeam_channel::Receiver;
-use tracing::{info, trace};
+use tracing::{error, info, trace};
 
 use crate::channel::builder::ChannelContainer;
+use crate::channel::diagnostics::ChannelStats;
 use crate::channel::receivers::ChannelReceive;
 use crate::channel::senders::ChannelSend;
-use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
+use crate::packet::error::Error as PacketError;
+use crate::packet::message::{FragmentData, MessageAck, MessageContainer, MessageId, SingleData};
 use crate::packet::packet::{Packet, PacketId, MTU_PAYLOAD_BYTES};
 use crate::packet::packet_manager::{PacketBuilder, Payload, PACKET_BUFFER_CAPACITY};
 use crate::packet::priority_manager::{PriorityConfig, PriorityManager};
@@ -47,6 +50,10 @@ pub struct MessageManager {
     writer: WriteWordBuffer,
     // read_buffer: WordBuffer,
     reader_pool: BufferPool,
+    /// Generation of the tick to include in the packets we send (if extended ticks are enabled)
+    pub(crate) tick_generation: Option<u32>,
+    /// Most recent extended tick received from the remote peer (if extended ticks are enabled)
+    pub(crate) remote_extended_tick: Option<u64>,
 }
 
 impl MessageManager {
@@ -61,6 +68,8 @@ impl MessageManager {
             // TODO: it looks like we don't really need the pool this case, we can just keep re-using the same buffer
             reader_pool: BufferPool::new(1),
             // read_buffer: WordBuffer::with_capacity(MTU_PAYLOAD_BYTES),
+            tick_generation: None,
+            remote_extended_tick: None,
         }
     }
 
@@ -123,6 +132,75 @@ impl MessageManager {
         Ok(channel.sender.buffer_send(message_bytes.into(), priority))
     }
 
+    /// Buffer a message that has already been serialized to be sent on this connection
+    /// Returns the message id associated with the message, if there is one
+    ///
+    /// The bytes are reference-counted, so the same serialized message can be buffered on multiple
+    /// connections (or resent multiple times) without copying the payload.
+    pub fn buffer_send_bytes(
+        &mut self,
+        message_bytes: Bytes,
+        channel_kind: ChannelKind,
+        priority: f32,
+    ) -> anyhow::Result<Option<MessageId>> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        Ok(channel.sender.buffer_send(message_bytes, priority))
+    }
+
+    /// Returns true if some messages sent on the channel were not delivered yet
+    pub(crate) fn has_unacked_messages(&self, channel_kind: &ChannelKind) -> bool {
+        self.channels
+            .get(channel_kind)
+            .is_some_and(|channel| channel.sender.has_unacked_messages())
+    }
+
+    /// Returns the packets that were considered lost since the last call, along with the number
+    /// of messages that were lost in each channel (for channels that track acks)
+    pub(crate) fn drain_lost_packets(
+        &mut self,
+    ) -> Vec<(PacketId, bevy::utils::HashMap<ChannelKind, usize>)> {
+        self.packet_manager
+            .header_manager
+            .drain_lost_packets()
+            .into_iter()
+            .map(|packet_id| {
+                let lost_messages = self
+                    .packet_to_message_ack_map
+                    .remove(&packet_id)
+                    .map(|message_map| {
+                        message_map
+                            .into_iter()
+                            .map(|(channel_kind, message_acks)| (channel_kind, message_acks.len()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (packet_id, lost_messages)
+            })
+            .collect()
+    }
+
+    /// Bandwidth stats for each channel since the last time they were reset
+    pub(crate) fn channel_stats(&mut self) -> impl Iterator<Item = (&str, &mut ChannelStats)> {
+        let channel_registry = &self.channel_registry;
+        self.channels.iter_mut().map(|(kind, channel)| {
+            (
+                channel_registry.name(kind).unwrap_or("unknown"),
+                &mut channel.stats,
+            )
+        })
+    }
+
+    /// Return payloads that have been sent over the network, so that their allocation can be
+    /// re-used for the next packets
+    pub fn release_payloads(&mut self, payloads: impl IntoIterator<Item = Payload>) {
+        payloads
+            .into_iter()
+            .for_each(|payload| self.packet_manager.release_payload(payload));
+    }
+
     /// Prepare buckets from the internal send buffers, and return the bytes to send
     // TODO: maybe pass TickManager instead of Tick? Find a more elegant way to pass extra data that might not be used?
     //  (ticks are not purely necessary without client prediction)
@@ -148,7 +226,7 @@ impl MessageManager {
             }
         }
         // return early if there are no messages to send
-        if !has_data_to_send {
+        if !has_data_to_send && !self.priority_manager.has_buffered_messages() {
             return Ok(vec![]);
         }
 
@@ -160,9 +238,24 @@ impl MessageManager {
             current_tick,
         );
 
+        // keep track of the bandwidth used by each channel
+        for (channel_id, (single_data, fragment_data)) in data_to_send.iter() {
+            let channel_kind = self
+                .channel_registry
+                .get_kind_from_net_id(*channel_id)
+                .expect("the channel ids come from the registry");
+            let channel = self
+                .channels
+                .get_mut(channel_kind)
+                .expect("every registered channel has a container");
+            channel.stats.bytes_sent += single_data.iter().map(|m| m.bytes.len()).sum::<usize>()
+                + fragment_data.iter().map(|m| m.bytes.len()).sum::<usize>();
+        }
+
+        self.packet_manager.tick_generation = self.tick_generation;
         let packets = self.packet_manager.build_packets(data_to_send);
 
-        let mut bytes = Vec::new();
+        let mut bytes = Vec::with_capacity(packets.len());
         for mut packet in packets {
             trace!(num_messages = ?packet.data.num_messages(), "sending packet");
             let packet_id = packet.header().packet_id;
@@ -221,10 +314,60 @@ impl MessageManager {
     /// Process packet received over the network as raw bytes
     /// Update the acks, and put the messages from the packets in internal buffers
     /// Returns the tick of the packet
-    pub fn recv_packet(&mut self, packet: Packet) -> anyhow::Result<Tick> {
+    ///
+    /// Returns an error if the packet contains invalid data; in that case the packet should be dropped
+    pub fn recv_packet(&mut self, packet: Packet) -> Result<Tick, PacketError> {
         // Step 1. Parse the packet
         let tick = packet.header().tick;
         trace!(?packet, "Received packet");
+        let contents = packet.data.contents();
+
+        // check that all the channels and messages are valid before updating any internal state,
+        // so that an invalid packet is dropped entirely
+        for (channel_net_id, messages) in contents.iter() {
+            let Some((channel_kind, channel)) = self
+                .channel_registry
+                .get_kind_from_net_id(*channel_net_id)
+                .and_then(|kind| self.channels.get(kind).map(|channel| (kind, channel)))
+            else {
+                return Err(PacketError::UnknownChannel(*channel_net_id));
+            };
+            // all the fragments of a message must agree on the number of fragments
+            let mut num_fragments = HashMap::<MessageId, u8>::new();
+            for message in messages {
+                channel.receiver.validate(message).map_err(|error| {
+                    PacketError::InvalidMessage {
+                        channel: *channel_kind,
+                        error,
+                    }
+                })?;
+                if let MessageContainer::Fragment(fragment) = message {
+                    let expected = *num_fragments
+                        .entry(fragment.message_id)
+                        .or_insert(fragment.num_fragments);
+                    if expected != fragment.num_fragments {
+                        return Err(PacketError::InvalidMessage {
+                            channel: *channel_kind,
+                            error: anyhow!(
+                                "fragments of message {:?} disagree on the number of fragments",
+                                fragment.message_id
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        // keep track of the most recent extended tick of the remote
+        if let Some(generation) = packet.tick_generation {
+            let extended_tick = ((generation as u64) << 16) | tick.0 as u64;
+            if self
+                .remote_extended_tick
+                .map_or(true, |remote| extended_tick > remote)
+            {
+                self.remote_extended_tick = Some(extended_tick);
+            }
+        }
 
         // TODO: if it's fragmented, put it in a buffer? while we wait for all the parts to be ready?
         //  maybe the channel can handle the fragmentation?
@@ -237,7 +380,7 @@ impl MessageManager {
         let acked_packets = self
             .packet_manager
             .header_manager
-            .process_recv_packet_header(packet.header());
+            .process_recv_packet_header(&packet.header);
 
         // Step 3. Update the list of messages that have been acked
         for acked_packet in acked_packets {
@@ -246,7 +389,7 @@ impl MessageManager {
                     let channel = self
                         .channels
                         .get_mut(&channel_kind)
-                        .context("Channel not found")?;
+                        .expect("acks are only stored for existing channels");
                     for message_ack in message_acks {
                         channel.sender.notify_message_delivered(&message_ack);
                     }
@@ -255,26 +398,29 @@ impl MessageManager {
         }
 
         // Step 4. Put the messages from the packet in the internal buffers for each channel
-        for (channel_net_id, messages) in packet.data.contents() {
+        for (channel_net_id, messages) in contents {
             let channel_kind = self
                 .channel_registry
                 .get_kind_from_net_id(channel_net_id)
-                .context(format!(
-                    "Could not recognize net_id {} as a channel",
-                    channel_net_id
-                ))?;
+                .expect("the channels were validated");
             let channel = self
                 .channels
                 .get_mut(channel_kind)
-                .ok_or_else(|| anyhow!("Channel not found"))?;
+                .expect("the channels were validated");
             trace!(
                 "received {:?} messages from channel: {:?}",
                 messages,
                 channel_kind
             );
             for mut message in messages {
+                channel.stats.bytes_received += message.bytes().len();
                 message.set_tick(tick);
-                channel.receiver.buffer_recv(message)?;
+                channel.receiver.buffer_recv(message).map_err(|error| {
+                    PacketError::InvalidMessage {
+                        channel: *channel_kind,
+                        error,
+                    }
+                })?;
             }
         }
         Ok(tick)
@@ -293,9 +439,18 @@ impl MessageManager {
                 //  we can just have a single buffer, and keep re-using that buffer
                 trace!(pool_len = ?self.reader_pool.0.len(), "read from message manager");
                 let mut reader = self.reader_pool.start_read(single_data.bytes.as_ref());
-                let message = M::decode(&mut reader).expect("Could not decode message");
+                let message = M::decode(&mut reader);
                 // return the buffer to the pool
                 self.reader_pool.attach(reader);
+                // the message was sent by the remote peer, it could contain invalid data
+                let Ok(message) = message.inspect_err(|e| {
+                    error!(
+                        ?channel_kind,
+                        "Could not decode message, dropping it: {:?}", e
+                    )
+                }) else {
+                    continue;
+                };
 
                 // SAFETY: when we receive the message, we set the tick of the message to the header tick
                 // so every message has a tick
@@ -319,7 +474,7 @@ mod tests {
 
     use crate::_reexport::*;
     use crate::packet::message::MessageId;
-    use crate::packet::packet::FRAGMENT_SIZE;
+    use crate::packet::packet::{PacketData, FRAGMENT_SIZE, TICK_GENERATION_BYTES};
     use crate::packet::priority_manager::PriorityConfig;
     use crate::prelude::*;
     use crate::tests::protocol::*;
@@ -366,7 +521,10 @@ mod tests {
 
         // server: receive bytes from the sent messages, then process them into messages
         for packet_byte in packet_bytes.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             server_message_manager.recv_packet(packet)?;
         }
         let mut data = server_message_manager.read_messages();
@@ -403,7 +561,10 @@ mod tests {
 
         // On client side: keep looping to receive bytes on the network, then process them into messages
         for packet_byte in packet_bytes.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             client_message_manager.recv_packet(packet)?;
         }
 
@@ -415,6 +576,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    /// The tick generation is only written after the header if extended ticks are enabled
+    fn test_message_manager_tick_generation() -> Result<(), anyhow::Error> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let message = MyMessageProtocol::Message1(Message1("1".to_string()));
+        let channel_kind = ChannelKind::of::<Channel1>();
+
+        client_message_manager.buffer_send(message.clone(), channel_kind)?;
+        let packet_bytes = client_message_manager.send_packets(Tick(5))?;
+        let packet_len = packet_bytes[0].len();
+
+        client_message_manager.tick_generation = Some(3);
+        client_message_manager.buffer_send(message.clone(), channel_kind)?;
+        let packet_bytes = client_message_manager.send_packets(Tick(5))?;
+        assert_eq!(packet_bytes[0].len(), packet_len + TICK_GENERATION_BYTES);
+
+        let packet = Packet::decode(
+            &mut ReadWordBuffer::start_read(packet_bytes[0].as_slice()),
+            true,
+        )?;
+        server_message_manager.recv_packet(packet)?;
+        assert_eq!(
+            server_message_manager.remote_extended_tick,
+            Some((3 << 16) | 5)
+        );
+        assert_eq!(
+            server_message_manager.read_messages().get(&channel_kind),
+            Some(&vec![(Tick(5), message)])
+        );
+        Ok(())
+    }
+
     #[test]
     /// We want to test that we can send/receive messages over a connection
     fn test_message_manager_fragment_message() -> Result<(), anyhow::Error> {
@@ -469,7 +666,10 @@ mod tests {
 
         // server: receive bytes from the sent messages, then process them into messages
         for packet_byte in packet_bytes.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             server_message_manager.recv_packet(packet)?;
         }
         let mut data = server_message_manager.read_messages();
@@ -514,7 +714,10 @@ mod tests {
 
         // On client side: keep looping to receive bytes on the network, then process them into messages
         for packet_byte in packet_bytes.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             client_message_manager.recv_packet(packet)?;
         }
 
@@ -564,7 +767,10 @@ mod tests {
 
         // server: receive bytes from the sent messages, then process them into messages
         for packet_byte in payloads.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             server_message_manager.recv_packet(packet)?;
         }
 
@@ -575,11 +781,126 @@ mod tests {
 
         // On client side: keep looping to receive bytes on the network, then process them into messages
         for packet_byte in packet_bytes.iter_mut() {
-            let packet = Packet::decode(&mut ReadWordBuffer::start_read(packet_byte.as_slice()))?;
+            let packet = Packet::decode(
+                &mut ReadWordBuffer::start_read(packet_byte.as_slice()),
+                false,
+            )?;
             client_message_manager.recv_packet(packet)?;
         }
 
         assert_eq!(update_acks_tracker.try_recv()?, message_id);
         Ok(())
     }
+
+    #[test]
+    /// A packet that contains a message for a channel that we don't know about is rejected
+    fn test_recv_packet_unknown_channel() -> anyhow::Result<()> {
+        let protocol = protocol();
+
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        // the server doesn't know about any channel
+        let mut server_message_manager =
+            MessageManager::new(&ChannelRegistry::new(), PriorityConfig::default());
+
+        client_message_manager.buffer_send(
+            MyMessageProtocol::Message1(Message1("1".to_string())),
+            Channel1::kind(),
+        )?;
+        let payloads = client_message_manager.send_packets(Tick(0))?;
+        assert!(!payloads.is_empty());
+        for payload in payloads {
+            let packet =
+                Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()), false)?;
+            assert!(matches!(
+                server_message_manager.recv_packet(packet),
+                Err(PacketError::UnknownChannel(_))
+            ));
+        }
+        assert!(server_message_manager
+            .read_messages::<MyMessageProtocol>()
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// A packet that contains an invalid message is dropped entirely, including its valid messages
+    fn test_recv_packet_invalid_message() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+
+        client_message_manager.buffer_send(
+            MyMessageProtocol::Message1(Message1("1".to_string())),
+            Channel1::kind(),
+        )?;
+        let payloads = client_message_manager.send_packets(Tick(0))?;
+        let mut packet = Packet::decode(
+            &mut ReadWordBuffer::start_read(payloads[0].as_slice()),
+            false,
+        )?;
+        // a message without id on a reliable channel
+        let reliable_net_id = *protocol
+            .channel_registry()
+            .get_net_from_kind(&ChannelKind::of::<EntityActionsChannel>())
+            .unwrap();
+        let PacketData::Single(single_packet) = &mut packet.data else {
+            unreachable!()
+        };
+        single_packet.data.insert(
+            reliable_net_id,
+            vec![SingleData::new(None, Bytes::from(vec![0u8]), 1.0)],
+        );
+
+        assert!(matches!(
+            server_message_manager.recv_packet(packet),
+            Err(PacketError::InvalidMessage { .. })
+        ));
+        assert!(server_message_manager
+            .read_messages::<MyMessageProtocol>()
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// The same serialized message can be buffered on multiple connections
+    fn test_buffer_send_bytes() -> anyhow::Result<()> {
+        let protocol = protocol();
+        let mut client_message_manager_1 =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut client_message_manager_2 =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+        let mut server_message_manager =
+            MessageManager::new(protocol.channel_registry(), PriorityConfig::default());
+
+        let message = MyMessageProtocol::Message1(Message1("1".to_string()));
+        let mut writer = WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY);
+        writer.start_write();
+        message.encode(&mut writer)?;
+        let message_bytes = Bytes::from(writer.finish_write().to_vec());
+
+        for client_message_manager in [&mut client_message_manager_1, &mut client_message_manager_2]
+        {
+            client_message_manager.buffer_send_bytes(
+                message_bytes.clone(),
+                Channel1::kind(),
+                DEFAULT_MESSAGE_PRIORITY,
+            )?;
+            for payload in client_message_manager.send_packets(Tick(0))? {
+                let packet =
+                    Packet::decode(&mut ReadWordBuffer::start_read(payload.as_slice()), false)?;
+                server_message_manager.recv_packet(packet)?;
+            }
+            assert_eq!(
+                server_message_manager
+                    .read_messages::<MyMessageProtocol>()
+                    .get(&Channel1::kind())
+                    .unwrap(),
+                &vec![(Tick(0), message.clone())]
+            );
+        }
+        Ok(())
+    }
 }
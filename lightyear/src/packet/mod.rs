@@ -19,6 +19,9 @@ Packets that are over the maximum packet size will be fragmented into multiple [
 [`FragmentedPacket`]: packet::FragmentedPacket
 */
 
+/// Defines the [`Error`](error::Error) type returned when an invalid packet is received
+pub mod error;
+
 /// Manages the [`PacketHeader`](header::PacketHeader) which includes important packet information
 pub mod header;
 
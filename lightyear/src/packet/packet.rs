@@ -20,6 +20,9 @@ wrapping_id!(PacketId);
 /// PacketType: 2 bits
 /// Rest: 10 bytes
 const HEADER_BYTES: usize = 11;
+/// Number of bytes of the tick generation that is written after the header if extended ticks are enabled
+/// (they are taken from the payload of the packet)
+pub(crate) const TICK_GENERATION_BYTES: usize = 4;
 /// The maximum of bytes that the payload of the packet can contain (excluding the header)
 /// remove 1 byte for byte alignment at the end
 pub(crate) const MTU_PAYLOAD_BYTES: usize = MAX_PACKET_SIZE - HEADER_BYTES - 1;
@@ -271,6 +274,9 @@ impl PacketData {
 #[derive(Debug)]
 pub struct Packet {
     pub(crate) header: PacketHeader,
+    /// Generation of the tick of the header. It is only written after the header if extended ticks are enabled
+    /// (see [`TickConfig::extended`](crate::prelude::TickConfig::extended))
+    pub(crate) tick_generation: Option<u32>,
     pub(crate) data: PacketData,
 }
 
@@ -288,6 +294,9 @@ impl Packet {
         // should still use gamma for packet type
         // TODO: add test
         writer.encode(&self.header, Fixed)?;
+        if let Some(tick_generation) = self.tick_generation {
+            writer.encode(&tick_generation, Fixed)?;
+        }
         match &self.data {
             PacketData::Single(single_packet) => single_packet.encode(writer),
             PacketData::Fragmented(fragmented_packet) => fragmented_packet.encode(writer),
@@ -295,14 +304,22 @@ impl Packet {
     }
 
     /// Decode a packet from the read buffer. The read buffer will only contain the bytes for a single packet
-    pub fn decode(reader: &mut impl ReadBuffer) -> anyhow::Result<Packet> {
+    ///
+    /// `extended_ticks` must match the [`TickConfig::extended`](crate::prelude::TickConfig::extended) of the sender
+    pub fn decode(reader: &mut impl ReadBuffer, extended_ticks: bool) -> anyhow::Result<Packet> {
         let header = reader.decode::<PacketHeader>(Fixed)?;
+        let tick_generation = if extended_ticks {
+            Some(reader.decode::<u32>(Fixed)?)
+        } else {
+            None
+        };
         let packet_type = header.get_packet_type();
         match packet_type {
             PacketType::Data => {
                 let single_packet = SinglePacket::decode(reader)?;
                 Ok(Self {
                     header,
+                    tick_generation,
                     data: PacketData::Single(single_packet),
                 })
             }
@@ -310,6 +327,7 @@ impl Packet {
                 let fragmented_packet = FragmentedPacket::decode(reader)?;
                 Ok(Self {
                     header,
+                    tick_generation,
                     data: PacketData::Fragmented(fragmented_packet),
                 })
             } // _ => Err(anyhow::anyhow!("Packet type not supported")),
@@ -2,12 +2,14 @@ use std::collections::{BTreeMap, VecDeque};
 
 use bitcode::encoding::Gamma;
 use bitcode::word_buffer::WordBuffer;
+use tracing::trace;
 
 use crate::connection::netcode::MAX_PACKET_SIZE;
 use crate::packet::header::PacketHeaderManager;
 use crate::packet::message::{FragmentData, MessageContainer, SingleData};
 use crate::packet::packet::{
     FragmentedPacket, Packet, PacketData, SinglePacket, FRAGMENT_SIZE, MTU_PAYLOAD_BYTES,
+    TICK_GENERATION_BYTES,
 };
 use crate::packet::packet_type::PacketType;
 use crate::protocol::registry::NetId;
@@ -15,6 +17,7 @@ use crate::protocol::BitSerializable;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::wordbuffer::writer::WriteWordBuffer;
 use crate::serialize::writer::WriteBuffer;
+use crate::utils::pool::Pool;
 
 // enough to hold a biggest fragment + writing channel/message_id/etc.
 // pub(crate) const PACKET_BUFFER_CAPACITY: usize = MTU_PAYLOAD_BYTES * (u8::BITS as usize) + 50;
@@ -22,6 +25,11 @@ pub(crate) const PACKET_BUFFER_CAPACITY: usize = MTU_PAYLOAD_BYTES * (u8::BITS a
 
 pub type Payload = Vec<u8>;
 
+fn new_payload() -> Payload {
+    trace!("Allocating new payload buffer");
+    Payload::with_capacity(MAX_PACKET_SIZE)
+}
+
 /// `PacketBuilder` handles the process of creating a packet (writing the header and packing the
 /// messages into packets)
 pub(crate) struct PacketBuilder {
@@ -30,6 +38,10 @@ pub(crate) struct PacketBuilder {
     // TODO: should this be associated with Packet?
     try_write_buffer: WriteWordBuffer,
     write_buffer: WriteWordBuffer,
+    // Payloads that have already been sent and can be re-used to hold the bytes of new packets
+    payload_pool: Pool<Payload>,
+    /// Generation of the tick to write after the header of the new packets (if extended ticks are enabled)
+    pub(crate) tick_generation: Option<u32>,
 }
 
 impl PacketBuilder {
@@ -39,6 +51,9 @@ impl PacketBuilder {
             // write buffer to encode packets bit by bit
             try_write_buffer: WriteBuffer::with_capacity(2 * PACKET_BUFFER_CAPACITY),
             write_buffer: WriteBuffer::with_capacity(PACKET_BUFFER_CAPACITY),
+            // the pool grows to the number of packets that are sent at the same time
+            payload_pool: Pool::new(0, new_payload),
+            tick_generation: None,
         }
     }
 
@@ -49,6 +64,11 @@ impl PacketBuilder {
         // self.try_write_buffer = WriteBuffer::with_capacity(2 * PACKET_BUFFER_CAPACITY);
         self.try_write_buffer
             .set_reserved_bits(PACKET_BUFFER_CAPACITY);
+        // the tick generation is written after the header, so it takes room from the payload
+        if self.tick_generation.is_some() {
+            self.try_write_buffer
+                .reserve_bits(TICK_GENERATION_BYTES * u8::BITS as usize);
+        }
     }
 
     //
@@ -60,22 +80,26 @@ impl PacketBuilder {
     }
 
     /// Encode a packet into raw bytes
+    ///
+    /// The write buffer is re-used between packets, and the returned payload is taken from the
+    /// pool of payloads (see [`PacketBuilder::release_payload`]) so that no allocation is needed
+    /// in the steady state.
     pub(crate) fn encode_packet(&mut self, packet: &Packet) -> anyhow::Result<Payload> {
-        // TODO: check that we haven't allocated!
-        // self.clear_write_buffer();
-
-        let mut write_buffer = WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY);
-        write_buffer.set_reserved_bits(PACKET_BUFFER_CAPACITY);
-        packet.encode(&mut write_buffer)?;
-        // TODO: we should actually call finish write to byte align!
+        self.clear_write_buffer();
+        packet.encode(&mut self.write_buffer)?;
         // TODO: CAREFUL, THIS COULD ALLOCATE A BIT MORE TO BYTE ALIGN?
-        let payload = Payload::from(write_buffer.finish_write());
-        assert!(payload.len() <= MAX_PACKET_SIZE, "packet = {:?}", packet);
+        let bytes = self.write_buffer.finish_write();
+        assert!(bytes.len() <= MAX_PACKET_SIZE, "packet = {:?}", packet);
+        let (_, mut payload) = self.payload_pool.pull(new_payload).detach();
+        payload.clear();
+        payload.extend_from_slice(bytes);
         Ok(payload)
+    }
 
-        // packet.encode(&mut self.write_buffer)?;
-        // let bytes = self.write_buffer.finish_write();
-        // Ok(bytes)
+    /// Return a payload that has been sent to the pool, so that its allocation can be re-used
+    /// for future packets
+    pub(crate) fn release_payload(&self, payload: Payload) {
+        self.payload_pool.attach(payload);
     }
 
     /// Start building new packet, we start with an empty packet
@@ -94,6 +118,7 @@ impl PacketBuilder {
             .prepare_send_packet_header(PacketType::Data);
         Packet {
             header,
+            tick_generation: self.tick_generation,
             data: PacketData::Single(SinglePacket::new()),
         }
     }
@@ -134,6 +159,7 @@ impl PacketBuilder {
 
         Packet {
             header,
+            tick_generation: self.tick_generation,
             data: PacketData::Fragmented(packet),
         }
 
@@ -781,6 +807,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_encode_packet_reuses_payload() -> anyhow::Result<()> {
+        let channel_registry = get_channel_registry();
+        let mut manager = PacketBuilder::new();
+        let channel_kind = ChannelKind::of::<Channel1>();
+        let channel_id = channel_registry.get_net_from_kind(&channel_kind).unwrap();
+        let small_message = SingleData::new(None, Bytes::from(vec![1u8; 10]), 1.0);
+
+        let mut packet = manager.build_new_single_packet();
+        manager.can_add_channel_to_packet(channel_id, &mut packet)?;
+        packet.add_message(*channel_id, small_message.clone());
+        let payload = manager.encode_packet(&packet)?;
+        let expected = payload.clone();
+        let ptr = payload.as_ptr();
+
+        // once the payload is released, its allocation is re-used for the next packet
+        manager.release_payload(payload);
+        let payload = manager.encode_packet(&packet)?;
+        assert_eq!(payload.as_ptr(), ptr);
+        assert_eq!(payload, expected);
+        Ok(())
+    }
+
     // #[test]
     // fn test_write_pack_messages_in_multiple_packets() -> anyhow::Result<()> {
     //     let channel_registry = get_channel_registry();
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::num::NonZeroU32;
 
 use crossbeam_channel::{Receiver, Sender};
@@ -6,7 +6,8 @@ use governor::{DefaultDirectRateLimiter, Quota};
 use nonzero_ext::*;
 use tracing::{debug, error, trace};
 
-use crate::_reexport::EntityUpdatesChannel;
+use crate::_reexport::{EntityUpdatesChannel, InputChannel};
+use crate::channel::builder::DropPolicy;
 use crate::packet::message::{FragmentData, MessageContainer, MessageId, SingleData};
 use crate::prelude::{ChannelKind, ChannelRegistry, Tick};
 use crate::protocol::registry::NetId;
@@ -59,8 +60,8 @@ pub(crate) struct PriorityManager {
     pub(crate) config: PriorityConfig,
     // TODO: can I do without this limiter?
     pub(crate) limiter: DefaultDirectRateLimiter,
-    // Messages that could not be sent because of the bandwidth quota
-    // buffered_data: Vec<BufferedMessage>,
+    /// Messages that could not be sent because of the bandwidth quota
+    buffered_data: Vec<BufferedMessage>,
     /// List of senders to notify when a replication update message is actually sent (included in packet)
     replication_update_senders: Vec<Sender<MessageId>>,
 }
@@ -70,7 +71,7 @@ impl PriorityManager {
         Self {
             config: config.clone(),
             limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
-            // buffered_data: Vec::new(),
+            buffered_data: Vec::new(),
             replication_update_senders: Vec::new(),
         }
     }
@@ -83,6 +84,11 @@ impl PriorityManager {
         receiver
     }
 
+    /// Returns true if some messages could not be sent previously because of the bandwidth quota
+    pub(crate) fn has_buffered_messages(&self) -> bool {
+        !self.buffered_data.is_empty()
+    }
+
     // TODO: maybe accumulat ethe used_bytes in the priority_manager instead of returning here?
     /// Filter the messages by priority and bandwidth quota
     /// Returns the list of messages that we can send, along with the amount of bytes we used
@@ -147,14 +153,48 @@ impl PriorityManager {
                     }))
             })
             .collect::<Vec<_>>();
-        // // NOTE: maybe we cannot do this, because for some channels we need to know
-        // //  if the message was actually sent or not? (reliable channels)
-        // // add all new messages to the list of messages that could not be sent
-        // self.buffered_data.extend(all_messages);
-
-        // sort from highest priority to lower
-        // self.buffered_data
-        all_messages.sort_by(|a, b| a.priority.partial_cmp(&b.priority).unwrap());
+
+        // add the messages that could not be sent previously, according to the drop policy of each channel
+        let drop_policy = |net_id: NetId| {
+            channel_registry
+                .get_builder_from_net_id(net_id)
+                .unwrap()
+                .settings
+                .drop_policy
+        };
+        let new_message_channels: HashSet<NetId> =
+            all_messages.iter().map(|m| m.channel_net_id).collect();
+        let buffered_message_channels: HashSet<NetId> = self
+            .buffered_data
+            .iter()
+            .map(|m| m.channel_net_id)
+            .collect();
+        all_messages.retain(|m| {
+            drop_policy(m.channel_net_id) != DropPolicy::DropNewest
+                || !buffered_message_channels.contains(&m.channel_net_id)
+        });
+        // the buffered messages are added last so that they are sent first among messages with the same priority
+        all_messages.extend(
+            std::mem::take(&mut self.buffered_data)
+                .into_iter()
+                .filter(|m| {
+                    drop_policy(m.channel_net_id) != DropPolicy::DropOldest
+                        || !new_message_channels.contains(&m.channel_net_id)
+                }),
+        );
+
+        // sort from highest priority to lower.
+        // The input messages are always sent first, so that the inputs are not delayed behind other
+        // messages (for example client-replicated entities) when the bandwidth is tight
+        let input_channel = channel_registry
+            .get_net_from_kind(&ChannelKind::of::<InputChannel>())
+            .copied();
+        let is_input = |m: &BufferedMessage| Some(m.channel_net_id) == input_channel;
+        all_messages.sort_by(|a, b| {
+            is_input(a)
+                .cmp(&is_input(b))
+                .then(a.priority.partial_cmp(&b.priority).unwrap())
+        });
         trace!(
             "all messages to send, sorted by priority: {:?}",
             all_messages
@@ -170,17 +210,25 @@ impl PriorityManager {
             // we will adjust for this later
             let message_bytes = buffered_message.message_container.bytes().len() as u32;
             let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
-            let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
-                error!("the bandwidth does not have enough capacity for a message of this size!");
-                break;
-            };
-            let Ok(()) = result else {
-                debug!("Bandwidth quota reached, no more messages can be sent this tick");
-                break;
-            };
-
-            // keep track of the bytes we added to the rate limiter
-            bytes_used += message_bytes;
+            match self.limiter.check_n(nonzero_message_bytes) {
+                // keep track of the bytes we added to the rate limiter
+                Ok(Ok(())) => bytes_used += message_bytes,
+                // input messages are sent even if the bandwidth quota is reached
+                _ if is_input(&buffered_message) => {
+                    trace!("Bandwidth quota reached, but input messages are always sent");
+                }
+                Err(_) => {
+                    error!(
+                        "the bandwidth does not have enough capacity for a message of this size!"
+                    );
+                    break;
+                }
+                Ok(Err(_)) => {
+                    debug!("Bandwidth quota reached, no more messages can be sent this tick");
+                    all_messages.push(buffered_message);
+                    break;
+                }
+            }
 
             // the message is allowed, add it to the list of messages to send
             let channel_data = data_to_send
@@ -191,9 +239,7 @@ impl PriorityManager {
             let channel_kind = channel_registry
                 .get_kind_from_net_id(buffered_message.channel_net_id)
                 .unwrap();
-            if channel_kind == &ChannelKind::of::<EntityUpdatesChannel>()
-                || channel_kind == &ChannelKind::of::<EntityUpdatesChannel>()
-            {
+            if channel_kind == &ChannelKind::of::<EntityUpdatesChannel>() {
                 // SAFETY: we are guaranteed in this situation to have a message id (because we use the unreliable with acks sender)
                 let message_id = buffered_message.message_container.message_id().unwrap();
                 for sender in self.replication_update_senders.iter() {
@@ -216,13 +262,24 @@ impl PriorityManager {
             }
         }
 
-        // all the other messages that don't make the cut, we just drop
-        // - unreliable messages: they are unreliable so it's ok
-        // - reliable messages: they will be retried later, maybe with higher priority?
+        // all the other messages that don't make the cut:
+        // - reliable messages: we drop them, they will be retried later by the reliable sender
+        // - unreliable messages: we drop them, unless the channel's DropPolicy asks to buffer them; in which case
+        //   the DropPolicy decides what happens to them when new messages are sent on the channel
         // - unreliable entity updates: the replication sender keeps track for each entity of when we were able to send an update
         //   - PROBLEM: we could have the entity action not get sent (bandwidth), and then the priority still drops because the entity update
         //     was sent right after...
         // - reliable entity actions:
+        self.buffered_data = all_messages
+            .into_iter()
+            .filter(|m| {
+                let settings = &channel_registry
+                    .get_builder_from_net_id(m.channel_net_id)
+                    .unwrap()
+                    .settings;
+                !settings.mode.is_reliable() && settings.drop_policy != DropPolicy::Discard
+            })
+            .collect();
         let num_messages_sent = data_to_send
             .values()
             .map(|(single, fragment)| single.len() + fragment.len())
@@ -230,9 +287,177 @@ impl PriorityManager {
         debug!(
             bytes_sent = ?bytes_used,
             ?num_messages_sent,
-            num_messages_discarded = ?all_messages.len(),
+            num_messages_buffered = ?self.buffered_data.len(),
             "priority filter done.");
 
         (data_to_send, bytes_used)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{default, TypePath};
+    use bytes::Bytes;
+
+    use lightyear_macros::ChannelInternal;
+
+    use crate::channel::builder::{ChannelMode, ChannelSettings};
+    use crate::prelude::*;
+
+    use super::*;
+
+    #[derive(ChannelInternal, TypePath)]
+    struct Channel1;
+
+    #[derive(ChannelInternal, TypePath)]
+    struct Channel2;
+
+    #[derive(ChannelInternal, TypePath)]
+    struct Channel3;
+
+    #[derive(ChannelInternal, TypePath)]
+    struct Channel4;
+
+    fn get_channel_registry() -> ChannelRegistry {
+        let mut c = ChannelRegistry::new();
+        c.add::<Channel1>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            drop_policy: DropPolicy::Queue,
+            ..default()
+        });
+        c.add::<Channel2>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            drop_policy: DropPolicy::DropOldest,
+            ..default()
+        });
+        c.add::<Channel3>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            drop_policy: DropPolicy::DropNewest,
+            ..default()
+        });
+        c.add::<Channel4>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..default()
+        });
+        c
+    }
+
+    fn message(value: u8) -> SingleData {
+        SingleData::new(None, Bytes::from(vec![value; 60]), 1.0)
+    }
+
+    /// Send 2 messages on a channel while the bandwidth quota only lets us send one,
+    /// then send a new message on the channel. Returns the bytes of the messages that are still buffered.
+    fn buffered_after_overflow<C: Channel>(channel_registry: &ChannelRegistry) -> Vec<u8> {
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: Quota::per_minute(nonzero!(100u32)),
+            enabled: true,
+        });
+        let net_id = *channel_registry.get_net_from_kind(&C::kind()).unwrap();
+        let (sent, _) = manager.priority_filter(
+            vec![(
+                net_id,
+                (VecDeque::from([message(0), message(1)]), VecDeque::new()),
+            )],
+            channel_registry,
+            Tick(0),
+        );
+        assert_eq!(sent.get(&net_id).unwrap().0.len(), 1);
+        assert_eq!(manager.buffered_data.len(), 1);
+
+        let (sent, _) = manager.priority_filter(
+            vec![(net_id, (VecDeque::from([message(2)]), VecDeque::new()))],
+            channel_registry,
+            Tick(1),
+        );
+        assert!(sent.is_empty());
+        manager
+            .buffered_data
+            .iter()
+            .map(|m| m.message_container.bytes()[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_input_messages_sent_first() {
+        let mut channel_registry = get_channel_registry();
+        channel_registry.add::<InputChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            drop_policy: DropPolicy::Queue,
+            ..default()
+        });
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: Quota::per_minute(nonzero!(100u32)),
+            enabled: true,
+        });
+        let channel_net_id = *channel_registry
+            .get_net_from_kind(&Channel1::kind())
+            .unwrap();
+        let input_net_id = *channel_registry
+            .get_net_from_kind(&InputChannel::kind())
+            .unwrap();
+        let mut high_priority_message = message(0);
+        high_priority_message.priority = 10.0;
+        let (sent, _) = manager.priority_filter(
+            vec![
+                (
+                    channel_net_id,
+                    (VecDeque::from([high_priority_message]), VecDeque::new()),
+                ),
+                (
+                    input_net_id,
+                    (VecDeque::from([message(1), message(2)]), VecDeque::new()),
+                ),
+            ],
+            &channel_registry,
+            Tick(0),
+        );
+        // the input messages are sent first, even if they exceed the bandwidth quota
+        assert_eq!(sent.get(&input_net_id).unwrap().0.len(), 2);
+        assert!(sent.get(&channel_net_id).is_none());
+        assert_eq!(manager.buffered_data.len(), 1);
+    }
+
+    #[test]
+    fn test_drop_policy() {
+        let channel_registry = get_channel_registry();
+        // the message that could not be sent is kept along with the new message
+        assert_eq!(
+            buffered_after_overflow::<Channel1>(&channel_registry).len(),
+            2
+        );
+        // the older message is discarded in favor of the new message
+        assert_eq!(
+            buffered_after_overflow::<Channel2>(&channel_registry),
+            vec![2]
+        );
+        // the new message is discarded
+        assert_eq!(
+            buffered_after_overflow::<Channel3>(&channel_registry),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_default_drop_policy_discards() {
+        let channel_registry = get_channel_registry();
+        let mut manager = PriorityManager::new(PriorityConfig {
+            bandwidth_quota: Quota::per_minute(nonzero!(100u32)),
+            enabled: true,
+        });
+        let net_id = *channel_registry
+            .get_net_from_kind(&Channel4::kind())
+            .unwrap();
+        let (sent, _) = manager.priority_filter(
+            vec![(
+                net_id,
+                (VecDeque::from([message(0), message(1)]), VecDeque::new()),
+            )],
+            &channel_registry,
+            Tick(0),
+        );
+        assert_eq!(sent.get(&net_id).unwrap().0.len(), 1);
+        // the message that could not be sent is not buffered
+        assert!(!manager.has_buffered_messages());
+    }
+}
@@ -3,7 +3,9 @@ use std::any::TypeId;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
-use bevy::prelude::{App, Component, Entity, EntityMapper, EntityWorldMut, TypePath, World};
+use bevy::prelude::{
+    App, Component, Entity, EntityMapper, EntityRef, EntityWorldMut, TypePath, World,
+};
 use bevy::reflect::{FromReflect, GetTypeRegistration};
 use bevy::utils::HashMap;
 use cfg_if::cfg_if;
@@ -13,13 +15,15 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::client::components::{ComponentSyncMode, LerpFn, SyncMetadata};
-use crate::prelude::{Message, PreSpawnedPlayerObject};
+use crate::prelude::{Authority, Message, PreSpawnedPlayerObject};
 use crate::protocol::{BitSerializable, EventContext, Protocol};
 use crate::shared::events::connection::{
     IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
 };
 use crate::shared::replication::components::ShouldBePredicted;
-use crate::shared::replication::components::{PrePredicted, ShouldBeInterpolated};
+use crate::shared::replication::components::{
+    PrePredicted, ShouldBeInterpolated, SyncModeOverride,
+};
 use crate::shared::replication::ReplicationSend;
 
 // client writes an Enum containing all their message type
@@ -39,6 +43,7 @@ pub trait ComponentProtocol:
     + From<ShouldBePredicted>
     + From<PrePredicted>
     + From<ShouldBeInterpolated>
+    + From<SyncModeOverride<<Self::Protocol as Protocol>::ComponentKinds>>
     + TryInto<ShouldBePredicted>
     + TryInto<PrePredicted>
 {
@@ -47,6 +52,9 @@ pub trait ComponentProtocol:
     /// Map from the type-id to the component kind for each component in the protocol
     fn type_ids() -> HashMap<TypeId, <Self::Protocol as Protocol>::ComponentKinds>;
 
+    /// Get a copy of every component of the protocol that is present on the entity
+    fn extract(entity: &EntityRef) -> Vec<Self>;
+
     /// Apply a ComponentInsert to an entity
     fn insert(self, entity: &mut EntityWorldMut);
 
@@ -171,6 +179,8 @@ cfg_if!(
             + FromType<ShouldBeInterpolated>
             + FromType<PrePredicted>
             + FromType<PreSpawnedPlayerObject>
+            + FromType<Authority>
+            + FromType<SyncModeOverride<<Self::Protocol as Protocol>::ComponentKinds>>
             + FromType<ActionState<<Self::Protocol as Protocol>::LeafwingInput1>>
             + FromType<ActionState<<Self::Protocol as Protocol>::LeafwingInput2>>
         {
@@ -201,6 +211,8 @@ cfg_if!(
             + FromType<ShouldBeInterpolated>
             + FromType<PrePredicted>
             + FromType<PreSpawnedPlayerObject>
+            + FromType<Authority>
+            + FromType<SyncModeOverride<<Self::Protocol as Protocol>::ComponentKinds>>
         {
             type Protocol: Protocol;
         }
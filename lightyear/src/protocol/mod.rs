@@ -161,32 +161,50 @@ macro_rules! protocolize {
                         direction: ChannelDirection::Bidirectional,
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<PingChannel>(ChannelSettings {
                         mode: ChannelMode::SequencedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         // we always want to include the ping in the packet
                         priority: 1000.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::ClientToServer,
                         priority: 3.0,
+                        drop_policy: DropPolicy::Discard,
+                    });
+                    protocol.add_channel::<InputBroadcastChannel>(ChannelSettings {
+                        mode: ChannelMode::UnorderedUnreliable,
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 3.0,
+                        drop_policy: DropPolicy::Discard,
+                    });
+                    protocol.add_channel::<ServerControlChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<TickBufferChannel>(ChannelSettings {
                         mode: ChannelMode::TickBuffered,
                         direction: ChannelDirection::ClientToServer,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol
                 }
@@ -296,32 +314,50 @@ macro_rules! protocolize {
                         direction: ChannelDirection::Bidirectional,
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<PingChannel>(ChannelSettings {
                         mode: ChannelMode::SequencedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         // we always want to include the ping in the packet
                         priority: 1000.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::ClientToServer,
                         priority: 3.0,
+                        drop_policy: DropPolicy::Discard,
+                    });
+                    protocol.add_channel::<InputBroadcastChannel>(ChannelSettings {
+                        mode: ChannelMode::UnorderedUnreliable,
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 3.0,
+                        drop_policy: DropPolicy::Discard,
+                    });
+                    protocol.add_channel::<ServerControlChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
                         direction: ChannelDirection::Bidirectional,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol.add_channel::<TickBufferChannel>(ChannelSettings {
                         mode: ChannelMode::TickBuffered,
                         direction: ChannelDirection::ClientToServer,
                         priority: 1.0,
+                        drop_policy: DropPolicy::Discard,
                     });
                     protocol
                 }
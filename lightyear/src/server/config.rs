@@ -1,15 +1,23 @@
 //! Defines server-specific configuration options
+use std::sync::Arc;
+
 use bevy::prelude::Resource;
+use bevy::utils::Duration;
 use governor::Quota;
 use nonzero_ext::nonzero;
 
-use crate::connection::netcode::Key;
+use crate::connection::netcode::{
+    ConnectionFilter, ConnectionRequest, ConnectionRequestHandler, Key, MAX_CLIENTS,
+};
 use crate::connection::server::NetConfig;
+use crate::server::input::{
+    InputBroadcastConfig, InputNudgeConfig, InputRateLimitConfig, LateInputConfig,
+};
 use crate::server::replication::ReplicationConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::ping::manager::PingConfig;
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct NetcodeConfig {
     pub num_disconnect_packets: usize,
     pub keep_alive_send_rate: f64,
@@ -19,6 +27,35 @@ pub struct NetcodeConfig {
     pub client_timeout_secs: i32,
     pub protocol_id: u64,
     pub private_key: Option<Key>,
+    /// If set, decides whether each connection request is accepted before the client is admitted
+    pub connection_request_handler: Option<ConnectionRequestHandler>,
+    /// If true, clients are disconnected when their connect token expires, unless they refreshed it
+    /// with a new token. The default is false.
+    pub disconnect_on_token_expiry: bool,
+    /// Maximum number of clients that can be connected at the same time.
+    /// It can be changed at runtime with [`ServerConnections::set_max_clients`](crate::connection::server::ServerConnections::set_max_clients)
+    pub max_clients: usize,
+    /// Allow and deny lists of addresses and client ids.
+    /// It can be changed at runtime with [`ServerConnections::modify_connection_filter`](crate::connection::server::ServerConnections::modify_connection_filter)
+    pub connection_filter: ConnectionFilter,
+}
+
+impl std::fmt::Debug for NetcodeConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetcodeConfig")
+            .field("num_disconnect_packets", &self.num_disconnect_packets)
+            .field("keep_alive_send_rate", &self.keep_alive_send_rate)
+            .field("client_timeout_secs", &self.client_timeout_secs)
+            .field("protocol_id", &self.protocol_id)
+            .field("private_key", &self.private_key)
+            .field(
+                "disconnect_on_token_expiry",
+                &self.disconnect_on_token_expiry,
+            )
+            .field("max_clients", &self.max_clients)
+            .field("connection_filter", &self.connection_filter)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for NetcodeConfig {
@@ -29,6 +66,10 @@ impl Default for NetcodeConfig {
             client_timeout_secs: 3,
             protocol_id: 0,
             private_key: None,
+            connection_request_handler: None,
+            disconnect_on_token_expiry: false,
+            max_clients: MAX_CLIENTS,
+            connection_filter: ConnectionFilter::default(),
         }
     }
 }
@@ -47,6 +88,31 @@ impl NetcodeConfig {
         self.client_timeout_secs = client_timeout_secs;
         self
     }
+
+    /// Accept or deny each connection request with the `handler` (for bans, allowlists, reserved slots, etc.)
+    pub fn with_connection_request_handler(
+        mut self,
+        handler: impl Fn(&ConnectionRequest) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.connection_request_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Disconnect the clients whose connect token expired and was not refreshed
+    pub fn with_disconnect_on_token_expiry(mut self, disconnect: bool) -> Self {
+        self.disconnect_on_token_expiry = disconnect;
+        self
+    }
+
+    pub fn with_max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    pub fn with_connection_filter(mut self, connection_filter: ConnectionFilter) -> Self {
+        self.connection_filter = connection_filter;
+        self
+    }
 }
 
 /// Configuration related to sending packets
@@ -56,6 +122,13 @@ pub struct PacketConfig {
     pub per_client_send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// If true, a client gets disconnected when it sends a packet that contains invalid data.
+    /// Otherwise the invalid packet is simply dropped.
+    pub disconnect_on_invalid_packet: bool,
+    /// Maximum amount of time we wait for a client to acknowledge its farewell message
+    /// (see [`ConnectionManager::disconnect_client_with_message`](crate::server::connection::ConnectionManager::disconnect_client_with_message))
+    /// before closing the connection
+    pub disconnect_message_timeout: Duration,
 }
 
 impl Default for PacketConfig {
@@ -64,6 +137,8 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             per_client_send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            disconnect_on_invalid_packet: false,
+            disconnect_message_timeout: Duration::from_secs(1),
         }
     }
 }
@@ -84,6 +159,16 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn enable_disconnect_on_invalid_packet(mut self) -> Self {
+        self.disconnect_on_invalid_packet = true;
+        self
+    }
+
+    pub fn with_disconnect_message_timeout(mut self, timeout: Duration) -> Self {
+        self.disconnect_message_timeout = timeout;
+        self
+    }
 }
 
 /// Configuration for the server plugin
@@ -96,4 +181,14 @@ pub struct ServerConfig {
     pub packet: PacketConfig,
     pub ping: PingConfig,
     pub replication: ReplicationConfig,
+    /// If set, the server relays the inputs of each client to the other clients
+    pub input_broadcast: Option<InputBroadcastConfig>,
+    /// If set, limits the number of input messages accepted from each client
+    pub input_rate_limit: Option<InputRateLimitConfig>,
+    /// If set, the inputs that arrive after their tick was simulated are emitted as
+    /// [`LateInputEvent`](crate::server::input::LateInputEvent)s instead of being dropped
+    pub late_inputs: Option<LateInputConfig>,
+    /// If set, the server asks the clients to speed up or slow down slightly depending on the depth of
+    /// their input buffer
+    pub input_nudge: Option<InputNudgeConfig>,
 }
@@ -1,51 +1,68 @@
 //! Specify how a Server sends/receives messages with a Client
+use std::collections::VecDeque;
+
 use anyhow::{Context, Result};
 use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::{EntityHash, MapEntities};
 use bevy::prelude::{Entity, Resource, World};
-use bevy::utils::{HashMap, HashSet};
+use bevy::utils::{Duration, HashMap, HashSet};
+use bytes::Bytes;
 use hashbrown::hash_map::Entry;
 use serde::Serialize;
-use tracing::{debug, info, trace, trace_span, warn};
+use tracing::{debug, error, info, trace, trace_span, warn};
 
 use crate::_reexport::{
-    EntityUpdatesChannel, FromType, InputMessageKind, MessageProtocol, PingChannel,
-    ReplicationSend, ServerMarker, ShouldBeInterpolated,
+    EntityActionsChannel, EntityUpdatesChannel, FromType, InputBroadcastChannel, InputMessageKind,
+    MessageProtocol, PingChannel, ReplicationSend, ServerControlChannel, ServerMarker,
+    ShouldBeInterpolated,
 };
 use crate::channel::senders::ChannelSend;
 use crate::client::message::ClientMessage;
 use crate::connection::id::ClientId;
-use crate::inputs::native::input_buffer::InputBuffer;
-use crate::packet::message_manager::MessageManager;
+use crate::connection::netcode::USER_DATA_BYTES;
+use crate::inputs::native::input_buffer::{InputBuffer, InputMessage};
+use crate::packet::message_manager::{MessageManager, DEFAULT_MESSAGE_PRIORITY};
 use crate::packet::packet::Packet;
-use crate::packet::packet_manager::Payload;
+use crate::packet::packet_manager::{Payload, PACKET_BUFFER_CAPACITY};
 use crate::prelude::{
-    Channel, ChannelKind, Message, Mode, PreSpawnedPlayerObject, ShouldBePredicted,
+    Authority, Channel, ChannelKind, DisconnectReason, Message, Mode, PreSpawnedPlayerObject,
+    ShouldBePredicted,
 };
 use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::BitSerializable;
 use crate::protocol::Protocol;
 use crate::serialize::reader::ReadBuffer;
+use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+use crate::serialize::writer::WriteBuffer;
 use crate::server::config::PacketConfig;
 use crate::server::events::ServerEvents;
+use crate::server::input::{
+    InputBroadcastConfig, InputBufferMetrics, InputNudgeConfig, InputNudger, InputRateLimitAction,
+    InputRateLimitConfig, InputRateLimitedEvent, InputRateLimiter, InputValidator, LateInputConfig,
+    LateInputEvent, MissingInputStrategy,
+};
 use crate::server::message::ServerMessage;
+use crate::server::replication::{ReplicationConfig, ReplicationValidators};
+use crate::server::room::RoomManager;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
 use crate::shared::replication::components::{NetworkTarget, Replicate, ReplicationGroupId};
+use crate::shared::replication::entity_map::RemoteEntityMap;
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
-use crate::shared::replication::ReplicationMessage;
 use crate::shared::replication::ReplicationMessageData;
+use crate::shared::replication::{compress_baseline, ReplicationMessage};
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
-use crate::shared::time_manager::TimeManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
 #[derive(Resource)]
 pub struct ConnectionManager<P: Protocol> {
     pub(crate) connections: HashMap<ClientId, Connection<P>>,
-    channel_registry: ChannelRegistry,
+    pub(crate) channel_registry: ChannelRegistry,
     pub(crate) events: ServerEvents<P>,
 
     // NOTE: we put this here because we only need one per world, not one per connection
@@ -59,6 +76,46 @@ pub struct ConnectionManager<P: Protocol> {
 
     packet_config: PacketConfig,
     ping_config: PingConfig,
+    pub(crate) replication_config: ReplicationConfig,
+    /// Buffer used to serialize messages that are sent to multiple clients
+    writer: WriteWordBuffer,
+    /// Entities that are simulated by a client instead of the server (see [`Authority`])
+    authority: EntityHashMap<Entity, ClientId>,
+    /// If set, the inputs received from each client are relayed to the other clients
+    input_broadcast: Option<InputBroadcastConfig>,
+    /// If set, limits the number of input messages accepted from each client
+    input_rate_limit: Option<InputRateLimitConfig>,
+    /// If set, the inputs that arrive late are emitted as [`LateInputEvent`]s
+    pub(crate) late_inputs: Option<LateInputConfig>,
+    /// If set, the clients are asked to speed up or slow down depending on the depth of their input buffer
+    input_nudge: Option<InputNudgeConfig>,
+    /// Tick duration that was set at runtime with [`ConnectionManager::set_tick_duration`], along with the
+    /// tick from which it applies
+    tick_duration: Option<(Tick, Duration)>,
+    /// Tick duration requested with [`ConnectionManager::set_tick_duration`], that will be scheduled at the
+    /// start of the next frame
+    pub(crate) pending_tick_duration: Option<Duration>,
+    /// Tick duration change that was sent to the clients, and that will be applied from the given tick
+    pub(crate) scheduled_tick_duration: Option<(Tick, Duration)>,
+    /// Pause (`true`) or resume (`false`) request that will be applied at the start of the next frame
+    pub(crate) pending_pause: Option<bool>,
+    /// The pause message was buffered; the simulation will be paused once it has been sent
+    pub(crate) pausing: bool,
+    /// Tick at which the simulation was paused
+    pub(crate) paused_at: Option<Tick>,
+    /// Clients that will be disconnected once their buffered messages have been sent
+    pub(crate) pending_disconnects: Vec<PendingDisconnect>,
+}
+
+/// A client that is being disconnected by the server
+#[derive(Debug)]
+pub(crate) struct PendingDisconnect {
+    pub(crate) client_id: ClientId,
+    pub(crate) reason: DisconnectReason,
+    /// Channel of the farewell message: the client stays connected until the message is acked
+    pub(crate) farewell_channel: Option<ChannelKind>,
+    /// Time after which the client is disconnected even if the farewell message was not acked
+    pub(crate) deadline: Option<WrappedTime>,
 }
 
 impl<P: Protocol> ConnectionManager<P> {
@@ -66,6 +123,11 @@ impl<P: Protocol> ConnectionManager<P> {
         channel_registry: ChannelRegistry,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        replication_config: ReplicationConfig,
+        input_broadcast: Option<InputBroadcastConfig>,
+        input_rate_limit: Option<InputRateLimitConfig>,
+        late_inputs: Option<LateInputConfig>,
+        input_nudge: Option<InputNudgeConfig>,
     ) -> Self {
         Self {
             connections: HashMap::default(),
@@ -75,7 +137,57 @@ impl<P: Protocol> ConnectionManager<P> {
             new_clients: vec![],
             packet_config,
             ping_config,
+            replication_config,
+            writer: WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY),
+            authority: EntityHashMap::default(),
+            input_broadcast,
+            input_rate_limit,
+            late_inputs,
+            input_nudge,
+            tick_duration: None,
+            pending_tick_duration: None,
+            scheduled_tick_duration: None,
+            pending_pause: None,
+            pausing: false,
+            paused_at: None,
+            pending_disconnects: vec![],
+        }
+    }
+
+    /// Update which peer has authority over the entity
+    ///
+    /// All the connections are updated; clients that connect later are updated when they are added.
+    pub(crate) fn set_authority(&mut self, entity: Entity, authority: Authority) {
+        match authority {
+            Authority::Server => self.authority.remove(&entity),
+            Authority::Client(client_id) => self.authority.insert(entity, client_id),
+        };
+        for (client_id, connection) in self.connections.iter_mut() {
+            let entity_map = &mut connection.replication_receiver.remote_entity_map;
+            if authority.is_client(*client_id) {
+                // the authoritative client replicates the entity using our entity id
+                entity_map.insert(entity, entity);
+            } else if entity_map.get_local(entity) == Some(&entity) {
+                // the client doesn't have the authority (anymore), it doesn't replicate the entity to us
+                entity_map.remove_by_remote(entity);
+            }
+        }
+    }
+
+    /// The client that has authority over an entity does not receive replication updates for it,
+    /// apart from the [`Authority`] component itself
+    pub(crate) fn exclude_authority(
+        &self,
+        entity: Entity,
+        kind: P::ComponentKinds,
+        mut target: NetworkTarget,
+    ) -> NetworkTarget {
+        if kind != <P::ComponentKinds as FromType<Authority>>::from_type() {
+            if let Some(client_id) = self.authority.get(&entity) {
+                target.exclude(vec![*client_id]);
+            }
         }
+        target
     }
 
     /// Find the list of clients that should receive the replication message
@@ -130,6 +242,74 @@ impl<P: Protocol> ConnectionManager<P> {
             .context("client id not found")
     }
 
+    /// Map between the entities of a client and the server's entities
+    pub fn remote_entity_map(&self, client_id: ClientId) -> Result<&RemoteEntityMap> {
+        Ok(&self
+            .connection(client_id)?
+            .replication_receiver
+            .remote_entity_map)
+    }
+
+    /// Map an entity of the client `client_id` to an already existing local entity.
+    ///
+    /// When the client replicates `remote_entity`, its components will be added to `local_entity`
+    /// instead of spawning a new entity.
+    pub fn register_entity_mapping(
+        &mut self,
+        client_id: ClientId,
+        remote_entity: Entity,
+        local_entity: Entity,
+    ) -> Result<()> {
+        self.connection_mut(client_id)?
+            .replication_receiver
+            .register_entity_mapping(remote_entity, local_entity);
+        Ok(())
+    }
+
+    /// Override the [`ReplicationConfig::max_spawns_per_send`] for a single client.
+    ///
+    /// For example you can lower the budget of a client that just teleported to a crowded area,
+    /// so that the spawns of all the entities it gained visibility of are spread over several send intervals.
+    pub fn set_max_spawns_per_send(
+        &mut self,
+        client_id: ClientId,
+        max_spawns_per_send: Option<usize>,
+    ) -> Result<()> {
+        self.connection_mut(client_id)?
+            .replication_sender
+            .max_spawns_per_send = max_spawns_per_send;
+        Ok(())
+    }
+
+    /// Statistics about the inputs received from the client
+    pub fn input_buffer_metrics(&self, client_id: ClientId) -> Result<&InputBufferMetrics> {
+        Ok(&self.connection(client_id)?.input_metrics)
+    }
+
+    /// User data that the client provided when connecting (the user data of its `ConnectToken`),
+    /// for example to associate the connection with a backend account
+    pub fn user_data(&self, client_id: ClientId) -> Result<Option<&[u8; USER_DATA_BYTES]>> {
+        Ok(self.connection(client_id)?.user_data.as_ref())
+    }
+
+    /// Number of entity spawns for the client that are queued because of the spawn budget
+    pub fn pending_spawns(&self, client_id: ClientId) -> Result<usize> {
+        Ok(self
+            .connection(client_id)?
+            .replication_sender
+            .num_pending_spawns())
+    }
+
+    /// Most recent 64-bit tick received from the client.
+    ///
+    /// Only available if [`TickConfig::extended`](crate::prelude::TickConfig::extended) is enabled
+    pub fn latest_received_extended_tick(&self, client_id: ClientId) -> Result<Option<u64>> {
+        Ok(self
+            .connection(client_id)?
+            .message_manager
+            .remote_extended_tick)
+    }
+
     pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
         self.connections.values_mut().for_each(|connection| {
             connection.update(time_manager, tick_manager);
@@ -147,15 +327,214 @@ impl<P: Protocol> ConnectionManager<P> {
                 &self.channel_registry,
                 self.packet_config.clone(),
                 self.ping_config.clone(),
+                &self.replication_config,
+                self.input_broadcast.is_some(),
+                self.input_rate_limit.as_ref(),
+                self.input_nudge.as_ref(),
             );
             self.events.push_connection(client_id);
             self.new_clients.push(client_id);
-            e.insert(connection);
+            let connection = e.insert(connection);
+            // the client could have received the authority over some entities before it connected
+            for (entity, _) in self
+                .authority
+                .iter()
+                .filter(|(_, owner)| **owner == client_id)
+            {
+                connection
+                    .replication_receiver
+                    .remote_entity_map
+                    .insert(*entity, *entity);
+            }
+            // the client was configured with the initial tick duration
+            for (tick, tick_duration) in self
+                .tick_duration
+                .into_iter()
+                .chain(self.scheduled_tick_duration)
+            {
+                let _ = connection
+                    .send_tick_duration(tick, tick_duration)
+                    .map_err(|e| {
+                        error!("could not send the tick duration to the client: {:?}", e);
+                    });
+            }
+            if let Some(tick) = self.paused_at {
+                let _ = connection.send_pause(tick).map_err(|e| {
+                    error!("could not send the pause to the client: {:?}", e);
+                });
+            }
         } else {
             info!("Client {} was already in the connections list", client_id);
         }
     }
 
+    /// Change the tick duration of the server at runtime (for example to lower the tick rate under load).
+    ///
+    /// The change is sent to all the clients along with the tick from which the new tick duration applies,
+    /// so that they adjust their own tick duration (and their prediction and interpolation timelines) at the same
+    /// tick as the server, without reconnecting. The tick is chosen far enough in the future (depending on the RTT
+    /// of the clients) for the clients to receive the change before they simulate that tick; a client that
+    /// receives it later applies it right away.
+    pub fn set_tick_duration(&mut self, tick_duration: Duration) {
+        self.pending_tick_duration = Some(tick_duration);
+    }
+
+    /// Number of ticks added to the RTT of the clients when scheduling a tick duration change
+    const TICK_DURATION_CHANGE_MARGIN: i16 = 10;
+
+    /// Schedule the new tick duration, and send it to all the clients.
+    /// Returns the tick from which the new tick duration applies
+    pub(crate) fn schedule_tick_duration(
+        &mut self,
+        tick_manager: &TickManager,
+        tick_duration: Duration,
+    ) -> Result<Tick> {
+        // the clients run ahead of the server by about half the RTT, and receive the message half an RTT later
+        let max_rtt = self
+            .connections
+            .values()
+            .map(|connection| connection.ping_manager.rtt())
+            .max()
+            .unwrap_or_default();
+        let rtt_ticks =
+            (max_rtt.as_secs_f32() / tick_manager.config.tick_duration.as_secs_f32()).ceil() as i16;
+        let tick = tick_manager.tick() + rtt_ticks + Self::TICK_DURATION_CHANGE_MARGIN;
+        self.scheduled_tick_duration = Some((tick, tick_duration));
+        self.connections
+            .values_mut()
+            .try_for_each(|connection| connection.send_tick_duration(tick, tick_duration))?;
+        Ok(tick)
+    }
+
+    /// Returns the scheduled tick duration if it should be applied at the current tick
+    pub(crate) fn take_scheduled_tick_duration(&mut self, current_tick: Tick) -> Option<Duration> {
+        let (tick, tick_duration) = self.scheduled_tick_duration?;
+        if current_tick < tick {
+            return None;
+        }
+        self.scheduled_tick_duration = None;
+        self.tick_duration = Some((tick, tick_duration));
+        Some(tick_duration)
+    }
+
+    /// Pause the simulation (for example in a host-client game when the host opens a menu).
+    ///
+    /// The clients are notified, then the server stops advancing ticks.
+    /// The server keeps sending packets while the simulation is paused, so that the pause message is
+    /// retransmitted until it is acked, and the connections are kept alive.
+    pub fn pause(&mut self) {
+        self.pending_pause = Some(true);
+    }
+
+    /// Resume the simulation that was paused with [`ConnectionManager::pause`].
+    ///
+    /// The clients are notified of the tick at which the simulation resumes.
+    pub fn resume(&mut self) {
+        self.pending_pause = Some(false);
+    }
+
+    /// Returns true if the simulation is paused (or about to be paused)
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Notify all the clients that the simulation is paused
+    pub(crate) fn apply_pause(&mut self, tick: Tick) -> Result<()> {
+        self.paused_at = Some(tick);
+        self.pausing = true;
+        self.connections
+            .values_mut()
+            .try_for_each(|connection| connection.send_pause(tick))
+    }
+
+    /// Notify all the clients that the simulation resumed
+    pub(crate) fn apply_resume(&mut self, tick: Tick) -> Result<()> {
+        self.paused_at = None;
+        self.pausing = false;
+        self.connections
+            .values_mut()
+            .try_for_each(|connection| connection.send_resume(tick))
+    }
+
+    /// Disconnect a client.
+    ///
+    /// The messages that were already buffered for the client are flushed during the next send, then the client
+    /// receives the disconnect `reason` and its connection is removed.
+    pub fn disconnect_client(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) -> Result<()> {
+        self.start_disconnect(client_id, reason, None)
+    }
+
+    /// Disconnect a client after sending them a final message (for example to tell them why they were kicked).
+    ///
+    /// The client stays in a disconnecting state until the message is acked (if `C` is a reliable channel),
+    /// or until the [`PacketConfig::disconnect_message_timeout`] has elapsed; then the connection is closed.
+    pub fn disconnect_client_with_message<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+        message: M,
+    ) -> Result<()>
+    where
+        M: Clone,
+        P::Message: From<M>,
+    {
+        self.send_message::<C, M>(client_id, message)?;
+        self.start_disconnect(client_id, reason, Some(ChannelKind::of::<C>()))
+    }
+
+    fn start_disconnect(
+        &mut self,
+        client_id: ClientId,
+        reason: DisconnectReason,
+        farewell_channel: Option<ChannelKind>,
+    ) -> Result<()> {
+        self.connection(client_id)?;
+        match self
+            .pending_disconnects
+            .iter_mut()
+            .find(|pending| pending.client_id == client_id)
+        {
+            Some(pending) => {
+                pending.farewell_channel = pending.farewell_channel.or(farewell_channel);
+            }
+            None => self.pending_disconnects.push(PendingDisconnect {
+                client_id,
+                reason,
+                farewell_channel,
+                deadline: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Returns the clients that can be disconnected now: their farewell message was acked,
+    /// or the [`PacketConfig::disconnect_message_timeout`] has elapsed.
+    pub(crate) fn drain_disconnects(
+        &mut self,
+        current_time: WrappedTime,
+    ) -> Vec<(ClientId, DisconnectReason)> {
+        let timeout = self.packet_config.disconnect_message_timeout;
+        let mut ready = vec![];
+        for mut pending in std::mem::take(&mut self.pending_disconnects) {
+            let deadline = *pending.deadline.get_or_insert(current_time + timeout);
+            let farewell_acked = pending.farewell_channel.map_or(true, |channel| {
+                self.connections
+                    .get(&pending.client_id)
+                    .map_or(true, |c| !c.message_manager.has_unacked_messages(&channel))
+            });
+            if farewell_acked || current_time >= deadline {
+                ready.push((pending.client_id, pending.reason));
+            } else {
+                self.pending_disconnects.push(pending);
+            }
+        }
+        ready
+    }
+
     pub(crate) fn remove(&mut self, client_id: ClientId) {
         #[cfg(feature = "metrics")]
         metrics::gauge!("connected_clients").decrement(1.0);
@@ -165,22 +544,60 @@ impl<P: Protocol> ConnectionManager<P> {
         self.connections.remove(&client_id);
     }
 
-    /// Get the inputs for all clients for the given tick
-    pub(crate) fn pop_inputs(
-        &mut self,
+    /// Get the inputs for all clients for the given tick, along with a flag indicating if the input
+    /// was missing and replaced according to the [`MissingInputStrategy`], and the sub-tick timestamp
+    /// of the input if the client recorded it.
+    ///
+    /// Inputs are only reported as missing once the client has sent at least one input.
+    pub(crate) fn pop_inputs<'a>(
+        &'a mut self,
         tick: Tick,
-    ) -> impl Iterator<Item = (Option<P::Input>, ClientId)> + '_ {
+        strategy: &'a MissingInputStrategy<P::Input>,
+    ) -> impl Iterator<Item = (Option<P::Input>, ClientId, bool, Option<f32>)> + 'a {
+        // keep the inputs that were applied during the late window, so that they can be re-applied
+        // when the server rewinds for late inputs
+        let rewind_history_len = self
+            .late_inputs
+            .as_ref()
+            .filter(|config| config.rewind)
+            .map(|config| config.max_late_ticks as usize + 1);
         self.connections
             .iter_mut()
             .map(move |(client_id, connection)| {
                 trace!(input_buffer = ?connection.input_buffer, ?tick, ?client_id, "input buffer for client");
+                let sub_tick = connection.input_buffer.get_sub_tick(tick);
                 let received_input = connection.input_buffer.pop(tick);
+                if let Some(limiter) = &mut connection.input_rate_limiter {
+                    limiter.refill();
+                }
                 let fallback = received_input.is_none();
+                connection.input_metrics.num_ticks += 1;
+                connection.input_metrics.buffer_depth = connection.input_buffer.buffer.len();
+                let last_received_tick = connection.input_buffer.last_received_tick;
+                if let Some(offset) = connection
+                    .input_nudger
+                    .as_mut()
+                    // clients that don't send inputs have an empty buffer, but should not be nudged
+                    .filter(|nudger| nudger.is_active(tick, last_received_tick))
+                    .and_then(|nudger| nudger.update(connection.input_metrics.buffer_depth))
+                {
+                    let _ = connection.send_time_nudge(offset).map_err(|e| {
+                        error!("could not send the time nudge to the client: {:?}", e);
+                    });
+                }
+                if fallback {
+                    connection.input_metrics.num_missing += 1;
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("missing_input").increment(1);
+                }
 
-                // NOTE: if there is no input for this tick, we should use the last input that we have
-                //  as a best-effort fallback.
+                // NOTE: if there is no input for this tick, we use a best-effort fallback
                 let input = match received_input {
-                    None => connection.last_input.clone(),
+                    None => match strategy {
+                        MissingInputStrategy::RepeatLast => connection.last_input.clone(),
+                        MissingInputStrategy::Default(input) => Some(input.clone()),
+                        MissingInputStrategy::Pause => None,
+                    },
                     Some(i) => {
                         connection.last_input = Some(i.clone());
                         Some(i)
@@ -198,25 +615,70 @@ impl<P: Protocol> ConnectionManager<P> {
                 // TODO: We should also let the user know that it needs to send inputs a bit earlier so that
                 //  we have more of a buffer. Send a SyncMessage to tell the user to speed up?
                 //  See Overwatch GDC video
-                (input, *client_id)
+                // clients that never sent any input (for example spectators) are not missing inputs
+                let missing = fallback && last_received_tick.is_some();
+                if let Some(history_len) = rewind_history_len {
+                    connection.applied_inputs.push_back((tick, input.clone()));
+                    while connection.applied_inputs.len() > history_len {
+                        connection.applied_inputs.pop_front();
+                    }
+                }
+                (input, *client_id, missing, sub_tick)
             })
     }
 
+    /// Replace the inputs that were applied for the client with its late inputs, so that they are
+    /// used when the server re-simulates these ticks (see [`LateInputConfig::rewind`])
+    pub(crate) fn apply_late_inputs(&mut self, client_id: ClientId, inputs: &[(Tick, P::Input)]) {
+        let Some(connection) = self.connections.get_mut(&client_id) else {
+            return;
+        };
+        for (tick, input) in inputs {
+            if let Some((_, applied)) = connection
+                .applied_inputs
+                .iter_mut()
+                .find(|(applied_tick, _)| applied_tick == tick)
+            {
+                *applied = Some(input.clone());
+            }
+        }
+    }
+
+    /// Input of the client to re-apply for `tick` when the server re-simulates past ticks
+    pub(crate) fn replay_input(&self, client_id: ClientId, tick: Tick) -> Option<P::Input> {
+        self.connections
+            .get(&client_id)?
+            .applied_inputs
+            .iter()
+            .find(|(applied_tick, _)| *applied_tick == tick)
+            .and_then(|(_, input)| input.clone())
+    }
+
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
         channel: ChannelKind,
         target: NetworkTarget,
     ) -> Result<()> {
-        // Rc is fine because the copies are all created on the same thread
-        // let message = Rc::new(message);
+        let message = ServerMessage::<P>::Message(message);
+        // serialize the message only once: all the clients share the same (reference-counted) bytes
+        self.writer.start_write();
+        message.encode(&mut self.writer)?;
+        let message_bytes = Bytes::from(self.writer.finish_write().to_vec());
+        // TODO: i know channel names never change so i should be able to get them as static
+        let channel_name = self.channel_registry.name(&channel).unwrap_or("unknown");
         self.connections
             .iter_mut()
             .filter(|(id, _)| target.should_send_to(id))
-            // TODO: here we should avoid the clone, it's the same message.. just use Rc?
-            //  need to update the ServerMessage enum to use Rc<P::Message>!
-            //  or serialize first, so we can use Bytes? where would the buffer be?
-            .try_for_each(|(_, c)| c.buffer_message(message.clone(), channel))
+            .try_for_each(|(_, c)| {
+                message.emit_send_logs(channel_name);
+                c.message_manager.buffer_send_bytes(
+                    message_bytes.clone(),
+                    channel,
+                    DEFAULT_MESSAGE_PRIORITY,
+                )?;
+                Ok(())
+            })
     }
 
     /// Queues up a message to be sent to all clients matching the specific [`NetworkTarget`]
@@ -245,6 +707,58 @@ impl<P: Protocol> ConnectionManager<P> {
         self.send_message_to_target::<C, M>(message, NetworkTarget::Only(vec![client_id]))
     }
 
+    /// Relay the inputs received from each client since the last send to the other clients,
+    /// according to the [`InputBroadcastConfig`]
+    pub(crate) fn broadcast_inputs(&mut self, room_manager: Option<&RoomManager>) -> Result<()> {
+        let Some(config) = &self.input_broadcast else {
+            return Ok(());
+        };
+        let channel = ChannelKind::of::<InputBroadcastChannel>();
+        let inputs = self
+            .connections
+            .iter_mut()
+            .flat_map(|(client_id, connection)| {
+                let client_id = *client_id;
+                let inputs = std::mem::take(&mut connection.inputs_to_broadcast)
+                    .into_iter()
+                    .map(move |message| ServerMessage::<P>::Inputs(client_id, message));
+                let additional_inputs =
+                    std::mem::take(&mut connection.additional_inputs_to_broadcast)
+                        .into_iter()
+                        .map(move |message| {
+                            ServerMessage::<P>::AdditionalInputs(client_id, message)
+                        });
+                inputs
+                    .chain(additional_inputs)
+                    .map(move |message| (client_id, message))
+            })
+            .collect::<Vec<_>>();
+        for (sender, message) in inputs {
+            // serialize the message only once: all the clients share the same (reference-counted) bytes
+            self.writer.start_write();
+            message.encode(&mut self.writer)?;
+            let message_bytes = Bytes::from(self.writer.finish_write().to_vec());
+            self.connections
+                .iter_mut()
+                .filter(|(id, _)| {
+                    **id != sender
+                        && config.target.should_send_to(id)
+                        && (!config.same_room_only
+                            || room_manager.is_some_and(|rooms| rooms.share_room(sender, **id)))
+                })
+                .try_for_each(|(_, c)| {
+                    message.emit_send_logs("InputBroadcastChannel");
+                    c.message_manager.buffer_send_bytes(
+                        message_bytes.clone(),
+                        channel,
+                        DEFAULT_MESSAGE_PRIORITY,
+                    )?;
+                    Ok::<(), anyhow::Error>(())
+                })?;
+        }
+        Ok(())
+    }
+
     /// Buffer all the replication messages to send.
     /// Keep track of the bevy Change Tick: when a message is acked, we know that we only have to send
     /// the updates since that Change Tick
@@ -259,6 +773,64 @@ impl<P: Protocol> ConnectionManager<P> {
             .try_for_each(move |c| c.buffer_replication_messages(tick, bevy_tick))
     }
 
+    /// Returns true if the input message sent by the client at `tick` is within the [`InputRateLimitConfig`]
+    pub(crate) fn accept_input_message(&mut self, tick: Tick) -> bool {
+        self.input_rate_limiter
+            .as_mut()
+            .map_or(true, |limiter| limiter.accept(tick))
+    }
+
+    /// Buffer the inputs of an input message received from the client.
+    /// The inputs for ticks that were already simulated are kept in `late_inputs`
+    pub(crate) fn receive_input_message(
+        &mut self,
+        input_message: InputMessage<P::Input>,
+        validate_input: impl FnMut(Tick, P::Input) -> Option<P::Input>,
+    ) {
+        debug!("Received input message: {:?}", input_message.end_tick);
+        let end_tick = input_message.end_tick;
+        let num_ticks = input_message.num_ticks();
+        let late_inputs = self
+            .input_buffer
+            .update_from_message(input_message, validate_input);
+        if self.broadcast_inputs {
+            // relay the inputs that were buffered after validation, instead of the raw inputs of the client
+            let message = self.input_buffer.create_message(end_tick, num_ticks);
+            if !message.is_empty() {
+                self.inputs_to_broadcast.push(message);
+            }
+        }
+        let num_late = late_inputs.len() as u32;
+        self.late_inputs.extend(late_inputs);
+        if num_late > 0 {
+            self.input_metrics.num_late += num_late;
+            #[cfg(feature = "metrics")]
+            metrics::counter!("late_input").increment(num_late as u64);
+        }
+    }
+
+    /// Take the late inputs received since the last call, and return them as a [`LateInputEvent`]
+    /// if they are within the window of the [`LateInputConfig`]
+    pub(crate) fn take_late_inputs(
+        &mut self,
+        client_id: ClientId,
+        config: Option<&LateInputConfig>,
+        current_tick: Tick,
+    ) -> Option<LateInputEvent<P::Input>> {
+        let inputs = std::mem::take(&mut self.late_inputs);
+        let config = config?;
+        // only keep the inputs that are within the late window
+        let inputs: Vec<_> = inputs
+            .into_iter()
+            .filter(|(tick, _)| current_tick - *tick <= config.max_late_ticks as i16)
+            .collect();
+        if inputs.is_empty() {
+            return None;
+        }
+        debug!(?client_id, num_inputs = ?inputs.len(), "accepting late inputs");
+        Some(LateInputEvent { client_id, inputs })
+    }
+
     pub(crate) fn receive(
         &mut self,
         world: &mut World,
@@ -266,20 +838,79 @@ impl<P: Protocol> ConnectionManager<P> {
         tick_manager: &TickManager,
     ) -> Result<()> {
         let mut messages_to_rebroadcast = vec![];
+        // take the validators out of the world so that we can apply them while mutating the world
+        let validators = world.remove_resource::<ReplicationValidators<P>>();
+        let input_validator = world.remove_resource::<InputValidator<P::Input>>();
+        let mut flagged_inputs = vec![];
+        let mut rate_limited = vec![];
+        let mut late_inputs = vec![];
         // TODO: do this in parallel
         self.connections
             .iter_mut()
             .for_each(|(client_id, connection)| {
                 let _span = trace_span!("receive", ?client_id).entered();
                 // receive events on the connection
-                let events = connection.receive(world, time_manager, tick_manager);
+                let events = connection.receive(
+                    world,
+                    time_manager,
+                    tick_manager,
+                    |entity, component| match &validators {
+                        Some(validators) => validators.validate(*client_id, entity, component),
+                        None => Some(component),
+                    },
+                    |tick, input| match &input_validator {
+                        Some(validator) => {
+                            validator.validate(*client_id, tick, input, &mut flagged_inputs)
+                        }
+                        None => Some(input),
+                    },
+                );
                 // move the events from the connection to the connection manager
                 self.events.push_events(*client_id, events);
+                if let Some(limiter) = &mut connection.input_rate_limiter {
+                    let num_messages = std::mem::take(&mut limiter.num_exceeded);
+                    if num_messages > 0 {
+                        warn!(
+                            ?client_id,
+                            ?num_messages,
+                            "client exceeded the input rate limit"
+                        );
+                        connection.input_metrics.num_rate_limited += num_messages;
+                        #[cfg(feature = "metrics")]
+                        metrics::counter!("rate_limited_input").increment(num_messages as u64);
+                        rate_limited.push(InputRateLimitedEvent {
+                            client_id: *client_id,
+                            num_messages,
+                            dropped: limiter.action == InputRateLimitAction::Drop,
+                        });
+                    }
+                }
+
+                late_inputs.extend(connection.take_late_inputs(
+                    *client_id,
+                    self.late_inputs.as_ref(),
+                    tick_manager.tick(),
+                ));
 
                 // rebroadcast messages
                 messages_to_rebroadcast
                     .extend(std::mem::take(&mut connection.messages_to_rebroadcast));
             });
+        if let Some(validators) = validators {
+            world.insert_resource(validators);
+        }
+        if let Some(input_validator) = input_validator {
+            world.insert_resource(input_validator);
+        }
+        if !flagged_inputs.is_empty() {
+            world.send_event_batch(flagged_inputs);
+        }
+        if !rate_limited.is_empty() {
+            world.send_event_batch(rate_limited);
+        }
+        if !late_inputs.is_empty() {
+            world.send_event_batch(late_inputs);
+        }
         for (message, target, channel_kind) in messages_to_rebroadcast {
             self.buffer_message(message, channel_kind, target)?;
         }
@@ -300,10 +931,33 @@ pub struct Connection<P: Protocol> {
     /// Stores the last input we have received from the client.
     /// In case we are missing the client input for a tick, we will fallback to using this.
     pub(crate) last_input: Option<P::Input>,
+    pub(crate) input_metrics: InputBufferMetrics,
+    /// If true, the input messages received from the client are relayed to the other clients
+    pub(crate) broadcast_inputs: bool,
+    /// Input messages received from the client that need to be relayed to the other clients
+    pub(crate) inputs_to_broadcast: Vec<InputMessage<P::Input>>,
+    /// Input messages of the additional input types (see [`AdditionalInputPlugin`](crate::server::input::AdditionalInputPlugin))
+    /// received from the client that need to be relayed to the other clients
+    pub(crate) additional_inputs_to_broadcast: Vec<P::Message>,
+    /// Limits the number of input messages accepted from the client
+    pub(crate) input_rate_limiter: Option<InputRateLimiter>,
+    /// Inputs received from the client for ticks that the server already simulated
+    pub(crate) late_inputs: Vec<(Tick, P::Input)>,
+    /// Inputs that were applied for the last few ticks (only kept if the server rewinds for late inputs)
+    pub(crate) applied_inputs: VecDeque<(Tick, Option<P::Input>)>,
+    /// Decides if the client should speed up or slow down depending on its input buffer depth
+    pub(crate) input_nudger: Option<InputNudger>,
     // TODO: maybe don't do any replication until connection is synced?
 
     // messages that we have received that need to be rebroadcasted to other clients
     pub(crate) messages_to_rebroadcast: Vec<(P::Message, NetworkTarget, ChannelKind)>,
+    /// If true, the next replication actions will be bundled in a single baseline message
+    /// (see [`ReplicationConfig::send_baseline`])
+    pub(crate) baseline_pending: bool,
+    /// User data provided by the client when connecting (the user data of its `ConnectToken`)
+    pub(crate) user_data: Option<[u8; USER_DATA_BYTES]>,
+    /// New connect token sent by the client to extend its session, that still needs to be validated
+    pub(crate) pending_token_refresh: Option<Vec<u8>>,
 }
 
 impl<P: Protocol> Connection<P> {
@@ -311,8 +965,13 @@ impl<P: Protocol> Connection<P> {
         channel_registry: &ChannelRegistry,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        replication_config: &ReplicationConfig,
+        broadcast_inputs: bool,
+        input_rate_limit: Option<&InputRateLimitConfig>,
+        input_nudge: Option<&InputNudgeConfig>,
     ) -> Self {
         // create the message manager and the channels
+        let bandwidth_cap_enabled = packet_config.bandwidth_cap_enabled;
         let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
         // get the acks-tracker for entity updates
         let update_acks_tracker = message_manager
@@ -324,8 +983,12 @@ impl<P: Protocol> Connection<P> {
         // get a channel to get notified when a replication update message gets actually send (to update priority)
         let replication_update_send_receiver =
             message_manager.get_replication_update_send_receiver();
-        let replication_sender =
-            ReplicationSender::new(update_acks_tracker, replication_update_send_receiver);
+        let mut replication_sender = ReplicationSender::new(
+            update_acks_tracker,
+            replication_update_send_receiver,
+            bandwidth_cap_enabled,
+        );
+        replication_sender.max_spawns_per_send = replication_config.max_spawns_per_send;
         let replication_receiver = ReplicationReceiver::new();
         Self {
             message_manager,
@@ -334,43 +997,105 @@ impl<P: Protocol> Connection<P> {
             ping_manager: PingManager::new(ping_config),
             input_buffer: InputBuffer::default(),
             last_input: None,
+            input_metrics: InputBufferMetrics::default(),
+            broadcast_inputs,
+            inputs_to_broadcast: vec![],
+            additional_inputs_to_broadcast: vec![],
+            input_rate_limiter: input_rate_limit.map(InputRateLimiter::new),
+            late_inputs: vec![],
+            applied_inputs: VecDeque::new(),
+            input_nudger: input_nudge.map(InputNudger::new),
             events: ConnectionEvents::default(),
             messages_to_rebroadcast: vec![],
+            baseline_pending: replication_config.send_baseline,
+            user_data: None,
+            pending_token_refresh: None,
         }
     }
 
-    pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
+    /// Notify the client that the simulation is paused
+    pub(crate) fn send_pause(&mut self, tick: Tick) -> Result<()> {
+        let message = ServerMessage::<P>::Pause(tick);
+        message.emit_send_logs("EntityActionsChannel");
         self.message_manager
-            .update(time_manager, &self.ping_manager, tick_manager);
-        self.ping_manager.update(time_manager);
+            .buffer_send(message, ChannelKind::of::<EntityActionsChannel>())?;
+        Ok(())
     }
 
-    pub(crate) fn buffer_message(
-        &mut self,
-        message: P::Message,
-        channel: ChannelKind,
-    ) -> Result<()> {
-        // TODO: i know channel names never change so i should be able to get them as static
-        // TODO: just have a channel registry enum as well?
-        let channel_name = self
-            .message_manager
-            .channel_registry
-            .name(&channel)
-            .unwrap_or("unknown")
-            .to_string();
-        let message = ServerMessage::<P>::Message(message);
-        message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
+    /// Notify the client that the simulation resumed at the given tick
+    pub(crate) fn send_resume(&mut self, tick: Tick) -> Result<()> {
+        let message = ServerMessage::<P>::Resume(tick);
+        message.emit_send_logs("EntityActionsChannel");
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<EntityActionsChannel>())?;
+        Ok(())
+    }
+
+    /// Ask the client to run `offset` ticks further ahead of the server than its sync objective
+    pub(crate) fn send_time_nudge(&mut self, offset: i16) -> Result<()> {
+        let message = ServerMessage::<P>::TimeNudge(offset);
+        message.emit_send_logs("ServerControlChannel");
+        // the client must apply the nudges in the order they were sent, so that it ends up with the latest one
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<ServerControlChannel>())?;
+        Ok(())
+    }
+
+    /// Notify the client that the tick duration changes from the given tick
+    pub(crate) fn send_tick_duration(&mut self, tick: Tick, tick_duration: Duration) -> Result<()> {
+        let message = ServerMessage::<P>::TickDuration(tick, tick_duration);
+        message.emit_send_logs("ServerControlChannel");
+        // the changes must be applied in the order they were made
+        self.message_manager
+            .buffer_send(message, ChannelKind::of::<ServerControlChannel>())?;
         Ok(())
     }
 
+    pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
+        self.message_manager
+            .update(time_manager, &self.ping_manager, tick_manager);
+        self.ping_manager.update(time_manager);
+        self.replication_sender.update(time_manager);
+    }
+
     pub(crate) fn buffer_replication_messages(
         &mut self,
         tick: Tick,
         bevy_tick: BevyTick,
     ) -> Result<()> {
-        self.replication_sender
-            .finalize(tick)
+        let mut messages = self.replication_sender.finalize(tick);
+        // the first time we send replication messages to this client, bundle all the actions together
+        // so that the client can apply the initial world state atomically
+        if self.baseline_pending {
+            let (actions, updates): (Vec<_>, Vec<_>) =
+                messages.into_iter().partition(|(_, _, message_data, _)| {
+                    matches!(message_data, ReplicationMessageData::Actions(_))
+                });
+            messages = updates;
+            let baseline_priority = actions
+                .iter()
+                .fold(DEFAULT_MESSAGE_PRIORITY, |acc, (_, _, _, priority)| {
+                    acc.max(*priority)
+                });
+            let baseline = actions
+                .into_iter()
+                .map(|(_, group_id, data, _)| ReplicationMessage { group_id, data })
+                .collect::<Vec<_>>();
+            // wait until there are actions to send, so that the baseline is not wasted on an empty world
+            if !baseline.is_empty() {
+                self.baseline_pending = false;
+                let bytes = compress_baseline(&baseline)?;
+                debug!(num_groups = ?baseline.len(), num_bytes = ?bytes.len(), "Sending replication baseline");
+                let message = ServerMessage::<P>::ReplicationBaseline(bytes);
+                message.emit_send_logs("EntityActionsChannel");
+                self.message_manager.buffer_send_with_priority(
+                    message,
+                    ChannelKind::of::<EntityActionsChannel>(),
+                    baseline_priority,
+                )?;
+            }
+        }
+        messages
             .into_iter()
             .try_for_each(|(channel, group_id, message_data, priority)| {
                 let should_track_ack = matches!(message_data, ReplicationMessageData::Updates(_));
@@ -437,6 +1162,7 @@ impl<P: Protocol> Connection<P> {
                     Ok::<(), anyhow::Error>(())
                 })?;
         }
+        self.message_manager.tick_generation = tick_manager.tick_generation();
         let payloads = self.message_manager.send_packets(tick_manager.tick());
 
         // update the replication sender about which messages were actually sent, and accumulate priority
@@ -444,11 +1170,15 @@ impl<P: Protocol> Connection<P> {
         payloads
     }
 
+    /// `validate` is applied to every component insert/update received from the client before it is
+    /// applied to the World, and `validate_input` to every input received from the client before it is buffered
     pub fn receive(
         &mut self,
         world: &mut World,
         time_manager: &TimeManager,
         tick_manager: &TickManager,
+        validate: impl Fn(Entity, P::Components) -> Option<P::Components>,
+        mut validate_input: impl FnMut(Tick, P::Input) -> Option<P::Input>,
     ) -> ConnectionEvents<P> {
         let _span = trace_span!("receive").entered();
         for (channel_kind, messages) in self.message_manager.read_messages::<ClientMessage<P>>() {
@@ -464,6 +1194,12 @@ impl<P: Protocol> Connection<P> {
                 for (tick, message) in messages.into_iter() {
                     match message {
                         ClientMessage::Message(mut message, target) => {
+                            if !matches!(message.input_message_kind(), InputMessageKind::None)
+                                && !self.accept_input_message(tick)
+                            {
+                                trace!("dropping input message that exceeds the rate limit");
+                                continue;
+                            }
                             trace!(
                                 "remote entity map: {:?}",
                                 self.replication_receiver.remote_entity_map
@@ -485,9 +1221,9 @@ impl<P: Protocol> Connection<P> {
                                     self.events.push_input_message(message);
                                 }
                                 InputMessageKind::Native => {
-                                    let input_message = message.try_into().unwrap();
-                                    debug!("Received input message: {:?}", input_message.end_tick);
-                                    self.input_buffer.update_from_message(input_message);
+                                    let input_message: InputMessage<P::Input> =
+                                        message.try_into().unwrap();
+                                    self.receive_input_message(input_message, &mut validate_input);
                                 }
                                 InputMessageKind::None => {
                                     // buffer the message
@@ -513,6 +1249,21 @@ impl<P: Protocol> Connection<P> {
                                 }
                             }
                         }
+                        ClientMessage::RefreshToken(token) => {
+                            // the token is validated by the netserver that handles this client
+                            self.pending_token_refresh = Some(token);
+                        }
+                        ClientMessage::Resync => {
+                            debug!("The client restarted the sync handshake");
+                            // the client dropped its nudge: start again from a neutral nudge
+                            if let Some(nudger) = self.input_nudger.as_mut() {
+                                nudger.reset();
+                                // the reset is ordered after the nudges that are still in flight
+                                let _ = self.send_time_nudge(0).map_err(|e| {
+                                    error!("could not send the time nudge to the client: {:?}", e);
+                                });
+                            }
+                        }
                     }
                 }
             }
@@ -534,6 +1285,7 @@ impl<P: Protocol> Connection<P> {
                         replication,
                         group,
                         &mut self.events,
+                        &validate,
                     );
                 });
         }
@@ -547,9 +1299,12 @@ impl<P: Protocol> Connection<P> {
     pub fn recv_packet(&mut self, packet: Packet, tick_manager: &TickManager) -> Result<()> {
         // receive the packets, buffer them, update any sender that were waiting for their sent messages to be acked
         let tick = self.message_manager.recv_packet(packet)?;
+        // track how far ahead of the server tick the client's packets arrive
+        self.input_metrics
+            .record_tick_offset(tick - tick_manager.tick());
         // notify the replication sender that some sent messages were received
         self.replication_sender.recv_update_acks();
-        debug!("Received server packet with tick: {:?}", tick);
+        debug!("Received client packet with tick: {:?}", tick);
         Ok(())
     }
 }
@@ -603,6 +1358,11 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
             //     .or_default()
             //     .update_collect_changes_since_this_tick(system_current_tick);
             replication_sender.prepare_entity_spawn(entity, group_id);
+            replication_sender
+                .group_channels
+                .entry(group_id)
+                .or_default()
+                .dependency = replicate.replication_group.dependency();
             // if we need to do prediction/interpolation, send a marker component to indicate that to the client
             if replicate.prediction_target.should_send_to(&client_id) {
                 replication_sender.prepare_component_insert(
@@ -618,6 +1378,18 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                     P::Components::from(ShouldBeInterpolated),
                 );
             }
+            if replicate.prediction_target.should_send_to(&client_id)
+                || replicate.interpolation_target.should_send_to(&client_id)
+            {
+                let sync_mode_override = replicate.sync_mode_override();
+                if !sync_mode_override.is_empty() {
+                    replication_sender.prepare_component_insert(
+                        entity,
+                        group_id,
+                        P::Components::from(sync_mode_override),
+                    );
+                }
+            }
             // also set the priority for the group when we spawn it
             self.update_priority(group_id, client_id, replicate.replication_group.priority())?;
 
@@ -685,6 +1457,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         {
             actual_target = replicate.prediction_target.clone();
         }
+        let actual_target = self.exclude_authority(entity, kind, actual_target);
 
         self.apply_replication(actual_target)
             .try_for_each(|client_id| {
@@ -716,6 +1489,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     ) -> Result<()> {
         let group_id = replicate.replication_group.group_id(Some(entity));
         debug!(?entity, ?component_kind, "Sending RemoveComponent");
+        let target = self.exclude_authority(entity, component_kind, target);
         self.apply_replication(target).try_for_each(|client_id| {
             let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
             // TODO: I don't think it's actually correct to only correct the changes since that action.
@@ -754,15 +1528,15 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         );
 
         let group_id = replicate.group_id(Some(entity));
+        let target = self.exclude_authority(entity, kind, target);
         self.apply_replication(target).try_for_each(|client_id| {
             // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
             let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
-            let collect_changes_since_this_tick = replication_sender
-                .group_channels
-                .entry(group_id)
-                .or_default()
-                .collect_changes_since_this_tick;
+            let group_channel = replication_sender.group_channels.entry(group_id).or_default();
+            group_channel.send_interval = replicate.send_interval;
+            let collect_changes_since_this_tick = group_channel.collect_changes_since_this_tick;
             // send the update for all changes newer than the last ack bevy tick for the group
+            // (groups in snapshot mode always send the full state)
             debug!(
                 ?kind,
                 change_tick = ?component_change_tick,
@@ -770,9 +1544,11 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                 "prepare entity update changed check (we want the component-change-tick to be higher than collect-changes-since-this-tick)"
             );
 
-            if collect_changes_since_this_tick.map_or(true, |tick| {
-                component_change_tick.is_newer_than(tick, system_current_tick)
-            }) {
+            if replicate.replication_group.is_snapshot()
+                || collect_changes_since_this_tick.map_or(true, |tick| {
+                    component_change_tick.is_newer_than(tick, system_current_tick)
+                })
+            {
                 trace!(
                     change_tick = ?component_change_tick,
                     ?collect_changes_since_this_tick,
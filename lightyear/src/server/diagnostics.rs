@@ -0,0 +1,51 @@
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::diagnostic::Diagnostics;
+use bevy::prelude::{Real, Res, ResMut, Time};
+
+use crate::channel::diagnostics::{ChannelDiagnosticPaths, ChannelDiagnosticsPlugin};
+use crate::prelude::Protocol;
+use crate::server::connection::ConnectionManager;
+
+pub struct ServerDiagnosticsPlugin<P> {
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P> Default for ServerDiagnosticsPlugin<P> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+/// The channel diagnostics are summed over all connected clients
+fn channel_diagnostics_system<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    paths: Res<ChannelDiagnosticPaths>,
+    time: Res<Time<Real>>,
+    mut diagnostics: Diagnostics,
+) {
+    let connection_manager = connection_manager.as_mut();
+    ChannelDiagnosticsPlugin::update_diagnostics(
+        connection_manager.channel_registry.names(),
+        connection_manager
+            .connections
+            .values_mut()
+            .flat_map(|connection| connection.message_manager.channel_stats()),
+        &paths,
+        &time,
+        &mut diagnostics,
+    );
+}
+
+impl<P: Protocol> Plugin for ServerDiagnosticsPlugin<P> {
+    fn build(&self, app: &mut App) {
+        let channel_registry = app
+            .world
+            .resource::<ConnectionManager<P>>()
+            .channel_registry
+            .clone();
+        app.add_plugins(ChannelDiagnosticsPlugin::new(channel_registry));
+        app.add_systems(PostUpdate, channel_diagnostics_system::<P>);
+    }
+}
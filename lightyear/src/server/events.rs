@@ -2,16 +2,18 @@
 use bevy::ecs::entity::EntityHash;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
-use tracing::trace;
+use tracing::{error, trace};
 
 use crate::_reexport::{
     FromType, IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
     ServerMarker,
 };
 use crate::connection::id::ClientId;
+use crate::connection::netcode::USER_DATA_BYTES;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
 use crate::packet::message::Message;
+use crate::prelude::Channel;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
 use crate::server::networking::clear_events;
@@ -20,7 +22,9 @@ use crate::shared::events::connection::IterInputMessageEvent;
 use crate::shared::events::connection::{
     ConnectionEvents, IterEntityDespawnEvent, IterEntitySpawnEvent, IterMessageEvent,
 };
+use crate::shared::events::network::{emit_network_events, ReceivedNetworkEvents};
 use crate::shared::events::plugin::EventsPlugin;
+use crate::shared::replication::components::NetworkTarget;
 use crate::shared::sets::InternalMainSet;
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
@@ -41,6 +45,10 @@ impl<P: Protocol> Default for ServerEventsPlugin<P> {
 impl<P: Protocol> Plugin for ServerEventsPlugin<P> {
     fn build(&self, app: &mut App) {
         app
+            // EVENTS
+            .add_event::<ConnectEvent>()
+            .add_event::<DisconnectEvent>()
+            .add_event::<TokenRefreshEvent>()
             // PLUGIN
             .add_plugins(EventsPlugin::<P, ClientId>::default())
             // SYSTEM_SET
@@ -48,6 +56,80 @@ impl<P: Protocol> Plugin for ServerEventsPlugin<P> {
     }
 }
 
+/// Plugin that replicates the bevy [`Event`] `E` between the server and the clients.
+///
+/// `E` must be a message of the protocol. Any event written on the server with an `EventWriter<E>` is sent
+/// to the clients in `target` on the channel `C`, and any `E` message received from a client is emitted as an event `E`.
+/// Events received from clients are not sent back to the clients.
+pub struct NetworkEventPlugin<P: Protocol, C: Channel, E: Message + Event + Clone> {
+    target: NetworkTarget,
+    _marker: std::marker::PhantomData<(P, C, E)>,
+}
+
+impl<P: Protocol, C: Channel, E: Message + Event + Clone> NetworkEventPlugin<P, C, E> {
+    /// Send the events to the clients in `target`
+    pub fn new(target: NetworkTarget) -> Self {
+        Self {
+            target,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, C: Channel, E: Message + Event + Clone> Default for NetworkEventPlugin<P, C, E> {
+    fn default() -> Self {
+        Self::new(NetworkTarget::All)
+    }
+}
+
+/// Clients that the network event `E` is sent to
+#[derive(Resource)]
+struct NetworkEventTarget<E> {
+    target: NetworkTarget,
+    _marker: std::marker::PhantomData<E>,
+}
+
+impl<P: Protocol, C: Channel, E: Message + Event + Clone> Plugin for NetworkEventPlugin<P, C, E>
+where
+    P::Message: From<E>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<E>()
+            .init_resource::<ReceivedNetworkEvents<E>>()
+            .insert_resource(NetworkEventTarget::<E> {
+                target: self.target.clone(),
+                _marker: std::marker::PhantomData,
+            })
+            .add_systems(
+                PreUpdate,
+                emit_network_events::<E, ClientId>.after(InternalMainSet::<ServerMarker>::Receive),
+            )
+            .add_systems(
+                PostUpdate,
+                send_network_events::<P, C, E>.before(InternalMainSet::<ServerMarker>::Send),
+            );
+    }
+}
+
+/// Send the events written on the server to the clients
+fn send_network_events<P: Protocol, C: Channel, E: Message + Event + Clone>(
+    mut events: EventReader<E>,
+    mut received: ResMut<ReceivedNetworkEvents<E>>,
+    target: Res<NetworkEventTarget<E>>,
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+) where
+    P::Message: From<E>,
+{
+    for (event, id) in events.read_with_id() {
+        if received.take(id.id) {
+            continue;
+        }
+        let _ = connection_manager
+            .send_message_to_target::<C, E>(event.clone(), target.target.clone())
+            .map_err(|e| error!("could not send network event: {:?}", e));
+    }
+}
+
 #[derive(Debug)]
 pub struct ServerEvents<P: Protocol> {
     pub connections: Vec<ClientId>,
@@ -292,9 +374,65 @@ impl<P: Protocol> IterComponentInsertEvent<P, ClientId> for ServerEvents<P> {
 }
 
 /// Bevy [`Event`] emitted on the server on the frame where a client is connected
-pub type ConnectEvent = crate::shared::events::components::ConnectEvent<ClientId>;
+///
+/// Contains the user data that the client provided when connecting (the user data of its `ConnectToken`)
+#[derive(Event)]
+pub struct ConnectEvent {
+    client_id: ClientId,
+    user_data: Option<[u8; USER_DATA_BYTES]>,
+}
+
+impl ConnectEvent {
+    pub fn new(client_id: ClientId) -> Self {
+        Self {
+            client_id,
+            user_data: None,
+        }
+    }
+    pub fn with_user_data(mut self, user_data: [u8; USER_DATA_BYTES]) -> Self {
+        self.user_data = Some(user_data);
+        self
+    }
+    /// The id of the client that connected
+    pub fn context(&self) -> &ClientId {
+        &self.client_id
+    }
+    pub fn client_id(&self) -> ClientId {
+        self.client_id
+    }
+    pub fn user_data(&self) -> Option<&[u8; USER_DATA_BYTES]> {
+        self.user_data.as_ref()
+    }
+}
+
+/// Bevy [`Event`] emitted on the server when a connected client extended its session with a new `ConnectToken`
+///
+/// Contains the user data of the new token, which replaces the one provided when connecting
+#[derive(Event)]
+pub struct TokenRefreshEvent {
+    client_id: ClientId,
+    user_data: Option<[u8; USER_DATA_BYTES]>,
+}
+
+impl TokenRefreshEvent {
+    pub fn new(client_id: ClientId, user_data: Option<[u8; USER_DATA_BYTES]>) -> Self {
+        Self {
+            client_id,
+            user_data,
+        }
+    }
+    pub fn client_id(&self) -> ClientId {
+        self.client_id
+    }
+    pub fn user_data(&self) -> Option<&[u8; USER_DATA_BYTES]> {
+        self.user_data.as_ref()
+    }
+}
+
 /// Bevy [`Event`] emitted on the server on the frame where a client is disconnected
 pub type DisconnectEvent = crate::shared::events::components::DisconnectEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a packet sent to a client is considered lost
+pub type PacketLostEvent = crate::shared::events::components::PacketLostEvent<ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where an input message from a client is received
 pub type InputEvent<I> = crate::shared::events::components::InputEvent<I, ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where a EntitySpawn replication message is received
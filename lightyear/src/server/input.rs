@@ -1,13 +1,24 @@
 //! Handles client-generated inputs
 use bevy::prelude::{
-    App, EventReader, EventWriter, FixedPostUpdate, FixedPreUpdate, IntoSystemConfigs, Plugin, Res,
-    ResMut, SystemSet,
+    not, App, Event, EventReader, EventWriter, Events, FixedPostUpdate, FixedPreUpdate,
+    IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, PreUpdate, Res, ResMut, Resource,
+    SystemSet,
 };
+use bevy::utils::HashMap;
+use tracing::{error, trace};
 
-use crate::prelude::{TickManager, UserAction};
+use crate::_reexport::ServerMarker;
+use crate::connection::id::ClientId;
+use crate::inputs::native::input_buffer::{InputBuffer, InputMessage};
+use crate::inputs::native::InputMode;
+use crate::prelude::{NetworkTarget, Tick, TickManager, UserAction};
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
-use crate::server::events::InputEvent;
+use crate::server::events::{DisconnectEvent, InputEvent, MessageEvent};
+use crate::server::rewind::{is_rewinding, rewind_late_inputs, InputRewind};
+use crate::server::room::RoomManager;
+use crate::shared::sets::InternalMainSet;
+use crate::shared::tick_manager::{is_first_substep, is_last_substep};
 
 // - ClientInputs:
 // - inputs will be sent via a special message
@@ -38,6 +49,554 @@ impl<P: Protocol> Default for InputPlugin<P> {
     }
 }
 
+/// What the server does when the input of a client for the current tick is missing
+/// (for example because the input packet was lost, or arrived too late)
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub enum MissingInputStrategy<A> {
+    /// Reuse the last input received from the client
+    RepeatLast,
+    /// Use this input instead (for example an 'idle' input)
+    Default(A),
+    /// Do not provide any input: the [`InputEvent`] for the client will not contain an input,
+    /// so that the entity controlled by the client can be paused
+    Pause,
+}
+
+impl<A> Default for MissingInputStrategy<A> {
+    fn default() -> Self {
+        Self::RepeatLast
+    }
+}
+
+/// Event emitted when the input of a client for the current tick is missing, and was replaced
+/// according to the [`MissingInputStrategy`]
+///
+/// It is only emitted for clients that have already sent at least one input.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct MissingInputEvent {
+    pub client_id: ClientId,
+    pub tick: Tick,
+}
+
+/// Result of the validation of an input received from a client
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputVerdict<A> {
+    /// Buffer the input as is
+    Accept,
+    /// Buffer this value instead of the received input (for example a clamped version of the input)
+    Clamp(A),
+    /// Discard the input: the server buffers the last valid input of the client for that tick instead.
+    /// If the client had no valid input, the input is missing for that tick (see [`MissingInputStrategy`]).
+    ///
+    /// For leafwing inputs, the rejected `ActionDiff` is dropped, so the action keeps its last valid state.
+    Reject,
+    /// Buffer the input, but emit an [`InputFlaggedEvent`] so that the game can react to it
+    Flag,
+}
+
+/// Validation callback that runs on every input received from a client, before it is buffered.
+///
+/// This is a good place to implement anti-cheat or sanity checks on the inputs, since the inputs
+/// haven't had any effect on the World yet.
+/// ```rust,ignore
+/// app.insert_resource(InputValidator::<MyInput>::new(|client_id, tick, input| match input {
+///     MyInput::Move(speed) if *speed > MAX_SPEED => InputVerdict::Clamp(MyInput::Move(MAX_SPEED)),
+///     _ => InputVerdict::Accept,
+/// }));
+/// ```
+#[derive(Resource)]
+pub struct InputValidator<A> {
+    validator: Box<dyn Fn(ClientId, Tick, &A) -> InputVerdict<A> + Send + Sync>,
+}
+
+impl<A: Clone> InputValidator<A> {
+    pub fn new(
+        validator: impl Fn(ClientId, Tick, &A) -> InputVerdict<A> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            validator: Box::new(validator),
+        }
+    }
+
+    /// Run the validation callback on the input.
+    /// Returns the input that should be buffered, or None if the input was rejected.
+    pub(crate) fn validate(
+        &self,
+        client_id: ClientId,
+        tick: Tick,
+        input: A,
+        flagged: &mut Vec<InputFlaggedEvent<A>>,
+    ) -> Option<A> {
+        match (self.validator)(client_id, tick, &input) {
+            InputVerdict::Accept => Some(input),
+            InputVerdict::Clamp(input) => Some(input),
+            InputVerdict::Reject => None,
+            InputVerdict::Flag => {
+                flagged.push(InputFlaggedEvent {
+                    client_id,
+                    tick,
+                    input: input.clone(),
+                });
+                Some(input)
+            }
+        }
+    }
+}
+
+/// Configuration to relay the inputs of each client to the other clients.
+///
+/// This is useful for deterministic or client-side simulations, where each client needs the inputs of the other
+/// clients instead of (or on top of) the replicated state. The inputs are sent on the
+/// [`InputBroadcastChannel`](crate::channel::builder::InputBroadcastChannel), and can be read on the client via
+/// [`ConnectionManager::remote_input`](crate::client::connection::ConnectionManager::remote_input).
+/// The inputs of the types added with [`AdditionalInputPlugin`] are read via
+/// [`RemoteInputs`](crate::client::input::RemoteInputs).
+///
+/// NOTE: only the inputs accepted by the rate limit and the [`InputValidator`] are relayed, with the
+/// modifications of the validator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputBroadcastConfig {
+    /// The clients that should receive the inputs. A client never receives its own inputs
+    pub target: NetworkTarget,
+    /// If true, the inputs of a client are only relayed to the clients that share a [`Room`](crate::server::room::Room) with it
+    pub same_room_only: bool,
+}
+
+impl Default for InputBroadcastConfig {
+    fn default() -> Self {
+        Self {
+            target: NetworkTarget::All,
+            same_room_only: false,
+        }
+    }
+}
+
+impl InputBroadcastConfig {
+    pub fn with_target(mut self, target: NetworkTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_same_room_only(mut self, same_room_only: bool) -> Self {
+        self.same_room_only = same_room_only;
+        self
+    }
+}
+
+/// What the server does with the input messages of a client that exceed the [`InputRateLimitConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputRateLimitAction {
+    /// Discard the excess input messages
+    #[default]
+    Drop,
+    /// Accept the excess input messages, but emit an [`InputRateLimitedEvent`]
+    Flag,
+}
+
+/// Accept the inputs that arrive up to `max_late_ticks` ticks after the server already simulated their tick.
+///
+/// By default, the inputs that arrive after their tick was simulated are dropped (the server already
+/// used a fallback input, see [`MissingInputStrategy`]). With this config, they are emitted as
+/// [`LateInputEvent`]s instead. This is useful for casual games that tolerate minor corrections.
+///
+/// If [`rewind`](Self::rewind) is disabled, it is up to the game to handle the [`LateInputEvent`]s.
+/// If it is enabled, the server rewinds the entities controlled by the client and re-applies the inputs
+/// up to the current tick (see [`InputRewindPlugin`](crate::server::rewind::InputRewindPlugin)).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LateInputConfig {
+    /// Maximum number of ticks an input can be late
+    pub max_late_ticks: u16,
+    /// If true, the server re-simulates the ticks of the late inputs for the entities controlled by the client:
+    /// - the components registered with [`InputRewindPlugin`](crate::server::rewind::InputRewindPlugin)
+    ///   of the entities with a [`ControlledBy`](crate::prelude::ControlledBy) for the client are restored from
+    ///   their [`LagCompensationHistory`](crate::server::lag_compensation::LagCompensationHistory) to the
+    ///   tick before the first late input
+    /// - the [`FixedMain`](bevy::app::FixedMain) schedule is re-run up to the current tick, with the late
+    ///   inputs of the client in the [`InputEvent`]s
+    ///
+    /// The other replicated entities are marked with [`ExcludedFromRewind`](crate::server::rewind::ExcludedFromRewind)
+    /// during the re-simulation: the game systems should skip them.
+    ///
+    /// Only the [`Protocol::Input`] inputs are re-applied. The lag compensation history must cover at least
+    /// `max_late_ticks + 1` ticks.
+    pub rewind: bool,
+}
+
+impl Default for LateInputConfig {
+    fn default() -> Self {
+        Self {
+            max_late_ticks: 5,
+            rewind: false,
+        }
+    }
+}
+
+impl LateInputConfig {
+    pub fn with_max_late_ticks(mut self, max_late_ticks: u16) -> Self {
+        self.max_late_ticks = max_late_ticks;
+        self
+    }
+
+    pub fn with_rewind(mut self, rewind: bool) -> Self {
+        self.rewind = rewind;
+        self
+    }
+}
+
+/// Event emitted when inputs of a client arrived after the server already simulated their ticks
+/// (see [`LateInputConfig`])
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct LateInputEvent<A> {
+    pub client_id: ClientId,
+    /// The late inputs, ordered by tick
+    pub inputs: Vec<(Tick, A)>,
+}
+
+impl<A> LateInputEvent<A> {
+    /// Earliest tick that needs to be re-simulated
+    pub fn start_tick(&self) -> Option<Tick> {
+        self.inputs.first().map(|(tick, _)| *tick)
+    }
+}
+
+/// Server-side cap on the number of input messages accepted from each client.
+///
+/// Each client can send on average one input message per tick, with bursts of up to `burst` messages
+/// (to absorb jitter or packets that arrive together). This protects the server against clients that spam
+/// input messages.
+///
+/// A client that runs at a higher frame rate than the tick rate can send a few messages for the same tick
+/// (up to 4) for a single token; any additional message for that tick
+/// consumes another token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputRateLimitConfig {
+    /// Maximum number of input messages that can be accepted at once
+    pub burst: u32,
+    /// What to do with the input messages that exceed the limit
+    pub action: InputRateLimitAction,
+}
+
+impl Default for InputRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            burst: 5,
+            action: InputRateLimitAction::default(),
+        }
+    }
+}
+
+impl InputRateLimitConfig {
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    pub fn with_action(mut self, action: InputRateLimitAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+/// Token bucket that limits the number of input messages accepted from a client.
+/// One token is added every tick, up to `burst` tokens
+#[derive(Debug)]
+pub(crate) struct InputRateLimiter {
+    tokens: u32,
+    burst: u32,
+    /// Client tick of the last input message that was accepted
+    last_tick: Option<Tick>,
+    /// Number of messages that were accepted for `last_tick` with the last consumed token
+    messages_for_token: u32,
+    pub(crate) action: InputRateLimitAction,
+    /// Number of input messages that exceeded the limit since the last [`InputRateLimitedEvent`]
+    pub(crate) num_exceeded: u32,
+}
+
+impl InputRateLimiter {
+    /// Number of messages for the same client tick that are accepted for a single token
+    pub(crate) const MESSAGES_PER_TOKEN: u32 = 4;
+
+    pub(crate) fn new(config: &InputRateLimitConfig) -> Self {
+        Self {
+            tokens: config.burst,
+            burst: config.burst,
+            last_tick: None,
+            messages_for_token: 0,
+            action: config.action,
+            num_exceeded: 0,
+        }
+    }
+
+    /// Add the token for the new tick
+    pub(crate) fn refill(&mut self) {
+        self.tokens = (self.tokens + 1).min(self.burst);
+    }
+
+    /// Returns true if the input message sent by the client at `tick` should be accepted.
+    ///
+    /// A token covers up to [`Self::MESSAGES_PER_TOKEN`] messages sent for the same tick (they only contain
+    /// redundant inputs, which are deduplicated by the input buffer); every other message consumes a token.
+    pub(crate) fn accept(&mut self, tick: Tick) -> bool {
+        if self.last_tick == Some(tick) && self.messages_for_token < Self::MESSAGES_PER_TOKEN {
+            self.messages_for_token += 1;
+            return true;
+        }
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            self.last_tick = Some(tick);
+            self.messages_for_token = 1;
+            return true;
+        }
+        self.num_exceeded += 1;
+        self.action == InputRateLimitAction::Flag
+    }
+}
+
+/// Event emitted when a client sent more input messages than allowed by the [`InputRateLimitConfig`]
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct InputRateLimitedEvent {
+    pub client_id: ClientId,
+    /// Number of input messages that exceeded the limit since the last event
+    pub num_messages: u32,
+    /// True if the messages were dropped, false if they were only flagged
+    pub dropped: bool,
+}
+
+/// Config to ask the clients to speed up or slow down slightly depending on the health of their input buffer on the server.
+///
+/// If the input buffer of a client is consistently too shallow, its inputs risk arriving too late: the server
+/// asks the client to run one more tick ahead of the server, so that its inputs arrive earlier. If the buffer is
+/// consistently too deep, the inputs are applied later than necessary: the server asks the client to run one tick
+/// less ahead. The offset is added to the objective of the client's sync, which then speeds up or slows down
+/// the client time as usual.
+///
+/// The clients that did not send any input during the last `window` ticks are not nudged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputNudgeConfig {
+    /// Number of ticks over which the buffer depth is averaged before deciding to nudge the client
+    pub window: u16,
+    /// The client is asked to run further ahead if the average buffer depth (in ticks) is below this value
+    pub min_buffer_depth: f32,
+    /// The client is asked to run less ahead if the average buffer depth (in ticks) is above this value
+    pub max_buffer_depth: f32,
+    /// Maximum offset (in ticks) that can be added to or removed from the client's objective
+    pub max_nudge_ticks: u16,
+}
+
+impl Default for InputNudgeConfig {
+    fn default() -> Self {
+        Self {
+            window: 60,
+            min_buffer_depth: 1.0,
+            max_buffer_depth: 4.0,
+            max_nudge_ticks: 5,
+        }
+    }
+}
+
+impl InputNudgeConfig {
+    pub fn with_window(mut self, window: u16) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_buffer_depth(mut self, min_buffer_depth: f32, max_buffer_depth: f32) -> Self {
+        self.min_buffer_depth = min_buffer_depth;
+        self.max_buffer_depth = max_buffer_depth;
+        self
+    }
+
+    pub fn with_max_nudge_ticks(mut self, max_nudge_ticks: u16) -> Self {
+        self.max_nudge_ticks = max_nudge_ticks;
+        self
+    }
+}
+
+/// Tracks the input buffer depth of a client to decide whether the client should be nudged
+#[derive(Debug)]
+pub(crate) struct InputNudger {
+    config: InputNudgeConfig,
+    /// Sum of the buffer depths over the current window
+    depth_sum: usize,
+    num_ticks: u16,
+    /// Offset (in ticks) that was last requested from the client
+    offset: i16,
+}
+
+impl InputNudger {
+    pub(crate) fn new(config: &InputNudgeConfig) -> Self {
+        Self {
+            config: config.clone(),
+            depth_sum: 0,
+            num_ticks: 0,
+            offset: 0,
+        }
+    }
+
+    /// Returns true if the client sent inputs recently enough to be nudged
+    pub(crate) fn is_active(&self, current_tick: Tick, last_received_tick: Option<Tick>) -> bool {
+        last_received_tick.is_some_and(|tick| current_tick - tick <= self.config.window as i16)
+    }
+
+    /// Forget the offset and the buffer depths recorded so far (for example after the client resynced)
+    pub(crate) fn reset(&mut self) {
+        self.depth_sum = 0;
+        self.num_ticks = 0;
+        self.offset = 0;
+    }
+
+    /// Record the buffer depth for the current tick.
+    /// Returns the offset (in ticks) to send to the client if it changed
+    pub(crate) fn update(&mut self, buffer_depth: usize) -> Option<i16> {
+        self.depth_sum += buffer_depth;
+        self.num_ticks += 1;
+        if self.num_ticks < self.config.window.max(1) {
+            return None;
+        }
+        let average_depth = self.depth_sum as f32 / self.num_ticks as f32;
+        self.depth_sum = 0;
+        self.num_ticks = 0;
+        let max_offset = self.config.max_nudge_ticks as i16;
+        let offset = if average_depth < self.config.min_buffer_depth {
+            (self.offset + 1).min(max_offset)
+        } else if average_depth > self.config.max_buffer_depth {
+            (self.offset - 1).max(-max_offset)
+        } else {
+            self.offset
+        };
+        if offset == self.offset {
+            return None;
+        }
+        self.offset = offset;
+        Some(offset)
+    }
+}
+
+/// Statistics about the inputs received from a client, to tune the input delay and the send rates
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputBufferMetrics {
+    /// Number of ticks for which the server read the input of the client
+    pub num_ticks: u32,
+    /// Number of ticks where the input of the client was missing, so the server reused the last input
+    pub num_missing: u32,
+    /// Number of inputs that arrived after the server already processed their tick
+    pub num_late: u32,
+    /// Number of ticks of inputs that are buffered ahead of the current server tick
+    pub buffer_depth: usize,
+    /// Number of input messages that exceeded the [`InputRateLimitConfig`]
+    pub num_rate_limited: u32,
+    /// Difference between the client tick of the latest packet received from the client and the server tick
+    /// when the packet was received.
+    ///
+    /// This is how many ticks ahead of the server the client's inputs arrive: a client that cannot keep up
+    /// has a small or negative offset.
+    pub tick_offset: Option<i16>,
+    /// Exponential moving average of the [`tick_offset`](Self::tick_offset)
+    pub average_tick_offset: f32,
+}
+
+impl InputBufferMetrics {
+    /// Weight of the latest measurement in the moving average of the tick offset
+    const TICK_OFFSET_SMOOTHING: f32 = 0.1;
+
+    pub(crate) fn record_tick_offset(&mut self, tick_offset: i16) {
+        self.average_tick_offset = match self.tick_offset {
+            None => tick_offset as f32,
+            Some(_) => {
+                self.average_tick_offset * (1.0 - Self::TICK_OFFSET_SMOOTHING)
+                    + tick_offset as f32 * Self::TICK_OFFSET_SMOOTHING
+            }
+        };
+        self.tick_offset = Some(tick_offset);
+    }
+
+    /// Fraction of the ticks where the input of the client was missing
+    pub fn missing_ratio(&self) -> f32 {
+        if self.num_ticks == 0 {
+            return 0.0;
+        }
+        self.num_missing as f32 / self.num_ticks as f32
+    }
+}
+
+/// Event emitted when the [`InputValidator`] flagged an input received from a client
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct InputFlaggedEvent<A> {
+    pub client_id: ClientId,
+    pub tick: Tick,
+    pub input: A,
+}
+
+/// Plugin to receive an additional input type `A` from the clients, on top of the [`Protocol::Input`] type.
+///
+/// The inputs are emitted as [`InputEvent<A>`] every tick, according to the [`InputMode`]:
+/// - [`InputMode::Continuous`]: an event is emitted for every client that sent inputs of type `A`; if the input
+///   for the tick is missing, the last input of the client is reused
+/// - [`InputMode::Sparse`]: an event is only emitted when a client issued a command for the tick
+///
+/// The input messages are subject to the same [`InputRateLimitConfig`] and [`InputBroadcastConfig`] as the
+/// [`Protocol::Input`] messages, and the inputs can be checked with an [`InputValidator<A>`].
+///
+/// See the client [`AdditionalInputPlugin`](crate::client::input::AdditionalInputPlugin) for more details.
+pub struct AdditionalInputPlugin<P, A> {
+    mode: InputMode,
+    _marker: std::marker::PhantomData<(P, A)>,
+}
+
+impl<P, A> AdditionalInputPlugin<P, A> {
+    pub fn new(mode: InputMode) -> Self {
+        Self {
+            mode,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Inputs of type `A` received from each client (see [`AdditionalInputPlugin`])
+#[derive(Resource, Debug)]
+pub(crate) struct AdditionalInputBuffers<A: UserAction> {
+    mode: InputMode,
+    /// Input buffer of each client, along with the last input received from the client
+    buffers: HashMap<ClientId, (InputBuffer<A>, Option<A>)>,
+}
+
+impl<A: UserAction> AdditionalInputBuffers<A> {
+    fn new(mode: InputMode) -> Self {
+        Self {
+            mode,
+            buffers: HashMap::default(),
+        }
+    }
+}
+
+impl<P: Protocol, A: UserAction> Plugin for AdditionalInputPlugin<P, A>
+where
+    P::Message: From<InputMessage<A>>,
+{
+    fn build(&self, app: &mut App) {
+        // EVENTS
+        app.add_event::<InputEvent<A>>();
+        app.add_event::<InputFlaggedEvent<A>>();
+        // RESOURCES
+        app.insert_resource(AdditionalInputBuffers::<A>::new(self.mode));
+        // SYSTEMS
+        app.add_systems(
+            PreUpdate,
+            receive_additional_inputs::<P, A>.after(InternalMainSet::<ServerMarker>::Receive),
+        );
+        app.add_systems(
+            FixedPreUpdate,
+            write_additional_input_events::<A>
+                .in_set(InputSystemSet::WriteInputEvents)
+                .run_if(not(is_rewinding)),
+        );
+        app.add_systems(
+            FixedPostUpdate,
+            clear_input_events::<A>.in_set(InputSystemSet::ClearInputEvents),
+        );
+    }
+}
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum InputSystemSet {
     /// FixedUpdate system to get any inputs from the client. This should be run before the game/physics logic
@@ -50,10 +609,29 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
     fn build(&self, app: &mut App) {
         // EVENTS
         app.add_event::<InputEvent<P::Input>>();
+        app.add_event::<InputFlaggedEvent<P::Input>>();
+        app.add_event::<MissingInputEvent>();
+        app.add_event::<InputRateLimitedEvent>();
+        app.add_event::<LateInputEvent<P::Input>>();
+        // RESOURCES
+        app.init_resource::<MissingInputStrategy<P::Input>>();
         // SETS
-        app.configure_sets(FixedPreUpdate, InputSystemSet::WriteInputEvents);
-        app.configure_sets(FixedPostUpdate, InputSystemSet::ClearInputEvents);
+        // inputs are written once per tick, on the first physics step,
+        // and the input events are kept for every physics step of the tick
+        app.configure_sets(
+            FixedPreUpdate,
+            InputSystemSet::WriteInputEvents.run_if(is_first_substep),
+        );
+        app.configure_sets(
+            FixedPostUpdate,
+            InputSystemSet::ClearInputEvents.run_if(is_last_substep),
+        );
 
+        // re-simulate the ticks of the late inputs, before the ticks of this frame
+        app.add_systems(
+            PreUpdate,
+            rewind_late_inputs::<P>.after(InternalMainSet::<ServerMarker>::Receive),
+        );
         // insert the input buffer resource
         app.add_systems(
             FixedPreUpdate,
@@ -63,25 +641,520 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
             FixedPostUpdate,
             clear_input_events::<P::Input>.in_set(InputSystemSet::ClearInputEvents),
         );
+        app.add_systems(
+            PostUpdate,
+            broadcast_inputs::<P>
+                .in_set(InternalMainSet::<ServerMarker>::Send)
+                .before(InternalMainSet::<ServerMarker>::SendPackets),
+        );
     }
 }
 
 // Create a system that reads from the input buffer and returns the inputs of all clients for the current tick.
 // The only tricky part is that events are cleared every frame, but we want to clear every tick instead
 // Do it in this system because we want an input for every tick
-fn write_input_event<P: Protocol>(
+pub(crate) fn write_input_event<P: Protocol>(
     tick_manager: Res<TickManager>,
+    strategy: Res<MissingInputStrategy<P::Input>>,
+    rewind: Option<Res<InputRewind>>,
     mut connection_manager: ResMut<ConnectionManager<P>>,
     mut input_events: EventWriter<InputEvent<P::Input>>,
+    mut missing_input_events: EventWriter<MissingInputEvent>,
 ) {
     let tick = tick_manager.tick();
-    for (input, client_id) in connection_manager.pop_inputs(tick) {
-        input_events.send(InputEvent::new(input, client_id));
+    // during a rewind, only re-apply the inputs of the client whose late inputs are being applied
+    if let Some(rewind) = rewind {
+        let client_id = rewind.client_id;
+        input_events.send(InputEvent::new(
+            connection_manager.replay_input(client_id, tick),
+            client_id,
+        ));
+        return;
+    }
+    for (input, client_id, missing, sub_tick) in
+        connection_manager.pop_inputs(tick, strategy.as_ref())
+    {
+        if missing {
+            missing_input_events.send(MissingInputEvent { client_id, tick });
+        }
+        input_events.send(InputEvent::new(input, client_id).with_sub_tick(sub_tick));
     }
 }
 
+/// Buffer the input messages of type `A` received from the clients.
+///
+/// The messages go through the same pipeline as the [`Protocol::Input`] messages: they count towards the
+/// [`InputRateLimitConfig`] of the client, the inputs are checked by the [`InputValidator<A>`], and the
+/// accepted inputs are relayed to the other clients if the [`InputBroadcastConfig`] is enabled.
+fn receive_additional_inputs<P: Protocol, A: UserAction>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut buffers: ResMut<AdditionalInputBuffers<A>>,
+    validator: Option<Res<InputValidator<A>>>,
+    mut messages: ResMut<Events<MessageEvent<InputMessage<A>>>>,
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut flagged_input_events: EventWriter<InputFlaggedEvent<A>>,
+) where
+    P::Message: From<InputMessage<A>>,
+{
+    let mut flagged_inputs = vec![];
+    for event in messages.drain() {
+        let client_id = *event.context();
+        let Ok(connection) = connection_manager.connection_mut(client_id) else {
+            continue;
+        };
+        let message = event.message().clone();
+        let end_tick = message.end_tick;
+        let num_ticks = message.num_ticks();
+        // the messages that exceed the limit are reported with the next InputRateLimitedEvent of the client
+        if !connection.accept_input_message(end_tick) {
+            trace!(
+                ?client_id,
+                "dropping input message that exceeds the rate limit"
+            );
+            continue;
+        }
+        let (input_buffer, _) = buffers.buffers.entry(client_id).or_default();
+        input_buffer.update_from_message(message, |tick, input| match &validator {
+            Some(validator) => validator.validate(client_id, tick, input, &mut flagged_inputs),
+            None => Some(input),
+        });
+        if connection.broadcast_inputs {
+            // relay the inputs that were buffered after validation, instead of the raw inputs of the client
+            let message = input_buffer.create_message(end_tick, num_ticks);
+            if !message.is_empty() {
+                connection
+                    .additional_inputs_to_broadcast
+                    .push(message.into());
+            }
+        }
+    }
+    flagged_input_events.send_batch(flagged_inputs);
+    for event in disconnections.read() {
+        buffers.buffers.remove(event.context());
+    }
+}
+
+/// Emit the inputs of type `A` of every client for the current tick
+fn write_additional_input_events<A: UserAction>(
+    tick_manager: Res<TickManager>,
+    mut buffers: ResMut<AdditionalInputBuffers<A>>,
+    mut input_events: EventWriter<InputEvent<A>>,
+) {
+    let tick = tick_manager.tick();
+    let mode = buffers.mode;
+    for (client_id, (input_buffer, last_input)) in buffers.buffers.iter_mut() {
+        let sub_tick = input_buffer.get_sub_tick(tick);
+        let input = input_buffer.pop(tick);
+        let input = match mode {
+            InputMode::Continuous => {
+                if input.is_some() {
+                    *last_input = input;
+                }
+                last_input.clone()
+            }
+            InputMode::Sparse => {
+                if input.is_none() {
+                    continue;
+                }
+                input
+            }
+        };
+        input_events.send(InputEvent::new(input, *client_id).with_sub_tick(sub_tick));
+    }
+}
+
+/// Relay the inputs received from each client to the other clients, if [`InputBroadcastConfig`] is enabled
+fn broadcast_inputs<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    room_manager: Option<Res<RoomManager>>,
+) {
+    connection_manager
+        .broadcast_inputs(room_manager.as_deref())
+        .unwrap_or_else(|e| {
+            error!("Error while broadcasting inputs: {:?}", e);
+        });
+}
+
 /// System that clears the input events.
 /// It is necessary because events are cleared every frame, but we want to clear every tick instead
 fn clear_input_events<I: UserAction>(mut input_events: EventReader<InputEvent<I>>) {
     input_events.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{FixedUpdate, ResMut, Resource, World};
+    use bevy::utils::Duration;
+
+    use crate::inputs::native::input_buffer::{InputData, InputMessage};
+    use crate::prelude::{PingConfig, Protocol, TickConfig};
+    use crate::server::config::PacketConfig;
+    use crate::server::replication::ReplicationConfig;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_input_nudger() {
+        let mut nudger = InputNudger::new(
+            &InputNudgeConfig::default()
+                .with_window(3)
+                .with_buffer_depth(1.0, 4.0)
+                .with_max_nudge_ticks(2),
+        );
+        // the buffer is too shallow: run further ahead
+        assert_eq!(nudger.update(0), None);
+        assert_eq!(nudger.update(1), None);
+        assert_eq!(nudger.update(0), Some(1));
+        assert_eq!(nudger.update(0), None);
+        assert_eq!(nudger.update(0), None);
+        assert_eq!(nudger.update(0), Some(2));
+        // the offset is capped
+        for _ in 0..3 {
+            assert_eq!(nudger.update(0), None);
+        }
+        // the buffer is healthy: keep the current offset
+        for _ in 0..3 {
+            assert_eq!(nudger.update(2), None);
+        }
+        // the buffer is too deep: run less ahead
+        assert_eq!(nudger.update(6), None);
+        assert_eq!(nudger.update(5), None);
+        assert_eq!(nudger.update(7), Some(1));
+
+        // after a resync the client starts again from a neutral nudge
+        assert_eq!(nudger.update(0), None);
+        nudger.reset();
+        assert_eq!(nudger.update(0), None);
+        assert_eq!(nudger.update(0), None);
+        assert_eq!(nudger.update(0), Some(1));
+
+        // clients that didn't send inputs recently are not nudged
+        assert!(!nudger.is_active(Tick(10), None));
+        assert!(nudger.is_active(Tick(10), Some(Tick(7))));
+        assert!(!nudger.is_active(Tick(10), Some(Tick(6))));
+    }
+
+    #[test]
+    fn test_tick_offset() {
+        let mut stepper = BevyStepper::default();
+        stepper.frame_step_n(10);
+        let metrics = stepper
+            .server_resource::<ConnectionManager<MyProtocol>>()
+            .input_buffer_metrics(ClientId::Netcode(111))
+            .unwrap()
+            .clone();
+        // the client runs ahead of the server so that its inputs arrive in time
+        assert!(metrics.tick_offset.is_some_and(|offset| offset >= 0));
+
+        let mut metrics = InputBufferMetrics::default();
+        metrics.record_tick_offset(2);
+        assert_eq!(metrics.average_tick_offset, 2.0);
+        metrics.record_tick_offset(-8);
+        assert_eq!(metrics.tick_offset, Some(-8));
+        assert!((metrics.average_tick_offset - 1.0).abs() < 1e-5);
+    }
+
+    #[derive(Resource, Default)]
+    struct ReceivedInputs {
+        inputs: Vec<Option<MyInput>>,
+        num_missing: usize,
+    }
+
+    fn record_inputs(
+        mut input_events: EventReader<InputEvent<MyInput>>,
+        mut missing_input_events: EventReader<MissingInputEvent>,
+        mut received: ResMut<ReceivedInputs>,
+    ) {
+        for event in input_events.read() {
+            received.inputs.push(event.input().clone());
+        }
+        received.num_missing += missing_input_events.read().count();
+    }
+
+    #[test]
+    fn test_missing_input_strategy() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .insert_resource(MissingInputStrategy::Default(MyInput(7)));
+        stepper.server_app.init_resource::<ReceivedInputs>();
+        stepper.server_app.add_systems(FixedUpdate, record_inputs);
+
+        // the client does not send any input: the fallback is used, but the inputs are not reported
+        // as missing since the client never sent any
+        stepper.frame_step();
+        let received = stepper.server_app.world.resource::<ReceivedInputs>();
+        assert!(!received.inputs.is_empty());
+        assert!(received
+            .inputs
+            .iter()
+            .all(|input| input == &Some(MyInput(7))));
+        assert_eq!(received.num_missing, 0);
+
+        // once the client has sent an input, the following ticks without inputs are missing
+        let tick = stepper.server_tick();
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ConnectionManager<MyProtocol>>()
+            .connection_mut(ClientId::Netcode(111))
+            .unwrap()
+            .input_buffer
+            .update_from_message(
+                InputMessage {
+                    end_tick: tick,
+                    inputs: vec![InputData::Input(MyInput(1))],
+                    sub_ticks: vec![],
+                },
+                |_, input| Some(input),
+            );
+        stepper
+            .server_app
+            .insert_resource(ReceivedInputs::default());
+        stepper.frame_step();
+        let received = stepper.server_app.world.resource::<ReceivedInputs>();
+        assert!(!received.inputs.is_empty());
+        assert_eq!(received.num_missing, received.inputs.len());
+    }
+
+    #[test]
+    fn test_broadcast_inputs() {
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            Some(
+                InputBroadcastConfig::default()
+                    .with_target(NetworkTarget::AllExceptSingle(ClientId::Netcode(3))),
+            ),
+            None,
+            None,
+            None,
+        );
+        let sender = ClientId::Netcode(1);
+        for id in 1..=3 {
+            manager.add(ClientId::Netcode(id));
+        }
+        manager
+            .connection_mut(sender)
+            .unwrap()
+            .inputs_to_broadcast
+            .push(InputMessage {
+                end_tick: Tick(10),
+                inputs: vec![InputData::Input(MyInput(1))],
+                sub_ticks: vec![],
+            });
+        manager.broadcast_inputs(None).unwrap();
+
+        // the inputs are only relayed to the clients in the target, and never to the sender
+        for (id, should_receive) in [(1, false), (2, true), (3, false)] {
+            let payloads = manager
+                .connection_mut(ClientId::Netcode(id))
+                .unwrap()
+                .message_manager
+                .send_packets(Tick(0))
+                .unwrap();
+            assert_eq!(!payloads.is_empty(), should_receive);
+        }
+        assert!(manager
+            .connection(sender)
+            .unwrap()
+            .inputs_to_broadcast
+            .is_empty());
+    }
+
+    #[test]
+    fn test_broadcast_validated_inputs() {
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            Some(InputBroadcastConfig::default()),
+            None,
+            None,
+            None,
+        );
+        let sender = ClientId::Netcode(1);
+        manager.add(sender);
+        let connection = manager.connection_mut(sender).unwrap();
+        connection.receive_input_message(
+            InputMessage {
+                end_tick: Tick(11),
+                inputs: vec![InputData::Input(MyInput(1)), InputData::Input(MyInput(50))],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(MyInput(input.0.min(10))),
+        );
+        // the inputs are relayed as they were buffered, after validation
+        assert_eq!(
+            connection.inputs_to_broadcast,
+            vec![InputMessage {
+                end_tick: Tick(11),
+                inputs: vec![InputData::Input(MyInput(1)), InputData::Input(MyInput(10))],
+                sub_ticks: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_late_inputs() {
+        let client_id = ClientId::Netcode(1);
+        let config = LateInputConfig::default().with_max_late_ticks(2);
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            None,
+            None,
+            Some(config.clone()),
+            None,
+        );
+        manager.add(client_id);
+        let connection = manager.connection_mut(client_id).unwrap();
+        connection.receive_input_message(
+            InputMessage {
+                end_tick: Tick(10),
+                inputs: vec![InputData::Input(MyInput(0))],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        // the server already simulated the ticks up to 14
+        connection.input_buffer.pop(Tick(14));
+
+        // the inputs for the ticks 11 to 14 arrive after their tick was simulated
+        connection.receive_input_message(
+            InputMessage {
+                end_tick: Tick(15),
+                inputs: (1..=5).map(|i| InputData::Input(MyInput(i))).collect(),
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        assert_eq!(connection.input_metrics.num_late, 4);
+        assert_eq!(connection.input_buffer.get(Tick(15)), Some(&MyInput(5)));
+
+        // only the inputs within the late window are emitted
+        let event = connection
+            .take_late_inputs(client_id, Some(&config), Tick(15))
+            .unwrap();
+        assert_eq!(
+            event.inputs,
+            vec![(Tick(13), MyInput(3)), (Tick(14), MyInput(4))]
+        );
+        assert_eq!(event.start_tick(), Some(Tick(13)));
+        assert!(connection
+            .take_late_inputs(client_id, Some(&config), Tick(15))
+            .is_none());
+    }
+
+    #[test]
+    fn test_additional_inputs() {
+        let client_id = ClientId::Netcode(1);
+        let mut world = World::new();
+        world.init_resource::<Events<InputEvent<usize>>>();
+        let mut run = |mode: InputMode| {
+            let mut buffers = AdditionalInputBuffers::<usize>::new(mode);
+            let mut input_buffer = InputBuffer::default();
+            input_buffer.set(Tick(0), Some(1));
+            buffers.buffers.insert(client_id, (input_buffer, None));
+            world.insert_resource(buffers);
+            // every run starts from tick 0
+            world.insert_resource(TickManager::from_config(TickConfig::new(
+                Duration::from_millis(10),
+            )));
+            for _ in 0..3 {
+                world.run_system_once(write_additional_input_events::<usize>);
+                world.resource_mut::<TickManager>().increment_tick();
+            }
+            world
+                .resource_mut::<Events<InputEvent<usize>>>()
+                .drain()
+                .map(|event| *event.input())
+                .collect::<Vec<_>>()
+        };
+        // continuous inputs reuse the last input
+        assert_eq!(run(InputMode::Continuous), vec![Some(1), Some(1), Some(1)]);
+        // sparse inputs are only emitted once
+        assert_eq!(run(InputMode::Sparse), vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_input_rate_limiter() {
+        let mut limiter = InputRateLimiter::new(&InputRateLimitConfig::default().with_burst(2));
+        assert!(limiter.accept(Tick(0)));
+        assert!(limiter.accept(Tick(1)));
+        assert!(!limiter.accept(Tick(2)));
+
+        // one message is allowed per tick
+        limiter.refill();
+        assert!(limiter.accept(Tick(3)));
+        assert!(!limiter.accept(Tick(4)));
+        // a few messages sent during the same client tick only count once
+        for _ in 1..InputRateLimiter::MESSAGES_PER_TOKEN {
+            assert!(limiter.accept(Tick(3)));
+        }
+        // but not an unlimited number of them
+        assert!(!limiter.accept(Tick(3)));
+
+        // the burst is capped
+        for _ in 0..10 {
+            limiter.refill();
+        }
+        assert!(limiter.accept(Tick(5)));
+        assert!(limiter.accept(Tick(6)));
+        assert!(!limiter.accept(Tick(7)));
+        assert_eq!(limiter.num_exceeded, 4);
+
+        // flagged messages are accepted
+        let mut limiter = InputRateLimiter::new(
+            &InputRateLimitConfig::default()
+                .with_burst(0)
+                .with_action(InputRateLimitAction::Flag),
+        );
+        assert!(limiter.accept(Tick(0)));
+        assert_eq!(limiter.num_exceeded, 1);
+    }
+
+    #[test]
+    fn test_input_validator() {
+        let validator = InputValidator::<usize>::new(|_, _, input| match *input {
+            0 => InputVerdict::Reject,
+            1..=10 => InputVerdict::Accept,
+            11..=100 => InputVerdict::Clamp(10),
+            _ => InputVerdict::Flag,
+        });
+        let client_id = ClientId::Netcode(1);
+        let mut flagged = vec![];
+        assert_eq!(
+            validator.validate(client_id, Tick(0), 0, &mut flagged),
+            None
+        );
+        assert_eq!(
+            validator.validate(client_id, Tick(0), 5, &mut flagged),
+            Some(5)
+        );
+        assert_eq!(
+            validator.validate(client_id, Tick(0), 50, &mut flagged),
+            Some(10)
+        );
+        assert_eq!(
+            validator.validate(client_id, Tick(1), 500, &mut flagged),
+            Some(500)
+        );
+        assert_eq!(
+            flagged,
+            vec![InputFlaggedEvent {
+                client_id,
+                tick: Tick(1),
+                input: 500,
+            }]
+        );
+    }
+}
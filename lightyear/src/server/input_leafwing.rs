@@ -10,7 +10,7 @@ use crate::client::config::ClientConfig;
 use crate::client::prediction::Predicted;
 use crate::connection::client::NetClient;
 use crate::inputs::leafwing::input_buffer::{
-    ActionDiffBuffer, ActionDiffEvent, InputBuffer, InputTarget,
+    ActionDiff, ActionDiffBuffer, ActionDiffEvent, InputBuffer, InputTarget,
 };
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
 use crate::prelude::client::is_in_rollback;
@@ -19,9 +19,11 @@ use crate::protocol::Protocol;
 use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
 use crate::server::events::InputMessageEvent;
+use crate::server::input::{InputFlaggedEvent, InputValidator};
 use crate::shared::events::connection::IterInputMessageEvent;
 use crate::shared::replication::components::PrePredicted;
 use crate::shared::sets::InternalMainSet;
+use crate::shared::tick_manager::is_first_substep;
 
 pub struct LeafwingInputPlugin<P, A> {
     marker: std::marker::PhantomData<(P, A)>,
@@ -67,6 +69,7 @@ where
     fn build(&self, app: &mut App) {
         // EVENTS
         app.add_event::<InputMessageEvent<A>>();
+        app.add_event::<InputFlaggedEvent<ActionDiff<A>>>();
         // RESOURCES
         // app.init_resource::<GlobalActions<A>>();
         // TODO: (global action states) add a resource tracking the action-state of all clients
@@ -80,7 +83,11 @@ where
             )
                 .chain(),
         );
-        app.configure_sets(FixedPreUpdate, InputSystemSet::Update);
+        // inputs are applied once per tick, on the first physics step
+        app.configure_sets(
+            FixedPreUpdate,
+            InputSystemSet::Update.run_if(is_first_substep),
+        );
         // SYSTEMS
         app.add_systems(
             PreUpdate,
@@ -125,9 +132,13 @@ fn add_action_diff_buffer<A: LeafwingUserAction>(
 }
 
 /// Read the input messages from the server events to update the ActionDiffBuffers
+///
+/// The diffs go through the [`InputValidator`] for `ActionDiff<A>`, if there is one.
 fn receive_input_message<P: Protocol, A: LeafwingUserAction>(
     // mut global: Option<ResMut<ActionDiffBuffer<A>>>,
     mut connection_manager: ResMut<ConnectionManager<P>>,
+    validator: Option<Res<InputValidator<ActionDiff<A>>>>,
+    mut flagged_events: EventWriter<InputFlaggedEvent<ActionDiff<A>>>,
     // TODO: currently we do not handle entities that are controlled by multiple clients
     mut query: Query<&mut ActionDiffBuffer<A>>,
 ) where
@@ -145,7 +156,16 @@ fn receive_input_message<P: Protocol, A: LeafwingUserAction>(
                     debug!("received input for entity: {:?}", entity);
                     if let Ok(mut buffer) = query.get_mut(entity) {
                         debug!(?entity, ?diffs, end_tick = ?message.end_tick, "update action diff buffer for PREPREDICTED using input message");
-                        buffer.update_from_message(message.end_tick, diffs);
+                        let mut flagged = vec![];
+                        buffer.update_from_message(message.end_tick, diffs, |tick, diff| {
+                            match &validator {
+                                Some(validator) => {
+                                    validator.validate(client_id, tick, diff, &mut flagged)
+                                }
+                                None => Some(diff),
+                            }
+                        });
+                        flagged_events.send_batch(flagged);
                     } else {
                         // TODO: maybe if the entity is pre-predicted, apply map-entities, so we can handle pre-predicted inputs
                         debug!(?entity, ?diffs, end_tick = ?message.end_tick, "received input message for unrecognized entity");
@@ -227,7 +247,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
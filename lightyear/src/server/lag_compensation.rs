@@ -0,0 +1,211 @@
+//! # Lag compensation
+//!
+//! When a client shoots at another entity, it sees that entity in the past (because remote entities are
+//! interpolated). To validate hitscan checks against what the shooting client actually saw, the server keeps a
+//! history of the component `C` (for example the position or the hitbox) of the [`LagCompensated`] entities for the
+//! last few ticks.
+//!
+//! Use the [`LagCompensation`] system parameter to get the state of an entity at a given tick, or [`rewind_world`]
+//! to run a check on the whole world as it was at that tick.
+//! The tick should be the tick at which the client saw the entities (its interpolation tick), which the client
+//! can send along with its inputs.
+use std::collections::VecDeque;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{
+    App, Commands, Component, DetectChangesMut, Entity, FixedPostUpdate, IntoSystemConfigs, Plugin,
+    Query, Res, Resource, With, Without, World,
+};
+
+use crate::prelude::TickManager;
+use crate::server::rewind::ExcludedFromRewind;
+use crate::shared::tick_manager::{is_last_substep, Tick};
+
+/// Marker component for the server entities whose history should be recorded for lag compensation
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct LagCompensated;
+
+/// Record the history of the component `C` of the [`LagCompensated`] entities
+pub struct LagCompensationPlugin<C> {
+    history_ticks: u16,
+    _marker: std::marker::PhantomData<C>,
+}
+
+impl<C> LagCompensationPlugin<C> {
+    /// Keep the history of the component for the last `history_ticks` ticks
+    pub fn new(history_ticks: u16) -> Self {
+        Self {
+            history_ticks,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<C: Component + Clone> Plugin for LagCompensationPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LagCompensationConfig::<C> {
+            history_ticks: self.history_ticks,
+            _marker: std::marker::PhantomData,
+        });
+        // record the state at the end of each tick
+        app.add_systems(
+            FixedPostUpdate,
+            record_lag_compensation_history::<C>.run_if(is_last_substep),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct LagCompensationConfig<C> {
+    history_ticks: u16,
+    _marker: std::marker::PhantomData<C>,
+}
+
+/// History of the component `C` for the last few ticks
+#[derive(Component, Debug)]
+pub struct LagCompensationHistory<C> {
+    buffer: VecDeque<(Tick, C)>,
+}
+
+impl<C> Default for LagCompensationHistory<C> {
+    fn default() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+        }
+    }
+}
+
+impl<C> LagCompensationHistory<C> {
+    /// Get the value of the component at the end of the specified tick
+    pub fn get(&self, tick: Tick) -> Option<&C> {
+        self.buffer
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= tick)
+            .map(|(_, value)| value)
+    }
+
+    /// Remove the values recorded after the specified tick
+    pub(crate) fn truncate_after(&mut self, tick: Tick) {
+        self.buffer.retain(|(t, _)| *t <= tick);
+    }
+}
+
+pub(crate) fn record_lag_compensation_history<C: Component + Clone>(
+    mut commands: Commands,
+    tick_manager: Res<TickManager>,
+    config: Res<LagCompensationConfig<C>>,
+    // the entities that are not re-simulated during an input rewind keep their recorded history
+    mut query: Query<
+        (Entity, &C, Option<&mut LagCompensationHistory<C>>),
+        (With<LagCompensated>, Without<ExcludedFromRewind>),
+    >,
+) {
+    let tick = tick_manager.tick();
+    for (entity, component, history) in query.iter_mut() {
+        let Some(mut history) = history else {
+            let mut history = LagCompensationHistory::<C>::default();
+            history.buffer.push_back((tick, component.clone()));
+            commands.entity(entity).insert(history);
+            continue;
+        };
+        history.buffer.push_back((tick, component.clone()));
+        while history.buffer.len() > config.history_ticks as usize {
+            history.buffer.pop_front();
+        }
+    }
+}
+
+/// [`SystemParam`] to access the past states of the [`LagCompensated`] entities
+#[derive(SystemParam)]
+pub struct LagCompensation<'w, 's, C: Component> {
+    query: Query<'w, 's, &'static LagCompensationHistory<C>>,
+}
+
+impl<'w, 's, C: Component> LagCompensation<'w, 's, C> {
+    /// Get the value of the component `C` of the entity at the end of the specified tick.
+    ///
+    /// Returns `None` if the tick is older than the recorded history.
+    pub fn get_state_at(&self, entity: Entity, tick: Tick) -> Option<&C> {
+        self.query
+            .get(entity)
+            .ok()
+            .and_then(|history| history.get(tick))
+    }
+}
+
+/// Run `f` on the world rewound to the specified tick: the component `C` of every [`LagCompensated`]
+/// entity is set to its value at the end of `tick`, and restored to its current value after `f` returns.
+///
+/// Entities that don't have any history for that tick are left untouched.
+/// Change detection is bypassed, so the rewind does not trigger any replication update.
+pub fn rewind_world<C: Component + Clone, R>(
+    world: &mut World,
+    tick: Tick,
+    f: impl FnOnce(&mut World) -> R,
+) -> R {
+    let mut query = world.query::<(Entity, &mut C, &LagCompensationHistory<C>)>();
+    let mut current_values = Vec::new();
+    for (entity, mut component, history) in query.iter_mut(world) {
+        if let Some(past) = history.get(tick) {
+            let current = std::mem::replace(component.bypass_change_detection(), past.clone());
+            current_values.push((entity, current));
+        }
+    }
+    let result = f(world);
+    // restore the current values
+    for (entity, current) in current_values {
+        if let Some(mut component) = world.get_mut::<C>(entity) {
+            *component.bypass_change_detection() = current;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::utils::Duration;
+
+    use crate::prelude::TickConfig;
+
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(f32);
+
+    #[test]
+    fn test_lag_compensation() {
+        let mut world = World::new();
+        world.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        world.insert_resource(LagCompensationConfig::<Position> {
+            history_ticks: 3,
+            _marker: std::marker::PhantomData,
+        });
+        let entity = world.spawn((Position(0.0), LagCompensated)).id();
+
+        // the entity moves during ticks 0 to 4
+        for x in 0..5 {
+            world.get_mut::<Position>(entity).unwrap().0 = x as f32;
+            world.run_system_once(record_lag_compensation_history::<Position>);
+            world.resource_mut::<TickManager>().increment_tick();
+        }
+
+        world.run_system_once(move |lag_compensation: LagCompensation<Position>| {
+            assert_eq!(
+                lag_compensation.get_state_at(entity, Tick(3)),
+                Some(&Position(3.0))
+            );
+            // only the last 3 ticks are kept
+            assert_eq!(lag_compensation.get_state_at(entity, Tick(1)), None);
+        });
+
+        let rewound = rewind_world::<Position, _>(&mut world, Tick(2), |world| {
+            world.get::<Position>(entity).unwrap().clone()
+        });
+        assert_eq!(rewound, Position(2.0));
+        assert_eq!(world.get::<Position>(entity), Some(&Position(4.0)));
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::Context;
-use tracing::{info_span, trace};
+use bevy::utils::Duration;
+use tracing::{debug, info_span, trace};
 
 use bitcode::__private::Fixed;
 use bitcode::{Decode, Encode};
 
 use crate::_reexport::{BitSerializable, MessageProtocol, ReadBuffer, WriteBuffer};
-use crate::prelude::Protocol;
+use crate::connection::id::ClientId;
+use crate::inputs::native::InputMessage;
+use crate::prelude::{Protocol, Tick};
 use crate::shared::ping::message::SyncMessage;
 use crate::shared::replication::{ReplicationMessage, ReplicationMessageData};
 
@@ -17,6 +20,36 @@ pub enum ServerMessage<P: Protocol> {
     #[bitcode_hint(frequency = 3)]
     #[bitcode(with_serde)]
     Replication(ReplicationMessage<P::Components, P::ComponentKinds>),
+    /// All the replication actions that are sent to a client right after it connects,
+    /// so that they are applied at the same time.
+    ///
+    /// The actions are serialized and compressed (see [`compress_baseline`](crate::shared::replication::compress_baseline))
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    ReplicationBaseline(Vec<u8>),
+    /// Inputs of another client, relayed by the server
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Inputs(ClientId, InputMessage<P::Input>),
+    /// Inputs of an additional input type of another client, relayed by the server
+    /// (see [`AdditionalInputPlugin`](crate::server::input::AdditionalInputPlugin))
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    AdditionalInputs(ClientId, P::Message),
+    /// The server changes its tick duration, starting from the given tick
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    TickDuration(Tick, Duration),
+    /// The host paused the simulation at the given tick
+    #[bitcode_hint(frequency = 1)]
+    Pause(Tick),
+    /// The host resumed the simulation, starting at the given tick
+    #[bitcode_hint(frequency = 1)]
+    Resume(Tick),
+    /// The server asks the client to speed up or slow down slightly, depending on the health of
+    /// the client's input buffer on the server
+    #[bitcode_hint(frequency = 1)]
+    TimeNudge(i16),
     // the reason why we include sync here instead of doing another MessageManager is so that
     // the sync messages can be added to packets that have other messages
     #[bitcode_hint(frequency = 1)]
@@ -122,6 +155,33 @@ impl<P: Protocol> ServerMessage<P> {
                     }
                 }
             }
+            ServerMessage::ReplicationBaseline(bytes) => {
+                trace!(channel = ?channel_name, num_bytes = ?bytes.len(), "Sending replication baseline");
+                #[cfg(metrics)]
+                metrics::counter!("send_replication_baseline").increment(1);
+            }
+            ServerMessage::TickDuration(tick, tick_duration) => {
+                debug!(channel = ?channel_name, ?tick, ?tick_duration, "Sending tick duration");
+            }
+            ServerMessage::Pause(tick) => {
+                debug!(channel = ?channel_name, ?tick, "Sending pause");
+            }
+            ServerMessage::Resume(tick) => {
+                debug!(channel = ?channel_name, ?tick, "Sending resume");
+            }
+            ServerMessage::TimeNudge(offset) => {
+                debug!(channel = ?channel_name, ?offset, "Sending time nudge");
+            }
+            ServerMessage::Inputs(client_id, message) => {
+                trace!(channel = ?channel_name, ?client_id, end_tick = ?message.end_tick, "Sending inputs of another client");
+                #[cfg(metrics)]
+                metrics::counter!("send_broadcast_inputs").increment(1);
+            }
+            ServerMessage::AdditionalInputs(client_id, _) => {
+                trace!(channel = ?channel_name, ?client_id, "Sending additional inputs of another client");
+                #[cfg(metrics)]
+                metrics::counter!("send_broadcast_inputs").increment(1);
+            }
             ServerMessage::Sync(message) => match message {
                 SyncMessage::Ping(_) => {
                     trace!(channel = ?channel_name, "Sending ping");
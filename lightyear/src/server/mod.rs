@@ -7,13 +7,21 @@ pub mod config;
 
 pub mod connection;
 
+mod diagnostics;
+
 pub mod events;
 
+pub mod lag_compensation;
+
 mod input;
 
 pub mod plugin;
 
+pub mod relevance;
+pub mod rewind;
 pub mod room;
+pub mod snapshot;
+pub mod target;
 
 #[cfg_attr(docsrs, doc(cfg(feature = "leafwing")))]
 #[cfg(feature = "leafwing")]
@@ -22,4 +30,5 @@ pub(crate) mod message;
 pub(crate) mod prediction;
 
 mod networking;
+pub mod ownership;
 pub mod replication;
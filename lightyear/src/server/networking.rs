@@ -2,20 +2,26 @@
 use anyhow::Context;
 use bevy::ecs::system::SystemChangeTick;
 use bevy::prelude::*;
-use tracing::{debug, error, trace, trace_span};
+use tracing::{debug, error, info, trace, trace_span};
 
 use crate::_reexport::{ComponentProtocol, ServerMarker};
+use crate::connection::id::ClientId;
 use crate::connection::server::{NetConfig, NetServer, ServerConnection, ServerConnections};
-use crate::prelude::{TickManager, TimeManager};
+use crate::prelude::{FixedUpdateSet, TickManager, TimeManager};
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
+use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
-use crate::server::events::{ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent};
+use crate::server::events::{
+    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, PacketLostEvent,
+    TokenRefreshEvent,
+};
 use crate::server::room::RoomManager;
 use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
+use crate::shared::replication::plugin::ReplicationSendTimer;
 use crate::shared::replication::ReplicationSend;
 use crate::shared::sets::InternalMainSet;
-use crate::shared::time_manager::is_server_ready_to_send;
+use crate::shared::time_manager::{is_server_ready_to_send, network_delta};
 
 pub(crate) struct ServerNetworkingPlugin<P: Protocol> {
     config: Vec<NetConfig>,
@@ -38,11 +44,16 @@ impl<P: Protocol> ServerNetworkingPlugin<P> {
 
 impl<P: Protocol> Plugin for ServerNetworkingPlugin<P> {
     fn build(&self, app: &mut App) {
+        let mut server_connections = ServerConnections::new(self.config.clone());
+        let extended_ticks = app.world.resource::<ServerConfig>().shared.tick.extended;
+        for server in server_connections.servers.iter_mut() {
+            server.set_extended_ticks(extended_ticks);
+        }
         app
             // RESOURCE
             // start the netcode servers
             // in practice this mostly just starts the io (spawns server io tasks, etc.)
-            .insert_resource(ServerConnections::new(self.config.clone()))
+            .insert_resource(server_connections)
             // SYSTEM SETS
             .configure_sets(
                 PreUpdate,
@@ -61,15 +72,106 @@ impl<P: Protocol> Plugin for ServerNetworkingPlugin<P> {
             // SYSTEMS //
             .add_systems(
                 PreUpdate,
-                (receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),),
+                (
+                    receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),
+                    update_tick_duration::<P>.after(InternalMainSet::<ServerMarker>::Receive),
+                    update_pause::<P>.after(InternalMainSet::<ServerMarker>::Receive),
+                ),
+            )
+            .add_systems(
+                FixedFirst,
+                apply_tick_duration::<P>.after(FixedUpdateSet::TickUpdate),
             )
             .add_systems(
                 PostUpdate,
-                (send::<P>.in_set(InternalMainSet::<ServerMarker>::SendPackets),),
+                (
+                    send::<P>.in_set(InternalMainSet::<ServerMarker>::SendPackets),
+                    apply_pause::<P>.after(InternalMainSet::<ServerMarker>::Send),
+                ),
             );
     }
 }
 
+/// Schedule the tick duration that was set with [`ConnectionManager::set_tick_duration`],
+/// and notify the clients
+pub(crate) fn update_tick_duration<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+) {
+    let Some(tick_duration) = connection_manager.pending_tick_duration.take() else {
+        return;
+    };
+    match connection_manager.schedule_tick_duration(&tick_manager, tick_duration) {
+        Ok(tick) => info!(?tick, ?tick_duration, "Scheduling a tick duration change"),
+        Err(e) => error!("could not send the tick duration to the clients: {:?}", e),
+    }
+}
+
+/// Apply the scheduled tick duration once the server reaches the tick of the change.
+///
+/// This runs right after the tick is incremented, so that the new tick is the first one that lasts the new
+/// tick duration.
+pub(crate) fn apply_tick_duration<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut config: ResMut<ServerConfig>,
+    mut tick_manager: ResMut<TickManager>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    let Some(tick_duration) = connection_manager.take_scheduled_tick_duration(tick_manager.tick())
+    else {
+        return;
+    };
+    info!(tick = ?tick_manager.tick(), ?tick_duration, "Changing the tick duration");
+    tick_manager.set_tick_duration(tick_duration, &mut fixed_time);
+    config.shared.tick.tick_duration = tick_duration;
+}
+
+/// Handle the pause/resume requests made with [`ConnectionManager::pause`] and [`ConnectionManager::resume`],
+/// and notify the clients
+pub(crate) fn update_pause<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Some(pause) = connection_manager.pending_pause.take() else {
+        return;
+    };
+    let tick = tick_manager.tick();
+    if pause {
+        if connection_manager.is_paused() {
+            return;
+        }
+        info!(?tick, "Pausing the simulation");
+        // the simulation is only paused after the pause message has been sent to the clients
+        let _ = connection_manager
+            .apply_pause(tick)
+            .map_err(|e| error!("could not send the pause to the clients: {:?}", e));
+    } else {
+        if !connection_manager.is_paused() {
+            return;
+        }
+        info!(?tick, "Resuming the simulation");
+        virtual_time.unpause();
+        let _ = connection_manager
+            .apply_resume(tick)
+            .map_err(|e| error!("could not send the resume to the clients: {:?}", e));
+    }
+}
+
+/// Pause the virtual time once the pause message has been sent to the clients.
+/// While the virtual time is paused, no ticks are run, but the server keeps sending packets so that the
+/// pause message can be retransmitted until the clients ack it
+pub(crate) fn apply_pause<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    time_manager: Res<TimeManager>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    if connection_manager.pausing && time_manager.is_server_ready_to_send() {
+        connection_manager.pausing = false;
+        virtual_time.pause();
+    }
+}
+
 pub(crate) fn receive<P: Protocol>(world: &mut World) {
     trace!("Receive client packets");
     world.resource_scope(|world: &mut World, mut connection_manager: Mut<ConnectionManager<P>>| {
@@ -82,9 +184,18 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                     world.resource_scope(
                                         |world: &mut World, mut room_manager: Mut<RoomManager>| {
                                             let delta = world.resource::<Time<Virtual>>().delta();
+                                            let real_delta = world.resource::<Time<Real>>().delta();
+                                            // keep the connections alive even if the simulation is paused
+                                            let network_delta = network_delta(world);
+                                            let disconnect_on_invalid_packet = world.resource::<ServerConfig>().packet.disconnect_on_invalid_packet;
                                             // UPDATE: update server state, send keep-alives, receive packets from io
                                             // update time manager
                                             time_manager.update(delta);
+                                            time_manager.update_real(real_delta);
+                                            if world.resource::<Time<Virtual>>().is_paused() {
+                                                // keep sending packets while the simulation is paused
+                                                time_manager.tick_send_timers(network_delta);
+                                            }
                                             trace!(time = ?time_manager.current_time(), tick = ?tick_manager.tick(), "receive");
 
                                             // update server net connections
@@ -92,11 +203,14 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                             let netservers = &mut *netservers;
                                             for (server_idx, netserver) in netservers.servers.iter_mut().enumerate() {
                                                 let _ = netserver
-                                                    .try_update(delta.as_secs_f64())
+                                                    .try_update(network_delta.as_secs_f64())
                                                     .map_err(|e| error!("Error updating netcode server: {:?}", e));
                                                 for client_id in netserver.new_connections().iter().copied() {
                                                     netservers.client_server_map.insert(client_id, server_idx);
                                                     connection_manager.add(client_id);
+                                                    if let Ok(connection) = connection_manager.connection_mut(client_id) {
+                                                        connection.user_data = netserver.user_data(client_id);
+                                                    }
                                                 }
                                                 // handle disconnections
                                                 for client_id in netserver.new_disconnections().iter().copied() {
@@ -121,7 +235,15 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                     // TODO: use connection to apply on BOTH message manager and replication manager
                                                     if let Ok(connection) = connection_manager
                                                         .connection_mut(client_id) {
-                                                        connection.recv_packet(packet, tick_manager.as_ref()).expect("could not receive packet");
+                                                        // the packet was sent by the client, it could contain invalid data
+                                                        if let Err(e) = connection.recv_packet(packet, tick_manager.as_ref()) {
+                                                            error!(?client_id, "Error receiving packet from client, dropping it: {:?}", e);
+                                                            if disconnect_on_invalid_packet {
+                                                                let _ = netserver.disconnect(client_id).map_err(|e| {
+                                                                    error!(?client_id, "Error disconnecting client: {:?}", e)
+                                                                });
+                                                            }
+                                                        }
                                                     } else {
                                                         // it's still possible to receive some packets from a client that just disconnected.
                                                         // (multiple packets arrived at the same time from that client)
@@ -137,6 +259,16 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                 }
                                             }
 
+                                            // LOST PACKETS: notify about the packets we sent that got lost
+                                            let mut packet_lost_event_writer =
+                                                world.get_resource_mut::<Events<PacketLostEvent>>().unwrap();
+                                            for (client_id, connection) in connection_manager.connections.iter_mut() {
+                                                for (packet_id, lost_messages) in connection.message_manager.drain_lost_packets() {
+                                                    packet_lost_event_writer
+                                                        .send(PacketLostEvent::new(packet_id, lost_messages, *client_id));
+                                                }
+                                            }
+
                                             // RECEIVE: read messages and parse them into events
                                             connection_manager
                                                 .receive(world, time_manager.as_ref(), tick_manager.as_ref())
@@ -144,6 +276,28 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                     error!("Error during receive: {}", e);
                                                 });
 
+                                            // TOKEN REFRESH: extend the sessions of the clients that sent a new connect token
+                                            for (client_id, connection) in connection_manager.connections.iter_mut() {
+                                                let Some(token) = connection.pending_token_refresh.take() else {
+                                                    continue;
+                                                };
+                                                let Some(netserver) = netservers
+                                                    .client_server_map
+                                                    .get(client_id)
+                                                    .and_then(|idx| netservers.servers.get_mut(*idx)) else {
+                                                    continue;
+                                                };
+                                                match netserver.refresh_token(*client_id, &token) {
+                                                    Ok(()) => {
+                                                        connection.user_data = netserver.user_data(*client_id);
+                                                        world
+                                                            .resource_mut::<Events<TokenRefreshEvent>>()
+                                                            .send(TokenRefreshEvent::new(*client_id, connection.user_data));
+                                                    }
+                                                    Err(e) => error!(?client_id, "Could not refresh the connect token of the client: {:?}", e),
+                                                }
+                                            }
+
                                             // EVENTS: Write the received events into bevy events
                                             if !connection_manager.events.is_empty() {
                                                 // TODO: write these as systems? might be easier to also add the events to the app
@@ -157,9 +311,14 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                 if connection_manager.events.has_connections() {
                                                     let mut connect_event_writer =
                                                         world.get_resource_mut::<Events<ConnectEvent>>().unwrap();
-                                                    for client_id in connection_manager.events.iter_connections() {
+                                                    let connections: Vec<ClientId> = connection_manager.events.iter_connections().collect();
+                                                    for client_id in connections {
                                                         debug!("Client connected event: {}", client_id);
-                                                        connect_event_writer.send(ConnectEvent::new(client_id));
+                                                        let mut event = ConnectEvent::new(client_id);
+                                                        if let Ok(Some(user_data)) = connection_manager.user_data(client_id) {
+                                                            event = event.with_user_data(*user_data);
+                                                        }
+                                                        connect_event_writer.send(event);
                                                     }
                                                 }
 
@@ -209,6 +368,7 @@ pub(crate) fn send<P: Protocol>(
     change_tick: SystemChangeTick,
     mut netservers: ResMut<ServerConnections>,
     mut connection_manager: ResMut<ConnectionManager<P>>,
+    replication_timer: Option<Res<ReplicationSendTimer<ConnectionManager<P>>>>,
     tick_manager: Res<TickManager>,
     time_manager: Res<TimeManager>,
 ) {
@@ -236,18 +396,32 @@ pub(crate) fn send<P: Protocol>(
                 .servers
                 .get_mut(netserver_idx)
                 .context("could not find server with the provided netserver idx")?;
-            for packet_byte in connection.send_packets(&time_manager, &tick_manager)? {
-                netserver.send(packet_byte.as_slice(), *client_id)?;
-            }
-            Ok(())
+            let payloads = connection.send_packets(&time_manager, &tick_manager)?;
+            let sent = payloads
+                .iter()
+                .try_for_each(|packet_byte| netserver.send(packet_byte.as_slice(), *client_id));
+            // return the payloads to the pool so they can be re-used for the next packets,
+            // even if some of them could not be sent
+            connection.message_manager.release_payloads(payloads);
+            sent
         })
         .unwrap_or_else(|e: anyhow::Error| {
             error!("Error sending packets: {}", e);
         });
 
-    // clear the list of newly connected clients
+    // disconnect the clients that were kicked, now that their last messages have been delivered
+    for (client_id, reason) in connection_manager.drain_disconnects(time_manager.current_time()) {
+        let _ = netservers
+            .disconnect_with_reason(client_id, reason)
+            .map_err(|e| error!(?client_id, "Error disconnecting client: {:?}", e));
+    }
+
+    // clear the list of newly connected clients, once the replication systems have used it to send them the
+    // entities that were already replicated
     // (cannot just use the ConnectionEvent because it is cleared after each frame)
-    connection_manager.new_clients.clear();
+    if replication_timer.map_or(true, |timer| timer.is_ready()) {
+        connection_manager.new_clients.clear();
+    }
 }
 
 /// Clear the received events
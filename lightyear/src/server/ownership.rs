@@ -0,0 +1,141 @@
+//! # Entity ownership
+//!
+//! Keeps track of the entities that are controlled by each client, using the [`ControlledBy`] component.
+//!
+//! The entities controlled by a client are despawned automatically when the client disconnects
+//! (unless [`ReplicationConfig::despawn_controlled_on_disconnect`](crate::server::replication::ReplicationConfig::despawn_controlled_on_disconnect) is false), so that games don't need
+//! to write their own disconnection system.
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::prelude::{
+    Changed, Commands, Entity, EventReader, Query, RemovedComponents, Res, ResMut, Resource,
+};
+use bevy::utils::HashMap;
+
+use crate::connection::id::ClientId;
+use crate::server::config::ServerConfig;
+use crate::server::events::DisconnectEvent;
+use crate::shared::replication::components::ControlledBy;
+
+/// Index of the entities controlled by each client, maintained from the [`ControlledBy`] components
+#[derive(Resource, Default, Debug)]
+pub struct ControlledEntities {
+    entities: HashMap<ClientId, EntityHashSet>,
+    owners: EntityHashMap<Entity, ClientId>,
+}
+
+impl ControlledEntities {
+    /// Iterate through all the entities controlled by the client
+    pub fn entities_owned_by(&self, client_id: ClientId) -> impl Iterator<Item = Entity> + '_ {
+        self.entities
+            .get(&client_id)
+            .into_iter()
+            .flat_map(|entities| entities.iter().copied())
+    }
+
+    /// The client that controls the entity, if any
+    pub fn owner(&self, entity: Entity) -> Option<ClientId> {
+        self.owners.get(&entity).copied()
+    }
+
+    fn insert(&mut self, entity: Entity, client_id: ClientId) {
+        self.remove(entity);
+        self.entities.entry(client_id).or_default().insert(entity);
+        self.owners.insert(entity, client_id);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(client_id) = self.owners.remove(&entity) else {
+            return;
+        };
+        if let Some(entities) = self.entities.get_mut(&client_id) {
+            entities.remove(&entity);
+            if entities.is_empty() {
+                self.entities.remove(&client_id);
+            }
+        }
+    }
+}
+
+/// Update the [`ControlledEntities`] index when [`ControlledBy`] components are added, changed or removed
+pub(crate) fn update_controlled_entities(
+    mut controlled_entities: ResMut<ControlledEntities>,
+    mut removed: RemovedComponents<ControlledBy>,
+    query: Query<(Entity, &ControlledBy), Changed<ControlledBy>>,
+) {
+    // handle removals first, in case the component was removed and inserted again in the same frame
+    for entity in removed.read() {
+        controlled_entities.remove(entity);
+    }
+    for (entity, controlled_by) in query.iter() {
+        controlled_entities.insert(entity, controlled_by.0);
+    }
+}
+
+/// Despawn the entities controlled by the clients that just disconnected
+pub(crate) fn despawn_controlled_on_disconnect(
+    mut commands: Commands,
+    config: Res<ServerConfig>,
+    mut controlled_entities: ResMut<ControlledEntities>,
+    mut disconnections: EventReader<DisconnectEvent>,
+) {
+    for event in disconnections.read() {
+        let client_id = *event.context();
+        let Some(entities) = controlled_entities.entities.remove(&client_id) else {
+            continue;
+        };
+        for entity in entities {
+            controlled_entities.owners.remove(&entity);
+            if config.replication.despawn_controlled_on_disconnect {
+                if let Some(mut entity_commands) = commands.get_entity(entity) {
+                    entity_commands.despawn();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Events, World};
+
+    use super::*;
+
+    #[test]
+    fn test_controlled_entities() {
+        let client_0 = ClientId::Netcode(0);
+        let client_1 = ClientId::Netcode(1);
+        let mut world = World::new();
+        world.init_resource::<ControlledEntities>();
+        world.init_resource::<ServerConfig>();
+        world.init_resource::<Events<DisconnectEvent>>();
+
+        let entity_a = world.spawn(ControlledBy(client_0)).id();
+        let entity_b = world.spawn(ControlledBy(client_0)).id();
+        let entity_c = world.spawn(ControlledBy(client_1)).id();
+        world.run_system_once(update_controlled_entities);
+        let controlled = world.resource::<ControlledEntities>();
+        assert_eq!(controlled.entities_owned_by(client_0).count(), 2);
+        assert_eq!(controlled.owner(entity_c), Some(client_1));
+
+        // transfer the ownership of an entity
+        world.entity_mut(entity_b).insert(ControlledBy(client_1));
+        world.run_system_once(update_controlled_entities);
+        let controlled = world.resource::<ControlledEntities>();
+        assert_eq!(
+            controlled.entities_owned_by(client_0).collect::<Vec<_>>(),
+            vec![entity_a]
+        );
+        assert_eq!(controlled.owner(entity_b), Some(client_1));
+
+        // the entities of a client are despawned when it disconnects
+        world.send_event(DisconnectEvent::new(client_1));
+        world.run_system_once(despawn_controlled_on_disconnect);
+        assert!(world.get_entity(entity_a).is_some());
+        assert!(world.get_entity(entity_b).is_none());
+        assert!(world.get_entity(entity_c).is_none());
+        let controlled = world.resource::<ControlledEntities>();
+        assert_eq!(controlled.entities_owned_by(client_1).count(), 0);
+        assert_eq!(controlled.owner(entity_b), None);
+    }
+}
@@ -8,6 +8,7 @@ use crate::protocol::component::ComponentProtocol;
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
+use crate::server::diagnostics::ServerDiagnosticsPlugin;
 use crate::server::events::ServerEventsPlugin;
 use crate::server::input::InputPlugin;
 use crate::server::networking::ServerNetworkingPlugin;
@@ -57,8 +58,14 @@ impl<P: Protocol> Plugin for ServerPlugin<P> {
                 config.protocol.channel_registry().clone(),
                 config.server_config.packet,
                 config.server_config.ping,
+                config.server_config.replication.clone(),
+                config.server_config.input_broadcast.clone(),
+                config.server_config.input_rate_limit.clone(),
+                config.server_config.late_inputs.clone(),
+                config.server_config.input_nudge.clone(),
             ))
             // PLUGINS
+            .add_plugins(ServerDiagnosticsPlugin::<P>::default())
             .add_plugins(ServerEventsPlugin::<P>::default())
             .add_plugins(ServerNetworkingPlugin::<P>::new(config.server_config.net))
             .add_plugins(InputPlugin::<P>::default())
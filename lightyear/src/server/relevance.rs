@@ -0,0 +1,579 @@
+//! # Distance-based relevance
+//!
+//! Optional interest management where each client has a point of interest (usually its player entity), and
+//! entities are only replicated to that client if they are within a given radius of the point of interest.
+//!
+//! Entities that enter or leave the radius are automatically spawned or despawned on the client.
+//! This uses the same visibility cache as [`Room`](crate::server::room::Room)s, so it only applies to entities
+//! that use [`ReplicationMode::Room`]; you should not add these entities to rooms as well.
+//!
+//! Two strategies are available:
+//! - [`DistanceRelevancePlugin`] checks the distance between every entity and every point of interest
+//! - [`GridRelevancePlugin`] partitions the world into a grid of cells; clients are subscribed to the cells around
+//!   their point of interest. Only the entities that change cell and the clients that change subscriptions are
+//!   processed, which scales much better to thousands of entities.
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::math::{IVec3, Vec3};
+use bevy::prelude::{
+    App, Changed, Component, DetectChanges, Entity, GlobalTransform, IntoSystemConfigs, Local, Or,
+    Plugin, PostUpdate, Query, Ref, Reflect, RemovedComponents, Res, ResMut, Resource, With,
+};
+use bevy::utils::{HashMap, HashSet};
+use tracing::trace;
+
+use crate::connection::id::ClientId;
+use crate::protocol::Protocol;
+use crate::server::room::RoomSystemSets;
+use crate::shared::replication::components::{Replicate, ReplicationMode};
+
+/// Marks the entity around which entities are replicated to the client `client_id`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct PointOfInterest {
+    pub client_id: ClientId,
+}
+
+/// Configuration of the [`DistanceRelevancePlugin`]; can be modified at runtime
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct DistanceRelevanceConfig {
+    /// Entities further than this distance from a client's [`PointOfInterest`] are not replicated to that client
+    pub radius: f32,
+}
+
+/// Plugin that replicates entities to a client only if they are close to the client's [`PointOfInterest`]
+pub struct DistanceRelevancePlugin<P: Protocol> {
+    config: DistanceRelevanceConfig,
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P: Protocol> DistanceRelevancePlugin<P> {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            config: DistanceRelevanceConfig { radius },
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol> Plugin for DistanceRelevancePlugin<P> {
+    fn build(&self, app: &mut App) {
+        // REFLECTION
+        app.register_type::<PointOfInterest>()
+            .register_type::<DistanceRelevanceConfig>();
+        // RESOURCES
+        app.insert_resource(self.config);
+        // SYSTEMS
+        app.add_systems(
+            PostUpdate,
+            update_relevance::<P>.in_set(RoomSystemSets::UpdateReplicationCaches),
+        );
+    }
+}
+
+/// Update the visibility of each entity for each client, depending on the distance to the client's points of interest.
+///
+/// An entity is relevant to a client if it is close to any of the client's points of interest.
+/// If no point of interest changed, only the entities that moved are updated.
+#[allow(clippy::type_complexity)]
+fn update_relevance<P: Protocol>(
+    config: Res<DistanceRelevanceConfig>,
+    points_of_interest: Query<(&PointOfInterest, &GlobalTransform)>,
+    changed_points_of_interest: Query<
+        (),
+        (
+            With<PointOfInterest>,
+            Or<(Changed<PointOfInterest>, Changed<GlobalTransform>)>,
+        ),
+    >,
+    mut removed_points_of_interest: RemovedComponents<PointOfInterest>,
+    // clients that had a point of interest during the previous run
+    mut clients: Local<HashSet<ClientId>>,
+    mut query: Query<(Ref<GlobalTransform>, &mut Replicate<P>)>,
+) {
+    let radius_squared = config.radius * config.radius;
+    let mut centers: HashMap<ClientId, Vec<Vec3>> = HashMap::default();
+    for (point_of_interest, center) in points_of_interest.iter() {
+        centers
+            .entry(point_of_interest.client_id)
+            .or_default()
+            .push(center.translation());
+    }
+    // clients that don't have any point of interest anymore lose all the entities
+    let lost_clients: Vec<ClientId> = clients
+        .iter()
+        .filter(|client_id| !centers.contains_key(*client_id))
+        .copied()
+        .collect();
+    *clients = centers.keys().copied().collect();
+    // always drain the removal events, otherwise they would be reported again on the next run
+    let points_of_interest_removed = removed_points_of_interest.read().count() > 0;
+    let points_of_interest_changed =
+        config.is_changed() || !changed_points_of_interest.is_empty() || points_of_interest_removed;
+
+    for (transform, mut replicate) in query.iter_mut() {
+        if replicate.replication_mode != ReplicationMode::Room {
+            continue;
+        }
+        if !points_of_interest_changed && !transform.is_changed() {
+            continue;
+        }
+        let position = transform.translation();
+        for client_id in lost_clients.iter() {
+            if replicate.is_visible(*client_id) {
+                replicate.set_visibility(*client_id, false);
+            }
+        }
+        for (client_id, centers) in centers.iter() {
+            let is_relevant = centers
+                .iter()
+                .any(|center| position.distance_squared(*center) <= radius_squared);
+            if replicate.is_visible(*client_id) != is_relevant {
+                trace!(?client_id, ?is_relevant, "entity relevance changed");
+                replicate.set_visibility(*client_id, is_relevant);
+            }
+        }
+    }
+}
+
+/// Configuration of the [`GridRelevancePlugin`]
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct GridRelevanceConfig {
+    /// Size of each (cubic) cell of the grid
+    pub cell_size: f32,
+    /// Clients are subscribed to all the cells that are at most `view_distance` cells away from the cell of their
+    /// [`PointOfInterest`] (on each axis)
+    pub view_distance: u32,
+}
+
+impl GridRelevanceConfig {
+    fn cell(&self, transform: &GlobalTransform) -> IVec3 {
+        (transform.translation() / self.cell_size)
+            .floor()
+            .as_ivec3()
+    }
+
+    fn cells_around(&self, center: IVec3) -> HashSet<IVec3> {
+        let d = self.view_distance as i32;
+        let mut cells = HashSet::default();
+        for x in -d..=d {
+            for y in -d..=d {
+                for z in -d..=d {
+                    cells.insert(center + IVec3::new(x, y, z));
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// Plugin that replicates entities to a client only if they are in a grid cell close to the client's [`PointOfInterest`]
+pub struct GridRelevancePlugin<P: Protocol> {
+    config: GridRelevanceConfig,
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P: Protocol> GridRelevancePlugin<P> {
+    pub fn new(cell_size: f32, view_distance: u32) -> Self {
+        Self {
+            config: GridRelevanceConfig {
+                cell_size,
+                view_distance,
+            },
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol> Plugin for GridRelevancePlugin<P> {
+    fn build(&self, app: &mut App) {
+        // REFLECTION
+        app.register_type::<PointOfInterest>()
+            .register_type::<GridRelevanceConfig>();
+        // RESOURCES
+        app.insert_resource(self.config)
+            .init_resource::<RelevanceGrid>();
+        // SYSTEMS
+        app.add_systems(
+            PostUpdate,
+            update_grid_relevance::<P>.in_set(RoomSystemSets::UpdateReplicationCaches),
+        );
+    }
+}
+
+/// Spatial index of the replicated entities and of the client subscriptions
+#[derive(Resource, Default, Debug)]
+pub(crate) struct RelevanceGrid {
+    /// Entities in each cell
+    cells: HashMap<IVec3, EntityHashSet>,
+    /// Cell of each entity
+    entity_cells: EntityHashMap<IVec3>,
+    /// Cells that each client is subscribed to, with the number of points of interest of the client that
+    /// are subscribed to the cell
+    subscriptions: HashMap<ClientId, HashMap<IVec3, u32>>,
+    /// Client and cell of each point of interest entity
+    points_of_interest: EntityHashMap<(ClientId, IVec3)>,
+}
+
+impl RelevanceGrid {
+    /// Subscribe a client to the cells, and return the cells that the client was not subscribed to yet
+    fn subscribe(&mut self, client_id: ClientId, cells: HashSet<IVec3>) -> Vec<IVec3> {
+        let subscriptions = self.subscriptions.entry(client_id).or_default();
+        let mut gained = vec![];
+        for cell in cells {
+            let count = subscriptions.entry(cell).or_default();
+            *count += 1;
+            if *count == 1 {
+                gained.push(cell);
+            }
+        }
+        gained
+    }
+
+    /// Unsubscribe a client from the cells, and return the cells that the client is not subscribed to anymore
+    fn unsubscribe(&mut self, client_id: ClientId, cells: HashSet<IVec3>) -> Vec<IVec3> {
+        let Some(subscriptions) = self.subscriptions.get_mut(&client_id) else {
+            return vec![];
+        };
+        let mut lost = vec![];
+        for cell in cells {
+            let Some(count) = subscriptions.get_mut(&cell) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                subscriptions.remove(&cell);
+                lost.push(cell);
+            }
+        }
+        if subscriptions.is_empty() {
+            self.subscriptions.remove(&client_id);
+        }
+        lost
+    }
+}
+
+/// Update the grid with the entities and points of interest that moved, and update the visibility of the
+/// entities whose cell is gained or lost by a client
+#[allow(clippy::type_complexity)]
+fn update_grid_relevance<P: Protocol>(
+    config: Res<GridRelevanceConfig>,
+    mut grid: ResMut<RelevanceGrid>,
+    points_of_interest: Query<
+        (Entity, &PointOfInterest, &GlobalTransform),
+        Or<(Changed<PointOfInterest>, Changed<GlobalTransform>)>,
+    >,
+    mut removed_points_of_interest: RemovedComponents<PointOfInterest>,
+    moved: Query<Entity, (Changed<GlobalTransform>, With<Replicate<P>>)>,
+    mut removed: RemovedComponents<Replicate<P>>,
+    mut query: Query<(&GlobalTransform, &mut Replicate<P>)>,
+) {
+    let grid = grid.as_mut();
+    // 1. clients that change subscriptions gain or lose the entities in the corresponding cells
+    let mut set_cells_visibility =
+        |grid: &RelevanceGrid, client_id: ClientId, cells: &[IVec3], visible: bool| {
+            for entity in cells
+                .iter()
+                .filter_map(|cell| grid.cells.get(cell))
+                .flatten()
+            {
+                if let Ok((_, mut replicate)) = query.get_mut(*entity) {
+                    if replicate.is_visible(client_id) != visible {
+                        replicate.set_visibility(client_id, visible);
+                    }
+                }
+            }
+        };
+    for entity in removed_points_of_interest.read() {
+        if let Some((client_id, cell)) = grid.points_of_interest.remove(&entity) {
+            let lost = grid.unsubscribe(client_id, config.cells_around(cell));
+            set_cells_visibility(grid, client_id, &lost, false);
+        }
+    }
+    for (entity, point_of_interest, transform) in points_of_interest.iter() {
+        let client_id = point_of_interest.client_id;
+        let cell = config.cell(transform);
+        let previous = grid.points_of_interest.insert(entity, (client_id, cell));
+        // the subscriptions only change when the point of interest changes cell (or client)
+        if previous == Some((client_id, cell)) {
+            continue;
+        }
+        // subscribe to the new cells before unsubscribing from the old ones, so that the cells that are
+        // in both are not lost and gained again
+        let gained = grid.subscribe(client_id, config.cells_around(cell));
+        set_cells_visibility(grid, client_id, &gained, true);
+        if let Some((previous_client_id, previous_cell)) = previous {
+            let lost = grid.unsubscribe(previous_client_id, config.cells_around(previous_cell));
+            set_cells_visibility(grid, previous_client_id, &lost, false);
+        }
+    }
+
+    // 2. entities that change cell are gained or lost by the clients subscribed to the cell
+    for entity in removed.read() {
+        if let Some(cell) = grid.entity_cells.remove(&entity) {
+            if let Some(entities) = grid.cells.get_mut(&cell) {
+                entities.remove(&entity);
+            }
+        }
+    }
+    for entity in moved.iter() {
+        let Ok((transform, mut replicate)) = query.get_mut(entity) else {
+            continue;
+        };
+        if replicate.replication_mode != ReplicationMode::Room {
+            continue;
+        }
+        let cell = config.cell(transform);
+        if let Some(old_cell) = grid.entity_cells.insert(entity, cell) {
+            if old_cell == cell {
+                continue;
+            }
+            if let Some(entities) = grid.cells.get_mut(&old_cell) {
+                entities.remove(&entity);
+            }
+        }
+        grid.cells.entry(cell).or_default().insert(entity);
+        for (client_id, cells) in grid.subscriptions.iter() {
+            let is_relevant = cells.contains_key(&cell);
+            if replicate.is_visible(*client_id) != is_relevant {
+                trace!(?client_id, ?is_relevant, "entity relevance changed");
+                replicate.set_visibility(*client_id, is_relevant);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Transform, World};
+
+    use crate::server::room::ClientVisibility;
+    use crate::tests::protocol::Replicate;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_distance_relevance() {
+        let mut world = World::new();
+        world.insert_resource(DistanceRelevanceConfig { radius: 10.0 });
+        let client_id = ClientId::Netcode(0);
+        world.spawn((
+            PointOfInterest { client_id },
+            GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
+        ));
+        let replicate = Replicate {
+            replication_mode: ReplicationMode::Room,
+            ..Default::default()
+        };
+        let near = world
+            .spawn((
+                GlobalTransform::from(Transform::from_xyz(5.0, 0.0, 0.0)),
+                replicate.clone(),
+            ))
+            .id();
+        let far = world
+            .spawn((
+                GlobalTransform::from(Transform::from_xyz(50.0, 0.0, 0.0)),
+                replicate.clone(),
+            ))
+            .id();
+
+        world.run_system_once(update_relevance::<MyProtocol>);
+        let visibility = |world: &World, entity| {
+            world
+                .get::<Replicate>(entity)
+                .unwrap()
+                .replication_clients_cache
+                .get(&client_id)
+                .copied()
+        };
+        assert_eq!(visibility(&world, near), Some(ClientVisibility::Gained));
+        assert_eq!(visibility(&world, far), None);
+
+        // the entity was replicated, then leaves the radius
+        world
+            .get_mut::<Replicate>(near)
+            .unwrap()
+            .replication_clients_cache
+            .insert(client_id, ClientVisibility::Maintained);
+        *world.get_mut::<GlobalTransform>(near).unwrap() =
+            GlobalTransform::from(Transform::from_xyz(20.0, 0.0, 0.0));
+        world.run_system_once(update_relevance::<MyProtocol>);
+        assert_eq!(visibility(&world, near), Some(ClientVisibility::Lost));
+    }
+
+    #[test]
+    fn test_distance_relevance_multiple_points_of_interest() {
+        let mut world = World::new();
+        world.insert_resource(DistanceRelevanceConfig { radius: 10.0 });
+        let client_id = ClientId::Netcode(0);
+        world.spawn((
+            PointOfInterest { client_id },
+            GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
+        ));
+        world.spawn((
+            PointOfInterest { client_id },
+            GlobalTransform::from(Transform::from_xyz(100.0, 0.0, 0.0)),
+        ));
+        let replicate = Replicate {
+            replication_mode: ReplicationMode::Room,
+            ..Default::default()
+        };
+        let entities = [0.0, 100.0].map(|x| {
+            world
+                .spawn((
+                    GlobalTransform::from(Transform::from_xyz(x, 0.0, 0.0)),
+                    replicate.clone(),
+                ))
+                .id()
+        });
+
+        // the entities close to any of the client's points of interest are relevant
+        world.run_system_once(update_relevance::<MyProtocol>);
+        for entity in entities {
+            assert_eq!(
+                world
+                    .get::<Replicate>(entity)
+                    .unwrap()
+                    .replication_clients_cache
+                    .get(&client_id),
+                Some(&ClientVisibility::Gained)
+            );
+        }
+    }
+
+    #[test]
+    fn test_grid_relevance() {
+        let mut world = World::new();
+        world.insert_resource(GridRelevanceConfig {
+            cell_size: 10.0,
+            view_distance: 1,
+        });
+        world.init_resource::<RelevanceGrid>();
+        let client_id = ClientId::Netcode(0);
+        let point_of_interest = world
+            .spawn((
+                PointOfInterest { client_id },
+                GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
+            ))
+            .id();
+        let replicate = Replicate {
+            replication_mode: ReplicationMode::Room,
+            ..Default::default()
+        };
+        let near = world
+            .spawn((
+                GlobalTransform::from(Transform::from_xyz(15.0, 0.0, 0.0)),
+                replicate.clone(),
+            ))
+            .id();
+        let far = world
+            .spawn((
+                GlobalTransform::from(Transform::from_xyz(55.0, 0.0, 0.0)),
+                replicate.clone(),
+            ))
+            .id();
+
+        world.run_system_once(update_grid_relevance::<MyProtocol>);
+        let visibility = |world: &World, entity| {
+            world
+                .get::<Replicate>(entity)
+                .unwrap()
+                .replication_clients_cache
+                .get(&client_id)
+                .copied()
+        };
+        assert_eq!(visibility(&world, near), Some(ClientVisibility::Gained));
+        assert_eq!(visibility(&world, far), None);
+
+        // the point of interest moves next to the far entity
+        *world.get_mut::<GlobalTransform>(point_of_interest).unwrap() =
+            GlobalTransform::from(Transform::from_xyz(50.0, 0.0, 0.0));
+        world.run_system_once(update_grid_relevance::<MyProtocol>);
+        assert_eq!(visibility(&world, near), None);
+        assert_eq!(visibility(&world, far), Some(ClientVisibility::Gained));
+
+        // the far entity moves out of the view
+        *world.get_mut::<GlobalTransform>(far).unwrap() =
+            GlobalTransform::from(Transform::from_xyz(100.0, 0.0, 0.0));
+        world.run_system_once(update_grid_relevance::<MyProtocol>);
+        assert_eq!(visibility(&world, far), None);
+    }
+
+    #[test]
+    fn test_grid_relevance_change_client() {
+        let mut world = World::new();
+        world.insert_resource(GridRelevanceConfig {
+            cell_size: 10.0,
+            view_distance: 1,
+        });
+        world.init_resource::<RelevanceGrid>();
+        let client_1 = ClientId::Netcode(1);
+        let client_2 = ClientId::Netcode(2);
+        // the client has two points of interest with overlapping views
+        let point_of_interest = world
+            .spawn((
+                PointOfInterest {
+                    client_id: client_1,
+                },
+                GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
+            ))
+            .id();
+        world.spawn((
+            PointOfInterest {
+                client_id: client_1,
+            },
+            GlobalTransform::from(Transform::from_xyz(10.0, 0.0, 0.0)),
+        ));
+        let replicate = Replicate {
+            replication_mode: ReplicationMode::Room,
+            ..Default::default()
+        };
+        let entities = [-5.0, 15.0].map(|x| {
+            world
+                .spawn((
+                    GlobalTransform::from(Transform::from_xyz(x, 0.0, 0.0)),
+                    replicate.clone(),
+                ))
+                .id()
+        });
+        world.run_system_once(update_grid_relevance::<MyProtocol>);
+        let visibility = |world: &World, entity, client_id| {
+            world
+                .get::<Replicate>(entity)
+                .unwrap()
+                .replication_clients_cache
+                .get(&client_id)
+                .copied()
+        };
+        for entity in entities {
+            assert_eq!(
+                visibility(&world, entity, client_1),
+                Some(ClientVisibility::Gained)
+            );
+        }
+
+        // the first point of interest now belongs to another client: client 1 keeps the cells of its
+        // other point of interest, and loses the others
+        world
+            .get_mut::<PointOfInterest>(point_of_interest)
+            .unwrap()
+            .client_id = client_2;
+        world.run_system_once(update_grid_relevance::<MyProtocol>);
+        assert_eq!(visibility(&world, entities[0], client_1), None);
+        assert_eq!(
+            visibility(&world, entities[1], client_1),
+            Some(ClientVisibility::Gained)
+        );
+        assert_eq!(
+            visibility(&world, entities[0], client_2),
+            Some(ClientVisibility::Gained)
+        );
+        assert!(!world
+            .resource::<RelevanceGrid>()
+            .subscriptions
+            .get(&client_1)
+            .unwrap()
+            .contains_key(&IVec3::new(-1, 0, 0)));
+    }
+}
@@ -1,16 +1,22 @@
 use bevy::ecs::query::QueryFilter;
 use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
 
-use crate::_reexport::ServerMarker;
+use crate::_reexport::{FromType, ServerMarker};
 use crate::client::components::Confirmed;
 use crate::client::interpolation::Interpolated;
 use crate::client::prediction::Predicted;
 use crate::connection::client::NetClient;
+use crate::connection::id::ClientId;
 use crate::prelude::client::ClientConnection;
-use crate::prelude::{Mode, PrePredicted, Protocol};
+use crate::prelude::{Authority, Mode, PrePredicted, Protocol};
 use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
+use crate::server::ownership::{
+    despawn_controlled_on_disconnect, update_controlled_entities, ControlledEntities,
+};
 use crate::server::prediction::compute_hash;
+use crate::server::target::{remove_disconnected_from_teams, resolve_dynamic_targets, Teams};
 use crate::shared::replication::components::Replicate;
 use crate::shared::replication::plugin::ReplicationPlugin;
 use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
@@ -21,6 +27,29 @@ pub struct ReplicationConfig {
     /// Set to true to disable replicating this server's entities to clients
     pub enable_send: bool,
     pub enable_receive: bool,
+    /// If true, the initial world state sent to a client that just connected is bundled into a single
+    /// reliable message, instead of one message per replication group.
+    ///
+    /// The client then applies the whole baseline at once, instead of seeing the entities appear
+    /// over several ticks when a client joins a world that contains many entities.
+    pub send_baseline: bool,
+    /// Maximum number of entity spawns sent to a client in a single send interval.
+    ///
+    /// When a client joins a world that contains thousands of entities, the spawns are spread over
+    /// multiple send intervals instead of producing one enormous burst of messages.
+    /// Entities in the same replication group are always spawned together.
+    /// By default there is no limit.
+    pub max_spawns_per_send: Option<usize>,
+    /// If true, the entities that have a [`ControlledBy`](crate::prelude::ControlledBy) component are
+    /// despawned when their controlling client disconnects.
+    pub despawn_controlled_on_disconnect: bool,
+    /// How often the replication updates are gathered and sent to the clients.
+    ///
+    /// This lets the server replicate at a lower rate (for example 20Hz) while messages and inputs are still
+    /// sent every [`SharedConfig::server_send_interval`](crate::prelude::SharedConfig::server_send_interval).
+    /// The updates are only gathered on frames where packets are sent.
+    /// A duration of 0 means that the updates are gathered every time we send packets.
+    pub send_interval: Duration,
 }
 
 impl Default for ReplicationConfig {
@@ -28,6 +57,74 @@ impl Default for ReplicationConfig {
         Self {
             enable_send: true,
             enable_receive: false,
+            send_baseline: false,
+            max_spawns_per_send: None,
+            despawn_controlled_on_disconnect: true,
+            send_interval: Duration::default(),
+        }
+    }
+}
+
+type ComponentValidator<P> = Box<
+    dyn Fn(ClientId, Entity, <P as Protocol>::Components) -> Option<<P as Protocol>::Components>
+        + Send
+        + Sync,
+>;
+
+/// Validation callbacks that run on the components that clients replicate to the server,
+/// before they get applied to the server's World.
+///
+/// Use this to reject or clamp incoming values when the clients are authoritative over some entities
+/// (see [`ReplicationConfig::enable_receive`]).
+#[derive(Resource)]
+pub struct ReplicationValidators<P: Protocol> {
+    validators: HashMap<P::ComponentKinds, ComponentValidator<P>>,
+}
+
+impl<P: Protocol> Default for ReplicationValidators<P> {
+    fn default() -> Self {
+        Self {
+            validators: HashMap::default(),
+        }
+    }
+}
+
+impl<P: Protocol> ReplicationValidators<P> {
+    /// Register a validation callback for the component `C`, which will run for every insert or update
+    /// of `C` received from a client.
+    ///
+    /// The callback receives the id of the client that sent the component, the local entity, and the
+    /// received value, which it can modify in place (for example to clamp it).
+    /// If the callback returns `false`, the insert/update is rejected and not applied to the World.
+    pub fn add_validator<C: Component>(
+        &mut self,
+        validator: impl Fn(ClientId, Entity, &mut C) -> bool + Send + Sync + 'static,
+    ) where
+        P::Components: From<C> + TryInto<C>,
+        P::ComponentKinds: FromType<C>,
+    {
+        let kind = <P::ComponentKinds as FromType<C>>::from_type();
+        self.validators.insert(
+            kind,
+            Box::new(move |client_id, entity, component| {
+                let mut component: C = component.try_into().ok()?;
+                validator(client_id, entity, &mut component).then(|| component.into())
+            }),
+        );
+    }
+
+    /// Run the validation callback for this component, if there is one.
+    /// Returns None if the component was rejected.
+    pub(crate) fn validate(
+        &self,
+        client_id: ClientId,
+        entity: Entity,
+        component: P::Components,
+    ) -> Option<P::Components> {
+        let kind: P::ComponentKinds = (&component).into();
+        match self.validators.get(&kind) {
+            Some(validator) => validator(client_id, entity, component),
+            None => Some(component),
         }
     }
 }
@@ -60,7 +157,12 @@ impl<P: Protocol> Plugin for ServerReplicationPlugin<P> {
                 config.shared.tick.tick_duration,
                 config.replication.enable_send,
                 config.replication.enable_receive,
+                config.replication.send_interval,
             ))
+            // RESOURCES
+            .init_resource::<ReplicationValidators<P>>()
+            .init_resource::<Teams>()
+            .init_resource::<ControlledEntities>()
             // SYSTEM SETS
             .configure_sets(
                 PreUpdate,
@@ -83,6 +185,16 @@ impl<P: Protocol> Plugin for ServerReplicationPlugin<P> {
                     .in_set(InternalReplicationSet::<ServerMarker>::SetPreSpawnedHash),),
             );
 
+        app.add_systems(
+            PostUpdate,
+            (
+                handle_authority_change::<P>,
+                (remove_disconnected_from_teams, resolve_dynamic_targets::<P>).chain(),
+                (update_controlled_entities, despawn_controlled_on_disconnect).chain(),
+            )
+                .before(InternalReplicationSet::<ServerMarker>::All),
+        );
+
         if app.world.resource::<ServerConfig>().shared.mode == Mode::HostServer {
             app.add_systems(
                 PostUpdate,
@@ -103,6 +215,22 @@ pub struct ServerFilter {
     ),
 }
 
+/// Keep track of which client has authority over each entity, so that we stop sending updates to that client
+/// and start accepting its updates instead.
+fn handle_authority_change<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    query: Query<(Entity, &Authority), Changed<Authority>>,
+    mut removed: RemovedComponents<Authority>,
+) {
+    for (entity, authority) in query.iter() {
+        debug!(?entity, ?authority, "authority changed");
+        connection_manager.set_authority(entity, *authority);
+    }
+    for entity in removed.read() {
+        connection_manager.set_authority(entity, Authority::Server);
+    }
+}
+
 /// In HostServer mode, we will add the Predicted/Interpolated components to the server entities
 /// So that client code can still query for them
 fn add_prediction_interpolation_components<P: Protocol>(
@@ -138,3 +266,177 @@ fn add_prediction_interpolation_components<P: Protocol>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Duration;
+
+    use crate::prelude::client::{
+        ClientConnectionManager, InterpolationConfig, PredictionConfig, SyncConfig,
+    };
+    use crate::prelude::{LinkConditionerConfig, SharedConfig, TickConfig};
+    use crate::tests::protocol::Replicate;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_replication_validators() {
+        let mut validators = ReplicationValidators::<MyProtocol>::default();
+        validators.add_validator::<Component1>(|_, _, component| {
+            component.0 = component.0.min(10.0);
+            component.0 >= 0.0
+        });
+        let client_id = ClientId::Netcode(0);
+        let entity = Entity::from_raw(0);
+
+        // values get clamped
+        assert_eq!(
+            validators.validate(
+                client_id,
+                entity,
+                MyComponentsProtocol::Component1(Component1(20.0))
+            ),
+            Some(MyComponentsProtocol::Component1(Component1(10.0)))
+        );
+        // values get rejected
+        assert_eq!(
+            validators.validate(
+                client_id,
+                entity,
+                MyComponentsProtocol::Component1(Component1(-1.0))
+            ),
+            None
+        );
+        // components without validators are accepted
+        assert_eq!(
+            validators.validate(
+                client_id,
+                entity,
+                MyComponentsProtocol::Component2(Component2(-1.0))
+            ),
+            Some(MyComponentsProtocol::Component2(Component2(-1.0)))
+        );
+    }
+
+    // Entities that exist before the client connects are all sent in the initial baseline message
+    #[test]
+    fn test_replication_baseline() {
+        let frame_duration = Duration::from_millis(10);
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let link_conditioner = LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        };
+        let sync_config = SyncConfig::default().speedup_factor(1.0);
+        let prediction_config = PredictionConfig::default().disable(false);
+        let interpolation_config = InterpolationConfig::default();
+        let mut stepper = BevyStepper::from_configs(
+            shared_config,
+            sync_config,
+            prediction_config,
+            interpolation_config,
+            link_conditioner,
+            frame_duration,
+        );
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ConnectionManager<MyProtocol>>()
+            .replication_config
+            .send_baseline = true;
+        // the entities are in different replication groups
+        let server_entities = [
+            stepper
+                .server_app
+                .world
+                .spawn((Component1(0.0), Replicate::default()))
+                .id(),
+            stepper
+                .server_app
+                .world
+                .spawn((Component1(1.0), Replicate::default()))
+                .id(),
+        ];
+        stepper.init();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // the initial actions were sent as a baseline
+        assert!(
+            !stepper
+                .server_app
+                .world
+                .resource::<ConnectionManager<MyProtocol>>()
+                .connection(stepper.client_id())
+                .unwrap()
+                .baseline_pending
+        );
+        for (i, server_entity) in server_entities.into_iter().enumerate() {
+            let client_entity = *stepper
+                .client_app
+                .world
+                .resource::<ClientConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .expect("entity was not replicated to client");
+            assert_eq!(
+                stepper.client_app.world.get::<Component1>(client_entity),
+                Some(&Component1(i as f32))
+            );
+        }
+    }
+
+    // The spawn budget of a client spreads the spawns over multiple send intervals
+    #[test]
+    fn test_client_spawn_budget() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ConnectionManager<MyProtocol>>()
+            .set_max_spawns_per_send(client_id, Some(1))
+            .unwrap();
+        let server_entities: Vec<Entity> = (0..3)
+            .map(|i| {
+                stepper
+                    .server_app
+                    .world
+                    .spawn((Component1(i as f32), Replicate::default()))
+                    .id()
+            })
+            .collect();
+        stepper.frame_step();
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .resource::<ConnectionManager<MyProtocol>>()
+                .pending_spawns(client_id)
+                .unwrap(),
+            2
+        );
+
+        for _ in 0..5 {
+            stepper.frame_step();
+        }
+        for server_entity in server_entities {
+            assert!(stepper
+                .client_app
+                .world
+                .resource::<ClientConnectionManager>()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(server_entity)
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! # Input rewind
+//!
+//! When the inputs of a client arrive after the server already simulated their ticks (see [`LateInputConfig`]),
+//! the server can rewind the entities controlled by that client and re-simulate the late ticks with the
+//! correct inputs, instead of keeping the result of the fallback inputs.
+//!
+//! The components registered with [`InputRewindPlugin`] are restored from their [`LagCompensationHistory`]
+//! (so the entities must be [`LagCompensated`](crate::server::lag_compensation::LagCompensated)), then the
+//! [`FixedMain`] schedule is re-run up to the current tick. The other replicated entities are marked with
+//! [`ExcludedFromRewind`] during the re-simulation, so that the game systems can skip them.
+//!
+//! [`LateInputConfig`]: crate::server::input::LateInputConfig
+use bevy::app::FixedMain;
+use bevy::ecs::event::{Events, ManualEventReader};
+use bevy::prelude::{App, Component, Entity, Local, Plugin, Res, Resource, With, Without, World};
+use tracing::debug;
+
+use crate::connection::id::ClientId;
+use crate::prelude::TickManager;
+use crate::protocol::Protocol;
+use crate::server::connection::ConnectionManager;
+use crate::server::input::LateInputEvent;
+use crate::server::lag_compensation::LagCompensationHistory;
+use crate::shared::replication::components::{ControlledBy, Replicate};
+use crate::shared::tick_manager::Tick;
+
+/// Marker component inserted on the replicated entities that are not re-simulated during an input rewind.
+///
+/// The systems that run in the [`FixedMain`] schedule should skip these entities with a
+/// `Without<ExcludedFromRewind>` filter, otherwise they would be simulated twice for the rewound ticks.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ExcludedFromRewind;
+
+/// Resource that is present while the server re-simulates the ticks of the late inputs of a client
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct InputRewind {
+    /// The client whose late inputs are re-applied
+    pub client_id: ClientId,
+    /// The first tick that is re-simulated
+    pub start_tick: Tick,
+}
+
+/// Run condition that returns true while the server re-simulates the ticks of late inputs
+pub fn is_rewinding(rewind: Option<Res<InputRewind>>) -> bool {
+    rewind.is_some()
+}
+
+/// Functions that restore the registered components of the entities to their value at a given tick
+#[derive(Resource, Default)]
+struct RewindRegistry {
+    restore_fns: Vec<fn(&mut World, &[Entity], Tick)>,
+}
+
+/// Restore the component `C` of the entities controlled by a client when the server rewinds for its late inputs.
+///
+/// The entities must record the history of `C` with a
+/// [`LagCompensationPlugin<C>`](crate::server::lag_compensation::LagCompensationPlugin).
+pub struct InputRewindPlugin<C> {
+    _marker: std::marker::PhantomData<C>,
+}
+
+impl<C> Default for InputRewindPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<C: Component + Clone> Plugin for InputRewindPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RewindRegistry>();
+        app.world
+            .resource_mut::<RewindRegistry>()
+            .restore_fns
+            .push(restore_component::<C>);
+    }
+}
+
+/// Set the component `C` of the entities to its value at the end of `tick`, and drop the history
+/// recorded after that tick (it is recorded again during the re-simulation)
+fn restore_component<C: Component + Clone>(world: &mut World, entities: &[Entity], tick: Tick) {
+    for entity in entities {
+        let Some(mut entity_mut) = world.get_entity_mut(*entity) else {
+            continue;
+        };
+        let Some(mut history) = entity_mut.get_mut::<LagCompensationHistory<C>>() else {
+            continue;
+        };
+        let past = history.get(tick).cloned();
+        history.truncate_after(tick);
+        if let Some(past) = past {
+            entity_mut.insert(past);
+        }
+    }
+}
+
+/// Re-simulate the ticks of the [`LateInputEvent`]s for the entities controlled by the client,
+/// if [`LateInputConfig::rewind`](crate::server::input::LateInputConfig::rewind) is enabled
+pub(crate) fn rewind_late_inputs<P: Protocol>(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<LateInputEvent<P::Input>>>,
+) {
+    let events: Vec<_> = reader
+        .read(world.resource::<Events<LateInputEvent<P::Input>>>())
+        .cloned()
+        .collect();
+    let rewind_enabled = world
+        .resource::<ConnectionManager<P>>()
+        .late_inputs
+        .as_ref()
+        .is_some_and(|config| config.rewind);
+    if !rewind_enabled {
+        return;
+    }
+    for event in events {
+        let Some(start_tick) = event.start_tick() else {
+            continue;
+        };
+        let client_id = event.client_id;
+        world
+            .resource_mut::<ConnectionManager<P>>()
+            .apply_late_inputs(client_id, &event.inputs);
+
+        let controlled: Vec<Entity> = world
+            .query::<(Entity, &ControlledBy)>()
+            .iter(world)
+            .filter(|(_, controlled_by)| controlled_by.0 == client_id)
+            .map(|(entity, _)| entity)
+            .collect();
+        if controlled.is_empty() {
+            continue;
+        }
+        let mut excluded: Vec<Entity> = world
+            .query_filtered::<Entity, (With<Replicate<P>>, Without<ControlledBy>)>()
+            .iter(world)
+            .collect();
+        excluded.extend(
+            world
+                .query::<(Entity, &ControlledBy)>()
+                .iter(world)
+                .filter(|(_, controlled_by)| controlled_by.0 != client_id)
+                .map(|(entity, _)| entity),
+        );
+
+        let tick_manager = world.resource::<TickManager>().clone();
+        let current_tick = tick_manager.tick();
+        // replay every physics step from the first substep of the first late tick up to the current step
+        let num_steps = (current_tick - start_tick) as usize
+            * tick_manager.config.substeps() as usize
+            + tick_manager.substep() as usize
+            + 1;
+        debug!(
+            ?client_id,
+            ?start_tick,
+            ?current_tick,
+            "rewinding the client entities to re-apply late inputs"
+        );
+
+        // restore the state at the end of the tick before the first late input
+        let restore_tick = start_tick - 1;
+        let restore_fns = world
+            .get_resource::<RewindRegistry>()
+            .map(|registry| registry.restore_fns.clone())
+            .unwrap_or_default();
+        for restore_fn in restore_fns {
+            restore_fn(world, &controlled, restore_tick);
+        }
+        for entity in &excluded {
+            world.entity_mut(*entity).insert(ExcludedFromRewind);
+        }
+        world.insert_resource(InputRewind {
+            client_id,
+            start_tick,
+        });
+
+        world
+            .resource_mut::<TickManager>()
+            .set_tick_to(restore_tick);
+        for _ in 0..num_steps {
+            world.run_schedule(FixedMain);
+        }
+
+        *world.resource_mut::<TickManager>() = tick_manager;
+        world.remove_resource::<InputRewind>();
+        for entity in excluded {
+            if let Some(mut entity_mut) = world.get_entity_mut(entity) {
+                entity_mut.remove::<ExcludedFromRewind>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{
+        EventReader, FixedFirst, FixedPreUpdate, FixedUpdate, IntoSystemConfigs, Query, ResMut,
+    };
+    use bevy::utils::Duration;
+
+    use crate::inputs::native::input_buffer::{InputData, InputMessage};
+    use crate::prelude::{PingConfig, TickConfig};
+    use crate::server::config::PacketConfig;
+    use crate::server::events::InputEvent;
+    use crate::server::input::{
+        write_input_event, LateInputConfig, MissingInputEvent, MissingInputStrategy,
+    };
+    use crate::server::lag_compensation::{LagCompensated, LagCompensationPlugin};
+    use crate::server::replication::ReplicationConfig;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[derive(Component, Debug, Clone, PartialEq)]
+    struct Position(i16);
+
+    #[test]
+    fn test_rewind_late_inputs() {
+        let client_id = ClientId::Netcode(1);
+        let mut app = App::new();
+        app.insert_resource(TickManager::from_config(TickConfig::new(
+            Duration::from_millis(10),
+        )));
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            None,
+            None,
+            Some(
+                LateInputConfig::default()
+                    .with_max_late_ticks(3)
+                    .with_rewind(true),
+            ),
+            None,
+        );
+        manager.add(client_id);
+        app.insert_resource(manager);
+        app.init_resource::<MissingInputStrategy<MyInput>>();
+        app.add_event::<InputEvent<MyInput>>();
+        app.add_event::<MissingInputEvent>();
+        app.add_event::<LateInputEvent<MyInput>>();
+        app.add_plugins((
+            LagCompensationPlugin::<Position>::new(10),
+            InputRewindPlugin::<Position>::default(),
+        ));
+        app.add_systems(FixedFirst, |mut tick_manager: ResMut<TickManager>| {
+            tick_manager.increment_tick()
+        });
+        app.add_systems(FixedPreUpdate, write_input_event::<MyProtocol>);
+        // the controlled entity moves by its input, the other entity moves by 1 every tick
+        app.add_systems(
+            FixedUpdate,
+            (
+                |mut events: EventReader<InputEvent<MyInput>>,
+                 mut query: Query<(&mut Position, &ControlledBy)>| {
+                    for event in events.read() {
+                        let Some(MyInput(delta)) = event.input() else {
+                            continue;
+                        };
+                        for (mut position, controlled_by) in query.iter_mut() {
+                            if controlled_by.0 == *event.context() {
+                                position.0 += delta;
+                            }
+                        }
+                    }
+                },
+                |mut query: Query<
+                    &mut Position,
+                    (Without<ControlledBy>, Without<ExcludedFromRewind>),
+                >| {
+                    for mut position in query.iter_mut() {
+                        position.0 += 1;
+                    }
+                },
+            ),
+        );
+        let controlled = app
+            .world
+            .spawn((
+                ControlledBy(client_id),
+                LagCompensated,
+                Replicate::<MyProtocol>::default(),
+                Position(0),
+            ))
+            .id();
+        let other = app
+            .world
+            .spawn((Replicate::<MyProtocol>::default(), Position(0)))
+            .id();
+
+        // simulate the ticks 1 to 5 without any input from the client
+        for _ in 0..5 {
+            app.world.run_schedule(FixedMain);
+        }
+        assert_eq!(app.world.resource::<TickManager>().tick(), Tick(5));
+        assert_eq!(app.world.get::<Position>(controlled), Some(&Position(0)));
+
+        // the inputs for the ticks 3 to 5 arrive late
+        let mut manager = app.world.resource_mut::<ConnectionManager<MyProtocol>>();
+        let connection = manager.connection_mut(client_id).unwrap();
+        connection.receive_input_message(
+            InputMessage {
+                end_tick: Tick(5),
+                inputs: vec![InputData::Input(MyInput(1)); 3],
+                sub_ticks: vec![],
+            },
+            |_, input| Some(input),
+        );
+        let event = connection
+            .take_late_inputs(
+                client_id,
+                Some(&LateInputConfig::default().with_max_late_ticks(3)),
+                Tick(5),
+            )
+            .unwrap();
+        app.world.send_event(event);
+        app.world.run_system_once(rewind_late_inputs::<MyProtocol>);
+
+        // the controlled entity was re-simulated with the late inputs, the other entity was not
+        assert_eq!(app.world.get::<Position>(controlled), Some(&Position(3)));
+        assert_eq!(app.world.get::<Position>(other), Some(&Position(5)));
+        assert_eq!(app.world.resource::<TickManager>().tick(), Tick(5));
+        assert!(app.world.get::<ExcludedFromRewind>(other).is_none());
+        assert!(app.world.get_resource::<InputRewind>().is_none());
+    }
+}
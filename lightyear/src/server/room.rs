@@ -3,7 +3,7 @@
 //! This module contains the room system, which is used to perform interest management. (being able to predict certain entities to certain clients only).
 //! You can also find more information in the [book](https://cbournhonesque.github.io/lightyear/book/concepts/advanced_replication/interest_management.html).
 use bevy::app::App;
-use bevy::ecs::entity::EntityHash;
+use bevy::ecs::entity::{Entities, EntityHash};
 use bevy::prelude::{
     Entity, IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, Query, RemovedComponents,
     Res, ResMut, Resource, SystemSet,
@@ -15,8 +15,10 @@ use tracing::{info, trace};
 use crate::_reexport::ServerMarker;
 use crate::connection::id::ClientId;
 use crate::protocol::Protocol;
+use crate::server::connection::ConnectionManager;
 use crate::shared::replication::components::{DespawnTracker, Replicate};
-use crate::shared::sets::InternalReplicationSet;
+use crate::shared::replication::plugin::ReplicationSendTimer;
+use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 use crate::shared::time_manager::is_server_ready_to_send;
 use crate::utils::wrapping_id::wrapping_id;
 
@@ -84,6 +86,42 @@ impl RoomManager {
     }
 }
 
+/// Resource that lets you manually control whether an entity is visible to a given client,
+/// independently of the [`Room`]s.
+///
+/// The entity will be spawned on the client when it gains visibility, and despawned when it loses it.
+/// Gaining visibility overrides the `replication_target` of the entity.
+/// This only applies to entities that use [`ReplicationMode::Room`](crate::prelude::ReplicationMode::Room):
+/// the changes for the other entities are ignored.
+///
+/// The changes are applied after the room events, so they take precedence over them. The changes requested
+/// for an entity that does not have a [`Replicate`] component yet are applied once it is replicated.
+/// You can also modify the visibility directly on the component with [`Replicate::set_visibility`].
+#[derive(Default, Resource, Debug)]
+pub struct VisibilityManager {
+    changes: EntityHashMap<Entity, HashMap<ClientId, bool>>,
+}
+
+impl VisibilityManager {
+    /// Make the `entity` visible or invisible to the client `client_id`
+    pub fn set_visibility(&mut self, entity: Entity, client_id: ClientId, visible: bool) {
+        self.changes
+            .entry(entity)
+            .or_default()
+            .insert(client_id, visible);
+    }
+
+    /// Make the `entity` visible to the client `client_id`
+    pub fn gain_visibility(&mut self, entity: Entity, client_id: ClientId) {
+        self.set_visibility(entity, client_id, true);
+    }
+
+    /// Make the `entity` invisible to the client `client_id`
+    pub fn lose_visibility(&mut self, entity: Entity, client_id: ClientId) {
+        self.set_visibility(entity, client_id, false);
+    }
+}
+
 /// Plugin used to handle interest managements via [`Room`]s
 pub struct RoomPlugin<P: Protocol> {
     _marker: std::marker::PhantomData<P>,
@@ -112,6 +150,7 @@ impl<P: Protocol> Plugin for RoomPlugin<P> {
     fn build(&self, app: &mut App) {
         // RESOURCES
         app.init_resource::<RoomManager>();
+        app.init_resource::<VisibilityManager>();
         // SETS
         app.configure_sets(
             PostUpdate,
@@ -121,6 +160,8 @@ impl<P: Protocol> Plugin for RoomPlugin<P> {
                     RoomSystemSets::UpdateReplicationCaches,
                     InternalReplicationSet::<ServerMarker>::All,
                     RoomSystemSets::RoomBookkeeping,
+                    // the bookkeeping must run before the replication timer is reset
+                    InternalMainSet::<ServerMarker>::SendPackets,
                 )
                     .chain(),
                 // the room systems can run every send_interval
@@ -135,7 +176,11 @@ impl<P: Protocol> Plugin for RoomPlugin<P> {
         app.add_systems(
             PostUpdate,
             (
-                update_entity_replication_cache::<P>
+                (
+                    update_entity_replication_cache::<P>,
+                    apply_visibility_changes::<P>,
+                )
+                    .chain()
                     .in_set(RoomSystemSets::UpdateReplicationCaches),
                 (clear_entity_replication_cache::<P>, clean_entity_despawns)
                     .in_set(RoomSystemSets::RoomBookkeeping),
@@ -194,6 +239,17 @@ impl RoomManager {
         self.room(room_id).has_entity(entity)
     }
 
+    /// Returns true if the two clients are in at least one common [`Room`]
+    pub(crate) fn share_room(&self, client_a: ClientId, client_b: ClientId) -> bool {
+        match (
+            self.data.client_to_rooms.get(&client_a),
+            self.data.client_to_rooms.get(&client_b),
+        ) {
+            (Some(rooms_a), Some(rooms_b)) => !rooms_a.is_disjoint(rooms_b),
+            _ => false,
+        }
+    }
+
     /// Get a room by its [`RoomId`]
     pub fn get_room(&self, room_id: RoomId) -> Option<&Room> {
         self.data.rooms.get(&room_id)
@@ -529,13 +585,23 @@ fn update_entity_replication_cache<P: Protocol>(
 /// After replication, update the Replication Cache:
 /// - Visibility Gained becomes Visibility Maintained
 /// - Visibility Lost gets removed from the cache
-fn clear_entity_replication_cache<P: Protocol>(mut query: Query<&mut Replicate<P>>) {
+///
+/// The entity spawns are only sent when the replication updates are gathered (every `send_interval`),
+/// so a Gained visibility is kept until then. The despawns are sent every frame, so a Lost visibility
+/// can always be removed.
+fn clear_entity_replication_cache<P: Protocol>(
+    timer: Option<Res<ReplicationSendTimer<ConnectionManager<P>>>>,
+    mut query: Query<&mut Replicate<P>>,
+) {
+    let replicated = timer.map_or(true, |timer| timer.is_ready());
     for mut replicate in query.iter_mut() {
         replicate
             .replication_clients_cache
             .retain(|_, visibility| match visibility {
                 ClientVisibility::Gained => {
-                    *visibility = ClientVisibility::Maintained;
+                    if replicated {
+                        *visibility = ClientVisibility::Maintained;
+                    }
                     true
                 }
                 ClientVisibility::Lost => false,
@@ -544,6 +610,27 @@ fn clear_entity_replication_cache<P: Protocol>(mut query: Query<&mut Replicate<P
     }
 }
 
+/// Apply the visibility changes that were requested via the [`VisibilityManager`]
+///
+/// The changes for entities that are not replicated yet are kept until the entity gets a [`Replicate`]
+/// component, or is despawned.
+fn apply_visibility_changes<P: Protocol>(
+    mut manager: ResMut<VisibilityManager>,
+    entities: &Entities,
+    mut query: Query<&mut Replicate<P>>,
+) {
+    manager.changes.retain(|entity, changes| {
+        let Ok(mut replicate) = query.get_mut(*entity) else {
+            return entities.contains(*entity);
+        };
+        for (client_id, visible) in changes.drain() {
+            trace!(?entity, ?client_id, ?visible, "manual visibility change");
+            replicate.set_visibility(client_id, visible);
+        }
+        false
+    });
+}
+
 /// Clear out the room metadata for any entity that was ever replicated
 fn clean_entity_despawns(
     mut room_manager: ResMut<RoomManager>,
@@ -1167,5 +1254,161 @@ mod tests {
         );
     }
 
+    #[test]
+    // the entity is not in any room, but we manually make it visible then invisible to the client
+    fn test_manual_visibility() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn(Replicate {
+                replication_mode: ReplicationMode::Room,
+                ..Default::default()
+            })
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .is_none());
+
+        // Gain visibility
+        stepper
+            .server_app
+            .world
+            .resource_mut::<VisibilityManager>()
+            .gain_visibility(server_entity, client_id);
+        stepper.frame_step();
+        assert!(stepper
+            .server_app
+            .world
+            .entity(server_entity)
+            .get::<Replicate>()
+            .unwrap()
+            .is_visible(client_id));
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .expect("entity was not replicated to client");
+
+        // Lose visibility
+        stepper
+            .server_app
+            .world
+            .resource_mut::<VisibilityManager>()
+            .lose_visibility(server_entity, client_id);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper.client_app.world.get_entity(client_entity).is_none());
+    }
+
+    #[test]
+    fn test_share_room() {
+        let mut room_manager = RoomManager::default();
+        let client_a = ClientId::Netcode(1);
+        let client_b = ClientId::Netcode(2);
+        room_manager.add_client(client_a, RoomId(0));
+        room_manager.add_client(client_b, RoomId(1));
+        assert!(!room_manager.share_room(client_a, client_b));
+
+        room_manager.add_client(client_b, RoomId(0));
+        assert!(room_manager.share_room(client_a, client_b));
+
+        room_manager.remove_client(client_a, RoomId(0));
+        assert!(!room_manager.share_room(client_a, client_b));
+    }
+
+    #[test]
+    // the visibility is set before the entity is replicated
+    fn test_manual_visibility_before_replicate() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+        let server_entity = stepper.server_app.world.spawn_empty().id();
+        stepper
+            .server_app
+            .world
+            .resource_mut::<VisibilityManager>()
+            .gain_visibility(server_entity, client_id);
+        stepper.frame_step();
+        stepper
+            .server_app
+            .world
+            .entity_mut(server_entity)
+            .insert(Replicate {
+                replication_mode: ReplicationMode::Room,
+                ..Default::default()
+            });
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .is_some());
+    }
+
+    #[test]
+    /// A visibility gain must be kept until the replication updates are gathered, otherwise
+    /// the entity is never spawned for the client
+    fn test_visibility_gained_kept_until_replication() {
+        use crate::shared::replication::plugin::ReplicationSendTimer;
+        use bevy::prelude::World;
+        use bevy::utils::Duration;
+
+        let mut world = World::new();
+        // the timer is not ready: the replication updates are not gathered this frame
+        world.insert_resource(ReplicationSendTimer::<ServerConnectionManager>::new(
+            Duration::from_millis(100),
+        ));
+        let gained = ClientId::Netcode(1);
+        let lost = ClientId::Netcode(2);
+        let entity = world
+            .spawn(Replicate {
+                replication_mode: ReplicationMode::Room,
+                replication_clients_cache: HashMap::from([
+                    (gained, ClientVisibility::Gained),
+                    (lost, ClientVisibility::Lost),
+                ]),
+                ..Default::default()
+            })
+            .id();
+
+        world.run_system_once(clear_entity_replication_cache::<MyProtocol>);
+        assert_eq!(
+            world
+                .entity(entity)
+                .get::<Replicate>()
+                .unwrap()
+                .replication_clients_cache,
+            HashMap::from([(gained, ClientVisibility::Gained)])
+        );
+
+        // once the replication ran, the visibility is maintained
+        world.remove_resource::<ReplicationSendTimer<ServerConnectionManager>>();
+        world.run_system_once(clear_entity_replication_cache::<MyProtocol>);
+        assert_eq!(
+            world
+                .entity(entity)
+                .get::<Replicate>()
+                .unwrap()
+                .replication_clients_cache,
+            HashMap::from([(gained, ClientVisibility::Maintained)])
+        );
+    }
+
     // TODO: check that entity despawn/client disconnect cleans the room metadata
 }
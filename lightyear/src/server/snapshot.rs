@@ -0,0 +1,146 @@
+//! # World snapshots
+//!
+//! Save the replicated state of the server world (every entity with a [`Replicate`] component, along with
+//! all its protocol components) to bytes, and restore it later; for example to persist a game session
+//! across server restarts.
+use bevy::ecs::entity::{EntityHashMap, MapEntities};
+use bevy::prelude::{Entity, EntityRef, With, World};
+
+use crate::_reexport::FromType;
+use crate::prelude::PreSpawnedPlayerObject;
+use crate::protocol::component::ComponentProtocol;
+use crate::protocol::Protocol;
+use crate::serialize::reader::ReadBuffer;
+use crate::serialize::wordbuffer::reader::ReadWordBuffer;
+use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+use crate::serialize::writer::WriteBuffer;
+use crate::shared::replication::components::{
+    PrePredicted, Replicate, ShouldBeInterpolated, ShouldBePredicted, SyncModeOverride,
+};
+use crate::shared::replication::entity_map::RemoteEntityMap;
+
+/// Initial capacity of the buffer used to serialize a snapshot; it grows as needed
+const SNAPSHOT_BUFFER_CAPACITY: usize = 4096;
+
+/// The protocol components of every replicated entity of the server world at a given point in time
+///
+/// The [`Replicate`] component itself is not part of the snapshot, as it contains
+/// connection-specific information (rooms, client ids, etc.). It should be added back
+/// after the snapshot is loaded.
+#[derive(Debug)]
+pub struct WorldSnapshot<P: Protocol> {
+    entities: Vec<(Entity, Vec<P::Components>)>,
+}
+
+impl<P: Protocol> WorldSnapshot<P> {
+    /// Take a snapshot of all the entities that have a [`Replicate`] component
+    pub fn save(world: &mut World) -> Self {
+        // the prediction/interpolation markers are added by the replication systems, they should not be saved
+        let excluded_kinds = [
+            <P::ComponentKinds as FromType<ShouldBePredicted>>::from_type(),
+            <P::ComponentKinds as FromType<PrePredicted>>::from_type(),
+            <P::ComponentKinds as FromType<ShouldBeInterpolated>>::from_type(),
+            <P::ComponentKinds as FromType<PreSpawnedPlayerObject>>::from_type(),
+            <P::ComponentKinds as FromType<SyncModeOverride<P::ComponentKinds>>>::from_type(),
+        ];
+        let entities = world
+            .query_filtered::<EntityRef, With<Replicate<P>>>()
+            .iter(world)
+            .map(|entity_ref| {
+                let components = P::Components::extract(&entity_ref)
+                    .into_iter()
+                    .filter(|component| !excluded_kinds.contains(&component.into()))
+                    .collect();
+                (entity_ref.id(), components)
+            })
+            .collect();
+        Self { entities }
+    }
+
+    /// Number of entities in the snapshot
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Serialize the snapshot to bytes
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut writer = WriteWordBuffer::with_capacity(SNAPSHOT_BUFFER_CAPACITY);
+        writer.serialize(&self.entities)?;
+        Ok(writer.finish_write().to_vec())
+    }
+
+    /// Deserialize a snapshot that was serialized with [`WorldSnapshot::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = ReadWordBuffer::start_read(bytes);
+        let entities = reader.deserialize::<Vec<(Entity, Vec<P::Components>)>>()?;
+        Ok(Self { entities })
+    }
+
+    /// Spawn the entities of the snapshot in the world.
+    ///
+    /// The entities are spawned with new ids; the components that reference other entities of the snapshot
+    /// are updated to use the new ids.
+    /// Returns the mapping from the entities of the snapshot to the newly spawned entities, so that
+    /// the [`Replicate`] components can be added back.
+    pub fn load(self, world: &mut World) -> EntityHashMap<Entity> {
+        let mut entity_map = RemoteEntityMap::default();
+        for (entity, _) in &self.entities {
+            entity_map.insert(*entity, world.spawn_empty().id());
+        }
+        for (entity, components) in self.entities {
+            let local_entity = *entity_map.get_local(entity).unwrap();
+            let mut local_entity_mut = world.entity_mut(local_entity);
+            for mut component in components {
+                component.map_entities(&mut entity_map);
+                component.insert(&mut local_entity_mut);
+            }
+        }
+        entity_map.to_local().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::protocol::Replicate;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut world = World::new();
+        let entity_a = world
+            .spawn((Replicate::default(), Component1(1.0), Component2(2.0)))
+            .id();
+        let entity_b = world
+            .spawn((Replicate::default(), Component4(entity_a)))
+            .id();
+        // entities that are not replicated are not saved
+        world.spawn(Component1(3.0));
+
+        let snapshot = WorldSnapshot::<MyProtocol>::save(&mut world);
+        assert_eq!(snapshot.len(), 2);
+        let bytes = snapshot.to_bytes().unwrap();
+
+        let mut new_world = World::new();
+        // make sure that the entity ids are different from the original ones
+        for _ in 0..10 {
+            new_world.spawn_empty();
+        }
+        let snapshot = WorldSnapshot::<MyProtocol>::from_bytes(&bytes).unwrap();
+        let entity_map = snapshot.load(&mut new_world);
+
+        let new_a = *entity_map.get(&entity_a).unwrap();
+        let new_b = *entity_map.get(&entity_b).unwrap();
+        assert_ne!(new_a, entity_a);
+        assert_eq!(new_world.get::<Component1>(new_a), Some(&Component1(1.0)));
+        assert_eq!(new_world.get::<Component2>(new_a), Some(&Component2(2.0)));
+        assert!(new_world.get::<Replicate>(new_a).is_none());
+        // entity references are mapped to the new entities
+        assert_eq!(new_world.get::<Component4>(new_b), Some(&Component4(new_a)));
+    }
+}
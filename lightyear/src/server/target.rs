@@ -0,0 +1,187 @@
+//! # Dynamic network targets
+//!
+//! A [`NetworkTarget`] is a fixed list of clients, so keeping it up-to-date as clients join teams or
+//! connect/disconnect means rebuilding it every frame.
+//!
+//! Instead you can add a [`DynamicTarget`] component to a replicated entity: its [`TargetRule`]s are resolved
+//! into [`NetworkTarget`]s every frame, right before replication, and written to the entity's [`Replicate`] component.
+use std::sync::Arc;
+
+use bevy::prelude::{Component, EventReader, Query, Reflect, Res, ResMut, Resource};
+use bevy::utils::{HashMap, HashSet};
+
+use crate::connection::id::ClientId;
+use crate::protocol::Protocol;
+use crate::server::connection::ConnectionManager;
+use crate::server::events::DisconnectEvent;
+use crate::shared::replication::components::{NetworkTarget, Replicate};
+
+/// Identifier of a team of clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct TeamId(pub u32);
+
+/// Keeps track of the clients that belong to each team.
+///
+/// Clients are removed from their teams automatically when they disconnect.
+#[derive(Resource, Default, Debug)]
+pub struct Teams {
+    teams: HashMap<TeamId, HashSet<ClientId>>,
+}
+
+impl Teams {
+    /// Add a client to a team. A client can be a member of multiple teams
+    pub fn join(&mut self, client_id: ClientId, team: TeamId) {
+        self.teams.entry(team).or_default().insert(client_id);
+    }
+
+    /// Remove a client from a team
+    pub fn leave(&mut self, client_id: ClientId, team: TeamId) {
+        if let Some(members) = self.teams.get_mut(&team) {
+            members.remove(&client_id);
+            if members.is_empty() {
+                self.teams.remove(&team);
+            }
+        }
+    }
+
+    /// Remove a client from all the teams
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.teams.retain(|_, members| {
+            members.remove(&client_id);
+            !members.is_empty()
+        });
+    }
+
+    /// Returns true if the client is a member of the team
+    pub fn is_member(&self, client_id: ClientId, team: TeamId) -> bool {
+        self.teams
+            .get(&team)
+            .is_some_and(|members| members.contains(&client_id))
+    }
+}
+
+type TargetPredicate = Arc<dyn Fn(ClientId) -> bool + Send + Sync>;
+
+/// A rule that gets resolved into a [`NetworkTarget`] every frame
+#[derive(Clone)]
+pub enum TargetRule {
+    /// All the members of the team
+    Team(TeamId),
+    /// All the clients that are not members of the team
+    AllExceptTeam(TeamId),
+    /// All the clients for which the predicate returns true
+    Predicate(TargetPredicate),
+}
+
+impl TargetRule {
+    /// All the clients for which the predicate returns true
+    pub fn predicate(predicate: impl Fn(ClientId) -> bool + Send + Sync + 'static) -> Self {
+        Self::Predicate(Arc::new(predicate))
+    }
+
+    /// Resolve the rule into a [`NetworkTarget`], given the list of connected clients
+    pub(crate) fn resolve(
+        &self,
+        teams: &Teams,
+        clients: impl Iterator<Item = ClientId>,
+    ) -> NetworkTarget {
+        let matches = |client_id: &ClientId| match self {
+            TargetRule::Team(team) => teams.is_member(*client_id, *team),
+            TargetRule::AllExceptTeam(team) => !teams.is_member(*client_id, *team),
+            TargetRule::Predicate(predicate) => predicate(*client_id),
+        };
+        let client_ids: Vec<ClientId> = clients.filter(matches).collect();
+        match client_ids.len() {
+            0 => NetworkTarget::None,
+            1 => NetworkTarget::Single(client_ids[0]),
+            _ => NetworkTarget::Only(client_ids),
+        }
+    }
+}
+
+/// Add this component to an entity with [`Replicate`] to compute its targets from [`TargetRule`]s.
+///
+/// The targets that are `None` are left untouched.
+#[derive(Component, Clone, Default)]
+pub struct DynamicTarget {
+    pub replication: Option<TargetRule>,
+    pub prediction: Option<TargetRule>,
+    pub interpolation: Option<TargetRule>,
+}
+
+/// Resolve the [`DynamicTarget`] rules and update the [`Replicate`] targets if they changed
+pub(crate) fn resolve_dynamic_targets<P: Protocol>(
+    teams: Res<Teams>,
+    connection_manager: Res<ConnectionManager<P>>,
+    mut query: Query<(&DynamicTarget, &mut Replicate<P>)>,
+) {
+    let clients = || connection_manager.connections.keys().copied();
+    for (dynamic_target, mut replicate) in query.iter_mut() {
+        if let Some(rule) = &dynamic_target.replication {
+            let target = rule.resolve(&teams, clients());
+            // only trigger change detection if the target actually changed
+            if replicate.replication_target != target {
+                replicate.replication_target = target;
+            }
+        }
+        if let Some(rule) = &dynamic_target.prediction {
+            let target = rule.resolve(&teams, clients());
+            if replicate.prediction_target != target {
+                replicate.prediction_target = target;
+            }
+        }
+        if let Some(rule) = &dynamic_target.interpolation {
+            let target = rule.resolve(&teams, clients());
+            if replicate.interpolation_target != target {
+                replicate.interpolation_target = target;
+            }
+        }
+    }
+}
+
+/// Remove disconnected clients from their teams
+pub(crate) fn remove_disconnected_from_teams(
+    mut teams: ResMut<Teams>,
+    mut disconnections: EventReader<DisconnectEvent>,
+) {
+    for event in disconnections.read() {
+        teams.remove_client(*event.context());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_target_rule() {
+        let client_0 = ClientId::Netcode(0);
+        let client_1 = ClientId::Netcode(1);
+        let client_2 = ClientId::Netcode(2);
+        let clients = [client_0, client_1, client_2];
+        let mut teams = Teams::default();
+        teams.join(client_0, TeamId(0));
+        teams.join(client_1, TeamId(0));
+        teams.join(client_2, TeamId(1));
+
+        assert_eq!(
+            TargetRule::Team(TeamId(0)).resolve(&teams, clients.into_iter()),
+            NetworkTarget::Only(vec![client_0, client_1])
+        );
+        assert_eq!(
+            TargetRule::AllExceptTeam(TeamId(0)).resolve(&teams, clients.into_iter()),
+            NetworkTarget::Single(client_2)
+        );
+        assert_eq!(
+            TargetRule::predicate(move |client_id| client_id != client_1)
+                .resolve(&teams, clients.into_iter()),
+            NetworkTarget::Only(vec![client_0, client_2])
+        );
+
+        teams.remove_client(client_2);
+        assert_eq!(
+            TargetRule::Team(TeamId(1)).resolve(&teams, clients.into_iter()),
+            NetworkTarget::None
+        );
+    }
+}
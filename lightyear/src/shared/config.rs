@@ -5,6 +5,7 @@ use bevy::utils::Duration;
 
 use crate::server::config::ServerConfig;
 use crate::shared::tick_manager::TickConfig;
+use crate::shared::time_manager::SendTiming;
 
 /// Configuration that has to be the same between the server and the client.
 #[derive(Clone, Debug, Reflect)]
@@ -15,6 +16,8 @@ pub struct SharedConfig {
     /// how often does the server send updates to the client?
     /// A duration of 0 means that we send updates every frame
     pub server_send_interval: Duration,
+    /// Whether the send intervals are measured in frame time or in simulation time
+    pub send_timing: SendTiming,
     /// configuration for the [`FixedUpdate`](bevy::prelude::FixedUpdate) schedule
     pub tick: TickConfig,
     pub mode: Mode,
@@ -46,6 +49,7 @@ impl Default for SharedConfig {
             // 0 means that we send updates every frame
             client_send_interval: Duration::from_millis(0),
             server_send_interval: Duration::from_millis(0),
+            send_timing: SendTiming::default(),
             tick: TickConfig::new(Duration::from_millis(16)),
             mode: Mode::default(),
         }
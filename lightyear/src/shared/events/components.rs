@@ -3,10 +3,13 @@
 use std::marker::PhantomData;
 
 use bevy::prelude::{Component, Entity, Event};
+use bevy::utils::HashMap;
 
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::InputMessage;
 use crate::packet::message::Message;
+use crate::packet::packet::PacketId;
+use crate::protocol::channel::ChannelKind;
 
 /// This event is emitted whenever a client connects to the server
 #[derive(Event)]
@@ -34,6 +37,48 @@ impl<Ctx> DisconnectEvent<Ctx> {
     }
 }
 
+/// This event is emitted whenever a packet that we sent is considered lost
+///
+/// A packet is lost if the remote can not ack it anymore (it is too old compared to the latest
+/// packets received by the remote), or if it hasn't been acked after a while.
+#[derive(Event, Debug)]
+pub struct PacketLostEvent<Ctx = ()> {
+    packet_id: PacketId,
+    lost_messages: HashMap<ChannelKind, usize>,
+    context: Ctx,
+}
+
+impl<Ctx> PacketLostEvent<Ctx> {
+    pub fn new(
+        packet_id: PacketId,
+        lost_messages: HashMap<ChannelKind, usize>,
+        context: Ctx,
+    ) -> Self {
+        Self {
+            packet_id,
+            lost_messages,
+            context,
+        }
+    }
+
+    pub fn packet_id(&self) -> PacketId {
+        self.packet_id
+    }
+
+    /// Number of messages that were lost with the packet, for each channel.
+    ///
+    /// Only the channels that track message acks (reliable channels, or
+    /// [`ChannelMode::UnorderedUnreliableWithAcks`](crate::prelude::ChannelMode::UnorderedUnreliableWithAcks)) are included.
+    /// Reliable channels will resend the lost messages.
+    pub fn lost_messages(&self) -> &HashMap<ChannelKind, usize> {
+        &self.lost_messages
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
 /// This event is emitted whenever we receive a message containing a user action
 #[cfg(feature = "leafwing")]
 #[derive(Event)]
@@ -80,18 +125,34 @@ impl<M: Message, Ctx> MessageEvent<M, Ctx> {
 /// Event emitted on server every time we receive an event
 pub struct InputEvent<I: crate::inputs::native::UserAction, Ctx = ()> {
     input: Option<I>,
+    sub_tick: Option<f32>,
     context: Ctx,
 }
 
 impl<I: crate::inputs::native::UserAction, Ctx> InputEvent<I, Ctx> {
     pub fn new(input: Option<I>, context: Ctx) -> Self {
-        Self { input, context }
+        Self {
+            input,
+            sub_tick: None,
+            context,
+        }
+    }
+
+    pub(crate) fn with_sub_tick(mut self, sub_tick: Option<f32>) -> Self {
+        self.sub_tick = sub_tick;
+        self
     }
 
     pub fn input(&self) -> &Option<I> {
         &self.input
     }
 
+    /// Fraction of the tick (in `[0.0, 1.0]`) at which the input was sampled by the client, if it was recorded
+    /// (see [`InputConfig::sub_tick_timestamps`](crate::client::input::InputConfig::sub_tick_timestamps))
+    pub fn sub_tick(&self) -> Option<f32> {
+        self.sub_tick
+    }
+
     pub fn context(&self) -> &Ctx {
         &self.context
     }
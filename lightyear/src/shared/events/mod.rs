@@ -2,5 +2,6 @@
 pub(crate) mod connection;
 
 pub mod components;
+pub(crate) mod network;
 pub mod plugin;
 pub mod systems;
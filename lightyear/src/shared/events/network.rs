@@ -0,0 +1,140 @@
+//! Shared logic to replicate bevy [`Event`]s over the network
+//!
+//! A network event is a message of the protocol that is also a bevy [`Event`]: the sender writes it with an
+//! [`EventWriter`](bevy::prelude::EventWriter), and the receiver reads it with an
+//! [`EventReader`](bevy::prelude::EventReader), without having to handle the [`MessageEvent`] manually.
+use bevy::prelude::{Event, EventReader, Events, ResMut, Resource};
+use bevy::utils::HashSet;
+
+use crate::packet::message::Message;
+use crate::protocol::EventContext;
+use crate::shared::events::components::MessageEvent;
+
+/// Ids of the network events that were received from the remote peer.
+///
+/// We keep track of them so that they are not sent back to the network.
+#[derive(Resource)]
+pub(crate) struct ReceivedNetworkEvents<E> {
+    ids: HashSet<usize>,
+    _marker: std::marker::PhantomData<E>,
+}
+
+impl<E> Default for ReceivedNetworkEvents<E> {
+    fn default() -> Self {
+        Self {
+            ids: HashSet::default(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E: Event> ReceivedNetworkEvents<E> {
+    /// Returns true if the event with this id was received from the network.
+    /// Each id is only checked once
+    pub(crate) fn take(&mut self, id: usize) -> bool {
+        self.ids.remove(&id)
+    }
+}
+
+/// Convert the [`MessageEvent`]s received from the network to the actual event `E`
+pub(crate) fn emit_network_events<E: Message + Event + Clone, Ctx: EventContext>(
+    mut messages: EventReader<MessageEvent<E, Ctx>>,
+    mut events: ResMut<Events<E>>,
+    mut received: ResMut<ReceivedNetworkEvents<E>>,
+) {
+    for message in messages.read() {
+        let id = events.send(message.message().clone());
+        received.ids.insert(id.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::utils::Duration;
+
+    use crate::prelude::client::*;
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_network_events() {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..Default::default()
+        };
+        let mut stepper = BevyStepper::from_configs(
+            shared_config,
+            SyncConfig::default().speedup_factor(1.0),
+            PredictionConfig::default(),
+            InterpolationConfig::default(),
+            LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(0),
+                incoming_jitter: Duration::from_millis(0),
+                incoming_loss: 0.0,
+            },
+            frame_duration,
+        );
+        stepper
+            .client_app
+            .add_plugins(NetworkEventPlugin::<MyProtocol, Channel1, Message1>::default());
+        stepper
+            .server_app
+            .add_plugins(server::NetworkEventPlugin::<MyProtocol, Channel1, Message1>::default());
+        stepper.init();
+
+        // messages received from the network on each side
+        let mut client_reader = ManualEventReader::<MessageEvent<Message1>>::default();
+        let mut server_reader = ManualEventReader::<server::MessageEvent<Message1>>::default();
+        let mut step = |stepper: &mut BevyStepper| {
+            let mut client_received = vec![];
+            let mut server_received = vec![];
+            for _ in 0..10 {
+                stepper.frame_step();
+                client_received.extend(
+                    client_reader
+                        .read(
+                            stepper
+                                .client_app
+                                .world
+                                .resource::<Events<MessageEvent<Message1>>>(),
+                        )
+                        .map(|e| e.message().clone()),
+                );
+                server_received.extend(
+                    server_reader
+                        .read(
+                            stepper
+                                .server_app
+                                .world
+                                .resource::<Events<server::MessageEvent<Message1>>>(),
+                        )
+                        .map(|e| e.message().clone()),
+                );
+            }
+            (client_received, server_received)
+        };
+
+        // the server event is received by the client, and not echoed back to the server
+        stepper
+            .server_app
+            .world
+            .send_event(Message1("from server".to_string()));
+        let (client_received, server_received) = step(&mut stepper);
+        assert_eq!(client_received, vec![Message1("from server".to_string())]);
+        assert_eq!(server_received, vec![]);
+
+        // the client event is received by the server, and not echoed back to the client
+        stepper
+            .client_app
+            .world
+            .send_event(Message1("from client".to_string()));
+        let (client_received, server_received) = step(&mut stepper);
+        assert_eq!(client_received, vec![]);
+        assert_eq!(server_received, vec![Message1("from client".to_string())]);
+    }
+}
@@ -5,9 +5,7 @@ use bevy::prelude::Plugin;
 
 use crate::_reexport::{ComponentProtocol, EventContext, MessageProtocol};
 use crate::prelude::Protocol;
-use crate::shared::events::components::{
-    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
-};
+use crate::shared::events::components::{EntityDespawnEvent, EntitySpawnEvent, PacketLostEvent};
 
 pub struct EventsPlugin<P, Ctx> {
     marker: std::marker::PhantomData<(P, Ctx)>,
@@ -28,9 +26,8 @@ impl<P: Protocol, Ctx: EventContext> Plugin for EventsPlugin<P, Ctx> {
         P::Components::add_events::<Ctx>(app);
         P::Message::add_events::<Ctx>(app);
 
-        app.add_event::<ConnectEvent<Ctx>>()
-            .add_event::<DisconnectEvent<Ctx>>()
-            .add_event::<EntitySpawnEvent<Ctx>>()
-            .add_event::<EntityDespawnEvent<Ctx>>();
+        app.add_event::<EntitySpawnEvent<Ctx>>()
+            .add_event::<EntityDespawnEvent<Ctx>>()
+            .add_event::<PacketLostEvent<Ctx>>();
     }
 }
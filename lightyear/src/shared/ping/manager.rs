@@ -129,6 +129,11 @@ impl PingManager {
         // older pings
     }
 
+    /// Discard the collected sync stats, so that a new handshake only uses fresh round-trip measurements
+    pub(crate) fn reset_sync_stats(&mut self) {
+        self.sync_stats = SyncStatsBuffer::new();
+    }
+
     /// Check if we are ready to send a ping to the remote
     pub(crate) fn maybe_prepare_ping(&mut self, time_manager: &TimeManager) -> Option<Ping> {
         // TODO: should we have something to start sending a sync ping right away? (so we don't wait for initial timer)
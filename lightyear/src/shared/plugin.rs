@@ -8,7 +8,7 @@ use crate::prelude::{
 use crate::server::config::ServerConfig;
 use crate::shared::config::SharedConfig;
 use crate::shared::tick_manager::TickManagerPlugin;
-use crate::shared::time_manager::TimePlugin;
+use crate::shared::time_manager::{SendTiming, TimePlugin};
 
 pub struct SharedPlugin<P: Protocol> {
     pub config: SharedConfig,
@@ -46,14 +46,15 @@ impl<P: Protocol> Plugin for SharedPlugin<P> {
         app.register_type::<Mode>()
             .register_type::<SharedConfig>()
             .register_type::<TickConfig>()
+            .register_type::<SendTiming>()
             .register_type::<PingConfig>()
             .register_type::<LinkConditionerConfig>()
             .register_type::<IoConfig>();
 
         // RESOURCES
         // NOTE: this tick duration must be the same as any previous existing fixed timesteps
-        app.insert_resource(Time::<Fixed>::from_seconds(
-            self.config.tick.tick_duration.as_secs_f64(),
+        app.insert_resource(Time::<Fixed>::from_duration(
+            self.config.tick.fixed_timestep(),
         ));
 
         // PLUGINS
@@ -63,6 +64,7 @@ impl<P: Protocol> Plugin for SharedPlugin<P> {
         app.add_plugins(TimePlugin {
             server_send_interval: self.config.server_send_interval,
             client_send_interval: self.config.client_send_interval,
+            send_timing: self.config.send_timing,
         });
     }
 }
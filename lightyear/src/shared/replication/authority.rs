@@ -0,0 +1,148 @@
+//! Transfer the authority over a replicated entity between the server and a client
+use bevy::prelude::{Component, Reflect};
+use serde::{Deserialize, Serialize};
+
+use crate::connection::id::ClientId;
+
+/// Component that indicates which peer has authority over a replicated entity, i.e. which peer
+/// simulates the entity and replicates its state to the other peers.
+///
+/// Insert or update this component on the server to transfer the authority over an entity:
+/// - the server stops sending updates for that entity to the authoritative client, and instead applies the updates
+///   it receives from that client. (they are still replicated to the other clients as usual)
+/// - the authoritative client starts replicating the entity to the server, and despawns its `Predicted` and
+///   `Interpolated` copies of the entity, since it now simulates the `Confirmed` entity directly
+///
+/// Setting the authority back to [`Authority::Server`] (or removing the component) reverts these changes.
+///
+/// Note that this requires `enable_receive` in the server's `ReplicationConfig`, and `enable_send` in the
+/// client's `ReplicationConfig`.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub enum Authority {
+    /// The server simulates the entity
+    #[default]
+    Server,
+    /// The client simulates the entity and replicates it to the server
+    Client(ClientId),
+}
+
+impl Authority {
+    /// Returns true if the given client has authority over the entity
+    pub fn is_client(&self, client_id: ClientId) -> bool {
+        *self == Authority::Client(client_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Entity;
+
+    use crate::_reexport::FromType;
+    use crate::prelude::{NetworkTarget, PingConfig, Protocol};
+    use crate::server::config::PacketConfig;
+    use crate::server::connection::ConnectionManager;
+    use crate::server::replication::ReplicationConfig;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_authority_target() {
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let client_id = ClientId::Netcode(1);
+        let entity = Entity::from_raw(0);
+        manager.add(client_id);
+        let component_kind = <MyComponentsProtocolKind as FromType<Component1>>::from_type();
+        let authority_kind = <MyComponentsProtocolKind as FromType<Authority>>::from_type();
+
+        // the client gets authority: it won't receive updates anymore, apart from the Authority component
+        manager.set_authority(entity, Authority::Client(client_id));
+        assert_eq!(
+            manager.exclude_authority(entity, component_kind, NetworkTarget::All),
+            NetworkTarget::AllExcept(vec![client_id])
+        );
+        assert_eq!(
+            manager.exclude_authority(entity, authority_kind, NetworkTarget::All),
+            NetworkTarget::All
+        );
+        // the client replicates the entity using the server's entity id
+        assert_eq!(
+            manager
+                .connection(client_id)
+                .unwrap()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(entity),
+            Some(&entity)
+        );
+
+        // the server gets the authority back
+        manager.set_authority(entity, Authority::Server);
+        assert_eq!(
+            manager.exclude_authority(entity, component_kind, NetworkTarget::All),
+            NetworkTarget::All
+        );
+        assert!(manager
+            .connection(client_id)
+            .unwrap()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(entity)
+            .is_none());
+    }
+
+    #[test]
+    fn test_authority_all_peers() {
+        let mut manager = ConnectionManager::<MyProtocol>::new(
+            protocol().channel_registry().clone(),
+            PacketConfig::default(),
+            PingConfig::default(),
+            ReplicationConfig::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let client_1 = ClientId::Netcode(1);
+        let client_2 = ClientId::Netcode(2);
+        let entity = Entity::from_raw(0);
+        let is_mapped = |manager: &ConnectionManager<MyProtocol>, client_id| {
+            manager
+                .connection(client_id)
+                .unwrap()
+                .replication_receiver
+                .remote_entity_map
+                .get_local(entity)
+                == Some(&entity)
+        };
+
+        // the authority is given to a client before it connects
+        manager.add(client_1);
+        manager.set_authority(entity, Authority::Client(client_2));
+        manager.add(client_2);
+        assert!(!is_mapped(&manager, client_1));
+        assert!(is_mapped(&manager, client_2));
+
+        // the authority is transferred to another client
+        manager.set_authority(entity, Authority::Client(client_1));
+        assert!(is_mapped(&manager, client_1));
+        assert!(!is_mapped(&manager, client_2));
+        assert_eq!(
+            manager.exclude_authority(
+                entity,
+                <MyComponentsProtocolKind as FromType<Component1>>::from_type(),
+                NetworkTarget::All
+            ),
+            NetworkTarget::AllExcept(vec![client_1])
+        );
+    }
+}
@@ -62,7 +62,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
@@ -1,14 +1,14 @@
 //! Components used for replication
 use bevy::ecs::entity::MapEntities;
 use bevy::ecs::query::QueryFilter;
-use bevy::prelude::{Component, Entity, EntityMapper, Reflect};
-use bevy::utils::{HashMap, HashSet};
+use bevy::prelude::{Component, Entity, EntityMapper, Reflect, Resource, World};
+use bevy::utils::{Duration, HashMap, HashSet};
 use serde::{Deserialize, Serialize};
-use tracing::trace;
+use tracing::{error, trace};
 
 use crate::_reexport::FromType;
 use crate::channel::builder::Channel;
-use crate::client::components::SyncComponent;
+use crate::client::components::{ComponentSyncMode, SyncComponent};
 use crate::connection::id::ClientId;
 use crate::prelude::ParentSync;
 use crate::protocol::Protocol;
@@ -18,6 +18,28 @@ use crate::server::room::ClientVisibility;
 #[derive(Component, Clone, Copy)]
 pub struct DespawnTracker;
 
+/// Add this component to a replicated entity on the receiving side to customize what happens
+/// when the remote despawns the entity. (by default, the entity is despawned immediately)
+///
+/// This can be useful to play a death animation or spawn a ragdoll before actually despawning the entity.
+/// In all cases the entity stops receiving replication updates, and an `EntityDespawnEvent` is emitted.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub enum DespawnBehavior {
+    /// Despawn the entity (and its children) immediately
+    #[default]
+    Despawn,
+    /// Keep the entity, but insert the [`Despawned`] marker component on it.
+    /// You will have to despawn the entity yourself.
+    InsertMarker,
+    /// Run a custom function on the entity instead of despawning it
+    Custom(fn(&mut World, Entity)),
+}
+
+/// Marker component inserted on entities that were despawned by the remote, if they
+/// have the [`DespawnBehavior::InsertMarker`] behavior
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct Despawned;
+
 /// Component that indicates that an entity should be replicated. Added to the entity when it is spawned
 /// in the world that sends replication updates.
 #[derive(Component, Clone, PartialEq, Debug, Reflect)]
@@ -44,6 +66,16 @@ pub struct Replicate<P: Protocol> {
     /// If false, you can still replicate hierarchies, but in a more fine-grained manner. You will have to add the `Replicate`
     /// and `ParentSync` components to the children yourself
     pub replicate_hierarchy: bool,
+    /// Minimum interval between two updates of the entity's replication group.
+    ///
+    /// This can be used to replicate far-away or low-importance entities less frequently than the
+    /// `send_interval` of the connection. The component changes that happen in between are not lost:
+    /// they are included in the next update that is sent.
+    /// Entity actions (spawns, despawns, component inserts/removals) are always sent right away.
+    ///
+    /// If the replication group contains multiple entities, the latest updated value takes precedence.
+    /// By default, this is `Duration::ZERO`: updates are sent every time the connection sends packets.
+    pub send_interval: Duration,
 
     /// Lets you override the replication modalities for a specific component
     pub per_component_metadata: HashMap<P::ComponentKinds, PerComponentReplicationMetadata>,
@@ -62,6 +94,8 @@ pub struct PerComponentReplicationMetadata {
     /// Custom replication target for this component. We will replicate to the intersection of
     /// the entity's replication target and this target
     target: NetworkTarget,
+    /// Override of the protocol's [`ComponentSyncMode`] for this component on the predicted entity
+    sync_mode: Option<ComponentSyncMode>,
 }
 impl Default for PerComponentReplicationMetadata {
     fn default() -> Self {
@@ -69,8 +103,40 @@ impl Default for PerComponentReplicationMetadata {
             disabled: false,
             replicate_once: false,
             target: NetworkTarget::All,
+            sync_mode: None,
+        }
+    }
+}
+
+type DistanceFn<C> = Box<dyn Fn(&C, &C) -> f32 + Send + Sync>;
+
+/// Insert this resource to only replicate updates of the component `C` if its value moved
+/// far enough from the last value that was sent.
+///
+/// This avoids sending updates for tiny changes, for example the physics jitter of a `Position`.
+///
+/// ```rust,ignore
+/// app.insert_resource(ReplicationThreshold::<Position>::new(0.01, |a, b| a.0.distance(b.0)));
+/// ```
+#[derive(Resource)]
+pub struct ReplicationThreshold<C> {
+    threshold: f32,
+    distance: DistanceFn<C>,
+}
+
+impl<C> ReplicationThreshold<C> {
+    /// Only send an update when `distance(last_sent_value, new_value) > threshold`
+    pub fn new(threshold: f32, distance: impl Fn(&C, &C) -> f32 + Send + Sync + 'static) -> Self {
+        Self {
+            threshold,
+            distance: Box::new(distance),
         }
     }
+
+    /// Returns true if the component changed enough since the last value that was sent
+    pub(crate) fn exceeds(&self, last_sent: &C, value: &C) -> bool {
+        (self.distance)(last_sent, value) > self.threshold
+    }
 }
 
 impl<P: Protocol> Replicate<P> {
@@ -78,6 +144,55 @@ impl<P: Protocol> Replicate<P> {
         self.replication_group.group_id(entity)
     }
 
+    /// Returns true if the entity is currently visible to the client.
+    ///
+    /// This is only used if the entity uses [`ReplicationMode::Room`]
+    pub fn is_visible(&self, client_id: ClientId) -> bool {
+        self.replication_clients_cache
+            .get(&client_id)
+            .is_some_and(|visibility| *visibility != ClientVisibility::Lost)
+    }
+
+    /// Manually make the entity visible or invisible to a given client.
+    ///
+    /// The entity will be spawned on the client when it gains visibility, and despawned when it loses it.
+    /// Gaining visibility adds the client to the `replication_target`, so it overrides the target.
+    ///
+    /// The visibility can only be controlled for entities that use [`ReplicationMode::Room`]: the change is
+    /// ignored (with an error) for the other entities.
+    pub fn set_visibility(&mut self, client_id: ClientId, visible: bool) {
+        if self.replication_mode != ReplicationMode::Room {
+            error!(
+                ?client_id,
+                "the visibility can only be set for entities that use ReplicationMode::Room"
+            );
+            return;
+        }
+        if visible && !self.replication_target.should_send_to(&client_id) {
+            self.replication_target
+                .union(&NetworkTarget::Only(vec![client_id]));
+        }
+        match (visible, self.replication_clients_cache.get(&client_id)) {
+            (true, None) => {
+                self.replication_clients_cache
+                    .insert(client_id, ClientVisibility::Gained);
+            }
+            (true, Some(ClientVisibility::Lost)) => {
+                self.replication_clients_cache
+                    .insert(client_id, ClientVisibility::Maintained);
+            }
+            // the entity was never spawned on the client, no need to despawn it
+            (false, Some(ClientVisibility::Gained)) => {
+                self.replication_clients_cache.remove(&client_id);
+            }
+            (false, Some(ClientVisibility::Maintained)) => {
+                self.replication_clients_cache
+                    .insert(client_id, ClientVisibility::Lost);
+            }
+            _ => {}
+        }
+    }
+
     /// Returns true if we don't want to replicate the component
     pub fn is_disabled<C>(&self) -> bool
     where
@@ -119,6 +234,36 @@ impl<P: Protocol> Replicate<P> {
         }
     }
 
+    /// Override the [`ComponentSyncMode`] of the component `C` for the predicted and interpolated entities, instead
+    /// of using the mode declared in the protocol. For example you can use `Full` sync for the local player's position,
+    /// but `Simple` sync for the positions of other players.
+    ///
+    /// The override can only lower the sync mode (`Full` > `Simple` > `Once`), since the systems needed for
+    /// a component are chosen using the mode declared in the protocol.
+    /// The override can be modified after the entity was spawned; the changes are replicated to the clients.
+    pub fn set_sync_mode<C>(&mut self, mode: ComponentSyncMode)
+    where
+        P::ComponentKinds: FromType<C>,
+    {
+        let kind = <P::ComponentKinds as FromType<C>>::from_type();
+        self.per_component_metadata
+            .entry(kind)
+            .or_default()
+            .sync_mode = Some(mode);
+    }
+
+    /// The overrides of the [`ComponentSyncMode`] that need to be sent to the clients
+    pub(crate) fn sync_mode_override(&self) -> SyncModeOverride<P::ComponentKinds> {
+        let mut modes: Vec<_> = self
+            .per_component_metadata
+            .iter()
+            .filter_map(|(kind, metadata)| metadata.sync_mode.map(|mode| (*kind, mode)))
+            .collect();
+        // sort the overrides so that we can compare them to detect changes
+        modes.sort_by_key(|(kind, _)| *kind);
+        SyncModeOverride { modes }
+    }
+
     /// Disable the replication of a component for this entity
     pub fn disable_component<C>(&mut self)
     where
@@ -149,6 +294,11 @@ impl<P: Protocol> Replicate<P> {
         }
     }
 
+    /// Replicate the component `C` only once, when the entity is replicated to a client.
+    /// Later changes to the component are not replicated.
+    ///
+    /// This is useful for static data (names, colors, model ids, etc.), since we can skip
+    /// change detection for these components entirely.
     pub fn enable_replicate_once<C>(&mut self)
     where
         P::ComponentKinds: FromType<C>,
@@ -160,6 +310,7 @@ impl<P: Protocol> Replicate<P> {
             .replicate_once = true;
     }
 
+    /// Replicate all the changes to the component `C` (this is the default behaviour)
     pub fn disable_replicate_once<C>(&mut self)
     where
         P::ComponentKinds: FromType<C>,
@@ -177,6 +328,11 @@ impl<P: Protocol> Replicate<P> {
         }
     }
 
+    /// Replicate the component `C` only to the clients in `target` (for example only to the entity's owner),
+    /// while the rest of the entity is replicated to the entity's `replication_target`.
+    ///
+    /// The target can be modified at runtime: the component will be inserted on the clients that are
+    /// added to the target, and removed from the clients that are removed from the target.
     pub fn add_target<C>(&mut self, target: NetworkTarget)
     where
         P::ComponentKinds: FromType<C>,
@@ -210,6 +366,12 @@ pub struct ReplicationGroup {
     /// the priority of the accumulation group
     /// (priority will get reset to this value every time a message gets sent successfully)
     base_priority: f32,
+    /// If true, the group is replicated in snapshot mode: every update message contains the full state
+    /// of all the replicated components of the group, instead of only the components that changed since
+    /// the last acked update.
+    snapshot: bool,
+    /// The spawns of this group are only applied by the remote after the spawns of this other group
+    dependency: Option<ReplicationGroupId>,
 }
 
 impl Default for ReplicationGroup {
@@ -217,6 +379,8 @@ impl Default for ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            snapshot: false,
+            dependency: None,
         }
     }
 }
@@ -226,6 +390,8 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            snapshot: false,
+            dependency: None,
         }
     }
 
@@ -233,6 +399,8 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::Group(id),
             base_priority: 1.0,
+            snapshot: false,
+            dependency: None,
         }
     }
 
@@ -258,6 +426,34 @@ impl ReplicationGroup {
         self.id_builder = ReplicationGroupIdBuilder::Group(id);
         self
     }
+
+    /// Replicate the group using periodic full snapshots.
+    ///
+    /// Every update message will contain the current value of all the replicated components of the group,
+    /// even if they didn't change. Use [`Replicate::send_interval`] to control the snapshot rate; clients that
+    /// interpolate the entities will interpolate between consecutive snapshots.
+    /// This uses more bandwidth, but a client never depends on older updates to have a consistent state.
+    pub fn set_snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    pub(crate) fn is_snapshot(&self) -> bool {
+        self.snapshot
+    }
+
+    /// The entities of this group will only be spawned by the remote after the entities of the group `group_id`
+    /// have been spawned (for example the level geometry before the props that reference it).
+    ///
+    /// The messages of this group are buffered by the receiver until the dependency is satisfied.
+    pub fn set_dependency(mut self, group_id: ReplicationGroupId) -> Self {
+        self.dependency = Some(group_id);
+        self
+    }
+
+    pub(crate) fn dependency(&self) -> Option<ReplicationGroupId> {
+        self.dependency
+    }
 }
 
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
@@ -283,6 +479,7 @@ impl<P: Protocol> Default for Replicate<P> {
             replication_mode: ReplicationMode::default(),
             replication_group: Default::default(),
             replicate_hierarchy: true,
+            send_interval: Duration::ZERO,
             per_component_metadata: HashMap::default(),
         };
         // those metadata components should only be replicated once
@@ -332,8 +529,28 @@ impl NetworkTarget {
         }
     }
 
+    /// Compute the complement of this target (all the clients that are not in this target)
+    pub fn complement(&self) -> NetworkTarget {
+        match self {
+            NetworkTarget::None => NetworkTarget::All,
+            NetworkTarget::All => NetworkTarget::None,
+            NetworkTarget::AllExceptSingle(client_id) => NetworkTarget::Single(*client_id),
+            NetworkTarget::AllExcept(client_ids) => NetworkTarget::Only(client_ids.clone()),
+            NetworkTarget::Only(client_ids) => NetworkTarget::AllExcept(client_ids.clone()),
+            NetworkTarget::Single(client_id) => NetworkTarget::AllExceptSingle(*client_id),
+        }
+    }
+
+    /// Compute the union of this target with another one (A ∪ B)
+    pub fn union(&mut self, target: &NetworkTarget) {
+        // A ∪ B = not(not(A) ∩ not(B))
+        let mut complement = self.complement();
+        complement.intersection(target.complement());
+        *self = complement.complement();
+    }
+
     /// Compute the intersection of this target with another one (A ∩ B)
-    pub(crate) fn intersection(&mut self, target: NetworkTarget) {
+    pub fn intersection(&mut self, target: NetworkTarget) {
         match self {
             NetworkTarget::All => {
                 *self = target;
@@ -415,8 +632,30 @@ impl NetworkTarget {
         }
     }
 
-    /// Compute the difference of this target with another one (A - B)
-    pub(crate) fn exclude(&mut self, client_ids: Vec<ClientId>) {
+    /// Compute the difference of this target with another target (A - B)
+    pub fn difference(&mut self, target: &NetworkTarget) {
+        match target {
+            NetworkTarget::None => {}
+            NetworkTarget::All => {
+                *self = NetworkTarget::None;
+            }
+            NetworkTarget::AllExceptSingle(client_id) => {
+                self.intersection(NetworkTarget::Single(*client_id));
+            }
+            NetworkTarget::AllExcept(client_ids) => {
+                self.intersection(NetworkTarget::Only(client_ids.clone()));
+            }
+            NetworkTarget::Only(client_ids) => {
+                self.exclude(client_ids.clone());
+            }
+            NetworkTarget::Single(client_id) => {
+                self.exclude(vec![*client_id]);
+            }
+        }
+    }
+
+    /// Remove the clients from this target
+    pub fn exclude(&mut self, client_ids: Vec<ClientId>) {
         match self {
             NetworkTarget::All => {
                 *self = NetworkTarget::AllExcept(client_ids);
@@ -469,6 +708,57 @@ pub struct PrePredicted {
 #[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Reflect)]
 pub struct ShouldBePredicted;
 
+/// Indicates which client controls (owns) a replicated entity, for example the player's character.
+///
+/// Insert it on the server; it is replicated to the clients like any other component.
+/// The server keeps an index of the entities controlled by each client (see `ControlledEntities`),
+/// and despawns them when the client disconnects (see `ReplicationConfig::despawn_controlled_on_disconnect`).
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ControlledBy(pub ClientId);
+
+/// Per-entity overrides of the [`ComponentSyncMode`] of some components, set with [`Replicate::set_sync_mode`]
+///
+/// It is sent to the clients along with [`ShouldBePredicted`] or [`ShouldBeInterpolated`], and is updated
+/// whenever the overrides are modified.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct SyncModeOverride<K> {
+    modes: Vec<(K, ComponentSyncMode)>,
+}
+
+impl<K: PartialEq> SyncModeOverride<K> {
+    /// Returns true if no component has its sync mode overridden
+    pub(crate) fn is_empty(&self) -> bool {
+        self.modes.is_empty()
+    }
+
+    /// Sync mode that should be used for the component `C` on this entity.
+    ///
+    /// Overrides that would raise the sync mode above `protocol_mode` are ignored.
+    pub(crate) fn mode<C>(
+        sync_mode_override: Option<&Self>,
+        protocol_mode: ComponentSyncMode,
+    ) -> ComponentSyncMode
+    where
+        K: FromType<C>,
+    {
+        let Some(sync_mode_override) = sync_mode_override else {
+            return protocol_mode;
+        };
+        let rank = |mode: ComponentSyncMode| match mode {
+            ComponentSyncMode::Full => 3,
+            ComponentSyncMode::Simple => 2,
+            ComponentSyncMode::Once => 1,
+            ComponentSyncMode::None => 0,
+        };
+        let kind = K::from_type();
+        sync_mode_override
+            .modes
+            .iter()
+            .find(|(k, mode)| *k == kind && rank(*mode) <= rank(protocol_mode))
+            .map_or(protocol_mode, |(_, mode)| *mode)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -542,4 +832,106 @@ mod tests {
         target.intersection(NetworkTarget::AllExcept(vec![client_0, client_2]));
         assert_eq!(target, NetworkTarget::None);
     }
+
+    #[test]
+    fn test_difference() {
+        let client_0 = ClientId::Netcode(0);
+        let client_1 = ClientId::Netcode(1);
+        let client_2 = ClientId::Netcode(2);
+        let mut target = NetworkTarget::All;
+        target.difference(&NetworkTarget::Only(vec![client_1]));
+        assert_eq!(target, NetworkTarget::AllExcept(vec![client_1]));
+
+        target = NetworkTarget::Only(vec![client_0, client_1]);
+        target.difference(&NetworkTarget::AllExcept(vec![client_0, client_2]));
+        assert_eq!(target, NetworkTarget::Only(vec![client_0]));
+
+        target = NetworkTarget::Only(vec![client_0, client_1]);
+        target.difference(&NetworkTarget::Single(client_1));
+        assert_eq!(target, NetworkTarget::Only(vec![client_0]));
+
+        target = NetworkTarget::Single(client_0);
+        target.difference(&NetworkTarget::All);
+        assert_eq!(target, NetworkTarget::None);
+
+        target = NetworkTarget::Single(client_0);
+        target.difference(&NetworkTarget::None);
+        assert_eq!(target, NetworkTarget::Single(client_0));
+    }
+
+    #[test]
+    fn test_sync_mode_override() {
+        use crate::tests::protocol::{Component1, Component2, Component3, MyProtocol};
+
+        let mut replicate = Replicate::<MyProtocol>::default();
+        assert!(replicate.sync_mode_override().is_empty());
+        replicate.set_sync_mode::<Component1>(ComponentSyncMode::Simple);
+        // overrides cannot raise the sync mode
+        replicate.set_sync_mode::<Component2>(ComponentSyncMode::Full);
+        let sync_mode_override = Some(replicate.sync_mode_override());
+        assert_eq!(
+            SyncModeOverride::mode::<Component1>(
+                sync_mode_override.as_ref(),
+                ComponentSyncMode::Full
+            ),
+            ComponentSyncMode::Simple
+        );
+        assert_eq!(
+            SyncModeOverride::mode::<Component2>(
+                sync_mode_override.as_ref(),
+                ComponentSyncMode::Simple
+            ),
+            ComponentSyncMode::Simple
+        );
+        assert_eq!(
+            SyncModeOverride::mode::<Component3>(
+                sync_mode_override.as_ref(),
+                ComponentSyncMode::Once
+            ),
+            ComponentSyncMode::Once
+        );
+    }
+
+    #[test]
+    fn test_set_visibility() {
+        use crate::tests::protocol::MyProtocol;
+
+        let client_id = ClientId::Netcode(0);
+        // the visibility is ignored for entities that don't use rooms
+        let mut replicate = Replicate::<MyProtocol>::default();
+        replicate.set_visibility(client_id, true);
+        assert!(!replicate.is_visible(client_id));
+
+        // gaining visibility overrides the replication target
+        let mut replicate = Replicate::<MyProtocol> {
+            replication_target: NetworkTarget::None,
+            replication_mode: ReplicationMode::Room,
+            ..Default::default()
+        };
+        replicate.set_visibility(client_id, true);
+        assert!(replicate.is_visible(client_id));
+        assert!(replicate.replication_target.should_send_to(&client_id));
+    }
+
+    #[test]
+    fn test_union() {
+        let client_0 = ClientId::Netcode(0);
+        let client_1 = ClientId::Netcode(1);
+        let client_2 = ClientId::Netcode(2);
+        let mut target = NetworkTarget::Single(client_0);
+        target.union(&NetworkTarget::Single(client_1));
+        assert!(target.should_send_to(&client_0));
+        assert!(target.should_send_to(&client_1));
+        assert!(!target.should_send_to(&client_2));
+
+        let mut target = NetworkTarget::AllExcept(vec![client_0, client_1]);
+        target.union(&NetworkTarget::Only(vec![client_1]));
+        assert!(!target.should_send_to(&client_0));
+        assert!(target.should_send_to(&client_1));
+        assert!(target.should_send_to(&client_2));
+
+        let mut target = NetworkTarget::None;
+        target.union(&NetworkTarget::All);
+        assert_eq!(target, NetworkTarget::All);
+    }
 }
@@ -74,13 +74,15 @@ impl RemoteEntityMap {
     //     Box::new(&self.remote_to_local)
     // }
 
+    /// Get the local entity that corresponds to the given remote entity
     #[inline]
-    pub(crate) fn get_local(&self, remote_entity: Entity) -> Option<&Entity> {
+    pub fn get_local(&self, remote_entity: Entity) -> Option<&Entity> {
         self.remote_to_local.get(&remote_entity)
     }
 
+    /// Get the remote entity that corresponds to the given local entity
     #[inline]
-    pub(crate) fn get_remote(&self, local_entity: Entity) -> Option<&Entity> {
+    pub fn get_remote(&self, local_entity: Entity) -> Option<&Entity> {
         self.local_to_remote.get(&local_entity)
     }
 
@@ -113,7 +115,7 @@ impl RemoteEntityMap {
         }
     }
 
-    pub(super) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
+    pub(crate) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
         let local_entity = self.remote_to_local.remove(&remote_entity);
         if let Some(local_entity) = local_entity {
             self.local_to_remote.remove(&local_entity);
@@ -175,7 +177,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
@@ -244,4 +246,47 @@ mod tests {
         );
         Ok(())
     }
+
+    // The client registers a mapping for an entity that already exists on both sides:
+    // the server's components are added to the existing client entity instead of spawning a new one.
+    #[test]
+    fn test_register_entity_mapping() {
+        let mut stepper = BevyStepper::default();
+
+        let server_entity = stepper.server_app.world.spawn(Component1(1.0)).id();
+        let client_entity = stepper.client_app.world.spawn_empty().id();
+        stepper
+            .client_app
+            .world
+            .resource_mut::<ClientConnectionManager>()
+            .register_entity_mapping(server_entity, client_entity);
+
+        // Replicate the entity
+        stepper
+            .server_app
+            .world
+            .entity_mut(server_entity)
+            .insert(Replicate::default());
+        stepper.frame_step();
+        stepper.frame_step();
+
+        assert_eq!(
+            stepper
+                .client_app
+                .world
+                .resource::<ClientConnectionManager>()
+                .remote_entity_map()
+                .get_local(server_entity),
+            Some(&client_entity)
+        );
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(1.0))
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Confirmed>(client_entity)
+            .is_some());
+    }
 }
@@ -62,6 +62,43 @@ impl<P: Protocol, R: ReplicationSend<P>> HierarchySendPlugin<P, R> {
         }
     }
 
+    /// Replicate the entities that were added to a replicated hierarchy after the `Replicate` component
+    /// was inserted on the root of the hierarchy
+    fn propagate_replicate_to_new_children(
+        mut commands: Commands,
+        new_children_query: Query<Entity, (Changed<Parent>, Without<Replicate<P>>)>,
+        parent_query: Query<&Parent>,
+        children_query: Query<&Children>,
+        replicate_query: Query<&Replicate<P>>,
+    ) {
+        for entity in new_children_query.iter() {
+            let root = parent_query
+                .iter_ancestors(entity)
+                .last()
+                .expect("the entity has a parent, so it has at least one ancestor");
+            let Ok(replicate) = replicate_query.get(root) else {
+                continue;
+            };
+            if !replicate.replicate_hierarchy {
+                continue;
+            }
+            let mut replicate = replicate.clone();
+            // the entire hierarchy is replicated as a single group, that uses the root's entity as the group id
+            replicate.replication_group = ReplicationGroup::new_id(root.to_bits());
+            // the new child might itself have children
+            for child in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+                trace!(
+                    ?child,
+                    ?root,
+                    "Replicating entity added to a replicated hierarchy"
+                );
+                commands
+                    .entity(child)
+                    .insert((replicate.clone(), ParentSync(None)));
+            }
+        }
+    }
+
     /// Update ParentSync if the hierarchy changed
     /// (run this in post-update before replicating, to account for any hierarchy changed initiated by the user)
     ///
@@ -99,7 +136,12 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for HierarchySendPlugin<P, R> {
         app.add_systems(
             PostUpdate,
             (
-                (Self::propagate_replicate, Self::update_parent_sync).chain(),
+                (
+                    Self::propagate_replicate,
+                    Self::propagate_replicate_to_new_children,
+                    Self::update_parent_sync,
+                )
+                    .chain(),
                 Self::removal_system,
             )
                 // we don't need to run these every frame, only every send_interval
@@ -274,6 +316,55 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn test_propagate_hierarchy_new_child() {
+        let (mut stepper, grandparent, parent, child) = setup_hierarchy();
+
+        stepper
+            .server_app
+            .world
+            .entity_mut(grandparent)
+            .insert(Replicate::default());
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // add a new child to the hierarchy after it has been replicated
+        let new_child = stepper.server_app.world.spawn(Component4(grandparent)).id();
+        stepper
+            .server_app
+            .world
+            .entity_mut(parent)
+            .add_child(new_child);
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // check that the new child has been replicated, with the correct hierarchy
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .entity(new_child)
+                .get::<Replicate>(),
+            Some(&Replicate {
+                replication_group: ReplicationGroup::new_id(grandparent.to_bits()),
+                ..Default::default()
+            })
+        );
+        let client_parent = stepper
+            .client_app
+            .world
+            .query_filtered::<Entity, With<Component2>>()
+            .get_single(&stepper.client_app.world)
+            .unwrap();
+        let client_parent_component = stepper
+            .client_app
+            .world
+            .query_filtered::<&Parent, With<Component4>>()
+            .get_single(&stepper.client_app.world)
+            .unwrap();
+        assert_eq!(client_parent_component.deref(), &client_parent);
+    }
+
     #[test]
     fn test_propagate_hierarchy() {
         let (mut stepper, grandparent, parent, child) = setup_hierarchy();
@@ -2,12 +2,13 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::EntityHashMap;
 use bevy::prelude::{Component, Entity, Resource};
 use bevy::reflect::Map;
 use bevy::utils::HashSet;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::_reexport::{ComponentProtocol, ComponentProtocolKind};
@@ -16,8 +17,13 @@ use crate::connection::id::ClientId;
 use crate::packet::message::MessageId;
 use crate::prelude::{NetworkTarget, Tick};
 use crate::protocol::{EventContext, Protocol};
+use crate::serialize::reader::ReadBuffer;
+use crate::serialize::wordbuffer::reader::ReadWordBuffer;
+use crate::serialize::wordbuffer::writer::WriteWordBuffer;
+use crate::serialize::writer::WriteBuffer;
 use crate::shared::replication::components::{Replicate, ReplicationGroupId};
 
+pub mod authority;
 pub mod components;
 
 mod commands;
@@ -75,6 +81,9 @@ impl<C, K: Hash + Eq> Default for EntityActions<C, K> {
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct EntityActionMessage<C, K: Hash + Eq> {
     sequence_id: MessageId,
+    /// Group whose actions must have been applied before this message can be applied
+    /// (only set for messages that contain entity spawns)
+    dependency: Option<ReplicationGroupId>,
     // we use vec but the order of entities should not matter
     pub(crate) actions: Vec<(Entity, EntityActions<C, K>)>,
 }
@@ -102,6 +111,31 @@ pub struct ReplicationMessage<C, K: Hash + Eq> {
     pub(crate) data: ReplicationMessageData<C, K>,
 }
 
+/// Initial capacity of the buffer used to serialize a replication baseline; it grows as needed
+const BASELINE_BUFFER_CAPACITY: usize = 4096;
+
+/// Serialize the replication messages of a baseline and compress them.
+///
+/// The baseline contains the whole replicated world, so it is usually large and very redundant
+/// (the same components for many entities); compressing it greatly reduces the number of fragments to send.
+pub(crate) fn compress_baseline<C: Serialize, K: Serialize + Hash + Eq>(
+    baseline: &Vec<ReplicationMessage<C, K>>,
+) -> Result<Vec<u8>> {
+    let mut writer = WriteWordBuffer::with_capacity(BASELINE_BUFFER_CAPACITY);
+    writer.serialize(baseline)?;
+    Ok(lz4_flex::compress_prepend_size(writer.finish_write()))
+}
+
+/// Decompress and deserialize a baseline that was compressed with [`compress_baseline`]
+pub(crate) fn decompress_baseline<C: DeserializeOwned, K: DeserializeOwned + Hash + Eq>(
+    bytes: &[u8],
+) -> Result<Vec<ReplicationMessage<C, K>>> {
+    let bytes =
+        lz4_flex::decompress_size_prepended(bytes).context("could not decompress the baseline")?;
+    let mut reader = ReadWordBuffer::start_read(&bytes);
+    reader.deserialize::<Vec<ReplicationMessage<C, K>>>()
+}
+
 #[doc(hidden)]
 /// Trait for any service that can send replication messages to the remote.
 /// (this trait is used to easily enable both client to server and server to client replication)
@@ -223,7 +257,7 @@ mod tests {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = BevyStepper::new(
+        let mut stepper = BevyStepper::from_configs(
             shared_config,
             sync_config,
             prediction_config,
@@ -280,4 +314,299 @@ mod tests {
             .is_none());
         Ok(())
     }
+
+    // The per-component target of a component is modified at runtime:
+    // the component should be removed/inserted on the client accordingly
+    #[test]
+    fn test_component_target_change() {
+        let mut stepper = BevyStepper::default();
+        let client_id = ClientId::Netcode(111);
+
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Component1>(client_entity)
+            .is_some());
+
+        // Remove the client from the component's target
+        stepper
+            .server_app
+            .world
+            .get_mut::<Replicate>(server_entity)
+            .unwrap()
+            .add_target::<Component1>(NetworkTarget::AllExceptSingle(client_id));
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Component1>(client_entity)
+            .is_none());
+
+        // Add the client back to the component's target
+        stepper
+            .server_app
+            .world
+            .get_mut::<Replicate>(server_entity)
+            .unwrap()
+            .add_target::<Component1>(NetworkTarget::All);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.0))
+        );
+    }
+
+    // The sync mode overrides are sent with the entity spawn, and are updated when they change
+    #[test]
+    fn test_sync_mode_override_change() {
+        use crate::client::components::ComponentSyncMode;
+
+        type Override = SyncModeOverride<<MyProtocol as Protocol>::ComponentKinds>;
+        let mut stepper = BevyStepper::default();
+
+        let mut replicate = Replicate {
+            interpolation_target: NetworkTarget::All,
+            ..Default::default()
+        };
+        replicate.set_sync_mode::<Component1>(ComponentSyncMode::Simple);
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), replicate))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            SyncModeOverride::mode::<Component1>(
+                stepper.client_app.world.get::<Override>(client_entity),
+                ComponentSyncMode::Full
+            ),
+            ComponentSyncMode::Simple
+        );
+
+        // Modify the override on the server
+        stepper
+            .server_app
+            .world
+            .get_mut::<Replicate>(server_entity)
+            .unwrap()
+            .set_sync_mode::<Component1>(ComponentSyncMode::Once);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            SyncModeOverride::mode::<Component1>(
+                stepper.client_app.world.get::<Override>(client_entity),
+                ComponentSyncMode::Full
+            ),
+            ComponentSyncMode::Once
+        );
+    }
+
+    // Replicate-once components are replicated along with the entity, but not updated afterwards
+    #[test]
+    fn test_replicate_once() {
+        let mut stepper = BevyStepper::default();
+
+        let mut replicate = Replicate::default();
+        replicate.enable_replicate_once::<Component1>();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), Component2(0.0), replicate))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.0))
+        );
+
+        // Update the components on the server: only Component2 gets updated on the client
+        stepper
+            .server_app
+            .world
+            .entity_mut(server_entity)
+            .insert((Component1(1.0), Component2(1.0)));
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.0))
+        );
+        assert_eq!(
+            stepper.client_app.world.get::<Component2>(client_entity),
+            Some(&Component2(1.0))
+        );
+    }
+
+    // Updates that are below the replication threshold are not replicated
+    #[test]
+    fn test_replication_threshold() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .world
+            .insert_resource(ReplicationThreshold::<Component1>::new(0.5, |a, b| {
+                (a.0 - b.0).abs()
+            }));
+
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+
+        // small change: not replicated
+        stepper
+            .server_app
+            .world
+            .get_mut::<Component1>(server_entity)
+            .unwrap()
+            .0 = 0.1;
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.0))
+        );
+
+        // the changes accumulate until they exceed the threshold
+        stepper
+            .server_app
+            .world
+            .get_mut::<Component1>(server_entity)
+            .unwrap()
+            .0 = 0.6;
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.6))
+        );
+    }
+
+    // Groups in snapshot mode resend the full state even if nothing changed
+    #[test]
+    fn test_snapshot_group() {
+        let mut stepper = BevyStepper::default();
+
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(0.0),
+                Replicate {
+                    replication_group: ReplicationGroup::default().set_snapshot(true),
+                    ..Default::default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+
+        // the client state diverges, but the next snapshot restores the server state
+        stepper
+            .client_app
+            .world
+            .entity_mut(client_entity)
+            .insert(Component1(5.0));
+        stepper.frame_step();
+        stepper.frame_step();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(0.0))
+        );
+    }
+
+    #[test]
+    fn test_baseline_compression_round_trip() {
+        use super::{
+            compress_baseline, decompress_baseline, EntityActionMessage, EntityActions,
+            ReplicationMessage, ReplicationMessageData,
+        };
+        use crate::packet::message::MessageId;
+        use crate::shared::replication::components::ReplicationGroupId;
+        use bevy::prelude::Entity;
+
+        let baseline = (0..100)
+            .map(|i| ReplicationMessage {
+                group_id: ReplicationGroupId(i),
+                data: ReplicationMessageData::Actions(EntityActionMessage {
+                    sequence_id: MessageId(0),
+                    dependency: None,
+                    actions: vec![(
+                        Entity::from_raw(i as u32),
+                        EntityActions {
+                            spawn: true,
+                            insert: vec![MyComponentsProtocol::Component1(Component1(1.0))],
+                            ..Default::default()
+                        },
+                    )],
+                }),
+            })
+            .collect::<Vec<ReplicationMessage<MyComponentsProtocol, MyComponentsProtocolKind>>>();
+        let bytes = compress_baseline(&baseline).unwrap();
+        let decompressed =
+            decompress_baseline::<MyComponentsProtocol, MyComponentsProtocolKind>(&bytes).unwrap();
+        assert_eq!(decompressed, baseline);
+
+        // corrupted bytes are reported instead of being applied
+        assert!(
+            decompress_baseline::<MyComponentsProtocol, MyComponentsProtocolKind>(
+                &bytes[..bytes.len() / 2]
+            )
+            .is_err()
+        );
+    }
 }
@@ -4,8 +4,8 @@ use bevy::utils::Duration;
 
 use crate::_reexport::{ComponentProtocol, ReplicationSend, ShouldBeInterpolated};
 use crate::prelude::{
-    NetworkTarget, PrePredicted, Protocol, RemoteEntityMap, ReplicationGroup, ReplicationMode,
-    ShouldBePredicted,
+    Authority, ControlledBy, Despawned, NetworkTarget, PrePredicted, Protocol, RemoteEntityMap,
+    ReplicationGroup, ReplicationMode, ShouldBePredicted, SyncModeOverride,
 };
 use crate::shared::replication::components::{
     PerComponentReplicationMetadata, Replicate, ReplicationGroupId, ReplicationGroupIdBuilder,
@@ -22,20 +22,80 @@ pub(crate) struct ReplicationPlugin<P: Protocol, R: ReplicationSend<P>> {
     tick_duration: Duration,
     enable_send: bool,
     enable_receive: bool,
+    send_interval: Duration,
     _marker: std::marker::PhantomData<(P, R)>,
 }
 
 impl<P: Protocol, R: ReplicationSend<P>> ReplicationPlugin<P, R> {
-    pub(crate) fn new(tick_duration: Duration, enable_send: bool, enable_receive: bool) -> Self {
+    pub(crate) fn new(
+        tick_duration: Duration,
+        enable_send: bool,
+        enable_receive: bool,
+        send_interval: Duration,
+    ) -> Self {
         Self {
             tick_duration,
             enable_send,
             enable_receive,
+            send_interval,
             _marker: std::marker::PhantomData,
         }
     }
 }
 
+/// Timer that controls how often the replication updates are gathered,
+/// independently of how often the packets are sent
+#[derive(Resource)]
+pub(crate) struct ReplicationSendTimer<R> {
+    /// If None, the replication updates are gathered every time we send packets
+    timer: Option<Timer>,
+    /// True if the timer finished since the last time the replication updates were gathered
+    ready: bool,
+    _marker: std::marker::PhantomData<R>,
+}
+
+impl<R> ReplicationSendTimer<R> {
+    pub(crate) fn new(send_interval: Duration) -> Self {
+        Self {
+            timer: (send_interval != Duration::default())
+                .then_some(Timer::new(send_interval, TimerMode::Repeating)),
+            ready: false,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn tick(&mut self, delta: Duration) {
+        if let Some(timer) = self.timer.as_mut() {
+            timer.tick(delta);
+            self.ready |= timer.just_finished();
+        }
+    }
+
+    pub(crate) fn is_ready(&self) -> bool {
+        self.timer.is_none() || self.ready
+    }
+}
+
+fn tick_replication_timer<R: Resource>(
+    mut timer: ResMut<ReplicationSendTimer<R>>,
+    time: Res<Time<Virtual>>,
+) {
+    timer.tick(time.delta());
+}
+
+/// Run condition to check if the replication updates should be gathered this frame
+///
+/// Any state that is consumed by the replication systems (visibility changes, newly connected clients, etc.)
+/// must only be cleared when this is true, otherwise the changes that happen between two replication
+/// updates are lost.
+pub(crate) fn is_ready_to_replicate<R: Resource>(timer: Res<ReplicationSendTimer<R>>) -> bool {
+    timer.is_ready()
+}
+
+fn reset_replication_timer<R: Resource>(mut timer: ResMut<ReplicationSendTimer<R>>) {
+    timer.ready = false;
+}
+
 impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
     fn build(&self, app: &mut App) {
         // TODO: have a better constant for clean_interval?
@@ -54,6 +114,10 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
             .register_type::<ShouldBeInterpolated>()
             .register_type::<PrePredicted>()
             .register_type::<ShouldBePredicted>()
+            .register_type::<SyncModeOverride<P::ComponentKinds>>()
+            .register_type::<Authority>()
+            .register_type::<ControlledBy>()
+            .register_type::<Despawned>()
             .register_type::<RemoteEntityMap>()
             .register_type::<PredictedEntityMap>()
             .register_type::<InterpolatedEntityMap>();
@@ -81,6 +145,13 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
             app.configure_sets(
                 PostUpdate,
                 (
+                    // the replication updates can be gathered less often than the messages are sent
+                    (
+                        InternalReplicationSet::<R::SetMarker>::SendEntityUpdates,
+                        InternalReplicationSet::<R::SetMarker>::SendResourceUpdates,
+                        InternalReplicationSet::<R::SetMarker>::SendComponentUpdates,
+                    )
+                        .run_if(is_ready_to_replicate::<R>),
                     (
                         InternalReplicationSet::<R::SetMarker>::SendEntityUpdates,
                         InternalReplicationSet::<R::SetMarker>::SendResourceUpdates,
@@ -103,7 +174,20 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
                         .chain(),
                 ),
             );
+            // RESOURCES
+            app.insert_resource(ReplicationSendTimer::<R>::new(self.send_interval));
             // SYSTEMS
+            app.add_systems(
+                PostUpdate,
+                (
+                    tick_replication_timer::<R>.before(InternalMainSet::<R::SetMarker>::Send),
+                    // reset the timer only once the packets are sent, so that all the systems that clear
+                    // replication state (room bookkeeping, newly connected clients) know if the replication ran
+                    reset_replication_timer::<R>
+                        .after(InternalMainSet::<R::SetMarker>::SendPackets)
+                        .in_set(InternalMainSet::<R::SetMarker>::Send),
+                ),
+            );
             add_replication_send_systems::<P, R>(app);
             P::Components::add_per_component_replication_send_systems::<R>(app);
             app.add_systems(Last, cleanup::<P, R>.run_if(on_timer(clean_interval)));
@@ -113,3 +197,25 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_send_timer() {
+        let mut timer = ReplicationSendTimer::<()>::new(Duration::default());
+        assert!(timer.is_ready());
+
+        let mut timer = ReplicationSendTimer::<()>::new(Duration::from_millis(50));
+        timer.tick(Duration::from_millis(30));
+        assert!(!timer.is_ready());
+        timer.tick(Duration::from_millis(30));
+        assert!(timer.is_ready());
+        // the timer stays ready until the replication updates are gathered
+        timer.tick(Duration::from_millis(10));
+        assert!(timer.is_ready());
+        timer.ready = false;
+        assert!(!timer.is_ready());
+    }
+}
@@ -16,7 +16,7 @@ use crate::protocol::component::ComponentProtocol;
 use crate::protocol::component::{ComponentBehaviour, ComponentKindBehaviour};
 use crate::protocol::Protocol;
 use crate::shared::events::connection::ConnectionEvents;
-use crate::shared::replication::components::ReplicationGroupId;
+use crate::shared::replication::components::{DespawnBehavior, Despawned, ReplicationGroupId};
 
 use super::entity_map::RemoteEntityMap;
 use super::{
@@ -27,12 +27,19 @@ type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
 type EntityHashSet<K> = hashbrown::HashSet<K, EntityHash>;
 
+/// Number of ticks an actions message waits for the group it depends on before being applied anyway.
+/// The dependency might never be replicated (for example if it was despawned, or is not visible to us).
+const DEPENDENCY_TIMEOUT_TICKS: i16 = 64;
+
 pub(crate) struct ReplicationReceiver<P: Protocol> {
     /// Map between local and remote entities. (used mostly on client because it's when we receive entity updates)
     pub remote_entity_map: RemoteEntityMap,
 
     /// Map from remote entity to the replication group-id
     pub remote_entity_to_group: EntityHashMap<Entity, ReplicationGroupId>,
+    /// Remote entities that were mapped by the user to an existing local entity before being replicated.
+    /// When we receive the spawn for these entities, we reuse the local entity instead of spawning a new one.
+    pub pre_mapped_entities: EntityHashSet<Entity>,
 
     // BOTH
     /// Buffer to so that we have an ordered receiver per group
@@ -45,6 +52,7 @@ impl<P: Protocol> ReplicationReceiver<P> {
             // RECEIVE
             remote_entity_map: RemoteEntityMap::default(),
             remote_entity_to_group: Default::default(),
+            pre_mapped_entities: Default::default(),
             // BOTH
             group_channels: Default::default(),
         }
@@ -132,14 +140,66 @@ impl<P: Protocol> ReplicationReceiver<P> {
         )>,
     )> {
         trace!(?current_tick, ?self.group_channels, "reading replication messages");
-        self.group_channels
-            .iter_mut()
-            .filter_map(|(group_id, channel)| {
-                channel
+        let mut res = Vec::new();
+        let mut pending: Vec<ReplicationGroupId> = self.group_channels.keys().copied().collect();
+        // groups that depend on another group are only read once the other group has been read,
+        // so we read the groups in multiple passes
+        loop {
+            let (ready, blocked): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|group_id| self.is_dependency_satisfied(*group_id, current_tick));
+            if ready.is_empty() {
+                break;
+            }
+            for group_id in ready {
+                if let Some(messages) = self
+                    .group_channels
+                    .get_mut(&group_id)
+                    .unwrap()
                     .read_messages(current_tick)
-                    .map(|messages| (*group_id, messages))
-            })
-            .collect()
+                {
+                    res.push((group_id, messages));
+                }
+            }
+            pending = blocked;
+        }
+        if !pending.is_empty() {
+            trace!(?pending, "groups waiting for their dependency");
+        }
+        res
+    }
+
+    /// Returns false if the next actions message of the group depends on a group whose actions
+    /// haven't been applied yet.
+    ///
+    /// The dependency is ignored once the message has been waiting for more than [`DEPENDENCY_TIMEOUT_TICKS`].
+    fn is_dependency_satisfied(&self, group_id: ReplicationGroupId, current_tick: Tick) -> bool {
+        let Some(channel) = self.group_channels.get(&group_id) else {
+            return true;
+        };
+        let Some((tick, dependency)) = channel
+            .actions_recv_message_buffer
+            .get(&channel.actions_pending_recv_message_id)
+            .and_then(|(tick, message)| message.dependency.map(|dependency| (*tick, dependency)))
+        else {
+            return true;
+        };
+        if self
+            .group_channels
+            .get(&dependency)
+            .is_some_and(|channel| channel.latest_tick.is_some())
+        {
+            return true;
+        }
+        if current_tick - tick > DEPENDENCY_TIMEOUT_TICKS {
+            warn!(
+                ?group_id,
+                ?dependency,
+                "the dependency of the replication group was not received in time, ignoring it"
+            );
+            return true;
+        }
+        false
     }
 
     /// Gets the tick at which the provided confirmed entity currently is
@@ -149,6 +209,13 @@ impl<P: Protocol> ReplicationReceiver<P> {
             .and_then(|channel| channel.latest_tick)
     }
 
+    /// Map a remote entity to an already existing local entity, so that the entity does not
+    /// get spawned a second time when it is replicated
+    pub(crate) fn register_entity_mapping(&mut self, remote_entity: Entity, local_entity: Entity) {
+        self.remote_entity_map.insert(remote_entity, local_entity);
+        self.pre_mapped_entities.insert(remote_entity);
+    }
+
     /// Get the replication group id associated with a given local entity
     pub(crate) fn get_replication_group_id(
         &self,
@@ -190,6 +257,9 @@ impl<P: Protocol> ReplicationReceiver<P> {
     /// Apply any replication messages to the world, and emit an event
     /// I think we don't need to emit a tick with the event anymore, because
     /// we can access the tick via the replication manager
+    ///
+    /// `validate` is called on every component insert/update before it is applied to the local entity;
+    /// it can modify the component, or return None to reject it.
     pub(crate) fn apply_world(
         &mut self,
         world: &mut World,
@@ -197,6 +267,7 @@ impl<P: Protocol> ReplicationReceiver<P> {
         replication: ReplicationMessageData<P::Components, P::ComponentKinds>,
         group_id: ReplicationGroupId,
         events: &mut ConnectionEvents<P>,
+        validate: &impl Fn(Entity, P::Components) -> Option<P::Components>,
     ) {
         let _span = trace_span!("Apply received replication message to world").entered();
         match replication {
@@ -212,6 +283,20 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     if actions.spawn {
                         self.remote_entity_to_group.insert(*entity, group_id);
                         if let Some(local_entity) = self.remote_entity_map.get_local(*entity) {
+                            if self.pre_mapped_entities.remove(entity) {
+                                if let Some(mut local_entity_mut) =
+                                    world.get_entity_mut(*local_entity)
+                                {
+                                    debug!(remote_entity = ?entity, ?local_entity, "Received spawn for a pre-mapped entity");
+                                    local_entity_mut.insert(Confirmed {
+                                        predicted: None,
+                                        interpolated: None,
+                                        tick,
+                                    });
+                                    events.push_spawn(*local_entity);
+                                    continue;
+                                }
+                            }
                             if world.get_entity(*local_entity).is_some() {
                                 warn!("Received spawn for an entity that already exists");
                                 continue;
@@ -245,9 +330,19 @@ impl<P: Protocol> ReplicationReceiver<P> {
                             if let Some(group) = self.group_channels.get_mut(&group_id) {
                                 group.remote_entities.remove(&entity);
                             }
-                            // TODO: we despawn all children as well right now, but that might not be what we want?
-                            if let Some(entity_mut) = world.get_entity_mut(local_entity) {
-                                entity_mut.despawn_recursive();
+                            if let Some(mut entity_mut) = world.get_entity_mut(local_entity) {
+                                match entity_mut
+                                    .get::<DespawnBehavior>()
+                                    .copied()
+                                    .unwrap_or_default()
+                                {
+                                    // TODO: we despawn all children as well right now, but that might not be what we want?
+                                    DespawnBehavior::Despawn => entity_mut.despawn_recursive(),
+                                    DespawnBehavior::InsertMarker => {
+                                        entity_mut.insert(Despawned);
+                                    }
+                                    DespawnBehavior::Custom(f) => f(world, local_entity),
+                                }
                             }
                             events.push_despawn(local_entity);
                             self.remote_entity_to_group.remove(&entity);
@@ -275,6 +370,10 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     for mut component in actions.insert {
                         // map any entities inside the component
                         component.map_entities(&mut self.remote_entity_map);
+                        let Some(component) = validate(local_entity_mut.id(), component) else {
+                            debug!(remote_entity = ?entity, "InsertComponent rejected by validation");
+                            continue;
+                        };
                         // TODO: figure out what to do with tick here
                         events.push_insert_component(
                             local_entity_mut.id(),
@@ -312,6 +411,10 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     for mut component in actions.updates {
                         // map any entities inside the component
                         component.map_entities(&mut self.remote_entity_map);
+                        let Some(component) = validate(local_entity_mut.id(), component) else {
+                            debug!(remote_entity = ?entity, "UpdateComponent rejected by validation");
+                            continue;
+                        };
                         events.push_update_component(
                             local_entity_mut.id(),
                             (&component).into(),
@@ -332,6 +435,10 @@ impl<P: Protocol> ReplicationReceiver<P> {
                         for mut component in components {
                             // map any entities inside the component
                             component.map_entities(&mut self.remote_entity_map);
+                            let Some(component) = validate(local_entity.id(), component) else {
+                                debug!(remote_entity = ?entity, "UpdateComponent rejected by validation");
+                                continue;
+                            };
                             events.push_update_component(
                                 local_entity.id(),
                                 (&component).into(),
@@ -522,6 +629,7 @@ mod tests {
                 group_id,
                 data: ReplicationMessageData::Actions(EntityActionMessage {
                     sequence_id: MessageId(0) - 1,
+                    dependency: None,
                     actions: Default::default(),
                 }),
             },
@@ -548,6 +656,7 @@ mod tests {
                 group_id: ReplicationGroupId(0),
                 data: ReplicationMessageData::Actions(EntityActionMessage {
                     sequence_id: MessageId(0),
+                    dependency: None,
                     actions: Default::default(),
                 }),
             },
@@ -615,6 +724,7 @@ mod tests {
                 group_id: ReplicationGroupId(0),
                 data: ReplicationMessageData::Actions(EntityActionMessage {
                     sequence_id: MessageId(2),
+                    dependency: None,
                     actions: Default::default(),
                 }),
             },
@@ -628,6 +738,7 @@ mod tests {
                 group_id: ReplicationGroupId(0),
                 data: ReplicationMessageData::Actions(EntityActionMessage {
                     sequence_id: MessageId(1),
+                    dependency: None,
                     actions: Default::default(),
                 }),
             },
@@ -640,4 +751,108 @@ mod tests {
         assert_eq!(replication_data.get(1).unwrap().0, Tick(3));
         assert_eq!(replication_data.get(2).unwrap().0, Tick(4));
     }
+
+    #[test]
+    fn test_despawn_behavior() {
+        use crate::tests::stepper::{BevyStepper, Step};
+
+        let mut stepper = BevyStepper::default();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component1(1.0), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<crate::client::connection::ConnectionManager<MyProtocol>>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+
+        // keep the entity on the client when it gets despawned on the server
+        stepper
+            .client_app
+            .world
+            .entity_mut(client_entity)
+            .insert(DespawnBehavior::InsertMarker);
+        stepper.server_app.world.entity_mut(server_entity).despawn();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Despawned>(client_entity)
+            .is_some());
+    }
+
+    #[test]
+    fn test_group_dependency() {
+        let mut manager = ReplicationReceiver::<MyProtocol>::new();
+        let group_a = ReplicationGroupId(0);
+        let group_b = ReplicationGroupId(1);
+
+        // the actions of group B (which depend on group A) are received first
+        manager.recv_message(
+            ReplicationMessage {
+                group_id: group_b,
+                data: ReplicationMessageData::Actions(EntityActionMessage {
+                    sequence_id: MessageId(0),
+                    dependency: Some(group_a),
+                    actions: Default::default(),
+                }),
+            },
+            Tick(1),
+        );
+        assert!(manager.read_messages(Tick(10)).is_empty());
+
+        // once the actions of group A are received, both groups are read, A before B
+        manager.recv_message(
+            ReplicationMessage {
+                group_id: group_a,
+                data: ReplicationMessageData::Actions(EntityActionMessage {
+                    sequence_id: MessageId(0),
+                    dependency: None,
+                    actions: Default::default(),
+                }),
+            },
+            Tick(0),
+        );
+        let read_messages = manager.read_messages(Tick(10));
+        assert_eq!(read_messages.len(), 2);
+        assert_eq!(read_messages[0].0, group_a);
+        assert_eq!(read_messages[1].0, group_b);
+    }
+
+    #[test]
+    fn test_group_dependency_timeout() {
+        let mut manager = ReplicationReceiver::<MyProtocol>::new();
+        let group_a = ReplicationGroupId(0);
+        let group_b = ReplicationGroupId(1);
+
+        // group B depends on group A, which is never replicated
+        manager.recv_message(
+            ReplicationMessage {
+                group_id: group_b,
+                data: ReplicationMessageData::Actions(EntityActionMessage {
+                    sequence_id: MessageId(0),
+                    dependency: Some(group_a),
+                    actions: Default::default(),
+                }),
+            },
+            Tick(1),
+        );
+        assert!(manager
+            .read_messages(Tick(1 + DEPENDENCY_TIMEOUT_TICKS as u16))
+            .is_empty());
+
+        // after the timeout, the actions of group B are applied anyway
+        let read_messages = manager.read_messages(Tick(2 + DEPENDENCY_TIMEOUT_TICKS as u16));
+        assert_eq!(read_messages.len(), 1);
+        assert_eq!(read_messages[0].0, group_b);
+    }
 }
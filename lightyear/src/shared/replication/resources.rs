@@ -1,9 +1,10 @@
 //! Module to handle the replication of bevy [`Resource`]s
 
 use crate::_reexport::{ComponentProtocol, ReplicationSend};
-use crate::prelude::{Message, Protocol};
+use crate::prelude::{Message, Protocol, Tick};
 use crate::shared::replication::components::Replicate;
 use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
+use crate::utils::ready_buffer::ReadyBuffer;
 use async_compat::CompatExt;
 use bevy::app::App;
 use bevy::ecs::system::Command;
@@ -23,15 +24,39 @@ mod command {
         /// Start replicating a resource to remote clients.
         ///
         /// Any change to the resource will be replicated to the clients.
+        /// If the resource is already being replicated, this only updates the replication parameters
+        /// (for example the `replication_target`).
         // TODO: we use `Replicate<P>` as argument instead of the simpler `NetworkTarget`
         //  because it helps with type-inference when calling this method.
         //  We can switch to `NetworkTarget` if we remove the `P` bound of `Replicate`.
         fn replicate_resource<R: Resource + Clone>(&mut self, replicate: Replicate<P>);
     }
 
+    struct StartReplicateCommand<P: Protocol, R> {
+        replicate: Replicate<P>,
+        _marker: PhantomData<R>,
+    }
+
+    impl<P: Protocol, R: Resource + Clone> Command for StartReplicateCommand<P, R> {
+        fn apply(self, world: &mut World) {
+            if let Ok(entity) = world
+                .query_filtered::<Entity, With<ReplicateResource<R>>>()
+                .get_single(world)
+            {
+                // the resource is already replicated, only update how it is replicated
+                world.entity_mut(entity).insert(self.replicate);
+            } else {
+                world.spawn((ReplicateResource::<R>::default(), self.replicate));
+            }
+        }
+    }
+
     impl<P: Protocol> ReplicateResourceExt<P> for Commands<'_, '_> {
         fn replicate_resource<R: Resource + Clone>(&mut self, replicate: Replicate<P>) {
-            self.spawn((ReplicateResource::<R>::default(), replicate));
+            self.add(StartReplicateCommand::<P, R> {
+                replicate,
+                _marker: PhantomData,
+            });
         }
     }
 
@@ -74,7 +99,7 @@ pub use command::{ReplicateResourceExt, StopReplicateResourceExt};
 /// Only one entity per World should have this component.
 #[derive(Component, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ReplicateResource<R> {
-    resource: Option<R>,
+    pub(crate) resource: Option<R>,
 }
 
 impl<R> Default for ReplicateResource<R> {
@@ -83,6 +108,39 @@ impl<R> Default for ReplicateResource<R> {
     }
 }
 
+/// History of the predicted values of the resource `R` (`None` if the resource was removed).
+///
+/// Replicated resources that have a history are not overwritten directly when a server update is received,
+/// they are rolled back instead (see `ResourcePredictionPlugin`).
+#[derive(Resource, Debug)]
+pub struct ResourceHistory<R> {
+    pub(crate) buffer: ReadyBuffer<Tick, Option<R>>,
+}
+
+impl<R> Default for ResourceHistory<R> {
+    fn default() -> Self {
+        Self {
+            buffer: ReadyBuffer::new(),
+        }
+    }
+}
+
+impl<R: Clone> ResourceHistory<R> {
+    /// Get the value of the resource at the specified tick, and clear all the older history.
+    ///
+    /// The returned value is kept in the history, since the resource keeps that value until the next update.
+    pub(crate) fn pop_until_tick(&mut self, tick: Tick) -> Option<Option<R>> {
+        self.buffer.pop_until(&tick).map(|(tick, value)| {
+            self.buffer.add_item(tick, value.clone());
+            value
+        })
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffer = ReadyBuffer::new();
+    }
+}
+
 pub(crate) mod send {
     use super::*;
     pub(crate) struct ResourceSendPlugin<P, R> {
@@ -192,7 +250,12 @@ pub(crate) mod receive {
         mut commands: Commands,
         replicating_entity: Query<Ref<ReplicateResource<R>>>,
         resource: Option<ResMut<R>>,
+        predicted: Option<Res<ResourceHistory<R>>>,
     ) {
+        // predicted resources are only updated when we rollback
+        if predicted.is_some() {
+            return;
+        }
         if replicating_entity.iter().len() > 1 {
             error!(
                 "Only one entity per World should have a ReplicateResource<{:?}> component",
@@ -393,6 +456,42 @@ mod tests {
         assert_eq!(stepper.client_app.world.resource::<Resource1>().0, 1.0);
     }
 
+    #[test]
+    fn test_update_resource_replication_target() {
+        let mut stepper = BevyStepper::default();
+
+        let start_replicate_system =
+            stepper
+                .server_app
+                .world
+                .register_system(|mut commands: Commands| {
+                    commands.replicate_resource::<Resource1>(Replicate::default());
+                });
+        let update_target_system =
+            stepper
+                .server_app
+                .world
+                .register_system(|mut commands: Commands| {
+                    commands.replicate_resource::<Resource1>(Replicate {
+                        replication_target: NetworkTarget::None,
+                        ..Default::default()
+                    });
+                });
+        let _ = stepper.server_app.world.run_system(start_replicate_system);
+        stepper.frame_step();
+        let _ = stepper.server_app.world.run_system(update_target_system);
+        stepper.frame_step();
+
+        // there is still a single entity replicating the resource, with the updated target
+        let replicate = stepper
+            .server_app
+            .world
+            .query_filtered::<&Replicate, With<ReplicateResource<Resource1>>>()
+            .get_single(&stepper.server_app.world)
+            .unwrap();
+        assert_eq!(replicate.replication_target, NetworkTarget::None);
+    }
+
     #[test]
     fn test_client_disconnect() {
         let mut stepper = BevyStepper::default();
@@ -6,13 +6,13 @@ use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::EntityHash;
 use bevy::prelude::{Entity, Reflect};
 use bevy::utils::petgraph::data::ElementIterator;
-use bevy::utils::{hashbrown, HashMap, HashSet};
+use bevy::utils::{hashbrown, Duration, HashMap, HashSet};
 use crossbeam_channel::Receiver;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::_reexport::{EntityActionsChannel, EntityUpdatesChannel, FromType};
+use crate::_reexport::{EntityActionsChannel, EntityUpdatesChannel, FromType, WrappedTime};
 use crate::packet::message::MessageId;
-use crate::prelude::{ShouldBePredicted, Tick};
+use crate::prelude::{ShouldBePredicted, Tick, TimeManager};
 use crate::protocol::channel::ChannelKind;
 use crate::protocol::component::ComponentProtocol;
 use crate::protocol::component::{ComponentBehaviour, ComponentKindBehaviour};
@@ -57,12 +57,21 @@ pub(crate) struct ReplicationSender<P: Protocol> {
     /// Get notified whenever a message for a given ReplicationGroup was actually sent
     /// (sometimes they might not be sent because of bandwidth constraints
     pub message_send_receiver: Receiver<MessageId>,
+    /// If false, there is no bandwidth cap so every message is sent right away: no need to accumulate priority
+    bandwidth_cap_enabled: bool,
+    /// Maximum number of entity spawns that we send in a single send interval.
+    /// The replication groups that would exceed the limit are sent during the next send intervals instead.
+    pub(crate) max_spawns_per_send: Option<usize>,
+
+    // copy of current time so that we don't pollute the function signatures to much
+    current_time: WrappedTime,
 }
 
 impl<P: Protocol> ReplicationSender<P> {
     pub(crate) fn new(
         updates_ack_tracker: Receiver<MessageId>,
         message_send_receiver: Receiver<MessageId>,
+        bandwidth_cap_enabled: bool,
     ) -> Self {
         Self {
             // SEND
@@ -75,14 +84,35 @@ impl<P: Protocol> ReplicationSender<P> {
             group_channels: Default::default(),
             // PRIORITY
             message_send_receiver,
+            bandwidth_cap_enabled,
+            max_spawns_per_send: None,
+            current_time: WrappedTime::default(),
         }
     }
 
-    /// If we got notified that an update got send (included in a packet), we reset the accumulated priority to 0.0
-    /// Then all replication_group_ids, we accumulate the priority.
+    pub(crate) fn update(&mut self, time_manager: &TimeManager) {
+        self.current_time = time_manager.current_time();
+    }
+
+    /// Number of entity spawns that are waiting to be sent because of `max_spawns_per_send`
+    pub(crate) fn num_pending_spawns(&self) -> usize {
+        self.pending_actions
+            .values()
+            .flat_map(|actions| actions.values())
+            .filter(|actions| actions.spawn)
+            .count()
+    }
+
+    /// If we got notified that an update got send (included in a packet), we reset the accumulated priority
+    /// to the base priority.
+    /// Then for all replication groups that still have an update waiting to be sent (because it didn't fit in the
+    /// bandwidth quota), we accumulate the priority, so that starved groups end up being sent first.
     ///
     /// This should be call after the Send SystemSet.
     pub(crate) fn recv_send_notification(&mut self) {
+        if !self.bandwidth_cap_enabled {
+            return;
+        }
         // TODO: handle errors that are not channel::isEmpty
         while let Ok(message_id) = self.message_send_receiver.try_recv() {
             if let Some((group_id, _)) = self.updates_message_id_to_group_id.get(&message_id) {
@@ -94,7 +124,8 @@ impl<P: Protocol> ReplicationSender<P> {
                         ?group_id,
                         "successfully sent message for replication group! Resetting priority"
                     );
-                    channel.accumulated_priority = Some(0.0);
+                    channel.accumulated_priority = None;
+                    channel.waiting_for_send = false;
                 } else {
                     error!(?message_id, ?group_id, "Received a send message-id notification but the corresponding group channel does not exist");
                 }
@@ -105,15 +136,18 @@ impl<P: Protocol> ReplicationSender<P> {
             }
         }
 
-        // TODO: don't accumulate priority if priority is not enabled
-        // then accumulate the priority for all replication groups
-        self.group_channels.values_mut().for_each(|channel| {
-            channel.accumulated_priority = channel
-                .accumulated_priority
-                .map_or(Some(channel.base_priority), |acc| {
-                    Some(acc + channel.base_priority)
-                });
-        });
+        // then accumulate the priority for all replication groups that could not be sent
+        self.group_channels
+            .values_mut()
+            .filter(|channel| channel.waiting_for_send)
+            .for_each(|channel| {
+                channel.accumulated_priority = Some(
+                    channel
+                        .accumulated_priority
+                        .unwrap_or(channel.base_priority)
+                        + channel.base_priority,
+                );
+            });
     }
 
     // TODO: call this in a system after receive
@@ -170,12 +204,20 @@ impl<P: Protocol> ReplicationSender<P> {
     }
 
     pub(crate) fn prepare_entity_despawn(&mut self, entity: Entity, group_id: ReplicationGroupId) {
-        self.pending_actions
-            .entry(group_id)
-            .or_default()
-            .entry(entity)
-            .or_default()
-            .despawn = true;
+        let group_actions = self.pending_actions.entry(group_id).or_default();
+        // the spawn was never sent (for example because it was delayed by `max_spawns_per_send`),
+        // so there is nothing to despawn on the remote
+        if group_actions
+            .get(&entity)
+            .is_some_and(|actions| actions.spawn)
+        {
+            group_actions.remove(&entity);
+            if group_actions.is_empty() {
+                self.pending_actions.remove(&group_id);
+            }
+            return;
+        }
+        group_actions.entry(entity).or_default().despawn = true;
     }
 
     // we want to send all component inserts that happen together for the same entity in a single message
@@ -300,12 +342,32 @@ impl<P: Protocol> ReplicationSender<P> {
         f32,
     )> {
         let mut messages = Vec::new();
+        let mut num_spawns = 0;
+        let mut delayed_actions = EntityHashMap::default();
 
         for (group_id, mut actions) in self.pending_actions.drain() {
             trace!(?group_id, "pending actions: {:?}", actions);
+            // spread large amounts of spawns (for example when a client joins a big world) over multiple send intervals
+            if let Some(max_spawns) = self.max_spawns_per_send {
+                let group_spawns = actions.values().filter(|actions| actions.spawn).count();
+                // we always send at least one group, even if it contains more than `max_spawns` entities
+                if group_spawns > 0 && num_spawns > 0 && num_spawns + group_spawns > max_spawns {
+                    trace!(
+                        ?group_id,
+                        "delaying entity spawns because of max_spawns_per_send"
+                    );
+                    // the updates will be collected again for the next send interval since they were not acked
+                    self.pending_updates.remove(&group_id);
+                    delayed_actions.insert(group_id, actions);
+                    continue;
+                }
+                num_spawns += group_spawns;
+            }
             // add any updates for that group
+            let channel = self.group_channels.entry(group_id).or_default();
             if let Some(updates) = self.pending_updates.remove(&group_id) {
                 trace!(?group_id, "found updates for group: {:?}", updates);
+                channel.last_updates_send_time = Some(self.current_time);
                 for (entity, components) in updates {
                     actions
                         .entry(entity)
@@ -314,11 +376,14 @@ impl<P: Protocol> ReplicationSender<P> {
                         .extend(components.into_iter());
                 }
             }
-            let channel = self.group_channels.entry(group_id).or_default();
             let priority = channel
                 .accumulated_priority
                 .unwrap_or(channel.base_priority);
             let message_id = channel.actions_next_send_message_id;
+            // only the spawns need to wait for the dependency
+            let dependency = channel
+                .dependency
+                .filter(|_| actions.values().any(|actions| actions.spawn));
             channel.actions_next_send_message_id += 1;
             channel.last_action_tick = Some(tick);
             messages.push((
@@ -326,6 +391,7 @@ impl<P: Protocol> ReplicationSender<P> {
                 group_id,
                 ReplicationMessageData::Actions(EntityActionMessage {
                     sequence_id: message_id,
+                    dependency,
                     // TODO: maybe we can just send the HashMap directly?
                     actions: Vec::from_iter(actions.into_iter()),
                 }),
@@ -337,6 +403,16 @@ impl<P: Protocol> ReplicationSender<P> {
         for (group_id, updates) in self.pending_updates.drain() {
             trace!(?group_id, "pending updates: {:?}", updates);
             let channel = self.group_channels.entry(group_id).or_default();
+            // the changes will be collected again for the next update, since they haven't been acked
+            if !channel.is_ready_to_send_updates(self.current_time) {
+                trace!(
+                    ?group_id,
+                    "skipping updates because of the group's send_interval"
+                );
+                continue;
+            }
+            channel.last_updates_send_time = Some(self.current_time);
+            channel.waiting_for_send = true;
             let priority = channel
                 .accumulated_priority
                 .unwrap_or(channel.base_priority);
@@ -359,6 +435,7 @@ impl<P: Protocol> ReplicationSender<P> {
 
         // clear send buffers
         self.pending_unique_components.clear();
+        self.pending_actions = delayed_actions;
         messages
     }
 }
@@ -379,6 +456,15 @@ pub struct GroupChannel {
     /// for this group because of the bandwidth cap, in which case it will be accumulated.
     pub accumulated_priority: Option<f32>,
     pub base_priority: f32,
+    /// True if we buffered an update message for this group that hasn't been sent yet
+    pub waiting_for_send: bool,
+
+    /// Minimum interval between two update messages for this group
+    pub send_interval: Duration,
+    /// Last time we sent updates for this group
+    pub last_updates_send_time: Option<WrappedTime>,
+    /// Group whose spawns must be applied by the remote before the spawns of this group
+    pub dependency: Option<ReplicationGroupId>,
 }
 
 impl Default for GroupChannel {
@@ -389,11 +475,22 @@ impl Default for GroupChannel {
             accumulated_priority: None,
             collect_changes_since_this_tick: None,
             base_priority: 1.0,
+            waiting_for_send: false,
+            send_interval: Duration::ZERO,
+            last_updates_send_time: None,
+            dependency: None,
         }
     }
 }
 
 impl GroupChannel {
+    /// Returns true if enough time has passed since the last update message, according to the group's `send_interval`
+    pub(crate) fn is_ready_to_send_updates(&self, current_time: WrappedTime) -> bool {
+        self.last_updates_send_time.map_or(true, |last_send_time| {
+            current_time >= last_send_time + self.send_interval
+        })
+    }
+
     /// Update the bevy_tick at which we received entity updates for this group
     /// (we will only collect updates since this tick)
     pub(crate) fn update_collect_changes_since_this_tick(&mut self, bevy_tick: BevyTick) {
@@ -421,7 +518,7 @@ mod tests {
     fn test_buffer_replication_messages() {
         // create fake channels for receiving updates about acks and sends
         let (sender, receiver) = crossbeam_channel::unbounded();
-        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver);
+        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver, false);
 
         let entity_1 = Entity::from_raw(0);
         let entity_2 = Entity::from_raw(1);
@@ -539,4 +636,115 @@ mod tests {
             Some(Tick(2))
         );
     }
+
+    #[test]
+    fn test_send_interval() {
+        let (_, receiver) = crossbeam_channel::unbounded();
+        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver, false);
+        let entity = Entity::from_raw(0);
+        let group = ReplicationGroupId(0);
+        manager.group_channels.insert(
+            group,
+            GroupChannel {
+                send_interval: Duration::from_millis(200),
+                ..Default::default()
+            },
+        );
+        let update = MyComponentsProtocol::Component1(Component1(1.0));
+
+        // the first update is sent right away
+        manager.prepare_entity_update(entity, group, update.clone());
+        assert_eq!(manager.finalize(Tick(0)).len(), 1);
+
+        // the send_interval hasn't elapsed yet, so the update is not sent
+        manager.current_time = WrappedTime::new(100);
+        manager.prepare_entity_update(entity, group, update.clone());
+        assert!(manager.finalize(Tick(1)).is_empty());
+
+        // the changes are collected again, and sent once the send_interval has elapsed
+        manager.current_time = WrappedTime::new(200);
+        manager.prepare_entity_update(entity, group, update.clone());
+        assert_eq!(manager.finalize(Tick(2)).len(), 1);
+    }
+
+    #[test]
+    fn test_max_spawns_per_send() {
+        let (_, receiver) = crossbeam_channel::unbounded();
+        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver, false);
+        manager.max_spawns_per_send = Some(2);
+        for i in 0..5 {
+            manager.prepare_entity_spawn(Entity::from_raw(i), ReplicationGroupId(i as u64));
+        }
+
+        // the spawns are spread over multiple send intervals
+        assert_eq!(manager.finalize(Tick(0)).len(), 2);
+        assert_eq!(manager.finalize(Tick(1)).len(), 2);
+        // the spawn of a delayed entity gets cancelled by the despawn
+        let (entity, group) = manager
+            .pending_actions
+            .iter()
+            .map(|(group, actions)| (*actions.keys().next().unwrap(), *group))
+            .next()
+            .unwrap();
+        manager.prepare_entity_despawn(entity, group);
+        assert!(manager.finalize(Tick(2)).is_empty());
+    }
+
+    #[test]
+    fn test_accumulate_priority() {
+        let (_, ack_receiver) = crossbeam_channel::unbounded();
+        let (send_sender, send_receiver) = crossbeam_channel::unbounded();
+        let mut manager = ReplicationSender::<MyProtocol>::new(ack_receiver, send_receiver, true);
+        let entity_1 = Entity::from_raw(0);
+        let entity_2 = Entity::from_raw(1);
+        let group_1 = ReplicationGroupId(0);
+        let group_2 = ReplicationGroupId(1);
+        manager.update_base_priority(group_1, 1.0);
+        manager.update_base_priority(group_2, 3.0);
+        for (entity, group) in [(entity_1, group_1), (entity_2, group_2)] {
+            manager.prepare_entity_update(
+                entity,
+                group,
+                MyComponentsProtocol::Component1(Component1(1.0)),
+            );
+        }
+        let messages = manager.finalize(Tick(0));
+        assert_eq!(messages.len(), 2);
+        manager
+            .updates_message_id_to_group_id
+            .insert(MessageId(0), (group_2, BevyTick::new(0)));
+
+        // only the update for group 2 was sent because of the bandwidth cap
+        send_sender.send(MessageId(0)).unwrap();
+        manager.recv_send_notification();
+
+        // the priority of group 1 accumulates, the priority of group 2 is reset
+        assert_eq!(
+            manager
+                .group_channels
+                .get(&group_1)
+                .unwrap()
+                .accumulated_priority,
+            Some(2.0)
+        );
+        assert_eq!(
+            manager
+                .group_channels
+                .get(&group_2)
+                .unwrap()
+                .accumulated_priority,
+            None
+        );
+
+        // group 1 keeps accumulating priority until it is sent
+        manager.recv_send_notification();
+        assert_eq!(
+            manager
+                .group_channels
+                .get(&group_1)
+                .unwrap()
+                .accumulated_priority,
+            Some(3.0)
+        );
+    }
 }
@@ -2,11 +2,12 @@
 use std::any::TypeId;
 use std::ops::Deref;
 
-use bevy::ecs::entity::Entities;
+use bevy::ecs::component::Tick as BevyTick;
+use bevy::ecs::entity::{Entities, EntityHashMap};
 use bevy::ecs::system::SystemChangeTick;
 use bevy::prelude::{
-    Added, App, Commands, Component, DetectChanges, Entity, IntoSystemConfigs, PostUpdate,
-    PreUpdate, Query, Ref, RemovedComponents, Res, ResMut, With, Without,
+    Added, App, Changed, Commands, Component, DetectChanges, Entity, IntoSystemConfigs, Local,
+    PostUpdate, PreUpdate, Query, Ref, RemovedComponents, Res, ResMut, With, Without,
 };
 use tracing::{debug, error, info, trace, warn};
 
@@ -15,7 +16,10 @@ use crate::prelude::{NetworkTarget, TickManager};
 use crate::protocol::Protocol;
 use crate::server::replication::ServerReplicationSet;
 use crate::server::room::ClientVisibility;
-use crate::shared::replication::components::{DespawnTracker, Replicate, ReplicationMode};
+use crate::shared::replication::components::{
+    DespawnTracker, Replicate, ReplicationMode, ReplicationThreshold,
+};
+use crate::shared::replication::plugin::is_ready_to_replicate;
 use crate::shared::replication::ReplicationSend;
 use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 
@@ -225,20 +229,62 @@ fn send_entity_spawn<P: Protocol, R: ReplicationSend<P>>(
 /// (currently we only check for the second condition, which is enough but less efficient)
 ///
 /// NOTE: cannot use ConnectEvents because they are reset every frame
+///
+/// If there is a [`ReplicationThreshold`] for the component, changes that are too small compared with the
+/// last value that was sent are not replicated.
 fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P>>(
     query: Query<(Entity, Ref<C>, Ref<Replicate<P>>)>,
     system_bevy_ticks: SystemChangeTick,
+    threshold: Option<Res<ReplicationThreshold<C>>>,
+    // last value that was sent for each entity, along with its change tick
+    mut last_sent: Local<EntityHashMap<(C, BevyTick)>>,
     mut sender: ResMut<R>,
 ) where
     <P as Protocol>::Components: From<C>,
     P::ComponentKinds: FromType<C>,
 {
     let kind = <P::ComponentKinds as FromType<C>>::from_type();
+    if threshold.is_some() {
+        last_sent.retain(|entity, _| query.contains(*entity));
+    }
     query.iter().for_each(|(entity, component, replicate)| {
+        // if the change is below the threshold, we act as if the component didn't change since the last value we sent
+        let mut change_tick = component.last_changed();
+        let mut record_sent_value = false;
+        if let Some(threshold) = threshold.as_ref() {
+            match last_sent.get(&entity) {
+                Some((last_value, last_change_tick))
+                    if !threshold.exceeds(last_value, &component) =>
+                {
+                    change_tick = *last_change_tick;
+                }
+                _ => record_sent_value = true,
+            }
+        }
+        let mut sent = false;
         // do not replicate components that are disabled
         if replicate.is_disabled::<C>() {
             return;
         }
+        // replicate-once components only need to be sent when the entity is replicated to a new client,
+        // so we can skip them entirely most of the time
+        if replicate.is_replicate_once::<C>()
+            && !component.is_added()
+            && !replicate.is_changed()
+            && sender.new_connected_clients().is_empty()
+        {
+            return;
+        }
+        // the target of the component might have changed since the last time we replicated it
+        if replicate.is_changed() && !replicate.is_added() && !component.is_added() {
+            send_component_target_change::<C, P, R>(
+                entity,
+                &component,
+                replicate.as_ref(),
+                &mut *sender,
+                system_bevy_ticks.this_run(),
+            );
+        }
         match replicate.replication_mode {
             ReplicationMode::Room => {
                 replicate
@@ -251,8 +297,9 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                                 //  but maybe we can instead serialize it to Bytes early and then have the bytes be shared between clients?
                                 //  or just pass a reference?
                                 ClientVisibility::Gained => {
-                                    let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
-                                    let _ = sender
+                                    let target = replicate
+                                        .target::<C>(NetworkTarget::Only(vec![*client_id]));
+                                    sent |= sender
                                         .prepare_component_insert(
                                             entity,
                                             component.clone().into(),
@@ -262,14 +309,16 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                                         )
                                         .map_err(|e| {
                                             error!("error sending component insert: {:?}", e);
-                                        });
+                                        })
+                                        .is_ok();
                                 }
                                 ClientVisibility::Lost => {}
                                 ClientVisibility::Maintained => {
                                     // send an component_insert for components that were newly added
                                     if component.is_added() {
-                                        let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
-                                        let _ = sender
+                                        let target = replicate
+                                            .target::<C>(NetworkTarget::Only(vec![*client_id]));
+                                        sent |= sender
                                             .prepare_component_insert(
                                                 entity,
                                                 component.clone().into(),
@@ -279,26 +328,29 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                                             )
                                             .map_err(|e| {
                                                 error!("error sending component insert: {:?}", e);
-                                            });
+                                            })
+                                            .is_ok();
                                         // only update components that were not newly added
                                     } else {
                                         // do not send updates for these components, only inserts/removes
                                         if replicate.is_replicate_once::<C>() {
                                             return;
                                         }
-                                        let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
-                                        let _ = sender
+                                        let target = replicate
+                                            .target::<C>(NetworkTarget::Only(vec![*client_id]));
+                                        sent |= sender
                                             .prepare_component_update(
                                                 entity,
                                                 component.clone().into(),
                                                 replicate.as_ref(),
                                                 target,
-                                                component.last_changed(),
+                                                change_tick,
                                                 system_bevy_ticks.this_run(),
                                             )
                                             .map_err(|e| {
                                                 error!("error sending component update: {:?}", e);
-                                            });
+                                            })
+                                            .is_ok();
                                     }
                                 }
                             }
@@ -315,7 +367,7 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                     let mut new_connected_target = target.clone();
                     new_connected_target
                         .intersection(NetworkTarget::Only(new_connected_clients.clone()));
-                    let _ = sender
+                    sent |= sender
                         .prepare_component_insert(
                             entity,
                             component.clone().into(),
@@ -325,7 +377,8 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                         )
                         .map_err(|e| {
                             error!("error sending component insert: {:?}", e);
-                        });
+                        })
+                        .is_ok();
                     // don't re-send to newly connection client
                     target.exclude(new_connected_clients.clone());
                 }
@@ -339,7 +392,7 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                 //  on the receiver's entity world mut to know if we emit a ComponentInsert or a ComponentUpdate?
                 if component.is_added() || replicate.is_added() {
                     trace!("component is added");
-                    let _ = sender
+                    sent |= sender
                         .prepare_component_insert(
                             entity,
                             component.clone().into(),
@@ -349,36 +402,177 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
                         )
                         .map_err(|e| {
                             error!("error sending component insert: {:?}", e);
-                        });
-                } else {
+                        })
+                        .is_ok();
+                } else if replicate.is_replicate_once::<C>() {
                     // do not send updates for these components, only inserts/removes
-                    if replicate.is_replicate_once::<C>() {
-                        trace!(?entity,
-                            "not replicating updates for {:?} because it is marked as replicate_once",
-                            kind
-                        );
-                        return;
-                    }
+                    trace!(
+                        ?entity,
+                        "not replicating updates for {:?} because it is marked as replicate_once",
+                        kind
+                    );
+                } else {
                     // otherwise send an update for all components that changed since the
                     // last update we have ack-ed
-                    let _ = sender
+                    sent |= sender
                         .prepare_component_update(
                             entity,
                             component.clone().into(),
                             replicate.as_ref(),
                             replicate.target::<C>(target),
-                            component.last_changed(),
+                            change_tick,
                             system_bevy_ticks.this_run(),
                         )
                         .map_err(|e| {
                             error!("error sending component update: {:?}", e);
-                        });
+                        })
+                        .is_ok();
                 }
             }
         }
+        // only remember the values that were actually sent
+        if record_sent_value && sent {
+            last_sent.insert(entity, (component.clone(), change_tick));
+        }
     });
 }
 
+/// If the per-component target of `C` was modified, insert the component on the clients that were added to the target
+/// and remove it from the clients that were removed from the target.
+fn send_component_target_change<C: Component + Clone, P: Protocol, R: ReplicationSend<P>>(
+    entity: Entity,
+    component: &C,
+    replicate: &Replicate<P>,
+    sender: &mut R,
+    bevy_tick: BevyTick,
+) where
+    <P as Protocol>::Components: From<C>,
+    P::ComponentKinds: FromType<C>,
+{
+    let Some(previous_replicate) = sender.get_mut_replicate_component_cache().get(&entity) else {
+        return;
+    };
+    // we only handle changes of the component target, not of the entity target
+    let previous_target = previous_replicate.target::<C>(replicate.replication_target.clone());
+    let target = replicate.target::<C>(replicate.replication_target.clone());
+    if previous_target == target {
+        return;
+    }
+    let mut gained = target.clone();
+    gained.difference(&previous_target);
+    // newly connected clients will receive the component anyway
+    gained.exclude(sender.new_connected_clients());
+    let mut lost = previous_target;
+    lost.difference(&target);
+    if replicate.replication_mode == ReplicationMode::Room {
+        // only handle the clients that already have the entity
+        let visible = NetworkTarget::Only(
+            replicate
+                .replication_clients_cache
+                .iter()
+                .filter(|(_, visibility)| matches!(visibility, ClientVisibility::Maintained))
+                .map(|(client_id, _)| *client_id)
+                .collect(),
+        );
+        gained.intersection(visible.clone());
+        lost.intersection(visible);
+    }
+    trace!(?entity, ?gained, ?lost, "component target changed");
+    if gained != NetworkTarget::None {
+        let _ = sender
+            .prepare_component_insert(
+                entity,
+                component.clone().into(),
+                replicate,
+                gained,
+                bevy_tick,
+            )
+            .map_err(|e| {
+                error!("error sending component insert: {:?}", e);
+            });
+    }
+    if lost != NetworkTarget::None {
+        let _ = sender
+            .prepare_component_remove(
+                entity,
+                <P::ComponentKinds as FromType<C>>::from_type(),
+                replicate,
+                lost,
+                bevy_tick,
+            )
+            .map_err(|e| {
+                error!("error sending component remove: {:?}", e);
+            });
+    }
+}
+
+/// If the [`SyncModeOverride`](crate::prelude::SyncModeOverride) of an entity was modified after the entity
+/// was spawned, send the new overrides to the clients that predict or interpolate the entity.
+fn send_sync_mode_override_change<P: Protocol, R: ReplicationSend<P>>(
+    system_bevy_ticks: SystemChangeTick,
+    query: Query<(Entity, Ref<Replicate<P>>), Changed<Replicate<P>>>,
+    mut sender: ResMut<R>,
+) {
+    for (entity, replicate) in query.iter() {
+        if replicate.is_added() {
+            continue;
+        }
+        let Some(previous_replicate) = sender.get_mut_replicate_component_cache().get(&entity)
+        else {
+            continue;
+        };
+        let sync_mode_override = replicate.sync_mode_override();
+        if previous_replicate.sync_mode_override() == sync_mode_override {
+            continue;
+        }
+        let mut target = replicate.prediction_target.clone();
+        target.union(&replicate.interpolation_target);
+        target.intersection(replicate.replication_target.clone());
+        // newly connected clients will receive the overrides with the entity spawn
+        target.exclude(sender.new_connected_clients());
+        if replicate.replication_mode == ReplicationMode::Room {
+            // only handle the clients that already have the entity
+            target.intersection(NetworkTarget::Only(
+                replicate
+                    .replication_clients_cache
+                    .iter()
+                    .filter(|(_, visibility)| matches!(visibility, ClientVisibility::Maintained))
+                    .map(|(client_id, _)| *client_id)
+                    .collect(),
+            ));
+        }
+        if target == NetworkTarget::None {
+            continue;
+        }
+        trace!(?entity, ?target, "sync mode override changed");
+        // the new overrides replace the previous ones on the client (an empty override removes them)
+        let _ = sender
+            .prepare_component_insert(
+                entity,
+                P::Components::from(sync_mode_override),
+                replicate.deref(),
+                target,
+                system_bevy_ticks.this_run(),
+            )
+            .map_err(|e| {
+                error!("error sending sync mode override: {:?}", e);
+            });
+    }
+}
+
+/// Keep the cached `Replicate` components up-to-date, so that we can detect what changed in them
+fn update_replicate_component_cache<P: Protocol, R: ReplicationSend<P>>(
+    mut sender: ResMut<R>,
+    query: Query<(Entity, &Replicate<P>), Changed<Replicate<P>>>,
+) {
+    let cache = sender.get_mut_replicate_component_cache();
+    for (entity, replicate) in query.iter() {
+        if let Some(cached) = cache.get_mut(&entity) {
+            *cached = replicate.clone();
+        }
+    }
+}
+
 /// This system sends updates for all components that were removed
 fn send_component_removed<C: Component + Clone, P: Protocol, R: ReplicationSend<P>>(
     // only remove the component for entities that are being actively replicated
@@ -464,6 +658,16 @@ pub fn add_replication_send_systems<P: Protocol, R: ReplicationSend<P>>(app: &mu
             )
                 .chain()
                 .in_set(InternalReplicationSet::<R::SetMarker>::SendDespawnsAndRemovals),
+            send_sync_mode_override_change::<P, R>
+                .in_set(InternalReplicationSet::<R::SetMarker>::SendComponentUpdates),
+            // NOTE: this must run after all the component updates were prepared, since they compare
+            //  the cached Replicate with the current one
+            //  (and only when the component updates were prepared, otherwise we would miss the changes)
+            update_replicate_component_cache::<P, R>
+                .run_if(is_ready_to_replicate::<R>)
+                .after(InternalReplicationSet::<R::SetMarker>::SendComponentUpdates)
+                .before(InternalMainSet::<R::SetMarker>::SendPackets)
+                .in_set(InternalMainSet::<R::SetMarker>::Send),
         ),
     );
 }
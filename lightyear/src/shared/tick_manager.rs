@@ -47,25 +47,70 @@ impl Plugin for TickManagerPlugin {
     }
 }
 
+/// Build it with [`TickConfig::new`] and the `with_*` methods, so that adding new options doesn't break your code
 #[derive(Clone, Debug, Reflect)]
 pub struct TickConfig {
     pub tick_duration: Duration,
+    /// If true, the 'generation' of the tick (the number of times the 16-bit tick wrapped around)
+    /// is included in every packet, so that both peers can compute a 64-bit tick with
+    /// [`TickManager::extended_tick`] that never wraps.
+    ///
+    /// This is useful for servers that run for days (the 16-bit tick wraps around every ~18 minutes at 60Hz),
+    /// at the cost of 4 extra bytes per packet. Nothing is added to the packets when it is disabled.
+    ///
+    /// The client and the server must use the same value.
+    pub extended: bool,
+    /// Number of [`FixedUpdate`] steps (physics steps) per network tick.
+    ///
+    /// For example, with a `tick_duration` of 1/30s and 4 substeps, the physics run at 120Hz
+    /// while the [`Tick`] (used for inputs, replication and prediction history) is incremented at 30Hz.
+    /// The inputs are written on the first substep of each tick and are available until the last substep;
+    /// the prediction history is recorded on the last substep.
+    pub physics_substeps: u16,
 }
 
 impl TickConfig {
     pub fn new(tick_duration: Duration) -> Self {
-        Self { tick_duration }
+        Self {
+            tick_duration,
+            extended: false,
+            physics_substeps: 1,
+        }
+    }
+
+    pub fn with_extended(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    pub fn with_physics_substeps(mut self, physics_substeps: u16) -> Self {
+        self.physics_substeps = physics_substeps;
+        self
+    }
+
+    /// Number of physics steps per network tick (at least 1)
+    pub fn substeps(&self) -> u16 {
+        self.physics_substeps.max(1)
+    }
+
+    /// Duration of a [`FixedUpdate`] step
+    pub fn fixed_timestep(&self) -> Duration {
+        self.tick_duration / self.substeps() as u32
     }
 }
 
 /// Manages the tick for the host system. Ticks are incremented by one every time
 /// the [`bevy::prelude::FixedUpdate`] schedule runs
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct TickManager {
     /// Tick configuration
     pub config: TickConfig,
     /// Current tick (sequence number of the FixedUpdate schedule)
     tick: Tick,
+    /// Number of times the tick wrapped around
+    generation: u32,
+    /// Index of the current physics step within the tick (see [`TickConfig::physics_substeps`])
+    substep: u16,
 }
 
 impl TickManager {
@@ -73,18 +118,38 @@ impl TickManager {
         Self {
             config,
             tick: Tick(0),
+            generation: 0,
+            substep: 0,
         }
     }
 
     // NOTE: this is public just for integration testing purposes
     #[doc(hidden)]
     pub fn increment_tick(&mut self) {
+        self.substep += 1;
+        if self.substep < self.config.substeps() {
+            trace!(tick = ?self.tick, substep = ?self.substep, "incremented substep");
+            return;
+        }
+        self.substep = 0;
         self.tick += 1;
+        if self.tick == Tick(0) {
+            self.generation = self.generation.wrapping_add(1);
+        }
         trace!(new_tick = ?self.tick, "incremented tick")
     }
     pub(crate) fn set_tick_to(&mut self, tick: Tick) -> TickEvent {
         let old_tick = self.tick;
         self.tick = tick;
+        // the next physics step will be the first substep of the next tick
+        self.substep = self.config.substeps() - 1;
+        // update the generation if the snap crossed a wrapping boundary
+        let diff = tick - old_tick;
+        if diff > 0 && tick.0 < old_tick.0 {
+            self.generation = self.generation.wrapping_add(1);
+        } else if diff < 0 && tick.0 > old_tick.0 {
+            self.generation = self.generation.saturating_sub(1);
+        }
         // info!(?old_tick, new_tick =?tick, "tick snap event");
         TickEvent::TickSnap {
             old_tick,
@@ -96,4 +161,118 @@ impl TickManager {
     pub fn tick(&self) -> Tick {
         self.tick
     }
+
+    /// Get the index of the current physics step within the tick (see [`TickConfig::physics_substeps`])
+    pub fn substep(&self) -> u16 {
+        self.substep
+    }
+
+    /// Get the current tick as a 64-bit value that does not wrap around.
+    ///
+    /// On the client, this is only consistent with the server's extended tick if [`TickConfig::extended`]
+    /// is enabled, since the generation is then synced from the server packets.
+    pub fn extended_tick(&self) -> u64 {
+        ((self.generation as u64) << 16) | self.tick.0 as u64
+    }
+
+    /// The generation of the tick to include in the packet header, if extended ticks are enabled
+    pub(crate) fn tick_generation(&self) -> Option<u32> {
+        self.config.extended.then_some(self.generation)
+    }
+
+    /// Update the generation of our tick from the extended tick of a remote peer,
+    /// assuming that our tick is within `i16::MAX` ticks of the remote tick
+    pub(crate) fn sync_generation(&mut self, remote_extended_tick: u64) {
+        let remote_tick = Tick(remote_extended_tick as u16);
+        let extended_tick = remote_extended_tick as i64 + (self.tick - remote_tick) as i64;
+        self.generation = (extended_tick.max(0) >> 16) as u32;
+    }
+
+    /// Change the tick duration, along with the timestep of the [`FixedUpdate`] schedule
+    pub(crate) fn set_tick_duration(
+        &mut self,
+        tick_duration: Duration,
+        fixed_time: &mut Time<Fixed>,
+    ) {
+        self.config.tick_duration = tick_duration;
+        fixed_time.set_timestep(self.config.fixed_timestep());
+    }
+}
+
+/// Index of the physics step within the tick, taking into account the rollback
+fn current_substep(tick_manager: &TickManager, rollback: Option<&Rollback>) -> u16 {
+    match rollback {
+        Some(rollback) if rollback.is_rollback() => rollback.substep,
+        _ => tick_manager.substep(),
+    }
+}
+
+/// Run condition that returns true on the first physics step of each tick
+/// (see [`TickConfig::physics_substeps`])
+pub fn is_first_substep(tick_manager: Res<TickManager>, rollback: Option<Res<Rollback>>) -> bool {
+    current_substep(&tick_manager, rollback.as_deref()) == 0
+}
+
+/// Run condition that returns true on the last physics step of each tick
+/// (see [`TickConfig::physics_substeps`])
+pub fn is_last_substep(tick_manager: Res<TickManager>, rollback: Option<Res<Rollback>>) -> bool {
+    current_substep(&tick_manager, rollback.as_deref()) == tick_manager.config.substeps() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_tick() {
+        let mut tick_manager = TickManager::from_config(
+            TickConfig::new(Duration::from_millis(10)).with_extended(true),
+        );
+        tick_manager.set_tick_to(Tick(u16::MAX));
+        assert_eq!(tick_manager.extended_tick(), u16::MAX as u64);
+        tick_manager.increment_tick();
+        assert_eq!(tick_manager.extended_tick(), 1 << 16);
+        assert_eq!(tick_manager.tick_generation(), Some(1));
+
+        // snapping across the wrapping boundary
+        tick_manager.set_tick_to(Tick(u16::MAX - 5));
+        assert_eq!(tick_manager.extended_tick(), u16::MAX as u64 - 5);
+        tick_manager.set_tick_to(Tick(3));
+        assert_eq!(tick_manager.extended_tick(), (1 << 16) + 3);
+
+        // sync the generation from a remote tick that did not wrap yet
+        let mut remote = TickManager::from_config(TickConfig::new(Duration::from_millis(10)));
+        remote.sync_generation((2 << 16) + u16::MAX as u64 - 10);
+        assert_eq!(remote.extended_tick(), 3 << 16);
+        remote.set_tick_to(Tick(5));
+        remote.sync_generation((3 << 16) + 2);
+        assert_eq!(remote.extended_tick(), (3 << 16) + 5);
+        assert_eq!(remote.tick_generation(), None);
+    }
+
+    #[test]
+    fn test_physics_substeps() {
+        let mut tick_manager = TickManager::from_config(
+            TickConfig::new(Duration::from_millis(40)).with_physics_substeps(4),
+        );
+        assert_eq!(
+            tick_manager.config.fixed_timestep(),
+            Duration::from_millis(10)
+        );
+        for _ in 0..3 {
+            tick_manager.increment_tick();
+            assert_eq!(tick_manager.tick(), Tick(0));
+        }
+        assert_eq!(tick_manager.substep(), 3);
+        tick_manager.increment_tick();
+        assert_eq!(tick_manager.tick(), Tick(1));
+        assert_eq!(tick_manager.substep(), 0);
+
+        // after a snap, the next physics step starts the next tick
+        tick_manager.increment_tick();
+        tick_manager.set_tick_to(Tick(10));
+        tick_manager.increment_tick();
+        assert_eq!(tick_manager.tick(), Tick(11));
+        assert_eq!(tick_manager.substep(), 0);
+    }
 }
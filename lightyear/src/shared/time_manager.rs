@@ -13,7 +13,10 @@ use std::fmt::Formatter;
 use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
 
 use bevy::app::{App, RunFixedMainLoop};
-use bevy::prelude::{IntoSystemConfigs, Plugin, Res, ResMut, Resource, Time, Timer, TimerMode};
+use bevy::prelude::{
+    IntoSystemConfigs, Plugin, Real, Res, ResMut, Resource, Time, Timer, TimerMode, Virtual, World,
+};
+use bevy::reflect::Reflect;
 use bevy::time::Fixed;
 use bevy::utils::Duration;
 use bevy::utils::Instant;
@@ -23,16 +26,50 @@ use serde::{Deserialize, Serialize};
 use bitcode::{Decode, Encode};
 pub use wrapped_time::WrappedTime;
 
-use crate::prelude::Tick;
+use crate::prelude::{Tick, TickManager};
 
 // TODO: put this in networking plugin instead?
-/// Run Condition to check if the server is ready to send packets
+/// Run Condition to check if the server is ready to send packets.
+/// The server keeps sending packets while the virtual time is paused, so that the clients can still
+/// receive (and ack) the pause message and the messages sent during the pause
 pub(crate) fn is_server_ready_to_send(time_manager: Res<TimeManager>) -> bool {
     time_manager.is_server_ready_to_send()
 }
-/// Run Condition to check if the client is ready to send packets
-pub(crate) fn is_client_ready_to_send(time_manager: Res<TimeManager>) -> bool {
-    time_manager.is_client_ready_to_send()
+/// Run Condition to check if the client is ready to send packets.
+/// No packets are sent while the virtual time is paused
+pub(crate) fn is_client_ready_to_send(
+    time_manager: Res<TimeManager>,
+    virtual_time: Res<Time<Virtual>>,
+) -> bool {
+    !virtual_time.is_paused() && time_manager.is_client_ready_to_send()
+}
+
+/// Time elapsed since the last frame, used to update the network connections.
+/// The connections are kept alive (keep-alives, timeouts) even while the virtual time is paused
+pub(crate) fn network_delta(world: &World) -> Duration {
+    let virtual_time = world.resource::<Time<Virtual>>();
+    if virtual_time.is_paused() {
+        world.resource::<Time<Real>>().delta()
+    } else {
+        virtual_time.delta()
+    }
+}
+
+/// How the send intervals are measured
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum SendTiming {
+    /// The send timers advance with the frame time: packets are sent on the first frame after the
+    /// send interval elapsed.
+    ///
+    /// When the frame rate beats against the tick rate, the number of ticks contained in each packet varies.
+    #[default]
+    Frame,
+    /// The send timers advance with the simulation time (the time of the [`FixedUpdate`](bevy::prelude::FixedUpdate) steps
+    /// that ran this frame), and the overstep is carried over to the next frame.
+    ///
+    /// Packets are only sent after enough ticks have been simulated, so the packet spacing stays even
+    /// in simulation time.
+    Simulation,
 }
 
 /// Plugin that will centralize information about the various times (real, virtual, fixed)
@@ -42,15 +79,17 @@ pub(crate) struct TimePlugin {
     pub(crate) server_send_interval: Duration,
     /// Interval at which the client should send packets to the remote
     pub(crate) client_send_interval: Duration,
+    /// How the send intervals are measured
+    pub(crate) send_timing: SendTiming,
 }
 
 impl Plugin for TimePlugin {
     fn build(&self, app: &mut App) {
         // RESOURCES
-        app.insert_resource(TimeManager::new(
-            self.server_send_interval,
-            self.client_send_interval,
-        ));
+        let mut time_manager =
+            TimeManager::new(self.server_send_interval, self.client_send_interval);
+        time_manager.send_timing = self.send_timing;
+        app.insert_resource(time_manager);
         // SYSTEMS
         app.add_systems(
             RunFixedMainLoop,
@@ -59,8 +98,19 @@ impl Plugin for TimePlugin {
     }
 }
 
-fn update_overstep(mut time_manager: ResMut<TimeManager>, fixed_time: Res<Time<Fixed>>) {
-    time_manager.update_overstep(fixed_time.overstep_fraction());
+fn update_overstep(
+    mut time_manager: ResMut<TimeManager>,
+    tick_manager: Option<Res<TickManager>>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    // the fixed-update overstep is a fraction of a physics step; convert it to a fraction of the network tick
+    // by taking into account the substeps of the current tick that have already run
+    let overstep = tick_manager.map_or(fixed_time.overstep_fraction(), |tick_manager| {
+        (tick_manager.substep() as f32 + fixed_time.overstep_fraction())
+            / tick_manager.config.substeps() as f32
+    });
+    time_manager.update_overstep(overstep);
+    time_manager.update_simulation(fixed_time.elapsed());
 }
 
 #[derive(Resource)]
@@ -70,9 +120,12 @@ pub struct TimeManager {
     /// The real time
     real_time: WrappedTime,
     /// The remaining time after running the fixed-update steps, as a fraction of the tick time
+    /// (including the physics substeps of the current tick that have already run)
     overstep: f32,
     /// The time since the last frame; gets update by bevy's Time resource at the start of the frame
     delta: Duration,
+    /// The real (wall-clock) time since the last frame
+    real_delta: Duration,
     /// The relative speed set by the client.
     pub base_relative_speed: f32,
     /// Should we speedup or slowdown the simulation to sync the ticks?
@@ -86,6 +139,10 @@ pub struct TimeManager {
     client_send_timer: Option<Timer>,
     /// Instant at the start of the frame
     frame_start: Option<Instant>,
+    /// How the send timers are advanced
+    pub(crate) send_timing: SendTiming,
+    /// Elapsed time of the [`Time<Fixed>`] clock at the end of the previous frame
+    simulation_elapsed: Option<Duration>,
 }
 
 impl Default for TimeManager {
@@ -105,11 +162,14 @@ impl TimeManager {
             real_time: WrappedTime::new(0),
             overstep: 0.0,
             delta: Duration::default(),
+            real_delta: Duration::default(),
             base_relative_speed: 1.0,
             sync_relative_speed: 1.0,
             server_send_timer,
             client_send_timer,
             frame_start: None,
+            send_timing: SendTiming::default(),
+            simulation_elapsed: None,
         }
     }
 
@@ -151,6 +211,27 @@ impl TimeManager {
         self.delta = delta;
         self.wrapped_time.elapsed += delta;
         self.frame_start = Some(Instant::now());
+        if self.send_timing == SendTiming::Frame {
+            self.tick_send_timers(delta);
+        }
+    }
+
+    /// Update the send timers with the simulation time that elapsed during the fixed-update steps of this frame
+    /// (only if the send timing is [`SendTiming::Simulation`])
+    pub(crate) fn update_simulation(&mut self, simulation_elapsed: Duration) {
+        let advance = self
+            .simulation_elapsed
+            .map_or(Duration::default(), |previous| {
+                simulation_elapsed.saturating_sub(previous)
+            });
+        self.simulation_elapsed = Some(simulation_elapsed);
+        if self.send_timing == SendTiming::Simulation {
+            self.tick_send_timers(advance);
+        }
+    }
+
+    /// Advance the send timers. This is also used to keep sending packets while the virtual time is paused
+    pub(crate) fn tick_send_timers(&mut self, delta: Duration) {
         if let Some(timer) = self.server_send_timer.as_mut() {
             timer.tick(delta);
         }
@@ -173,10 +254,26 @@ impl TimeManager {
         self.overstep = overstep;
     }
 
-    fn update_real(&mut self, real_delta: Duration) {
+    /// Update the real time by applying the latest wall-clock delta
+    pub(crate) fn update_real(&mut self, real_delta: Duration) {
+        self.real_delta = real_delta;
         self.real_time.elapsed += real_delta;
     }
 
+    /// The real (wall-clock) time since the last frame
+    pub fn real_delta(&self) -> Duration {
+        self.real_delta
+    }
+
+    /// Returns the duration of the wall-clock jump if the last frame took longer than `threshold`.
+    ///
+    /// This happens when the OS suspends the process (sleep, the app is sent to the background)
+    /// or when the process is paused by a debugger. The virtual time only advances by
+    /// the `max_delta` of [`Time<Virtual>`], so the simulation is now far behind the remote.
+    pub(crate) fn clock_jump(&self, threshold: Duration) -> Option<Duration> {
+        (self.real_delta > threshold).then_some(self.real_delta)
+    }
+
     /// Current time since start, wrapped around 46 days
     pub fn current_time(&self) -> WrappedTime {
         self.wrapped_time
@@ -449,6 +546,33 @@ mod wrapped_time {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_simulation_send_timing() {
+        let mut time_manager = TimeManager::new(Duration::from_millis(40), Duration::default());
+        time_manager.send_timing = SendTiming::Simulation;
+        time_manager.update_simulation(Duration::default());
+
+        // the frame time does not advance the send timers
+        time_manager.update(Duration::from_millis(50));
+        time_manager.update_simulation(Duration::from_millis(30));
+        assert!(!time_manager.is_server_ready_to_send());
+        // a frame without any fixed-update step
+        time_manager.update(Duration::from_millis(5));
+        time_manager.update_simulation(Duration::from_millis(30));
+        assert!(!time_manager.is_server_ready_to_send());
+        time_manager.update(Duration::from_millis(15));
+        time_manager.update_simulation(Duration::from_millis(45));
+        assert!(time_manager.is_server_ready_to_send());
+        // the leftover simulation time is carried over
+        time_manager.update(Duration::from_millis(30));
+        time_manager.update_simulation(Duration::from_millis(75));
+        assert!(!time_manager.is_server_ready_to_send());
+        time_manager.update(Duration::from_millis(10));
+        time_manager.update_simulation(Duration::from_millis(80));
+        assert!(time_manager.is_server_ready_to_send());
+        assert!(time_manager.is_client_ready_to_send());
+    }
+
     #[test]
     fn test_mul() {
         let a = WrappedTime::new(u32::MAX);
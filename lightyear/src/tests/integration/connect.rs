@@ -0,0 +1,95 @@
+use crate::client::networking::NetworkingState;
+use crate::connection::netcode::{ConnectToken, USER_DATA_BYTES};
+use crate::connection::server::ServerConnections;
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::server::{ConnectEvent, TokenRefreshEvent};
+use crate::prelude::*;
+use crate::server::connection::ConnectionManager;
+use crate::tests::protocol::*;
+use crate::tests::stepper::BevyStepper;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+/// The server's ConnectEvent contains the user data of the client's connect token
+#[test]
+fn test_connect_event_user_data() {
+    let mut stepper = BevyStepper::from_configs(
+        SharedConfig {
+            tick: TickConfig::new(Duration::from_millis(10)),
+            ..default()
+        },
+        SyncConfig::default(),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::default(),
+            incoming_jitter: Duration::default(),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    );
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnections>()
+        .start()
+        .unwrap();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<NextState<NetworkingState>>()
+        .set(NetworkingState::Connecting);
+
+    stepper.record_server_events(|event: &ConnectEvent| {
+        (event.client_id(), event.user_data().copied())
+    });
+    stepper.frame_step_n(20);
+    // the test client connects with a token that has empty user data
+    let client_id = ClientId::Netcode(111);
+    assert_eq!(
+        stepper.recorded_server_events::<ConnectEvent, _>(),
+        &[(client_id, Some([0u8; USER_DATA_BYTES]))]
+    );
+    assert_eq!(
+        stepper
+            .server_resource::<ConnectionManager<MyProtocol>>()
+            .user_data(client_id)
+            .unwrap(),
+        Some(&[0u8; USER_DATA_BYTES])
+    );
+}
+
+/// A token refresh sent by the client reaches the server and replaces the user data of the connection
+#[test]
+fn test_token_refresh() {
+    let mut stepper = BevyStepper::default();
+    let client_id = ClientId::Netcode(111);
+    let user_data = [7u8; USER_DATA_BYTES];
+    let token = ConnectToken::build("127.0.0.1:0", 0, 111, stepper.private_key)
+        .expire_seconds(3600)
+        .user_data(user_data)
+        .generate()
+        .unwrap();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .refresh_token(token)
+        .unwrap();
+
+    stepper.record_server_events(|event: &TokenRefreshEvent| {
+        (event.client_id(), event.user_data().copied())
+    });
+    stepper.frame_step_n(10);
+    assert_eq!(
+        stepper.recorded_server_events::<TokenRefreshEvent, _>(),
+        &[(client_id, Some(user_data))]
+    );
+    assert_eq!(
+        stepper
+            .server_resource::<ConnectionManager<MyProtocol>>()
+            .user_data(client_id)
+            .unwrap(),
+        Some(&user_data)
+    );
+}
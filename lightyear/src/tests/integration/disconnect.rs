@@ -0,0 +1,87 @@
+use crate::_reexport::EntityActionsChannel;
+use crate::connection::server::ServerConnections;
+use crate::prelude::client::DisconnectEvent;
+use crate::prelude::*;
+use crate::server::connection::ConnectionManager;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+/// The client receives the reason sent by the server when it gets disconnected
+#[test]
+fn test_disconnect_reason() {
+    let mut stepper = BevyStepper::default();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnections>()
+        .disconnect_with_reason(ClientId::Netcode(111), DisconnectReason::VersionMismatch)
+        .unwrap();
+
+    stepper.record_client_events(|event: &DisconnectEvent| event.reason());
+    stepper.frame_step_n(10);
+    assert_eq!(
+        stepper.recorded_client_events::<DisconnectEvent, _>(),
+        &[Some(DisconnectReason::VersionMismatch)]
+    );
+}
+
+/// The farewell message is flushed before the client gets disconnected
+#[test]
+fn test_disconnect_client_with_message() {
+    let mut stepper = BevyStepper::default();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ConnectionManager<MyProtocol>>()
+        .disconnect_client_with_message::<Channel1, Message1>(
+            ClientId::Netcode(111),
+            DisconnectReason::Kicked,
+            Message1("bye".to_string()),
+        )
+        .unwrap();
+
+    stepper.record_client_events(|event: &DisconnectEvent| event.reason());
+    stepper.record_client_events(|event: &client::MessageEvent<Message1>| event.message().clone());
+    stepper.frame_step_n(10);
+    assert_eq!(
+        stepper.recorded_client_events::<client::MessageEvent<Message1>, _>(),
+        &[Message1("bye".to_string())]
+    );
+    assert_eq!(
+        stepper.recorded_client_events::<DisconnectEvent, _>(),
+        &[Some(DisconnectReason::Kicked)]
+    );
+    assert!(stepper
+        .server_resource::<ConnectionManager<MyProtocol>>()
+        .connection(ClientId::Netcode(111))
+        .is_err());
+}
+
+/// On a reliable channel, the client stays connected until the farewell message is acked
+#[test]
+fn test_disconnect_client_waits_for_farewell_ack() {
+    let mut stepper = BevyStepper::default();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ConnectionManager<MyProtocol>>()
+        .disconnect_client_with_message::<EntityActionsChannel, Message1>(
+            ClientId::Netcode(111),
+            DisconnectReason::Kicked,
+            Message1("bye".to_string()),
+        )
+        .unwrap();
+
+    // the message was sent but not acked yet
+    stepper.frame_step();
+    assert!(stepper
+        .server_resource::<ConnectionManager<MyProtocol>>()
+        .connection(ClientId::Netcode(111))
+        .is_ok());
+
+    stepper.frame_step_n(10);
+    assert!(stepper
+        .server_resource::<ConnectionManager<MyProtocol>>()
+        .connection(ClientId::Netcode(111))
+        .is_err());
+}
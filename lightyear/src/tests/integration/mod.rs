@@ -1,2 +1,4 @@
+mod connect;
+mod disconnect;
 mod multi_transport;
 mod tick_wrapping;
@@ -277,7 +277,7 @@ fn test_multi_transport() {
         incoming_jitter: Duration::from_millis(0),
         incoming_loss: 0.0,
     };
-    let mut stepper = MultiBevyStepper::new(
+    let mut stepper = MultiBevyStepper::from_configs(
         shared_config,
         SyncConfig::default(),
         PredictionConfig::default(),
@@ -34,7 +34,7 @@ fn test_sync_after_tick_wrap() {
         incoming_jitter: Duration::from_millis(0),
         incoming_loss: 0.0,
     };
-    let mut stepper = BevyStepper::new(
+    let mut stepper = BevyStepper::from_configs(
         shared_config,
         SyncConfig::default(),
         client::PredictionConfig::default(),
@@ -126,7 +126,7 @@ fn test_sync_after_tick_half_wrap() {
         incoming_jitter: Duration::from_millis(0),
         incoming_loss: 0.0,
     };
-    let mut stepper = BevyStepper::new(
+    let mut stepper = BevyStepper::from_configs(
         shared_config,
         SyncConfig::default(),
         client::PredictionConfig::default(),
@@ -1,5 +1,5 @@
 use bevy::ecs::entity::MapEntities;
-use bevy::prelude::{default, Component, Entity, EntityMapper, Reflect, Resource};
+use bevy::prelude::{default, Component, Entity, EntityMapper, Event, Reflect, Resource};
 use cfg_if::cfg_if;
 use derive_more::{Add, Mul};
 use std::ops::Mul;
@@ -10,7 +10,7 @@ use crate::_reexport::*;
 use crate::prelude::*;
 
 // Messages
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
+#[derive(Event, Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
 pub struct Message1(pub String);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
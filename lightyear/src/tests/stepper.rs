@@ -1,42 +1,15 @@
+use bevy::prelude::{default, App, Event, EventReader, Last, ResMut, Resource};
 use bevy::utils::Duration;
-use std::net::SocketAddr;
-use std::str::FromStr;
 
-use crate::client::networking::ClientConnectionParam;
-use crate::connection::client::{ClientConnection, NetClient};
-use bevy::ecs::system::SystemState;
-use bevy::prelude::{default, App, Mut, NextState, PluginGroup, Real, State, Time, World};
-use bevy::time::TimeUpdateStrategy;
-use bevy::{DefaultPlugins, MinimalPlugins};
-use tracing_subscriber::fmt::format::FmtSpan;
-
-use crate::connection::netcode::generate_key;
-use crate::connection::server::{NetServer, ServerConnection, ServerConnections};
-use crate::prelude::client::{
-    Authentication, ClientConfig, InputConfig, InterpolationConfig, NetworkingState,
-    PredictionConfig, SyncConfig,
-};
-use crate::prelude::server::{NetcodeConfig, ServerConfig};
+use crate::prelude::client::{ClientConfig, InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::server::ServerConfig;
 use crate::prelude::*;
 use crate::tests::protocol::*;
+use crate::utils::stepper::ClientServerStepper;
+pub use crate::utils::stepper::Step;
 
-/// Helpers to setup a bevy app where I can just step the world easily
-pub trait Step {
-    /// Advance both apps by one frame duration
-    fn frame_step(&mut self);
-
-    /// Advance both apps by on fixed timestep duration
-    fn tick_step(&mut self);
-}
-
-pub struct BevyStepper {
-    pub client_app: App,
-    pub server_app: App,
-    pub frame_duration: Duration,
-    /// fixed timestep duration
-    pub tick_duration: Duration,
-    pub current_time: bevy::utils::Instant,
-}
+/// Stepper that uses the test protocol
+pub type BevyStepper = ClientServerStepper<MyProtocol>;
 
 impl Default for BevyStepper {
     fn default() -> Self {
@@ -54,7 +27,7 @@ impl Default for BevyStepper {
         let sync_config = SyncConfig::default().speedup_factor(1.0);
         let prediction_config = PredictionConfig::default().disable(false);
         let interpolation_config = InterpolationConfig::default();
-        let mut stepper = Self::new(
+        let mut stepper = Self::from_configs(
             shared_config,
             sync_config,
             prediction_config,
@@ -69,7 +42,7 @@ impl Default for BevyStepper {
 
 // Do not forget to use --features mock_time when using the LinkConditioner
 impl BevyStepper {
-    pub fn new(
+    pub fn from_configs(
         shared_config: SharedConfig,
         sync_config: SyncConfig,
         prediction_config: PredictionConfig,
@@ -77,163 +50,83 @@ impl BevyStepper {
         conditioner: LinkConditionerConfig,
         frame_duration: Duration,
     ) -> Self {
-        // tracing_subscriber::FmtSubscriber::builder()
-        //     // .with_span_events(FmtSpan::ENTER)
-        //     .with_max_level(tracing::Level::INFO)
-        //     .init();
-
-        // Use local channels instead of UDP for testing
-        let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
-        // channels to receive a message from/to server
-        let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
-        let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
-        let client_io = IoConfig::from_transport(TransportConfig::LocalChannel {
-            send: to_server_send,
-            recv: from_server_recv,
-        })
-        .with_conditioner(conditioner.clone());
-
-        let server_io = IoConfig::from_transport(TransportConfig::Channels {
-            channels: vec![(addr, to_server_recv, from_server_send)],
-        })
-        .with_conditioner(conditioner.clone());
-
-        // Shared config
-        let protocol_id = 0;
-        let private_key = generate_key();
-        let client_id = 111;
-
-        // Setup server
-        let mut server_app = App::new();
-        server_app.add_plugins(MinimalPlugins.build());
-        let net_config = server::NetConfig::Netcode {
-            config: NetcodeConfig::default()
-                .with_protocol_id(protocol_id)
-                .with_key(private_key),
-            io: server_io,
-        };
-        let config = ServerConfig {
-            shared: shared_config.clone(),
-            net: vec![net_config],
-            ping: PingConfig::default(),
-            ..default()
-        };
-        let plugin_config = server::PluginConfig::new(config, protocol());
-        let plugin = server::ServerPlugin::new(plugin_config);
-        server_app.add_plugins(plugin);
-
-        // Setup client
-        let mut client_app = App::new();
-        client_app.add_plugins(MinimalPlugins.build());
-        let net_config = client::NetConfig::Netcode {
-            auth: Authentication::Manual {
-                server_addr: addr,
-                protocol_id,
-                private_key,
-                client_id,
-            },
-            config: Default::default(),
-            io: client_io,
-        };
-        let config = ClientConfig {
-            shared: shared_config.clone(),
-            net: net_config,
+        let client_config = ClientConfig {
             sync: sync_config,
             prediction: prediction_config,
             interpolation: interpolation_config,
             ..default()
         };
-        let plugin_config = client::PluginConfig::new(config, protocol());
-        let plugin = client::ClientPlugin::new(plugin_config);
-        client_app.add_plugins(plugin);
+        let mut stepper = Self::new_with_conditioner(
+            protocol(),
+            shared_config,
+            client_config,
+            ServerConfig::default(),
+            conditioner,
+        );
+        stepper.frame_duration = frame_duration;
+        stepper
+    }
+}
+
+/// Events `E` received by one of the apps, converted with the function given to
+/// [`BevyStepper::record_client_events`] or [`BevyStepper::record_server_events`]
+#[derive(Resource)]
+struct RecordedEvents<E, T> {
+    values: Vec<T>,
+    map: fn(&E) -> T,
+}
 
-        // Initialize Real time (needed only for the first TimeSystem run)
-        let now = bevy::utils::Instant::now();
-        client_app
-            .world
-            .get_resource_mut::<Time<Real>>()
-            .unwrap()
-            .update_with_instant(now);
-        server_app
-            .world
-            .get_resource_mut::<Time<Real>>()
-            .unwrap()
-            .update_with_instant(now);
+fn record_events<E: Event, T: Send + Sync + 'static>(
+    mut events: EventReader<E>,
+    mut recorded: ResMut<RecordedEvents<E, T>>,
+) {
+    let map = recorded.map;
+    recorded.values.extend(events.read().map(map));
+}
 
-        Self {
-            client_app,
-            server_app,
-            frame_duration,
-            tick_duration: shared_config.tick.tick_duration,
-            current_time: now,
+fn record_app_events<E: Event, T: Send + Sync + 'static>(app: &mut App, map: fn(&E) -> T) {
+    app.insert_resource(RecordedEvents {
+        values: Vec::<T>::new(),
+        map,
+    });
+    app.add_systems(Last, record_events::<E, T>);
+}
+
+impl BevyStepper {
+    /// Advance both apps by `frames` frames
+    pub fn frame_step_n(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.frame_step();
         }
     }
 
-    pub(crate) fn interpolation_tick(&mut self) -> Tick {
-        self.client_app.world.resource_scope(
-            |world: &mut World, manager: Mut<ClientConnectionManager>| {
-                manager
-                    .sync_manager
-                    .interpolation_tick(world.resource::<TickManager>())
-            },
-        )
+    /// Get a resource of the client app
+    pub fn client_resource<R: Resource>(&self) -> &R {
+        self.client_app.world.resource::<R>()
     }
 
-    pub(crate) fn client_tick(&self) -> Tick {
-        self.client_app.world.resource::<TickManager>().tick()
-    }
-    pub(crate) fn server_tick(&self) -> Tick {
-        self.server_app.world.resource::<TickManager>().tick()
+    /// Get a resource of the server app
+    pub fn server_resource<R: Resource>(&self) -> &R {
+        self.server_app.world.resource::<R>()
     }
-    pub(crate) fn init(&mut self) {
-        self.server_app
-            .world
-            .resource_mut::<ServerConnections>()
-            .start()
-            .expect("could not start server");
-        self.client_app
-            .world
-            .resource_mut::<NextState<NetworkingState>>()
-            .set(NetworkingState::Connecting);
 
-        // Advance the world to let the connection process complete
-        for _ in 0..100 {
-            if self
-                .client_app
-                .world
-                .resource::<ClientConnectionManager>()
-                .is_synced()
-            {
-                break;
-            }
-            self.frame_step();
-        }
+    /// Start recording the events `E` received by the client, converted with `map`
+    pub fn record_client_events<E: Event, T: Send + Sync + 'static>(&mut self, map: fn(&E) -> T) {
+        record_app_events(&mut self.client_app, map);
     }
 
-    pub(crate) fn advance_time(&mut self, duration: Duration) {
-        self.current_time += duration;
-        self.client_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        self.server_app
-            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
-        mock_instant::MockClock::advance(duration);
+    /// Start recording the events `E` received by the server, converted with `map`
+    pub fn record_server_events<E: Event, T: Send + Sync + 'static>(&mut self, map: fn(&E) -> T) {
+        record_app_events(&mut self.server_app, map);
     }
-}
 
-impl Step for BevyStepper {
-    /// Advance the world by one frame duration
-    fn frame_step(&mut self) {
-        self.advance_time(self.frame_duration);
-        self.client_app.update();
-        // sleep a bit to make sure that local io receives the packets
-        // std::thread::sleep(Duration::from_millis(1));
-        self.server_app.update();
-        // std::thread::sleep(Duration::from_millis(1));
+    /// The events `E` recorded on the client since [`BevyStepper::record_client_events`] was called
+    pub fn recorded_client_events<E: Event, T: Send + Sync + 'static>(&self) -> &[T] {
+        &self.client_resource::<RecordedEvents<E, T>>().values
     }
 
-    fn tick_step(&mut self) {
-        self.advance_time(self.tick_duration);
-        self.client_app.update();
-        self.server_app.update();
+    /// The events `E` recorded on the server since [`BevyStepper::record_server_events`] was called
+    pub fn recorded_server_events<E: Event, T: Send + Sync + 'static>(&self) -> &[T] {
+        &self.server_resource::<RecordedEvents<E, T>>().values
     }
 }
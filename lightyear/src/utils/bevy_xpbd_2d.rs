@@ -1,17 +1,44 @@
-//! Implement lightyear traits for some common bevy types
+//! Integration with the `bevy_xpbd_2d` physics engine
+//!
+//! The [`RigidBody`], [`Position`], [`Rotation`], [`LinearVelocity`] and [`AngularVelocity`] components can be
+//! added to the component protocol with the `xpbd_2d` attribute, with the `Full` sync mode (`Once` for
+//! [`RigidBody`]) so that they can be predicted and interpolated:
+//! ```rust,ignore
+//! #[component_protocol(protocol = "MyProtocol", xpbd_2d)]
+//! pub enum Components {
+//!     // your own components, without the xpbd ones
+//! }
+//! ```
+//!
+//! Add the [`PhysicsRollbackPlugin`] to run the physics simulation in a way that is compatible with rollback.
 use std::ops::{Add, Mul};
 
-use bevy::prelude::EntityMapper;
+use bevy::app::{App, FixedUpdate, Plugin, PreUpdate};
+use bevy::prelude::{
+    Commands, Component, Entity, EntityMapper, IntoSystemConfigs, Query, Time, With, Without,
+};
+use bevy::utils::Duration;
 use bevy_xpbd_2d::components::*;
+use bevy_xpbd_2d::plugins::PhysicsPlugins;
+use bevy_xpbd_2d::prelude::Physics;
 use tracing::trace;
 
+pub use bevy_xpbd_2d::components::{
+    AngularVelocity, LinearVelocity, Position, RigidBody, Rotation,
+};
+
 pub use angular_velocity::*;
 pub use linear_velocity::*;
 pub use position::*;
 pub use rotation::*;
 
 use crate::client::components::{LerpFn, SyncComponent};
-use crate::prelude::Message;
+use crate::client::prediction::plugin::{is_in_rollback, PredictionSet};
+use crate::client::prediction::rollback::{
+    exclude_from_rollback, remove_rollback_exclusion, ExcludedFromRollback,
+};
+use crate::client::prediction::Predicted;
+use crate::prelude::{Message, TickConfig};
 
 pub mod position {
     use super::*;
@@ -100,3 +127,138 @@ pub mod angular_velocity {
         }
     }
 }
+
+/// Runs the physics simulation in the `FixedUpdate` schedule and integrates it with the client rollback.
+///
+/// - the simulation advances by exactly one fixed timestep every time the `FixedMain` schedule runs,
+///   regardless of how much time has elapsed. Rollback re-runs `FixedMain` once for every step to
+///   resimulate, so the physics are resimulated with the same delta as the original steps.
+/// - the inputs are applied in `FixedPreUpdate` and the prediction history is recorded in
+///   `FixedPostUpdate`, so the physics step always sees the inputs of its tick and the history
+///   contains its result. Your own `FixedUpdate` systems that modify the physics components
+///   should run before `PhysicsSet::Prepare`.
+/// - after the predicted components are reset to the confirmed state, the rigid bodies are woken up
+///   (the sleeping state is not part of the rolled-back state), so that they are resimulated from the
+///   restored [`Position`], [`Rotation`], [`LinearVelocity`] and [`AngularVelocity`].
+/// - the rigid bodies that are [`ExcludedFromRollback`] still take part in the resimulated steps (for
+///   example as obstacles), but their physics components are restored to their pre-rollback value
+///   once the rollback is over.
+///
+/// This plugin adds the [`PhysicsPlugins`], so they should not be added separately.
+pub struct PhysicsRollbackPlugin {
+    fixed_timestep: Duration,
+}
+
+impl PhysicsRollbackPlugin {
+    /// `fixed_timestep` should be the duration of a `FixedUpdate` step, i.e. [`TickConfig::fixed_timestep`]
+    pub fn new(fixed_timestep: Duration) -> Self {
+        Self { fixed_timestep }
+    }
+
+    /// Use the fixed timestep of the `TickConfig`
+    pub fn from_tick_config(config: &TickConfig) -> Self {
+        Self::new(config.fixed_timestep())
+    }
+}
+
+impl Plugin for PhysicsRollbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PhysicsPlugins::new(FixedUpdate))
+            // step the simulation once per schedule run, regardless of how much time has elapsed
+            .insert_resource(Time::new_with(Physics::fixed_once_hz(
+                1.0 / self.fixed_timestep.as_secs_f64(),
+            )));
+        // these systems only run on the client, during a rollback
+        app.add_systems(
+            PreUpdate,
+            (
+                (
+                    wake_up_rolled_back_bodies,
+                    save_excluded_bodies.after(exclude_from_rollback),
+                )
+                    .in_set(PredictionSet::PreRollback),
+                restore_excluded_bodies
+                    .before(remove_rollback_exclusion)
+                    .in_set(PredictionSet::PostRollback),
+            )
+                .run_if(is_in_rollback),
+        );
+    }
+}
+
+/// State of a rigid body that is excluded from the rollback, saved before the rollback
+#[derive(Component, Debug, Clone)]
+struct PreRollbackPhysicsState {
+    position: Position,
+    rotation: Rotation,
+    linear_velocity: LinearVelocity,
+    angular_velocity: AngularVelocity,
+}
+
+/// Wake up the rigid bodies that are resimulated, so that the restored state is simulated
+/// even if the body was sleeping before the rollback
+fn wake_up_rolled_back_bodies(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, Option<&mut TimeSleeping>),
+        (
+            With<RigidBody>,
+            With<Predicted>,
+            Without<ExcludedFromRollback>,
+        ),
+    >,
+) {
+    for (entity, time_sleeping) in query.iter_mut() {
+        if let Some(mut time_sleeping) = time_sleeping {
+            time_sleeping.0 = 0.0;
+        }
+        commands.entity(entity).remove::<Sleeping>();
+    }
+}
+
+/// Save the physics state of the rigid bodies that are excluded from the rollback
+fn save_excluded_bodies(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Position,
+            &Rotation,
+            &LinearVelocity,
+            &AngularVelocity,
+        ),
+        (With<RigidBody>, With<ExcludedFromRollback>),
+    >,
+) {
+    for (entity, position, rotation, linear_velocity, angular_velocity) in query.iter() {
+        commands.entity(entity).insert(PreRollbackPhysicsState {
+            position: *position,
+            rotation: *rotation,
+            linear_velocity: *linear_velocity,
+            angular_velocity: *angular_velocity,
+        });
+    }
+}
+
+/// Restore the physics state of the rigid bodies that were excluded from the rollback
+fn restore_excluded_bodies(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &PreRollbackPhysicsState,
+        &mut Position,
+        &mut Rotation,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+) {
+    for (entity, state, mut position, mut rotation, mut linear_velocity, mut angular_velocity) in
+        query.iter_mut()
+    {
+        *position = state.position;
+        *rotation = state.rotation;
+        *linear_velocity = state.linear_velocity;
+        *angular_velocity = state.angular_velocity;
+        commands.entity(entity).remove::<PreRollbackPhysicsState>();
+    }
+}
@@ -12,4 +12,7 @@ pub mod bevy;
 pub mod bevy_xpbd_2d;
 
 pub(crate) mod pool;
+
+#[cfg(any(test, feature = "test_utils"))]
+pub mod stepper;
 pub mod wrapping_id;
@@ -0,0 +1,287 @@
+//! Helpers to write deterministic integration tests for a lightyear game.
+//!
+//! The [`ClientServerStepper`] runs a server app and a client app in the same process, connected via
+//! local channels, and advances them with a mocked clock: no real sleeps are needed and the state of both
+//! apps can be checked at specific ticks.
+//! ```rust,no_run,ignore
+//! let mut stepper = ClientServerStepper::new(protocol(), shared_config, client_config, server_config);
+//! stepper.init();
+//! let tick = stepper.server_tick();
+//! stepper.tick_step();
+//! assert_eq!(stepper.server_tick(), tick + 1);
+//! ```
+//! The `mock_time` feature (enabled by the `test_utils` feature) needs to be enabled so that the
+//! [`LinkConditionerConfig`] uses the mocked clock.
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use bevy::prelude::{App, MinimalPlugins, Mut, NextState, PluginGroup, Real, Time, World};
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::{Duration, Instant};
+
+use crate::client::connection::ConnectionManager as ClientConnectionManager;
+use crate::client::networking::NetworkingState;
+use crate::connection::id::ClientId;
+use crate::connection::netcode::{generate_key, Key};
+use crate::connection::server::ServerConnections;
+use crate::prelude::client::{Authentication, ClientConfig};
+use crate::prelude::server::{NetcodeConfig, ServerConfig};
+use crate::prelude::{
+    client, server, IoConfig, LinkConditionerConfig, Protocol, SharedConfig, Tick, TickManager,
+    TransportConfig,
+};
+
+/// Helpers to advance the apps of a stepper
+pub trait Step {
+    /// Advance both apps by one frame duration
+    fn frame_step(&mut self);
+
+    /// Advance both apps by one fixed timestep duration
+    fn tick_step(&mut self);
+}
+
+/// Runs a server app and a client app connected via local channels, and advances them with a mocked clock
+pub struct ClientServerStepper<P: Protocol> {
+    pub client_app: App,
+    pub server_app: App,
+    /// Duration of a frame when calling [`ClientServerStepper::frame_step`]
+    pub frame_duration: Duration,
+    /// Fixed timestep duration
+    pub tick_duration: Duration,
+    pub current_time: Instant,
+    /// Private key shared by the server and the client, to generate connect tokens
+    pub private_key: Key,
+    client_id: u64,
+    marker: std::marker::PhantomData<P>,
+}
+
+impl<P: Protocol> ClientServerStepper<P> {
+    /// Create the server and client apps.
+    ///
+    /// The `shared` and `net` fields of the provided configs are overwritten so that both apps
+    /// use the same `shared_config` and are connected with local channels.
+    pub fn new(
+        protocol: P,
+        shared_config: SharedConfig,
+        client_config: ClientConfig,
+        server_config: ServerConfig,
+    ) -> Self {
+        Self::new_with_conditioner(
+            protocol,
+            shared_config,
+            client_config,
+            server_config,
+            LinkConditionerConfig {
+                incoming_latency: Duration::default(),
+                incoming_jitter: Duration::default(),
+                incoming_loss: 0.0,
+            },
+        )
+    }
+
+    /// Create the server and client apps, with a link conditioner on both sides
+    pub fn new_with_conditioner(
+        protocol: P,
+        shared_config: SharedConfig,
+        client_config: ClientConfig,
+        server_config: ServerConfig,
+        conditioner: LinkConditionerConfig,
+    ) -> Self {
+        // use local channels instead of UDP
+        let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+        let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+        let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+        let client_io = IoConfig::from_transport(TransportConfig::LocalChannel {
+            send: to_server_send,
+            recv: from_server_recv,
+        })
+        .with_conditioner(conditioner.clone());
+        let server_io = IoConfig::from_transport(TransportConfig::Channels {
+            channels: vec![(addr, to_server_recv, from_server_send)],
+        })
+        .with_conditioner(conditioner);
+
+        let protocol_id = 0;
+        let private_key = generate_key();
+        let client_id = 111;
+
+        // server
+        let mut server_app = App::new();
+        server_app.add_plugins(MinimalPlugins.build());
+        let server_config = ServerConfig {
+            shared: shared_config.clone(),
+            net: vec![server::NetConfig::Netcode {
+                config: NetcodeConfig::default()
+                    .with_protocol_id(protocol_id)
+                    .with_key(private_key),
+                io: server_io,
+            }],
+            ..server_config
+        };
+        server_app.add_plugins(server::ServerPlugin::new(server::PluginConfig::new(
+            server_config,
+            protocol.clone(),
+        )));
+
+        // client
+        let mut client_app = App::new();
+        client_app.add_plugins(MinimalPlugins.build());
+        let client_config = ClientConfig {
+            shared: shared_config.clone(),
+            net: client::NetConfig::Netcode {
+                auth: Authentication::Manual {
+                    server_addr: addr,
+                    protocol_id,
+                    private_key,
+                    client_id,
+                },
+                config: Default::default(),
+                io: client_io,
+            },
+            ..client_config
+        };
+        client_app.add_plugins(client::ClientPlugin::new(client::PluginConfig::new(
+            client_config,
+            protocol,
+        )));
+
+        // initialize the real time (needed only for the first TimeSystem run)
+        let now = Instant::now();
+        client_app
+            .world
+            .resource_mut::<Time<Real>>()
+            .update_with_instant(now);
+        server_app
+            .world
+            .resource_mut::<Time<Real>>()
+            .update_with_instant(now);
+
+        Self {
+            client_app,
+            server_app,
+            frame_duration: shared_config.tick.tick_duration,
+            tick_duration: shared_config.tick.tick_duration,
+            current_time: now,
+            private_key,
+            client_id,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Start the server, connect the client and advance the apps until the client is synced with the server
+    pub fn init(&mut self) {
+        self.server_app
+            .world
+            .resource_mut::<ServerConnections>()
+            .start()
+            .expect("could not start server");
+        self.client_app
+            .world
+            .resource_mut::<NextState<NetworkingState>>()
+            .set(NetworkingState::Connecting);
+        for _ in 0..100 {
+            if self.is_synced() {
+                break;
+            }
+            self.frame_step();
+        }
+    }
+
+    /// Returns true if the client is connected and synced with the server
+    pub fn is_synced(&self) -> bool {
+        self.client_app
+            .world
+            .resource::<ClientConnectionManager<P>>()
+            .is_synced()
+    }
+
+    /// The client id used by the client app
+    pub fn client_id(&self) -> ClientId {
+        ClientId::Netcode(self.client_id)
+    }
+
+    pub fn client_tick(&self) -> Tick {
+        self.client_app.world.resource::<TickManager>().tick()
+    }
+
+    pub fn server_tick(&self) -> Tick {
+        self.server_app.world.resource::<TickManager>().tick()
+    }
+
+    /// The tick at which the client is currently interpolating the server entities
+    pub fn interpolation_tick(&mut self) -> Tick {
+        self.client_app.world.resource_scope(
+            |world: &mut World, manager: Mut<ClientConnectionManager<P>>| {
+                manager
+                    .sync_manager
+                    .interpolation_tick(world.resource::<TickManager>())
+            },
+        )
+    }
+
+    /// Advance the mocked clock by `duration`. The apps will see the elapsed time on their next update
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.current_time += duration;
+        self.client_app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
+        self.server_app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
+        mock_instant::MockClock::advance(duration);
+    }
+}
+
+impl<P: Protocol> Step for ClientServerStepper<P> {
+    fn frame_step(&mut self) {
+        self.advance_time(self.frame_duration);
+        self.client_app.update();
+        self.server_app.update();
+    }
+
+    fn tick_step(&mut self) {
+        self.advance_time(self.tick_duration);
+        self.client_app.update();
+        self.server_app.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::default;
+
+    use crate::prelude::client::SyncConfig;
+    use crate::prelude::TickConfig;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_tick() {
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(Duration::from_millis(10)),
+            ..default()
+        };
+        let mut stepper = ClientServerStepper::new(
+            protocol(),
+            shared_config,
+            ClientConfig {
+                // do not speed up or slow down the client, so that both apps advance by the same number of ticks
+                sync: SyncConfig::default().speedup_factor(1.0),
+                ..default()
+            },
+            ServerConfig::default(),
+        );
+        stepper.init();
+        assert!(stepper.is_synced());
+
+        let server_tick = stepper.server_tick();
+        let client_tick = stepper.client_tick();
+        stepper.tick_step();
+        assert_eq!(stepper.server_tick(), server_tick + 1);
+        assert_eq!(stepper.client_tick(), client_tick + 1);
+        for _ in 0..5 {
+            stepper.tick_step();
+        }
+        assert_eq!(stepper.server_tick(), server_tick + 6);
+        assert_eq!(stepper.client_tick(), client_tick + 6);
+    }
+}
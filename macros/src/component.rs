@@ -18,6 +18,9 @@ struct MacroAttrs {
     protocol: Ident,
     #[darling(default)]
     derive: PathList,
+    /// Add the `bevy_xpbd_2d` components to the protocol
+    #[darling(default)]
+    xpbd_2d: Flag,
 }
 
 const ATTRIBUTES: &[&str] = &["protocol"];
@@ -112,6 +115,56 @@ pub fn component_protocol_impl(
         #[protocol(map_entities)]
         ParentSync(ParentSync)
     });
+    input.variants.push(parse_quote! {
+        Authority(Authority)
+    });
+    input.variants.push(parse_quote! {
+        SyncModeOverride(SyncModeOverride<<#protocol as Protocol>::ComponentKinds>)
+    });
+    input.variants.push(parse_quote! {
+        ControlledBy(ControlledBy)
+    });
+    // the xpbd components are re-exported by the shared crate, so that the user crate doesn't need to import them
+    let xpbd = quote! { #shared_crate_name::utils::bevy_xpbd_2d };
+    let xpbd_imports = if attr.xpbd_2d.is_present() {
+        input.variants.push(parse_quote! {
+            #[protocol(sync(mode = "once"))]
+            RigidBody(#xpbd::RigidBody)
+        });
+        input.variants.push(parse_quote! {
+            #[protocol(sync(
+                mode = "full",
+                lerp = "PositionLinearInterpolation",
+                corrector = "InterpolatedCorrector"
+            ))]
+            Position(#xpbd::Position)
+        });
+        input.variants.push(parse_quote! {
+            #[protocol(sync(
+                mode = "full",
+                lerp = "RotationLinearInterpolation",
+                corrector = "InterpolatedCorrector"
+            ))]
+            Rotation(#xpbd::Rotation)
+        });
+        input.variants.push(parse_quote! {
+            #[protocol(sync(mode = "full", lerp = "LinearVelocityLinearInterpolation"))]
+            LinearVelocity(#xpbd::LinearVelocity)
+        });
+        input.variants.push(parse_quote! {
+            #[protocol(sync(mode = "full", lerp = "AngularVelocityLinearInterpolation"))]
+            AngularVelocity(#xpbd::AngularVelocity)
+        });
+        // only the interpolation functions referenced by the `lerp` attributes need to be in scope
+        quote! {
+            use #xpbd::{
+                AngularVelocityLinearInterpolation, LinearVelocityLinearInterpolation,
+                PositionLinearInterpolation, RotationLinearInterpolation,
+            };
+        }
+    } else {
+        quote! {}
+    };
     #[cfg(feature = "leafwing")]
     for i in 1..3 {
         let variant = Ident::new(&format!("ActionState{}", i), Span::call_site());
@@ -156,6 +209,7 @@ pub fn component_protocol_impl(
     let insert_method = insert_method(&input, &fields);
     let update_method = update_method(&input, &fields);
     let type_ids_method = type_ids_method(&fields, &enum_kind_name);
+    let extract_method = extract_method(&fields);
 
     // EnumKind methods
     let enum_kind = get_enum_kind(&input, &enum_kind_name);
@@ -171,12 +225,13 @@ pub fn component_protocol_impl(
             use #shared_crate_name::prelude::*;
             use #shared_crate_name::prelude::client::*;
             use bevy::ecs::entity::{EntityHashSet, MapEntities, EntityMapper};
-            use bevy::prelude::{App, Entity, IntoSystemConfigs, EntityWorldMut, World, Reflect};
+            use bevy::prelude::{App, Entity, IntoSystemConfigs, EntityRef, EntityWorldMut, World, Reflect};
             use bevy::utils::HashMap;
             use std::any::TypeId;
             use #shared_crate_name::shared::events::components::{ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent};
             #[cfg(feature = "leafwing")]
             use leafwing_input_manager::prelude::*;
+            #xpbd_imports
 
             #[derive(Serialize, Deserialize, Clone, PartialEq)]
             #extra_derives
@@ -187,6 +242,7 @@ pub fn component_protocol_impl(
                 type Protocol = #protocol;
 
                 #type_ids_method
+                #extract_method
                 #insert_method
                 #update_method
                 #add_resource_send_method
@@ -712,6 +768,28 @@ fn update_method(input: &ItemEnum, fields: &Vec<Field>) -> TokenStream {
     }
 }
 
+fn extract_method(fields: &Vec<Field>) -> TokenStream {
+    let mut body = quote! {
+        let mut res = Vec::new();
+    };
+    for field in fields {
+        let ident = &field.ident;
+        let component_type = &field.ty;
+        body = quote! {
+            #body
+            if let Some(x) = entity.get::<#component_type>() {
+                res.push(Self::#ident(x.clone()));
+            }
+        };
+    }
+    quote! {
+        fn extract(entity: &EntityRef) -> Vec<Self> {
+            #body
+            res
+        }
+    }
+}
+
 fn type_ids_method(fields: &Vec<Field>, enum_kind_name: &Ident) -> TokenStream {
     let mut body = quote! {
         let mut res = HashMap::default();
@@ -5,7 +5,7 @@ pub mod some_component {
     use serde::{Deserialize, Serialize};
     use std::ops::Mul;
 
-    use lightyear::prelude::client::LerpFn;
+    use lightyear::prelude::client::{Lerp, LerpFn};
     use lightyear::prelude::*;
     use lightyear_macros::{component_protocol, message_protocol};
 
@@ -40,6 +40,15 @@ pub mod some_component {
     #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
     pub struct Component5(pub f32);
 
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
+    pub struct Component6(pub i32);
+
+    impl Lerp for Component6 {
+        fn lerp(&self, other: &Self, t: f32) -> Self {
+            Component6(self.0 + ((other.0 - self.0) as f32 * t).round() as i32)
+        }
+    }
+
     #[component_protocol(protocol = "MyProtocol")]
     pub enum MyComponentProtocol {
         #[protocol(sync(mode = "full", lerp = "LinearInterpolator"))]
@@ -51,6 +60,8 @@ pub mod some_component {
         Component4(Component4),
         #[protocol(sync(mode = "full", lerp = "MyCustomInterpolator"))]
         Component5(Component5),
+        #[protocol(sync(mode = "full", lerp = "LerpInterpolator"))]
+        Component6(Component6),
         Resource1(ReplicateResource<Resource1>),
     }
 
@@ -110,6 +121,12 @@ mod tests {
             MyComponentProtocol::lerp(&component1, &Component1(1.0), 0.5)
         );
 
+        // interpolation using the `Lerp` implementation of the component
+        assert_eq!(
+            Component6(5),
+            MyComponentProtocol::lerp(&Component6(0), &Component6(10), 0.5)
+        );
+
         let mut mapper = RemoteEntityMap::default();
         let remote_entity = Entity::from_raw(0);
         let local_entity = Entity::from_raw(1);